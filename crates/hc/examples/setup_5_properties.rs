@@ -45,11 +45,11 @@ async fn main() -> anyhow::Result<()> {
         // Create a command runner to run admin commands.
         // This runs the conductor in the background and cleans
         // up the process when the guard is dropped.
-        let (mut cmd, _conductor_guard) =
+        let (cmd, _conductor_guard) =
             CmdRunner::from_setup_with_bin_path(&input.holochain_path, path.clone()).await?;
 
         // Generate a new agent key using the simple calls api.
-        let agent_key = hc::calls::generate_agent_pub_key(&mut cmd).await?;
+        let agent_key = hc::calls::generate_agent_pub_key(&cmd).await?;
 
         // Turn dnas into payloads.
         let dnas = dnas
@@ -87,7 +87,7 @@ async fn main() -> anyhow::Result<()> {
 
         // Activate the app using the simple calls api.
         hc::calls::activate_app(
-            &mut cmd,
+            &cmd,
             ActivateApp {
                 app_id: installed_app.installed_app_id,
             },
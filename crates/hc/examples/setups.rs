@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use holochain_hc as hc;
+use holochain_hc::Setups;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+struct Input {
+    #[structopt(short, long, default_value = "holochain")]
+    holochain_path: PathBuf,
+    /// How many conductors to run.
+    #[structopt(short, long, default_value = "2")]
+    num_conductors: usize,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // Get and parse any input.
+    let input = Input::from_args();
+
+    // Create a setup for each conductor in a temp directory.
+    let paths = (0..input.num_conductors)
+        .map(|_| hc::generate::generate(None, None, None))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // Start a conductor for each setup and connect to them.
+    // This waits until every conductor's keystore is ready.
+    let mut setups = Setups::run(&input.holochain_path, paths.clone()).await?;
+
+    // Use the connections to make admin calls.
+    for i in 0..setups.len() {
+        let admin_port = setups.handle(i).unwrap().admin_port();
+        let cmd = setups.get(i).unwrap();
+        let agent_key = hc::calls::generate_agent_pub_key(cmd).await?;
+        let cells = cmd.list_cell_ids().await?;
+        println!(
+            "Conductor {} on admin port {} generated agent {} and has {} cells",
+            i,
+            admin_port,
+            agent_key,
+            cells.len()
+        );
+    }
+
+    // Dropping the setups asks every conductor to exit and
+    // kills any that are still running after a few seconds.
+    // Use `setups.shutdown().await` instead to not block the runtime.
+    drop(setups);
+
+    // Clean up the setups.
+    for path in paths {
+        std::fs::remove_dir_all(path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! Named agents that keep the same key in every setup they are used in.
+//!
+//! Each agent has its own keystore in `$(pwd)/.hc_agents/<name>`.
+//! Setups generated with `hc gen --reuse-agent <name>` point
+//! their conductor at this keystore instead of the setup's own
+//! so they can all sign as the same agent.
+//! The keystore is outside the setup so `hc clean` doesn't remove it.
+use std::convert::TryFrom;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::ensure;
+use holochain_types::prelude::AgentPubKey;
+use structopt::StructOpt;
+
+use crate::config::read_config;
+use crate::config::write_config;
+use crate::CmdRunner;
+
+/// The directory in the `hc_dir` that holds the agents.
+pub const AGENTS_DIR: &str = ".hc_agents";
+const AGENT_KEY: &str = "agent_key";
+const KEYSTORE: &str = "keystore";
+
+#[derive(Debug, StructOpt, Clone)]
+/// Manage agents that are reused across setups.
+pub enum Agent {
+    /// List the agents in `$(pwd)/.hc_agents` and their public keys.
+    List,
+}
+
+/// An agent from the registry.
+#[derive(Debug, Clone)]
+pub struct StoredAgent {
+    /// The name passed to `--reuse-agent`.
+    pub name: String,
+    /// The agent's public key.
+    /// `None` until a setup using this agent has been generated.
+    pub agent_key: Option<AgentPubKey>,
+    /// The keystore every setup for this agent uses.
+    pub keystore: PathBuf,
+}
+
+/// Run the agent commands.
+pub fn agent(hc_dir: PathBuf, cmd: Agent) -> anyhow::Result<()> {
+    match cmd {
+        Agent::List => {
+            for agent in list(&hc_dir)? {
+                match agent.agent_key {
+                    Some(key) => data!("{}: {}", agent.name, key),
+                    None => data!("{}: no key generated yet", agent.name),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Get an agent from the registry in the `hc_dir`,
+/// creating its keystore if this is the first use.
+pub fn get_or_create(hc_dir: &Path, name: &str) -> anyhow::Result<StoredAgent> {
+    check_name(name)?;
+    let dir = hc_dir.join(AGENTS_DIR).join(name);
+    std::fs::create_dir_all(dir.join(KEYSTORE))?;
+    // The conductor could be started from any directory.
+    let dir = dir.canonicalize()?;
+    load(name, dir)
+}
+
+/// Get an agent that is already in the registry in the `hc_dir`.
+pub fn get(hc_dir: &Path, name: &str) -> anyhow::Result<StoredAgent> {
+    check_name(name)?;
+    let dir = hc_dir.join(AGENTS_DIR).join(name);
+    if !dir.is_dir() {
+        let names: Vec<_> = list(hc_dir)?.into_iter().map(|a| a.name).collect();
+        bail!(
+            "There is no agent {} in {}. The agents are: {}",
+            name,
+            hc_dir.join(AGENTS_DIR).display(),
+            names.join(", ")
+        );
+    }
+    load(name, dir.canonicalize()?)
+}
+
+/// Record the agent's public key after it is first generated.
+pub fn store_key(agent: &StoredAgent, agent_key: &AgentPubKey) -> anyhow::Result<()> {
+    let dir = agent
+        .keystore
+        .parent()
+        .ok_or_else(|| anyhow!("Agent keystore has no parent directory"))?;
+    std::fs::write(dir.join(AGENT_KEY), agent_key.to_string())?;
+    Ok(())
+}
+
+/// List the agents in the `hc_dir` sorted by name.
+pub fn list(hc_dir: &Path) -> anyhow::Result<Vec<StoredAgent>> {
+    let dir = hc_dir.join(AGENTS_DIR);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut agents = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            agents.push(load(&name, entry.path())?);
+        }
+    }
+    agents.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(agents)
+}
+
+/// Point the setup's conductor at the agent's keystore.
+pub fn use_keystore(setup_path: PathBuf, agent: &StoredAgent) -> anyhow::Result<()> {
+    let mut config = read_config(setup_path.clone())?
+        .ok_or_else(|| anyhow!("No conductor config in {}", setup_path.display()))?;
+    config.keystore_path = Some(agent.keystore.clone());
+    write_config(setup_path, &config);
+    Ok(())
+}
+
+/// Get the agent's key, generating it in the agent's keystore
+/// if this is the first setup to use the agent.
+/// The conductor must be using the agent's keystore.
+pub async fn agent_key(cmd: &CmdRunner, agent: &mut StoredAgent) -> anyhow::Result<AgentPubKey> {
+    if let Some(key) = &agent.agent_key {
+        return Ok(key.clone());
+    }
+    let key = crate::calls::generate_agent_pub_key(cmd).await?;
+    store_key(agent, &key)?;
+    msg!("Generated agent {}: {}", agent.name, key);
+    agent.agent_key = Some(key.clone());
+    Ok(key)
+}
+
+fn load(name: &str, dir: PathBuf) -> anyhow::Result<StoredAgent> {
+    let key_path = dir.join(AGENT_KEY);
+    let agent_key = if key_path.is_file() {
+        let key = std::fs::read_to_string(&key_path)?;
+        let key = AgentPubKey::try_from(key.trim())
+            .map_err(|e| anyhow!("Invalid agent key in {}: {:?}", key_path.display(), e))?;
+        Some(key)
+    } else {
+        None
+    };
+    Ok(StoredAgent {
+        name: name.to_string(),
+        agent_key,
+        keystore: dir.join(KEYSTORE),
+    })
+}
+
+fn check_name(name: &str) -> anyhow::Result<()> {
+    ensure!(
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "Agent name {:?} must only use letters, numbers, `-` and `_`",
+        name
+    );
+    Ok(())
+}
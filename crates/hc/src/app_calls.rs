@@ -0,0 +1,177 @@
+//! Helpers for making [`AppRequest`]s to the app api.
+//!
+//! An [`AppCmdRunner`] is the app interface equivalent of the
+//! [`CmdRunner`](crate::CmdRunner) and can be used to make zome calls.
+//! Signals from the app interface are kept in its [`SignalBuffer`].
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use holochain_conductor_api::AppRequest;
+use holochain_conductor_api::AppResponse;
+use holochain_conductor_api::HostFnCall;
+use holochain_conductor_api::ZomeCall;
+use holochain_types::prelude::CapSecret;
+use holochain_types::prelude::CellId;
+use holochain_types::prelude::ExternIO;
+use holochain_types::prelude::FunctionName;
+use holochain_types::prelude::InstalledApp;
+use holochain_types::prelude::ZomeName;
+use holochain_websocket::WebsocketConfig;
+
+use crate::ports::get_app_api;
+use crate::signals::SignalBuffer;
+use crate::signals::SignalBufferConfig;
+use crate::websocket_config;
+use crate::WsClient;
+use crate::DEFAULT_HOST;
+
+/// An active connection to a running conductor's app interface.
+/// This is cheaply clone-able and all clones share
+/// the same websocket connection.
+/// The connection is closed when the last clone is dropped.
+#[derive(Clone)]
+pub struct AppCmdRunner {
+    client: Arc<WsClient>,
+    signals: SignalBuffer,
+}
+
+impl AppCmdRunner {
+    /// Create a new connection for calling app interface commands.
+    pub async fn try_new(port: u16) -> std::io::Result<Self> {
+        Self::try_new_on_host(DEFAULT_HOST, port).await
+    }
+
+    /// Create a new connection to an app interface on another machine.
+    pub async fn try_new_on_host(host: &str, port: u16) -> std::io::Result<Self> {
+        Self::try_new_with_config(host, port, websocket_config()).await
+    }
+
+    /// Create a new connection with a custom websocket config,
+    /// e.g. the [`CmdRunner::websocket_config`](crate::CmdRunner::websocket_config)
+    /// of the conductor the app interface was attached to.
+    pub async fn try_new_with_config(
+        host: &str,
+        port: u16,
+        config: WebsocketConfig,
+    ) -> std::io::Result<Self> {
+        Self::try_new_with_signal_buffer(host, port, config, SignalBufferConfig::default()).await
+    }
+
+    /// Create a new connection that buffers signals with
+    /// a custom capacity and overflow policy.
+    pub async fn try_new_with_signal_buffer(
+        host: &str,
+        port: u16,
+        config: WebsocketConfig,
+        signals: SignalBufferConfig,
+    ) -> std::io::Result<Self> {
+        let (client, recv) = get_app_api(host, port, config).await?;
+        let signals = SignalBuffer::new(signals);
+        signals.spawn_feed(recv);
+        Ok(Self {
+            client: Arc::new(WsClient(client)),
+            signals,
+        })
+    }
+
+    /// The signals received on this connection.
+    /// All clones share the same buffer.
+    pub fn signals(&self) -> &SignalBuffer {
+        &self.signals
+    }
+
+    /// Make an App request to this conductor.
+    pub async fn request(&self, req: AppRequest) -> anyhow::Result<AppResponse> {
+        let response: Result<AppResponse, _> = self.client.0.clone().request(req).await;
+        Ok(response?)
+    }
+
+    /// The info of an installed app, active or not,
+    /// or `None` if there is no app with this id.
+    pub async fn app_info(
+        &self,
+        installed_app_id: impl Into<String>,
+    ) -> anyhow::Result<Option<InstalledApp>> {
+        let resp = self
+            .request(AppRequest::AppInfo {
+                installed_app_id: installed_app_id.into(),
+            })
+            .await?;
+        match resp {
+            AppResponse::AppInfo(info) => Ok(info),
+            _ => Err(anyhow!("Failed to get the app info, got: {:?}", resp)),
+        }
+    }
+
+    /// Call a zome function on a cell.
+    /// The payload is serialized and the output deserialized for you.
+    /// The agent of the cell is used as the provenance of the call.
+    /// A [`CapSecret`] is only needed if the function is not
+    /// granted unrestricted access.
+    pub async fn call_zome<I, O>(
+        &self,
+        cell_id: CellId,
+        zome_name: impl Into<ZomeName>,
+        fn_name: impl Into<FunctionName>,
+        cap: Option<CapSecret>,
+        payload: I,
+    ) -> anyhow::Result<O>
+    where
+        I: serde::Serialize + std::fmt::Debug,
+        O: serde::de::DeserializeOwned + std::fmt::Debug,
+    {
+        let call = zome_call(cell_id, zome_name, fn_name, cap, payload)?;
+        let resp = self.request(AppRequest::ZomeCall(Box::new(call))).await?;
+        match resp {
+            AppResponse::ZomeCall(output) => Ok(output.decode()?),
+            _ => Err(anyhow!("Failed to call zome, got: {:?}", resp)),
+        }
+    }
+
+    /// Call a zome function on a cell like [`call_zome`](Self::call_zome)
+    /// and also get the host functions it called, in the order they were called.
+    pub async fn call_zome_traced<I, O>(
+        &self,
+        cell_id: CellId,
+        zome_name: impl Into<ZomeName>,
+        fn_name: impl Into<FunctionName>,
+        cap: Option<CapSecret>,
+        payload: I,
+    ) -> anyhow::Result<(O, Vec<HostFnCall>)>
+    where
+        I: serde::Serialize + std::fmt::Debug,
+        O: serde::de::DeserializeOwned + std::fmt::Debug,
+    {
+        let call = zome_call(cell_id, zome_name, fn_name, cap, payload)?;
+        let resp = self
+            .request(AppRequest::ZomeCallTraced(Box::new(call)))
+            .await?;
+        match resp {
+            AppResponse::ZomeCallTraced(traced) => {
+                Ok((traced.output.decode()?, traced.host_fn_calls))
+            }
+            _ => Err(anyhow!("Failed to call zome with a trace, got: {:?}", resp)),
+        }
+    }
+}
+
+fn zome_call<I>(
+    cell_id: CellId,
+    zome_name: impl Into<ZomeName>,
+    fn_name: impl Into<FunctionName>,
+    cap: Option<CapSecret>,
+    payload: I,
+) -> anyhow::Result<ZomeCall>
+where
+    I: serde::Serialize + std::fmt::Debug,
+{
+    let provenance = cell_id.agent_pubkey().clone();
+    Ok(ZomeCall {
+        cell_id,
+        zome_name: zome_name.into(),
+        fn_name: fn_name.into(),
+        payload: ExternIO::encode(payload)?,
+        cap,
+        provenance,
+    })
+}
@@ -5,32 +5,71 @@
 //! For simple calls like [`AdminRequest::ListDnas`] this is probably easier
 //! but if you want more control use [`CmdRunner::command`].
 use std::convert::TryInto;
+use std::fmt::Write;
+use std::ops::Range;
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::Instant;
 
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::ensure;
+use anyhow::Context;
 use holochain_conductor_api::AdminInterfaceConfig;
 use holochain_conductor_api::AdminRequest;
 use holochain_conductor_api::AdminResponse;
+use holochain_conductor_api::AgentArc;
+use holochain_conductor_api::HostFnCall;
 use holochain_conductor_api::InterfaceDriver;
+use holochain_conductor_api::OpCounts;
+use holochain_conductor_api::OpStatusCounts;
+use holochain_conductor_api::ZomeFnMetering;
 use holochain_p2p::kitsune_p2p;
 use holochain_p2p::kitsune_p2p::agent_store::AgentInfoSigned;
+use holochain_types::prelude::ActivityRequest;
+use holochain_types::prelude::AgentActivity;
 use holochain_types::prelude::AgentPubKey;
+use holochain_types::prelude::AppEntryType;
 use holochain_types::prelude::CellId;
+use holochain_types::prelude::ChainQueryFilter;
+use holochain_types::prelude::ChainStatus;
+use holochain_types::prelude::DnaFile;
 use holochain_types::prelude::DnaHash;
+use holochain_types::prelude::Element;
+use holochain_types::prelude::ElementEntry;
+use holochain_types::prelude::ElementVec;
+use holochain_types::prelude::Entry;
+use holochain_types::prelude::EntryType;
+use holochain_types::prelude::EntryVisibility;
+use holochain_types::prelude::ExternIO;
+use holochain_types::prelude::HeaderHash;
+use holochain_types::prelude::HeaderType;
 use holochain_types::prelude::InstallAppDnaPayload;
 use holochain_types::prelude::InstallAppPayload;
+use holochain_types::prelude::InstalledApp;
 use holochain_types::prelude::InstalledCell;
+use holochain_types::prelude::MembraneProof;
+use holochain_types::prelude::UnsafeBytes;
+use holochain_websocket::ConnectionClosedByPeer;
+use holochain_websocket::TlsConfig;
 use portpicker::is_free;
 use std::convert::TryFrom;
 
 use crate::cmds::Existing;
 use crate::expect_match;
-use crate::ports::get_admin_ports;
-use crate::run::run_async;
+use crate::failures::FailurePolicy;
+use crate::failures::Failures;
+use crate::is_local_host;
+use crate::passphrase::Passphrase;
+use crate::ports::get_admin_address;
+use crate::ports::host_port;
+use crate::run::spawn;
+use crate::run::RunConfig;
+use crate::state_dump::SourceChainDump;
+use crate::state_dump::TimeWindow;
+use crate::AppCmdRunner;
 use crate::CmdRunner;
+use crate::ConnectOptions;
 use structopt::StructOpt;
 
 #[doc(hidden)]
@@ -41,8 +80,38 @@ pub struct Call {
     /// If this is empty existing setups will be used.
     /// Cannot be combined with existing setups.
     pub running: Vec<u16>,
+    #[structopt(long, requires = "running")]
+    /// The host of the `--running` conductors.
+    /// Can be a DNS name or an IP address. Defaults to `127.0.0.1`.
+    /// hc never starts conductors on other hosts.
+    pub host: Option<String>,
+    #[structopt(flatten)]
+    pub tls: Tls,
     #[structopt(flatten)]
     pub existing: Existing,
+    #[structopt(short = "i", long = "index", value_delimiter = ",", conflicts_with_all = &["all", "last"])]
+    /// Indices of existing setups to call.
+    /// Conductors that have been setup and are
+    /// available in `hc list`.
+    /// For example `hc call -i=0,2 list-cells`.
+    pub index: Vec<usize>,
+    #[structopt(long)]
+    /// Make the call to all the chosen conductors concurrently.
+    /// The output is grouped by setup and a failure
+    /// on one conductor doesn't stop the others
+    /// unless `hc --fail-fast` is passed.
+    pub parallel: bool,
+    #[structopt(long)]
+    /// Print how long the call took.
+    /// With `--parallel` a table of the time taken
+    /// by each conductor is printed at the end.
+    pub timing: bool,
+    #[structopt(long)]
+    /// If a conductor closes the connection while handling
+    /// a call that only reads from it, like `list-dnas`,
+    /// reconnect and make the call once more.
+    /// Calls that change the conductor are never retried.
+    pub reconnect: bool,
     #[structopt(subcommand)]
     /// The admin request you want to make.
     pub call: AdminRequestCli,
@@ -56,21 +125,47 @@ pub enum AdminRequestCli {
     AddAdminWs(AddAdminWs),
     AddAppWs(AddAppWs),
     InstallApp(InstallApp),
+    ValidateDna(ValidateDna),
     /// Calls AdminRequest::ListDnas.
     ListDnas,
     /// Calls AdminRequest::GenerateAgentPubKey.
     NewAgent,
-    /// Calls AdminRequest::ListCellIds.
+    /// Calls AdminRequest::ListCellIds and AdminRequest::ListInactiveApps.
+    ///
+    /// The cells of inactive apps are not running
+    /// so they are listed separately under their app.
     ListCells,
     /// Calls AdminRequest::ListActiveApps.
     ListActiveApps,
+    AppInfo(AppInfo),
+    #[structopt(alias = "enable-app")]
     ActivateApp(ActivateApp),
+    #[structopt(alias = "disable-app")]
     DeactivateApp(DeactivateApp),
     DumpState(DumpState),
+    #[structopt(alias = "ops")]
+    DumpOpCounts(DumpOpCounts),
+    #[structopt(alias = "metering")]
+    DumpZomeCallMetering(DumpZomeCallMetering),
+    Coverage(Coverage),
+    /// Dumps the apps, cells and dnas of the whole conductor as JSON.
+    ///
+    /// Calls AdminRequest::ListActiveApps, AdminRequest::ListCellIds
+    /// and AdminRequest::ListDnas.
+    /// If one of these fails the error is included
+    /// in the output instead of the result.
+    DumpConductorState,
     /// Calls AdminRequest::AddAgentInfo.
     /// [Unimplemented].
     AddAgents,
     ListAgents(ListAgents),
+    QuerySourceChain(QuerySourceChain),
+    GetRecord(GetRecord),
+    GetAgentActivity(GetAgentActivity),
+    Zome(CallZome),
+    Raw(Raw),
+    ExportKeystore(ExportKeystore),
+    ImportKeystore(ImportKeystore),
 }
 #[derive(Debug, StructOpt, Clone)]
 /// Calls AdminRequest::AddAdminInterfaces
@@ -88,14 +183,41 @@ pub struct AddAppWs {
     /// Optional port number.
     /// Defaults to assigned by OS.
     pub port: Option<u16>,
+    #[structopt(long)]
+    /// Check the new app interface accepts a
+    /// websocket connection before returning.
+    pub verify: bool,
+    #[structopt(long, value_delimiter = ",")]
+    /// Only accept connections from these origins and hc's own origin,
+    /// or from any origin with `*`.
+    /// Defaults to the origins the setup's admin interface accepts.
+    ///
+    /// For example `hc call add-app-ws 9000 --allowed-origins http://localhost:3000`.
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+/// Calls AdminRequest::ValidateDna
+/// and checks a dna the way installing it would
+/// without installing it or storing anything in the conductor.
+///
+/// The dna's wasm is loaded and asked for its entry defs so a dna
+/// with missing or broken wasm fails. Fails with the conductor's
+/// error if the dna isn't valid so it can be used as a CI check,
+/// for example `hc call validate-dna workdir/my-dna.dna.gz`.
+pub struct ValidateDna {
+    /// The dna file to check,
+    /// or a directory to search for a `*.dna.gz` in.
+    pub path: PathBuf,
 }
 
 #[derive(Debug, StructOpt, Clone)]
 /// Calls AdminRequest::InstallApp
 /// and installs a new app.
 ///
-/// Setting properties and membrane proofs is not
-/// yet supported.
+/// Setting properties is not yet supported.
+/// If no membrane proofs are passed and stdin is a terminal
+/// you are asked for a proof for each dna.
 /// CellNicks are set to `my-app-0`, `my-app-1` etc.
 pub struct InstallApp {
     #[structopt(short, long, default_value = "test-app")]
@@ -109,6 +231,20 @@ pub struct InstallApp {
     #[structopt(required = true, min_values = 1)]
     /// List of dnas to install.
     pub dnas: Vec<PathBuf>,
+    #[structopt(long, conflicts_with = "memproof")]
+    /// Install every dna with an empty membrane proof.
+    /// Useful in development for dnas that only
+    /// check that a proof is present.
+    pub memproof_none: bool,
+    #[structopt(long, number_of_values = 1)]
+    /// A file to read a membrane proof from.
+    /// Pass once per dna in the same order as the dnas.
+    /// An empty path (`--memproof ""`) is an empty proof.
+    pub memproof: Vec<PathBuf>,
+    #[structopt(long)]
+    /// Install the app without activating it.
+    /// Activate it later with `hc call activate-app <app-id>`.
+    pub no_enable: bool,
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -127,10 +263,29 @@ pub struct DeactivateApp {
     pub app_id: String,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+/// Calls AppRequest::AppInfo on the app interface
+/// and prints each role of the app with the id of its cell.
+///
+/// Each cell nick is a role with a single cell
+/// because this holochain has no clone cells.
+/// Fails with the installed apps if the app isn't installed.
+/// For example `hc call app-info test-app --json`.
+pub struct AppInfo {
+    /// The InstalledAppId to get the info of.
+    pub app_id: String,
+    #[structopt(long)]
+    /// Use an existing app interface on this port.
+    /// If not set a new app interface will be attached.
+    pub app_port: Option<u16>,
+    #[structopt(long)]
+    /// Print the app info as JSON.
+    pub json: bool,
+}
+
 #[derive(Debug, StructOpt, Clone)]
 /// Calls AdminRequest::DumpState
 /// and dumps the current cell's state.
-/// TODO: Add pretty print.
 /// TODO: Default to dumping all cell state.
 pub struct DumpState {
     #[structopt(parse(try_from_str = parse_dna_hash))]
@@ -139,6 +294,72 @@ pub struct DumpState {
     #[structopt(parse(try_from_str = parse_agent_key))]
     /// The agent half of the cell id to dump.
     pub agent_key: AgentPubKey,
+    #[structopt(long)]
+    /// Print the JSON the conductor responds with
+    /// instead of a line for each element.
+    pub json: bool,
+    #[structopt(flatten)]
+    pub window: TimeWindow,
+}
+#[derive(Debug, StructOpt, Clone)]
+/// Calls AdminRequest::DumpOpCounts
+/// and prints how many DHT ops the cell holds
+/// by op type and validation / integration status.
+///
+/// Ops that stay pending or awaiting dependencies
+/// are a sign that validation or integration is stuck.
+pub struct DumpOpCounts {
+    #[structopt(parse(try_from_str = parse_dna_hash))]
+    /// The dna hash half of the cell id to count ops for.
+    pub dna: DnaHash,
+    #[structopt(parse(try_from_str = parse_agent_key))]
+    /// The agent half of the cell id to count ops for.
+    pub agent_key: AgentPubKey,
+    #[structopt(long)]
+    /// Print the counts as JSON.
+    pub json: bool,
+}
+#[derive(Debug, StructOpt, Clone)]
+/// Calls AdminRequest::DumpZomeCallMetering
+/// and prints how many times each zome function
+/// has run on the cell, how long it took
+/// and how many host functions it called.
+///
+/// The conductor only records zome calls for
+/// setups generated with `hc gen --zome-call-metered`.
+pub struct DumpZomeCallMetering {
+    #[structopt(parse(try_from_str = parse_dna_hash))]
+    /// The dna hash half of the cell id to dump the metering for.
+    pub dna: DnaHash,
+    #[structopt(parse(try_from_str = parse_agent_key))]
+    /// The agent half of the cell id to dump the metering for.
+    pub agent_key: AgentPubKey,
+    #[structopt(long)]
+    /// Print the metering as JSON.
+    pub json: bool,
+}
+#[derive(Debug, StructOpt, Clone)]
+/// Calls AdminRequest::DumpArcs
+/// and shows the arc of the DHT each agent
+/// the conductor knows of holds for the cell's dna.
+///
+/// Each agent gets a bar of the whole DHT with the locations
+/// it holds as `#`, the percentage of the DHT it holds and
+/// the first and last location of its arc.
+/// Locations that no agent holds have nobody storing their data.
+pub struct Coverage {
+    #[structopt(parse(try_from_str = parse_dna_hash))]
+    /// The dna hash half of the cell id to show the coverage for.
+    pub dna: DnaHash,
+    #[structopt(parse(try_from_str = parse_agent_key))]
+    /// The agent half of the cell id to show the coverage for.
+    pub agent_key: AgentPubKey,
+    #[structopt(long, default_value = "64")]
+    /// The number of characters in each bar.
+    pub width: usize,
+    #[structopt(long)]
+    /// Print the arcs and their coverage as JSON.
+    pub json: bool,
 }
 #[derive(Debug, StructOpt, Clone)]
 /// Calls AdminRequest::RequestAgentInfo
@@ -153,32 +374,382 @@ pub struct ListAgents {
     pub dna: Option<DnaHash>,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+/// Queries a cell's source chain with a zome call over
+/// the app interface and prints the matching elements.
+///
+/// The zome function must take a `ChainQueryFilter` and
+/// return an `ElementVec` (e.g. by calling `hdk3::prelude::query`).
+/// Filters can be combined and only elements matching
+/// all of them are returned.
+pub struct QuerySourceChain {
+    #[structopt(parse(try_from_str = parse_dna_hash))]
+    /// The dna hash half of the cell id to query.
+    pub dna: DnaHash,
+    #[structopt(parse(try_from_str = parse_agent_key))]
+    /// The agent half of the cell id to query.
+    /// This agent is also used as the provenance of the zome call.
+    pub agent_key: AgentPubKey,
+    #[structopt(short, long)]
+    /// The zome that has the query function.
+    pub zome: String,
+    #[structopt(long, default_value = "query")]
+    /// The name of the query function.
+    pub function: String,
+    #[structopt(long, parse(try_from_str = parse_entry_type))]
+    /// Only match elements with this entry type.
+    /// One of `agent`, `cap-claim`, `cap-grant` or
+    /// `app:<zome-id>:<entry-def-index>[:private]`.
+    /// For example `--entry-type app:0:1`.
+    pub entry_type: Option<EntryType>,
+    #[structopt(long, parse(try_from_str = parse_header_type))]
+    /// Only match elements with this header type.
+    /// For example `--header-type create-link`.
+    pub header_type: Option<HeaderType>,
+    #[structopt(long, parse(try_from_str = parse_sequence_range))]
+    /// Only match elements in this range of source chain sequence numbers.
+    /// The start is inclusive and the end is exclusive.
+    /// For example `--sequence 3..10`.
+    pub sequence: Option<Range<u32>>,
+    // The zome function still returns all the matches
+    // and they are filtered by their header's time.
+    #[structopt(flatten)]
+    pub window: TimeWindow,
+    #[structopt(long)]
+    /// Include the entries in the elements.
+    pub include_entries: bool,
+    #[structopt(long)]
+    /// Use an existing app interface on this port.
+    /// If not set a new app interface will be attached.
+    pub app_port: Option<u16>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+/// Gets a single record by its header hash with a zome
+/// call over the app interface and prints it decoded.
+///
+/// The zome function must take a `HeaderHash` and return
+/// an `Option<Element>` (e.g. by calling `hdk3::prelude::get`).
+/// This goes to the network if the cell doesn't hold the record.
+pub struct GetRecord {
+    #[structopt(parse(try_from_str = parse_dna_hash))]
+    /// The dna hash half of the cell id to get from.
+    pub dna: DnaHash,
+    #[structopt(parse(try_from_str = parse_agent_key))]
+    /// The agent half of the cell id to get from.
+    /// This agent is also used as the provenance of the zome call.
+    pub agent_key: AgentPubKey,
+    #[structopt(parse(try_from_str = parse_header_hash))]
+    /// The hash of the record's header.
+    pub header_hash: HeaderHash,
+    #[structopt(short, long)]
+    /// The zome that has the get function.
+    pub zome: String,
+    #[structopt(long, default_value = "get")]
+    /// The name of the get function.
+    pub function: String,
+    #[structopt(long)]
+    /// Print the record as JSON.
+    pub json: bool,
+    #[structopt(long)]
+    /// Use an existing app interface on this port.
+    /// If not set a new app interface will be attached.
+    pub app_port: Option<u16>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+/// Gets an agent's chain headers as a cell sees them on the DHT
+/// with a zome call over the app interface and prints them in order.
+///
+/// The zome function must take an input with the `agent`, the `query`
+/// and the `request` and return the `AgentActivity`
+/// (e.g. by calling `hdk3::prelude::get_agent_activity`).
+/// Missing headers, rejected headers, forks and warrants are marked with `!`.
+/// It is an error if the cell doesn't know of the agent.
+pub struct GetAgentActivity {
+    #[structopt(parse(try_from_str = parse_dna_hash))]
+    /// The dna hash half of the cell id to ask.
+    pub dna: DnaHash,
+    #[structopt(parse(try_from_str = parse_agent_key))]
+    /// The agent half of the cell id to ask.
+    /// This agent is also used as the provenance of the zome call.
+    pub agent_key: AgentPubKey,
+    #[structopt(parse(try_from_str = parse_agent_key))]
+    /// The agent whose activity to get.
+    pub agent: AgentPubKey,
+    #[structopt(short, long)]
+    /// The zome that has the activity function.
+    pub zome: String,
+    #[structopt(long, default_value = "get_activity")]
+    /// The name of the activity function.
+    pub function: String,
+    #[structopt(long)]
+    /// Only get the status of the agent's chain without its headers.
+    pub status_only: bool,
+    #[structopt(long)]
+    /// Print the activity as JSON.
+    pub json: bool,
+    #[structopt(long)]
+    /// Use an existing app interface on this port.
+    /// If not set a new app interface will be attached.
+    pub app_port: Option<u16>,
+}
+
+/// The input [`get_agent_activity`] calls its zome function with.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AgentActivityInput {
+    /// The agent whose activity to get.
+    pub agent: AgentPubKey,
+    /// Which of the agent's headers to get.
+    pub query: ChainQueryFilter,
+    /// Whether to get the headers or only the status.
+    pub request: ActivityRequest,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+/// Calls a zome function over the app interface
+/// and prints the output as JSON.
+pub struct CallZome {
+    #[structopt(parse(try_from_str = parse_dna_hash))]
+    /// The dna hash half of the cell id to call.
+    pub dna: DnaHash,
+    #[structopt(parse(try_from_str = parse_agent_key))]
+    /// The agent half of the cell id to call.
+    /// This agent is also used as the provenance of the zome call.
+    pub agent_key: AgentPubKey,
+    /// The zome that has the function.
+    pub zome: String,
+    /// The name of the function to call.
+    pub function: String,
+    #[structopt(long, default_value = "null", parse(try_from_str = parse_json))]
+    /// The input to the function as JSON.
+    /// For example `--payload '{"content": "hello"}'`.
+    pub payload: serde_json::Value,
+    #[structopt(long)]
+    /// Also print the host functions the zome function called
+    /// in the order they were called and how long each took.
+    pub trace: bool,
+    #[structopt(long)]
+    /// Use an existing app interface on this port.
+    /// If not set a new app interface will be attached.
+    pub app_port: Option<u16>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+/// Sends any AdminRequest written as JSON
+/// and prints the AdminResponse as JSON.
+///
+/// This is an escape hatch for requests that don't
+/// have a subcommand yet.
+/// The request is read from stdin if it is not passed
+/// as an argument.
+/// For example `echo '{"type": "list_dnas"}' | hc call raw`.
+pub struct Raw {
+    /// The AdminRequest as JSON.
+    pub request: Option<String>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+/// Exports the agent keys of a setup to a file
+/// encrypted with a passphrase.
+///
+/// The file holds the private keys of every agent in the setup.
+/// Anyone with the file and the passphrase can sign as these agents
+/// so keep it private and delete it once it is imported.
+/// The passphrase is read from `--passphrase-file` or `HC_KEYSTORE_PASSPHRASE`
+/// and exporting without one is refused.
+/// The setup's conductor must be stopped.
+pub struct ExportKeystore {
+    #[structopt(short, long)]
+    /// The file to write the encrypted keys to.
+    /// It must not exist yet.
+    pub out: PathBuf,
+    #[structopt(long)]
+    /// A file holding the passphrase.
+    pub passphrase_file: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+/// Imports the agent keys from a file written by
+/// `export-keystore` into the chosen setups.
+///
+/// Every setup the file is imported into has the same agents.
+/// Install apps for an imported agent with `install-app --agent-key`.
+/// The setups' conductors must be stopped.
+pub struct ImportKeystore {
+    /// The file written by `export-keystore`.
+    pub path: PathBuf,
+    #[structopt(long)]
+    /// A file holding the passphrase.
+    pub passphrase_file: Option<PathBuf>,
+    #[structopt(long)]
+    /// Replace the keys a setup already has.
+    /// They are lost unless they were exported first.
+    pub force: bool,
+    #[structopt(long, requires = "force")]
+    /// Replace the keys of a shared keystore even though other setups use it.
+    /// The keys of every setup using the keystore are replaced.
+    pub replace_shared: bool,
+}
+
+/// The JSON `type` of each [`AdminRequest`] variant.
+pub const ADMIN_REQUEST_TYPES: &[&str] = &[
+    "add_admin_interfaces",
+    "register_dna",
+    "validate_dna",
+    "install_app",
+    "list_dnas",
+    "generate_agent_pub_key",
+    "list_cell_ids",
+    "list_active_apps",
+    "list_inactive_apps",
+    "activate_app",
+    "deactivate_app",
+    "attach_app_interface",
+    "dump_state",
+    "dump_op_counts",
+    "dump_zome_call_metering",
+    "dump_arcs",
+    "add_agent_info",
+    "request_agent_info",
+    "request_version",
+];
+
+#[derive(Debug, StructOpt, Clone, Default)]
+/// Connect to `--running` conductors over `wss://`,
+/// e.g. when they are behind a tls terminating proxy.
+pub struct Tls {
+    #[structopt(long, requires = "running")]
+    /// Use tls for the admin and app connections.
+    /// The certificate must be signed by a
+    /// certificate authority the system trusts.
+    pub tls: bool,
+    #[structopt(long, requires = "tls")]
+    /// A PEM file of certificate authorities
+    /// to trust instead of the system ones.
+    pub tls_ca: Option<PathBuf>,
+    #[structopt(long, requires = "tls", conflicts_with = "tls_ca")]
+    /// Accept any certificate.
+    /// Only use this for self-signed certificates in a lab.
+    pub tls_insecure: bool,
+}
+
+impl Tls {
+    /// The tls config for these flags if `--tls` is set.
+    pub fn config(&self) -> anyhow::Result<Option<TlsConfig>> {
+        if !self.tls {
+            return Ok(None);
+        }
+        let mut config = TlsConfig::default().insecure(self.tls_insecure);
+        if let Some(path) = &self.tls_ca {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Failed to read tls ca {}", path.display()))?;
+            config = config.ca_pem(pem);
+        }
+        Ok(Some(config))
+    }
+}
+
 #[doc(hidden)]
-pub async fn call(holochain_path: &Path, req: Call) -> anyhow::Result<()> {
+/// Make the call on each of the chosen conductors.
+/// If a call fails the others are still made when `on_failure` is
+/// [`FailurePolicy::KeepGoing`]. Without a policy the calls stop at the
+/// first failure unless they are made in parallel.
+/// The conductors are connected to with the `connect` options.
+pub async fn call(
+    holochain_path: &Path,
+    req: Call,
+    connect: &ConnectOptions,
+    on_failure: Option<FailurePolicy>,
+) -> anyhow::Result<()> {
     let Call {
-        existing,
+        mut existing,
         running,
-        call,
+        host,
+        tls,
+        index,
+        parallel,
+        timing,
+        reconnect,
+        mut call,
     } = req;
+    // Read stdin once so the same request can be sent to every conductor.
+    if let AdminRequestCli::Raw(Raw {
+        request: request @ None,
+    }) = &mut call
+    {
+        let mut json = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut json)
+            .context("Failed to read the raw request from stdin")?;
+        *request = Some(json);
+    }
+    // Ask for membrane proofs once so every conductor gets the same proofs.
+    if let AdminRequestCli::InstallApp(args) = &mut call {
+        prompt_membrane_proofs(args)?;
+    }
+    existing.indices.extend(index);
+    // Keystores are files in the setups so the conductors aren't used.
+    if matches!(
+        call,
+        AdminRequestCli::ExportKeystore(_) | AdminRequestCli::ImportKeystore(_)
+    ) {
+        ensure!(
+            running.is_empty(),
+            "Keystores can only be moved between setups on this machine. \
+            Choose the setups with --existing or --index instead of --running"
+        );
+        let paths = if existing.is_empty() {
+            crate::save::load(std::env::current_dir()?)?
+        } else {
+            existing.load()?
+        };
+        return keystore_call(paths, call).await;
+    }
     let cmds = if running.is_empty() {
         let paths = if existing.is_empty() {
             crate::save::load(std::env::current_dir()?)?
         } else {
             existing.load()?
         };
-        let ports = get_admin_ports(paths.clone()).await?;
-        let mut cmds = Vec::with_capacity(ports.len());
-        for (port, path) in ports.into_iter().zip(paths.into_iter()) {
-            match CmdRunner::try_new(port).await {
-                Ok(cmd) => cmds.push((cmd, None)),
+        let setups = crate::save::load(std::env::current_dir()?)?;
+        let mut cmds = Vec::with_capacity(paths.len());
+        for path in paths {
+            let admin = match get_admin_address(path.clone())? {
+                Some(admin) => admin,
+                None => continue,
+            };
+            let name = match setups.iter().position(|p| *p == path) {
+                Some(i) => format!("Setup {}", i),
+                None => format!("Setup {}", path.display()),
+            };
+            match CmdRunner::try_new_at(
+                &admin,
+                connect.websocket_config(),
+                std::time::Duration::from_secs(0),
+            )
+            .await
+            {
+                Ok(cmd) => cmds.push((name, cmd.with_setup_path(path), None)),
                 Err(e) => match e.kind() {
-                    std::io::ErrorKind::ConnectionRefused => {
-                        let (port, holochain) = run_async(holochain_path, path, None).await?;
-                        cmds.push((CmdRunner::new(port).await, Some(holochain)))
+                    // A stopped conductor can leave its socket file behind.
+                    std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::NotFound => {
+                        let holochain = spawn(RunConfig {
+                            holochain_path: holochain_path.to_path_buf(),
+                            passphrase: crate::passphrase::for_setups(&[path.clone()], None)?,
+                            connect: connect.clone(),
+                            ..RunConfig::new(path.clone())
+                        })
+                        .await?;
+                        let cmd = holochain.connect().await.with_context(|| {
+                            format!("Started the conductor for setup {}", path.display())
+                        })?;
+                        cmds.push((name, cmd, Some(holochain)))
                     }
                     _ => {
                         bail!(
-                            "Failed to connect to running conductor or start one {:?}",
+                            "Failed to connect to the running conductor for setup {} on admin {}: {}",
+                            path.display(),
+                            admin,
                             e
                         )
                     }
@@ -187,71 +758,318 @@ pub async fn call(holochain_path: &Path, req: Call) -> anyhow::Result<()> {
         }
         cmds
     } else {
+        let host = host.as_deref().unwrap_or(crate::DEFAULT_HOST);
+        let tls = tls.config()?;
         let mut cmds = Vec::with_capacity(running.len());
         for port in running {
-            cmds.push((CmdRunner::new(port).await, None));
+            let config = match &tls {
+                Some(tls) => connect.websocket_config().tls(tls.clone()),
+                None => connect.websocket_config(),
+            };
+            let cmd = match CmdRunner::try_new_on_host(host, port, config).await {
+                Ok(cmd) => cmd,
+                // The conductor is running but is the wrong version
+                // or its tls certificate was rejected.
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => bail!(e),
+                Err(e) if is_local_host(host) => {
+                    return Err(e).with_context(|| {
+                        format!(
+                            "Failed to connect to a conductor admin interface on port {}. \
+                            Is holochain running? You can start your setups with `hc run`",
+                            port
+                        )
+                    })
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!(
+                            "Failed to connect to a conductor admin interface at {}. \
+                            Is holochain running there and is the port reachable from this machine?",
+                            host_port(host, port)
+                        )
+                    })
+                }
+            };
+            let name = if is_local_host(host) {
+                format!("Conductor on port {}", port)
+            } else {
+                format!("Conductor at {}", host_port(host, port))
+            };
+            cmds.push((name, cmd, None));
         }
         cmds
     };
-    for mut cmd in cmds {
-        call_inner(&mut cmd.0, call.clone()).await?;
+    let cmds: Vec<_> = cmds
+        .into_iter()
+        .map(|(name, cmd, holochain)| (name, cmd.with_reconnect(reconnect), holochain))
+        .collect();
+    if parallel {
+        let calls = cmds.iter().map(|(name, cmd, _)| {
+            let call = call.clone();
+            async move {
+                let mut out = String::new();
+                let start = Instant::now();
+                let result = call_inner(cmd, call, &mut out).await;
+                (name, start.elapsed(), result.map(|_| out))
+            }
+        });
+        let results = match on_failure.unwrap_or(FailurePolicy::KeepGoing) {
+            // Stop waiting for the other conductors at the first failure.
+            FailurePolicy::FailFast => {
+                futures::future::try_join_all(calls.map(|call| async move {
+                    match call.await {
+                        (name, elapsed, Ok(out)) => Ok((name, elapsed, Ok(out))),
+                        (name, _, Err(e)) => Err(e.context(format!("{} failed", name))),
+                    }
+                }))
+                .await?
+            }
+            FailurePolicy::KeepGoing => futures::future::join_all(calls).await,
+        };
+        let times: Vec<_> = results
+            .iter()
+            .map(|(name, elapsed, result)| (*name, *elapsed, result.is_ok()))
+            .collect();
+        let mut failures = Failures::new(FailurePolicy::KeepGoing);
+        for (name, _, result) in results {
+            match result {
+                Ok(out) => {
+                    msg!("{}:", name);
+                    print_output(&out);
+                }
+                Err(e) => failures.fail(name, e)?,
+            }
+        }
+        if timing {
+            let mut table = String::new();
+            writeln!(table, "{:<30} {:>8} {:>12}", "conductor", "result", "time")?;
+            for (name, elapsed, ok) in times {
+                let result = if ok { "ok" } else { "failed" };
+                writeln!(
+                    table,
+                    "{:<30} {:>8} {:>12}",
+                    name,
+                    result,
+                    format!("{:?}", elapsed)
+                )?;
+            }
+            msg!("Timing:\n{}", table.trim_end());
+        }
+        failures.finish("Call", cmds.len())?;
+    } else {
+        let mut failures = Failures::new(on_failure.unwrap_or(FailurePolicy::FailFast));
+        for (name, cmd, _) in &cmds {
+            let mut out = String::new();
+            let start = Instant::now();
+            match call_inner(cmd, call.clone(), &mut out).await {
+                Ok(()) => print_output(&out),
+                Err(e) => failures.fail(name, e)?,
+            }
+            if timing {
+                msg!("{} took {:?}", name, start.elapsed());
+            }
+        }
+        failures.finish("Call", cmds.len())?;
     }
     Ok(())
 }
 
-async fn call_inner(cmd: &mut CmdRunner, call: AdminRequestCli) -> anyhow::Result<()> {
+/// Print the output of a call.
+/// This is the data of the call so it is kept apart from
+/// the summary lines and can be piped to other tools.
+fn print_output(out: &str) {
+    data!("{}", out.trim_end());
+}
+
+/// Make the call and write the output for the user into `out`.
+pub(crate) async fn call_inner(
+    cmd: &CmdRunner,
+    call: AdminRequestCli,
+    out: &mut String,
+) -> anyhow::Result<()> {
+    make_call(cmd, call, out)
+        .await
+        .map_err(explain_closed_by_peer)
+}
+
+/// Explain a call that failed because the conductor shut down
+/// before it responded. Other errors are unchanged.
+pub fn explain_closed_by_peer(e: anyhow::Error) -> anyhow::Error {
+    let closed = e
+        .chain()
+        .filter_map(|e| e.downcast_ref::<std::io::Error>()?.get_ref())
+        .find_map(|e| e.downcast_ref::<ConnectionClosedByPeer>())
+        .cloned();
+    match closed {
+        Some(ConnectionClosedByPeer {
+            during_request: Some(request),
+            ..
+        }) => e.context(format!(
+            "The conductor shut down while handling your {} request. \
+            Check its logs to see why it stopped",
+            request
+        )),
+        Some(_) => e.context(
+            "The conductor shut down while handling your request. \
+            Check its logs to see why it stopped",
+        ),
+        None => e,
+    }
+}
+
+async fn make_call(cmd: &CmdRunner, call: AdminRequestCli, out: &mut String) -> anyhow::Result<()> {
     match call {
         AdminRequestCli::AddAdminWs(args) => {
             let port = add_admin_interface(cmd, args).await?;
-            msg!("Added Admin port {}", port);
+            writeln!(out, "Added Admin port {}", port)?;
         }
         AdminRequestCli::AddAppWs(args) => {
             let port = attach_app_interface(cmd, args).await?;
-            msg!("Added App port {}", port);
+            writeln!(out, "Added App port {}", port)?;
         }
         AdminRequestCli::InstallApp(args) => {
             let app_id = args.app_id.clone();
+            let no_enable = args.no_enable;
             let cells = install_app(cmd, args).await?;
-            msg!("Installed App: {} with cells {:?}", app_id, cells);
+            writeln!(out, "Installed App: {} with cells {:?}", app_id, cells)?;
+            if no_enable {
+                writeln!(
+                    out,
+                    "The app is inactive. Activate it with `hc call activate-app {}`",
+                    app_id
+                )?;
+            }
         }
         AdminRequestCli::ListDnas => {
             let dnas = list_dnas(cmd).await?;
-            msg!("Dnas: {:?}", dnas);
+            writeln!(out, "Dnas: {:?}", dnas)?;
+        }
+        AdminRequestCli::ValidateDna(args) => {
+            let path = args.path.clone();
+            let hash = validate_dna(cmd, args).await?;
+            writeln!(out, "Dna {} is valid: {}", path.display(), hash)?;
         }
         AdminRequestCli::NewAgent => {
             let agent = generate_agent_pub_key(cmd).await?;
-            msg!("Added agent {}", agent);
+            writeln!(out, "Added agent {}", agent)?;
         }
         AdminRequestCli::ListCells => {
             let cells = list_cell_ids(cmd).await?;
-            msg!("Cell Ids: {:?}", cells);
+            writeln!(out, "Cell Ids: {:?}", cells)?;
+            // Conductors from before the request can't list inactive apps.
+            match list_inactive_apps(cmd).await {
+                Ok(apps) => {
+                    for app in apps {
+                        let cells: Vec<_> = app.cell_data.iter().map(|c| c.as_id()).collect();
+                        writeln!(
+                            out,
+                            "Inactive App: {} with cells {:?} (not running)",
+                            app.installed_app_id, cells
+                        )?;
+                    }
+                }
+                Err(e) => tracing::debug!(?e, "Failed to list inactive apps"),
+            }
         }
         AdminRequestCli::ListActiveApps => {
             let apps = list_active_apps(cmd).await?;
-            msg!("Active Apps: {:?}", apps);
+            writeln!(out, "Active Apps: {:?}", apps)?;
+        }
+        AdminRequestCli::AppInfo(args) => {
+            let json = args.json;
+            let info = app_info(cmd, args).await?;
+            if json {
+                writeln!(out, "{}", serde_json::to_string_pretty(&info)?)?;
+            } else {
+                writeln!(
+                    out,
+                    "App {} ({})",
+                    info.installed_app_id,
+                    if info.active { "active" } else { "inactive" }
+                )?;
+                for role in &info.roles {
+                    writeln!(out, "  {}: {}", role.role, role.cell.cell_id)?;
+                    writeln!(out, "    dna: {}", role.cell.dna)?;
+                    writeln!(out, "    agent: {}", role.cell.agent)?;
+                }
+            }
         }
         AdminRequestCli::ActivateApp(args) => {
             let app_id = args.app_id.clone();
             activate_app(cmd, args).await?;
-            msg!("Activated app: {:?}", app_id);
+            writeln!(out, "Activated app: {:?}", app_id)?;
         }
         AdminRequestCli::DeactivateApp(args) => {
             let app_id = args.app_id.clone();
             deactivate_app(cmd, args).await?;
-            msg!("Deactivated app: {:?}", app_id);
+            writeln!(out, "Deactivated app: {:?}", app_id)?;
         }
         AdminRequestCli::DumpState(args) => {
-            let state = dump_state(cmd, args).await?;
-            msg!("DUMP STATE \n{}", state);
+            if args.json {
+                writeln!(out, "{}", dump_state_raw(cmd, args).await?)?;
+            } else {
+                let state = dump_state(cmd, args).await?;
+                writeln!(out, "Source chain of {} elements", state.len())?;
+                for element in state.elements() {
+                    let record = RecordSummary::from(&Element::from(element.clone()));
+                    writeln!(
+                        out,
+                        "#{} {} {} at {}",
+                        record.seq, record.header_type, record.header_hash, record.timestamp
+                    )?;
+                    if !matches!(record.entry, EntrySummary::NotApplicable) {
+                        writeln!(out, "  entry: {}", serde_json::to_string(&record.entry)?)?;
+                    }
+                }
+                if state.missing() > 0 {
+                    writeln!(
+                        out,
+                        "{} headers are missing their element. The cell's database may be corrupted",
+                        state.missing()
+                    )?;
+                }
+            }
+        }
+        AdminRequestCli::DumpOpCounts(args) => {
+            let json = args.json;
+            let counts = dump_op_counts(cmd, args).await?;
+            if json {
+                writeln!(out, "{}", serde_json::to_string_pretty(&counts)?)?;
+            } else {
+                write_op_counts(out, &counts)?;
+            }
+        }
+        AdminRequestCli::DumpZomeCallMetering(args) => {
+            let json = args.json;
+            let metering = dump_zome_call_metering(cmd, args).await?;
+            if json {
+                writeln!(out, "{}", serde_json::to_string_pretty(&metering)?)?;
+            } else {
+                write_zome_call_metering(out, &metering)?;
+            }
+        }
+        AdminRequestCli::Coverage(args) => {
+            let json = args.json;
+            let width = args.width;
+            let arcs = dump_arcs(cmd, args).await?;
+            if json {
+                let report = crate::coverage::CoverageReport::new(&arcs);
+                writeln!(out, "{}", serde_json::to_string_pretty(&report)?)?;
+            } else {
+                crate::coverage::write_coverage(out, &arcs, width)?;
+            }
+        }
+        AdminRequestCli::DumpConductorState => {
+            let state = dump_conductor_state(cmd).await;
+            writeln!(out, "{}", serde_json::to_string_pretty(&state)?)?;
         }
         AdminRequestCli::AddAgents => {
             todo!("Adding agent info via cli is coming soon")
         }
         AdminRequestCli::ListAgents(args) => {
-            use std::fmt::Write;
             let agent_infos = request_agent_info(cmd, args).await?;
             for info in agent_infos {
-                let mut out = String::new();
+                let mut info_out = String::new();
                 let cell_info = list_cell_ids(cmd).await?;
                 let agents = cell_info
                     .iter()
@@ -268,8 +1086,12 @@ async fn call_inner(cmd: &mut CmdRunner, call: AdminRequestCli) -> anyhow::Resul
                 let info: kitsune_p2p::agent_store::AgentInfo = (&info).try_into().unwrap();
                 let this_agent = agents.iter().find(|a| *info.as_agent_ref() == a.1).unwrap();
                 let this_dna = dnas.iter().find(|d| *info.as_space_ref() == d.1).unwrap();
-                writeln!(out, "This Agent {:?} is {:?}", this_agent.0, this_agent.1)?;
-                writeln!(out, "This DNA {:?} is {:?}", this_dna.0, this_dna.1)?;
+                writeln!(
+                    info_out,
+                    "This Agent {:?} is {:?}",
+                    this_agent.0, this_agent.1
+                )?;
+                writeln!(info_out, "This DNA {:?} is {:?}", this_dna.0, this_dna.1)?;
 
                 use chrono::{DateTime, Duration, NaiveDateTime, Utc};
                 let duration = Duration::milliseconds(info.signed_at_ms() as i64);
@@ -279,25 +1101,248 @@ async fn call_inner(cmd: &mut CmdRunner, call: AdminRequestCli) -> anyhow::Resul
                 let exp = dt + Duration::milliseconds(info.expires_after_ms() as i64);
                 let now = Utc::now();
 
-                writeln!(out, "signed at {}", dt)?;
+                writeln!(info_out, "signed at {}", dt)?;
                 writeln!(
-                    out,
+                    info_out,
                     "expires at {} in {}mins",
                     exp,
                     (exp - now).num_minutes()
                 )?;
-                writeln!(out, "space: {:?}", info.as_space_ref())?;
-                writeln!(out, "agent: {:?}", info.as_agent_ref())?;
-                writeln!(out, "urls: {:?}", info.as_urls_ref())?;
-                msg!("{}\n", out);
+                writeln!(info_out, "space: {:?}", info.as_space_ref())?;
+                writeln!(info_out, "agent: {:?}", info.as_agent_ref())?;
+                writeln!(info_out, "urls: {:?}", info.as_urls_ref())?;
+                writeln!(out, "{}", info_out)?;
+            }
+        }
+        AdminRequestCli::QuerySourceChain(args) => {
+            let elements = query_source_chain(cmd, args).await?;
+            writeln!(out, "Found {} elements", elements.len())?;
+            for element in elements {
+                writeln!(out, "{:?}\n", element)?;
+            }
+        }
+        AdminRequestCli::GetRecord(args) => {
+            let header_hash = args.header_hash.clone();
+            let json = args.json;
+            let record = match get_record(cmd, args).await? {
+                Some(record) => RecordSummary::from(&record),
+                None => bail!("Record {} was not found", header_hash),
+            };
+            if json {
+                writeln!(out, "{}", serde_json::to_string_pretty(&record)?)?;
+            } else {
+                writeln!(out, "Record {}", record.header_hash)?;
+                writeln!(
+                    out,
+                    "header: {} #{} by {} at {}",
+                    record.header_type, record.seq, record.author, record.timestamp
+                )?;
+                if let Some(entry_hash) = &record.entry_hash {
+                    writeln!(out, "entry hash: {}", entry_hash)?;
+                }
+                writeln!(
+                    out,
+                    "entry: {}",
+                    serde_json::to_string_pretty(&record.entry)?
+                )?;
+            }
+        }
+        AdminRequestCli::GetAgentActivity(args) => {
+            let agent = args.agent.clone();
+            let json = args.json;
+            let activity = get_agent_activity(cmd, args).await?;
+            let summary = AgentActivitySummary::new(&agent, &activity)?;
+            if json {
+                writeln!(out, "{}", serde_json::to_string_pretty(&summary)?)?;
+            } else {
+                write!(out, "{}", summary)?;
+            }
+        }
+        AdminRequestCli::Zome(args) => {
+            let (output, host_fn_calls) = call_zome(cmd, args).await?;
+            writeln!(out, "Output:\n{}", serde_json::to_string_pretty(&output)?)?;
+            if let Some(host_fn_calls) = host_fn_calls {
+                writeln!(
+                    out,
+                    "{:>4} {:<36} {:>12} {:>12}",
+                    "#", "host fn", "start", "time"
+                )?;
+                for (i, host_fn_call) in host_fn_calls.iter().enumerate() {
+                    writeln!(
+                        out,
+                        "{:>4} {:<36} {:>12} {:>12}",
+                        i,
+                        host_fn_call.name,
+                        format!("{:?}", host_fn_call.start),
+                        format!("{:?}", host_fn_call.elapsed)
+                    )?;
+                }
+                let total: std::time::Duration = host_fn_calls.iter().map(|c| c.elapsed).sum();
+                writeln!(
+                    out,
+                    "{} host fn calls took {:?}",
+                    host_fn_calls.len(),
+                    total
+                )?;
             }
         }
+        AdminRequestCli::Raw(args) => {
+            let request = args.request.unwrap_or_default();
+            let resp = raw_request(cmd, &request).await?;
+            writeln!(out, "{}", serde_json::to_string_pretty(&resp)?)?;
+        }
+        AdminRequestCli::ExportKeystore(_) | AdminRequestCli::ImportKeystore(_) => {
+            bail!("Keystores are exported and imported without a conductor")
+        }
     }
     Ok(())
 }
 
+/// Export or import the keystores of these setups.
+async fn keystore_call(paths: Vec<PathBuf>, call: AdminRequestCli) -> anyhow::Result<()> {
+    match call {
+        AdminRequestCli::ExportKeystore(args) => {
+            let passphrase = crate::passphrase::read_passphrase_with_env(
+                args.passphrase_file.as_deref(),
+                crate::keystore::KEYSTORE_PASSPHRASE_ENV,
+                false,
+            )?;
+            let path = match paths.as_slice() {
+                [path] => path,
+                [] => bail!("There are no setups to export a keystore from"),
+                _ => bail!(
+                    "Choose one setup to export with --index or --existing. {} setups were chosen",
+                    paths.len()
+                ),
+            };
+            export_keystore(path, &args.out, &passphrase).await?;
+        }
+        AdminRequestCli::ImportKeystore(args) => {
+            let passphrase = crate::passphrase::read_passphrase_with_env(
+                args.passphrase_file.as_deref(),
+                crate::keystore::KEYSTORE_PASSPHRASE_ENV,
+                false,
+            )?;
+            ensure!(
+                !paths.is_empty(),
+                "There are no setups to import the keystore into"
+            );
+            for path in paths {
+                let files = import_keystore(
+                    &path,
+                    &args.path,
+                    &passphrase,
+                    args.force,
+                    args.replace_shared,
+                )
+                .await?;
+                msg!(
+                    "Imported {} keystore files into setup {}",
+                    files,
+                    path.display()
+                );
+            }
+        }
+        _ => unreachable!("Only keystore calls are made without a conductor"),
+    }
+    Ok(())
+}
+
+/// Export the keystore of the setup to the file at `out`,
+/// encrypted with the passphrase.
+/// See [`crate::keystore`] for the format.
+/// The setup's conductor must be stopped.
+pub async fn export_keystore(
+    setup_path: &Path,
+    out: &Path,
+    passphrase: &Passphrase,
+) -> anyhow::Result<()> {
+    ensure_stopped(setup_path).await?;
+    ensure!(
+        !out.exists(),
+        "{} already exists. Choose another file to export to",
+        out.display()
+    );
+    let keystore = crate::setups::keystore_path(setup_path)?;
+    let exported = crate::keystore::export_keystore(&keystore, passphrase)?;
+    crate::keystore::write_private(out, &exported)?;
+    tracing::warn!(
+        "{} holds the private keys of every agent in setup {}. \
+        Anyone with the file and the passphrase can sign as these agents. \
+        Keep it private and delete it once it is imported",
+        out.display(),
+        setup_path.display()
+    );
+    msg!(
+        "Exported the keystore of setup {} to {}",
+        setup_path.display(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// Import a keystore exported with [`export_keystore`] into the setup
+/// and return the number of keystore files written.
+/// The setup's keys are only replaced with `force`.
+/// The keys of a shared keystore other setups use are
+/// only replaced with `replace_shared` as well.
+/// The setup's conductor must be stopped.
+pub async fn import_keystore(
+    setup_path: &Path,
+    exported: &Path,
+    passphrase: &Passphrase,
+    force: bool,
+    replace_shared: bool,
+) -> anyhow::Result<usize> {
+    ensure_stopped(setup_path).await?;
+    if force && !replace_shared {
+        ensure_not_shared(setup_path)?;
+    }
+    let keystore = crate::setups::keystore_path(setup_path)?;
+    let exported = std::fs::read(exported)
+        .with_context(|| format!("Failed to read exported keystore {}", exported.display()))?;
+    crate::keystore::import_keystore(&keystore, &exported, passphrase, force)
+}
+
+/// Replacing the keys of a shared keystore replaces
+/// the keys of every other setup using it.
+fn ensure_not_shared(setup_path: &Path) -> anyhow::Result<()> {
+    let keystore = match crate::keystore::shared_keystore(setup_path)? {
+        Some(keystore) => keystore,
+        None => return Ok(()),
+    };
+    let setup_path = setup_path
+        .canonicalize()
+        .unwrap_or_else(|_| setup_path.to_path_buf());
+    let others: Vec<_> = crate::keystore::keystore_users(&keystore)?
+        .into_iter()
+        .filter(|user| *user != setup_path)
+        .map(|user| user.display().to_string())
+        .collect();
+    ensure!(
+        others.is_empty(),
+        "The keystore {} of setup {} is shared with setups {}. \
+        Replacing its keys replaces theirs too. \
+        Use --replace-shared to replace them anyway",
+        keystore.display(),
+        setup_path.display(),
+        others.join(", ")
+    );
+    Ok(())
+}
+
+/// The keystore can't be copied while the conductor is using it.
+async fn ensure_stopped(setup_path: &Path) -> anyhow::Result<()> {
+    ensure!(
+        !crate::run::is_running(setup_path.to_path_buf()).await?,
+        "The conductor for setup {} is running. Stop it before moving its keystore",
+        setup_path.display()
+    );
+    Ok(())
+}
+
 /// Calls [`AdminRequest::AddAdminInterfaces`] and adds another admin interface.
-pub async fn add_admin_interface(cmd: &mut CmdRunner, args: AddAdminWs) -> anyhow::Result<u16> {
+pub async fn add_admin_interface(cmd: &CmdRunner, args: AddAdminWs) -> anyhow::Result<u16> {
     let port = match args.port {
         Some(port) => {
             ensure!(is_free(port), "port {} is not free", port);
@@ -309,6 +1354,8 @@ pub async fn add_admin_interface(cmd: &mut CmdRunner, args: AddAdminWs) -> anyho
         .command(AdminRequest::AddAdminInterfaces(vec![
             AdminInterfaceConfig {
                 driver: InterfaceDriver::Websocket { port },
+                allowed_origins: None,
+                address: None,
             },
         ]))
         .await?;
@@ -324,94 +1371,340 @@ pub async fn add_admin_interface(cmd: &mut CmdRunner, args: AddAdminWs) -> anyho
 /// Calls [`AdminRequest::InstallApp`] and installs a new app.
 /// Creates an app per dna with the app id of `{app-id}-{dna-index}`
 /// e.g. `my-cool-app-3`.
-pub async fn install_app(
-    cmd: &mut CmdRunner,
-    args: InstallApp,
-) -> anyhow::Result<Vec<InstalledCell>> {
+pub async fn install_app(cmd: &CmdRunner, args: InstallApp) -> anyhow::Result<Vec<InstalledCell>> {
+    let proofs = membrane_proofs(&args)?;
     let InstallApp {
         app_id,
         agent_key,
         dnas,
+        no_enable,
+        ..
     } = args;
+    let dnas = dnas.into_iter().map(AppDna::Path).zip(proofs).collect();
+    if no_enable {
+        install_app_dnas_inactive(cmd, app_id, agent_key, dnas).await
+    } else {
+        install_app_dnas_with_proofs(cmd, app_id, agent_key, dnas).await
+    }
+}
+
+/// The membrane proof for each dna of an [`InstallApp`].
+/// Proofs are `None` if neither `--memproof-none`
+/// nor `--memproof` is passed.
+pub fn membrane_proofs(args: &InstallApp) -> anyhow::Result<Vec<Option<MembraneProof>>> {
+    if args.memproof_none {
+        return Ok(args
+            .dnas
+            .iter()
+            .map(|_| Some(empty_membrane_proof()))
+            .collect());
+    }
+    if args.memproof.is_empty() {
+        return Ok(args.dnas.iter().map(|_| None).collect());
+    }
+    ensure!(
+        args.memproof.len() == args.dnas.len(),
+        "Got {} membrane proofs for {} dnas. Pass one `--memproof` per dna",
+        args.memproof.len(),
+        args.dnas.len()
+    );
+    args.memproof
+        .iter()
+        .map(|path| read_membrane_proof(path).map(Some))
+        .collect()
+}
+
+/// Ask for a membrane proof file for each dna
+/// if none were passed and stdin is a terminal.
+/// The answers are set as the `--memproof` paths
+/// so the same proofs are used for every conductor.
+pub fn prompt_membrane_proofs(args: &mut InstallApp) -> anyhow::Result<()> {
+    if args.memproof_none || !args.memproof.is_empty() || !atty::is(atty::Stream::Stdin) {
+        return Ok(());
+    }
+    let stdin = std::io::stdin();
+    for dna in &args.dnas {
+        eprint!(
+            "Membrane proof file for {} (leave empty for an empty proof): ",
+            dna.display()
+        );
+        std::io::Write::flush(&mut std::io::stderr())?;
+        let mut answer = String::new();
+        stdin
+            .read_line(&mut answer)
+            .context("Failed to read the membrane proof path from stdin")?;
+        args.memproof.push(PathBuf::from(answer.trim()));
+    }
+    Ok(())
+}
+
+fn empty_membrane_proof() -> MembraneProof {
+    UnsafeBytes::from(Vec::new()).into()
+}
+
+fn read_membrane_proof(path: &Path) -> anyhow::Result<MembraneProof> {
+    if path.as_os_str().is_empty() {
+        return Ok(empty_membrane_proof());
+    }
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Failed to read membrane proof {}", path.display()))?;
+    Ok(UnsafeBytes::from(bytes).into())
+}
+
+/// Describe the membrane proofs an app was installed with
+/// so a rejection says which dna got which proof.
+fn describe_membrane_proofs(payloads: &[InstallAppDnaPayload]) -> String {
+    payloads
+        .iter()
+        .map(|payload| match &payload.membrane_proof {
+            None => format!("{}: no proof", payload.nick),
+            Some(proof) if proof.bytes().is_empty() => format!("{}: an empty proof", payload.nick),
+            Some(proof) => format!("{}: a {} byte proof", payload.nick, proof.bytes().len()),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A dna to install with [`install_app_dnas`].
+#[derive(Debug, Clone)]
+pub enum AppDna {
+    /// A `*.dna.gz` file the conductor reads from disk.
+    Path(PathBuf),
+    /// A dna in memory.
+    /// It is registered with the conductor before the app is installed.
+    File(DnaFile),
+}
+
+impl AppDna {
+    /// Decode the contents of a `*.dna.gz` file.
+    /// Fails early if the bytes are not a valid dna.
+    pub async fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self::File(crate::dna::dna_from_bytes(bytes).await?))
+    }
+}
+
+impl From<PathBuf> for AppDna {
+    fn from(path: PathBuf) -> Self {
+        Self::Path(path)
+    }
+}
+
+impl From<DnaFile> for AppDna {
+    fn from(dna: DnaFile) -> Self {
+        Self::File(dna)
+    }
+}
+
+/// Same as [`install_app`] but the dnas can be in memory.
+/// If the agent key is not set a new one is generated.
+/// CellNicks are set to `my-app-0`, `my-app-1` etc.
+pub async fn install_app_dnas(
+    cmd: &CmdRunner,
+    app_id: String,
+    agent_key: Option<AgentPubKey>,
+    dnas: Vec<AppDna>,
+) -> anyhow::Result<Vec<InstalledCell>> {
+    let dnas = dnas.into_iter().map(|dna| (dna, None)).collect();
+    install_app_dnas_with_proofs(cmd, app_id, agent_key, dnas).await
+}
+
+/// Same as [`install_app_dnas`] with a membrane proof for each dna.
+/// If the conductor rejects the app the error says
+/// which proof each dna was given.
+pub async fn install_app_dnas_with_proofs(
+    cmd: &CmdRunner,
+    app_id: String,
+    agent_key: Option<AgentPubKey>,
+    dnas: Vec<(AppDna, Option<MembraneProof>)>,
+) -> anyhow::Result<Vec<InstalledCell>> {
+    let installed_app = install_dnas(cmd, app_id, agent_key, dnas).await?;
+    cmd.activate_app(installed_app.installed_app_id).await?;
+    Ok(installed_app.cell_data)
+}
+
+/// Same as [`install_app_dnas_with_proofs`] but leaves the app inactive.
+/// The app's cells don't run until it is activated with [`activate_app`]
+/// so they are listed by [`list_inactive_apps`] instead of [`list_cell_ids`].
+pub async fn install_app_dnas_inactive(
+    cmd: &CmdRunner,
+    app_id: String,
+    agent_key: Option<AgentPubKey>,
+    dnas: Vec<(AppDna, Option<MembraneProof>)>,
+) -> anyhow::Result<Vec<InstalledCell>> {
+    let installed_app = install_dnas(cmd, app_id, agent_key, dnas).await?;
+    Ok(installed_app.cell_data)
+}
+
+async fn install_dnas(
+    cmd: &CmdRunner,
+    app_id: String,
+    agent_key: Option<AgentPubKey>,
+    dnas: Vec<(AppDna, Option<MembraneProof>)>,
+) -> anyhow::Result<InstalledApp> {
     let agent_key = match agent_key {
         Some(agent) => agent,
         None => generate_agent_pub_key(cmd).await?,
     };
 
-    for path in &dnas {
-        ensure!(path.is_file(), "Dna path {} must be a file", path.display());
+    // Turn dnas into payloads
+    let mut payloads = Vec::with_capacity(dnas.len());
+    for (i, (dna, membrane_proof)) in dnas.into_iter().enumerate() {
+        let nick = format!("{}-{}", app_id, i);
+        let mut payload = match dna {
+            AppDna::Path(path) => {
+                ensure!(path.is_file(), "Dna path {} must be a file", path.display());
+                InstallAppDnaPayload::path_only(path, nick)
+            }
+            AppDna::File(dna) => {
+                let expected = dna.dna_hash().clone();
+                let hash = cmd.register_dna_file(dna, None, None).await?;
+                ensure!(
+                    hash == expected,
+                    "Conductor registered the dna as {} but its hash is {}",
+                    hash,
+                    expected
+                );
+                InstallAppDnaPayload::hash_only(hash, nick)
+            }
+        };
+        payload.membrane_proof = membrane_proof;
+        payloads.push(payload);
     }
+    let proofs = describe_membrane_proofs(&payloads);
+    let dnas = payloads;
 
-    // Turn dnas into payloads
-    let dnas = dnas
+    let app = InstallAppPayload {
+        installed_app_id: app_id.clone(),
+        agent_key,
+        dnas,
+    };
+
+    cmd.install_app(app).await.with_context(|| {
+        format!(
+            "The conductor rejected installing app {} with {}. \
+            If a dna requires a membrane proof pass it with `--memproof <file>`",
+            app_id, proofs
+        )
+    })
+}
+
+/// Install an app that uses cells other apps already installed
+/// instead of creating new ones, like an app composed of other apps.
+/// Each cell is paired with the dna the app requires it to have.
+/// The conductor rejects the app if a cell isn't installed or has another dna.
+/// The app is installed for the agent of the first cell and left inactive.
+/// CellNicks are set to `my-app-0`, `my-app-1` etc.
+pub async fn install_app_with_existing_cells(
+    cmd: &CmdRunner,
+    app_id: String,
+    cells: Vec<(CellId, DnaHash)>,
+) -> anyhow::Result<InstalledApp> {
+    let agent_key = match cells.first() {
+        Some((cell_id, _)) => cell_id.agent_pubkey().clone(),
+        None => bail!("App {} needs at least one existing cell", app_id),
+    };
+    let dnas = cells
         .into_iter()
         .enumerate()
-        .map(|(i, path)| InstallAppDnaPayload::path_only(path, format!("{}-{}", app_id, i)))
-        .collect::<Vec<_>>();
-
+        .map(|(i, (cell_id, hash))| {
+            let mut payload = InstallAppDnaPayload::existing(cell_id, format!("{}-{}", app_id, i));
+            payload.hash = Some(hash);
+            payload
+        })
+        .collect();
     let app = InstallAppPayload {
-        installed_app_id: app_id,
+        installed_app_id: app_id.clone(),
         agent_key,
         dnas,
     };
-
-    let r = AdminRequest::InstallApp(app.into());
-    let installed_app = cmd.command(r).await?;
-    let installed_app =
-        expect_match!(installed_app => AdminResponse::AppInstalled, "Failed to install app");
-    activate_app(
-        cmd,
-        ActivateApp {
-            app_id: installed_app.installed_app_id,
-        },
-    )
-    .await?;
-    Ok(installed_app.cell_data)
+    cmd.install_app(app).await.with_context(|| {
+        format!(
+            "The conductor rejected installing app {} with existing cells. \
+            Check the cells are in `hc call list-cells`",
+            app_id
+        )
+    })
 }
 
 /// Calls [`AdminRequest::ListCellIds`].
-pub async fn list_dnas(cmd: &mut CmdRunner) -> anyhow::Result<Vec<DnaHash>> {
+pub async fn list_dnas(cmd: &CmdRunner) -> anyhow::Result<Vec<DnaHash>> {
     let resp = cmd.command(AdminRequest::ListDnas).await?;
     Ok(expect_match!(resp => AdminResponse::DnasListed, "Failed to list dnas"))
 }
 
 /// Calls [`AdminRequest::GenerateAgentPubKey`].
-pub async fn generate_agent_pub_key(cmd: &mut CmdRunner) -> anyhow::Result<AgentPubKey> {
-    let resp = cmd.command(AdminRequest::GenerateAgentPubKey).await?;
-    Ok(
-        expect_match!(resp => AdminResponse::AgentPubKeyGenerated, "Failed to generate agent pubkey"),
-    )
+pub async fn generate_agent_pub_key(cmd: &CmdRunner) -> anyhow::Result<AgentPubKey> {
+    cmd.generate_agent_pub_key().await
 }
 
 /// Calls [`AdminRequest::ListCellIds`].
-pub async fn list_cell_ids(cmd: &mut CmdRunner) -> anyhow::Result<Vec<CellId>> {
-    let resp = cmd.command(AdminRequest::ListCellIds).await?;
-    Ok(expect_match!(resp => AdminResponse::CellIdsListed, "Failed to list cell ids"))
+pub async fn list_cell_ids(cmd: &CmdRunner) -> anyhow::Result<Vec<CellId>> {
+    cmd.list_cell_ids().await
 }
 
 /// Calls [`AdminRequest::ListActiveApps`].
-pub async fn list_active_apps(cmd: &mut CmdRunner) -> anyhow::Result<Vec<String>> {
+pub async fn list_active_apps(cmd: &CmdRunner) -> anyhow::Result<Vec<String>> {
     let resp = cmd.command(AdminRequest::ListActiveApps).await?;
     Ok(expect_match!(resp => AdminResponse::ActiveAppsListed, "Failed to list active apps"))
 }
 
+/// Calls [`AdminRequest::ListInactiveApps`] and lists the apps
+/// that are installed but not active, with their cells.
+pub async fn list_inactive_apps(cmd: &CmdRunner) -> anyhow::Result<Vec<InstalledApp>> {
+    let resp = cmd.command(AdminRequest::ListInactiveApps).await?;
+    Ok(expect_match!(resp => AdminResponse::InactiveAppsListed, "Failed to list inactive apps"))
+}
+
+/// Calls [`AppRequest::AppInfo`](holochain_conductor_api::AppRequest::AppInfo)
+/// and returns the cell of each role of the app and whether it is active.
+/// Attaches a new app interface if an app port is not provided.
+/// It is an error listing the installed apps if the app isn't installed.
+pub async fn app_info(cmd: &CmdRunner, args: AppInfo) -> anyhow::Result<AppInfoSummary> {
+    let app = app_cmd_runner(cmd, args.app_port).await?;
+    let active = list_active_apps(cmd).await?;
+    let info = match app.app_info(args.app_id.clone()).await? {
+        Some(info) => info,
+        None => {
+            let mut installed = active;
+            installed.extend(
+                list_inactive_apps(cmd)
+                    .await?
+                    .into_iter()
+                    .map(|app| app.installed_app_id),
+            );
+            installed.sort();
+            bail!(
+                "There is no app {} installed. The installed apps are: {}",
+                args.app_id,
+                if installed.is_empty() {
+                    "none".to_string()
+                } else {
+                    installed.join(", ")
+                }
+            );
+        }
+    };
+    Ok(AppInfoSummary {
+        active: active.contains(&info.installed_app_id),
+        roles: info
+            .cell_data
+            .iter()
+            .map(|cell| RoleSummary {
+                role: cell.as_nick().clone(),
+                cell: CellSummary::from(cell.as_id()),
+            })
+            .collect(),
+        installed_app_id: info.installed_app_id,
+    })
+}
+
 /// Calls [`AdminRequest::ActivateApp`] and activates the installed app.
-pub async fn activate_app(cmd: &mut CmdRunner, args: ActivateApp) -> anyhow::Result<()> {
-    let resp = cmd
-        .command(AdminRequest::ActivateApp {
-            installed_app_id: args.app_id,
-        })
-        .await?;
-    ensure!(
-        matches!(resp, AdminResponse::AppActivated),
-        "Failed to activate app, got: {:?}",
-        resp
-    );
-    Ok(())
+pub async fn activate_app(cmd: &CmdRunner, args: ActivateApp) -> anyhow::Result<()> {
+    cmd.activate_app(args.app_id).await
 }
 
 /// Calls [`AdminRequest::DeactivateApp`] and deactivates the installed app.
-pub async fn deactivate_app(cmd: &mut CmdRunner, args: DeactivateApp) -> anyhow::Result<()> {
+pub async fn deactivate_app(cmd: &CmdRunner, args: DeactivateApp) -> anyhow::Result<()> {
     let resp = cmd
         .command(AdminRequest::DeactivateApp {
             installed_app_id: args.app_id,
@@ -426,37 +1719,240 @@ pub async fn deactivate_app(cmd: &mut CmdRunner, args: DeactivateApp) -> anyhow:
 }
 
 /// Calls [`AdminRequest::AttachAppInterface`] and adds another app interface.
-pub async fn attach_app_interface(cmd: &mut CmdRunner, args: AddAppWs) -> anyhow::Result<u16> {
+pub async fn attach_app_interface(cmd: &CmdRunner, args: AddAppWs) -> anyhow::Result<u16> {
     if let Some(port) = args.port {
         ensure!(is_free(port), "port {} is not free", port);
     }
+    match (args.allowed_origins.is_empty(), args.verify) {
+        (true, true) => cmd.attach_app_interface_verified(args.port).await,
+        (true, false) => cmd.attach_app_interface(args.port).await,
+        (false, verify) => {
+            let origin = cmd.origin().unwrap_or(crate::DEFAULT_ORIGIN);
+            let allowed_origins = crate::ports::allowed_origins(origin, args.allowed_origins);
+            cmd.attach_app_interface_with_origins(args.port, allowed_origins, verify)
+                .await
+        }
+    }
+}
+
+/// Calls [`AdminRequest::DumpState`] and returns the current cell's source chain
+/// with only the elements in the args' time window.
+// TODO: Default to dumping all cell state.
+pub async fn dump_state(cmd: &CmdRunner, args: DumpState) -> anyhow::Result<SourceChainDump> {
+    args.window.check()?;
+    let window = args.window.clone();
+    let mut state = cmd.dump_state(args.into()).await?;
+    state.retain_within(&window);
+    Ok(state)
+}
+
+/// Calls [`AdminRequest::DumpState`] and returns the current cell's state
+/// as the JSON the conductor responds with.
+/// If the args have a time window the JSON is of the elements within it.
+pub async fn dump_state_raw(cmd: &CmdRunner, args: DumpState) -> anyhow::Result<String> {
+    if args.window.is_unbounded() {
+        return cmd.dump_state_raw(args.into()).await;
+    }
+    Ok(serde_json::to_string(&dump_state(cmd, args).await?)?)
+}
+
+/// Calls [`AdminRequest::DumpOpCounts`] and counts the DHT ops
+/// the cell holds by op type and validation / integration status.
+pub async fn dump_op_counts(cmd: &CmdRunner, args: DumpOpCounts) -> anyhow::Result<OpCounts> {
     let resp = cmd
-        .command(AdminRequest::AttachAppInterface { port: args.port })
+        .command(AdminRequest::DumpOpCounts {
+            cell_id: Box::new(args.into()),
+        })
         .await?;
-    match resp {
-        AdminResponse::AppInterfaceAttached { port } => Ok(port),
-        _ => Err(anyhow!(
-            "Failed to attach app interface {:?}, got: {:?}",
-            args.port,
-            resp
-        )),
+    Ok(expect_match!(resp => AdminResponse::OpCountsDumped, "Failed to dump op counts"))
+}
+
+/// Print the op counts as a table with a row per op type and a total.
+fn write_op_counts(out: &mut String, counts: &OpCounts) -> anyhow::Result<()> {
+    let row = |out: &mut String, name: &str, c: &OpStatusCounts| {
+        writeln!(
+            out,
+            "{:<28} {:>8} {:>8} {:>8} {:>12} {:>10} {:>8}",
+            name,
+            c.pending,
+            c.awaiting_deps,
+            c.sys_validated,
+            c.awaiting_integration,
+            c.integrated,
+            c.rejected
+        )
+    };
+    writeln!(
+        out,
+        "{:<28} {:>8} {:>8} {:>8} {:>12} {:>10} {:>8}",
+        "op type", "pending", "deps", "sys ok", "integrating", "integrated", "rejected"
+    )?;
+    for (op_type, c) in &counts.by_type {
+        row(out, op_type, c)?;
     }
+    row(out, "total", &counts.total())?;
+    Ok(())
 }
 
-/// Calls [`AdminRequest::DumpState`] and dumps the current cell's state.
-// TODO: Add pretty print.
-// TODO: Default to dumping all cell state.
-pub async fn dump_state(cmd: &mut CmdRunner, args: DumpState) -> anyhow::Result<String> {
+/// Calls [`AdminRequest::DumpZomeCallMetering`] and gets the totals
+/// for each zome function that has run on the cell.
+pub async fn dump_zome_call_metering(
+    cmd: &CmdRunner,
+    args: DumpZomeCallMetering,
+) -> anyhow::Result<Vec<ZomeFnMetering>> {
+    let resp = cmd
+        .command(AdminRequest::DumpZomeCallMetering {
+            cell_id: Box::new(args.into()),
+        })
+        .await?;
+    Ok(
+        expect_match!(resp => AdminResponse::ZomeCallMeteringDumped, "Failed to dump zome call metering"),
+    )
+}
+
+/// Calls [`AdminRequest::ValidateDna`] with the dna file at
+/// [`ValidateDna::path`] and gets the hash it would be registered with.
+/// Nothing is installed.
+pub async fn validate_dna(cmd: &CmdRunner, args: ValidateDna) -> anyhow::Result<DnaHash> {
+    let path = crate::dna::parse_dnas(vec![args.path])?.remove(0);
+    let dna = crate::dna::read_dna(&path).await?;
+    cmd.validate_dna_file(dna)
+        .await
+        .with_context(|| format!("Dna {} is not valid", path.display()))
+}
+
+/// Calls [`AdminRequest::DumpArcs`] and gets the arc
+/// each agent known to the conductor holds of the cell's dna.
+pub async fn dump_arcs(cmd: &CmdRunner, args: Coverage) -> anyhow::Result<Vec<AgentArc>> {
     let resp = cmd
-        .command(AdminRequest::DumpState {
+        .command(AdminRequest::DumpArcs {
             cell_id: Box::new(args.into()),
         })
         .await?;
-    Ok(expect_match!(resp => AdminResponse::StateDumped, "Failed to dump state"))
+    Ok(expect_match!(resp => AdminResponse::ArcsDumped, "Failed to dump arcs"))
+}
+
+/// Print the zome call metering as a table with a row per zome function.
+fn write_zome_call_metering(out: &mut String, metering: &[ZomeFnMetering]) -> anyhow::Result<()> {
+    if metering.is_empty() {
+        writeln!(out, "No zome calls recorded")?;
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "{:<32} {:>8} {:>12} {:>12} {:>10}",
+        "zome function", "calls", "total", "mean", "host fns"
+    )?;
+    for m in metering {
+        writeln!(
+            out,
+            "{:<32} {:>8} {:>12} {:>12} {:>10}",
+            format!("{}::{}", m.zome_name, m.fn_name),
+            m.calls,
+            format!("{:.2?}", m.total_time),
+            format!("{:.2?}", m.mean_time()),
+            m.host_fn_calls
+        )?;
+    }
+    Ok(())
+}
+
+/// A snapshot of the state of a whole conductor.
+/// Each part holds either the result of the
+/// admin request or the error it failed with.
+#[derive(Debug, serde::Serialize)]
+pub struct ConductorStateDump {
+    /// The ids of the active apps.
+    pub active_apps: StateDumpPart<Vec<String>>,
+    /// All the cells in the conductor.
+    pub cells: StateDumpPart<Vec<CellSummary>>,
+    /// The hashes of all the installed dnas.
+    pub dnas: StateDumpPart<Vec<String>>,
+    /// The admin api can't list interfaces yet so this is
+    /// always an error explaining that.
+    pub interfaces: StateDumpPart<Vec<String>>,
+}
+
+/// The result of one of the requests in a [`ConductorStateDump`].
+#[allow(missing_docs)]
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateDumpPart<T> {
+    Ok(T),
+    Error(String),
+}
+
+/// A cell id in a readable form.
+#[allow(missing_docs)]
+#[derive(Debug, serde::Serialize)]
+pub struct CellSummary {
+    pub cell_id: String,
+    pub dna: String,
+    pub agent: String,
+}
+
+/// The roles of an installed app and their cells.
+#[derive(Debug, serde::Serialize)]
+pub struct AppInfoSummary {
+    /// The app's id.
+    pub installed_app_id: String,
+    /// Is the app active.
+    pub active: bool,
+    /// The cell of each role.
+    pub roles: Vec<RoleSummary>,
+}
+
+/// A role of an app and its cell.
+#[derive(Debug, serde::Serialize)]
+pub struct RoleSummary {
+    /// The cell nick of the role.
+    pub role: String,
+    /// The cell that plays the role.
+    #[serde(flatten)]
+    pub cell: CellSummary,
+}
+
+impl<T> From<anyhow::Result<T>> for StateDumpPart<T> {
+    fn from(r: anyhow::Result<T>) -> Self {
+        match r {
+            Ok(t) => StateDumpPart::Ok(t),
+            Err(e) => StateDumpPart::Error(e.to_string()),
+        }
+    }
+}
+
+impl From<&CellId> for CellSummary {
+    fn from(cell_id: &CellId) -> Self {
+        Self {
+            cell_id: cell_id.to_string(),
+            dna: cell_id.dna_hash().to_string(),
+            agent: cell_id.agent_pubkey().to_string(),
+        }
+    }
+}
+
+/// Makes all the listing requests concurrently and collects
+/// them into a [`ConductorStateDump`].
+/// A failing request does not stop the others.
+pub async fn dump_conductor_state(cmd: &CmdRunner) -> ConductorStateDump {
+    let (active_apps, cells, dnas) =
+        futures::future::join3(list_active_apps(cmd), list_cell_ids(cmd), list_dnas(cmd)).await;
+    ConductorStateDump {
+        active_apps: active_apps.into(),
+        cells: cells
+            .map(|cells| cells.iter().map(CellSummary::from).collect())
+            .into(),
+        dnas: dnas
+            .map(|dnas| dnas.iter().map(|d| d.to_string()).collect())
+            .into(),
+        interfaces: StateDumpPart::Error(
+            "Listing interfaces is not supported by the admin api".to_string(),
+        ),
+    }
 }
 
 /// Calls [`AdminRequest::AddAgentInfo`] with and adds the list of agent info.
-pub async fn add_agent_info(cmd: &mut CmdRunner, args: Vec<AgentInfoSigned>) -> anyhow::Result<()> {
+pub async fn add_agent_info(cmd: &CmdRunner, args: Vec<AgentInfoSigned>) -> anyhow::Result<()> {
     let resp = cmd
         .command(AdminRequest::AddAgentInfo { agent_infos: args })
         .await?;
@@ -470,7 +1966,7 @@ pub async fn add_agent_info(cmd: &mut CmdRunner, args: Vec<AgentInfoSigned>) ->
 
 /// Calls [`AdminRequest::RequestAgentInfo`] and pretty prints the agent info on this conductor.
 pub async fn request_agent_info(
-    cmd: &mut CmdRunner,
+    cmd: &CmdRunner,
     args: ListAgents,
 ) -> anyhow::Result<Vec<AgentInfoSigned>> {
     let resp = cmd
@@ -481,6 +1977,439 @@ pub async fn request_agent_info(
     Ok(expect_match!(resp => AdminResponse::AgentInfoRequested, "Failed to request agent info"))
 }
 
+/// Makes a zome call with a [`ChainQueryFilter`] built from the args
+/// and returns the matching elements.
+/// Attaches a new app interface if an app port is not provided.
+pub async fn query_source_chain(
+    cmd: &CmdRunner,
+    args: QuerySourceChain,
+) -> anyhow::Result<Vec<Element>> {
+    let window = args.window;
+    window.check()?;
+    let app = app_cmd_runner(cmd, args.app_port).await?;
+    let mut filter = ChainQueryFilter::new().include_entries(args.include_entries);
+    if let Some(entry_type) = args.entry_type {
+        filter = filter.entry_type(entry_type);
+    }
+    if let Some(header_type) = args.header_type {
+        filter = filter.header_type(header_type);
+    }
+    if let Some(sequence) = args.sequence {
+        filter = filter.sequence_range(sequence);
+    }
+    let elements: ElementVec = app
+        .call_zome(
+            CellId::new(args.dna, args.agent_key),
+            args.zome,
+            args.function,
+            None,
+            filter,
+        )
+        .await?;
+    let mut elements = elements.0;
+    elements.retain(|e| window.contains(e.header().timestamp()));
+    Ok(elements)
+}
+
+/// Makes a zome call to get the record with the header hash from the args.
+/// A record that the cell and the network don't have is `None`.
+/// A get that timed out waiting for the network is an error that says so.
+/// Attaches a new app interface if an app port is not provided.
+pub async fn get_record(cmd: &CmdRunner, args: GetRecord) -> anyhow::Result<Option<Element>> {
+    let app = app_cmd_runner(cmd, args.app_port).await?;
+    let header_hash = args.header_hash;
+    let result: anyhow::Result<Option<Element>> = app
+        .call_zome(
+            CellId::new(args.dna, args.agent_key),
+            args.zome,
+            args.function,
+            None,
+            header_hash.clone(),
+        )
+        .await;
+    match result {
+        Ok(record) => Ok(record),
+        Err(e) if is_timeout(&e) => Err(e.context(format!(
+            "Timed out getting record {}. The peers holding it may not be reachable",
+            header_hash
+        ))),
+        Err(e) => Err(e.context(format!("Failed to get record {}", header_hash))),
+    }
+}
+
+/// Makes a zome call to get the activity of the agent from the args.
+/// A get that timed out waiting for the network is an error that says so.
+/// Attaches a new app interface if an app port is not provided.
+pub async fn get_agent_activity(
+    cmd: &CmdRunner,
+    args: GetAgentActivity,
+) -> anyhow::Result<AgentActivity> {
+    let app = app_cmd_runner(cmd, args.app_port).await?;
+    let agent = args.agent;
+    let request = if args.status_only {
+        ActivityRequest::Status
+    } else {
+        ActivityRequest::Full
+    };
+    let input = AgentActivityInput {
+        agent: agent.clone(),
+        query: ChainQueryFilter::new(),
+        request,
+    };
+    let result: anyhow::Result<AgentActivity> = app
+        .call_zome(
+            CellId::new(args.dna, args.agent_key),
+            args.zome,
+            args.function,
+            None,
+            input,
+        )
+        .await;
+    match result {
+        Ok(activity) => Ok(activity),
+        Err(e) if is_timeout(&e) => Err(e.context(format!(
+            "Timed out getting the activity of agent {}. \
+            The peers holding it may not be reachable",
+            agent
+        ))),
+        Err(e) => Err(e.context(format!("Failed to get the activity of agent {}", agent))),
+    }
+}
+
+/// Did the request time out, either waiting for the conductor
+/// or in the conductor waiting for the network.
+fn is_timeout(e: &anyhow::Error) -> bool {
+    e.chain().any(|e| match e.downcast_ref::<std::io::Error>() {
+        Some(e) => e.kind() == std::io::ErrorKind::TimedOut,
+        // Network errors come back from the conductor as text.
+        None => {
+            let e = e.to_string().to_lowercase();
+            e.contains("timeout") || e.contains("timed out")
+        }
+    })
+}
+
+/// A record in a readable form.
+#[derive(Debug, serde::Serialize)]
+pub struct RecordSummary {
+    /// The hash of the header.
+    pub header_hash: String,
+    /// The type of the header, e.g. `Create`.
+    pub header_type: String,
+    /// The agent that authored the header.
+    pub author: String,
+    /// The position of the header in the author's source chain.
+    pub seq: u32,
+    /// When the header was authored.
+    pub timestamp: String,
+    /// The hash of the entry if the header has one.
+    pub entry_hash: Option<String>,
+    /// The entry decoded.
+    pub entry: EntrySummary,
+}
+
+/// An agent's activity in a readable form
+/// with the missing parts of the chain worked out.
+#[derive(Debug, serde::Serialize)]
+pub struct AgentActivitySummary {
+    /// The agent the activity is of.
+    pub agent: String,
+    /// The status of the agent's chain.
+    pub status: ChainStatusSummary,
+    /// The highest header sequence seen for the agent
+    /// and the headers claiming to be at it.
+    pub highest_observed: Option<(u32, Vec<String>)>,
+    /// Valid headers in chain order.
+    pub valid: Vec<(u32, String)>,
+    /// Rejected headers in chain order.
+    pub rejected: Vec<(u32, String)>,
+    /// Ranges of sequence numbers that have no valid or rejected header,
+    /// up to the highest one seen. Empty if only the status was requested.
+    pub gaps: Vec<Range<u32>>,
+    /// How many warrants there are against the agent.
+    pub warrants: usize,
+}
+
+/// The status of an [`AgentActivitySummary`].
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum ChainStatusSummary {
+    /// The cell has no information on the chain.
+    Empty,
+    /// The chain is valid up to this header.
+    Valid {
+        /// The sequence number of the head.
+        seq: u32,
+        /// The hash of the head.
+        hash: String,
+    },
+    /// The chain has two headers at this sequence number.
+    Forked {
+        /// Where the chain forked.
+        seq: u32,
+        /// The two headers at this sequence number.
+        hashes: (String, String),
+    },
+    /// The chain is invalid because of this header.
+    Invalid {
+        /// The sequence number of the invalid header.
+        seq: u32,
+        /// The hash of the invalid header.
+        hash: String,
+    },
+}
+
+impl AgentActivitySummary {
+    /// Summarize the activity of the agent.
+    /// It is an error if the activity is empty
+    /// because then the cell doesn't know of the agent.
+    pub fn new(agent: &AgentPubKey, activity: &AgentActivity) -> anyhow::Result<Self> {
+        ensure!(
+            activity.status != ChainStatus::Empty
+                || !activity.valid_activity.is_empty()
+                || !activity.rejected_activity.is_empty()
+                || activity.highest_observed.is_some(),
+            "Agent {} is unknown to this cell: it has no activity for them. \
+            The agent may not have published their chain yet \
+            or the peers holding it may not be reachable",
+            agent
+        );
+        let status = match &activity.status {
+            ChainStatus::Empty => ChainStatusSummary::Empty,
+            ChainStatus::Valid(head) => ChainStatusSummary::Valid {
+                seq: head.header_seq,
+                hash: head.hash.to_string(),
+            },
+            ChainStatus::Forked(fork) => ChainStatusSummary::Forked {
+                seq: fork.fork_seq,
+                hashes: (
+                    fork.first_header.to_string(),
+                    fork.second_header.to_string(),
+                ),
+            },
+            ChainStatus::Invalid(head) => ChainStatusSummary::Invalid {
+                seq: head.header_seq,
+                hash: head.hash.to_string(),
+            },
+        };
+        let headers = |activity: &[(u32, HeaderHash)]| {
+            let mut headers: Vec<_> = activity
+                .iter()
+                .map(|(seq, hash)| (*seq, hash.to_string()))
+                .collect();
+            headers.sort();
+            headers
+        };
+        let valid = headers(&activity.valid_activity);
+        let rejected = headers(&activity.rejected_activity);
+        let highest_observed = activity
+            .highest_observed
+            .as_ref()
+            .map(|h| (h.header_seq, h.hash.iter().map(|h| h.to_string()).collect()));
+        let mut gaps = Vec::new();
+        if !valid.is_empty() || !rejected.is_empty() {
+            let seen: std::collections::BTreeSet<u32> =
+                valid.iter().chain(&rejected).map(|(seq, _)| *seq).collect();
+            let highest = seen
+                .iter()
+                .copied()
+                .chain(highest_observed.as_ref().map(|(seq, _)| *seq))
+                .max()
+                .unwrap_or(0);
+            let mut next = 0;
+            for seq in seen.into_iter().chain(std::iter::once(highest + 1)) {
+                if seq > next {
+                    gaps.push(next..seq);
+                }
+                next = next.max(seq + 1);
+            }
+        }
+        Ok(Self {
+            agent: agent.to_string(),
+            status,
+            highest_observed,
+            valid,
+            rejected,
+            gaps,
+            warrants: activity.warrants.len(),
+        })
+    }
+}
+
+impl std::fmt::Display for AgentActivitySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Activity of agent {}", self.agent)?;
+        match &self.status {
+            ChainStatusSummary::Empty => writeln!(f, "status: empty")?,
+            ChainStatusSummary::Valid { seq, hash } => {
+                writeln!(f, "status: valid up to #{} {}", seq, hash)?
+            }
+            ChainStatusSummary::Forked { seq, hashes } => writeln!(
+                f,
+                "! status: forked at #{} between {} and {}",
+                seq, hashes.0, hashes.1
+            )?,
+            ChainStatusSummary::Invalid { seq, hash } => {
+                writeln!(f, "! status: invalid at #{} {}", seq, hash)?
+            }
+        }
+        if let Some((seq, hashes)) = &self.highest_observed {
+            writeln!(f, "highest observed: #{} {}", seq, hashes.join(", "))?;
+        }
+        if self.warrants > 0 {
+            writeln!(f, "! {} warrants", self.warrants)?;
+        }
+        let mut lines: Vec<(u32, String)> = self
+            .valid
+            .iter()
+            .map(|(seq, hash)| (*seq, format!("#{} {}", seq, hash)))
+            .chain(
+                self.rejected
+                    .iter()
+                    .map(|(seq, hash)| (*seq, format!("! #{} {} rejected", seq, hash))),
+            )
+            .chain(self.gaps.iter().map(|gap| {
+                let line = if gap.end - gap.start == 1 {
+                    format!("! #{} missing", gap.start)
+                } else {
+                    format!("! #{} to #{} missing", gap.start, gap.end - 1)
+                };
+                (gap.start, line)
+            }))
+            .collect();
+        lines.sort_by_key(|(seq, _)| *seq);
+        for (_, line) in lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// The entry of a [`RecordSummary`].
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case", tag = "type", content = "entry")]
+pub enum EntrySummary {
+    /// An app entry decoded from MessagePack.
+    App(serde_json::Value),
+    /// App entry bytes that are not valid MessagePack.
+    AppBytes(String),
+    /// The agent key of an agent entry.
+    Agent(String),
+    /// A capability claim.
+    CapClaim(String),
+    /// A capability grant.
+    CapGrant(String),
+    /// The entry is private.
+    Hidden,
+    /// The header has no entry.
+    NotApplicable,
+    /// The entry was not stored with the header.
+    NotStored,
+}
+
+impl From<&Element> for RecordSummary {
+    fn from(element: &Element) -> Self {
+        let header = element.header();
+        Self {
+            header_hash: element.header_address().to_string(),
+            header_type: format!("{:?}", header.header_type()),
+            author: header.author().to_string(),
+            seq: header.header_seq(),
+            timestamp: header.timestamp().to_string(),
+            entry_hash: header.entry_hash().map(|h| h.to_string()),
+            entry: element.entry().into(),
+        }
+    }
+}
+
+impl From<&ElementEntry> for EntrySummary {
+    fn from(entry: &ElementEntry) -> Self {
+        match entry {
+            ElementEntry::Present(Entry::App(bytes)) => {
+                match ExternIO(bytes.bytes().clone()).decode() {
+                    Ok(value) => EntrySummary::App(value),
+                    Err(_) => EntrySummary::AppBytes(format!("{:?}", bytes.bytes())),
+                }
+            }
+            ElementEntry::Present(Entry::Agent(agent)) => EntrySummary::Agent(agent.to_string()),
+            ElementEntry::Present(Entry::CapClaim(claim)) => {
+                EntrySummary::CapClaim(format!("{:?}", claim))
+            }
+            ElementEntry::Present(Entry::CapGrant(grant)) => {
+                EntrySummary::CapGrant(format!("{:?}", grant))
+            }
+            ElementEntry::Hidden => EntrySummary::Hidden,
+            ElementEntry::NotApplicable => EntrySummary::NotApplicable,
+            ElementEntry::NotStored => EntrySummary::NotStored,
+        }
+    }
+}
+
+/// Calls a zome function with the JSON payload from the args
+/// and returns the output as JSON.
+/// The host function calls are returned too if `args.trace` is set.
+/// Attaches a new app interface if an app port is not provided.
+pub async fn call_zome(
+    cmd: &CmdRunner,
+    args: CallZome,
+) -> anyhow::Result<(serde_json::Value, Option<Vec<HostFnCall>>)> {
+    let app = app_cmd_runner(cmd, args.app_port).await?;
+    let cell_id = CellId::new(args.dna, args.agent_key);
+    if args.trace {
+        let (output, host_fn_calls) = app
+            .call_zome_traced(cell_id, args.zome, args.function, None, args.payload)
+            .await?;
+        Ok((output, Some(host_fn_calls)))
+    } else {
+        let output = app
+            .call_zome(cell_id, args.zome, args.function, None, args.payload)
+            .await?;
+        Ok((output, None))
+    }
+}
+
+/// Connect to the app interface on `app_port`
+/// or attach a new one if it is not set.
+async fn app_cmd_runner(cmd: &CmdRunner, app_port: Option<u16>) -> anyhow::Result<AppCmdRunner> {
+    let app_port = match app_port {
+        Some(port) => port,
+        None => {
+            attach_app_interface(
+                cmd,
+                AddAppWs {
+                    port: None,
+                    verify: true,
+                    allowed_origins: Vec::new(),
+                },
+            )
+            .await?
+        }
+    };
+    Ok(AppCmdRunner::try_new_with_config(cmd.host(), app_port, cmd.websocket_config()).await?)
+}
+
+/// Parse an [`AdminRequest`] from JSON, send it with
+/// [`CmdRunner::command`] and return the response.
+pub async fn raw_request(cmd: &CmdRunner, json: &str) -> anyhow::Result<AdminResponse> {
+    let request = parse_raw_request(json)?;
+    cmd.command(request).await
+}
+
+/// Parse an [`AdminRequest`] from JSON.
+/// The error lists the valid request types.
+pub fn parse_raw_request(json: &str) -> anyhow::Result<AdminRequest> {
+    serde_json::from_str(json).map_err(|e| {
+        anyhow!(
+            "Failed to parse the AdminRequest: {}\n\
+            Requests look like {{\"type\": \"list_dnas\"}} or \
+            {{\"type\": \"activate_app\", \"data\": {{\"installed_app_id\": \"test-app\"}}}}.\n\
+            Valid types are: {}",
+            e,
+            ADMIN_REQUEST_TYPES.join(", ")
+        )
+    })
+}
+
 fn parse_agent_key(arg: &str) -> anyhow::Result<AgentPubKey> {
     AgentPubKey::try_from(arg).map_err(|e| anyhow::anyhow!("{:?}", e))
 }
@@ -489,10 +2418,85 @@ fn parse_dna_hash(arg: &str) -> anyhow::Result<DnaHash> {
     DnaHash::try_from(arg).map_err(|e| anyhow::anyhow!("{:?}", e))
 }
 
+fn parse_header_hash(arg: &str) -> anyhow::Result<HeaderHash> {
+    HeaderHash::try_from(arg).map_err(|e| anyhow::anyhow!("{:?}", e))
+}
+
+fn parse_json(arg: &str) -> anyhow::Result<serde_json::Value> {
+    serde_json::from_str(arg).context("The payload must be valid JSON")
+}
+
+fn parse_entry_type(arg: &str) -> anyhow::Result<EntryType> {
+    let entry_type = match arg {
+        "agent" => EntryType::AgentPubKey,
+        "cap-claim" => EntryType::CapClaim,
+        "cap-grant" => EntryType::CapGrant,
+        _ => {
+            let parts: Vec<_> = arg.split(':').collect();
+            ensure!(
+                parts.len() >= 3 && parts.len() <= 4 && parts[0] == "app",
+                "Entry type {} must be one of agent, cap-claim, cap-grant or app:<zome-id>:<entry-def-index>[:private]",
+                arg
+            );
+            let visibility = match parts.get(3) {
+                None => EntryVisibility::Public,
+                Some(&"private") => EntryVisibility::Private,
+                Some(v) => bail!("Unknown entry visibility {}", v),
+            };
+            let zome_id: u8 = parts[1].parse()?;
+            let entry_def_index: u8 = parts[2].parse()?;
+            EntryType::App(AppEntryType::new(
+                entry_def_index.into(),
+                zome_id.into(),
+                visibility,
+            ))
+        }
+    };
+    Ok(entry_type)
+}
+
+fn parse_header_type(arg: &str) -> anyhow::Result<HeaderType> {
+    let header_type = match arg {
+        "dna" => HeaderType::Dna,
+        "agent-validation-pkg" => HeaderType::AgentValidationPkg,
+        "init-zomes-complete" => HeaderType::InitZomesComplete,
+        "create-link" => HeaderType::CreateLink,
+        "delete-link" => HeaderType::DeleteLink,
+        "open-chain" => HeaderType::OpenChain,
+        "close-chain" => HeaderType::CloseChain,
+        "create" => HeaderType::Create,
+        "update" => HeaderType::Update,
+        "delete" => HeaderType::Delete,
+        _ => bail!("Unknown header type {}", arg),
+    };
+    Ok(header_type)
+}
+
+fn parse_sequence_range(arg: &str) -> anyhow::Result<Range<u32>> {
+    let mut parts = arg.splitn(2, "..");
+    let start = parts.next().unwrap_or_default();
+    let end = parts
+        .next()
+        .ok_or_else(|| anyhow!("Sequence {} must be a range like 3..10", arg))?;
+    let start = if start.is_empty() { 0 } else { start.parse()? };
+    let end = if end.is_empty() {
+        u32::MAX
+    } else {
+        end.parse()?
+    };
+    ensure!(start < end, "Sequence range {} is empty", arg);
+    Ok(start..end)
+}
+
 impl From<CellId> for DumpState {
     fn from(cell_id: CellId) -> Self {
         let (dna, agent_key) = cell_id.into_dna_and_agent();
-        Self { agent_key, dna }
+        Self {
+            agent_key,
+            dna,
+            json: false,
+            window: TimeWindow::default(),
+        }
     }
 }
 
@@ -502,6 +2506,24 @@ impl From<DumpState> for CellId {
     }
 }
 
+impl From<DumpOpCounts> for CellId {
+    fn from(d: DumpOpCounts) -> Self {
+        CellId::new(d.dna, d.agent_key)
+    }
+}
+
+impl From<DumpZomeCallMetering> for CellId {
+    fn from(d: DumpZomeCallMetering) -> Self {
+        CellId::new(d.dna, d.agent_key)
+    }
+}
+
+impl From<Coverage> for CellId {
+    fn from(c: Coverage) -> Self {
+        CellId::new(c.dna, c.agent_key)
+    }
+}
+
 impl From<ListAgents> for Option<CellId> {
     fn from(la: ListAgents) -> Self {
         let ListAgents {
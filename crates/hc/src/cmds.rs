@@ -1,12 +1,41 @@
+use std::convert::TryFrom;
+use std::convert::TryInto;
+use std::path::Component;
 use std::path::PathBuf;
 
+use holochain_conductor_api::config::conductor::DbSyncLevel;
+use holochain_conductor_api::AdminInterfaceConfig;
+use holochain_conductor_api::InterfaceDriver;
 use holochain_p2p::kitsune_p2p::KitsuneP2pConfig;
-use holochain_p2p::kitsune_p2p::TransportConfig;
+use holochain_p2p::kitsune_p2p::KitsuneP2pTuningParams;
 use holochain_types::prelude::InstalledAppId;
 use structopt::StructOpt;
 use url2::Url2;
 
-const DEFAULT_APP_ID: &str = "test-app";
+use crate::config::ConductorConfigSpec;
+use crate::config::NetworkConfigBuilder;
+use crate::config::ProxyMode;
+use crate::keystore::KeystoreKind;
+use crate::ConnectOptions;
+
+/// The app id of setups generated without `--app-id`.
+pub const DEFAULT_APP_ID: &str = "test-app";
+
+/// The address the quic network of `--dev` setups binds to.
+pub const DEV_BIND_TO: &str = "kitsune-quic://127.0.0.1:0";
+
+/// The milliseconds between gossip rounds of `--dev` setups.
+pub const DEV_GOSSIP_DELAY_MS: u32 = 1;
+
+/// The warning printed when setups are generated with `--dev`.
+pub const DEV_BANNER: &str = "
+!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!
+!! DEV MODE: these setups keep their keys in memory, don't wait for  !!
+!! database writes to reach the disk and gossip as fast as they can. !!
+!! They are for local development only and must NEVER be connected   !!
+!! to a real network. Remove them with `hc clean --dev`.             !!
+!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!";
+
 #[derive(Debug, StructOpt, Clone)]
 // This creates a new holochain setup
 // which is a
@@ -35,6 +64,288 @@ pub struct Create {
     /// For example `hc gen -r path/to/my/chains -n 3 -d=first,second,third`
     /// will create three setups with directories named "first", "second", and "third".
     pub directories: Vec<PathBuf>,
+    #[structopt(long)]
+    /// Install the app for a named agent that keeps the same key
+    /// in every setup generated with this name.
+    /// The key is generated on first use and kept in `$(pwd)/.hc_agents`.
+    /// See `hc agent list`.
+    ///
+    /// For example `hc gen --reuse-agent alice` run twice
+    /// creates two setups with the same agent.
+    pub reuse_agent: Option<String>,
+    #[structopt(long, conflicts_with_all = &["reuse-agent", "keystore-path"])]
+    /// Where the conductor keeps its keys:
+    /// `persistent` in the setup's own keystore, which is the default,
+    /// `ephemeral` in memory only, for setups that are thrown away,
+    /// or `shared:<path>` in a keystore directory other setups use too.
+    /// `hc clean` removes a shared keystore with the last setup using it.
+    ///
+    /// For example `hc gen -n 2 --keystore shared:keys` creates two setups
+    /// whose conductors keep their keys in `keys`.
+    pub keystore: Option<KeystoreKind>,
+    #[structopt(long, conflicts_with = "reuse-agent")]
+    /// Keep the keys in this keystore directory, which other setups
+    /// can use too. The same as `--keystore shared:<path>`.
+    ///
+    /// For example `hc gen --keystore-path ../keys`.
+    pub keystore_path: Option<PathBuf>,
+    #[structopt(long)]
+    /// Serve the admin interface on a unix domain socket
+    /// in the setup directory instead of a port.
+    /// Only supported on unix.
+    ///
+    /// For example `hc gen --uds` creates a setup with
+    /// its admin interface on `<setup>/admin.sock`.
+    pub uds: bool,
+    #[structopt(long)]
+    /// Install the app without activating it
+    /// so it can be inspected before it runs.
+    /// Activate it later with `hc call activate-app <app-id>`.
+    pub no_enable: bool,
+    #[structopt(long)]
+    /// Record how many times each zome function is called,
+    /// how long the calls take and how many host functions they call.
+    /// See the totals with `hc call metering <dna> <agent_key>`.
+    ///
+    /// Recording adds a small overhead to every zome call.
+    pub zome_call_metered: bool,
+    #[structopt(long, conflicts_with = "uds")]
+    /// The address the conductor's admin and app interfaces listen on.
+    /// Defaults to `127.0.0.1`, or `::1` on machines without IPv4.
+    ///
+    /// For example `hc gen --bind ::1` creates a setup that is
+    /// only reachable over the IPv6 loopback.
+    pub bind: Option<std::net::IpAddr>,
+    #[structopt(long = "admin-interface", conflicts_with = "uds", number_of_values = 1)]
+    /// Add an admin interface listening on this address and port,
+    /// or on a free port if the port is `0`. Can be passed more than once.
+    /// hc uses the first one on the loopback so one of them must be.
+    /// The others are for other tools, like a dashboard on another network.
+    /// See them with `hc status`.
+    ///
+    /// For example `hc gen --admin-interface 127.0.0.1:0 --admin-interface 172.17.0.1:9200`.
+    pub admin_interfaces: Vec<std::net::SocketAddr>,
+    #[structopt(
+        long = "app-port",
+        number_of_values = 1,
+        parse(try_from_str = parse_app_port)
+    )]
+    /// Add an app interface on this port to the conductor config
+    /// so it is there whenever the conductor starts, without hc
+    /// attaching it. Can be passed more than once.
+    /// It accepts the same origins as the admin interface.
+    /// `hc run` reports it as ready and won't attach another interface on it.
+    /// Only for a single conductor because they can't all listen on the port.
+    ///
+    /// For example `hc gen --app-port 8888`.
+    pub app_ports: Vec<u16>,
+    #[structopt(long, value_delimiter = ",")]
+    /// The origins web based tools can connect to the conductor from,
+    /// or `*` for any origin. The admin interface and the app interfaces
+    /// hc attaches only accept these and hc's own origin.
+    /// Change them later with `hc config set-origins`.
+    ///
+    /// For example `hc gen --allowed-origins http://localhost:3000,http://localhost:8888`.
+    pub allowed_origins: Vec<String>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_db_sync_level),
+        possible_values = &["normal", "fast", "off"]
+    )]
+    /// How long the conductor waits for database writes to reach the disk.
+    /// `fast` and `off` are quicker but a crash can lose the latest writes
+    /// so only use them for setups that can be thrown away.
+    /// Default: normal.
+    pub db_sync_level: Option<DbSyncLevel>,
+    #[structopt(long)]
+    /// Keep the setup's databases in this directory instead of
+    /// in the setup, for example on a faster disk.
+    /// It is created if it doesn't exist and `hc clean` removes it.
+    /// Conductors can't share databases so this can't be used
+    /// with more than one conductor or a directory another setup uses.
+    ///
+    /// For example `hc gen --db-dir /mnt/ssd/alice`.
+    pub db_dir: Option<PathBuf>,
+    #[structopt(long, conflicts_with = "db-dir", parse(try_from_str = parse_env_dir))]
+    /// Keep the setup's databases in this directory inside the setup.
+    /// The path is relative to the setup and stays relative in the
+    /// config so the setup still runs after it is moved with `hc relocate`.
+    /// Use `--db-dir` for databases outside the setup.
+    ///
+    /// For example `hc gen --env-dir databases`.
+    pub env_dir: Option<PathBuf>,
+    #[structopt(long)]
+    /// A YAML file of conductor config fields to set,
+    /// with the same names as in `conductor-config.yaml`.
+    /// The flags above win over the file.
+    /// The fields stay set when the config is changed
+    /// later with `hc config`.
+    ///
+    /// For example `hc gen --config-overrides overrides.yaml`.
+    pub config_overrides: Option<PathBuf>,
+    #[structopt(long)]
+    /// Path to a JSON file of properties that replace
+    /// the properties of the installed dnas.
+    pub properties: Option<PathBuf>,
+    #[structopt(long)]
+    /// After installing the app, install another app with this id
+    /// that uses the app's cells instead of creating new ones,
+    /// for testing apps that are composed of other apps' cells.
+    /// The conductor checks each cell is installed and has the dna it should.
+    ///
+    /// For example `hc gen --install-with-existing-cells composed my-app.dna.gz`
+    /// installs `test-app` and `composed`, which share the same cells.
+    pub install_with_existing_cells: Option<InstalledAppId>,
+    #[structopt(long)]
+    /// Set the options for fast local development that weren't passed:
+    /// `--keystore ephemeral`, `--db-sync-level off` and
+    /// `network quic -b kitsune-quic://127.0.0.1:0 --gossip-delay-ms 1`
+    /// without a bootstrap service.
+    /// The setups are tagged `[dev]` in `hc list` and removed with `hc clean --dev`.
+    /// They must never be connected to a real network.
+    ///
+    /// For example `hc gen --dev --db-sync-level fast`.
+    pub dev: bool,
+    #[structopt(long)]
+    /// Use the options of a preset in the `hc.toml`.
+    /// Flags that are passed win over the preset.
+    ///
+    /// For example `hc gen --preset quic-cluster`.
+    pub preset: Option<String>,
+    #[structopt(skip)]
+    /// Conductor config fields from a preset.
+    /// The `--config-overrides` file and the flags win over these.
+    pub config: ConductorConfigSpec,
+    #[structopt(skip)]
+    /// How hc connects to the conductors it starts to install the app.
+    /// The interfaces always allow its origin.
+    /// The `hc` binary sets it from its flags, like `hc --origin`.
+    pub connect: ConnectOptions,
+}
+
+impl Create {
+    /// Fill in the options `--dev` sets that weren't passed.
+    /// An explicit network keeps its transport and bootstrap service
+    /// and only gets the short gossip delay.
+    /// Does nothing without `--dev`.
+    pub fn apply_dev(&mut self) {
+        if !self.dev {
+            return;
+        }
+        if self.keystore.is_none() && self.keystore_path.is_none() && self.reuse_agent.is_none() {
+            self.keystore = Some(KeystoreKind::Ephemeral);
+        }
+        self.db_sync_level = self.db_sync_level.or(Some(DbSyncLevel::Off));
+        let NetworkCmd::Network(network) = self.network.get_or_insert_with(|| {
+            NetworkCmd::Network(Network {
+                transport: NetworkType::Quic(Quic {
+                    bind_to: Some(Url2::parse(DEV_BIND_TO)),
+                    override_host: None,
+                    override_port: None,
+                    proxy: None,
+                    proxy_mode: None,
+                    proxy_accept_all: false,
+                }),
+                bootstrap: None,
+                tuning: Tuning::default(),
+            })
+        });
+        network
+            .tuning
+            .gossip_delay_ms
+            .get_or_insert(DEV_GOSSIP_DELAY_MS);
+    }
+
+    /// The keystore chosen with `--keystore` or `--keystore-path`.
+    pub fn keystore_kind(&self) -> KeystoreKind {
+        match &self.keystore_path {
+            Some(path) => KeystoreKind::Shared(path.clone()),
+            None => self.keystore.clone().unwrap_or_default(),
+        }
+    }
+
+    /// The conductor config fields these options set:
+    /// the preset's config, then the `--config-overrides` file
+    /// and then the flags on top.
+    pub fn config_spec(&self) -> anyhow::Result<ConductorConfigSpec> {
+        anyhow::ensure!(
+            self.admin_interfaces.is_empty()
+                || self.admin_interfaces.iter().any(|a| a.ip().is_loopback()),
+            "None of the admin interfaces are on the loopback for hc to connect to. \
+            Add one like `--admin-interface 127.0.0.1:0`"
+        );
+        let file = match &self.config_overrides {
+            Some(path) => ConductorConfigSpec::from_file(path)?,
+            None => ConductorConfigSpec::default(),
+        };
+        let flags = ConductorConfigSpec {
+            network: self
+                .network
+                .clone()
+                .map(|n| n.into_inner().try_into())
+                .transpose()?,
+            zome_call_metering: if self.zome_call_metered {
+                Some(true)
+            } else {
+                None
+            },
+            use_dangerous_test_keystore: if self.keystore_kind() == KeystoreKind::Ephemeral {
+                Some(true)
+            } else {
+                None
+            },
+            interface_address: self.bind,
+            db_sync_level: self.db_sync_level,
+            admin_interfaces: if self.admin_interfaces.is_empty() {
+                None
+            } else {
+                Some(
+                    self.admin_interfaces
+                        .iter()
+                        .map(|address| AdminInterfaceConfig {
+                            driver: InterfaceDriver::Websocket {
+                                port: address.port(),
+                            },
+                            // Like the interface of a config without these.
+                            allowed_origins: Some(vec![self.connect.origin.clone()]),
+                            address: Some(address.ip()),
+                        })
+                        .collect(),
+                )
+            },
+            ..Default::default()
+        };
+        Ok(self.config.clone().merge(file).merge(flags))
+    }
+
+    /// Check the network options work together.
+    /// Like [`Create::check_platform`] the error is a usage error.
+    pub fn check_network(&self) -> Result<(), structopt::clap::Error> {
+        if let Some(network) = &self.network {
+            if let Err(e) = network.clone().into_inner().builder().build() {
+                return Err(structopt::clap::Error::with_description(
+                    &e.to_string(),
+                    structopt::clap::ErrorKind::ArgumentConflict,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the options are supported on this platform.
+    /// The error is a usage error like a bad argument
+    /// so call [`structopt::clap::Error::exit`] on it
+    /// straight after parsing.
+    pub fn check_platform(&self) -> Result<(), structopt::clap::Error> {
+        if self.uds && cfg!(not(unix)) {
+            return Err(structopt::clap::Error::with_description(
+                "--uds is not supported on this platform: \
+                unix domain sockets are only available on unix",
+                structopt::clap::ErrorKind::InvalidValue,
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -60,6 +371,144 @@ pub struct Network {
     /// A bootstrap service can used for peers to discover each other without
     /// prior knowledge of each other.
     pub bootstrap: Option<Url2>,
+    #[structopt(flatten)]
+    pub tuning: Tuning,
+}
+
+#[derive(Debug, StructOpt, Clone, Default)]
+// Network tuning parameters.
+// Unset values use the kitsune defaults.
+//
+// Every agent currently holds the full DHT arc so
+// there is no arc size to tune yet.
+pub struct Tuning {
+    #[structopt(long, parse(try_from_str = parse_ms))]
+    /// Milliseconds between gossip rounds.
+    /// Higher values simulate slower gossip.
+    /// Default: 10.
+    pub gossip_delay_ms: Option<u32>,
+    #[structopt(long, parse(try_from_str = parse_agent_count))]
+    /// How many remote agents to notify when publishing.
+    /// Lower values simulate a sparser network.
+    /// Must be between 1 and 255.
+    /// Default: 5.
+    pub notify_agents: Option<u32>,
+    #[structopt(long, parse(try_from_str = parse_ms))]
+    /// Timeout for notifying remote agents in milliseconds.
+    /// Default: 1000.
+    pub notify_timeout_ms: Option<u32>,
+    #[structopt(long, parse(try_from_str = parse_ms))]
+    /// Timeout for a request to a single remote agent in milliseconds.
+    /// Default: 2000.
+    pub rpc_timeout_ms: Option<u32>,
+    #[structopt(long, parse(try_from_str = parse_agent_count))]
+    /// How many remote agents to make requests like `get` to.
+    /// Must be between 1 and 255.
+    /// Default: 2.
+    pub rpc_multi_agents: Option<u32>,
+    #[structopt(long, parse(try_from_str = parse_ms))]
+    /// Timeout for a request to several remote agents in milliseconds.
+    /// Default: 2000.
+    pub rpc_multi_timeout_ms: Option<u32>,
+    #[structopt(long, parse(try_from_str = parse_ms))]
+    /// How long agent info is valid for in milliseconds.
+    /// Default: 1200000 (20 minutes).
+    pub agent_info_expires_ms: Option<u32>,
+}
+
+/// The longest duration a tuning parameter can be set to (1 hour).
+pub const MAX_TUNING_MS: u32 = 60 * 60 * 1000;
+
+impl Tuning {
+    /// Set the parameters that were passed in.
+    pub fn apply(&self, params: &mut KitsuneP2pTuningParams) {
+        let Tuning {
+            gossip_delay_ms,
+            notify_agents,
+            notify_timeout_ms,
+            rpc_timeout_ms,
+            rpc_multi_agents,
+            rpc_multi_timeout_ms,
+            agent_info_expires_ms,
+        } = self.clone();
+        let set = |param: &mut u32, value: Option<u32>| {
+            if let Some(value) = value {
+                *param = value;
+            }
+        };
+        set(&mut params.gossip_loop_iteration_delay_ms, gossip_delay_ms);
+        set(&mut params.default_notify_remote_agent_count, notify_agents);
+        set(&mut params.default_notify_timeout_ms, notify_timeout_ms);
+        set(&mut params.default_rpc_single_timeout_ms, rpc_timeout_ms);
+        set(
+            &mut params.default_rpc_multi_remote_agent_count,
+            rpc_multi_agents,
+        );
+        set(
+            &mut params.default_rpc_multi_timeout_ms,
+            rpc_multi_timeout_ms,
+        );
+        set(
+            &mut params.agent_info_expires_after_ms,
+            agent_info_expires_ms,
+        );
+    }
+}
+
+fn parse_env_dir(arg: &str) -> anyhow::Result<PathBuf> {
+    let path = PathBuf::from(arg);
+    anyhow::ensure!(
+        path.components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir)),
+        "{} is not a directory inside the setup. \
+        Use --db-dir for databases outside the setup",
+        arg
+    );
+    Ok(path)
+}
+
+fn parse_app_port(arg: &str) -> anyhow::Result<u16> {
+    let port: u16 = arg
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{} is not a port: {}", arg, e))?;
+    anyhow::ensure!(
+        port != 0,
+        "The app port in the config can't be 0 because it would change \
+        every time the conductor starts. Use `hc run -p 0` to attach one instead"
+    );
+    Ok(port)
+}
+
+fn parse_db_sync_level(arg: &str) -> anyhow::Result<DbSyncLevel> {
+    match arg {
+        "normal" => Ok(DbSyncLevel::Normal),
+        "fast" => Ok(DbSyncLevel::Fast),
+        "off" => Ok(DbSyncLevel::Off),
+        _ => anyhow::bail!("{} is not a sync level: use normal, fast or off", arg),
+    }
+}
+
+fn parse_ms(arg: &str) -> anyhow::Result<u32> {
+    parse_in_range(arg, 1, MAX_TUNING_MS)
+}
+
+fn parse_agent_count(arg: &str) -> anyhow::Result<u32> {
+    // Kitsune sends these counts as a u8.
+    parse_in_range(arg, 1, u8::MAX as u32)
+}
+
+fn parse_in_range(arg: &str, min: u32, max: u32) -> anyhow::Result<u32> {
+    let value: u32 = arg
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{} is not a number: {}", arg, e))?;
+    anyhow::ensure!(
+        (min..=max).contains(&value),
+        "{} must be between {} and {}",
+        value,
+        min,
+        max
+    );
+    Ok(value)
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -90,6 +539,15 @@ pub struct Quic {
     #[structopt(short, parse(from_str = Url2::parse))]
     /// Run through an external proxy at this url.
     pub proxy: Option<Url2>,
+    #[structopt(long, possible_values = &["client", "server"])]
+    /// Connect through the proxy at `-p` as a client
+    /// or run a proxy server for other conductors.
+    /// Default: client if `-p` is set.
+    pub proxy_mode: Option<ProxyMode>,
+    #[structopt(long)]
+    /// Proxy for any conductor that asks.
+    /// Only with `--proxy-mode server`.
+    pub proxy_accept_all: bool,
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -138,7 +596,7 @@ impl Existing {
                 .push(setups.last().cloned().expect("Safe due to check above"));
         } else if setups.len() > 1 {
             // There is multiple setups, the use must disambiguate
-            msg!(
+            tracing::warn!(
                 "
 There are multiple setups and hc doesn't know which to run.
 You can run:
@@ -152,7 +610,7 @@ Run `hc list` to see the setups or `hc r --help` for more information."
             crate::save::list(std::env::current_dir()?, 0)?;
         } else {
             // There is no setups
-            msg!(
+            tracing::warn!(
                 "
 Before running or calling you need to generate a setup.
 You can use `hc generate` or `hc g` to do this.
@@ -167,49 +625,47 @@ Run `hc g --help` for more options."
     }
 }
 
-impl From<Network> for KitsuneP2pConfig {
-    fn from(n: Network) -> Self {
+impl Network {
+    /// A builder with these options.
+    pub fn builder(self) -> NetworkConfigBuilder {
         let Network {
             transport,
             bootstrap,
-        } = n;
-        let mut kit = KitsuneP2pConfig::default();
-        kit.bootstrap_service = bootstrap;
-
+            tuning,
+        } = self;
+        let mut params = KitsuneP2pTuningParams::default();
+        tuning.apply(&mut params);
+        let builder = NetworkConfigBuilder::new()
+            .bootstrap(bootstrap)
+            .tuning(params);
         match transport {
-            NetworkType::Mem => (),
+            NetworkType::Mem => builder.mem(),
             NetworkType::Quic(Quic {
                 bind_to,
                 override_host,
                 override_port,
-                proxy: None,
+                proxy,
+                proxy_mode,
+                proxy_accept_all,
             }) => {
-                kit.transport_pool = vec![TransportConfig::Quic {
-                    bind_to,
-                    override_host,
-                    override_port,
-                }];
-            }
-            NetworkType::Quic(Quic {
-                bind_to,
-                override_host,
-                override_port,
-                proxy: Some(proxy_url),
-            }) => {
-                let transport = TransportConfig::Quic {
-                    bind_to,
-                    override_host,
-                    override_port,
-                };
-                kit.transport_pool = vec![TransportConfig::Proxy {
-                    sub_transport: Box::new(transport),
-                    proxy_config: holochain_p2p::kitsune_p2p::ProxyConfig::RemoteProxyClient {
-                        proxy_url,
-                    },
-                }]
+                let builder = builder
+                    .quic(bind_to, override_host, override_port)
+                    .proxy_accept_all(proxy_accept_all);
+                // A proxy url on its own means a client of that proxy.
+                match (proxy_mode, proxy) {
+                    (None, None) => builder,
+                    (mode, proxy) => builder.proxy(mode.unwrap_or(ProxyMode::Client), proxy),
+                }
             }
         }
-        kit
+    }
+}
+
+impl TryFrom<Network> for KitsuneP2pConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(n: Network) -> Result<Self, Self::Error> {
+        n.builder().build()
     }
 }
 
@@ -220,6 +676,26 @@ impl Default for Create {
             app_id: DEFAULT_APP_ID.to_string(),
             root: None,
             directories: Vec::with_capacity(0),
+            reuse_agent: None,
+            keystore: None,
+            keystore_path: None,
+            uds: false,
+            no_enable: false,
+            zome_call_metered: false,
+            bind: None,
+            admin_interfaces: Vec::new(),
+            app_ports: Vec::new(),
+            allowed_origins: Vec::new(),
+            db_sync_level: None,
+            db_dir: None,
+            env_dir: None,
+            config_overrides: None,
+            properties: None,
+            install_with_existing_cells: None,
+            dev: false,
+            preset: None,
+            config: ConductorConfigSpec::default(),
+            connect: ConnectOptions::default(),
         }
     }
 }
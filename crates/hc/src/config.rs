@@ -1,20 +1,855 @@
 //! Helpers for creating, reading and writing [`ConductorConfig`]s.
+//!
+//! hc generates a minimal config. Anything else the conductor supports
+//! can be set with a [`ConductorConfigSpec`], e.g. from a YAML file
+//! passed to `hc gen --config-overrides`. The fields a spec sets are
+//! kept next to the config in [`CONFIG_OVERRIDES`] so applying another
+//! spec later with [`apply_overrides`] keeps them instead of
+//! going back to the generated values.
+//! The network config is put together with a [`NetworkConfigBuilder`]
+//! which rejects combinations the conductor can't use.
+//! Tools that generate setups themselves can build the whole config
+//! with a [`ConductorConfigBuilder`], which `hc gen` uses too.
+use std::net::IpAddr;
+use std::path::Path;
 use std::path::PathBuf;
 
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
 use holochain_conductor_api::config::conductor::ConductorConfig;
+use holochain_conductor_api::config::conductor::DbSyncLevel;
+use holochain_conductor_api::config::conductor::DpkiConfig;
+use holochain_conductor_api::config::conductor::PassphraseServiceConfig;
+use holochain_conductor_api::AdminInterfaceConfig;
+use holochain_conductor_api::InterfaceDriver;
+use holochain_conductor_api::StaticAppInterfaceConfig;
+use holochain_p2p::kitsune_p2p::KitsuneP2pConfig;
+use holochain_p2p::kitsune_p2p::KitsuneP2pTuningParams;
+use holochain_p2p::kitsune_p2p::ProxyAcceptConfig;
+use holochain_p2p::kitsune_p2p::ProxyConfig;
+use holochain_p2p::kitsune_p2p::TransportConfig;
+use structopt::StructOpt;
+use url2::Url2;
+
+use crate::cmds::Existing;
+use crate::cmds::MAX_TUNING_MS;
 
 /// Name of the file that conductor config is written to.
 pub const CONDUCTOR_CONFIG: &str = "conductor-config.yaml";
 
+/// Name of the file in a setup that keeps the fields set
+/// by [`ConductorConfigSpec`]s so they survive regeneration.
+pub const CONFIG_OVERRIDES: &str = "conductor-config-overrides.yaml";
+
+/// The fields of a [`ConductorConfig`] to set instead of the generated ones.
+/// Fields that are `None` are left as they are.
+/// The environment path isn't here because it is chosen
+/// when the setup is generated, see `hc gen --db-dir`.
+///
+/// It is read from YAML with the same field names as the conductor config:
+/// ```yaml
+/// zome_call_metering: true
+/// passphrase_service:
+///   type: cmd
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConductorConfigSpec {
+    /// See [`ConductorConfig::use_dangerous_test_keystore`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_dangerous_test_keystore: Option<bool>,
+    /// See [`ConductorConfig::signing_service_uri`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing_service_uri: Option<String>,
+    /// See [`ConductorConfig::encryption_service_uri`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption_service_uri: Option<String>,
+    /// See [`ConductorConfig::decryption_service_uri`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decryption_service_uri: Option<String>,
+    /// See [`ConductorConfig::dpki`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dpki: Option<DpkiConfig>,
+    /// See [`ConductorConfig::keystore_path`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keystore_path: Option<PathBuf>,
+    /// See [`ConductorConfig::passphrase_service`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passphrase_service: Option<PassphraseServiceConfig>,
+    /// See [`ConductorConfig::admin_interfaces`].
+    /// hc still chooses the port of the first interface
+    /// when the setup is run if it is `0`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_interfaces: Option<Vec<AdminInterfaceConfig>>,
+    /// See [`ConductorConfig::app_interfaces`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_interfaces: Option<Vec<StaticAppInterfaceConfig>>,
+    /// See [`ConductorConfig::interface_address`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface_address: Option<IpAddr>,
+    /// See [`ConductorConfig::network`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<KitsuneP2pConfig>,
+    /// See [`ConductorConfig::zome_call_metering`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zome_call_metering: Option<bool>,
+    /// See [`ConductorConfig::db_sync_level`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub db_sync_level: Option<DbSyncLevel>,
+}
+
+impl ConductorConfigSpec {
+    /// Read a spec from a YAML file.
+    /// Fields the conductor config doesn't have are an error
+    /// so a typo isn't silently ignored.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let yaml = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config overrides {}", path.display()))?;
+        serde_yaml::from_str(&yaml)
+            .with_context(|| format!("Failed to parse config overrides {}", path.display()))
+    }
+
+    /// Combine this spec with a newer one.
+    /// The fields the newer spec sets win.
+    pub fn merge(self, newer: Self) -> Self {
+        Self {
+            use_dangerous_test_keystore: newer
+                .use_dangerous_test_keystore
+                .or(self.use_dangerous_test_keystore),
+            signing_service_uri: newer.signing_service_uri.or(self.signing_service_uri),
+            encryption_service_uri: newer.encryption_service_uri.or(self.encryption_service_uri),
+            decryption_service_uri: newer.decryption_service_uri.or(self.decryption_service_uri),
+            dpki: newer.dpki.or(self.dpki),
+            keystore_path: newer.keystore_path.or(self.keystore_path),
+            passphrase_service: newer.passphrase_service.or(self.passphrase_service),
+            admin_interfaces: newer.admin_interfaces.or(self.admin_interfaces),
+            app_interfaces: newer.app_interfaces.or(self.app_interfaces),
+            interface_address: newer.interface_address.or(self.interface_address),
+            network: newer.network.or(self.network),
+            zome_call_metering: newer.zome_call_metering.or(self.zome_call_metering),
+            db_sync_level: newer.db_sync_level.or(self.db_sync_level),
+        }
+    }
+
+    /// Set the fields of the config that this spec sets.
+    pub fn apply(&self, config: &mut ConductorConfig) {
+        if let Some(v) = self.use_dangerous_test_keystore {
+            config.use_dangerous_test_keystore = v;
+        }
+        if let Some(v) = &self.signing_service_uri {
+            config.signing_service_uri = Some(v.clone());
+        }
+        if let Some(v) = &self.encryption_service_uri {
+            config.encryption_service_uri = Some(v.clone());
+        }
+        if let Some(v) = &self.decryption_service_uri {
+            config.decryption_service_uri = Some(v.clone());
+        }
+        if let Some(v) = &self.dpki {
+            config.dpki = Some(v.clone());
+        }
+        if let Some(v) = &self.keystore_path {
+            config.keystore_path = Some(v.clone());
+        }
+        if let Some(v) = &self.passphrase_service {
+            config.passphrase_service = Some(v.clone());
+        }
+        if let Some(v) = &self.admin_interfaces {
+            config.admin_interfaces = Some(v.clone());
+        }
+        if let Some(v) = &self.app_interfaces {
+            config.app_interfaces = Some(v.clone());
+        }
+        if let Some(v) = self.interface_address {
+            config.interface_address = Some(v);
+        }
+        if let Some(v) = &self.network {
+            config.network = Some(v.clone());
+        }
+        if let Some(v) = self.zome_call_metering {
+            config.zome_call_metering = v;
+        }
+        if let Some(v) = self.db_sync_level {
+            config.db_sync_level = v;
+        }
+    }
+}
+
+/// Builds the conductor config of a setup for tools that
+/// generate setups without going through `hc gen`.
+///
+/// It starts from the same config hc generates: the keystore is in the
+/// environment directory and there is one admin websocket on a port
+/// chosen when the setup is run that allows the [`DEFAULT_ORIGIN`](crate::DEFAULT_ORIGIN).
+/// Fields this builder has no method for, including ones a newer
+/// conductor knows, can be set with [`extra`](Self::extra).
+///
+/// ```
+/// use holochain_hc::config::ConductorConfigBuilder;
+/// use holochain_hc::config::NetworkConfigBuilder;
+/// let yaml = ConductorConfigBuilder::new("/tmp/setup".into())
+///     .network(NetworkConfigBuilder::new().build().unwrap())
+///     .extra("db_sync_level", serde_yaml::Value::String("off".into()))
+///     .build();
+/// assert!(yaml.contains("db_sync_level: off"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConductorConfigBuilder {
+    config: ConductorConfig,
+    extra: serde_yaml::Mapping,
+}
+
+impl ConductorConfigBuilder {
+    /// The config hc generates for a setup keeping its
+    /// databases in `environment_path`.
+    pub fn new(environment_path: PathBuf) -> Self {
+        let mut config = ConductorConfig::default();
+        config.environment_path = environment_path.clone().into();
+        config.keystore_path = Some(environment_path.join("keystore"));
+        // The port is chosen when the setup is run.
+        config.admin_interfaces = Some(vec![AdminInterfaceConfig {
+            driver: InterfaceDriver::Websocket { port: 0 },
+            allowed_origins: Some(vec![crate::DEFAULT_ORIGIN.to_string()]),
+            address: None,
+        }]);
+        Self::from(config)
+    }
+
+    /// Keep the databases in this directory.
+    /// The keystore path isn't changed.
+    pub fn environment_path(mut self, path: PathBuf) -> Self {
+        self.config.environment_path = path.into();
+        self
+    }
+
+    /// Add an admin interface to the ones the config already has.
+    pub fn admin_interface(mut self, interface: AdminInterfaceConfig) -> Self {
+        self.config
+            .admin_interfaces
+            .get_or_insert_with(Vec::new)
+            .push(interface);
+        self
+    }
+
+    /// Use these admin interfaces instead of the generated one.
+    pub fn admin_interfaces(mut self, interfaces: Vec<AdminInterfaceConfig>) -> Self {
+        self.config.admin_interfaces = Some(interfaces);
+        self
+    }
+
+    /// Keep the keystore in this directory.
+    pub fn keystore_path(mut self, path: PathBuf) -> Self {
+        self.config.keystore_path = Some(path);
+        self
+    }
+
+    /// Use the test keystore, which has no passphrase
+    /// and must never be used for real keys.
+    pub fn use_dangerous_test_keystore(mut self, dangerous: bool) -> Self {
+        self.config.use_dangerous_test_keystore = dangerous;
+        self
+    }
+
+    /// How the conductor gets the keystore passphrase.
+    pub fn passphrase_service(mut self, service: PassphraseServiceConfig) -> Self {
+        self.config.passphrase_service = Some(service);
+        self
+    }
+
+    /// Use this network, e.g. one from a [`NetworkConfigBuilder`].
+    pub fn network(mut self, network: KitsuneP2pConfig) -> Self {
+        self.config.network = Some(network);
+        self
+    }
+
+    /// Bind the interfaces to this address instead of the loopback.
+    pub fn interface_address(mut self, address: IpAddr) -> Self {
+        self.config.interface_address = Some(address);
+        self
+    }
+
+    /// Set the fields the spec sets, like `hc gen --config-overrides` does.
+    pub fn spec(mut self, spec: &ConductorConfigSpec) -> Self {
+        spec.apply(&mut self.config);
+        self
+    }
+
+    /// Set a top level field of the YAML to `value`.
+    /// It replaces the field if the config already has it.
+    pub fn extra(mut self, key: &str, value: serde_yaml::Value) -> Self {
+        self.extra
+            .insert(serde_yaml::Value::String(key.to_string()), value);
+        self
+    }
+
+    /// The config without the [`extra`](Self::extra) fields,
+    /// which the conductor's types may not have.
+    pub fn config(&self) -> &ConductorConfig {
+        &self.config
+    }
+
+    /// The YAML of the config with the extra fields.
+    pub fn build(&self) -> String {
+        let mut yaml = match serde_yaml::to_value(&self.config) {
+            Ok(serde_yaml::Value::Mapping(yaml)) => yaml,
+            _ => unreachable!("A conductor config is a mapping"),
+        };
+        for (key, value) in &self.extra {
+            yaml.insert(key.clone(), value.clone());
+        }
+        serde_yaml::to_string(&yaml).expect("Failed to serialize the conductor config")
+    }
+
+    /// Write the config to [`CONDUCTOR_CONFIG`] in the setup at `setup_path`
+    /// and return the path of the file.
+    pub fn write_to(&self, setup_path: &Path) -> anyhow::Result<PathBuf> {
+        let path = setup_path.join(CONDUCTOR_CONFIG);
+        std::fs::write(&path, self.build())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+impl From<ConductorConfig> for ConductorConfigBuilder {
+    fn from(config: ConductorConfig) -> Self {
+        Self {
+            config,
+            extra: serde_yaml::Mapping::new(),
+        }
+    }
+}
+
+/// Builds a [`KitsuneP2pConfig`] and checks the options
+/// work together before anything is written.
+///
+/// ```
+/// use holochain_hc::config::NetworkConfigBuilder;
+/// use holochain_hc::config::ProxyMode;
+/// let config = NetworkConfigBuilder::new()
+///     .quic(None, None, None)
+///     .proxy(ProxyMode::Client, Some(url2::url2!("kitsune-proxy://a.host")))
+///     .build()
+///     .unwrap();
+/// assert_eq!(config.transport_pool.len(), 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkConfigBuilder {
+    transport: Transport,
+    proxy: Option<(ProxyMode, Option<Url2>)>,
+    proxy_accept_all: bool,
+    bootstrap: Option<Url2>,
+    tuning: KitsuneP2pTuningParams,
+}
+
+/// The transport a [`NetworkConfigBuilder`] uses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transport {
+    /// The in memory transport. Only conductors in the
+    /// same process can reach each other.
+    Mem,
+    /// The QUIC transport.
+    Quic {
+        /// The network interface and port to bind to.
+        bind_to: Option<Url2>,
+        /// The host to tell peers instead of the local NIC ip.
+        override_host: Option<String>,
+        /// The port to tell peers instead of the local NIC port.
+        override_port: Option<u16>,
+    },
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport::Mem
+    }
+}
+
+/// How the conductor uses a proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyMode {
+    /// Connect through a remote proxy.
+    Client,
+    /// Run a proxy other conductors can connect through.
+    Server,
+}
+
+impl std::str::FromStr for ProxyMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(ProxyMode::Client),
+            "server" => Ok(ProxyMode::Server),
+            _ => bail!("{} is not a proxy mode: use client or server", s),
+        }
+    }
+}
+
+impl NetworkConfigBuilder {
+    /// A network with the in memory transport and default tuning.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the in memory transport.
+    pub fn mem(mut self) -> Self {
+        self.transport = Transport::Mem;
+        self
+    }
+
+    /// Use the QUIC transport.
+    pub fn quic(
+        mut self,
+        bind_to: Option<Url2>,
+        override_host: Option<String>,
+        override_port: Option<u16>,
+    ) -> Self {
+        self.transport = Transport::Quic {
+            bind_to,
+            override_host,
+            override_port,
+        };
+        self
+    }
+
+    /// Run the transport through a proxy.
+    /// A client needs the url of the proxy and a server must not have one.
+    pub fn proxy(mut self, mode: ProxyMode, url: Option<Url2>) -> Self {
+        self.proxy = Some((mode, url));
+        self
+    }
+
+    /// Proxy for any conductor that asks.
+    /// Only a proxy server can accept requests.
+    pub fn proxy_accept_all(mut self, accept_all: bool) -> Self {
+        self.proxy_accept_all = accept_all;
+        self
+    }
+
+    /// Use a bootstrap service for peers to find each other.
+    pub fn bootstrap(mut self, url: Option<Url2>) -> Self {
+        self.bootstrap = url;
+        self
+    }
+
+    /// Use these tuning parameters instead of the kitsune defaults.
+    pub fn tuning(mut self, tuning: KitsuneP2pTuningParams) -> Self {
+        self.tuning = tuning;
+        self
+    }
+
+    /// Check the options and build the config.
+    /// The errors name the `hc gen network` options that conflict.
+    pub fn build(self) -> anyhow::Result<KitsuneP2pConfig> {
+        let Self {
+            transport,
+            proxy,
+            proxy_accept_all,
+            bootstrap,
+            tuning,
+        } = self;
+        check_tuning(&tuning)?;
+        let transport = match transport {
+            Transport::Mem => {
+                ensure!(
+                    proxy.is_none() && !proxy_accept_all,
+                    "The proxy options -p, --proxy-mode and --proxy-accept-all \
+                    need the quic transport"
+                );
+                None
+            }
+            Transport::Quic {
+                bind_to,
+                override_host,
+                override_port,
+            } => Some(TransportConfig::Quic {
+                bind_to,
+                override_host,
+                override_port,
+            }),
+        };
+        let transport = match (transport, proxy) {
+            (transport, None) => {
+                ensure!(
+                    !proxy_accept_all,
+                    "--proxy-accept-all needs --proxy-mode server"
+                );
+                transport
+            }
+            (Some(sub_transport), Some((ProxyMode::Client, url))) => {
+                ensure!(
+                    !proxy_accept_all,
+                    "--proxy-accept-all can't be used with --proxy-mode client: \
+                    only a proxy server accepts requests"
+                );
+                let proxy_url = url.ok_or_else(|| {
+                    anyhow::anyhow!("--proxy-mode client needs the url of the proxy in -p")
+                })?;
+                Some(TransportConfig::Proxy {
+                    sub_transport: Box::new(sub_transport),
+                    proxy_config: ProxyConfig::RemoteProxyClient { proxy_url },
+                })
+            }
+            (Some(sub_transport), Some((ProxyMode::Server, url))) => {
+                ensure!(
+                    url.is_none(),
+                    "-p can't be used with --proxy-mode server: \
+                    a proxy server doesn't connect to another proxy"
+                );
+                Some(TransportConfig::Proxy {
+                    sub_transport: Box::new(sub_transport),
+                    proxy_config: ProxyConfig::LocalProxyServer {
+                        proxy_accept_config: Some(if proxy_accept_all {
+                            ProxyAcceptConfig::AcceptAll
+                        } else {
+                            ProxyAcceptConfig::RejectAll
+                        }),
+                    },
+                })
+            }
+            (None, Some(_)) => unreachable!("The mem transport is checked above"),
+        };
+        let mut config = KitsuneP2pConfig::default();
+        config.transport_pool = transport.into_iter().collect();
+        config.bootstrap_service = bootstrap;
+        config.tuning_params = tuning;
+        Ok(config)
+    }
+}
+
+/// Kitsune sends the agent counts as a `u8` and
+/// durations of `0` would spin or time out straight away.
+fn check_tuning(tuning: &KitsuneP2pTuningParams) -> anyhow::Result<()> {
+    let counts = [
+        ("--notify-agents", tuning.default_notify_remote_agent_count),
+        (
+            "--rpc-multi-agents",
+            tuning.default_rpc_multi_remote_agent_count,
+        ),
+    ];
+    for (option, count) in counts.iter() {
+        ensure!(
+            (1..=u8::MAX as u32).contains(count),
+            "{} must be between 1 and {}",
+            option,
+            u8::MAX
+        );
+    }
+    let durations = [
+        ("--gossip-delay-ms", tuning.gossip_loop_iteration_delay_ms),
+        ("--notify-timeout-ms", tuning.default_notify_timeout_ms),
+        ("--rpc-timeout-ms", tuning.default_rpc_single_timeout_ms),
+        (
+            "--rpc-multi-timeout-ms",
+            tuning.default_rpc_multi_timeout_ms,
+        ),
+        (
+            "--agent-info-expires-ms",
+            tuning.agent_info_expires_after_ms,
+        ),
+    ];
+    for (option, ms) in durations.iter() {
+        ensure!(
+            (1..=MAX_TUNING_MS).contains(ms),
+            "{} must be between 1 and {}",
+            option,
+            MAX_TUNING_MS
+        );
+    }
+    Ok(())
+}
+
+#[derive(Debug, StructOpt, Clone)]
+/// Change the conductor config of existing setups.
+pub struct Reconfigure {
+    #[structopt(subcommand)]
+    /// Set the allowed origins or show or check a config
+    /// instead of applying an overrides file.
+    pub cmd: Option<ConfigCmd>,
+    #[structopt(flatten)]
+    pub existing: Existing,
+    #[structopt(long)]
+    /// A YAML file of conductor config fields to set.
+    /// Fields set when the setup was generated
+    /// or configured before are kept unless the file sets them too.
+    pub config_overrides: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+#[allow(missing_docs)]
+pub enum ConfigCmd {
+    /// Set the origins the admin interface of existing setups
+    /// and the app interfaces hc attaches accept.
+    ///
+    /// hc's own origin is always accepted. Pass `*` to accept any origin.
+    /// The conductor must be restarted to use them and app interfaces
+    /// attached before keep the origins they had.
+    /// For example `hc config set-origins 0 --allowed-origins http://localhost:3000`.
+    SetOrigins {
+        #[structopt(flatten)]
+        existing: Existing,
+        #[structopt(long, value_delimiter = ",", required = true)]
+        /// The origins to accept, or `*` for any origin.
+        allowed_origins: Vec<String>,
+    },
+    Show(ShowConfig),
+    Check(crate::config_check::CheckConfig),
+    /// Print the defaults `hc` uses with where each one came from:
+    /// a flag, an environment variable, the `hc.toml`,
+    /// the user config at `~/.config/hc/config.toml` or built-in.
+    ///
+    /// For example `hc -h ./holochain config defaults`.
+    Defaults,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+/// Print the conductor config of a setup.
+///
+/// The config is parsed and printed as YAML, or as JSON with `--json`.
+/// For example `hc config show -i 0 --diff-default`.
+pub struct ShowConfig {
+    #[structopt(short, long)]
+    /// The index of the setup in `hc list`.
+    /// Can be left out if there is only one setup.
+    pub index: Option<usize>,
+    #[structopt(long, conflicts_with_all = &["json", "diff-default"])]
+    /// Print the file as it is without parsing it.
+    pub raw: bool,
+    #[structopt(long)]
+    /// Print the config as JSON.
+    pub json: bool,
+    #[structopt(long, conflicts_with = "json")]
+    /// Only print the fields that are different from
+    /// the config hc generates for a new setup.
+    pub diff_default: bool,
+}
+
+/// A field of a config that is different from the default config.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigDiff {
+    /// The path of the field, like `network.bootstrap_service`.
+    pub field: String,
+    /// The value in the default config, or `None` if it isn't there.
+    pub default: Option<serde_json::Value>,
+    /// The value in the config, or `None` if it isn't there.
+    pub value: Option<serde_json::Value>,
+}
+
+/// Apply the overrides file or set the allowed origins
+/// of each of the chosen setups, or show or check a config.
+/// The allowed origins always include hc's own `origin`.
+/// This is what `hc config` does.
+pub fn reconfigure(reconfigure: Reconfigure, origin: &str) -> anyhow::Result<()> {
+    match reconfigure {
+        Reconfigure {
+            cmd:
+                Some(ConfigCmd::SetOrigins {
+                    existing,
+                    allowed_origins,
+                }),
+            ..
+        } => {
+            let allowed = crate::ports::allowed_origins(origin, allowed_origins);
+            for path in existing.load()? {
+                crate::ports::use_allowed_origins(path.clone(), allowed.clone())?;
+                msg!(
+                    "Updated the allowed origins of {}. \
+                    Restart its conductor for them to take effect",
+                    path.display()
+                );
+            }
+        }
+        Reconfigure {
+            existing,
+            config_overrides: Some(config_overrides),
+            ..
+        } => {
+            let spec = ConductorConfigSpec::from_file(&config_overrides)?;
+            for path in existing.load()? {
+                apply_overrides(path.clone(), spec.clone())?;
+                msg!("Updated the conductor config of {}", path.display());
+            }
+        }
+        Reconfigure {
+            cmd: Some(ConfigCmd::Show(show)),
+            ..
+        } => show_config(std::env::current_dir()?, show)?,
+        Reconfigure {
+            cmd: Some(ConfigCmd::Check(check)),
+            ..
+        } => crate::config_check::check(std::env::current_dir()?, check)?,
+        // The `hc` binary prints these itself with the flags it was passed.
+        Reconfigure {
+            cmd: Some(ConfigCmd::Defaults),
+            ..
+        } => {
+            let project = crate::project::ProjectConfig::discover(&std::env::current_dir()?)?;
+            let defaults = crate::defaults::Defaults::load(&project, Default::default())?;
+            data!("{}", defaults);
+        }
+        Reconfigure { .. } => bail!(
+            "Pass --config-overrides with a file of fields to set \
+            or use `hc config set-origins`"
+        ),
+    }
+    Ok(())
+}
+
+/// Print the conductor config of a setup from the `.hc` file in `hc_dir`.
+/// This is what `hc config show` does.
+pub fn show_config(hc_dir: PathBuf, cmd: ShowConfig) -> anyhow::Result<()> {
+    let ShowConfig {
+        index,
+        raw,
+        json,
+        diff_default,
+    } = cmd;
+    let (index, setup) = crate::save::choose_setup(hc_dir, index, "show the config of")?;
+    let path = setup.join(CONDUCTOR_CONFIG);
+    msg!("Conductor config of setup {} in {}", index, path.display());
+    if raw {
+        let yaml = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        data!("{}", yaml.trim_end());
+        return Ok(());
+    }
+    let config = parse_config(&path)?;
+    if !diff_default {
+        if json {
+            data!("{}", serde_json::to_string_pretty(&config)?);
+        } else {
+            data!("{}", serde_yaml::to_string(&config)?.trim_end());
+        }
+        return Ok(());
+    }
+    let diffs = diff_configs(&create_config(setup), &config)?;
+    if diffs.is_empty() {
+        msg!("The config is the same as the config of a new setup");
+    }
+    let red = ansi_term::Color::Red;
+    let green = ansi_term::Color::Green;
+    for diff in diffs {
+        if let Some(default) = &diff.default {
+            data!("{}", red.paint(format!("- {}: {}", diff.field, default)));
+        }
+        if let Some(value) = &diff.value {
+            data!("{}", green.paint(format!("+ {}: {}", diff.field, value)));
+        }
+    }
+    Ok(())
+}
+
+/// The fields that are different between the two configs, in order of
+/// their path. Objects are compared field by field and anything else,
+/// like a list of interfaces, is compared as a whole.
+pub fn diff_configs(
+    default: &ConductorConfig,
+    config: &ConductorConfig,
+) -> anyhow::Result<Vec<ConfigDiff>> {
+    let mut default_fields = std::collections::BTreeMap::new();
+    flatten_fields(
+        String::new(),
+        serde_json::to_value(default)?,
+        &mut default_fields,
+    );
+    let mut fields = std::collections::BTreeMap::new();
+    flatten_fields(String::new(), serde_json::to_value(config)?, &mut fields);
+    let mut names: Vec<_> = default_fields
+        .keys()
+        .chain(fields.keys())
+        .cloned()
+        .collect();
+    names.sort();
+    names.dedup();
+    Ok(names
+        .into_iter()
+        .filter_map(|field| {
+            let default = default_fields.remove(&field);
+            let value = fields.remove(&field);
+            if default == value {
+                None
+            } else {
+                Some(ConfigDiff {
+                    field,
+                    default,
+                    value,
+                })
+            }
+        })
+        .collect())
+}
+
+fn flatten_fields(
+    prefix: String,
+    value: serde_json::Value,
+    fields: &mut std::collections::BTreeMap<String, serde_json::Value>,
+) {
+    match value {
+        serde_json::Value::Object(object) if !object.is_empty() => {
+            for (name, value) in object {
+                let field = if prefix.is_empty() {
+                    name
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                flatten_fields(field, value, fields);
+            }
+        }
+        // Unset fields are the same as missing ones.
+        serde_json::Value::Null => (),
+        value => {
+            fields.insert(prefix, value);
+        }
+    }
+}
+
+/// Apply the spec to the config of the setup at `path` on top of the
+/// overrides applied to it before, and keep them all for next time.
+/// A setup without a config gets a new one from [`create_config`].
+/// Fields no spec has set are left as they are.
+pub fn apply_overrides(
+    path: PathBuf,
+    spec: ConductorConfigSpec,
+) -> anyhow::Result<ConductorConfig> {
+    let spec = read_overrides(path.clone())?.merge(spec);
+    let mut config = match read_config(path.clone())? {
+        Some(config) => config,
+        None => create_config(path.clone()),
+    };
+    spec.apply(&mut config);
+    write_config(path.clone(), &config);
+    write_overrides(&path, &spec)?;
+    Ok(config)
+}
+
+/// Keep the overrides of the setup at `path` for next time.
+pub fn write_overrides(path: &Path, spec: &ConductorConfigSpec) -> anyhow::Result<()> {
+    let overrides = path.join(CONFIG_OVERRIDES);
+    std::fs::write(&overrides, serde_yaml::to_string(spec)?)
+        .with_context(|| format!("Failed to write {}", overrides.display()))
+}
+
+/// The overrides applied to the setup at `path` so far.
+/// A setup without any has an empty spec.
+pub fn read_overrides(path: PathBuf) -> anyhow::Result<ConductorConfigSpec> {
+    let path = path.join(CONFIG_OVERRIDES);
+    if !path.exists() {
+        return Ok(ConductorConfigSpec::default());
+    }
+    ConductorConfigSpec::from_file(&path)
+}
+
 /// Create a new default [`ConductorConfig`] with environment path
 /// and keystore all in the same directory.
+/// The admin interface only allows connections from the
+/// [`DEFAULT_ORIGIN`](crate::DEFAULT_ORIGIN) hc sends.
+/// See [`ConductorConfigBuilder`] to change it before it is written.
 pub fn create_config(environment_path: PathBuf) -> ConductorConfig {
-    let mut conductor_config = ConductorConfig::default();
-    conductor_config.environment_path = environment_path.clone().into();
-    let mut keystore_path = environment_path;
-    keystore_path.push("keystore");
-    conductor_config.keystore_path = Some(keystore_path);
-    conductor_config
+    ConductorConfigBuilder::new(environment_path)
+        .config()
+        .clone()
 }
 
 /// Write [`ConductorConfig`] to [`CONDUCTOR_CONFIG`]
@@ -24,12 +859,148 @@ pub fn write_config(mut path: PathBuf, config: &ConductorConfig) -> PathBuf {
     path
 }
 
+/// Turn on zome call metering in the config of the setup at `path`.
+/// This is what `hc generate --zome-call-metered` does.
+pub fn use_zome_call_metering(path: PathBuf) -> anyhow::Result<()> {
+    let mut config = read_config(path.clone())?
+        .ok_or_else(|| anyhow::anyhow!("Failed to find config in {}", path.display()))?;
+    config.zome_call_metering = true;
+    write_config(path, &config);
+    Ok(())
+}
+
+/// Make the admin and app interfaces of the setup at `path`
+/// listen on this address. This is what `hc generate --bind` does.
+pub fn use_interface_address(path: PathBuf, address: IpAddr) -> anyhow::Result<()> {
+    let mut config = read_config(path.clone())?
+        .ok_or_else(|| anyhow::anyhow!("Failed to find config in {}", path.display()))?;
+    config.interface_address = Some(address);
+    write_config(path, &config);
+    Ok(())
+}
+
+/// Keep the databases of the setup at `path` in another directory.
+/// This is what `hc generate --db-dir` does.
+pub fn use_environment_path(path: PathBuf, environment_path: PathBuf) -> anyhow::Result<()> {
+    let mut config = read_config(path.clone())?
+        .ok_or_else(|| anyhow::anyhow!("Failed to find config in {}", path.display()))?;
+    config.environment_path = environment_path.into();
+    write_config(path, &config);
+    Ok(())
+}
+
+/// The directory the conductor of the setup at `setup_path`
+/// keeps its databases in. A relative environment path,
+/// like one from `hc gen --env-dir`, is relative to the setup.
+pub fn environment_path(setup_path: &Path, config: &ConductorConfig) -> PathBuf {
+    let path: PathBuf = config.environment_path.clone().into();
+    if path.is_relative() {
+        setup_path.join(path)
+    } else {
+        path
+    }
+}
+
+/// Rewrite the absolute paths in the config and the saved overrides
+/// of a setup that moved from `from` to `to`, which is where it is now.
+/// Paths outside the setup, like a shared agent keystore,
+/// and relative paths are left as they are.
+/// This is what `hc relocate` does after moving the directory.
+pub fn relocate_setup(from: &Path, to: &Path) -> anyhow::Result<()> {
+    let mut config = read_config(to.to_path_buf())?
+        .ok_or_else(|| anyhow::anyhow!("Failed to find config in {}", to.display()))?;
+    relocate_config(&mut config, from, to);
+    write_config(to.to_path_buf(), &config);
+    if to.join(CONFIG_OVERRIDES).exists() {
+        let mut spec = read_overrides(to.to_path_buf())?;
+        relocate_spec(&mut spec, from, to);
+        write_overrides(to, &spec)?;
+    }
+    Ok(())
+}
+
+/// Rewrite the paths in the config that are in `from` to be in `to`.
+pub fn relocate_config(config: &mut ConductorConfig, from: &Path, to: &Path) {
+    let mut environment_path: PathBuf = config.environment_path.clone().into();
+    relocate_path(&mut environment_path, from, to);
+    config.environment_path = environment_path.into();
+    if let Some(path) = &mut config.keystore_path {
+        relocate_path(path, from, to);
+    }
+    if let Some(interfaces) = &mut config.admin_interfaces {
+        relocate_interfaces(interfaces, from, to);
+    }
+    if let Some(passphrase_service) = &mut config.passphrase_service {
+        relocate_passphrase_service(passphrase_service, from, to);
+    }
+}
+
+/// Rewrite the paths in the spec that are in `from` to be in `to`.
+pub fn relocate_spec(spec: &mut ConductorConfigSpec, from: &Path, to: &Path) {
+    if let Some(path) = &mut spec.keystore_path {
+        relocate_path(path, from, to);
+    }
+    if let Some(interfaces) = &mut spec.admin_interfaces {
+        relocate_interfaces(interfaces, from, to);
+    }
+    if let Some(passphrase_service) = &mut spec.passphrase_service {
+        relocate_passphrase_service(passphrase_service, from, to);
+    }
+}
+
+fn relocate_interfaces(interfaces: &mut [AdminInterfaceConfig], from: &Path, to: &Path) {
+    for interface in interfaces {
+        if let InterfaceDriver::UnixSocket { path } = &mut interface.driver {
+            relocate_path(path, from, to);
+        }
+    }
+}
+
+fn relocate_passphrase_service(service: &mut PassphraseServiceConfig, from: &Path, to: &Path) {
+    if let PassphraseServiceConfig::UnixSocket { path } = service {
+        relocate_path(path, from, to);
+    }
+}
+
+/// Move `path` to be in `to` if it is in `from`.
+pub fn relocate_path(path: &mut PathBuf, from: &Path, to: &Path) {
+    if let Ok(rest) = path.strip_prefix(from) {
+        // Joining an empty path would add a trailing slash.
+        *path = if rest.as_os_str().is_empty() {
+            to.to_path_buf()
+        } else {
+            to.join(rest)
+        };
+    }
+}
+
 /// Read the [`ConductorConfig`] from the file [`CONDUCTOR_CONFIG`] in the provided path.
 pub fn read_config(mut path: PathBuf) -> anyhow::Result<Option<ConductorConfig>> {
     path.push(CONDUCTOR_CONFIG);
 
-    match std::fs::read_to_string(path) {
-        Ok(yaml) => Ok(Some(serde_yaml::from_str(&yaml)?)),
+    match std::fs::read_to_string(&path) {
+        Ok(yaml) => Ok(Some(parse_yaml(&path, &yaml)?)),
         Err(_) => Ok(None),
     }
 }
+
+/// Read and parse the conductor config file at `path`.
+/// The error names the file and where in it the YAML is wrong.
+pub fn parse_config(path: &Path) -> anyhow::Result<ConductorConfig> {
+    let yaml = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_yaml(path, &yaml)
+}
+
+pub(crate) fn parse_yaml(path: &Path, yaml: &str) -> anyhow::Result<ConductorConfig> {
+    serde_yaml::from_str(yaml).map_err(|e| match e.location() {
+        Some(location) => anyhow::anyhow!(
+            "Failed to parse {}:{}:{}: {}",
+            path.display(),
+            location.line(),
+            location.column(),
+            e
+        ),
+        None => anyhow::anyhow!("Failed to parse {}: {}", path.display(), e),
+    })
+}
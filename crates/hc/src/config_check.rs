@@ -0,0 +1,327 @@
+//! Check conductor configs before their conductors are run.
+//!
+//! The conductor only reads its config when it starts, so a config
+//! that was edited by hand and is broken shows up as a crash with a
+//! serde error. [`check_config`] parses the config with the conductor's
+//! own types and checks what hc can know without running it: the
+//! directories in it exist, its admin interfaces are usable and their
+//! ports and sockets, and the ports of its app interfaces,
+//! don't collide with the other setups in the `.hc` file.
+//!
+//! `hc config check` prints the problems it finds and `hc run` refuses
+//! to start conductors whose configs have any unless it is passed
+//! `--skip-validation`.
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::ensure;
+use holochain_conductor_api::config::conductor::ConductorConfig;
+use holochain_conductor_api::InterfaceDriver;
+use structopt::StructOpt;
+
+use crate::config::read_config;
+use crate::config::CONDUCTOR_CONFIG;
+use crate::ports::admin_interfaces_in_config;
+use crate::ports::AdminInterface;
+use crate::AdminAddress;
+
+#[derive(Debug, StructOpt, Clone)]
+/// Check conductor configs for problems that would stop
+/// their conductors from starting or hc from using them.
+///
+/// Checks the config at the path, the config of the setup
+/// with the index or else the configs of all the setups in `$(pwd)/.hc`.
+/// Fails if there are any problems.
+/// For example `hc config check -i 0`.
+pub struct CheckConfig {
+    #[structopt(conflicts_with = "index")]
+    /// A conductor config file or a setup directory.
+    pub path: Option<PathBuf>,
+    #[structopt(short, long)]
+    /// The index of the setup in `hc list`.
+    pub index: Option<usize>,
+}
+
+/// The problems found in one conductor config.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigProblems {
+    /// The config file.
+    pub path: PathBuf,
+    /// What is wrong with it.
+    pub problems: Vec<String>,
+}
+
+impl std::fmt::Display for ConfigProblems {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:", self.path.display())?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the config file at `config_path`, which is in its setup's directory.
+/// The admin and app interfaces mustn't collide with the ones of the `other_setups`.
+/// A config that can't be read or parsed only has that problem.
+pub fn check_config(config_path: &Path, other_setups: &[PathBuf]) -> Vec<String> {
+    let yaml = match std::fs::read_to_string(config_path) {
+        Ok(yaml) => yaml,
+        Err(e) => return vec![format!("Failed to read {}: {}", config_path.display(), e)],
+    };
+    let config = match crate::config::parse_yaml(config_path, &yaml) {
+        Ok(config) => config,
+        Err(e) => return vec![e.to_string()],
+    };
+    let setup = config_path.parent().unwrap_or_else(|| Path::new("."));
+    let mut problems = unknown_fields(&yaml);
+    check_dir(
+        &mut problems,
+        "environment path",
+        &crate::config::environment_path(setup, &config),
+    );
+    match &config.keystore_path {
+        Some(keystore) if !config.use_dangerous_test_keystore => {
+            check_dir(&mut problems, "keystore path", &setup.join(keystore))
+        }
+        _ => (),
+    }
+    check_admin_interfaces(&mut problems, setup, &config);
+    check_app_interfaces(&mut problems, &config);
+
+    let interfaces = admin_interfaces_in_config(&config);
+    let app_ports = app_ports_in_config(&config);
+    for other in other_setups {
+        if same_path(other, setup) {
+            continue;
+        }
+        // Setups that can't be read are problems of their own config.
+        let other_config = match read_config(other.clone()) {
+            Ok(Some(other_config)) => other_config,
+            _ => continue,
+        };
+        for theirs in admin_interfaces_in_config(&other_config) {
+            for ours in &interfaces {
+                if collide(setup, ours, other, &theirs) {
+                    problems.push(format!(
+                        "Admin {} is also used by setup {}",
+                        ours.address,
+                        other.display()
+                    ));
+                }
+            }
+        }
+        for port in app_ports_in_config(&other_config) {
+            if app_ports.contains(&port) {
+                problems.push(format!(
+                    "App port {} is also used by setup {}",
+                    port,
+                    other.display()
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// Check the configs of the setups that are about to be run.
+/// Setups without a config are skipped because one is created for them.
+/// It is an error listing the problems if there are any.
+/// This is what `hc run` does unless it is passed `--skip-validation`.
+pub fn check_setups(setups: &[PathBuf], hc_setups: &[PathBuf]) -> anyhow::Result<()> {
+    let found: Vec<_> = setups
+        .iter()
+        .map(|setup| setup.join(CONDUCTOR_CONFIG))
+        .filter(|path| path.exists())
+        .filter_map(|path| problems_of(path, hc_setups))
+        .collect();
+    ensure!(
+        found.is_empty(),
+        "{}\nFix the conductor configs or run with --skip-validation",
+        found
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    Ok(())
+}
+
+/// Print the problems with the configs chosen by the command
+/// using the `.hc` file in `hc_dir`.
+/// This is what `hc config check` does.
+pub fn check(hc_dir: PathBuf, cmd: CheckConfig) -> anyhow::Result<()> {
+    let hc_setups = crate::save::load(hc_dir.clone())?;
+    let configs = match (cmd.path, cmd.index) {
+        (Some(path), _) if path.is_dir() => vec![path.join(CONDUCTOR_CONFIG)],
+        (Some(path), _) => vec![path],
+        (None, Some(index)) => {
+            let (_, setup) = crate::save::choose_setup(hc_dir, Some(index), "check the config of")?;
+            vec![setup.join(CONDUCTOR_CONFIG)]
+        }
+        (None, None) => hc_setups.iter().map(|s| s.join(CONDUCTOR_CONFIG)).collect(),
+    };
+    ensure!(
+        !configs.is_empty(),
+        "There are no setups to check. Pass the path of a conductor config"
+    );
+    let mut total = 0;
+    for path in configs {
+        match problems_of(path.clone(), &hc_setups) {
+            Some(found) => {
+                total += found.problems.len();
+                data!("{}", found);
+            }
+            None => msg!("{} is valid", path.display()),
+        }
+    }
+    if total > 0 {
+        bail!("Found {} problems in the conductor configs", total);
+    }
+    Ok(())
+}
+
+fn problems_of(path: PathBuf, hc_setups: &[PathBuf]) -> Option<ConfigProblems> {
+    let problems = check_config(&path, hc_setups);
+    if problems.is_empty() {
+        None
+    } else {
+        Some(ConfigProblems { path, problems })
+    }
+}
+
+/// The top level fields the conductor doesn't know.
+/// It ignores them so a misspelled field is silently left out.
+fn unknown_fields(yaml: &str) -> Vec<String> {
+    let known = match serde_json::to_value(ConductorConfig::default()) {
+        Ok(serde_json::Value::Object(known)) => known,
+        _ => return Vec::new(),
+    };
+    match serde_yaml::from_str(yaml) {
+        Ok(serde_yaml::Value::Mapping(fields)) => fields
+            .into_iter()
+            .filter_map(|(field, _)| field.as_str().map(str::to_string))
+            .filter(|field| !known.contains_key(field))
+            .map(|field| format!("Unknown field {} is ignored by the conductor", field))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn check_dir(problems: &mut Vec<String>, name: &str, dir: &Path) {
+    if !dir.exists() {
+        problems.push(format!("The {} {} doesn't exist", name, dir.display()));
+    } else if !dir.is_dir() {
+        problems.push(format!("The {} {} is not a directory", name, dir.display()));
+    }
+}
+
+fn check_admin_interfaces(problems: &mut Vec<String>, setup: &Path, config: &ConductorConfig) {
+    let configs = config.admin_interfaces.as_deref().unwrap_or_default();
+    if configs.is_empty() {
+        problems.push("There are no admin_interfaces so hc can't connect to the conductor".into());
+    }
+    for (i, interface) in configs.iter().enumerate() {
+        if let InterfaceDriver::UnixSocket { path } = &interface.driver {
+            if cfg!(not(unix)) {
+                problems.push(format!(
+                    "Admin interface {} is a unix socket which this platform doesn't support",
+                    i
+                ));
+            }
+            match setup.join(path).parent() {
+                Some(dir) if !dir.is_dir() => problems.push(format!(
+                    "The directory {} of admin socket {} doesn't exist",
+                    dir.display(),
+                    path.display()
+                )),
+                _ => (),
+            }
+        }
+        if matches!(&interface.allowed_origins, Some(origins) if origins.is_empty()) {
+            problems.push(format!(
+                "Admin interface {} has no allowed_origins so nothing can connect to it",
+                i
+            ));
+        }
+    }
+    let interfaces = admin_interfaces_in_config(config);
+    for (i, ours) in interfaces.iter().enumerate() {
+        for (j, theirs) in interfaces.iter().enumerate().take(i) {
+            if collide(setup, ours, setup, theirs) {
+                problems.push(format!(
+                    "Admin interfaces {} and {} both use {}",
+                    j, i, ours.address
+                ));
+            }
+        }
+    }
+}
+
+fn check_app_interfaces(problems: &mut Vec<String>, config: &ConductorConfig) {
+    let ports = app_ports_in_config(config);
+    for (i, port) in ports.iter().enumerate() {
+        if let Some(j) = ports[..i].iter().position(|p| p == port) {
+            problems.push(format!(
+                "App interfaces {} and {} both use port {}",
+                j, i, port
+            ));
+        }
+        let admin = config
+            .admin_interfaces
+            .iter()
+            .flatten()
+            .any(|a| a.driver == InterfaceDriver::Websocket { port: *port });
+        if admin {
+            problems.push(format!("App port {} is also an admin port", port));
+        }
+    }
+}
+
+/// The ports of the app interfaces the conductor starts with.
+/// Port `0` is a free port chosen when the conductor starts.
+fn app_ports_in_config(config: &ConductorConfig) -> Vec<u16> {
+    config
+        .app_interfaces
+        .iter()
+        .flatten()
+        .map(|i| i.port)
+        .filter(|port| *port != 0)
+        .collect()
+}
+
+/// Can't both of these interfaces listen at once.
+/// Port `0` is a free port chosen when the conductor starts.
+fn collide(
+    our_setup: &Path,
+    ours: &AdminInterface,
+    their_setup: &Path,
+    theirs: &AdminInterface,
+) -> bool {
+    match (&ours.address, &theirs.address) {
+        (AdminAddress::Port(0), _) | (_, AdminAddress::Port(0)) => false,
+        (AdminAddress::Port(a), AdminAddress::Port(b)) => {
+            a == b && same_host(ours.host, theirs.host)
+        }
+        (AdminAddress::Socket(a), AdminAddress::Socket(b)) => {
+            let (a, b) = (our_setup.join(a), their_setup.join(b));
+            same_path(&a, &b)
+        }
+        _ => false,
+    }
+}
+
+/// Do the addresses overlap. Interfaces without an address
+/// listen on the loopback and the unspecified address is all of them.
+fn same_host(a: Option<IpAddr>, b: Option<IpAddr>) -> bool {
+    let loopback = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let (a, b) = (a.unwrap_or(loopback), b.unwrap_or(loopback));
+    a == b || a.is_unspecified() || b.is_unspecified()
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    a == b || matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
@@ -0,0 +1,157 @@
+//! Show how much of the DHT each agent holds.
+//!
+//! Every agent claims an arc of DHT locations around its own
+//! location and is responsible for storing the data in it.
+//! [`write_coverage`] prints a bar of the whole DHT for each agent
+//! with the locations it holds filled in, so locations that
+//! nobody holds stand out.
+use std::fmt::Write;
+use std::ops::Bound;
+
+use holochain_conductor_api::AgentArc;
+use holochain_p2p::dht_arc::DhtArc;
+
+/// The number of locations on the DHT.
+const DHT_SIZE: u64 = 1 << 32;
+
+/// The default number of characters in a coverage bar.
+pub const DEFAULT_BAR_WIDTH: usize = 64;
+
+/// An [`AgentArc`] as printed by `hc call coverage --json`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ArcCoverage {
+    /// The agent that holds the arc.
+    pub agent: String,
+    /// The location at the center of the arc.
+    pub center: u32,
+    /// The first location the arc holds or `None` if it holds nothing.
+    pub first: Option<u32>,
+    /// The last location the arc holds or `None` if it holds nothing.
+    /// This is before the first if the arc wraps around the end of the DHT.
+    pub last: Option<u32>,
+    /// The fraction of the DHT the arc holds, from `0.0` to `1.0`.
+    pub coverage: f64,
+}
+
+/// The arcs of every agent of a dna as printed by `hc call coverage --json`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct CoverageReport {
+    /// Each agent's arc.
+    pub arcs: Vec<ArcCoverage>,
+    /// The fraction of the DHT held by at least one agent.
+    pub total: f64,
+}
+
+impl CoverageReport {
+    /// Work out the coverage of each arc and all of them together.
+    pub fn new(arcs: &[AgentArc]) -> Self {
+        Self {
+            arcs: arcs
+                .iter()
+                .map(|a| {
+                    let bounds = arc_bounds(&a.arc);
+                    ArcCoverage {
+                        agent: a.agent.to_string(),
+                        center: a.arc.center_loc.into(),
+                        first: bounds.map(|b| b.0),
+                        last: bounds.map(|b| b.1),
+                        coverage: a.coverage(),
+                    }
+                })
+                .collect(),
+            total: total_coverage(arcs),
+        }
+    }
+}
+
+/// The first and last DHT location the arc holds,
+/// or `None` if it holds nothing.
+/// The arc wraps around the end of the DHT if the first is after the last.
+pub fn arc_bounds(arc: &DhtArc) -> Option<(u32, u32)> {
+    let range = arc.range();
+    match (range.start, range.end) {
+        (Bound::Included(first), Bound::Included(last)) => Some((first, last)),
+        _ => None,
+    }
+}
+
+/// The fraction of the DHT held by at least one of the arcs,
+/// from `0.0` to `1.0`.
+pub fn total_coverage(arcs: &[AgentArc]) -> f64 {
+    let mut ranges: Vec<_> = arcs.iter().flat_map(|a| arc_ranges(&a.arc)).collect();
+    ranges.sort_unstable();
+    let mut covered = 0;
+    let mut covered_to = 0;
+    for (start, end) in ranges {
+        let start = start.max(covered_to);
+        if end > start {
+            covered += end - start;
+            covered_to = end;
+        }
+    }
+    covered as f64 / DHT_SIZE as f64
+}
+
+/// A bar of `width` characters for the whole DHT starting from location 0.
+/// A character is `#` if the arc holds all of its part of the DHT,
+/// `+` if the arc holds some of it and `.` if it holds none of it.
+pub fn coverage_bar(arc: &DhtArc, width: usize) -> String {
+    let ranges = arc_ranges(arc);
+    let width = width as u64;
+    (0..width)
+        .map(|i| {
+            let start = i * DHT_SIZE / width;
+            let end = (i + 1) * DHT_SIZE / width;
+            let held: u64 = ranges
+                .iter()
+                .map(|r| r.1.min(end).saturating_sub(r.0.max(start)))
+                .sum();
+            if held == 0 {
+                '.'
+            } else if held == end - start {
+                '#'
+            } else {
+                '+'
+            }
+        })
+        .collect()
+}
+
+/// Print each agent with a coverage bar, the percentage of the DHT
+/// it holds and its arc bounds, followed by the percentage of the DHT
+/// held by at least one agent.
+pub fn write_coverage(out: &mut String, arcs: &[AgentArc], width: usize) -> anyhow::Result<()> {
+    if arcs.is_empty() {
+        writeln!(out, "No agents found for this dna")?;
+        return Ok(());
+    }
+    for a in arcs {
+        let bounds = match arc_bounds(&a.arc) {
+            Some((first, last)) => format!("{:#010x}..={:#010x}", first, last),
+            None => "holds nothing".to_string(),
+        };
+        writeln!(out, "{}", a.agent)?;
+        writeln!(
+            out,
+            "  [{}] {:>6.2}% {}",
+            coverage_bar(&a.arc, width),
+            a.coverage() * 100.0,
+            bounds
+        )?;
+    }
+    writeln!(
+        out,
+        "Held by at least one agent: {:.2}%",
+        total_coverage(arcs) * 100.0
+    )?;
+    Ok(())
+}
+
+/// The arc as half open ranges of locations that don't wrap.
+fn arc_ranges(arc: &DhtArc) -> Vec<(u64, u64)> {
+    match arc_bounds(arc) {
+        None => Vec::new(),
+        Some((first, last)) if first <= last => vec![(first as u64, last as u64 + 1)],
+        Some((first, last)) => vec![(0, last as u64 + 1), (first as u64, DHT_SIZE)],
+    }
+}
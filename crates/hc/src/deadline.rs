@@ -0,0 +1,68 @@
+//! A wall clock deadline for a whole `hc` invocation.
+//!
+//! `hc --deadline <secs>` stops the command when the deadline passes
+//! instead of letting a conductor that never responds hang a CI job.
+//! The conductors the command started are killed as it stops
+//! and `hc` exits with [`DEADLINE_EXIT_CODE`] so a timeout can be
+//! told apart from other failures, which exit with `1`.
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::ensure;
+
+/// The exit code of `hc` when the deadline passes.
+/// This is the same code the `timeout` command uses.
+pub const DEADLINE_EXIT_CODE: i32 = 124;
+
+/// The command was still running when its deadline passed.
+#[derive(Debug, Clone)]
+pub struct DeadlineExceeded {
+    /// How long the command was given.
+    pub deadline: Duration,
+}
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Timed out: the command didn't finish within the --deadline of {}s. \
+            The conductors it started were stopped",
+            self.deadline.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+/// Parse the seconds of a `--deadline`.
+pub fn parse_deadline(secs: &str) -> anyhow::Result<Duration> {
+    let secs: u64 = secs.parse()?;
+    ensure!(secs > 0, "The deadline must be at least 1 second");
+    Ok(Duration::from_secs(secs))
+}
+
+/// Run `f` until it finishes or the `deadline` passes.
+/// `f` is dropped when the deadline passes so anything it owns,
+/// like a running conductor, is stopped and the result is
+/// a [`DeadlineExceeded`] error.
+pub async fn with_deadline<T>(
+    deadline: Option<Duration>,
+    f: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<T> {
+    match deadline {
+        Some(deadline) => match tokio::time::timeout(deadline, f).await {
+            Ok(result) => result,
+            Err(_) => Err(DeadlineExceeded { deadline }.into()),
+        },
+        None => f.await,
+    }
+}
+
+/// The code `hc` exits with for this error.
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    if error.is::<DeadlineExceeded>() {
+        DEADLINE_EXIT_CODE
+    } else {
+        1
+    }
+}
@@ -0,0 +1,399 @@
+//! User level defaults for `hc` commands and where each one came from.
+//!
+//! The options that are the same for every project, like the path to
+//! the holochain binary, can be set in a user config file at
+//! `~/.config/hc/config.toml` (or `$XDG_CONFIG_HOME/hc/config.toml`).
+//! The `HC_USER_CONFIG` environment variable points at another file.
+//!
+//! Each default is taken from the first of these that sets it:
+//! CLI flags > environment variables > `hc.toml` > user config > built-in defaults.
+//! `generate`, `run` and `call` all use the same [`Defaults`]
+//! and `hc config defaults` prints them with their [`Source`]s.
+//!
+//! Example `config.toml`:
+//! ```toml
+//! # Relative paths are relative to this file and `~` is the home directory.
+//! holochain_path = "~/holochain/target/release/holochain"
+//! root = "~/chains"
+//!
+//! [network]
+//! transport = "quic"
+//!
+//! [conductor_log]
+//! filter = "warn"
+//! format = "json"
+//! ```
+//! A user config that can't be read or parsed is only a warning
+//! and the built-in defaults are used instead.
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::cmds::Network;
+use crate::logs::ConductorLog;
+use crate::logs::LogFormat;
+use crate::project::ProjectConfig;
+use crate::project::ProjectNetwork;
+use crate::project::ProjectTransport;
+
+/// Set this environment variable to use another user config file.
+pub const USER_CONFIG_ENV: &str = "HC_USER_CONFIG";
+
+/// The path to the holochain binary.
+/// This is the same as `hc --holochain-path`.
+pub const HOLOCHAIN_PATH_ENV: &str = "HC_HOLOCHAIN_PATH";
+
+/// The root directory for new setups.
+/// This is the same as `hc gen --root`.
+pub const ROOT_ENV: &str = "HC_ROOT";
+
+/// The network transport for new setups, `mem` or `quic`.
+pub const NETWORK_ENV: &str = "HC_NETWORK";
+
+/// The bootstrap service for the network set with [`NETWORK_ENV`].
+pub const BOOTSTRAP_ENV: &str = "HC_BOOTSTRAP";
+
+/// The conductors' log filter.
+/// This is the same as `hc run --conductor-log`.
+pub const CONDUCTOR_LOG_ENV: &str = "HC_CONDUCTOR_LOG";
+
+/// How the conductors write their logs.
+/// This is the same as `hc run --conductor-log-format`.
+pub const CONDUCTOR_LOG_FORMAT_ENV: &str = "HC_CONDUCTOR_LOG_FORMAT";
+
+/// Defaults for `hc` commands loaded from the user config file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    /// Default path to the holochain binary.
+    pub holochain_path: Option<PathBuf>,
+    /// Default root directory for new setups.
+    pub root: Option<PathBuf>,
+    /// Default network for new setups.
+    pub network: Option<ProjectNetwork>,
+    /// Default log settings for the conductors.
+    #[serde(default)]
+    pub conductor_log: ConductorLog,
+    /// The file these were loaded from.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+/// The flags that were passed for the defaults.
+/// Flags that weren't passed are `None`.
+#[derive(Debug, Default, Clone)]
+pub struct DefaultFlags {
+    /// `hc --holochain-path`.
+    pub holochain_path: Option<PathBuf>,
+    /// `hc gen --root`.
+    pub root: Option<PathBuf>,
+    /// `hc run --conductor-log` and `--conductor-log-format`.
+    pub conductor_log: ConductorLog,
+}
+
+/// Where a default came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Nothing set it.
+    BuiltIn,
+    /// The `hc.toml` or user config at this path.
+    File(PathBuf),
+    /// This environment variable.
+    Env(&'static str),
+    /// A CLI flag.
+    Flag,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BuiltIn => write!(f, "built-in"),
+            Self::File(path) => write!(f, "file {}", path.display()),
+            Self::Env(var) => write!(f, "env {}", var),
+            Self::Flag => write!(f, "flag"),
+        }
+    }
+}
+
+/// A default and where it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sourced<T> {
+    /// The value that is used.
+    pub value: T,
+    /// Where it came from.
+    pub source: Source,
+}
+
+/// The defaults `generate`, `run` and `call` use after
+/// the flags, environment and config files are merged.
+#[derive(Debug, Clone)]
+pub struct Defaults {
+    /// The holochain binary.
+    pub holochain_path: Sourced<PathBuf>,
+    /// The root directory for new setups.
+    /// New setups go in a temp directory if it is `None`.
+    pub root: Sourced<Option<PathBuf>>,
+    /// The network for new setups.
+    /// `hc gen network` wins over it.
+    pub network: Sourced<Option<ProjectNetwork>>,
+    /// The conductors' log filter.
+    pub conductor_log_filter: Sourced<Option<String>>,
+    /// How the conductors write their logs.
+    pub conductor_log_format: Sourced<Option<LogFormat>>,
+}
+
+impl UserConfig {
+    /// Where the user config file is.
+    /// This is [`USER_CONFIG_ENV`] if it is set or else `hc/config.toml`
+    /// in `$XDG_CONFIG_HOME` or `~/.config`.
+    /// `None` if there is no home directory.
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(USER_CONFIG_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| home_dir().map(|home| home.join(".config")))?;
+        Some(config_dir.join("hc").join("config.toml"))
+    }
+
+    /// Load a user config from a path.
+    /// Relative paths in the file are resolved from the file's directory.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let toml = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let mut config: Self = toml::from_str(&toml)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        config.holochain_path = config
+            .holochain_path
+            .map(|p| crate::project::resolve_holochain_path(base, expand_home(p)));
+        config.root = config.root.map(|p| base.join(expand_home(p)));
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Load the user config at `path` if there is one.
+    /// A file that can't be read or parsed is a warning
+    /// and the empty config is used instead.
+    pub fn load_or_default(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::default();
+        }
+        match Self::load(path) {
+            Ok(config) => {
+                tracing::debug!(user_config = %path.display());
+                config
+            }
+            Err(e) => {
+                tracing::warn!("{}. Using the built-in defaults instead", e);
+                Self::default()
+            }
+        }
+    }
+}
+
+impl Defaults {
+    /// Merge the `flags` with the environment, the `project`
+    /// config and the user config.
+    /// This is what the `hc` binary does on every invocation.
+    pub fn load(project: &ProjectConfig, flags: DefaultFlags) -> anyhow::Result<Self> {
+        let user = UserConfig::path()
+            .map(|path| UserConfig::load_or_default(&path))
+            .unwrap_or_default();
+        Self::resolve(flags, project, &user, |var| std::env::var(var).ok())
+    }
+
+    /// Merge the layers of defaults, reading the environment with `env`.
+    /// Environment variables that are set but invalid are an error.
+    pub fn resolve(
+        flags: DefaultFlags,
+        project: &ProjectConfig,
+        user: &UserConfig,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> anyhow::Result<Self> {
+        let project_file = || file_source(&project.path);
+        let user_file = || file_source(&user.path);
+        let env_network = match env(NETWORK_ENV) {
+            Some(transport) => Some(ProjectNetwork {
+                transport: parse_transport(&transport)?,
+                bootstrap: env(BOOTSTRAP_ENV),
+                bind_to: None,
+                override_host: None,
+                override_port: None,
+                proxy: None,
+                proxy_mode: None,
+                proxy_accept_all: false,
+            }),
+            None => None,
+        };
+        let env_format = env(CONDUCTOR_LOG_FORMAT_ENV)
+            .map(|format| {
+                format
+                    .parse::<LogFormat>()
+                    .map_err(|e| anyhow!("Invalid {}: {}", CONDUCTOR_LOG_FORMAT_ENV, e))
+            })
+            .transpose()?;
+
+        let holochain_path = first(vec![
+            (flags.holochain_path, Source::Flag),
+            (
+                env(HOLOCHAIN_PATH_ENV).map(PathBuf::from),
+                Source::Env(HOLOCHAIN_PATH_ENV),
+            ),
+            (project.holochain_path.clone(), project_file()),
+            (user.holochain_path.clone(), user_file()),
+        ]);
+        let root = first(vec![
+            (flags.root, Source::Flag),
+            (env(ROOT_ENV).map(PathBuf::from), Source::Env(ROOT_ENV)),
+            (project.root.clone(), project_file()),
+            (user.root.clone(), user_file()),
+        ]);
+        let network = first(vec![
+            (env_network, Source::Env(NETWORK_ENV)),
+            (project.network.clone(), project_file()),
+            (user.network.clone(), user_file()),
+        ]);
+        let conductor_log_filter = first(vec![
+            (flags.conductor_log.filter, Source::Flag),
+            (env(CONDUCTOR_LOG_ENV), Source::Env(CONDUCTOR_LOG_ENV)),
+            (user.conductor_log.filter.clone(), user_file()),
+        ]);
+        let conductor_log_format = first(vec![
+            (flags.conductor_log.format, Source::Flag),
+            (env_format, Source::Env(CONDUCTOR_LOG_FORMAT_ENV)),
+            (user.conductor_log.format, user_file()),
+        ]);
+        Ok(Self {
+            holochain_path: Sourced {
+                value: holochain_path
+                    .value
+                    .unwrap_or_else(|| PathBuf::from("holochain")),
+                source: holochain_path.source,
+            },
+            root,
+            network,
+            conductor_log_filter,
+            conductor_log_format,
+        })
+    }
+
+    /// The network as the same type that the CLI parses to.
+    pub fn network(&self) -> anyhow::Result<Option<Network>> {
+        use std::convert::TryFrom;
+        self.network
+            .value
+            .clone()
+            .map(Network::try_from)
+            .transpose()
+    }
+
+    /// The conductors' log settings.
+    pub fn conductor_log(&self) -> ConductorLog {
+        ConductorLog {
+            filter: self.conductor_log_filter.value.clone(),
+            format: self.conductor_log_format.value,
+        }
+    }
+}
+
+impl std::fmt::Display for Defaults {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let network = match &self.network.value {
+            Some(network) => {
+                let transport = match network.transport {
+                    ProjectTransport::Mem => "mem",
+                    ProjectTransport::Quic => "quic",
+                };
+                match &network.bootstrap {
+                    Some(bootstrap) => format!("{}, bootstrap {}", transport, bootstrap),
+                    None => transport.to_string(),
+                }
+            }
+            None => "none".to_string(),
+        };
+        let format = match self.conductor_log_format.value {
+            Some(LogFormat::Compact) => "compact",
+            Some(LogFormat::Json) => "json",
+            None => "the conductor's default",
+        };
+        writeln!(
+            f,
+            "holochain_path: {} ({})",
+            self.holochain_path.value.display(),
+            self.holochain_path.source
+        )?;
+        writeln!(
+            f,
+            "root: {} ({})",
+            self.root.value.as_ref().map_or_else(
+                || "a temp directory".to_string(),
+                |p| p.display().to_string()
+            ),
+            self.root.source
+        )?;
+        writeln!(f, "network: {} ({})", network, self.network.source)?;
+        writeln!(
+            f,
+            "conductor_log.filter: {} ({})",
+            self.conductor_log_filter
+                .value
+                .as_deref()
+                .unwrap_or("the RUST_LOG hc is run with"),
+            self.conductor_log_filter.source
+        )?;
+        write!(
+            f,
+            "conductor_log.format: {} ({})",
+            format, self.conductor_log_format.source
+        )
+    }
+}
+
+/// The first layer that sets a value.
+fn first<T>(layers: Vec<(Option<T>, Source)>) -> Sourced<Option<T>> {
+    layers
+        .into_iter()
+        .find(|(value, _)| value.is_some())
+        .map_or(
+            Sourced {
+                value: None,
+                source: Source::BuiltIn,
+            },
+            |(value, source)| Sourced { value, source },
+        )
+}
+
+fn file_source(path: &Option<PathBuf>) -> Source {
+    path.clone().map_or(Source::BuiltIn, Source::File)
+}
+
+fn parse_transport(transport: &str) -> anyhow::Result<ProjectTransport> {
+    match transport {
+        "mem" => Ok(ProjectTransport::Mem),
+        "quic" => Ok(ProjectTransport::Quic),
+        _ => Err(anyhow!(
+            "Invalid {}: {} is not a transport. Use mem or quic",
+            NETWORK_ENV,
+            transport
+        )),
+    }
+}
+
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Replace a leading `~` with the home directory.
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path,
+    }
+}
@@ -1,15 +1,838 @@
 //! Helpers for working with dna files.
+use std::convert::TryFrom;
 use std::path::Path;
 use std::path::PathBuf;
 
+use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::ensure;
+use anyhow::Context;
+use holochain_keystore::KeystoreSender;
+use holochain_types::prelude::AgentPubKey;
+use holochain_types::prelude::AgentPubKeyExt;
+use holochain_types::prelude::DnaFile;
+use holochain_types::prelude::DnaHash;
+use holochain_types::prelude::JsonProperties;
+use holochain_types::prelude::SerializedBytes;
+use holochain_types::prelude::Signature;
+use holochain_types::prelude::UnsafeBytes;
+use holochain_types::prelude::ZomeDef;
+use structopt::StructOpt;
 use walkdir::WalkDir;
 
+/// Added to the dna path for the default signature path.
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// The first bytes of every gzip file.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Callbacks holochain calls on a zome.
+/// Functions starting with these names are
+/// not meant to be called directly.
+const CALLBACKS: &[&str] = &[
+    "init",
+    "entry_defs",
+    "validate",
+    "validation_package",
+    "post_commit",
+    "migrate_agent",
+];
+
+#[doc(hidden)]
+#[derive(Debug, StructOpt)]
+/// Work with dna files.
+pub enum Dna {
+    /// Print the hash, properties and zomes of a dna file.
+    ///
+    /// This reads the file directly so no conductor needs to be running.
+    Inspect(Inspect),
+    /// Print the hash the dna will have once it is installed with
+    /// these modifiers, without installing anything.
+    ///
+    /// Agents are only on the same network if their dnas have the same hash.
+    /// The hash is computed the same way the conductor does when
+    /// the dna is registered with a uuid and properties.
+    Hash(Hash),
+    /// Sign a dna file as an agent from `hc agent list`
+    /// so the people you share it with can check it came from you.
+    ///
+    /// The signature is written next to the dna,
+    /// e.g. `my-dna.dna.gz.sig`, and signs the exact bytes of the file.
+    /// The agent's key never leaves its keystore.
+    Sign(Sign),
+    /// Check a dna file has this hash, or was signed with
+    /// `hc dna sign` by this agent and hasn't changed since.
+    ///
+    /// Exits with a non-zero code if it doesn't.
+    Verify(Verify),
+}
+
+#[doc(hidden)]
+#[derive(Debug, StructOpt)]
+pub struct Inspect {
+    /// Path to the dna file e.g. `my-dna.dna.gz`.
+    pub path: PathBuf,
+    #[structopt(long)]
+    /// Print as JSON.
+    pub json: bool,
+}
+
+#[doc(hidden)]
+#[derive(Debug, StructOpt)]
+pub struct Hash {
+    /// Path to the dna file e.g. `my-dna.dna.gz`.
+    pub path: PathBuf,
+    #[structopt(long, alias = "uuid")]
+    /// Replace the dna's uuid (the network seed).
+    /// Dnas that only differ by network seed are on separate networks.
+    pub network_seed: Option<String>,
+    #[structopt(long)]
+    /// Path to a JSON file of properties that replace the dna's properties.
+    pub properties: Option<PathBuf>,
+}
+
+/// Find dnas in a directory and its subdirectories with globs.
+#[derive(Debug, StructOpt, Clone, Default)]
+pub struct DnaGlobs {
+    #[structopt(long = "dna-glob", number_of_values = 1)]
+    /// Install the dnas that match this glob, searching the current
+    /// directory and all the directories in it, e.g. `--dna-glob 'dnas/*/workdir/*.dna.gz'`.
+    /// `*` and `?` match within a directory or file name and `**` matches
+    /// any number of directories. Can be passed more than once.
+    /// Files that are the same dna are only installed once.
+    pub dna_globs: Vec<String>,
+    #[structopt(long = "exclude", number_of_values = 1, requires = "dna_globs")]
+    /// Leave out the dnas that match this glob
+    /// or are in a directory that matches it, e.g. `--exclude target`.
+    pub exclude: Vec<String>,
+}
+
+#[doc(hidden)]
+#[derive(Debug, StructOpt)]
+pub struct Sign {
+    /// Path to the dna file e.g. `my-dna.dna.gz`.
+    pub path: PathBuf,
+    #[structopt(long)]
+    /// The name of the agent to sign as.
+    /// This is the name passed to `hc gen --reuse-agent`.
+    pub key: String,
+    #[structopt(long)]
+    /// Where to write the signature.
+    /// Defaults to the dna path with `.sig` added.
+    pub out: Option<PathBuf>,
+}
+
+#[doc(hidden)]
+#[derive(Debug, StructOpt)]
+pub struct Verify {
+    /// Path to the dna file e.g. `my-dna.dna.gz`.
+    pub path: PathBuf,
+    #[structopt(parse(from_str = verify_against))]
+    /// The hash the dna should have e.g. `uhC0k...`
+    /// or the path to its signature e.g. `my-dna.dna.gz.sig`.
+    pub against: VerifyAgainst,
+    #[structopt(long, parse(try_from_str = parse_agent_key))]
+    /// The public key of the agent that should have signed the dna.
+    /// Needed to check a signature.
+    pub pubkey: Option<AgentPubKey>,
+}
+
+/// What `hc dna verify` checks a dna against.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyAgainst {
+    /// The hash the dna should have.
+    Hash(DnaHash),
+    /// The path to a signature written by `hc dna sign`.
+    Signature(PathBuf),
+}
+
+/// Check the dnas `hc generate` installs have the hashes
+/// that are expected of them before anything is installed.
+#[derive(Debug, StructOpt, Clone, Default)]
+pub struct ExpectedDnaHashes {
+    #[structopt(
+        long = "expect-dna-hash",
+        number_of_values = 1,
+        parse(try_from_str = parse_expected_dna_hash)
+    )]
+    /// Fail without generating any setups unless the dna has this hash
+    /// once its properties are applied, e.g. `--expect-dna-hash uhC0k...`.
+    /// Hashes are matched to the dnas in order unless they are given
+    /// with the dna's path, e.g. `--expect-dna-hash ./my-dna.dna.gz=uhC0k...`.
+    /// Can be passed more than once.
+    pub expected: Vec<ExpectedDnaHash>,
+}
+
+/// A hash passed to `--expect-dna-hash`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedDnaHash {
+    /// The dna that should have the hash.
+    /// Without a path the hash is for the dna in the same position.
+    pub path: Option<PathBuf>,
+    /// The hash the dna should have.
+    pub hash: DnaHash,
+}
+
+/// A detached signature of a dna file written by `hc dna sign`.
+/// The signature is over the bytes of the file,
+/// made with the agent's key in its keystore.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DnaSignature {
+    /// The hash of the signed dna.
+    pub dna_hash: String,
+    /// The public key of the agent that signed the dna.
+    pub agent: String,
+    /// The base64 encoded ed25519 signature.
+    pub signature: String,
+}
+
+/// The contents of a dna file.
+#[derive(Debug, serde::Serialize)]
+pub struct DnaInspection {
+    /// The name of the dna.
+    pub name: String,
+    /// The hash of the dna.
+    pub hash: String,
+    /// The uuid of the dna.
+    pub uuid: String,
+    /// The properties of the dna.
+    pub properties: PropertiesInspection,
+    /// The zomes in the order they are defined.
+    pub zomes: Vec<ZomeInspection>,
+}
+
+/// The contents of a single zome.
+#[derive(Debug, serde::Serialize)]
+pub struct ZomeInspection {
+    /// The name of the zome.
+    pub name: String,
+    /// The hash of the zome's wasm.
+    /// Inline zomes have no wasm.
+    pub wasm_hash: Option<String>,
+    /// The size of the zome's wasm in bytes.
+    pub wasm_size: Option<usize>,
+    /// Functions that can be called with a zome call.
+    pub functions: Vec<String>,
+    /// Callbacks that holochain calls on this zome.
+    /// If `entry_defs` is here then the zome defines entry types.
+    pub callbacks: Vec<String>,
+    /// The entry types the zome's `entry_defs` callback defines, in order.
+    /// Only listed when hc is built with the `entry_types` feature
+    /// because running the callback needs the holochain crate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry_types: Option<Vec<EntryTypeInspection>>,
+}
+
+/// An entry type a zome defines.
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct EntryTypeInspection {
+    /// The id of the entry type e.g. `post`.
+    pub name: String,
+    /// Whether entries of this type are published to the DHT.
+    pub visibility: EntryVisibilityInspection,
+}
+
+/// The visibility of an [`EntryTypeInspection`].
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryVisibilityInspection {
+    /// Published to the DHT.
+    Public,
+    /// Only kept on the author's source chain.
+    Private,
+}
+
+/// The properties of a dna.
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(untagged)]
+pub enum PropertiesInspection {
+    /// Properties decoded from msgpack.
+    /// A dna without properties has `null`.
+    Decoded(serde_json::Value),
+    /// Properties that can't be shown as JSON,
+    /// for example because they contain raw bytes.
+    Undecoded {
+        /// The size of the encoded properties.
+        undecoded_bytes: usize,
+    },
+}
+
+#[doc(hidden)]
+pub async fn dna(cmd: Dna) -> anyhow::Result<()> {
+    match cmd {
+        Dna::Inspect(Inspect { path, json }) => {
+            let inspection = inspect(&path).await?;
+            if json {
+                data!("{}", serde_json::to_string_pretty(&inspection)?);
+            } else {
+                data!("{}", inspection);
+            }
+        }
+        Dna::Hash(Hash {
+            path,
+            network_seed,
+            properties,
+        }) => {
+            let properties = properties.map(|p| read_properties(&p)).transpose()?;
+            let hash = hash_with_modifiers(&path, network_seed, properties).await?;
+            data!("{}", hash);
+        }
+        Dna::Sign(Sign { path, key, out }) => {
+            let agent = crate::agents::get(&std::env::current_dir()?, &key)?;
+            let agent_key = agent.agent_key.clone().ok_or_else(|| {
+                anyhow!(
+                    "Agent {} doesn't have a key yet. \
+                    Generate a setup with `hc gen --reuse-agent {}` to create it",
+                    key,
+                    key
+                )
+            })?;
+            let keystore =
+                holochain_keystore::lair_keystore::spawn_lair_keystore(Some(&agent.keystore))
+                    .await
+                    .map_err(|e| anyhow!("Failed to open the keystore of agent {}: {}", key, e))?;
+            let signature = sign(&keystore, &agent_key, &path).await?;
+            let out = out.unwrap_or_else(|| signature_path(&path));
+            write_signature(&out, &signature)?;
+            msg!(
+                "Signed dna {} as agent {} ({})",
+                path.display(),
+                key,
+                agent_key
+            );
+            data!("{}", out.display());
+        }
+        Dna::Verify(Verify {
+            path,
+            against: VerifyAgainst::Hash(hash),
+            ..
+        }) => {
+            check_dna_hash(&path, &hash).await?;
+            data!("Dna {} has the hash {}", path.display(), hash);
+        }
+        Dna::Verify(Verify {
+            path,
+            against: VerifyAgainst::Signature(signature),
+            pubkey,
+        }) => {
+            let pubkey = pubkey.ok_or_else(|| {
+                anyhow!(
+                    "{} is a signature and --pubkey is needed to check it",
+                    signature.display()
+                )
+            })?;
+            let signature = read_signature(&signature)?;
+            verify(&path, &signature, &pubkey).await?;
+            data!(
+                "Dna {} ({}) was signed by {}",
+                path.display(),
+                signature.dna_hash,
+                pubkey
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The path `hc dna sign` writes the signature of this dna to.
+pub fn signature_path(dna: &Path) -> PathBuf {
+    let mut path = dna.as_os_str().to_owned();
+    path.push(".");
+    path.push(SIGNATURE_EXTENSION);
+    path.into()
+}
+
+/// Sign the bytes of the dna file at this path as the agent.
+/// The file must be a valid dna so only dnas can be signed.
+pub async fn sign(
+    keystore: &KeystoreSender,
+    agent: &AgentPubKey,
+    path: &Path,
+) -> anyhow::Result<DnaSignature> {
+    let content =
+        std::fs::read(path).map_err(|e| anyhow!("Failed to read dna {}: {}", path.display(), e))?;
+    let dna = dna_from_bytes(&content)
+        .await
+        .with_context(|| format!("Invalid dna file {}", path.display()))?;
+    let signature = agent.sign_raw(keystore, &content).await.map_err(|e| {
+        anyhow!(
+            "Failed to sign as {}. Is the agent in this keystore? {}",
+            agent,
+            e
+        )
+    })?;
+    Ok(DnaSignature {
+        dna_hash: dna.dna_hash().to_string(),
+        agent: agent.to_string(),
+        signature: base64::encode(&signature.0),
+    })
+}
+
+/// Check the signature is by this agent and is over the
+/// exact bytes of the dna file at this path.
+pub async fn verify(
+    path: &Path,
+    signature: &DnaSignature,
+    agent: &AgentPubKey,
+) -> anyhow::Result<()> {
+    ensure!(
+        signature.agent == agent.to_string(),
+        "The signature is by agent {}, not {}",
+        signature.agent,
+        agent
+    );
+    let content =
+        std::fs::read(path).map_err(|e| anyhow!("Failed to read dna {}: {}", path.display(), e))?;
+    let dna = dna_from_bytes(&content)
+        .await
+        .with_context(|| format!("Invalid dna file {}", path.display()))?;
+    ensure!(
+        signature.dna_hash == dna.dna_hash().to_string(),
+        "The signature is for dna {} but {} has the hash {}",
+        signature.dna_hash,
+        path.display(),
+        dna.dna_hash()
+    );
+    let bytes = base64::decode(&signature.signature)
+        .map_err(|e| anyhow!("The signature is not valid base64: {}", e))?;
+    let valid = agent
+        .verify_signature_raw(&Signature(bytes), &content)
+        .await
+        .map_err(|e| anyhow!("Failed to check the signature: {}", e))?;
+    ensure!(
+        valid,
+        "The signature doesn't match dna {}. \
+        The dna or the signature was changed after it was signed",
+        path.display()
+    );
+    Ok(())
+}
+
+/// Write a signature as JSON.
+pub fn write_signature(path: &Path, signature: &DnaSignature) -> anyhow::Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(signature)?)
+        .with_context(|| format!("Failed to write signature {}", path.display()))
+}
+
+/// Read a signature written by [`write_signature`].
+pub fn read_signature(path: &Path) -> anyhow::Result<DnaSignature> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read signature {}: {}", path.display(), e))?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("{} is not a signature from `hc dna sign`", path.display()))
+}
+
+fn parse_agent_key(arg: &str) -> anyhow::Result<AgentPubKey> {
+    AgentPubKey::try_from(arg).map_err(|e| anyhow!("{:?}", e))
+}
+
+/// A dna hash if the argument is one or else a signature path.
+fn verify_against(arg: &str) -> VerifyAgainst {
+    match DnaHash::try_from(arg) {
+        Ok(hash) => VerifyAgainst::Hash(hash),
+        Err(_) => VerifyAgainst::Signature(arg.into()),
+    }
+}
+
+/// Parse a `--expect-dna-hash` of a hash or a `path=hash` pair.
+pub fn parse_expected_dna_hash(arg: &str) -> anyhow::Result<ExpectedDnaHash> {
+    // Hashes are unpadded base64 so the last `=` is the separator.
+    let (path, hash) = match arg.rfind('=') {
+        Some(i) => (Some(PathBuf::from(&arg[..i])), &arg[i + 1..]),
+        None => (None, arg),
+    };
+    let hash =
+        DnaHash::try_from(hash).map_err(|e| anyhow!("{} is not a dna hash: {:?}", hash, e))?;
+    Ok(ExpectedDnaHash { path, hash })
+}
+
+/// Check the dna file at this path has this hash.
+pub async fn check_dna_hash(path: &Path, expected: &DnaHash) -> anyhow::Result<()> {
+    let dna = read_dna(path).await?;
+    ensure!(
+        dna.dna_hash() == expected,
+        "Dna {} has the hash {}, not the expected {}",
+        path.display(),
+        dna.dna_hash(),
+        expected
+    );
+    Ok(())
+}
+
+/// Check each dna that has an expected hash has that hash
+/// once the properties are applied, which is the hash it is installed with.
+/// Every mismatch is reported in the error
+/// so it is known which dnas weren't the expected ones.
+pub async fn check_dna_hashes(
+    dnas: &[PathBuf],
+    expected: &[ExpectedDnaHash],
+    properties: Option<JsonProperties>,
+) -> anyhow::Result<()> {
+    let list = || {
+        dnas.iter()
+            .map(|d| d.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let in_order: Vec<_> = expected.iter().filter(|e| e.path.is_none()).collect();
+    ensure!(
+        in_order.len() <= dnas.len(),
+        "There are {} --expect-dna-hash hashes without a path but only {} dnas: {}",
+        in_order.len(),
+        dnas.len(),
+        list()
+    );
+    let mut checks: Vec<(&Path, &DnaHash)> = dnas
+        .iter()
+        .map(PathBuf::as_path)
+        .zip(in_order.iter().map(|e| &e.hash))
+        .collect();
+    for e in expected {
+        if let Some(path) = &e.path {
+            let dna = dnas
+                .iter()
+                .find(|dna| same_file(dna, path))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "--expect-dna-hash is for {} but it isn't one of the dnas: {}",
+                        path.display(),
+                        list()
+                    )
+                })?;
+            checks.push((dna, &e.hash));
+        }
+    }
+    let mut mismatches = Vec::new();
+    for (path, expected) in &checks {
+        let hash = hash_with_modifiers(path, None, properties.clone()).await?;
+        if hash != **expected {
+            mismatches.push(format!(
+                "{}: expected {} but it is {}",
+                path.display(),
+                expected,
+                hash
+            ));
+        } else {
+            msg!("Dna {} has the expected hash {}", path.display(), hash);
+        }
+    }
+    ensure!(
+        mismatches.is_empty(),
+        "{} of {} dnas don't have the expected hash:\n{}",
+        mismatches.len(),
+        checks.len(),
+        mismatches.join("\n")
+    );
+    Ok(())
+}
+
+/// Are these paths to the same file.
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b
+        || match (a.canonicalize(), b.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+}
+
+/// Read dna properties from a JSON file.
+pub fn read_properties(path: &Path) -> anyhow::Result<JsonProperties> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read properties {}: {}", path.display(), e))?;
+    let properties = serde_json::from_str(&json)
+        .with_context(|| format!("Properties {} are not valid JSON", path.display()))?;
+    Ok(JsonProperties::new(properties))
+}
+
+/// The hash of the dna at this path once its uuid and properties
+/// are replaced, like [`CmdRunner::register_dna`] does on the conductor.
+///
+/// [`CmdRunner::register_dna`]: crate::CmdRunner::register_dna
+pub async fn hash_with_modifiers(
+    path: &Path,
+    uuid: Option<String>,
+    properties: Option<JsonProperties>,
+) -> anyhow::Result<DnaHash> {
+    let dna = read_dna(path).await?;
+    Ok(with_modifiers(dna, uuid, properties)
+        .await?
+        .dna_hash()
+        .clone())
+}
+
+/// Replace the uuid and properties of a dna.
+/// The properties are replaced first, the same as the conductor does.
+pub async fn with_modifiers(
+    mut dna: DnaFile,
+    uuid: Option<String>,
+    properties: Option<JsonProperties>,
+) -> anyhow::Result<DnaFile> {
+    if let Some(properties) = properties {
+        let properties = SerializedBytes::try_from(properties)
+            .map_err(|e| anyhow!("Failed to encode the properties: {}", e))?;
+        dna = dna.with_properties(properties).await?;
+    }
+    if let Some(uuid) = uuid {
+        dna = dna.with_uuid(uuid).await?;
+    }
+    Ok(dna)
+}
+
+/// Read a dna file from disk.
+pub async fn read_dna(path: &Path) -> anyhow::Result<DnaFile> {
+    let content =
+        std::fs::read(path).map_err(|e| anyhow!("Failed to read dna {}: {}", path.display(), e))?;
+    dna_from_bytes(&content)
+        .await
+        .with_context(|| format!("Invalid dna file {}", path.display()))
+}
+
+/// Decode the contents of a `*.dna.gz` file.
+/// Fails if the bytes are not a gzipped, msgpack encoded dna.
+/// The error says which of these steps failed:
+/// unzipping, reading the msgpack or matching the msgpack to a dna.
+/// The computed hash is available from [`DnaFile::dna_hash`].
+pub async fn dna_from_bytes(bytes: &[u8]) -> anyhow::Result<DnaFile> {
+    ensure!(
+        bytes.starts_with(&GZIP_MAGIC),
+        "Dna is not gzipped (expected the contents of a *.dna.gz file)"
+    );
+    let bytes = bytes.to_vec();
+    let dna = tokio::task::spawn_blocking(move || decode_dna(&bytes))
+        .await
+        .map_err(|e| anyhow!("Failed to decode the dna: {}", e))??;
+    dna.verify_hash().await.map_err(|e| {
+        anyhow!(
+            "Failed to decode the dna: the file was changed after it was built: {}",
+            e
+        )
+    })?;
+    Ok(dna)
+}
+
+fn decode_dna(bytes: &[u8]) -> anyhow::Result<DnaFile> {
+    use std::io::Read;
+    let mut msgpack = Vec::new();
+    flate2::read::GzDecoder::new(bytes)
+        .read_to_end(&mut msgpack)
+        .map_err(|e| anyhow!("Failed to decode the dna: it is not valid gzip: {}", e))?;
+    let _: serde::de::IgnoredAny = holochain_types::prelude::decode(&msgpack).map_err(|e| {
+        anyhow!(
+            "Failed to decode the dna: it unzipped but is not valid msgpack: {}",
+            e
+        )
+    })?;
+    DnaFile::try_from(SerializedBytes::from(UnsafeBytes::from(msgpack))).map_err(|e| {
+        anyhow!(
+            "Failed to decode the dna: it is msgpack but doesn't have the fields of a dna \
+            (it may be from another version of holochain): {}",
+            e
+        )
+    })
+}
+
+/// Decode dna properties from msgpack.
+pub fn inspect_properties(properties: &SerializedBytes) -> PropertiesInspection {
+    match holochain_types::prelude::decode(properties.bytes()) {
+        Ok(value) => PropertiesInspection::Decoded(value),
+        Err(_) => PropertiesInspection::Undecoded {
+            undecoded_bytes: properties.bytes().len(),
+        },
+    }
+}
+
+/// Unpack a dna file and list its properties, the zomes
+/// and the functions they export.
+pub async fn inspect(path: &Path) -> anyhow::Result<DnaInspection> {
+    let dna = read_dna(path).await?;
+    #[cfg(feature = "entry_types")]
+    let mut entry_types = Some(entry_defs::entry_types(&dna).await?);
+    #[cfg(not(feature = "entry_types"))]
+    let mut entry_types: Option<
+        std::collections::BTreeMap<holochain_types::prelude::ZomeName, Vec<EntryTypeInspection>>,
+    > = None;
+    let def = dna.dna_def();
+    let mut zomes = Vec::with_capacity(def.zomes.len());
+    for (name, zome) in &def.zomes {
+        let (wasm_hash, wasm_size, exports) = match zome {
+            ZomeDef::Wasm(wasm_zome) => {
+                let code = dna.get_wasm_for_zome(name)?.code();
+                (
+                    Some(wasm_zome.wasm_hash.to_string()),
+                    Some(code.len()),
+                    wasm_exports(&code)?,
+                )
+            }
+            ZomeDef::Inline(_) => (None, None, Vec::new()),
+        };
+        let (callbacks, functions): (Vec<_>, Vec<_>) =
+            exports.into_iter().partition(|f| is_callback(f));
+        zomes.push(ZomeInspection {
+            name: name.to_string(),
+            wasm_hash,
+            wasm_size,
+            functions,
+            callbacks,
+            entry_types: entry_types
+                .as_mut()
+                .map(|types| types.remove(name).unwrap_or_default()),
+        });
+    }
+    Ok(DnaInspection {
+        name: def.name.clone(),
+        hash: dna.dna_hash().to_string(),
+        uuid: def.uuid.clone(),
+        properties: inspect_properties(&def.properties),
+        zomes,
+    })
+}
+
+#[cfg(feature = "entry_types")]
+mod entry_defs {
+    use std::collections::BTreeMap;
+
+    use anyhow::anyhow;
+    use anyhow::bail;
+    use holochain::core::ribosome::guest_callback::entry_defs::EntryDefsHostAccess;
+    use holochain::core::ribosome::guest_callback::entry_defs::EntryDefsInvocation;
+    use holochain::core::ribosome::guest_callback::entry_defs::EntryDefsResult;
+    use holochain::core::ribosome::real_ribosome::RealRibosome;
+    use holochain::core::ribosome::RibosomeT;
+    use holochain_types::prelude::DnaFile;
+    use holochain_types::prelude::EntryDef;
+    use holochain_types::prelude::EntryDefId;
+    use holochain_types::prelude::EntryVisibility;
+    use holochain_types::prelude::ZomeName;
+
+    use super::EntryTypeInspection;
+    use super::EntryVisibilityInspection;
+
+    /// The entry types each zome defines.
+    /// This runs the `entry_defs` callbacks in a ribosome
+    /// the same way the conductor does when the dna is installed.
+    pub(super) async fn entry_types(
+        dna: &DnaFile,
+    ) -> anyhow::Result<BTreeMap<ZomeName, Vec<EntryTypeInspection>>> {
+        let ribosome = RealRibosome::new(dna.clone());
+        let result = tokio::task::spawn_blocking(move || {
+            ribosome.run_entry_defs(EntryDefsHostAccess, EntryDefsInvocation)
+        })
+        .await?
+        .map_err(|e| anyhow!("Failed to run the entry_defs callbacks: {}", e))?;
+        match result {
+            EntryDefsResult::Defs(defs) => Ok(defs
+                .into_iter()
+                .map(|(zome, defs)| (zome, defs.into_iter().map(inspect_entry_def).collect()))
+                .collect()),
+            EntryDefsResult::Err(zome, e) => {
+                bail!("The entry_defs callback of zome {} failed: {}", zome, e)
+            }
+        }
+    }
+
+    fn inspect_entry_def(def: EntryDef) -> EntryTypeInspection {
+        let name = match def.id {
+            EntryDefId::App(name) => name,
+            EntryDefId::CapClaim => "cap_claim".to_string(),
+            EntryDefId::CapGrant => "cap_grant".to_string(),
+        };
+        let visibility = match def.visibility {
+            EntryVisibility::Public => EntryVisibilityInspection::Public,
+            EntryVisibility::Private => EntryVisibilityInspection::Private,
+        };
+        EntryTypeInspection { name, visibility }
+    }
+}
+
+/// The names of all the functions a wasm exports
+/// except the `__` prefixed functions used by the host.
+fn wasm_exports(code: &[u8]) -> anyhow::Result<Vec<String>> {
+    use wasmparser::{ExternalKind, ModuleReader, SectionCode};
+    let error = |e| anyhow!("Failed to parse zome wasm: {:?}", e);
+    let mut reader = ModuleReader::new(code).map_err(error)?;
+    let mut exports = Vec::new();
+    while !reader.eof() {
+        let section = reader.read().map_err(error)?;
+        if let SectionCode::Export = section.code {
+            for export in section.get_export_section_reader().map_err(error)? {
+                let export = export.map_err(error)?;
+                if let ExternalKind::Function = export.kind {
+                    if !export.field.starts_with("__") {
+                        exports.push(export.field.to_string());
+                    }
+                }
+            }
+        }
+    }
+    exports.sort();
+    Ok(exports)
+}
+
+fn is_callback(function: &str) -> bool {
+    CALLBACKS.iter().any(|c| function.starts_with(c))
+}
+
+impl std::fmt::Display for DnaInspection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Dna: {}", self.name)?;
+        writeln!(f, "Hash: {}", self.hash)?;
+        writeln!(f, "Uuid: {}", self.uuid)?;
+        match &self.properties {
+            PropertiesInspection::Decoded(serde_json::Value::Null) => {
+                writeln!(f, "Properties: none")?
+            }
+            PropertiesInspection::Decoded(properties) => {
+                writeln!(f, "Properties:")?;
+                let yaml = serde_yaml::to_string(properties).map_err(|_| std::fmt::Error)?;
+                for line in yaml.lines().filter(|l| *l != "---") {
+                    writeln!(f, "    {}", line)?;
+                }
+            }
+            PropertiesInspection::Undecoded { undecoded_bytes } => writeln!(
+                f,
+                "Properties: {} bytes that can't be shown as YAML",
+                undecoded_bytes
+            )?,
+        }
+        for zome in &self.zomes {
+            writeln!(f, "Zome: {}", zome.name)?;
+            if let (Some(hash), Some(size)) = (&zome.wasm_hash, zome.wasm_size) {
+                writeln!(f, "    Wasm: {} ({} bytes)", hash, size)?;
+            }
+            writeln!(f, "    Functions: {}", zome.functions.join(", "))?;
+            writeln!(f, "    Callbacks: {}", zome.callbacks.join(", "))?;
+            match &zome.entry_types {
+                None => {}
+                Some(entry_types) if entry_types.is_empty() => {
+                    writeln!(f, "    Entry types: none")?
+                }
+                Some(entry_types) => {
+                    let entry_types: Vec<_> = entry_types
+                        .iter()
+                        .map(|e| match e.visibility {
+                            EntryVisibilityInspection::Public => e.name.clone(),
+                            EntryVisibilityInspection::Private => format!("{} (private)", e.name),
+                        })
+                        .collect();
+                    writeln!(f, "    Entry types: {}", entry_types.join(", "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// App bundles by file extension.
+/// This version of holochain installs apps from dna files
+/// so these are rejected with an error saying why.
+const APP_BUNDLES: &[(&str, &str)] = &[(".webhapp", "web hApp"), (".happ", "hApp")];
+
 /// Parse a list of dnas.
 /// If paths are directories then each directory
 /// will be searched for the first file that matches
 /// `*.dna.gz`.
+/// hApp and web hApp bundles are an error
+/// because the conductor can't install them.
 pub fn parse_dnas(mut dnas: Vec<PathBuf>) -> anyhow::Result<Vec<PathBuf>> {
     if dnas.is_empty() {
         dnas.push(std::env::current_dir()?);
@@ -19,6 +842,19 @@ pub fn parse_dnas(mut dnas: Vec<PathBuf>) -> anyhow::Result<Vec<PathBuf>> {
             let file_path = search_for_dna(&dna)?;
             *dna = file_path;
         }
+        let name = dna
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if let Some((_, kind)) = APP_BUNDLES.iter().find(|(ext, _)| name.ends_with(ext)) {
+            bail!(
+                "{} is a {} bundle. This version of holochain installs apps \
+                from dna files (e.g. my-dna.dna.gz) and can't install hApp or web hApp bundles. \
+                Pass the dna files the bundle was built from instead",
+                dna.display(),
+                kind
+            );
+        }
         ensure!(
             dna.file_name()
                 .map(|f| f.to_string_lossy().ends_with(".dna.gz"))
@@ -30,6 +866,108 @@ pub fn parse_dnas(mut dnas: Vec<PathBuf>) -> anyhow::Result<Vec<PathBuf>> {
     Ok(dnas)
 }
 
+/// Find the dnas in `dir` and all its subdirectories that match the globs.
+/// Files that decode to the same dna hash as an earlier match are skipped.
+/// The dnas found and the ones skipped are printed so a glob
+/// that matches more than expected is noticed.
+/// It is an error if no dnas match.
+pub async fn discover_dnas(dir: &Path, globs: &DnaGlobs) -> anyhow::Result<Vec<PathBuf>> {
+    let mut matches: Vec<_> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let relative = e.path().strip_prefix(dir).ok()?;
+            let relative = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let included = globs.dna_globs.iter().any(|g| glob_matches(g, &relative));
+            let excluded = globs.exclude.iter().any(|g| {
+                let mut path = relative.as_str();
+                loop {
+                    if glob_matches(g, path) {
+                        break true;
+                    }
+                    match path.rfind('/') {
+                        Some(i) => path = &path[..i],
+                        None => break false,
+                    }
+                }
+            });
+            if included && !excluded {
+                Some(e.into_path())
+            } else {
+                None
+            }
+        })
+        .collect();
+    matches.sort();
+    if matches.is_empty() {
+        let mut tried = globs
+            .dna_globs
+            .iter()
+            .map(|g| format!("--dna-glob {}", g))
+            .collect::<Vec<_>>();
+        tried.extend(globs.exclude.iter().map(|g| format!("--exclude {}", g)));
+        bail!("No dnas in {} match {}", dir.display(), tried.join(" "));
+    }
+    let mut found: Vec<(PathBuf, DnaHash)> = Vec::with_capacity(matches.len());
+    for path in matches {
+        let hash = read_dna(&path).await?.dna_hash().clone();
+        match found.iter().find(|(_, h)| *h == hash) {
+            Some((same, _)) => msg!(
+                "Skipping {}: it is the same dna as {}",
+                path.display(),
+                same.display()
+            ),
+            None => found.push((path, hash)),
+        }
+    }
+    msg!("Found {} dnas:", found.len());
+    for (path, hash) in &found {
+        msg!("    {} ({})", path.display(), hash);
+    }
+    Ok(found.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Does the path match the glob. Both have `/` between their parts.
+/// `*` matches any characters in one part, `?` matches one character
+/// and `**` matches any number of parts.
+pub fn glob_matches(glob: &str, path: &str) -> bool {
+    match_parts(&glob_parts(glob), &glob_parts(path))
+}
+
+fn glob_parts(s: &str) -> Vec<Vec<char>> {
+    s.split('/')
+        .filter(|p| !p.is_empty() && *p != ".")
+        .map(|p| p.chars().collect())
+        .collect()
+}
+
+fn match_parts(glob: &[Vec<char>], path: &[Vec<char>]) -> bool {
+    match glob.split_first() {
+        None => path.is_empty(),
+        Some((part, rest)) if part[..] == ['*', '*'] => {
+            (0..=path.len()).any(|i| match_parts(rest, &path[i..]))
+        }
+        Some((part, rest)) => match path.split_first() {
+            Some((name, path)) => match_part(part, name) && match_parts(rest, path),
+            None => false,
+        },
+    }
+}
+
+fn match_part(glob: &[char], name: &[char]) -> bool {
+    match glob.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| match_part(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && match_part(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_part(rest, &name[1..]),
+    }
+}
+
 fn search_for_dna(dna: &Path) -> anyhow::Result<PathBuf> {
     let dir: Vec<_> = WalkDir::new(dna)
         .max_depth(1)
@@ -0,0 +1,300 @@
+//! Diagnose common problems with the holochain binary
+//! and the setups in a `.hc` file.
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use portpicker::is_free;
+use walkdir::WalkDir;
+
+use crate::config::read_config;
+use crate::ports::get_admin_address;
+use crate::AdminAddress;
+
+/// How long to wait for `holochain --version`.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The outcome of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Nothing is wrong.
+    Pass,
+    /// Something might cause problems but hc can still work.
+    Warn,
+    /// This will stop hc from working.
+    Fail,
+}
+
+/// A single check and how to fix it.
+#[derive(Debug, Clone)]
+pub struct Check {
+    /// The outcome of the check.
+    pub status: Status,
+    /// What was found.
+    pub message: String,
+    /// How to fix the problem if there is one.
+    pub hint: Option<String>,
+}
+
+/// The checks made by [`doctor`].
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// All the checks in the order they were made.
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// The number of checks that are hard failures.
+    pub fn failures(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|c| c.status == Status::Fail)
+            .count()
+    }
+
+    fn pass(&mut self, message: String) {
+        self.checks.push(Check {
+            status: Status::Pass,
+            message,
+            hint: None,
+        });
+    }
+
+    fn warn(&mut self, message: String, hint: &str) {
+        self.checks.push(Check {
+            status: Status::Warn,
+            message,
+            hint: Some(hint.to_string()),
+        });
+    }
+
+    fn fail(&mut self, message: String, hint: &str) {
+        self.checks.push(Check {
+            status: Status::Fail,
+            message,
+            hint: Some(hint.to_string()),
+        });
+    }
+}
+
+/// Check the holochain binary can be run and
+/// the setups and dnas in the `hc_dir` are usable.
+pub async fn doctor(holochain_path: &Path, hc_dir: PathBuf) -> anyhow::Result<Report> {
+    let mut report = Report::default();
+    check_holochain(&mut report, holochain_path).await;
+    for path in check_hc_file(&mut report, &hc_dir) {
+        check_admin_port(&mut report, path).await?;
+    }
+    check_dnas(&mut report, &hc_dir).await;
+    Ok(report)
+}
+
+async fn check_holochain(report: &mut Report, holochain_path: &Path) {
+    let hint =
+        "Install holochain or set the path with `hc --holochain-path` or `HC_HOLOCHAIN_PATH`";
+    let output = tokio::process::Command::new(holochain_path)
+        .arg("--version")
+        .output();
+    match tokio::time::timeout(VERSION_TIMEOUT, output).await {
+        Ok(Ok(output)) if output.status.success() => report.pass(format!(
+            "Found {}",
+            String::from_utf8_lossy(&output.stdout).trim()
+        )),
+        Ok(Ok(output)) => report.fail(
+            format!(
+                "`{} --version` failed with {}",
+                holochain_path.display(),
+                output.status
+            ),
+            hint,
+        ),
+        Ok(Err(e)) => report.fail(
+            format!("Failed to run `{}`: {}", holochain_path.display(), e),
+            hint,
+        ),
+        Err(_) => report.fail(
+            format!(
+                "`{} --version` didn't finish within {:?}",
+                holochain_path.display(),
+                VERSION_TIMEOUT
+            ),
+            hint,
+        ),
+    }
+}
+
+/// Check each setup in the `.hc` file and return the usable ones.
+fn check_hc_file(report: &mut Report, hc_dir: &Path) -> Vec<PathBuf> {
+    let hc_file = hc_dir.join(".hc");
+    if !hc_file.exists() {
+        report.warn(
+            format!("No `.hc` file in {}", hc_dir.display()),
+            "Create setups with `hc generate`",
+        );
+        return Vec::new();
+    }
+    let existing = match std::fs::read_to_string(&hc_file) {
+        Ok(existing) => existing,
+        Err(e) => {
+            report.fail(
+                format!("Failed to read {}: {}", hc_file.display(), e),
+                "Remove the `.hc` file with `hc clean`",
+            );
+            return Vec::new();
+        }
+    };
+    let mut paths = Vec::new();
+    for (i, setup) in existing.lines().enumerate() {
+        let path = PathBuf::from(setup);
+        if !path.is_dir() {
+            report.warn(
+                format!("Setup {} at {} doesn't exist", i, path.display()),
+                "Remove stale setups with `hc clean`",
+            );
+            continue;
+        }
+        match read_config(path.clone()) {
+            Ok(Some(_)) => {
+                report.pass(format!("Setup {} at {}", i, path.display()));
+                paths.push(path);
+            }
+            Ok(None) => report.warn(
+                format!("Setup {} at {} has no conductor config", i, path.display()),
+                "Remove stale setups with `hc clean`",
+            ),
+            Err(e) => report.fail(
+                format!(
+                    "Setup {} at {} has an invalid conductor config: {}",
+                    i,
+                    path.display(),
+                    e
+                ),
+                "Fix or remove the conductor config",
+            ),
+        }
+    }
+    paths
+}
+
+async fn check_admin_port(report: &mut Report, path: PathBuf) -> anyhow::Result<()> {
+    let port = match get_admin_address(path.clone())? {
+        Some(AdminAddress::Socket(socket)) => {
+            check_admin_socket(report, path, socket).await?;
+            return Ok(());
+        }
+        None | Some(AdminAddress::Port(0)) => {
+            match crate::run::live_admin_address(&path)? {
+                Some(AdminAddress::Port(port))
+                    if port != 0 && crate::run::is_running(path.clone()).await? =>
+                {
+                    report.pass(format!(
+                        "Conductor for setup {} is running on admin port {}",
+                        path.display(),
+                        port
+                    ))
+                }
+                _ => report.pass(format!(
+                    "Setup {} will use a free admin port",
+                    path.display()
+                )),
+            }
+            return Ok(());
+        }
+        Some(AdminAddress::Port(port)) => port,
+    };
+    if is_free(port) {
+        report.pass(format!(
+            "Admin port {} for setup {} is free",
+            port,
+            path.display()
+        ));
+    } else if crate::run::is_running(path.clone()).await? {
+        report.pass(format!(
+            "Conductor for setup {} is running on admin port {}",
+            path.display(),
+            port
+        ));
+    } else {
+        report.warn(
+            format!(
+                "Admin port {} for setup {} is used by another process",
+                port,
+                path.display()
+            ),
+            "`hc run` will choose a free port unless it's forced with `hc -f`",
+        );
+    }
+    Ok(())
+}
+
+async fn check_admin_socket(
+    report: &mut Report,
+    path: PathBuf,
+    socket: PathBuf,
+) -> anyhow::Result<()> {
+    if cfg!(not(unix)) {
+        report.fail(
+            format!(
+                "Setup {} uses admin socket {} but unix sockets are not supported on this platform",
+                path.display(),
+                socket.display()
+            ),
+            "Generate the setup without `--uds`",
+        );
+    } else if crate::run::is_running(path.clone()).await? {
+        report.pass(format!(
+            "Conductor for setup {} is running on admin socket {}",
+            path.display(),
+            socket.display()
+        ));
+    } else if socket.exists() {
+        report.pass(format!(
+            "Stale admin socket {} for setup {} will be replaced",
+            socket.display(),
+            path.display()
+        ));
+    } else {
+        report.pass(format!(
+            "Setup {} will use admin socket {}",
+            path.display(),
+            socket.display()
+        ));
+    }
+    Ok(())
+}
+
+async fn check_dnas(report: &mut Report, dir: &Path) {
+    let dnas = WalkDir::new(dir)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|d| d.file_type().is_file())
+        .filter(|f| f.file_name().to_string_lossy().ends_with(".dna.gz"))
+        .map(|f| f.into_path());
+    for dna in dnas {
+        match crate::dna::read_dna(&dna).await {
+            Ok(_) => report.pass(format!("Dna {} is valid", dna.display())),
+            Err(e) => report.fail(
+                format!("Dna {} is not a valid dna file: {:#}", dna.display(), e),
+                "Rebuild the dna file",
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            let status = match check.status {
+                Status::Pass => "pass",
+                Status::Warn => "warn",
+                Status::Fail => "FAIL",
+            };
+            writeln!(f, "[{}] {}", status, check.message)?;
+            if let Some(hint) = &check.hint {
+                writeln!(f, "       {}", hint)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//! What to do when a command fails on one of several setups.
+//!
+//! `hc --fail-fast` stops at the first setup that fails and
+//! `hc --keep-going` carries on with the rest and ends with a
+//! summary of the setups that failed. Without either flag each
+//! command uses its own default:
+//! - `hc run` fails fast so a conductor that doesn't start is noticed.
+//! - `hc call` fails fast, or keeps going with `--parallel`.
+//! - `hc clean` keeps going so one setup that can't be removed
+//!   doesn't leave the others behind.
+use structopt::StructOpt;
+
+/// Whether to stop at the first setup that fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Stop at the first failure.
+    FailFast,
+    /// Carry on with the other setups and report all the failures at the end.
+    KeepGoing,
+}
+
+#[derive(Debug, StructOpt, Clone, Default)]
+/// The flags that choose a [`FailurePolicy`].
+pub struct FailureFlags {
+    #[structopt(long, conflicts_with = "keep-going")]
+    /// Stop at the first setup that fails.
+    /// This is the default for `hc run` and `hc call`.
+    pub fail_fast: bool,
+    #[structopt(long)]
+    /// Carry on with the other setups when one fails
+    /// and list the setups that failed at the end.
+    /// This is the default for `hc clean` and `hc call --parallel`.
+    pub keep_going: bool,
+}
+
+impl FailureFlags {
+    /// The policy the flags choose or `None` if neither was passed
+    /// so the command's default is used.
+    pub fn policy(&self) -> Option<FailurePolicy> {
+        if self.fail_fast {
+            Some(FailurePolicy::FailFast)
+        } else if self.keep_going {
+            Some(FailurePolicy::KeepGoing)
+        } else {
+            None
+        }
+    }
+}
+
+/// Collects the failures of a command that runs on several setups.
+#[derive(Debug)]
+pub struct Failures {
+    policy: FailurePolicy,
+    failed: Vec<String>,
+}
+
+impl Failures {
+    /// Start with no failures.
+    pub fn new(policy: FailurePolicy) -> Self {
+        Self {
+            policy,
+            failed: Vec::new(),
+        }
+    }
+
+    /// The policy failures are handled with.
+    pub fn policy(&self) -> FailurePolicy {
+        self.policy
+    }
+
+    /// Record that the command failed on the setup called `name`.
+    /// Returns the error if failing fast, otherwise it is logged
+    /// and the command carries on.
+    pub fn fail(&mut self, name: &str, e: anyhow::Error) -> anyhow::Result<()> {
+        match self.policy {
+            FailurePolicy::FailFast => Err(e.context(format!("{} failed", name))),
+            FailurePolicy::KeepGoing => {
+                tracing::error!("{} failed: {:?}", name, e);
+                self.failed.push(name.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// The names of the setups that failed so far.
+    pub fn failed(&self) -> &[String] {
+        &self.failed
+    }
+
+    /// Finish the command, e.g. `"Call"`, that ran on `total` setups.
+    /// It is an error listing the setups that failed if any did.
+    pub fn finish(self, command: &str, total: usize) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.failed.is_empty(),
+            "{} failed on {} of {}: {}",
+            command,
+            self.failed.len(),
+            total,
+            self.failed.join(", ")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+//! Download dnas that are passed to `hc generate` as URLs.
+//!
+//! A dna argument like `https://example.com/my-dna.dna.gz` is downloaded
+//! into the [`cache_dir`] and the setup is generated from the downloaded file
+//! exactly as if its path had been passed instead.
+//!
+//! The cache is content addressed. Each download is stored in
+//! `<cache>/<sha256 of the file>/<file name>` and `<cache>/urls/<sha256 of the url>.json`
+//! records which file a URL downloaded to along with its etag.
+//! A URL that is in the cache isn't downloaded again unless `--refresh` is passed,
+//! which asks the server for the file again and keeps the cached file
+//! if its etag hasn't changed.
+//!
+//! Downloads are checked to be valid dnas before they are cached and
+//! are written to a temporary file that is only moved into the cache
+//! once it is complete, so a failed download leaves the cache as it was.
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+
+/// Set this environment variable to use another directory for the dna cache.
+pub const DNA_CACHE_ENV: &str = "HC_DNA_CACHE";
+
+/// The directory in the cache that maps URLs to downloaded files.
+const URLS_DIR: &str = "urls";
+
+/// The name of a downloaded dna whose URL doesn't end with a dna file name.
+const DEFAULT_FILE_NAME: &str = "downloaded.dna.gz";
+
+/// What a URL downloaded to.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct CachedUrl {
+    url: String,
+    etag: Option<String>,
+    sha256: String,
+    file_name: String,
+}
+
+/// Is this dna argument a URL to download instead of a path.
+pub fn is_url(dna: &Path) -> bool {
+    dna.to_str()
+        .map(|d| d.starts_with("https://") || d.starts_with("http://"))
+        .unwrap_or(false)
+}
+
+/// Where downloaded dnas are cached.
+/// This is [`DNA_CACHE_ENV`] if it is set or else `hc/dnas`
+/// in `$XDG_CACHE_HOME` or `~/.cache`.
+/// Without a home directory it is `.hc_dna_cache` in the current directory.
+pub fn cache_dir() -> PathBuf {
+    if let Some(path) = std::env::var_os(DNA_CACHE_ENV) {
+        return PathBuf::from(path);
+    }
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| crate::defaults::home_dir().map(|home| home.join(".cache")))
+        .map(|dir| dir.join("hc").join("dnas"))
+        .unwrap_or_else(|| PathBuf::from(".hc_dna_cache"))
+}
+
+/// Replace the URLs in a list of dnas with the paths
+/// of the downloaded files. Paths are left as they are.
+pub async fn fetch_dnas(
+    dnas: Vec<PathBuf>,
+    cache: &Path,
+    refresh: bool,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut fetched = Vec::with_capacity(dnas.len());
+    for dna in dnas {
+        match dna.to_str() {
+            Some(url) if is_url(&dna) => fetched.push(fetch(url, cache, refresh).await?),
+            _ => fetched.push(dna),
+        }
+    }
+    Ok(fetched)
+}
+
+/// Download the dna at this URL into the cache and return its path.
+/// A URL that is already in the cache is only downloaded again
+/// if `refresh` is set.
+pub async fn fetch(url: &str, cache: &Path, refresh: bool) -> anyhow::Result<PathBuf> {
+    let index = cache
+        .join(URLS_DIR)
+        .join(format!("{}.json", sha256(url.as_bytes())));
+    let cached = read_cached(&index, cache);
+    if let (Some((_, path)), false) = (&cached, refresh) {
+        msg!("Using dna {} downloaded from {}", path.display(), url);
+        return Ok(path.clone());
+    }
+
+    let mut request = reqwest::Client::new().get(url);
+    if let Some(etag) = cached.as_ref().and_then(|(c, _)| c.etag.as_ref()) {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    let error = |e| anyhow!("Failed to download dna {}: {}", url, e);
+    let response = request.send().await.map_err(error)?;
+    if let (Some((_, path)), reqwest::StatusCode::NOT_MODIFIED) = (&cached, response.status()) {
+        msg!("Dna {} hasn't changed since it was downloaded", url);
+        return Ok(path.clone());
+    }
+    let response = response.error_for_status().map_err(error)?;
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(String::from);
+    let bytes = response.bytes().await.map_err(error)?;
+    crate::dna::dna_from_bytes(&bytes)
+        .await
+        .with_context(|| format!("The file downloaded from {} is not a valid dna", url))?;
+
+    let entry = CachedUrl {
+        url: url.to_string(),
+        etag,
+        sha256: sha256(&bytes),
+        file_name: file_name(url),
+    };
+    let path = cache.join(&entry.sha256).join(&entry.file_name);
+    if !path.is_file() {
+        write_atomic(cache, &path, &bytes)?;
+    }
+    write_atomic(
+        cache,
+        &index,
+        serde_json::to_string_pretty(&entry)?.as_bytes(),
+    )?;
+    msg!("Downloaded dna {} to {}", url, path.display());
+    Ok(path)
+}
+
+/// The cached entry for a URL if it and its file are in the cache.
+fn read_cached(index: &Path, cache: &Path) -> Option<(CachedUrl, PathBuf)> {
+    let json = std::fs::read_to_string(index).ok()?;
+    let entry: CachedUrl = serde_json::from_str(&json).ok()?;
+    let path = cache.join(&entry.sha256).join(&entry.file_name);
+    if path.is_file() {
+        Some((entry, path))
+    } else {
+        None
+    }
+}
+
+/// Write a file in the cache so it either has all of
+/// the contents or isn't there at all.
+fn write_atomic(cache: &Path, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow!("{} has no parent directory", path.display()))?;
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create the dna cache {}", dir.display()))?;
+    let tmp = cache.join(format!(".{}.part", nanoid::nanoid!()));
+    let result = std::fs::write(&tmp, contents).and_then(|_| std::fs::rename(&tmp, path));
+    if result.is_err() {
+        std::fs::remove_file(&tmp).ok();
+    }
+    result.with_context(|| format!("Failed to write {} to the dna cache", path.display()))
+}
+
+/// The last part of the URL if it is a dna file name.
+fn file_name(url: &str) -> String {
+    url.split(|c| c == '?' || c == '#')
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| name.ends_with(".dna.gz"))
+        .unwrap_or(DEFAULT_FILE_NAME)
+        .to_string()
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
@@ -5,8 +5,7 @@ use std::path::PathBuf;
 use holochain_conductor_api::conductor::ConductorConfig;
 use holochain_p2p::kitsune_p2p::KitsuneP2pConfig;
 
-use crate::config::create_config;
-use crate::config::write_config;
+use crate::config::ConductorConfigBuilder;
 
 /// Generate a new setup.
 /// This creates a directory and a [`ConductorConfig`]
@@ -20,10 +19,12 @@ pub fn generate(
     directory: Option<PathBuf>,
 ) -> anyhow::Result<PathBuf> {
     let dir = generate_directory(root, directory)?;
-    let mut config = create_config(dir.clone());
-    config.network = network;
-    let path = write_config(dir.clone(), &config);
-    msg!("Config {:?}", config);
+    let mut builder = ConductorConfigBuilder::new(dir.clone());
+    if let Some(network) = network {
+        builder = builder.network(network);
+    }
+    let path = builder.write_to(&dir)?;
+    tracing::debug!("Config {:?}", builder.config());
     msg!(
         "Created directory at: {} {}",
         ansi_term::Style::new()
@@ -36,7 +37,7 @@ pub fn generate(
             .bold()
             .paint("Keep this path to rerun the same setup")
     );
-    msg!("Created config at {}", path.display());
+    tracing::info!("Created config at {}", path.display());
     Ok(dir)
 }
 
@@ -47,8 +48,11 @@ pub fn generate_with_config(
     directory: Option<PathBuf>,
 ) -> anyhow::Result<PathBuf> {
     let dir = generate_directory(root, directory)?;
-    let config = config.unwrap_or_else(|| create_config(dir.clone()));
-    write_config(dir.clone(), &config);
+    let builder = match config {
+        Some(config) => ConductorConfigBuilder::from(config),
+        None => ConductorConfigBuilder::new(dir.clone()),
+    };
+    builder.write_to(&dir)?;
     Ok(dir)
 }
 
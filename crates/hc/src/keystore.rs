@@ -0,0 +1,410 @@
+//! Choose a setup's keystore and move its agent keys to another machine.
+//!
+//! A setup keeps its keys in its own keystore directory unless
+//! `hc gen --keystore` chooses an ephemeral keystore that is never written
+//! to disk or a [`KeystoreKind::Shared`] directory several setups use.
+//! A shared keystore records the setups using it in [`KEYSTORE_USERS_FILE`]
+//! and `hc clean` only removes it with the last of them.
+//!
+//! The keystore directory of a setup holds the private keys of
+//! every agent in it. [`export_keystore`] packs the directory into
+//! one file encrypted with a passphrase and [`import_keystore`]
+//! unpacks it into another setup so the same agents can be used there.
+//!
+//! The file is encrypted with ChaCha20-Poly1305 using a key derived
+//! from the passphrase with PBKDF2-HMAC-SHA256, so a wrong passphrase
+//! or a modified file fails to import instead of writing bad keys.
+//! Exporting without a passphrase is refused.
+use std::convert::TryFrom;
+use std::num::NonZeroU32;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use ring::aead::Aad;
+use ring::aead::LessSafeKey;
+use ring::aead::Nonce;
+use ring::aead::UnboundKey;
+use ring::aead::CHACHA20_POLY1305;
+use ring::aead::NONCE_LEN;
+use ring::rand::SecureRandom;
+use ring::rand::SystemRandom;
+use walkdir::WalkDir;
+
+use crate::config::read_config;
+use crate::config::write_config;
+use crate::passphrase::Passphrase;
+
+/// Set this environment variable to the passphrase for
+/// `hc call export-keystore` and `hc call import-keystore`
+/// instead of passing `--passphrase-file`.
+pub const KEYSTORE_PASSPHRASE_ENV: &str = "HC_KEYSTORE_PASSPHRASE";
+
+/// Name of the file in a shared keystore that lists
+/// the setups using it, one path per line.
+pub const KEYSTORE_USERS_FILE: &str = "hc-setups";
+
+/// Name of the file in a setup that records
+/// the shared keystore its conductor uses.
+pub const SHARED_KEYSTORE_FILE: &str = "shared-keystore";
+
+/// Identifies an exported keystore and its format version.
+const MAGIC: &[u8; 8] = b"hckeys01";
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Where a setup's conductor keeps its keys.
+/// Parsed from `ephemeral`, `persistent` or `shared:<path>`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum KeystoreKind {
+    /// The conductor's test keystore that is only in memory.
+    /// Its keys are lost when the conductor stops and aren't secret
+    /// so only use it for setups that are thrown away.
+    Ephemeral,
+    /// The setup's own keystore directory. This is the default.
+    Persistent,
+    /// A keystore directory outside the setup that other setups can use.
+    /// It is created if it doesn't exist.
+    Shared(PathBuf),
+}
+
+impl Default for KeystoreKind {
+    fn default() -> Self {
+        Self::Persistent
+    }
+}
+
+impl FromStr for KeystoreKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ephemeral" => Ok(Self::Ephemeral),
+            "persistent" => Ok(Self::Persistent),
+            _ => match s.strip_prefix("shared:") {
+                Some(path) if !path.is_empty() => Ok(Self::Shared(PathBuf::from(path))),
+                Some(_) => bail!("A shared keystore needs a path, like shared:../keys"),
+                None => bail!(
+                    "{} is not a keystore. Use ephemeral, persistent or shared:<path>",
+                    s
+                ),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for KeystoreKind {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Point the conductor of the setup at `setup_path` at the shared
+/// keystore in `keystore`, creating it if it doesn't exist, and add
+/// the setup to the keystore's users so [`release_keystore`] only
+/// removes it with its last setup.
+/// Returns the absolute path of the keystore.
+pub fn share_keystore(setup_path: &Path, keystore: &Path) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(keystore)
+        .with_context(|| format!("Failed to create the keystore {}", keystore.display()))?;
+    let keystore = keystore.canonicalize()?;
+    let setup_path = setup_path.canonicalize()?;
+    let mut config = read_config(setup_path.clone())?
+        .ok_or_else(|| anyhow!("No conductor config in {}", setup_path.display()))?;
+    config.keystore_path = Some(keystore.clone());
+    write_config(setup_path.clone(), &config);
+
+    let file = setup_path.join(SHARED_KEYSTORE_FILE);
+    std::fs::write(&file, keystore.display().to_string())
+        .with_context(|| format!("Failed to write {}", file.display()))?;
+    let mut users = keystore_users(&keystore)?;
+    if !users.contains(&setup_path) {
+        users.push(setup_path);
+    }
+    write_users(&keystore, &users)?;
+    Ok(keystore)
+}
+
+/// The shared keystore the setup at `setup_path` uses
+/// if it was generated with `--keystore shared:<path>`.
+pub fn shared_keystore(setup_path: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let file = setup_path.join(SHARED_KEYSTORE_FILE);
+    if !file.exists() {
+        return Ok(None);
+    }
+    let dir = std::fs::read_to_string(&file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    Ok(Some(PathBuf::from(dir.trim_end())))
+}
+
+/// The setups that use the shared keystore in `keystore`.
+pub fn keystore_users(keystore: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let file = keystore.join(KEYSTORE_USERS_FILE);
+    if !file.exists() {
+        return Ok(Vec::new());
+    }
+    let users = std::fs::read_to_string(&file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    Ok(users.lines().map(PathBuf::from).collect())
+}
+
+/// Remove the setup at `setup_path` from the users of its shared keystore
+/// and remove the keystore if no other setup uses it.
+/// Returns the keystore if it was removed.
+/// Setups without a shared keystore are left alone.
+pub fn release_keystore(setup_path: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let keystore = match shared_keystore(setup_path)? {
+        Some(keystore) => keystore,
+        None => return Ok(None),
+    };
+    let setup_path = setup_path
+        .canonicalize()
+        .unwrap_or_else(|_| setup_path.to_path_buf());
+    let users: Vec<_> = keystore_users(&keystore)?
+        .into_iter()
+        .filter(|user| *user != setup_path)
+        .collect();
+    if !users.is_empty() {
+        write_users(&keystore, &users)?;
+        return Ok(None);
+    }
+    if keystore.is_dir() {
+        std::fs::remove_dir_all(&keystore)
+            .with_context(|| format!("Failed to remove keystore {}", keystore.display()))?;
+    }
+    Ok(Some(keystore))
+}
+
+/// Replace `old_path` with `new_path` in the users of the
+/// shared keystore of the setup that was moved to `new_path`.
+pub(crate) fn move_keystore_user(old_path: &Path, new_path: &Path) -> anyhow::Result<()> {
+    if let Some(keystore) = shared_keystore(new_path)? {
+        let users: Vec<_> = keystore_users(&keystore)?
+            .into_iter()
+            .map(|user| {
+                if user == old_path {
+                    new_path.to_path_buf()
+                } else {
+                    user
+                }
+            })
+            .collect();
+        write_users(&keystore, &users)?;
+    }
+    Ok(())
+}
+
+fn write_users(keystore: &Path, users: &[PathBuf]) -> anyhow::Result<()> {
+    let file = keystore.join(KEYSTORE_USERS_FILE);
+    let users: String = users
+        .iter()
+        .map(|user| format!("{}\n", user.display()))
+        .collect();
+    std::fs::write(&file, users).with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// Pack the keystore directory into an encrypted export.
+/// Sockets and the pid file of a running keystore are skipped
+/// but the keystore should not be running while it is exported.
+/// The users of a shared keystore are not exported.
+pub fn export_keystore(keystore: &Path, passphrase: &Passphrase) -> anyhow::Result<Vec<u8>> {
+    ensure!(
+        !passphrase.as_bytes().is_empty(),
+        "The passphrase can't be empty"
+    );
+    ensure!(
+        keystore.is_dir(),
+        "There is no keystore at {}",
+        keystore.display()
+    );
+    let mut packed = Vec::new();
+    let mut files = 0;
+    for entry in WalkDir::new(keystore).sort_by(|a, b| a.file_name().cmp(b.file_name())) {
+        let entry = entry?;
+        if !entry.file_type().is_file()
+            || is_pid_file(entry.path())
+            || is_users_file(keystore, entry.path())
+        {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(keystore)
+            .expect("Walked paths are in the keystore");
+        let name = relative
+            .to_str()
+            .ok_or_else(|| anyhow!("Keystore file {} is not utf8", relative.display()))?
+            .replace('\\', "/");
+        let data = std::fs::read(entry.path())
+            .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+        packed.extend_from_slice(&(name.len() as u32).to_be_bytes());
+        packed.extend_from_slice(name.as_bytes());
+        packed.extend_from_slice(&(data.len() as u64).to_be_bytes());
+        packed.extend_from_slice(&data);
+        files += 1;
+    }
+    ensure!(
+        files > 0,
+        "The keystore at {} has no keys to export",
+        keystore.display()
+    );
+    encrypt(packed, passphrase)
+}
+
+/// Unpack an export into the keystore directory and
+/// return the number of files written.
+/// A keystore that already has files is only replaced with `force`.
+/// A shared keystore keeps its users when it is replaced.
+pub fn import_keystore(
+    keystore: &Path,
+    exported: &[u8],
+    passphrase: &Passphrase,
+    force: bool,
+) -> anyhow::Result<usize> {
+    let files = unpack(&decrypt(exported, passphrase)?)?;
+    let has_files = keystore.is_dir()
+        && std::fs::read_dir(keystore)?
+            .filter_map(Result::ok)
+            .any(|entry| entry.file_name() != KEYSTORE_USERS_FILE);
+    if has_files {
+        ensure!(
+            force,
+            "The keystore at {} already has keys. Use --force to replace them",
+            keystore.display()
+        );
+        let users = keystore_users(keystore)?;
+        std::fs::remove_dir_all(keystore)
+            .with_context(|| format!("Failed to remove keystore {}", keystore.display()))?;
+        std::fs::create_dir_all(keystore)?;
+        if !users.is_empty() {
+            write_users(keystore, &users)?;
+        }
+    }
+    std::fs::create_dir_all(keystore)?;
+    for (name, data) in &files {
+        let path = keystore.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_private(&path, data)?;
+    }
+    Ok(files.len())
+}
+
+/// Write a file only the current user can read.
+pub(crate) fn write_private(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    std::io::Write::write_all(&mut file, data)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+fn is_users_file(keystore: &Path, path: &Path) -> bool {
+    path.strip_prefix(keystore)
+        .map_or(false, |p| p == Path::new(KEYSTORE_USERS_FILE))
+}
+
+fn is_pid_file(path: &Path) -> bool {
+    path.file_name().map_or(false, |n| n == "pid") || path.extension().map_or(false, |e| e == "pid")
+}
+
+fn unpack(mut packed: &[u8]) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut files = Vec::new();
+    while !packed.is_empty() {
+        let len = take_len(&mut packed, 4)?;
+        let name = std::str::from_utf8(take(&mut packed, len)?)?;
+        let path = PathBuf::from(name);
+        let is_relative = path.components().all(|c| matches!(c, Component::Normal(_)));
+        ensure!(
+            is_relative && !name.is_empty(),
+            "Refusing to import keystore file {:?} outside the keystore",
+            name
+        );
+        let len = take_len(&mut packed, 8)?;
+        files.push((path, take(&mut packed, len)?.to_vec()));
+    }
+    Ok(files)
+}
+
+fn take<'a>(packed: &mut &'a [u8], len: usize) -> anyhow::Result<&'a [u8]> {
+    if packed.len() < len {
+        bail!("The exported keystore is truncated");
+    }
+    let (taken, rest) = packed.split_at(len);
+    *packed = rest;
+    Ok(taken)
+}
+
+fn take_len(packed: &mut &[u8], bytes: usize) -> anyhow::Result<usize> {
+    let mut len = [0; 8];
+    len[8 - bytes..].copy_from_slice(take(packed, bytes)?);
+    Ok(usize::try_from(u64::from_be_bytes(len))?)
+}
+
+fn key(passphrase: &Passphrase, salt: &[u8]) -> LessSafeKey {
+    let mut key = [0; 32];
+    ring::pbkdf2::derive(
+        ring::pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(PBKDF2_ITERATIONS).expect("Iterations are not zero"),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &key).expect("Key is the right length"))
+}
+
+fn encrypt(mut data: Vec<u8>, passphrase: &Passphrase) -> anyhow::Result<Vec<u8>> {
+    let rng = SystemRandom::new();
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    rng.fill(&mut salt)
+        .and_then(|_| rng.fill(&mut nonce))
+        .map_err(|_| anyhow!("Failed to generate a random salt"))?;
+    key(passphrase, &salt)
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(MAGIC),
+            &mut data,
+        )
+        .map_err(|_| anyhow!("Failed to encrypt the keystore"))?;
+    let mut out = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + data.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&data);
+    Ok(out)
+}
+
+fn decrypt(mut exported: &[u8], passphrase: &Passphrase) -> anyhow::Result<Vec<u8>> {
+    let magic = take(&mut exported, MAGIC.len())
+        .map_err(|_| anyhow!("This is not an exported keystore"))?;
+    ensure!(magic == MAGIC, "This is not an exported keystore");
+    let salt = take(&mut exported, SALT_LEN)?;
+    let mut nonce = [0; NONCE_LEN];
+    nonce.copy_from_slice(take(&mut exported, NONCE_LEN)?);
+    let mut data = exported.to_vec();
+    let len = key(passphrase, salt)
+        .open_in_place(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(MAGIC),
+            &mut data,
+        )
+        .map_err(|_| anyhow!("Wrong passphrase or the exported keystore is corrupt"))?
+        .len();
+    data.truncate(len);
+    Ok(data)
+}
@@ -0,0 +1,167 @@
+//! Launch a conductor with apps installed in one call.
+//! This is useful for integration tests.
+//!
+//! ```no_run
+//! # async fn f() -> anyhow::Result<()> {
+//! use holochain_hc::launch::*;
+//! use std::path::PathBuf;
+//! let launched = launch(LaunchOptions {
+//!     dnas: vec![PathBuf::from("my-dna.dna.gz").into()],
+//!     agents: 2,
+//!     ..Default::default()
+//! })
+//! .await?;
+//! let cells = launched.cmd.list_cell_ids().await?;
+//! assert_eq!(cells.len(), 2);
+//! launched.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::path::Path;
+use std::path::PathBuf;
+
+use holochain_p2p::kitsune_p2p::KitsuneP2pConfig;
+use holochain_types::prelude::InstalledAppId;
+use holochain_types::prelude::InstalledCell;
+
+use crate::calls::AppDna;
+use crate::run::run_async_ready;
+use crate::run::ConductorHandle;
+use crate::run::DEFAULT_KEYSTORE_TIMEOUT;
+use crate::CmdRunner;
+
+/// Options for [`launch`].
+#[derive(Debug, Clone)]
+pub struct LaunchOptions {
+    /// Path to the holochain binary.
+    /// Defaults to `holochain` on the path.
+    pub holochain_path: PathBuf,
+    /// The dnas to install for each agent.
+    /// These can be paths or dnas already in memory.
+    pub dnas: Vec<AppDna>,
+    /// The id of the installed app.
+    /// When there is more than one agent each app id
+    /// has the agent's index appended e.g. `test-app-1`.
+    pub app_id: InstalledAppId,
+    /// The number of agents to generate and install the app for.
+    pub agents: usize,
+    /// An optional network config.
+    pub network: Option<KitsuneP2pConfig>,
+    /// The ports for the app interfaces.
+    /// Use `0` to let the conductor choose.
+    pub app_ports: Vec<u16>,
+    /// Keep the setup directory after shutting down.
+    pub keep: bool,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            holochain_path: PathBuf::from("holochain"),
+            dnas: Vec::new(),
+            app_id: "test-app".to_string(),
+            agents: 1,
+            network: None,
+            app_ports: vec![0],
+            keep: false,
+        }
+    }
+}
+
+/// A running conductor created by [`launch`].
+/// The conductor is stopped and the setup directory is
+/// removed on drop unless [`LaunchOptions::keep`] is set.
+pub struct Launched {
+    /// Connection to the admin interface.
+    pub cmd: CmdRunner,
+    /// The ports of the attached app interfaces.
+    pub app_ports: Vec<u16>,
+    /// The installed cells for each agent.
+    pub cells: Vec<Vec<InstalledCell>>,
+    path: PathBuf,
+    keep: bool,
+    holochain: Option<ConductorHandle>,
+}
+
+/// Generate a new setup, run the conductor, install the dnas for
+/// each agent and attach the app interfaces.
+pub async fn launch(options: LaunchOptions) -> anyhow::Result<Launched> {
+    let LaunchOptions {
+        holochain_path,
+        dnas,
+        app_id,
+        agents,
+        network,
+        app_ports,
+        keep,
+    } = options;
+    let path = crate::generate::generate(network, None, None)?;
+    let (cmd, holochain) = run_async_ready(
+        &holochain_path,
+        path.clone(),
+        None,
+        DEFAULT_KEYSTORE_TIMEOUT,
+    )
+    .await?;
+    // From here the setup is cleaned up if anything fails.
+    let mut launched = Launched {
+        cmd,
+        app_ports: Vec::with_capacity(app_ports.len()),
+        cells: Vec::with_capacity(agents),
+        path,
+        keep,
+        holochain: Some(holochain),
+    };
+    for i in 0..agents {
+        let app_id = if agents == 1 {
+            app_id.clone()
+        } else {
+            format!("{}-{}", app_id, i)
+        };
+        let cells =
+            crate::calls::install_app_dnas(&launched.cmd, app_id, None, dnas.clone()).await?;
+        launched.cells.push(cells);
+    }
+    for port in app_ports {
+        let port = launched
+            .cmd
+            .attach_app_interface_verified(Some(port))
+            .await?;
+        launched.app_ports.push(port);
+    }
+    Ok(launched)
+}
+
+impl Launched {
+    /// The setup directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stop the conductor and wait for it to exit.
+    /// Then remove the setup directory unless [`LaunchOptions::keep`] is set.
+    pub async fn shutdown(mut self) -> anyhow::Result<()> {
+        if let Some(mut holochain) = self.holochain.take() {
+            holochain.kill()?;
+            holochain.wait().await?;
+        }
+        if !self.keep {
+            std::fs::remove_dir_all(&self.path)?;
+            // Already removed so don't try again on drop.
+            self.keep = true;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Launched {
+    fn drop(&mut self) {
+        // Kill the conductor before removing its setup.
+        drop(self.holochain.take());
+        if !self.keep {
+            if let Err(e) = std::fs::remove_dir_all(&self.path) {
+                tracing::warn!("Failed to remove setup {}: {:?}", self.path.display(), e);
+            }
+        }
+    }
+}
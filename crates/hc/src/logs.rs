@@ -0,0 +1,384 @@
+//! Watch a conductor's logs as they are written.
+//!
+//! The admin interface has no way to subscribe to a conductor's tracing
+//! so `hc logs` tails the log files instead. A conductor writes them when
+//! it is run with `hc run --log-dir` or with [`RunConfig::log_dir`], and the
+//! log directory is recorded in the setup in [`LOG_DIR_FILE`] so `hc logs`
+//! finds them however the conductor was started.
+//!
+//! The files are read again from the start when they get shorter,
+//! which is what happens when the conductor is run again.
+//!
+//! What the conductor logs is set apart from hc's own verbosity with a
+//! [`ConductorLog`], e.g. from `hc run --conductor-log warn`. The filter and
+//! format a conductor was started with are recorded in [`CONDUCTOR_LOG_FILE`]
+//! for `hc status`.
+//!
+//! [`RunConfig::log_dir`]: crate::run::RunConfig::log_dir
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use structopt::StructOpt;
+
+use crate::run::ConductorLogs;
+
+/// Name of the file in a setup that records
+/// the directory its conductor's logs are written to.
+pub const LOG_DIR_FILE: &str = "log-dir";
+
+/// Name of the file in a setup that records the log filter
+/// and format its conductor was last started with.
+pub const CONDUCTOR_LOG_FILE: &str = "conductor-log";
+
+/// How often the log files are checked for new lines.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, StructOpt, Clone)]
+/// Print a setup's conductor logs as they are written.
+///
+/// The conductor must have been run with `hc run --log-dir`.
+/// For example `hc logs -i 0 --level warn`.
+pub struct Logs {
+    #[structopt(short, long)]
+    /// The index of the setup in `hc list`.
+    /// Can be left out if there is only one setup.
+    pub index: Option<usize>,
+    #[structopt(long)]
+    /// Only print lines at this level or above:
+    /// `trace`, `debug`, `info`, `warn` or `error`.
+    pub level: Option<LogLevel>,
+    #[structopt(short = "n", long, default_value = "10")]
+    /// How many of the lines already written to print first.
+    pub lines: usize,
+    #[structopt(long)]
+    /// Print the lines already written and stop
+    /// instead of waiting for more.
+    pub no_follow: bool,
+}
+
+/// The level of a tracing line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// `TRACE`
+    Trace,
+    /// `DEBUG`
+    Debug,
+    /// `INFO`
+    Info,
+    /// `WARN`
+    Warn,
+    /// `ERROR`
+    Error,
+}
+
+impl LogLevel {
+    /// The level written as tracing writes it, e.g. `INFO`.
+    fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "TRACE" => Some(Self::Trace),
+            "DEBUG" => Some(Self::Debug),
+            "INFO" => Some(Self::Info),
+            "WARN" => Some(Self::Warn),
+            "ERROR" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_label(&s.to_ascii_uppercase()).ok_or_else(|| {
+            anyhow!(
+                "{} is not a log level. Use trace, debug, info, warn or error",
+                s
+            )
+        })
+    }
+}
+
+/// What the conductor logs and how, independent of hc's own output.
+#[derive(Debug, StructOpt, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ConductorLog {
+    #[structopt(long = "conductor-log")]
+    /// The conductors' log filter, e.g. `warn` or `info,kitsune_p2p=debug`.
+    /// It is the conductors' `RUST_LOG` instead of the one hc is run with
+    /// and wins over `--env RUST_LOG=..`.
+    /// hc's own output is still set with `-v` and `-q`.
+    pub filter: Option<String>,
+    #[structopt(
+        long = "conductor-log-format",
+        possible_values = &["compact", "json"]
+    )]
+    /// How the conductors write their logs: `compact` lines or `json`.
+    pub format: Option<LogFormat>,
+}
+
+/// How the conductor writes its logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// A short human readable line per event.
+    Compact,
+    /// A JSON object per event.
+    Json,
+}
+
+impl LogFormat {
+    /// The value of the conductor's `--structured` option.
+    pub fn structured(self) -> &'static str {
+        match self {
+            Self::Compact => "Compact",
+            Self::Json => "Json",
+        }
+    }
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "compact" => Ok(Self::Compact),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!("{} is not a log format. Use compact or json", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ConductorLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.filter {
+            Some(filter) => write!(f, "filter {}", filter)?,
+            None => write!(f, "default filter")?,
+        }
+        match self.format {
+            Some(LogFormat::Compact) => write!(f, ", compact format"),
+            Some(LogFormat::Json) => write!(f, ", json format"),
+            None => write!(f, ", default format"),
+        }
+    }
+}
+
+impl ConductorLog {
+    /// What a conductor started with these settings and the
+    /// variables in `env` logs. Without a filter it is the
+    /// `RUST_LOG` in `env` or else the one hc is run with.
+    pub fn effective(&self, env: &[(String, String)]) -> Self {
+        let filter = self.filter.clone().or_else(|| {
+            env.iter()
+                .rev()
+                .find(|(key, _)| key == "RUST_LOG")
+                .map(|(_, value)| value.clone())
+                .or_else(|| std::env::var("RUST_LOG").ok())
+        });
+        Self {
+            filter,
+            format: self.format,
+        }
+    }
+}
+
+/// Record the log settings the conductor of the
+/// setup at `setup_path` was started with.
+pub fn record_conductor_log(setup_path: &Path, log: &ConductorLog) -> anyhow::Result<()> {
+    let file = setup_path.join(CONDUCTOR_LOG_FILE);
+    std::fs::write(&file, serde_json::to_string(log)?)
+        .with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// The log settings the conductor of the setup at `setup_path`
+/// was last started with if it was started by hc.
+pub fn read_conductor_log(setup_path: &Path) -> anyhow::Result<Option<ConductorLog>> {
+    let file = setup_path.join(CONDUCTOR_LOG_FILE);
+    if !file.exists() {
+        return Ok(None);
+    }
+    let json = std::fs::read_to_string(&file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let log = serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse {}", file.display()))?;
+    Ok(Some(log))
+}
+
+/// The level of a line the conductor logged,
+/// in the human readable or the JSON format.
+/// Lines that continue the line before, like the lines
+/// of a span or a panic, don't have a level.
+pub fn line_level(line: &str) -> Option<LogLevel> {
+    let line = crate::output::strip_ansi(line);
+    if line.starts_with('{') {
+        let json: serde_json::Value = serde_json::from_str(&line).ok()?;
+        return LogLevel::from_label(json.get("level")?.as_str()?);
+    }
+    line.split_whitespace().find_map(LogLevel::from_label)
+}
+
+/// Keeps the lines at a level or above.
+/// A line without a level is kept if the line before it was
+/// so a multi-line message is kept or dropped as a whole.
+#[derive(Debug, Clone)]
+pub struct LevelFilter {
+    level: Option<LogLevel>,
+    keeping: bool,
+}
+
+impl LevelFilter {
+    /// Keep the lines at `level` or above or all lines if it's `None`.
+    pub fn new(level: Option<LogLevel>) -> Self {
+        Self {
+            level,
+            keeping: true,
+        }
+    }
+
+    /// Should this line be printed.
+    pub fn keep(&mut self, line: &str) -> bool {
+        if let (Some(level), Some(line_level)) = (self.level, line_level(line)) {
+            self.keeping = line_level >= level;
+        }
+        self.keeping
+    }
+}
+
+/// Follows a log file from the end like `tail -f`.
+#[derive(Debug)]
+pub struct LogTail {
+    path: PathBuf,
+    offset: u64,
+    partial: String,
+}
+
+impl LogTail {
+    /// Start following the file at `path`.
+    /// Returns the last `lines` lines already in it.
+    /// The file doesn't have to exist yet.
+    pub fn open(path: PathBuf, lines: usize) -> anyhow::Result<(Self, Vec<String>)> {
+        let mut tail = Self {
+            path,
+            offset: 0,
+            partial: String::new(),
+        };
+        let mut existing = tail.read_new()?;
+        let skip = existing.len().saturating_sub(lines);
+        existing.drain(..skip);
+        Ok((tail, existing))
+    }
+
+    /// The lines written since the last read.
+    /// A line that is still being written is kept until it is finished.
+    pub fn read_new(&mut self) -> anyhow::Result<Vec<String>> {
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to open {}", self.path.display()))
+            }
+        };
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            // The conductor was run again and the file was recreated.
+            self.offset = 0;
+            self.partial.clear();
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+        self.offset += bytes.len() as u64;
+        self.partial.push_str(&String::from_utf8_lossy(&bytes));
+
+        let mut lines: Vec<String> = self.partial.split('\n').map(str::to_string).collect();
+        // What is after the last newline isn't a whole line yet.
+        self.partial = lines.pop().unwrap_or_default();
+        Ok(lines)
+    }
+}
+
+/// Record that the conductor of the setup at `setup_path`
+/// writes its logs to `log_dir` so `hc logs` can find them.
+pub fn record_log_dir(setup_path: &Path, log_dir: &Path) -> anyhow::Result<()> {
+    let file = setup_path.join(LOG_DIR_FILE);
+    std::fs::write(&file, log_dir.display().to_string())
+        .with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// The log files of the setup at `setup_path`
+/// if its conductor was run with a log directory.
+pub fn log_files(setup_path: &Path) -> anyhow::Result<Option<ConductorLogs>> {
+    let file = setup_path.join(LOG_DIR_FILE);
+    if !file.exists() {
+        return Ok(None);
+    }
+    let dir = std::fs::read_to_string(&file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    Ok(Some(ConductorLogs::in_dir(Path::new(dir.trim_end()))))
+}
+
+/// The log directory of the setup at `setup_path` for `hc run --log-dir`.
+/// Each setup gets its own directory in `log_dir` so
+/// several setups can be run with the same `--log-dir`.
+pub fn setup_log_dir(log_dir: &Path, setup_path: &Path) -> PathBuf {
+    match setup_path.file_name() {
+        Some(name) => log_dir.join(name),
+        None => log_dir.to_path_buf(),
+    }
+}
+
+/// Print the logs of a setup from the `.hc` file in `hc_dir`
+/// and keep printing new lines until Ctrl-C is pressed,
+/// unless [`Logs::no_follow`] is set.
+pub async fn logs(hc_dir: PathBuf, cmd: Logs) -> anyhow::Result<()> {
+    let Logs {
+        index,
+        level,
+        lines,
+        no_follow,
+    } = cmd;
+    let (index, path) = crate::save::choose_setup(hc_dir, index, "show the logs of")?;
+    let files = log_files(&path)?.ok_or_else(|| {
+        anyhow!(
+            "Setup {} has no log files. Run it with `hc run --log-dir <dir>` \
+            so its conductor writes its logs where `hc logs` can read them",
+            index
+        )
+    })?;
+    msg!("Logs of setup {} in {}", index, files.stdout.display());
+
+    let mut tails = Vec::new();
+    for file in &[files.stdout, files.stderr] {
+        let (tail, existing) = LogTail::open(file.clone(), lines)?;
+        let mut filter = LevelFilter::new(level);
+        print_lines(&mut filter, existing);
+        tails.push((tail, filter));
+    }
+    if no_follow {
+        return Ok(());
+    }
+
+    let mut ctrl_c = Box::pin(tokio::signal::ctrl_c());
+    loop {
+        tokio::select! {
+            _ = &mut ctrl_c => return Ok(()),
+            _ = tokio::time::delay_for(POLL_INTERVAL) => (),
+        }
+        for (tail, filter) in &mut tails {
+            print_lines(filter, tail.read_new()?);
+        }
+    }
+}
+
+fn print_lines(filter: &mut LevelFilter, lines: Vec<String>) {
+    for line in lines {
+        if filter.keep(&line) {
+            data!("{}", line);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Latency metrics for the requests made by a [`CmdRunner`].
+//!
+//! [`CmdRunner`]: crate::CmdRunner
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How many latencies are kept per request variant for the percentiles.
+/// Past this the percentiles are estimated from a uniform sample
+/// of all the requests so runners that live a long time,
+/// like the ones in a [`RunnerPool`], use a fixed amount of memory.
+///
+/// [`RunnerPool`]: crate::pool::RunnerPool
+const RESERVOIR_SIZE: usize = 1024;
+
+/// Records the latency of every completed request.
+/// The count, total, min and max are exact and the
+/// percentiles come from a fixed size sample of the latencies.
+#[derive(Default)]
+pub(crate) struct MetricsRecorder(Mutex<HashMap<&'static str, LatencyStats>>);
+
+/// The running totals of one request variant.
+#[derive(Default)]
+struct LatencyStats {
+    count: usize,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+    /// A uniform sample of at most [`RESERVOIR_SIZE`] latencies.
+    reservoir: Vec<Duration>,
+    /// The state of the generator that picks which samples are kept.
+    rng: u64,
+}
+
+/// A summary of the requests a [`CmdRunner`] has completed
+/// since it was created or the metrics were last reset.
+///
+/// [`CmdRunner`]: crate::CmdRunner
+#[derive(Debug, Clone, Default)]
+pub struct CmdMetrics {
+    /// The metrics for each request variant that was made.
+    /// Keyed by the name of the variant e.g. `ListCellIds`.
+    pub requests: HashMap<&'static str, RequestMetrics>,
+}
+
+/// The latency of completed requests of one variant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestMetrics {
+    /// The number of completed requests.
+    pub count: usize,
+    /// The fastest request.
+    pub min: Duration,
+    /// The average latency.
+    pub avg: Duration,
+    /// The 95th percentile latency.
+    /// This is estimated from a sample of the requests once there are many of them.
+    pub p95: Duration,
+    /// The slowest request.
+    pub max: Duration,
+}
+
+impl MetricsRecorder {
+    /// Record a completed request.
+    pub(crate) fn record(&self, request: &'static str, elapsed: Duration) {
+        self.0
+            .lock()
+            .expect("Metrics lock poisoned")
+            .entry(request)
+            .or_default()
+            .record(elapsed);
+    }
+
+    /// Summarize the recorded requests.
+    pub(crate) fn metrics(&self) -> CmdMetrics {
+        let samples = self.0.lock().expect("Metrics lock poisoned");
+        let requests = samples
+            .iter()
+            .filter(|(_, stats)| stats.count > 0)
+            .map(|(request, stats)| (*request, RequestMetrics::from_stats(stats)))
+            .collect();
+        CmdMetrics { requests }
+    }
+
+    /// Remove all the recorded requests.
+    pub(crate) fn reset(&self) {
+        self.0.lock().expect("Metrics lock poisoned").clear();
+    }
+}
+
+impl LatencyStats {
+    fn record(&mut self, elapsed: Duration) {
+        if self.count == 0 || elapsed < self.min {
+            self.min = elapsed;
+        }
+        self.max = self.max.max(elapsed);
+        self.count += 1;
+        self.total += elapsed;
+        if self.reservoir.len() < RESERVOIR_SIZE {
+            self.reservoir.push(elapsed);
+        } else {
+            // Keep this sample with a chance of size / count
+            // so every request is equally likely to be in the sample.
+            let i = (self.next_random() % self.count as u64) as usize;
+            if i < RESERVOIR_SIZE {
+                self.reservoir[i] = elapsed;
+            }
+        }
+    }
+
+    /// A xorshift generator is plenty for picking samples.
+    fn next_random(&mut self) -> u64 {
+        if self.rng == 0 {
+            self.rng = 0x9E37_79B9_7F4A_7C15;
+        }
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+impl RequestMetrics {
+    fn from_stats(stats: &LatencyStats) -> Self {
+        let mut sorted = stats.reservoir.clone();
+        sorted.sort();
+        // Nearest rank percentile.
+        let p95 = (sorted.len() * 95 + 99) / 100;
+        Self {
+            count: stats.count,
+            min: stats.min,
+            avg: stats.total / stats.count as u32,
+            p95: sorted[p95.saturating_sub(1)],
+            max: stats.max,
+        }
+    }
+}
+
+impl std::fmt::Display for CmdMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut requests: Vec<_> = self.requests.iter().collect();
+        requests.sort_by_key(|(request, _)| *request);
+        writeln!(
+            f,
+            "{:<20} {:>6} {:>12} {:>12} {:>12} {:>12}",
+            "request", "count", "min", "avg", "p95", "max"
+        )?;
+        for (request, m) in requests {
+            writeln!(
+                f,
+                "{:<20} {:>6} {:>12} {:>12} {:>12} {:>12}",
+                request,
+                m.count,
+                format!("{:?}", m.min),
+                format!("{:?}", m.avg),
+                format!("{:?}", m.p95),
+                format!("{:?}", m.max)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+//! How `hc` shows its output.
+//!
+//! The library only emits [`tracing`] events so nothing is printed
+//! unless a subscriber is installed.
+//! The `hc` binary installs one with [`init`] that prints the results
+//! of commands, like JSON and cell lists, to stdout and everything else,
+//! including the summary lines like the admin port and setup paths,
+//! to stderr. This keeps stdout clean for piping to other tools.
+//!
+//! With `hc --output-file` the results are written to an [`OutputFile`]
+//! instead of stdout, in the [`OutputFormat`] of the file's extension.
+//! The summary lines still go to stderr.
+use std::fmt::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Context as _;
+
+use ansi_term::Color::Blue;
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::Event;
+use tracing::Level;
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::layer::Layer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::EnvFilter;
+
+/// The target of events that are shown on stderr at any verbosity.
+/// These tell a user of the CLI what happened, like the admin port
+/// a conductor is running on.
+pub const SUMMARY_TARGET: &str = "hc_summary";
+
+/// The target of events that are printed to stdout as they are,
+/// without a prefix. These are the results of a command.
+pub const DATA_TARGET: &str = "hc_data";
+
+/// How much `hc` shows on stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Only warnings and errors.
+    Quiet,
+    /// What `hc` is doing.
+    Normal,
+    /// More detail from `hc`.
+    Verbose,
+    /// Everything from `hc` and the websocket.
+    Trace,
+}
+
+impl Verbosity {
+    /// From the number of `-v` flags and the `-q` flag.
+    pub fn from_flags(verbose: u8, quiet: bool) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Trace,
+        }
+    }
+
+    fn directives(self) -> &'static str {
+        match self {
+            Verbosity::Quiet => "holochain_hc=warn,hc=warn",
+            Verbosity::Normal => "holochain_hc=info,hc=info",
+            Verbosity::Verbose => "holochain_hc=debug,hc=debug",
+            Verbosity::Trace => "holochain_hc=trace,hc=trace,holochain_websocket=trace",
+        }
+    }
+}
+
+/// How the data written to an [`OutputFile`] is formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The lines as they would be printed.
+    Text,
+    /// JSON. Data that is JSON is kept as it is and other lines become strings.
+    /// Several results are written as an array.
+    Json,
+    /// The same as [`OutputFormat::Json`] written as YAML.
+    Yaml,
+}
+
+impl OutputFormat {
+    /// `.json` files are JSON, `.yaml` and `.yml` files are YAML
+    /// and any other file is text.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("json") => OutputFormat::Json,
+            Some("yaml") | Some("yml") => OutputFormat::Yaml,
+            _ => OutputFormat::Text,
+        }
+    }
+}
+
+/// A file the data of a command is written to instead of stdout.
+/// Color codes are removed and the file is UTF-8.
+/// Text is written as it comes so a file being followed, like with
+/// `hc logs`, fills up as it goes. JSON and YAML are only written
+/// by [`OutputFile::finish`] because they need all the data.
+#[derive(Debug, Clone)]
+pub struct OutputFile(Arc<Mutex<OutputFileInner>>);
+
+#[derive(Debug)]
+struct OutputFileInner {
+    path: PathBuf,
+    format: OutputFormat,
+    file: std::fs::File,
+    values: Vec<serde_json::Value>,
+    error: Option<std::io::Error>,
+}
+
+impl OutputFile {
+    /// Create or empty the file at `path`
+    /// so a path that can't be written fails before the command runs.
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to create output file {}", path.display()))?;
+        Ok(Self(Arc::new(Mutex::new(OutputFileInner {
+            path: path.to_path_buf(),
+            format: OutputFormat::from_path(path),
+            file,
+            values: Vec::new(),
+            error: None,
+        }))))
+    }
+
+    /// Write the data of one event.
+    /// Errors are kept for [`OutputFile::finish`] to return.
+    pub fn write(&self, data: &str) {
+        use std::io::Write;
+        let data = strip_ansi(data);
+        let mut inner = self.0.lock().expect("Output file lock poisoned");
+        match inner.format {
+            OutputFormat::Text => {
+                if inner.error.is_none() {
+                    if let Err(e) = writeln!(inner.file, "{}", data) {
+                        inner.error = Some(e);
+                    }
+                }
+            }
+            OutputFormat::Json | OutputFormat::Yaml => {
+                match serde_json::from_str::<serde_json::Value>(&data) {
+                    Ok(value) => inner.values.push(value),
+                    Err(_) => inner.values.extend(
+                        data.lines()
+                            .map(|line| serde_json::Value::String(line.to_string())),
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Write the JSON or YAML and fail if any of the writes did.
+    pub fn finish(self) -> anyhow::Result<()> {
+        use std::io::Write;
+        let mut inner = self.0.lock().expect("Output file lock poisoned");
+        let path = inner.path.clone();
+        let failed =
+            |e| anyhow::Error::new(e).context(format!("Failed to write {}", path.display()));
+        if let Some(e) = inner.error.take() {
+            return Err(failed(e));
+        }
+        let mut values = std::mem::take(&mut inner.values);
+        let value = if values.len() == 1 {
+            values.remove(0)
+        } else {
+            serde_json::Value::Array(values)
+        };
+        let contents = match inner.format {
+            OutputFormat::Text => return inner.file.flush().map_err(failed),
+            OutputFormat::Json => serde_json::to_string_pretty(&value)? + "\n",
+            OutputFormat::Yaml => serde_yaml::to_string(&value)?,
+        };
+        inner.file.write_all(contents.as_bytes()).map_err(failed)
+    }
+}
+
+/// Remove terminal color codes.
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip to the letter that ends the escape code.
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Install the subscriber for the `hc` binary.
+/// `RUST_LOG` replaces the filter chosen by the verbosity
+/// but summary lines and data are always shown.
+/// The `hc-admin:` prefix is only colored when stderr is a terminal.
+/// Data is written to the `output_file` instead of stdout if there is one.
+/// Call [`OutputFile::finish`] on the file that is returned once the command is done.
+pub fn init(
+    verbosity: Verbosity,
+    output_file: Option<&Path>,
+) -> anyhow::Result<Option<OutputFile>> {
+    let directives = match std::env::var("RUST_LOG") {
+        Ok(rust_log) if !rust_log.is_empty() => rust_log,
+        _ => verbosity.directives().to_string(),
+    };
+    let filter = EnvFilter::try_new(format!(
+        "{},{}=info,{}=info",
+        directives, SUMMARY_TARGET, DATA_TARGET
+    ))?;
+    let output_file = output_file.map(OutputFile::create).transpose()?;
+    let subscriber = tracing_subscriber::registry().with(filter).with(Output {
+        verbosity,
+        color: atty::is(atty::Stream::Stderr),
+        output_file: output_file.clone(),
+    });
+    tracing::subscriber::set_global_default(subscriber)?;
+    Ok(output_file)
+}
+
+/// Prints data events to stdout, or the output file if there is one,
+/// and the rest to stderr.
+struct Output {
+    verbosity: Verbosity,
+    color: bool,
+    output_file: Option<OutputFile>,
+}
+
+impl<S: Subscriber> Layer<S> for Output {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut message = Message::default();
+        event.record(&mut message);
+        if metadata.target() == DATA_TARGET {
+            match &self.output_file {
+                Some(file) => file.write(message.0.trim()),
+                None => println!("{}", message.0.trim()),
+            }
+            return;
+        }
+        let message = message.0.trim_start();
+        let prefix = if self.color {
+            Blue.bold().paint("hc-admin:").to_string()
+        } else {
+            "hc-admin:".to_string()
+        };
+        if metadata.target() == SUMMARY_TARGET {
+            eprintln!("{} {}", prefix, message);
+            return;
+        }
+        match *metadata.level() {
+            Level::INFO if self.verbosity != Verbosity::Trace => {
+                eprintln!("{} {}", prefix, message)
+            }
+            level => eprintln!("{} {} {}: {}", prefix, level, metadata.target(), message),
+        }
+    }
+}
+
+/// The message of an event followed by its other fields.
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let rest = std::mem::take(&mut self.0);
+            write!(self.0, "{:?}{}", value, rest).ok();
+        } else {
+            write!(self.0, " {}={:?}", field.name(), value).ok();
+        }
+    }
+}
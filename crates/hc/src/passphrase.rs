@@ -0,0 +1,190 @@
+//! Get the passphrase a conductor's keystore is unlocked with.
+//!
+//! A setup whose config has `passphrase_service: {type: cmd}` reads the
+//! passphrase from stdin when the conductor starts. hc gets it from
+//! `--passphrase-file`, then [`PASSPHRASE_ENV`], then by asking for it
+//! with the input hidden if stdin is a terminal, and writes it to the
+//! conductor's stdin. Without a terminal and without a passphrase it
+//! fails straight away instead of leaving the conductor waiting.
+//!
+//! The passphrase is kept in a [`Passphrase`] that zeroes its memory
+//! when it is dropped.
+use std::io::BufRead;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use holochain_conductor_api::config::conductor::ConductorConfig;
+use holochain_conductor_api::config::conductor::PassphraseServiceConfig;
+use zeroize::Zeroizing;
+
+use crate::config::read_config;
+
+/// Set this environment variable to the passphrase for
+/// conductors that ask for one instead of passing `--passphrase-file`.
+pub const PASSPHRASE_ENV: &str = "HC_PASSPHRASE";
+
+/// A keystore passphrase.
+/// Its memory is zeroed when it is dropped and it isn't printed by `Debug`.
+#[derive(Clone)]
+pub struct Passphrase(Zeroizing<Vec<u8>>);
+
+impl Passphrase {
+    /// The passphrase without a trailing newline.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<String> for Passphrase {
+    fn from(passphrase: String) -> Self {
+        Self(Zeroizing::new(passphrase.into_bytes()))
+    }
+}
+
+impl std::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+/// Does the conductor with this config read a passphrase from stdin
+/// when it starts.
+pub fn needs_passphrase(config: &ConductorConfig) -> bool {
+    matches!(
+        config.passphrase_service,
+        Some(PassphraseServiceConfig::Cmd)
+    )
+}
+
+/// Get the passphrase for the conductors of these setups
+/// if any of them need one, asking for it once if stdin is a terminal.
+/// See [`read_passphrase`].
+pub fn for_setups(
+    setup_paths: &[PathBuf],
+    file: Option<&Path>,
+) -> anyhow::Result<Option<Passphrase>> {
+    for path in setup_paths {
+        match read_config(path.clone())? {
+            Some(config) if needs_passphrase(&config) => {
+                return read_passphrase(file, atty::is(atty::Stream::Stdin)).map(Some)
+            }
+            _ => (),
+        }
+    }
+    Ok(None)
+}
+
+/// Read the passphrase from this file, or from [`PASSPHRASE_ENV`]
+/// if there is no file, or else ask for it on the terminal if `interactive`.
+/// A trailing newline in the file is not part of the passphrase.
+/// It is an error if there is no passphrase or it is empty.
+pub fn read_passphrase(file: Option<&Path>, interactive: bool) -> anyhow::Result<Passphrase> {
+    read_passphrase_with_env(file, PASSPHRASE_ENV, interactive)
+}
+
+/// Same as [`read_passphrase`] with the passphrase in the
+/// environment variable `env` instead of [`PASSPHRASE_ENV`],
+/// like [`KEYSTORE_PASSPHRASE_ENV`](crate::keystore::KEYSTORE_PASSPHRASE_ENV)
+/// for exported keystores.
+pub fn read_passphrase_with_env(
+    file: Option<&Path>,
+    env: &str,
+    interactive: bool,
+) -> anyhow::Result<Passphrase> {
+    let passphrase = match file {
+        Some(file) => {
+            let mut passphrase = std::fs::read_to_string(file)
+                .with_context(|| format!("Failed to read passphrase file {}", file.display()))?;
+            trim_newline(&mut passphrase);
+            passphrase
+        }
+        None => match std::env::var(env) {
+            Ok(passphrase) => passphrase,
+            Err(_) if interactive => ask("Keystore passphrase: ")?,
+            Err(_) => bail!(
+                "The keystore needs a passphrase and there is no \
+                terminal to ask for it on. Pass --passphrase-file or set {}",
+                env
+            ),
+        },
+    };
+    let passphrase = Passphrase::from(passphrase);
+    ensure!(
+        !passphrase.as_bytes().is_empty(),
+        "The keystore passphrase can't be empty"
+    );
+    Ok(passphrase)
+}
+
+/// Remove a trailing newline without copying the passphrase.
+fn trim_newline(s: &mut String) {
+    let len = s.trim_end_matches(&['\r', '\n'][..]).len();
+    // Overwrite what is cut off so it doesn't stay in the buffer.
+    let cut = s.split_off(len);
+    drop(Zeroizing::new(cut));
+}
+
+/// Ask for a line on the terminal without echoing what is typed.
+fn ask(prompt: &str) -> anyhow::Result<String> {
+    eprint!("{}", prompt);
+    std::io::Write::flush(&mut std::io::stderr())?;
+    let mut line = String::new();
+    let read = {
+        let _echo = EchoOff::new()?;
+        std::io::stdin().lock().read_line(&mut line)
+    };
+    // The newline that was typed wasn't echoed either.
+    eprintln!();
+    read.context("Failed to read the passphrase")?;
+    trim_newline(&mut line);
+    Ok(line)
+}
+
+/// Turns off terminal echo until it is dropped.
+#[cfg(unix)]
+struct EchoOff(Option<nix::sys::termios::Termios>);
+
+#[cfg(unix)]
+impl EchoOff {
+    fn new() -> anyhow::Result<Self> {
+        use nix::sys::termios;
+        use std::os::unix::io::AsRawFd;
+        let fd = std::io::stdin().as_raw_fd();
+        let original = termios::tcgetattr(fd).context("Failed to read the terminal settings")?;
+        let mut hidden = original.clone();
+        hidden.local_flags.remove(termios::LocalFlags::ECHO);
+        termios::tcsetattr(fd, termios::SetArg::TCSANOW, &hidden)
+            .context("Failed to hide the passphrase as it is typed")?;
+        Ok(Self(Some(original)))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        use nix::sys::termios;
+        use std::os::unix::io::AsRawFd;
+        if let Some(original) = self.0.take() {
+            let fd = std::io::stdin().as_raw_fd();
+            termios::tcsetattr(fd, termios::SetArg::TCSANOW, &original).ok();
+        }
+    }
+}
+
+/// Input can't be hidden on this platform.
+#[cfg(not(unix))]
+struct EchoOff;
+
+#[cfg(not(unix))]
+impl EchoOff {
+    fn new() -> anyhow::Result<Self> {
+        bail!(
+            "Can't hide the passphrase as it is typed on this platform. \
+            Pass --passphrase-file or set {}",
+            PASSPHRASE_ENV
+        )
+    }
+}
@@ -0,0 +1,347 @@
+//! Reuse admin connections across many commands.
+//!
+//! A [`CmdRunner`] already shares one connection between its clones
+//! but tools that talk to many conductors over and over end up
+//! opening a new runner for every operation. A [`RunnerPool`] keeps
+//! one runner per host and admin port and hands out the same connection
+//! each time it is asked for that conductor. Connections that have been
+//! idle for a while are checked before they are handed out and dead ones
+//! are replaced with a new connection. Connections that aren't used
+//! for longer than the idle timeout are closed.
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::time::Duration;
+use std::time::Instant;
+
+use holochain_websocket::WebsocketConfig;
+
+use crate::ports::websocket_config;
+use crate::CmdRunner;
+use crate::DEFAULT_HOST;
+
+/// How long a pooled connection can be idle before it is
+/// checked again on [`RunnerPool::get`].
+pub const DEFAULT_HEALTH_CHECK_AFTER: Duration = Duration::from_secs(5);
+
+/// How long the conductor has to answer a health check
+/// before the connection is treated as dead.
+pub const DEFAULT_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a pooled connection can go unused before it is closed.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A pool of [`CmdRunner`] connections keyed by host and admin port.
+/// This is cheaply clone-able and all clones share the same connections.
+///
+/// ```no_run
+/// # async fn f() -> std::io::Result<()> {
+/// use holochain_hc::RunnerPool;
+/// let pool = RunnerPool::new();
+/// for _ in 0..10 {
+///     // Only the first call connects.
+///     let cmd = pool.get(9000).await?;
+///     cmd.list_cell_ids().await.ok();
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RunnerPool(Arc<Shared>);
+
+struct Shared {
+    config: Box<dyn Fn() -> WebsocketConfig + Send + Sync>,
+    check_after: Duration,
+    check_timeout: Duration,
+    idle_timeout: Duration,
+    /// Has the task that closes idle connections been started.
+    reaping: AtomicBool,
+    /// Each conductor has its own slot so connecting to one
+    /// doesn't hold up getting the others.
+    slots: Mutex<HashMap<Key, Arc<Slot>>>,
+}
+
+/// The host and admin port of a conductor.
+type Key = (String, u16);
+
+type Slot = tokio::sync::Mutex<Option<Pooled>>;
+
+struct Pooled {
+    runner: CmdRunner,
+    /// When the connection was last known to be alive.
+    checked: Instant,
+    /// When the connection was last handed out.
+    used: Instant,
+}
+
+/// A connection from a [`RunnerPool`].
+/// Use it like a [`CmdRunner`]. The connection stays
+/// in the pool when this is dropped.
+#[derive(Clone)]
+pub struct PooledRunner {
+    runner: CmdRunner,
+    port: u16,
+    reused: bool,
+}
+
+/// Configures a [`RunnerPool`] before it is created
+/// so every clone of the pool has the same settings.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// use holochain_hc::RunnerPool;
+/// let pool = RunnerPool::builder()
+///     .idle_timeout(Duration::from_secs(10))
+///     .build();
+/// ```
+pub struct RunnerPoolBuilder {
+    config: Box<dyn Fn() -> WebsocketConfig + Send + Sync>,
+    check_after: Duration,
+    check_timeout: Duration,
+    idle_timeout: Duration,
+}
+
+impl RunnerPoolBuilder {
+    /// Start with hc's [`websocket_config`] and the default timeouts.
+    pub fn new() -> Self {
+        Self {
+            config: Box::new(websocket_config),
+            check_after: DEFAULT_HEALTH_CHECK_AFTER,
+            check_timeout: DEFAULT_HEALTH_CHECK_TIMEOUT,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+
+    /// Connect with the websocket config returned by `config`,
+    /// e.g. to set the [`WebsocketConfig::origin`].
+    pub fn config(mut self, config: impl Fn() -> WebsocketConfig + Send + Sync + 'static) -> Self {
+        self.config = Box::new(config);
+        self
+    }
+
+    /// Check connections that have been idle for longer than this
+    /// before handing them out. Zero checks every time.
+    /// Defaults to [`DEFAULT_HEALTH_CHECK_AFTER`].
+    pub fn health_check_after(mut self, check_after: Duration) -> Self {
+        self.check_after = check_after;
+        self
+    }
+
+    /// How long a health check waits for the conductor.
+    /// Defaults to [`DEFAULT_HEALTH_CHECK_TIMEOUT`].
+    pub fn health_check_timeout(mut self, check_timeout: Duration) -> Self {
+        self.check_timeout = check_timeout;
+        self
+    }
+
+    /// Close connections that haven't been handed out for this long.
+    /// They are connected again the next time they are asked for.
+    /// Defaults to [`DEFAULT_IDLE_TIMEOUT`].
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Create the empty pool.
+    pub fn build(self) -> RunnerPool {
+        RunnerPool(Arc::new(Shared {
+            config: self.config,
+            check_after: self.check_after,
+            check_timeout: self.check_timeout,
+            idle_timeout: self.idle_timeout,
+            reaping: AtomicBool::new(false),
+            slots: Mutex::new(HashMap::new()),
+        }))
+    }
+}
+
+impl Default for RunnerPoolBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RunnerPool {
+    /// Create an empty pool that connects with hc's [`websocket_config`].
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Create an empty pool that connects with the websocket config
+    /// returned by `config`, e.g. to set the [`WebsocketConfig::origin`].
+    pub fn with_config(config: impl Fn() -> WebsocketConfig + Send + Sync + 'static) -> Self {
+        Self::builder().config(config).build()
+    }
+
+    /// Configure the timeouts of a pool before creating it.
+    pub fn builder() -> RunnerPoolBuilder {
+        RunnerPoolBuilder::new()
+    }
+
+    /// Get the connection to the admin interface on this port
+    /// of the [`DEFAULT_HOST`], connecting if there isn't one in
+    /// the pool yet or if the pooled connection has died.
+    pub async fn get(&self, port: u16) -> std::io::Result<PooledRunner> {
+        self.get_on_host(DEFAULT_HOST, port).await
+    }
+
+    /// Get the connection to the admin interface on this port of
+    /// the host like [`RunnerPool::get`].
+    /// The host is used as it is given, so `localhost` and `127.0.0.1`
+    /// get different connections.
+    pub async fn get_on_host(&self, host: &str, port: u16) -> std::io::Result<PooledRunner> {
+        self.spawn_reaper();
+        let key = (host.to_string(), port);
+        let slot = self.slot(&key);
+        let mut slot = slot.lock().await;
+        if let Some(pooled) = slot.as_mut() {
+            if pooled.checked.elapsed() < self.0.check_after
+                || pooled.runner.is_alive(self.0.check_timeout).await
+            {
+                pooled.checked = Instant::now();
+                pooled.used = Instant::now();
+                return Ok(PooledRunner {
+                    runner: pooled.runner.clone(),
+                    port,
+                    reused: true,
+                });
+            }
+            tracing::debug!(host, port, "Evicting dead admin connection");
+            *slot = None;
+        }
+        let runner = match CmdRunner::try_new_on_host(host, port, (self.0.config)()).await {
+            Ok(runner) => runner,
+            Err(e) => {
+                self.slots().remove(&key);
+                return Err(e);
+            }
+        };
+        *slot = Some(Pooled {
+            runner: runner.clone(),
+            checked: Instant::now(),
+            used: Instant::now(),
+        });
+        Ok(PooledRunner {
+            runner,
+            port,
+            reused: false,
+        })
+    }
+
+    /// Remove the connection to this port of the [`DEFAULT_HOST`]
+    /// from the pool.
+    /// It is closed once every [`PooledRunner`] for it is dropped.
+    /// Use this when a command fails in a way that means the
+    /// connection shouldn't be used again.
+    pub fn evict(&self, port: u16) {
+        self.evict_on_host(DEFAULT_HOST, port);
+    }
+
+    /// Remove the connection to this port of the host from the pool
+    /// like [`RunnerPool::evict`].
+    pub fn evict_on_host(&self, host: &str, port: u16) {
+        self.slots().remove(&(host.to_string(), port));
+    }
+
+    /// Remove every connection from the pool.
+    pub fn clear(&self) {
+        self.slots().clear();
+    }
+
+    /// The ports with a connection in the pool on any host.
+    pub fn ports(&self) -> Vec<u16> {
+        let mut ports: Vec<_> = self.slots().keys().map(|k| k.1).collect();
+        ports.sort_unstable();
+        ports.dedup();
+        ports
+    }
+
+    /// The hosts and ports with a connection in the pool.
+    pub fn addresses(&self) -> Vec<(String, u16)> {
+        let mut addresses: Vec<_> = self.slots().keys().cloned().collect();
+        addresses.sort_unstable();
+        addresses
+    }
+
+    fn slot(&self, key: &Key) -> Arc<Slot> {
+        self.slots().entry(key.clone()).or_default().clone()
+    }
+
+    fn slots(&self) -> std::sync::MutexGuard<'_, HashMap<Key, Arc<Slot>>> {
+        self.0.slots.lock().expect("Runner pool poisoned")
+    }
+
+    /// Start the task that closes idle connections the first time a
+    /// connection is asked for, because that is when there is a runtime.
+    /// The task stops once the pool is dropped.
+    fn spawn_reaper(&self) {
+        if self.0.reaping.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let shared = Arc::downgrade(&self.0);
+        let period = self.0.idle_timeout / 2;
+        tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(period.max(Duration::from_millis(10)));
+            loop {
+                interval.tick().await;
+                match Weak::upgrade(&shared) {
+                    Some(shared) => shared.close_idle(),
+                    None => break,
+                }
+            }
+        });
+    }
+}
+
+impl Shared {
+    fn close_idle(&self) {
+        let mut slots = self.slots.lock().expect("Runner pool poisoned");
+        slots.retain(|(host, port), slot| {
+            // A slot that is locked is being handed out so it isn't idle.
+            let idle = match slot.try_lock() {
+                Ok(pooled) => pooled
+                    .as_ref()
+                    .map_or(false, |p| p.used.elapsed() >= self.idle_timeout),
+                Err(_) => false,
+            };
+            if idle {
+                tracing::debug!(%host, port, "Closing idle admin connection");
+            }
+            !idle
+        });
+    }
+}
+
+impl Default for RunnerPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PooledRunner {
+    /// The admin port this connection is to.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Was this connection already in the pool.
+    pub fn is_reused(&self) -> bool {
+        self.reused
+    }
+
+    /// The runner for this connection.
+    pub fn into_inner(self) -> CmdRunner {
+        self.runner
+    }
+}
+
+impl std::ops::Deref for PooledRunner {
+    type Target = CmdRunner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.runner
+    }
+}
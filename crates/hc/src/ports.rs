@@ -1,76 +1,1198 @@
 //! Helpers for working with websockets and ports.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
 
+use anyhow::Context;
 use holochain_conductor_api::{
-    config::conductor::ConductorConfig, AdminInterfaceConfig, InterfaceDriver,
+    config::conductor::ConductorConfig, AdminInterfaceConfig, AdminRequest, AdminResponse,
+    InterfaceDriver, StaticAppInterfaceConfig,
 };
-use holochain_websocket::{websocket_connect, WebsocketConfig, WebsocketReceiver, WebsocketSender};
+use holochain_types::prelude::SerializedBytes;
+use holochain_websocket::{
+    websocket_connect, ConnectionClosedByPeer, ProxyConfig, WebsocketConfig, WebsocketReceiver,
+    WebsocketSender,
+};
+use once_cell::sync::OnceCell;
+use portpicker::is_free;
 use url2::prelude::*;
 
 use crate::config::read_config;
 use crate::config::write_config;
+use crate::config::CONDUCTOR_CONFIG;
 
-/// Update the first admin interface to use this port.
+/// Update the [`hc_admin_interface`] to use this port.
+/// This only changes the config of the setup at `path`
+/// so setups can be forced to different ports at the same time.
+/// To force the port for one run without changing the setup
+/// use [`RunConfig::force_admin_port`].
+///
+/// [`RunConfig::force_admin_port`]: crate::run::RunConfig::force_admin_port
+///
+/// The port is reserved for this setup in the [`PortPool::global`] pool
+/// so forcing the same port on two setups in one process is an error.
 pub fn force_admin_port(path: PathBuf, port: u16) -> anyhow::Result<()> {
+    PortPool::global().reserve(port, &path)?;
     let mut config = read_config(path.clone())?.expect("Failed to find config to force admin port");
     set_admin_port(&mut config, port);
     write_config(path, &config);
     Ok(())
 }
 
-/// List the admin ports for each setup.
-pub async fn get_admin_ports(paths: Vec<PathBuf>) -> anyhow::Result<Vec<u16>> {
-    let mut ports = Vec::new();
-    for p in paths {
-        if let Some(config) = read_config(p)? {
-            if let Some(ai) = config.admin_interfaces {
-                if let Some(AdminInterfaceConfig {
-                    driver: InterfaceDriver::Websocket { port },
-                }) = ai.get(0)
-                {
-                    ports.push(*port)
-                }
+/// Set this environment variable to change the range of the
+/// [`PortPool::global`] pool, e.g. `HC_PORT_RANGE=9000-9100`.
+pub const PORT_RANGE_ENV: &str = "HC_PORT_RANGE";
+
+/// The default range of the [`PortPool::global`] pool.
+/// It is below the ephemeral range Linux hands out for port `0`
+/// so pooled ports don't race conductors that bind their own port.
+pub const DEFAULT_PORT_RANGE: RangeInclusive<u16> = 30000..=32767;
+
+/// Hands out ports from a range without handing the same port to
+/// two setups. Allocations are tracked for the life of the process
+/// so forced and pooled ports of setups run at the same time never collide.
+/// Ports that something else is already bound to are skipped.
+///
+/// Generating a setup doesn't draw from the pool because it doesn't
+/// choose a port. Generated setups ask for port `0` and the conductor binds
+/// a free port itself when it starts, which can't collide. A pooled port
+/// could be bound by another process between the pool checking it is free
+/// and the conductor starting.
+/// Use [`pooled_admin_port`] when the port has to be known before running.
+#[derive(Debug)]
+pub struct PortPool {
+    range: RangeInclusive<u16>,
+    allocated: Mutex<HashMap<u16, PathBuf>>,
+}
+
+/// A port is already allocated to another setup in this process.
+#[derive(Debug)]
+pub struct PortTaken {
+    /// The port.
+    pub port: u16,
+    /// The setup that has the port.
+    pub owner: PathBuf,
+}
+
+impl std::fmt::Display for PortTaken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Port {} is already used by the setup at {}",
+            self.port,
+            self.owner.display()
+        )
+    }
+}
+
+impl std::error::Error for PortTaken {}
+
+impl PortPool {
+    /// Create a pool that hands out ports in this range.
+    pub fn new(range: RangeInclusive<u16>) -> Self {
+        Self {
+            range,
+            allocated: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The pool shared by everything in this process.
+    /// Its range is read from [`PORT_RANGE_ENV`] the first time it is used
+    /// and defaults to [`DEFAULT_PORT_RANGE`].
+    pub fn global() -> &'static PortPool {
+        static POOL: OnceCell<PortPool> = OnceCell::new();
+        POOL.get_or_init(|| {
+            let range = match std::env::var(PORT_RANGE_ENV) {
+                Ok(range) => parse_port_range(&range).unwrap_or_else(|e| {
+                    tracing::warn!(?e, "Ignoring {}", PORT_RANGE_ENV);
+                    DEFAULT_PORT_RANGE
+                }),
+                Err(_) => DEFAULT_PORT_RANGE,
+            };
+            PortPool::new(range)
+        })
+    }
+
+    /// The range ports are allocated from.
+    pub fn range(&self) -> RangeInclusive<u16> {
+        self.range.clone()
+    }
+
+    /// Allocate a free port in the range to the setup at `owner`.
+    /// A setup that already has a port in the pool gets another one.
+    pub fn allocate(&self, owner: &Path) -> anyhow::Result<u16> {
+        let mut allocated = self.allocated.lock().expect("Port pool poisoned");
+        let port = self
+            .range
+            .clone()
+            .find(|port| !allocated.contains_key(port) && is_free(*port))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No free ports left in {}-{}",
+                    self.range.start(),
+                    self.range.end()
+                )
+            })?;
+        allocated.insert(port, owner.to_path_buf());
+        Ok(port)
+    }
+
+    /// Reserve a chosen port for the setup at `owner`.
+    /// The port doesn't need to be in the range.
+    /// Reserving a port the setup already has is fine
+    /// but a port another setup has is a [`PortTaken`] error.
+    pub fn reserve(&self, port: u16, owner: &Path) -> Result<(), PortTaken> {
+        let mut allocated = self.allocated.lock().expect("Port pool poisoned");
+        match allocated.get(&port) {
+            Some(current) if current != owner => Err(PortTaken {
+                port,
+                owner: current.clone(),
+            }),
+            _ => {
+                allocated.insert(port, owner.to_path_buf());
+                Ok(())
             }
         }
     }
+
+    /// Give a port back so it can be allocated again.
+    pub fn release(&self, port: u16) {
+        self.allocated
+            .lock()
+            .expect("Port pool poisoned")
+            .remove(&port);
+    }
+
+    /// The setup a port is allocated to.
+    pub fn owner(&self, port: u16) -> Option<PathBuf> {
+        self.allocated
+            .lock()
+            .expect("Port pool poisoned")
+            .get(&port)
+            .cloned()
+    }
+}
+
+/// Parse a port range like `9000-9100`.
+pub fn parse_port_range(range: &str) -> anyhow::Result<RangeInclusive<u16>> {
+    let mut parts = range.splitn(2, '-');
+    let (start, end) = match (parts.next(), parts.next()) {
+        (Some(start), Some(end)) => (start, end),
+        _ => anyhow::bail!("Port range {:?} should look like 9000-9100", range),
+    };
+    let start: u16 = start
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid start of port range {:?}", range))?;
+    let end: u16 = end
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid end of port range {:?}", range))?;
+    if start == 0 || start > end {
+        anyhow::bail!("Port range {:?} is empty or starts at 0", range);
+    }
+    Ok(start..=end)
+}
+
+/// Allocate an admin port from the pool for the setup at `path`
+/// and write it to the setup's config like [`force_admin_port`].
+/// Pass the port to [`RunConfig::force_admin_port`] to keep it when running.
+///
+/// [`RunConfig::force_admin_port`]: crate::run::RunConfig::force_admin_port
+pub fn pooled_admin_port(path: PathBuf, pool: &PortPool) -> anyhow::Result<u16> {
+    let mut config = read_config(path.clone())?
+        .ok_or_else(|| anyhow::anyhow!("Failed to find config in {}", path.display()))?;
+    let port = pool.allocate(&path)?;
+    set_admin_port(&mut config, port);
+    write_config(path, &config);
+    Ok(port)
+}
+
+/// The file name of the admin socket in a setup
+/// generated with `hc generate --uds`.
+pub const ADMIN_SOCKET_FILE: &str = "admin.sock";
+
+/// Where a conductor's admin interface listens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminAddress {
+    /// A websocket on this port of the local host.
+    Port(u16),
+    /// A websocket on the unix domain socket at this path.
+    Socket(PathBuf),
+}
+
+impl std::fmt::Display for AdminAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdminAddress::Port(port) => write!(f, "port {}", port),
+            AdminAddress::Socket(path) => write!(f, "socket {}", path.display()),
+        }
+    }
+}
+
+impl From<&InterfaceDriver> for AdminAddress {
+    fn from(driver: &InterfaceDriver) -> Self {
+        match driver {
+            InterfaceDriver::Websocket { port } => AdminAddress::Port(*port),
+            InterfaceDriver::UnixSocket { path } => AdminAddress::Socket(path.clone()),
+        }
+    }
+}
+
+/// The admin interface hc connects to in a config with several,
+/// like one for hc and one for a dashboard on another network.
+/// It is the first interface that is only reachable from this machine,
+/// a unix socket or a websocket on the loopback,
+/// or the first interface if none are.
+pub fn hc_admin_interface(config: &ConductorConfig) -> Option<&AdminInterfaceConfig> {
+    let index = hc_admin_index(config);
+    config.admin_interfaces.as_ref()?.get(index)
+}
+
+fn hc_admin_interface_mut(config: &mut ConductorConfig) -> Option<&mut AdminInterfaceConfig> {
+    let index = hc_admin_index(config);
+    config.admin_interfaces.as_mut()?.get_mut(index)
+}
+
+fn hc_admin_index(config: &ConductorConfig) -> usize {
+    config
+        .admin_interfaces
+        .iter()
+        .flatten()
+        .position(|i| is_local_interface(i, config.interface_address))
+        .unwrap_or(0)
+}
+
+fn is_local_interface(interface: &AdminInterfaceConfig, interface_address: Option<IpAddr>) -> bool {
+    match interface.driver {
+        InterfaceDriver::UnixSocket { .. } => true,
+        // The conductor listens on the loopback without an address.
+        InterfaceDriver::Websocket { .. } => interface
+            .address
+            .or(interface_address)
+            .map_or(true, |ip| ip.is_loopback()),
+    }
+}
+
+/// The address of the [`hc_admin_interface`] in the setup's config.
+/// This is `None` if the setup has no config or admin interface.
+pub fn get_admin_address(path: PathBuf) -> anyhow::Result<Option<AdminAddress>> {
+    Ok(read_config(path)?
+        .as_ref()
+        .and_then(hc_admin_interface)
+        .map(|i| AdminAddress::from(&i.driver)))
+}
+
+/// The port of the [`hc_admin_interface`] in the setup's config,
+/// read from disk without starting anything.
+/// This is `None` if the conductor picks a free port when it is run,
+/// either because the port is `0` or there is no admin interface yet,
+/// and for setups that use an admin socket instead of a port.
+///
+/// Unlike [`get_admin_address`] a missing config is an error
+/// and parse errors name the config file.
+pub fn admin_port_from_config(setup_path: &Path) -> anyhow::Result<Option<u16>> {
+    let config = parse_config_file(setup_path)?;
+    let port = hc_admin_interface(&config).and_then(|i| match i.driver {
+        InterfaceDriver::Websocket { port: 0 } => None,
+        InterfaceDriver::Websocket { port } => Some(port),
+        InterfaceDriver::UnixSocket { .. } => None,
+    });
+    Ok(port)
+}
+
+/// The host to connect to the [`hc_admin_interface`] of the setup at `setup_path`
+/// in the form `--host` takes, e.g. `127.0.0.1` or `::1`.
+/// It follows the interface's address or else the `interface_address`
+/// in the config so a setup generated with `hc gen --bind ::1`
+/// is connected to over IPv6.
+/// This is `None` for setups that use an admin socket.
+/// Errors like [`admin_port_from_config`].
+pub fn admin_host_from_config(setup_path: &Path) -> anyhow::Result<Option<String>> {
+    let config = parse_config_file(setup_path)?;
+    let interface = hc_admin_interface(&config);
+    if let Some(AdminInterfaceConfig {
+        driver: InterfaceDriver::UnixSocket { .. },
+        ..
+    }) = interface
+    {
+        return Ok(None);
+    }
+    let address = interface
+        .and_then(|i| i.address)
+        .or(config.interface_address);
+    let host = match address {
+        // A conductor listening on every address is on the loopback too.
+        Some(std::net::IpAddr::V4(ip)) if ip.is_unspecified() => DEFAULT_HOST.to_string(),
+        Some(std::net::IpAddr::V6(ip)) if ip.is_unspecified() => DEFAULT_HOST_V6.to_string(),
+        Some(ip) => ip.to_string(),
+        None => DEFAULT_HOST.to_string(),
+    };
+    Ok(Some(host))
+}
+
+/// The origins the [`hc_admin_interface`] of the setup at `setup_path`
+/// accepts or `None` if it accepts any origin.
+/// hc attaches app interfaces that accept the same origins.
+/// Errors like [`admin_port_from_config`].
+pub fn allowed_origins_from_config(setup_path: &Path) -> anyhow::Result<Option<Vec<String>>> {
+    let config = parse_config_file(setup_path)?;
+    Ok(hc_admin_interface(&config).and_then(|i| i.allowed_origins.clone()))
+}
+
+/// Set the origins the admin interfaces of the setup at `path` accept,
+/// or let them accept any origin if it's `None`. See [`allowed_origins`].
+/// The saved overrides are updated too so `hc config` doesn't undo it.
+/// This is what `hc generate --allowed-origins` and `hc config set-origins` do.
+pub fn use_allowed_origins(
+    path: PathBuf,
+    allowed_origins: Option<Vec<String>>,
+) -> anyhow::Result<()> {
+    let mut config = read_config(path.clone())?
+        .ok_or_else(|| anyhow::anyhow!("Failed to find config in {}", path.display()))?;
+    for interface in config.admin_interfaces.iter_mut().flatten() {
+        interface.allowed_origins = allowed_origins.clone();
+    }
+    for interface in config.app_interfaces.iter_mut().flatten() {
+        interface.allowed_origins = allowed_origins.clone();
+    }
+    write_config(path.clone(), &config);
+    let mut spec = crate::config::read_overrides(path.clone())?;
+    if spec.admin_interfaces.is_some() || spec.app_interfaces.is_some() {
+        for interface in spec.admin_interfaces.iter_mut().flatten() {
+            interface.allowed_origins = allowed_origins.clone();
+        }
+        for interface in spec.app_interfaces.iter_mut().flatten() {
+            interface.allowed_origins = allowed_origins.clone();
+        }
+        crate::config::write_overrides(&path, &spec)?;
+    }
+    Ok(())
+}
+
+/// Add app interfaces on these ports to the config of the setup at `path`
+/// so the conductor starts with them instead of hc attaching them
+/// each time it is run. They accept the same origins as the
+/// [`hc_admin_interface`]. The saved overrides are updated too
+/// so `hc config` keeps them.
+/// This is what `hc generate --app-port` does.
+pub fn use_config_app_ports(path: PathBuf, ports: &[u16]) -> anyhow::Result<()> {
+    let allowed_origins = allowed_origins_from_config(&path)?;
+    let mut interfaces = crate::config::read_overrides(path.clone())?
+        .app_interfaces
+        .unwrap_or_default();
+    for port in ports {
+        if !interfaces.iter().any(|i| i.port == *port) {
+            interfaces.push(StaticAppInterfaceConfig {
+                port: *port,
+                allowed_origins: allowed_origins.clone(),
+            });
+        }
+    }
+    crate::config::apply_overrides(
+        path,
+        crate::config::ConductorConfigSpec {
+            app_interfaces: Some(interfaces),
+            ..Default::default()
+        },
+    )?;
+    Ok(())
+}
+
+/// The ports of the app interfaces in the config of the setup at
+/// `setup_path`, which the conductor starts with.
+/// Errors like [`admin_port_from_config`].
+pub fn app_ports_from_config(setup_path: &Path) -> anyhow::Result<Vec<u16>> {
+    let config = parse_config_file(setup_path)?;
+    Ok(config
+        .app_interfaces
+        .iter()
+        .flatten()
+        .map(|i| i.port)
+        .collect())
+}
+
+/// Check hc isn't about to attach an app interface on a port
+/// the setup's conductor already has one on from its config.
+/// Setups without a config have no app interfaces in it.
+pub(crate) fn check_not_in_config(setup_path: &Path, port: u16) -> anyhow::Result<()> {
+    if !setup_path.join(CONDUCTOR_CONFIG).exists() {
+        return Ok(());
+    }
+    anyhow::ensure!(
+        !app_ports_from_config(setup_path)?.contains(&port),
+        "App port {} is already in the conductor config of {} \
+        so the conductor starts with an app interface on it. \
+        Use the interface from the config instead of attaching another one",
+        port,
+        setup_path.display()
+    );
+    Ok(())
+}
+
+fn parse_config_file(setup_path: &Path) -> anyhow::Result<ConductorConfig> {
+    let config_path = setup_path.join(CONDUCTOR_CONFIG);
+    let yaml = std::fs::read_to_string(&config_path)
+        .with_context(|| format!("Failed to read conductor config {}", config_path.display()))?;
+    serde_yaml::from_str(&yaml).with_context(|| {
+        format!(
+            "Failed to parse conductor config {}. \
+            Expected a holochain conductor config with `admin_interfaces` \
+            like `- driver: {{type: websocket, port: 1234}}`",
+            config_path.display()
+        )
+    })
+}
+
+/// Update the [`hc_admin_interface`] of the setup at `path`
+/// to listen on [`ADMIN_SOCKET_FILE`] in the setup.
+/// This is what `hc generate --uds` does.
+/// Unix sockets are only supported on unix.
+pub fn use_admin_socket(path: PathBuf) -> anyhow::Result<PathBuf> {
+    let mut config = read_config(path.clone())?
+        .ok_or_else(|| anyhow::anyhow!("Failed to find config in {}", path.display()))?;
+    let socket = path.join(ADMIN_SOCKET_FILE);
+    let interface = AdminInterfaceConfig {
+        driver: InterfaceDriver::UnixSocket {
+            path: socket.clone(),
+        },
+        allowed_origins: None,
+        address: None,
+    };
+    match hc_admin_interface_mut(&mut config) {
+        // Keep the rest of the interface config, like the allowed origins.
+        Some(admin_interface) => admin_interface.driver = interface.driver,
+        None => config.admin_interfaces = Some(vec![interface]),
+    }
+    write_config(path, &config);
+    tracing::info!("Admin socket set to: {}", socket.display());
+    Ok(socket)
+}
+
+/// Who an admin interface of a setup is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminRole {
+    /// The [`hc_admin_interface`].
+    Hc,
+    /// Another client, like a dashboard on another network.
+    External,
+}
+
+impl std::fmt::Display for AdminRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdminRole::Hc => write!(f, "hc"),
+            AdminRole::External => write!(f, "external"),
+        }
+    }
+}
+
+impl std::str::FromStr for AdminRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hc" => Ok(AdminRole::Hc),
+            "external" => Ok(AdminRole::External),
+            _ => anyhow::bail!("{:?} is not an admin interface role", s),
+        }
+    }
+}
+
+/// One of the admin interfaces of a setup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminInterface {
+    /// Who the interface is for.
+    pub role: AdminRole,
+    /// The address a websocket interface listens on
+    /// or `None` if it is the loopback or a unix socket.
+    pub host: Option<IpAddr>,
+    /// The port or unix socket.
+    /// The port is `0` if the conductor picks it and hasn't been run by hc yet.
+    pub address: AdminAddress,
+}
+
+impl std::fmt::Display for AdminInterface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.address, self.host) {
+            (AdminAddress::Port(port), Some(host)) => {
+                write!(f, "{}", SocketAddr::new(host, *port))
+            }
+            (address, _) => write!(f, "{}", address),
+        }
+    }
+}
+
+/// The file in a setup that lists its admin interfaces
+/// with the ports the conductor bound when hc last started it.
+pub const ADMIN_INTERFACES_FILE: &str = "admin-interfaces";
+
+/// The admin interfaces in a conductor config
+/// with the ports in the config.
+pub fn admin_interfaces_in_config(config: &ConductorConfig) -> Vec<AdminInterface> {
+    let hc = hc_admin_index(config);
+    config
+        .admin_interfaces
+        .iter()
+        .flatten()
+        .enumerate()
+        .map(|(i, interface)| AdminInterface {
+            role: if i == hc {
+                AdminRole::Hc
+            } else {
+                AdminRole::External
+            },
+            host: match interface.driver {
+                InterfaceDriver::Websocket { .. } => interface.address.or(config.interface_address),
+                InterfaceDriver::UnixSocket { .. } => None,
+            },
+            address: AdminAddress::from(&interface.driver),
+        })
+        .collect()
+}
+
+/// Fill in the ports the conductor reported it bound.
+/// The conductor reports the ports of its websocket interfaces
+/// in the order they are in the config.
+pub(crate) fn bound_admin_interfaces(
+    interfaces: Vec<AdminInterface>,
+    reported: &[AdminAddress],
+) -> Vec<AdminInterface> {
+    let mut ports = reported.iter().filter_map(|a| match a {
+        AdminAddress::Port(port) => Some(*port),
+        AdminAddress::Socket(_) => None,
+    });
+    interfaces
+        .into_iter()
+        .map(|interface| match interface.address {
+            AdminAddress::Port(port) => AdminInterface {
+                address: AdminAddress::Port(ports.next().unwrap_or(port)),
+                ..interface
+            },
+            AdminAddress::Socket(_) => interface,
+        })
+        .collect()
+}
+
+/// List the admin interfaces of the setup at `setup_path`
+/// as hc last started its conductor.
+/// A setup that hasn't been run by hc lists the interfaces in its config.
+pub fn get_admin_interfaces(setup_path: &Path) -> anyhow::Result<Vec<AdminInterface>> {
+    let path = setup_path.join(ADMIN_INTERFACES_FILE);
+    if !path.is_file() {
+        return Ok(admin_interfaces_in_config(&parse_config_file(setup_path)?));
+    }
+    std::fs::read_to_string(&path)?
+        .lines()
+        .map(|line| {
+            parse_admin_interface(line).with_context(|| {
+                format!("Invalid admin interface {:?} in {}", line, path.display())
+            })
+        })
+        .collect()
+}
+
+/// Parse a line like `hc 127.0.0.1:9000` or `hc /setup/admin.sock`.
+fn parse_admin_interface(line: &str) -> anyhow::Result<AdminInterface> {
+    let mut parts = line.trim().splitn(2, ' ');
+    let (role, address) = match (parts.next(), parts.next()) {
+        (Some(role), Some(address)) => (role.parse()?, address),
+        _ => anyhow::bail!("Expected a role and an address"),
+    };
+    Ok(match address.parse::<SocketAddr>() {
+        Ok(addr) => AdminInterface {
+            role,
+            host: Some(addr.ip()),
+            address: AdminAddress::Port(addr.port()),
+        },
+        Err(_) => match address.strip_prefix("port ") {
+            Some(port) => AdminInterface {
+                role,
+                host: None,
+                address: AdminAddress::Port(port.parse()?),
+            },
+            None => AdminInterface {
+                role,
+                host: None,
+                address: AdminAddress::Socket(PathBuf::from(address)),
+            },
+        },
+    })
+}
+
+/// Replace the setup's list of admin interfaces.
+pub(crate) fn record_admin_interfaces(
+    setup_path: &Path,
+    interfaces: &[AdminInterface],
+) -> anyhow::Result<()> {
+    let contents: String = interfaces
+        .iter()
+        .map(|i| match &i.address {
+            AdminAddress::Socket(path) => format!("{} {}\n", i.role, path.display()),
+            AdminAddress::Port(_) => format!("{} {}\n", i.role, i),
+        })
+        .collect();
+    std::fs::write(setup_path.join(ADMIN_INTERFACES_FILE), contents)?;
+    Ok(())
+}
+
+/// Is something accepting connections on this admin interface.
+/// Interfaces on every address or the loopback are checked on the loopback
+/// and a port of `0` is never reachable.
+pub async fn is_reachable(interface: &AdminInterface, timeout: Duration) -> bool {
+    let check = async {
+        match (&interface.address, interface.host) {
+            (AdminAddress::Port(0), _) => false,
+            (AdminAddress::Port(port), Some(host)) if !host.is_unspecified() => {
+                tokio::net::TcpStream::connect(SocketAddr::new(host, *port))
+                    .await
+                    .is_ok()
+            }
+            (AdminAddress::Port(port), _) => {
+                let v4 = (DEFAULT_HOST, *port);
+                let v6 = (DEFAULT_HOST_V6, *port);
+                tokio::net::TcpStream::connect(v4).await.is_ok()
+                    || tokio::net::TcpStream::connect(v6).await.is_ok()
+            }
+            (AdminAddress::Socket(path), _) => is_socket_reachable(path).await,
+        }
+    };
+    tokio::time::timeout(timeout, check).await.unwrap_or(false)
+}
+
+#[cfg(unix)]
+async fn is_socket_reachable(path: &Path) -> bool {
+    tokio::net::UnixStream::connect(path).await.is_ok()
+}
+
+#[cfg(not(unix))]
+async fn is_socket_reachable(_path: &Path) -> bool {
+    false
+}
+
+/// The file in a setup that lists the app ports attached
+/// since hc last started the conductor.
+const APP_PORTS_FILE: &str = "app-ports";
+
+/// List the app ports of this setup's conductor: the ones in its
+/// config, see [`app_ports_from_config`], and then the ones attached
+/// since hc last started it. Only app interfaces attached through a
+/// [`CmdRunner`] that knows the setup path are recorded.
+///
+/// [`CmdRunner`]: crate::CmdRunner
+pub fn get_app_ports(setup_path: &Path) -> anyhow::Result<Vec<u16>> {
+    let mut ports = if setup_path.join(CONDUCTOR_CONFIG).exists() {
+        app_ports_from_config(setup_path)?
+    } else {
+        Vec::new()
+    };
+    for port in attached_app_ports(setup_path)? {
+        if !ports.contains(&port) {
+            ports.push(port);
+        }
+    }
     Ok(ports)
 }
 
-pub(crate) async fn get_admin_api(port: u16) -> std::io::Result<WebsocketSender> {
-    tracing::debug!(port);
-    websocket_client_by_port(port).await.map(|p| p.0)
+fn attached_app_ports(setup_path: &Path) -> anyhow::Result<Vec<u16>> {
+    let path = setup_path.join(APP_PORTS_FILE);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    std::fs::read_to_string(&path)?
+        .lines()
+        .map(|line| {
+            line.trim()
+                .parse::<u16>()
+                .with_context(|| format!("Invalid app port {:?} in {}", line, path.display()))
+        })
+        .collect()
+}
+
+/// Add an attached app port to the setup's list.
+pub(crate) fn record_app_port(setup_path: &Path, port: u16) -> anyhow::Result<()> {
+    let mut ports = attached_app_ports(setup_path)?;
+    if !ports.contains(&port) {
+        ports.push(port);
+    }
+    write_app_ports(setup_path, &ports)
+}
+
+/// Forget the app ports when the conductor is restarted.
+pub(crate) fn clear_app_ports(setup_path: &Path) -> anyhow::Result<()> {
+    write_app_ports(setup_path, &[])
+}
+
+fn write_app_ports(setup_path: &Path, ports: &[u16]) -> anyhow::Result<()> {
+    let contents: String = ports.iter().map(|p| format!("{}\n", p)).collect();
+    std::fs::write(setup_path.join(APP_PORTS_FILE), contents)?;
+    Ok(())
+}
+
+/// The largest message or frame a connection accepts by default.
+/// State dumps and agent info can be large so this is generous.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// The `hc` binary reads this environment variable when it starts
+/// to ask conductors not to compress responses.
+/// This is what `hc --no-compression` does.
+/// The library doesn't read it, set [`ConnectOptions::compression`] instead.
+pub const NO_COMPRESSION_ENV: &str = "HC_NO_COMPRESSION";
+
+/// The `Origin` header hc sends unless another is set
+/// with [`ConnectOptions::origin`].
+/// Generated setups only allow this origin on their admin interface.
+pub const DEFAULT_ORIGIN: &str = "hc-cli";
+
+/// The `hc` binary reads this environment variable when it starts
+/// if `--origin` isn't passed. The library doesn't read it.
+pub const ORIGIN_ENV: &str = "HC_ORIGIN";
+
+/// Pass this as an allowed origin to accept connections from any origin.
+pub const ANY_ORIGIN: &str = "*";
+
+/// The `allowed_origins` of an interface that accepts `origins`,
+/// or `None` to accept any origin if one of them is [`ANY_ORIGIN`].
+/// hc's own `origin` is always allowed so hc can still connect.
+pub fn allowed_origins(origin: &str, origins: Vec<String>) -> Option<Vec<String>> {
+    if origins.iter().any(|o| o == ANY_ORIGIN) {
+        return None;
+    }
+    let mut allowed = vec![origin.to_string()];
+    for o in origins {
+        if !allowed.contains(&o) {
+            allowed.push(o);
+        }
+    }
+    Some(allowed)
+}
+
+/// The `hc` binary reads this environment variable when it starts
+/// if `--proxy` isn't passed, e.g. `HC_PROXY=socks5://bastion:1080`.
+/// `http://` proxies are asked to `CONNECT`.
+/// The library doesn't read it, set [`ConnectOptions::proxy`] instead.
+pub const PROXY_ENV: &str = "HC_PROXY";
+
+/// The `hc` binary reads this environment variable when it starts
+/// to also use the [`PROXY_ENV`] proxy for conductors on this machine.
+/// This is what `hc --proxy-local` does.
+pub const PROXY_LOCAL_ENV: &str = "HC_PROXY_LOCAL";
+
+/// How the connections of a command reach conductors.
+/// The `hc` binary builds this once from its flags and passes it
+/// to the connections it makes, so other connections in the process
+/// and the conductors it starts aren't changed by it.
+#[derive(Clone)]
+pub struct ConnectOptions {
+    /// Send this `Origin` header. [default = [`DEFAULT_ORIGIN`]]
+    pub origin: String,
+    /// Connect through this proxy. Local hosts skip it unless
+    /// [`ProxyConfig::force_local`] is set. [default = None]
+    pub proxy: Option<ProxyConfig>,
+    /// Ask conductors that support it to compress large responses.
+    /// [default = true]
+    pub compression: bool,
+    /// Ping idle conductors every [`DEFAULT_PING_INTERVAL`].
+    /// The `hc` binary turns this off for `hc call` and `hc list`
+    /// because their calls are over before a dead conductor would be noticed.
+    /// [default = true]
+    pub keepalive: bool,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            origin: DEFAULT_ORIGIN.to_string(),
+            proxy: None,
+            compression: true,
+            keepalive: true,
+        }
+    }
+}
+
+impl ConnectOptions {
+    /// The [`websocket_config`] for a connection with these options.
+    pub fn websocket_config(&self) -> WebsocketConfig {
+        let mut config = websocket_config()
+            .origin(self.origin.clone())
+            .accept_compressed_responses(self.compression);
+        if !self.keepalive {
+            config = config.ping_interval_ms(0);
+        }
+        config.proxy = self.proxy.clone();
+        config
+    }
+}
+
+impl std::fmt::Debug for ConnectOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The proxy url can have a password in it.
+        let proxy = self.proxy.as_ref().map(|p| {
+            (
+                p.url.scheme().to_string(),
+                p.url.host_str().map(str::to_string),
+                p.url.port(),
+            )
+        });
+        f.debug_struct("ConnectOptions")
+            .field("origin", &self.origin)
+            .field("proxy", &proxy)
+            .field("compression", &self.compression)
+            .field("keepalive", &self.keepalive)
+            .finish()
+    }
+}
+
+/// How often an idle connection pings the conductor.
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How many pings in a row can go unanswered before the connection
+/// is treated as lost and pending requests fail.
+pub const DEFAULT_MAX_MISSED_PINGS: usize = 3;
+
+/// The websocket config used for connections to a conductor.
+/// Use the builder-style setters to change the limits
+/// and pass it to [`CmdRunner::try_new_with_config`].
+///
+/// Large responses are compressed by conductors that support it.
+///
+/// Idle connections ping the conductor every [`DEFAULT_PING_INTERVAL`]
+/// unless [`ConnectOptions::keepalive`] is off. If a conductor is killed
+/// requests fail with a "Connection lost" error after
+/// [`DEFAULT_MAX_MISSED_PINGS`] pings instead of hanging.
+/// A large response that is still arriving counts as an answer.
+///
+/// Connections send the [`DEFAULT_ORIGIN`] as their `Origin` header
+/// and don't go through a proxy. Use [`ConnectOptions`] to change them
+/// or to turn compression off.
+///
+/// [`CmdRunner::try_new_with_config`]: crate::CmdRunner::try_new_with_config
+pub fn websocket_config() -> WebsocketConfig {
+    WebsocketConfig::default()
+        .max_message_size(DEFAULT_MAX_MESSAGE_SIZE)
+        .max_frame_size(DEFAULT_MAX_MESSAGE_SIZE)
+        .accept_compressed_responses(true)
+        .ping_interval_ms(DEFAULT_PING_INTERVAL.as_millis() as usize)
+        .max_missed_pings(DEFAULT_MAX_MISSED_PINGS)
+        .origin(DEFAULT_ORIGIN.to_string())
 }
 
-async fn websocket_client_by_port(
+/// The host conductors are connected to unless another is given.
+/// Connections to it fall back to [`DEFAULT_HOST_V6`] if
+/// nothing answers on IPv4.
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+
+/// The IPv6 loopback, for conductors on machines without IPv4
+/// or setups generated with `hc gen --bind ::1`.
+pub const DEFAULT_HOST_V6: &str = "::1";
+
+/// Is this host the machine hc is running on.
+/// hc only starts conductors for local hosts.
+pub fn is_local_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false)
+}
+
+/// How long to keep trying to connect to the admin port
+/// of a conductor that was just started.
+/// The conductor reports its admin port before it is always
+/// accepting connections on a slow machine.
+pub const DEFAULT_CONNECT_DEADLINE: Duration = Duration::from_secs(10);
+
+/// The wait before the first retry. It doubles after each attempt.
+const CONNECT_RETRY_START: Duration = Duration::from_millis(20);
+
+/// The longest wait between two attempts.
+const CONNECT_RETRY_MAX: Duration = Duration::from_millis(500);
+
+/// Connecting to an admin port failed on every attempt before the deadline.
+/// It is wrapped in a [`std::io::Error`] of the same kind as the last
+/// attempt's error so `ConnectionRefused` can still be matched on.
+#[derive(Debug)]
+pub struct ConnectError {
+    /// The host of the admin interface.
+    pub host: String,
+    /// The admin port.
+    pub port: u16,
+    /// How many times connecting was tried.
+    pub attempts: u32,
+    /// How long was spent trying.
+    pub waited: Duration,
+    /// The error from the last attempt.
+    pub error: std::io::Error,
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to connect to admin interface at {} after {} attempt{} over {:?}: {}",
+            host_port(&self.host, self.port),
+            self.attempts,
+            if self.attempts == 1 { "" } else { "s" },
+            self.waited,
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Connect to the admin port on the host, retrying with exponential
+/// backoff until the `deadline` has passed. A zero deadline tries once.
+/// Returns the host that answered, which is [`DEFAULT_HOST_V6`]
+/// if the conductor was only found on the IPv6 loopback.
+pub(crate) async fn get_admin_api(
+    host: &str,
     port: u16,
+    config: WebsocketConfig,
+    deadline: Duration,
+) -> std::io::Result<(WebsocketSender, String)> {
+    tracing::debug!(host, port);
+    let config = Arc::new(config);
+    let start = Instant::now();
+    let mut attempts = 0;
+    let mut delay = CONNECT_RETRY_START;
+    loop {
+        attempts += 1;
+        let error = match loopback_client(host, port, config.clone()).await {
+            Ok((sender, _, host)) => return Ok((sender, host)),
+            Err(e) => e,
+        };
+        let waited = start.elapsed();
+        // A rejected certificate, bad host or origin won't fix itself.
+        let retry = !matches!(
+            error.kind(),
+            std::io::ErrorKind::InvalidData
+                | std::io::ErrorKind::InvalidInput
+                | std::io::ErrorKind::PermissionDenied
+        );
+        if !retry || waited + delay > deadline {
+            return Err(std::io::Error::new(
+                error.kind(),
+                ConnectError {
+                    host: host.to_string(),
+                    port,
+                    attempts,
+                    waited,
+                    error,
+                },
+            ));
+        }
+        tracing::debug!(port, attempts, ?error, "Retrying the admin port");
+        tokio::time::delay_for(delay).await;
+        delay = (delay * 2).min(CONNECT_RETRY_MAX);
+    }
+}
+
+/// Connect to the admin interface on a unix domain socket.
+/// The conductor reports the socket after it is listening
+/// so this doesn't retry.
+#[cfg(unix)]
+pub(crate) async fn get_admin_api_unix(
+    path: &Path,
+    config: WebsocketConfig,
+) -> std::io::Result<WebsocketSender> {
+    tracing::debug!(path = %path.display());
+    let origin = config.origin.clone();
+    let (sender, _) = holochain_websocket::websocket_connect_unix(path, Arc::new(config))
+        .await
+        .map_err(|e| origin_not_allowed(e, origin.as_deref()))?;
+    Ok(sender)
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn get_admin_api_unix(
+    path: &Path,
+    _config: WebsocketConfig,
+) -> std::io::Result<WebsocketSender> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        format!(
+            "Can't connect to admin socket {}: unix domain sockets are not supported on this platform",
+            path.display()
+        ),
+    ))
+}
+
+/// Make an admin request on this connection.
+/// With `RUST_LOG=holochain_hc::ports=trace` each request logs
+/// the id its response is matched by, its variant and payload size,
+/// and each response logs the id and how long it took.
+/// Responses that match no request are warnings from `holochain_websocket`.
+pub(crate) async fn admin_request(
+    sender: &WebsocketSender,
+    request: AdminRequest,
+) -> std::io::Result<AdminResponse> {
+    let variant = crate::request_name(&request);
+    let bytes = serialize_admin_request(request)?;
+    send_admin_request(sender, variant, bytes).await
+}
+
+/// Serialize a request so it can be sent more than once.
+pub(crate) fn serialize_admin_request(request: AdminRequest) -> std::io::Result<SerializedBytes> {
+    SerializedBytes::try_from(request)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
+/// Send a serialized admin request like [`admin_request`].
+/// If the conductor closes the connection before it responds the error
+/// is a [`ConnectionClosedByPeer`] naming the request variant.
+pub(crate) async fn send_admin_request(
+    sender: &WebsocketSender,
+    variant: &'static str,
+    bytes: SerializedBytes,
+) -> std::io::Result<AdminResponse> {
+    let id = nanoid::nanoid!();
+    let size = bytes.bytes().len();
+    tracing::trace!(%id, request = variant, size, "Sending admin request");
+    let start = Instant::now();
+    let response = sender
+        .clone()
+        .request_with_id(id.clone(), bytes)
+        .await
+        .map_err(|e| ConnectionClosedByPeer::name_request(e, variant));
+    let elapsed = start.elapsed();
+    match &response {
+        Ok(_) => tracing::trace!(%id, request = variant, ?elapsed, "Matched admin response"),
+        Err(e) => tracing::trace!(%id, request = variant, ?elapsed, %e, "Admin request failed"),
+    }
+    response
+}
+
+pub(crate) async fn get_app_api(
+    host: &str,
+    port: u16,
+    config: WebsocketConfig,
+) -> std::io::Result<(WebsocketSender, WebsocketReceiver)> {
+    tracing::debug!(host, port);
+    let (sender, receiver, _) = loopback_client(host, port, Arc::new(config)).await?;
+    Ok((sender, receiver))
+}
+
+/// Format the host and port for a url or an error message.
+/// IPv6 addresses need brackets.
+pub(crate) fn host_port(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// Connect to the host and port like [`websocket_client`] and
+/// return the host that answered.
+/// Connections to [`DEFAULT_HOST`] try [`DEFAULT_HOST_V6`] too
+/// if nothing answers on IPv4, e.g. on a machine without IPv4.
+async fn loopback_client(
+    host: &str,
+    port: u16,
+    config: Arc<WebsocketConfig>,
+) -> std::io::Result<(WebsocketSender, WebsocketReceiver, String)> {
+    let error = match websocket_client(host, port, config.clone()).await {
+        Ok((sender, receiver)) => return Ok((sender, receiver, host.to_string())),
+        Err(e) => e,
+    };
+    // Something answered so trying another address won't help.
+    let answered = matches!(
+        error.kind(),
+        std::io::ErrorKind::InvalidData
+            | std::io::ErrorKind::InvalidInput
+            | std::io::ErrorKind::PermissionDenied
+    );
+    if host != DEFAULT_HOST || answered {
+        return Err(error);
+    }
+    match websocket_client(DEFAULT_HOST_V6, port, config).await {
+        Ok((sender, receiver)) => {
+            tracing::debug!(port, "Connected over the IPv6 loopback");
+            Ok((sender, receiver, DEFAULT_HOST_V6.to_string()))
+        }
+        // Nothing is on either loopback so report the IPv4 error.
+        Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => Err(error),
+        Err(e) => Err(e),
+    }
+}
+
+/// Connect to the host and port.
+/// DNS names are resolved by the websocket.
+async fn websocket_client(
+    host: &str,
+    port: u16,
+    config: Arc<WebsocketConfig>,
 ) -> std::io::Result<(WebsocketSender, WebsocketReceiver)> {
-    Ok(websocket_connect(
-        url2!("ws://127.0.0.1:{}", port),
-        Arc::new(WebsocketConfig::default()),
+    let url = format!("{}://{}", config.scheme, host_port(host, port));
+    let url = Url2::try_parse(&url).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid host {:?}: {:?}", host, e),
+        )
+    })?;
+    let origin = config.origin.clone();
+    websocket_connect(url, config)
+        .await
+        .map_err(|e| origin_not_allowed(e, origin.as_deref()))
+}
+
+/// A conductor with allowed origins rejects the handshake
+/// of a connection from any other origin.
+/// Say so instead of reporting a protocol error.
+fn origin_not_allowed(error: std::io::Error, origin: Option<&str>) -> std::io::Error {
+    if error.kind() != std::io::ErrorKind::PermissionDenied {
+        return error;
+    }
+    std::io::Error::new(
+        std::io::ErrorKind::PermissionDenied,
+        format!(
+            "origin {:?} not allowed by conductor. \
+            Use `hc --origin` or `{}` to send an origin it allows",
+            origin.unwrap_or_default(),
+            ORIGIN_ENV
+        ),
     )
-    .await?)
 }
 
+/// Let the conductor choose the port of the [`hc_admin_interface`].
+/// The ports of the other admin interfaces are kept.
+/// Port `0` is bound by the conductor itself so the OS hands out
+/// a free port atomically and the conductor reports the port it got.
+/// Picking a free port in hc instead would leave a window where
+/// setups started at the same time could pick the same port.
 pub(crate) fn random_admin_port_if_busy(config: &mut ConductorConfig) {
-    match config.admin_interfaces.as_mut().and_then(|i| i.first_mut()) {
+    match hc_admin_interface_mut(config) {
         Some(AdminInterfaceConfig {
             driver: InterfaceDriver::Websocket { port },
+            ..
         }) => {
-            if *port != 0 {
-                *port = 0;
-            }
-            // if !is_free(dbg!(*port)) {
-            //     dbg!("not free");
-            //     *port = pick_unused_port().expect("No ports free");
-            // }
-            // dbg!(*port)
+            *port = 0;
         }
+        // The socket path is chosen when the setup is generated.
+        Some(AdminInterfaceConfig {
+            driver: InterfaceDriver::UnixSocket { .. },
+            ..
+        }) => (),
         None => {
-            // let port = pick_unused_port().expect("No ports free");
             let port = 0;
             config.admin_interfaces = Some(vec![AdminInterfaceConfig {
                 driver: InterfaceDriver::Websocket { port },
+                allowed_origins: None,
+                address: None,
             }]);
         }
     }
@@ -80,16 +1202,14 @@ pub(crate) fn set_admin_port(config: &mut ConductorConfig, port: u16) {
     let p = port;
     let port = AdminInterfaceConfig {
         driver: InterfaceDriver::Websocket { port },
+        allowed_origins: None,
+        address: None,
     };
-    match config
-        .admin_interfaces
-        .as_mut()
-        .and_then(|ai| ai.get_mut(0))
-    {
+    match hc_admin_interface_mut(config) {
         Some(admin_interface) => {
-            *admin_interface = port;
+            admin_interface.driver = port.driver;
         }
         None => config.admin_interfaces = Some(vec![port]),
     }
-    msg!("Admin port set to: {}", p);
+    tracing::info!("Admin port set to: {}", p);
 }
@@ -0,0 +1,341 @@
+//! Project level defaults from a `hc.toml` file.
+//!
+//! The `hc.toml` is found by looking in the current directory
+//! and then each parent directory until one is found.
+//! The values in this file are only defaults so the precedence is:
+//! CLI flags > environment variables > `hc.toml` > user config > built-in defaults.
+//! See [`crate::defaults`] for the user config.
+//!
+//! Example `hc.toml`:
+//! ```toml
+//! # Relative paths are relative to this file.
+//! holochain_path = "../holochain/target/release/holochain"
+//! root = "chains"
+//!
+//! [network]
+//! transport = "quic"
+//! bootstrap = "https://bootstrap.holo.host"
+//! ```
+//!
+//! It can also have named [`Preset`]s of `hc gen` options
+//! that are used with `hc gen --preset <name>`:
+//! ```toml
+//! [presets.quic-cluster]
+//! num_conductors = 3
+//! app_id = "my-app"
+//! dnas = ["my-app.dna.gz"]
+//! properties = "properties.json"
+//! network = { transport = "quic", bootstrap = "https://bootstrap.holo.host" }
+//!
+//! [presets.offline-test]
+//! network = { transport = "mem" }
+//! config_overrides = "overrides.yaml"
+//! [presets.offline-test.config]
+//! zome_call_metering = true
+//! ```
+//! The flags passed to `hc gen` win over the preset,
+//! and the preset wins over the defaults above.
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::ensure;
+use serde::Deserialize;
+use url2::Url2;
+
+use crate::cmds::Create;
+use crate::cmds::Network;
+use crate::cmds::NetworkCmd;
+use crate::cmds::NetworkType;
+use crate::cmds::Quic;
+use crate::cmds::DEFAULT_APP_ID;
+use crate::config::ConductorConfigSpec;
+use crate::config::ProxyMode;
+use crate::keystore::KeystoreKind;
+
+/// Name of the project config file.
+pub const PROJECT_CONFIG: &str = "hc.toml";
+
+/// Defaults for `hc` commands loaded from a `hc.toml`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Default path to the holochain binary.
+    pub holochain_path: Option<PathBuf>,
+    /// Default root directory for new setups.
+    pub root: Option<PathBuf>,
+    /// Default network for new setups.
+    pub network: Option<ProjectNetwork>,
+    /// Named sets of `hc gen` options.
+    #[serde(default)]
+    pub presets: BTreeMap<String, Preset>,
+    /// The file these were loaded from.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+/// A named set of `hc gen` options from the `presets` of a `hc.toml`.
+/// The fields are the same as the `hc gen` flags.
+/// Relative paths are relative to the `hc.toml`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    /// Number of conductor setups to create.
+    pub num_conductors: Option<usize>,
+    /// ID for the installed app.
+    pub app_id: Option<String>,
+    /// The dnas to install.
+    /// These can be paths or URLs to download.
+    #[serde(default)]
+    pub dnas: Vec<PathBuf>,
+    /// Root directory for the setups.
+    pub root: Option<PathBuf>,
+    /// The network for the setups.
+    pub network: Option<ProjectNetwork>,
+    /// Install the app for this named agent.
+    pub reuse_agent: Option<String>,
+    /// Where the conductors keep their keys,
+    /// like `"ephemeral"` or `"shared:keys"`.
+    pub keystore: Option<KeystoreKind>,
+    /// Serve the admin interface on a unix domain socket.
+    #[serde(default)]
+    pub uds: bool,
+    /// Install the app without activating it.
+    #[serde(default)]
+    pub no_enable: bool,
+    /// Record zome call metering.
+    #[serde(default)]
+    pub zome_call_metered: bool,
+    /// Set the options for fast local development like `--dev`.
+    #[serde(default)]
+    pub dev: bool,
+    /// The address the conductor's interfaces listen on.
+    pub bind: Option<IpAddr>,
+    /// Admin interfaces to add, like `"127.0.0.1:0"`.
+    #[serde(default)]
+    pub admin_interfaces: Vec<SocketAddr>,
+    /// The origins the interfaces accept, or `["*"]` for any.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// A JSON file of properties for the dnas.
+    pub properties: Option<PathBuf>,
+    /// A YAML file of conductor config fields to set.
+    pub config_overrides: Option<PathBuf>,
+    /// Conductor config fields to set.
+    /// The `config_overrides` file wins over these.
+    #[serde(default)]
+    pub config: ConductorConfigSpec,
+}
+
+/// The network section of a `hc.toml`.
+/// These are the same options as `hc gen network`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectNetwork {
+    /// Either `mem` or `quic`.
+    pub transport: ProjectTransport,
+    /// Optional bootstrap service URL.
+    pub bootstrap: Option<String>,
+    /// Quic only: interface / port to bind to.
+    pub bind_to: Option<String>,
+    /// Quic only: override the local NIC ip.
+    pub override_host: Option<String>,
+    /// Quic only: override the local NIC port.
+    pub override_port: Option<u16>,
+    /// Quic only: run through an external proxy at this url.
+    pub proxy: Option<String>,
+    /// Quic only: `client` or `server`.
+    pub proxy_mode: Option<ProxyMode>,
+    /// Quic only: proxy for any conductor that asks.
+    #[serde(default)]
+    pub proxy_accept_all: bool,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectTransport {
+    Mem,
+    Quic,
+}
+
+impl ProjectConfig {
+    /// Search `dir` and its parents for a `hc.toml`.
+    /// Returns the empty config if none is found.
+    pub fn discover(dir: &Path) -> anyhow::Result<Self> {
+        for dir in dir.ancestors() {
+            let path = dir.join(PROJECT_CONFIG);
+            if path.is_file() {
+                tracing::debug!(project_config = %path.display());
+                return Self::load(&path);
+            }
+        }
+        Ok(Self::default())
+    }
+
+    /// Load a `hc.toml` from a path.
+    /// Relative paths in the file are resolved from the file's directory.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let toml = std::fs::read_to_string(path)?;
+        let mut config: Self = toml::from_str(&toml)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        config.holochain_path = config
+            .holochain_path
+            .map(|p| resolve_holochain_path(base, p));
+        config.root = config.root.map(|p| base.join(p));
+        for preset in config.presets.values_mut() {
+            preset.dnas = preset
+                .dnas
+                .iter()
+                .map(|p| {
+                    if crate::fetch::is_url(p) {
+                        p.clone()
+                    } else {
+                        base.join(p)
+                    }
+                })
+                .collect();
+            preset.root = preset.root.take().map(|p| base.join(p));
+            preset.properties = preset.properties.take().map(|p| base.join(p));
+            preset.config_overrides = preset.config_overrides.take().map(|p| base.join(p));
+            if let Some(KeystoreKind::Shared(keystore)) = &mut preset.keystore {
+                *keystore = base.join(&keystore);
+            }
+        }
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// The preset with this name.
+    pub fn preset(&self, name: &str) -> anyhow::Result<&Preset> {
+        self.presets.get(name).ok_or_else(|| {
+            let names: Vec<_> = self.presets.keys().map(String::as_str).collect();
+            anyhow!(
+                "There is no preset named {} in {}. The presets are: {}",
+                name,
+                PROJECT_CONFIG,
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            )
+        })
+    }
+
+    /// The network as the same type that the CLI parses to.
+    pub fn network(&self) -> anyhow::Result<Option<Network>> {
+        self.network.clone().map(Network::try_from).transpose()
+    }
+}
+
+impl Preset {
+    /// Fill in the options of `create` that weren't passed with this preset.
+    /// Flags that are off and options that aren't set are taken from the preset.
+    pub fn apply(&self, create: &mut Create) -> anyhow::Result<()> {
+        if create.network.is_none() {
+            create.network = self
+                .network
+                .clone()
+                .map(Network::try_from)
+                .transpose()?
+                .map(NetworkCmd::Network);
+        }
+        if create.app_id == DEFAULT_APP_ID {
+            if let Some(app_id) = &self.app_id {
+                create.app_id = app_id.clone();
+            }
+        }
+        create.root = create.root.take().or_else(|| self.root.clone());
+        create.reuse_agent = create
+            .reuse_agent
+            .take()
+            .or_else(|| self.reuse_agent.clone());
+        if create.keystore.is_none() && create.keystore_path.is_none() {
+            create.keystore = self.keystore.clone();
+        }
+        create.uds |= self.uds;
+        create.no_enable |= self.no_enable;
+        create.zome_call_metered |= self.zome_call_metered;
+        create.dev |= self.dev;
+        create.bind = create.bind.or(self.bind);
+        if create.admin_interfaces.is_empty() {
+            create.admin_interfaces = self.admin_interfaces.clone();
+        }
+        if create.allowed_origins.is_empty() {
+            create.allowed_origins = self.allowed_origins.clone();
+        }
+        create.properties = create.properties.take().or_else(|| self.properties.clone());
+        create.config_overrides = create
+            .config_overrides
+            .take()
+            .or_else(|| self.config_overrides.clone());
+        create.config = self.config.clone().merge(create.config.clone());
+        ensure!(
+            !(create.uds && create.bind.is_some()),
+            "--uds and --bind can't be used together but the preset sets one of them"
+        );
+        ensure!(
+            !(create.uds && !create.admin_interfaces.is_empty()),
+            "--uds and --admin-interface can't be used together but the preset sets one of them"
+        );
+        ensure!(
+            create.reuse_agent.is_none()
+                || (create.keystore.is_none() && create.keystore_path.is_none()),
+            "--reuse-agent and --keystore can't be used together but the preset sets one of them"
+        );
+        Ok(())
+    }
+}
+
+impl TryFrom<ProjectNetwork> for Network {
+    type Error = anyhow::Error;
+
+    fn try_from(n: ProjectNetwork) -> Result<Self, Self::Error> {
+        let ProjectNetwork {
+            transport,
+            bootstrap,
+            bind_to,
+            override_host,
+            override_port,
+            proxy,
+            proxy_mode,
+            proxy_accept_all,
+        } = n;
+        let transport = match transport {
+            ProjectTransport::Mem => NetworkType::Mem,
+            ProjectTransport::Quic => NetworkType::Quic(Quic {
+                bind_to: bind_to.map(parse_url).transpose()?,
+                override_host,
+                override_port,
+                proxy: proxy.map(parse_url).transpose()?,
+                proxy_mode,
+                proxy_accept_all,
+            }),
+        };
+        Ok(Network {
+            transport,
+            bootstrap: bootstrap.map(parse_url).transpose()?,
+            tuning: Default::default(),
+        })
+    }
+}
+
+/// Resolve a relative holochain path in a config file from the file's directory.
+/// A bare binary name is looked up on the PATH so it is left alone.
+pub(crate) fn resolve_holochain_path(base: &Path, path: PathBuf) -> PathBuf {
+    if path.is_relative() && path.components().count() > 1 {
+        base.join(path)
+    } else {
+        path
+    }
+}
+
+fn parse_url(url: String) -> anyhow::Result<Url2> {
+    Url2::try_parse(&url).map_err(|e| anyhow!("Invalid url {} in {}: {:?}", url, PROJECT_CONFIG, e))
+}
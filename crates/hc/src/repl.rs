@@ -0,0 +1,432 @@
+//! Make many admin calls over the same connections.
+//!
+//! `hc repl` reads admin calls, one per line and written the same
+//! way as after `hc call`, and makes each of them on the chosen
+//! running conductors. The connections come from a [`RunnerPool`]
+//! so every call to a conductor reuses the same connection instead
+//! of connecting again, which is quicker and keeps the conductor's
+//! logs quiet. Connections that are idle for a while are closed
+//! until they are needed again.
+//!
+//! Calls can be typed at a prompt or piped in as a batch:
+//! ```shell
+//! hc repl --running=9000
+//! printf 'list-dnas\nlist-cells\n' | hc repl --running=9000,9001
+//! ```
+//!
+//! `--replay` makes the calls in a file as a golden test.
+//! Each line of the file is a JSON object with the `call` and
+//! the response it is `expected` to get. Zome calls are compared
+//! on their output and other calls on the text they print.
+//! Responses that differ are reported with the fields that changed
+//! and the replay fails at the end.
+use std::io::BufRead;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use serde::Deserialize;
+use serde_json::Value;
+use structopt::StructOpt;
+
+use crate::calls::call_inner;
+use crate::calls::call_zome;
+use crate::calls::explain_closed_by_peer;
+use crate::calls::prompt_membrane_proofs;
+use crate::calls::AdminRequestCli;
+use crate::calls::Raw;
+use crate::calls::Tls;
+use crate::is_local_host;
+use crate::ports::host_port;
+use crate::CmdRunner;
+use crate::ConnectOptions;
+use crate::RunnerPool;
+use crate::DEFAULT_HOST;
+
+#[doc(hidden)]
+#[derive(Debug, StructOpt)]
+pub struct Repl {
+    #[structopt(short, long, required = true, value_delimiter = ",")]
+    /// Ports to running conductor admin interfaces.
+    /// Every call is made to each of them.
+    pub running: Vec<u16>,
+    #[structopt(long)]
+    /// The host of the `--running` conductors.
+    /// Can be a DNS name or an IP address. Defaults to `127.0.0.1`.
+    pub host: Option<String>,
+    #[structopt(flatten)]
+    pub tls: Tls,
+    #[structopt(long)]
+    /// Make the calls in this file instead of reading them from stdin
+    /// and compare each response to the one it is expected to get.
+    ///
+    /// Each line is JSON like
+    /// `{"call": "zome <dna> <agent> posts get_all --app-port 8888", "expected": []}`.
+    /// Lines without `expected` only have to succeed.
+    /// Fails if any response is different.
+    pub replay: Option<PathBuf>,
+    #[structopt(long = "ignore-field", requires = "replay")]
+    /// Leave fields with this name out of the comparison
+    /// wherever they are in a response, e.g. `--ignore-field timestamp`
+    /// for values that change on every run.
+    pub ignore_fields: Vec<String>,
+}
+
+/// A line of a `--replay` file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplayLine {
+    /// The call written the same way as after `hc call`.
+    pub call: String,
+    /// The response the call should get.
+    /// An `expected` of `null` expects a `null` response.
+    #[serde(default, deserialize_with = "present")]
+    pub expected: Option<Value>,
+}
+
+fn present<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(d).map(Some)
+}
+
+/// Read calls from stdin and make them on the conductors until
+/// stdin ends or `exit` is read.
+/// You are prompted for each call if stdin is a terminal.
+/// The conductors are connected to with the `connect` options.
+pub async fn repl(repl: Repl, connect: &ConnectOptions) -> anyhow::Result<()> {
+    let Repl {
+        running,
+        host,
+        tls,
+        replay,
+        ignore_fields,
+    } = repl;
+    let host = host.unwrap_or_else(|| DEFAULT_HOST.to_string());
+    let tls = tls.config()?;
+    let connect = connect.clone();
+    let pool = RunnerPool::with_config(move || match &tls {
+        Some(tls) => connect.websocket_config().tls(tls.clone()),
+        None => connect.websocket_config(),
+    });
+    if let Some(replay) = replay {
+        let file = std::fs::File::open(&replay)
+            .with_context(|| format!("Failed to open replay file {}", replay.display()))?;
+        let input = std::io::BufReader::new(file);
+        return replay_lines(&pool, &host, &running, input, &ignore_fields).await;
+    }
+    let stdin = std::io::stdin();
+    let prompt = atty::is(atty::Stream::Stdin);
+    run_lines(&pool, &host, &running, stdin.lock(), prompt).await
+}
+
+/// Make the call on each line of `input` on the conductors on these ports
+/// with connections from the `pool`.
+/// Empty lines and lines starting with `#` are skipped.
+/// A call that fails is reported and the rest are still made,
+/// but it is an error at the end if any of them failed.
+pub async fn run_lines(
+    pool: &RunnerPool,
+    host: &str,
+    ports: &[u16],
+    mut input: impl BufRead,
+    prompt: bool,
+) -> anyhow::Result<()> {
+    let mut calls = 0;
+    let mut failed = 0;
+    loop {
+        if prompt {
+            eprint!("hc> ");
+            std::io::Write::flush(&mut std::io::stderr())?;
+        }
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line == "exit" || line == "quit" {
+            break;
+        }
+        let call = match parse_call(line) {
+            Ok(Some(call)) => call,
+            Ok(None) => continue,
+            Err(e) => {
+                calls += 1;
+                failed += 1;
+                tracing::error!("{}", e);
+                continue;
+            }
+        };
+        for &port in ports {
+            calls += 1;
+            if let Err(e) = call_port(pool, host, port, call.clone(), ports.len() > 1).await {
+                failed += 1;
+                tracing::error!("{} failed: {:?}", conductor_name(host, port), e);
+            }
+        }
+    }
+    ensure!(failed == 0, "{} of {} calls failed", failed, calls);
+    Ok(())
+}
+
+/// Make the call on each [`ReplayLine`] of `input` in order on the conductors
+/// on these ports and compare the responses to the expected ones
+/// after removing the `ignore_fields` from both.
+/// Empty lines and lines starting with `#` are skipped.
+/// Every call is made even if some don't match
+/// but it is an error at the end if any failed or didn't match.
+pub async fn replay_lines(
+    pool: &RunnerPool,
+    host: &str,
+    ports: &[u16],
+    input: impl BufRead,
+    ignore_fields: &[String],
+) -> anyhow::Result<()> {
+    let mut calls = 0;
+    let mut failed = 0;
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = serde_json::from_str::<ReplayLine>(line)
+            .map_err(|e| anyhow::anyhow!("Line {} is not a replay line: {}", i + 1, e))
+            .and_then(|replay| Ok((parse_call(&replay.call)?, replay.expected)));
+        let (call, expected) = match parsed {
+            Ok((Some(call), expected)) => (call, expected),
+            Ok((None, _)) => continue,
+            Err(e) => {
+                calls += 1;
+                failed += 1;
+                tracing::error!("Line {}: {}", i + 1, e);
+                continue;
+            }
+        };
+        for &port in ports {
+            calls += 1;
+            let name = conductor_name(host, port);
+            match response(pool, host, port, call.clone()).await {
+                Ok(mut actual) => {
+                    let expected = match &expected {
+                        Some(expected) => expected,
+                        None => continue,
+                    };
+                    let mut expected = expected.clone();
+                    normalize(&mut expected, ignore_fields);
+                    normalize(&mut actual, ignore_fields);
+                    let diff = diff(&expected, &actual);
+                    if !diff.is_empty() {
+                        failed += 1;
+                        tracing::error!(
+                            "Line {} on {} doesn't match the expected response:\n    {}",
+                            i + 1,
+                            name,
+                            diff.join("\n    ")
+                        );
+                    }
+                }
+                Err(e) => {
+                    failed += 1;
+                    tracing::error!("Line {} on {} failed: {:?}", i + 1, name, e);
+                }
+            }
+        }
+    }
+    ensure!(
+        failed == 0,
+        "{} of {} replayed calls failed or didn't match",
+        failed,
+        calls
+    );
+    msg!("All {} replayed calls matched", calls);
+    Ok(())
+}
+
+/// Remove the fields with these names from every object in the value.
+pub fn normalize(value: &mut Value, ignore_fields: &[String]) {
+    match value {
+        Value::Object(fields) => {
+            fields.retain(|name, _| !ignore_fields.contains(name));
+            for value in fields.values_mut() {
+                normalize(value, ignore_fields);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                normalize(value, ignore_fields);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The differences between two values, one per line,
+/// with the path to where they are e.g. `.posts[1].content`.
+/// Empty if they are the same.
+pub fn diff(expected: &Value, actual: &Value) -> Vec<String> {
+    let mut diff = Vec::new();
+    diff_at("", expected, actual, &mut diff);
+    diff
+}
+
+fn diff_at(path: &str, expected: &Value, actual: &Value, diff: &mut Vec<String>) {
+    let at = if path.is_empty() { "." } else { path };
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (name, e) in expected {
+                let path = format!("{}.{}", path, name);
+                match actual.get(name) {
+                    Some(a) => diff_at(&path, e, a, diff),
+                    None => diff.push(format!("{}: expected {} but it is missing", path, e)),
+                }
+            }
+            for (name, a) in actual {
+                if !expected.contains_key(name) {
+                    diff.push(format!("{}.{}: not expected but got {}", path, name, a));
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            if expected.len() != actual.len() {
+                diff.push(format!(
+                    "{}: expected {} items but got {}",
+                    at,
+                    expected.len(),
+                    actual.len()
+                ));
+            }
+            for (i, (e, a)) in expected.iter().zip(actual).enumerate() {
+                diff_at(&format!("{}[{}]", path, i), e, a, diff);
+            }
+        }
+        (expected, actual) if expected != actual => {
+            diff.push(format!("{}: expected {} but got {}", at, expected, actual))
+        }
+        _ => (),
+    }
+}
+
+/// The response to compare for a replayed call.
+/// This is the output of a zome call and
+/// the text other calls print.
+async fn response(
+    pool: &RunnerPool,
+    host: &str,
+    port: u16,
+    call: AdminRequestCli,
+) -> anyhow::Result<Value> {
+    let cmd = pool.get_on_host(host, port).await?;
+    let response = match call {
+        AdminRequestCli::Zome(args) => call_zome(&cmd, args)
+            .await
+            .map(|(output, _)| output)
+            .map_err(explain_closed_by_peer),
+        call => {
+            let mut out = String::new();
+            call_inner(&cmd, call, &mut out)
+                .await
+                .map(|_| Value::String(out.trim_end().to_string()))
+        }
+    };
+    if response.is_err() {
+        evict_if_broken(pool, host, port, &cmd).await;
+    }
+    response
+}
+
+/// Don't hand out a connection a call broke.
+async fn evict_if_broken(pool: &RunnerPool, host: &str, port: u16, cmd: &CmdRunner) {
+    if !cmd
+        .is_alive(crate::pool::DEFAULT_HEALTH_CHECK_TIMEOUT)
+        .await
+    {
+        pool.evict_on_host(host, port);
+    }
+}
+
+/// Split a line into words like a shell does.
+/// Words can be quoted with `'` or `"` to keep spaces in them,
+/// e.g. for a JSON payload, and `\` escapes the next character
+/// outside of single quotes.
+pub fn split_words(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => bail!("The line ends with an escape: {}", line),
+            },
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    ensure!(quote.is_none(), "The line has an unclosed quote: {}", line);
+    words.extend(word);
+    Ok(words)
+}
+
+/// Parse the call on a line or print the help if it asked for it.
+fn parse_call(line: &str) -> anyhow::Result<Option<AdminRequestCli>> {
+    let words = split_words(line)?;
+    let call = AdminRequestCli::from_iter_safe(std::iter::once("call".to_string()).chain(words));
+    let mut call = match call {
+        Ok(call) => call,
+        Err(e) if e.kind == structopt::clap::ErrorKind::HelpDisplayed => {
+            msg!("{}", e.message);
+            return Ok(None);
+        }
+        Err(e) => bail!(e.message),
+    };
+    match &mut call {
+        AdminRequestCli::Raw(Raw { request: None }) => {
+            bail!("Pass the raw request as an argument in the repl")
+        }
+        AdminRequestCli::ExportKeystore(_) | AdminRequestCli::ImportKeystore(_) => {
+            bail!("Keystores can only be moved between setups with `hc call`")
+        }
+        AdminRequestCli::InstallApp(args) => prompt_membrane_proofs(args)?,
+        _ => (),
+    }
+    Ok(Some(call))
+}
+
+async fn call_port(
+    pool: &RunnerPool,
+    host: &str,
+    port: u16,
+    call: AdminRequestCli,
+    named: bool,
+) -> anyhow::Result<()> {
+    let cmd = pool.get_on_host(host, port).await?;
+    let mut out = String::new();
+    if let Err(e) = call_inner(&cmd, call, &mut out).await {
+        evict_if_broken(pool, host, port, &cmd).await;
+        return Err(e);
+    }
+    if named {
+        msg!("{}:", conductor_name(host, port));
+    }
+    data!("{}", out.trim_end());
+    Ok(())
+}
+
+fn conductor_name(host: &str, port: u16) -> String {
+    if is_local_host(host) {
+        format!("Conductor on port {}", port)
+    } else {
+        format!("Conductor at {}", host_port(host, port))
+    }
+}
@@ -1,18 +1,73 @@
 //! Helpers for running the conductor.
 use std::path::Path;
+use std::process::ExitStatus;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
 use std::{path::PathBuf, process::Stdio};
 
+use anyhow::anyhow;
+use anyhow::ensure;
+use anyhow::Context;
+
+use holochain_conductor_api::AdminRequest;
+use structopt::StructOpt;
 use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncWriteExt;
 use tokio::io::BufReader;
 use tokio::process::{Child, Command};
 use tokio::sync::oneshot;
+use tokio::sync::Semaphore;
+use tokio::sync::SemaphorePermit;
 
 use crate::calls::attach_app_interface;
 use crate::calls::AddAppWs;
 use crate::config::*;
+use crate::logs::ConductorLog;
+use crate::passphrase::needs_passphrase;
+use crate::passphrase::read_passphrase;
+use crate::passphrase::Passphrase;
+use crate::ports::admin_interfaces_in_config;
+use crate::ports::app_ports_from_config;
+use crate::ports::bound_admin_interfaces;
+use crate::ports::check_not_in_config;
+use crate::ports::clear_app_ports;
+use crate::ports::get_admin_address;
+use crate::ports::get_admin_interfaces;
 use crate::ports::random_admin_port_if_busy;
+use crate::ports::record_admin_interfaces;
 use crate::ports::set_admin_port;
+use crate::ports::AdminRole;
+use crate::ports::PortPool;
+use crate::websocket_config;
+use crate::AdminAddress;
 use crate::CmdRunner;
+use crate::ConnectOptions;
+use crate::DEFAULT_CONNECT_DEADLINE;
+
+/// The default time to wait for the keystore to be ready.
+pub const DEFAULT_KEYSTORE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait between keystore readiness checks.
+const KEYSTORE_RETRY: Duration = Duration::from_millis(100);
+
+/// The default time to wait for the conductor to report its admin port.
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long [`ConductorHandle::stop`] and dropping [`Setups`] wait
+/// for a conductor to exit after asking it to before killing it.
+///
+/// [`Setups`]: crate::Setups
+pub const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a conductor that was asked to exit is checked
+/// when it can't be awaited.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long to wait for a conductor to respond in [`is_running`].
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Run a conductor and wait for it to finish.
 /// Use [`run_async`] to run in the background.
@@ -29,125 +84,934 @@ pub async fn run(
     app_ports: Vec<u16>,
     force_admin_port: Option<u16>,
 ) -> anyhow::Result<()> {
-    let (port, holochain) = run_async(holochain_path, setup_path.clone(), force_admin_port).await?;
-    msg!("Running conductor on admin port {}", port);
-    for app_port in app_ports {
-        msg!("Attaching app port {}", app_port);
-        let mut cmd = CmdRunner::try_new(port).await?;
-        attach_app_interface(
-            &mut cmd,
-            AddAppWs {
-                port: Some(app_port),
-            },
-        )
-        .await?;
-    }
-    msg!("Connected successfully to a running holochain");
-    let e = format!("Failed to run holochain at {}", setup_path.display());
+    run_with_config(
+        RunConfig {
+            holochain_path: holochain_path.to_path_buf(),
+            force_admin_port,
+            ..RunConfig::new(setup_path)
+        },
+        app_ports,
+    )
+    .await
+}
 
-    holochain.await.expect(&e);
+/// Same as [`run`] with the other options of a [`RunConfig`],
+/// e.g. the [`RunConfig::env`] of the conductor.
+/// The app interfaces in the setup's config are reported as ready
+/// without attaching them and none of the `app_ports` can be one of them.
+pub async fn run_with_config(config: RunConfig, app_ports: Vec<u16>) -> anyhow::Result<()> {
+    run_in_wave(config, app_ports, &StartupWave::new(1, &Stagger::default())).await
+}
+
+/// Same as [`run_with_config`] but the conductor only starts when
+/// the `wave` lets it, so several conductors run with the same wave
+/// come up a few at a time instead of all at once.
+pub async fn run_in_wave(
+    config: RunConfig,
+    app_ports: Vec<u16>,
+    wave: &StartupWave,
+) -> anyhow::Result<()> {
+    let setup_path = config.setup_path.clone();
+    for app_port in &app_ports {
+        check_not_in_config(&setup_path, *app_port)?;
+    }
+    let permit = wave.start().await;
+    let holochain = spawn(config).await?;
+    msg!("Running conductor on admin {}", holochain.admin_address());
+    for app_port in app_ports_from_config(&setup_path)? {
+        msg!("App port {} is ready from the conductor config", app_port);
+    }
+    if !app_ports.is_empty() {
+        let cmd = holochain.connect().await?;
+        for app_port in app_ports {
+            tracing::debug!("Attaching app port {}", app_port);
+            let port = attach_app_interface(
+                &cmd,
+                AddAppWs {
+                    port: Some(app_port),
+                    verify: true,
+                    allowed_origins: Vec::new(),
+                },
+            )
+            .await?;
+            if port != app_port {
+                msg!("App port {} is bound to {}", app_port, port);
+            }
+        }
+    }
+    drop(permit);
+    wave.started();
+    tracing::info!("Connected successfully to a running holochain");
+    holochain
+        .wait()
+        .await
+        .with_context(|| format!("Failed to run holochain at {}", setup_path.display()))?;
     Ok(())
 }
 
+/// How `hc run` starts several conductors.
+/// By default they all start at once.
+#[derive(Debug, StructOpt, Clone, Default)]
+pub struct Stagger {
+    #[structopt(long = "stagger", parse(try_from_str = parse_stagger))]
+    /// Wait this many milliseconds after starting a conductor
+    /// before starting the next one.
+    pub stagger: Option<Duration>,
+    #[structopt(long, parse(try_from_str = parse_max_concurrent_starts))]
+    /// Only start this many conductors at a time.
+    /// The next one starts when one of them has reported its admin port
+    /// and has its app interfaces.
+    pub max_concurrent_starts: Option<usize>,
+}
+
+fn parse_stagger(ms: &str) -> anyhow::Result<Duration> {
+    let ms: u64 = ms
+        .parse()
+        .map_err(|e| anyhow!("{} is not a number of milliseconds: {}", ms, e))?;
+    Ok(Duration::from_millis(ms))
+}
+
+fn parse_max_concurrent_starts(n: &str) -> anyhow::Result<usize> {
+    let n: usize = n
+        .parse()
+        .map_err(|e| anyhow!("{} is not a number: {}", n, e))?;
+    ensure!(n > 0, "At least one conductor has to start at a time");
+    Ok(n)
+}
+
+/// The conductors [`run_in_wave`] is starting together.
+/// It limits how many start at once and waits the [`Stagger::stagger`]
+/// between starts, so a large cluster on a small machine doesn't spike
+/// the CPU or have its conductors contend for ports and keystores.
+/// When the last of them has started it reports how long they took.
+#[derive(Debug)]
+pub struct StartupWave {
+    starts: Option<Semaphore>,
+    stagger: Duration,
+    last_start: tokio::sync::Mutex<Option<tokio::time::Instant>>,
+    began: std::time::Instant,
+    total: usize,
+    started: AtomicUsize,
+}
+
+impl StartupWave {
+    /// A wave of `total` conductors started with these options.
+    pub fn new(total: usize, stagger: &Stagger) -> Self {
+        Self {
+            starts: stagger.max_concurrent_starts.map(Semaphore::new),
+            stagger: stagger.stagger.unwrap_or_default(),
+            last_start: tokio::sync::Mutex::new(None),
+            began: std::time::Instant::now(),
+            total,
+            started: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait until another conductor can start.
+    /// It counts as starting until the permit is dropped.
+    async fn start(&self) -> Option<SemaphorePermit<'_>> {
+        let permit = match &self.starts {
+            Some(starts) => Some(starts.acquire().await),
+            None => None,
+        };
+        let mut last_start = self.last_start.lock().await;
+        if let Some(last_start) = *last_start {
+            tokio::time::delay_until(last_start + self.stagger).await;
+        }
+        *last_start = Some(tokio::time::Instant::now());
+        permit
+    }
+
+    /// A conductor has started.
+    fn started(&self) {
+        let started = self.started.fetch_add(1, Ordering::SeqCst) + 1;
+        if started == self.total && self.total > 1 {
+            msg!(
+                "Started {} conductors in {:.1}s",
+                self.total,
+                self.began.elapsed().as_secs_f64()
+            );
+        }
+    }
+}
+
 /// Run a conductor in the background.
-/// Requires the holochain binary is available
-/// on the `holochain_path`.
-/// Uses the setup provided by the `setup_path`.
-/// Can optionally force the admin port used. Otherwise
-/// the port in the config will be used if it's free or
-/// a random free port will be chosen.
-pub async fn run_async(
+/// Returns the admin port and the conductor process.
+/// Set [`RunConfig::force_admin_port`] to choose the admin port,
+/// e.g. so another process can reconnect to it later.
+/// Use [`spawn`] to get a [`ConductorHandle`] instead.
+pub async fn run_async(config: RunConfig) -> anyhow::Result<(u16, Child)> {
+    let (port, child, _, _) = start(&config, true).await?;
+    Ok((port, child))
+}
+
+/// Same as [`run_async`] with the positional arguments
+/// it used to take.
+#[deprecated(note = "Use run_async with a RunConfig")]
+pub async fn run_async_with_path(
     holochain_path: &Path,
     setup_path: PathBuf,
     force_admin_port: Option<u16>,
 ) -> anyhow::Result<(u16, Child)> {
-    let mut config = match read_config(setup_path.clone())? {
+    run_async(RunConfig {
+        holochain_path: holochain_path.to_path_buf(),
+        force_admin_port,
+        ..RunConfig::new(setup_path)
+    })
+    .await
+}
+
+/// How to run a conductor with [`run_async`] or [`spawn`].
+/// Use [`RunConfig::new`] for the defaults and
+/// struct update syntax to change them.
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    /// Path to the holochain binary.
+    /// Defaults to `holochain` on the path.
+    pub holochain_path: PathBuf,
+    /// The setup to run.
+    /// A conductor config is created if it doesn't have one yet.
+    pub setup_path: PathBuf,
+    /// Force the admin port used. It is written into the conductor
+    /// config before the conductor starts so the admin interface binds
+    /// it and [`ConductorHandle::connect`] connects to it.
+    /// Starting fails straight away if something is already bound to it.
+    /// Otherwise the port in the config will be used if it's free or
+    /// the conductor binds a free port and reports it.
+    /// The port is reserved in the [`PortPool::global`] pool
+    /// so two setups in one process can't be forced to the same port.
+    pub force_admin_port: Option<u16>,
+    /// Extra environment variables for the conductor process,
+    /// e.g. `("RUST_LOG", "debug")`.
+    /// Use [`read_env_file`] and [`merge_env`] to load them from a file.
+    pub env: Vec<(String, String)>,
+    /// Write the conductor's output to log files in this
+    /// directory instead of printing it.
+    /// The directory is recorded in the setup for [`logs`](crate::logs).
+    pub log_dir: Option<PathBuf>,
+    /// The conductor's log filter and format.
+    /// The filter is its `RUST_LOG` and wins over the one in [`RunConfig::env`].
+    /// What it was started with is recorded in the setup for `hc status`.
+    pub conductor_log: ConductorLog,
+    /// How long to wait for the conductor to report its admin port
+    /// before failing.
+    /// Defaults to [`DEFAULT_STARTUP_TIMEOUT`].
+    pub startup_timeout: Duration,
+    /// Also wait until the conductor's keystore is ready
+    /// with [`wait_for_keystore`] before returning.
+    /// Not set by default.
+    pub keystore_timeout: Option<Duration>,
+    /// How long [`ConductorHandle::connect`] keeps retrying
+    /// if the admin port isn't accepting connections yet.
+    /// Defaults to [`DEFAULT_CONNECT_DEADLINE`].
+    pub connect_deadline: Duration,
+    /// The passphrase to write to the conductor's stdin if its config
+    /// has it ask for one with [`needs_passphrase`].
+    /// If it isn't set it is read from [`PASSPHRASE_ENV`](crate::passphrase::PASSPHRASE_ENV)
+    /// and starting fails if that isn't set either.
+    /// Use [`for_setups`](crate::passphrase::for_setups) to ask for it on the terminal.
+    pub passphrase: Option<Passphrase>,
+    /// How [`ConductorHandle::connect`] connects to the conductor,
+    /// e.g. the proxy from `hc --proxy`. They aren't passed to the conductor.
+    pub connect: ConnectOptions,
+}
+
+impl RunConfig {
+    /// Run this setup with the default options.
+    pub fn new(setup_path: PathBuf) -> Self {
+        Self {
+            holochain_path: PathBuf::from("holochain"),
+            setup_path,
+            force_admin_port: None,
+            env: Vec::new(),
+            log_dir: None,
+            conductor_log: ConductorLog::default(),
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            keystore_timeout: None,
+            connect_deadline: DEFAULT_CONNECT_DEADLINE,
+            passphrase: None,
+            connect: ConnectOptions::default(),
+        }
+    }
+}
+
+/// Parse a `KEY=VALUE` environment variable for the conductor,
+/// e.g. from `hc run --env RUST_LOG=debug`.
+/// The value can be empty but the key can't.
+pub fn parse_env_var(arg: &str) -> anyhow::Result<(String, String)> {
+    let (key, value) =
+        split_env_var(arg).ok_or_else(|| anyhow!("Expected KEY=VALUE but found `{}`", arg))?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Read the environment variables for the conductor from a file
+/// of `KEY=VALUE` lines like a `.env` file.
+/// Empty lines and lines starting with `#` are skipped, a leading
+/// `export ` is ignored and values can be quoted with `'` or `"`.
+/// A line that isn't a variable is an error that names the line.
+pub fn read_env_file(path: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let file = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read env file {}", path.display()))?;
+    let mut env = Vec::new();
+    for (i, line) in file.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = split_env_var(line).ok_or_else(|| {
+            anyhow!(
+                "Line {} of env file {} is not KEY=VALUE: {}",
+                i + 1,
+                path.display(),
+                line
+            )
+        })?;
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find(|&&q| value.len() >= 2 && value.starts_with(q) && value.ends_with(q))
+            .map_or(value, |_| &value[1..value.len() - 1]);
+        env.push((key.trim().to_string(), value.to_string()));
+    }
+    Ok(env)
+}
+
+/// Merge the variables from an env file with the ones that were
+/// passed directly. The passed variables win when both set a key.
+pub fn merge_env(
+    file: Vec<(String, String)>,
+    passed: Vec<(String, String)>,
+) -> Vec<(String, String)> {
+    let mut env: Vec<_> = file
+        .into_iter()
+        .filter(|(key, _)| !passed.iter().any(|p| p.0 == *key))
+        .collect();
+    env.extend(passed);
+    env
+}
+
+fn split_env_var(var: &str) -> Option<(&str, &str)> {
+    let i = var.find('=')?;
+    let key = var[..i].trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key, &var[i + 1..]))
+}
+
+/// The files the conductor's output is written to
+/// when [`RunConfig::log_dir`] is set.
+#[derive(Debug, Clone)]
+pub struct ConductorLogs {
+    /// The conductor's stdout.
+    pub stdout: PathBuf,
+    /// The conductor's stderr.
+    pub stderr: PathBuf,
+}
+
+impl ConductorLogs {
+    /// The log files in `log_dir`.
+    pub fn in_dir(log_dir: &Path) -> Self {
+        Self {
+            stdout: log_dir.join("holochain-stdout.log"),
+            stderr: log_dir.join("holochain-stderr.log"),
+        }
+    }
+}
+
+/// A conductor running in the background.
+/// The conductor is killed when the handle is dropped
+/// unless [`ConductorHandle::detach`] is called.
+pub struct ConductorHandle {
+    admin: AdminAddress,
+    setup_path: PathBuf,
+    logs: Option<ConductorLogs>,
+    connect_deadline: Duration,
+    connect: ConnectOptions,
+    exited: Exited,
+    holochain: Option<Child>,
+}
+
+/// Set when the conductor's stdout closes, which happens when it exits.
+/// This lets connection errors say whether the conductor died
+/// or is just slow without needing the child.
+#[derive(Debug, Clone, Default)]
+struct Exited(Arc<AtomicBool>);
+
+impl Exited {
+    fn set(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Describe the conductor process for an error message.
+    fn describe(&self) -> &'static str {
+        if self.0.load(Ordering::SeqCst) {
+            "the holochain process has exited"
+        } else {
+            "the holochain process is still running"
+        }
+    }
+}
+
+/// Run a conductor in the background and get a handle to it.
+pub async fn spawn(config: RunConfig) -> anyhow::Result<ConductorHandle> {
+    // The handle kills the conductor on drop itself so `detach` can leave it running.
+    let (admin, child, logs, exited) = start(&config, false).await?;
+    Ok(ConductorHandle {
+        admin,
+        setup_path: config.setup_path,
+        logs,
+        connect_deadline: config.connect_deadline,
+        connect: config.connect,
+        exited,
+        holochain: Some(child),
+    })
+}
+
+impl ConductorHandle {
+    /// The port of the conductor's admin interface.
+    /// With several admin interfaces this is the [`hc_admin_interface`].
+    /// This is `0` if the admin interface is a unix socket,
+    /// see [`ConductorHandle::admin_address`].
+    ///
+    /// [`hc_admin_interface`]: crate::ports::hc_admin_interface
+    pub fn admin_port(&self) -> u16 {
+        match self.admin {
+            AdminAddress::Port(port) => port,
+            AdminAddress::Socket(_) => 0,
+        }
+    }
+
+    /// The port or unix socket of the conductor's admin interface.
+    pub fn admin_address(&self) -> &AdminAddress {
+        &self.admin
+    }
+
+    /// The setup the conductor is running.
+    pub fn setup_path(&self) -> &Path {
+        &self.setup_path
+    }
+
+    /// The log files if [`RunConfig::log_dir`] was set.
+    pub fn logs(&self) -> Option<&ConductorLogs> {
+        self.logs.as_ref()
+    }
+
+    /// Connect to the conductor's admin interface.
+    /// Retries until the [`RunConfig::connect_deadline`]
+    /// if the conductor isn't accepting connections yet.
+    pub async fn connect(&self) -> anyhow::Result<CmdRunner> {
+        let cmd = connect(
+            &self.admin,
+            &self.connect,
+            self.connect_deadline,
+            &self.exited,
+        )
+        .await?;
+        Ok(cmd.with_setup_path(self.setup_path.clone()))
+    }
+
+    /// Kill the conductor.
+    /// Use [`ConductorHandle::wait`] to wait for it to exit.
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        match self.holochain.as_mut() {
+            Some(holochain) => holochain.kill(),
+            None => Ok(()),
+        }
+    }
+
+    /// Ask the conductor to exit with `SIGTERM` so it can stop cleanly.
+    /// It is killed on platforms without signals.
+    /// Use [`ConductorHandle::wait`] to wait for it to exit.
+    pub fn terminate(&mut self) -> std::io::Result<()> {
+        match self.holochain.as_mut() {
+            Some(holochain) => terminate(holochain),
+            None => Ok(()),
+        }
+    }
+
+    /// Ask the conductor to exit and wait up to `timeout` for it to.
+    /// If it is still running after the `timeout` it is killed.
+    pub async fn stop(mut self, timeout: Duration) -> std::io::Result<ExitStatus> {
+        self.terminate()?;
+        let holochain = self
+            .holochain
+            .as_mut()
+            .expect("The conductor is only taken on detach");
+        let status = match tokio::time::timeout(timeout, &mut *holochain).await {
+            Ok(status) => status,
+            Err(_) => {
+                tracing::warn!(
+                    setup = %self.setup_path.display(),
+                    "conductor didn't exit in time, killing it"
+                );
+                holochain.kill()?;
+                holochain.await
+            }
+        };
+        self.holochain = None;
+        status
+    }
+
+    /// The same as [`ConductorHandle::stop`] after [`ConductorHandle::terminate`]
+    /// for places that can't await, like drop.
+    /// Blocks the thread until the conductor exits or the `deadline` passes.
+    pub(crate) fn wait_or_kill_blocking(&mut self, deadline: std::time::Instant) {
+        use futures::FutureExt;
+        let holochain = match self.holochain.as_mut() {
+            Some(holochain) => holochain,
+            None => return,
+        };
+        loop {
+            if (&mut *holochain).now_or_never().is_some() {
+                self.holochain = None;
+                return;
+            }
+            if std::time::Instant::now() >= deadline {
+                tracing::warn!(
+                    setup = %self.setup_path.display(),
+                    "conductor didn't exit in time, killing it"
+                );
+                holochain.kill().ok();
+                return;
+            }
+            std::thread::sleep(STOP_POLL_INTERVAL);
+        }
+    }
+
+    /// Wait for the conductor to exit.
+    /// The conductor is killed if this is dropped before it exits.
+    pub async fn wait(mut self) -> std::io::Result<ExitStatus> {
+        let holochain = self
+            .holochain
+            .as_mut()
+            .expect("The conductor is only taken on detach");
+        let status = holochain.await;
+        // It has exited so there is nothing to kill on drop.
+        self.holochain = None;
+        status
+    }
+
+    /// Leave the conductor running after this handle is dropped
+    /// and get its process. Dropping the child doesn't kill the conductor.
+    /// Await it to get the exit status or use its `id` to stop it later.
+    pub fn detach(mut self) -> Child {
+        self.holochain
+            .take()
+            .expect("The conductor is only taken on detach")
+    }
+}
+
+impl Drop for ConductorHandle {
+    fn drop(&mut self) {
+        if let Some(holochain) = self.holochain.as_mut() {
+            holochain.kill().ok();
+        }
+    }
+}
+
+#[cfg(unix)]
+fn terminate(holochain: &mut Child) -> std::io::Result<()> {
+    use futures::FutureExt;
+    use nix::sys::signal::kill;
+    use nix::sys::signal::Signal;
+    use nix::unistd::Pid;
+    // The pid of a conductor that already exited could have been reused.
+    if (&mut *holochain).now_or_never().is_some() {
+        return Ok(());
+    }
+    kill(Pid::from_raw(holochain.id() as i32), Signal::SIGTERM)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+}
+
+#[cfg(not(unix))]
+fn terminate(holochain: &mut Child) -> std::io::Result<()> {
+    holochain.kill()
+}
+
+/// Connect to a conductor that was just started.
+async fn connect(
+    admin: &AdminAddress,
+    options: &ConnectOptions,
+    deadline: Duration,
+    exited: &Exited,
+) -> anyhow::Result<CmdRunner> {
+    CmdRunner::try_new_at(admin, options.websocket_config(), deadline)
+        .await
+        .map_err(|e| {
+            anyhow!(e).context(format!(
+                "Could not connect to the conductor on admin {} and {}",
+                admin,
+                exited.describe()
+            ))
+        })
+}
+
+/// Start the conductor and wait for it to report its admin ports or sockets.
+/// They are recorded in the setup and the address of the
+/// [`hc_admin_interface`] is returned.
+///
+/// Dropping the returned child only kills the conductor if `kill_on_drop` is set.
+/// The conductor is killed if it fails to start either way.
+///
+/// [`hc_admin_interface`]: crate::ports::hc_admin_interface
+async fn start(
+    config: &RunConfig,
+    kill_on_drop: bool,
+) -> anyhow::Result<(AdminAddress, Child, Option<ConductorLogs>, Exited)> {
+    let RunConfig {
+        holochain_path,
+        setup_path,
+        force_admin_port,
+        env,
+        log_dir,
+        conductor_log,
+        startup_timeout,
+        keystore_timeout,
+        connect_deadline,
+        passphrase,
+        connect: connect_options,
+    } = config;
+    let mut conductor_config = match read_config(setup_path.clone())? {
         Some(c) => c,
         None => create_config(setup_path.clone()),
     };
     match force_admin_port {
         Some(port) => {
-            set_admin_port(&mut config, port);
+            PortPool::global().reserve(*port, setup_path)?;
+            ensure!(
+                *port == 0 || portpicker::is_free(*port),
+                "Can't run the conductor for {} on admin port {} because something \
+                is already bound to it. Stop it or force another port",
+                setup_path.display(),
+                port
+            );
+            set_admin_port(&mut conductor_config, *port);
         }
-        None => random_admin_port_if_busy(&mut config),
+        None => random_admin_port_if_busy(&mut conductor_config),
     }
-    let config_path = write_config(setup_path.clone(), &config);
+    let passphrase = if needs_passphrase(&conductor_config) {
+        match passphrase {
+            Some(passphrase) => Some(passphrase.clone()),
+            None => Some(read_passphrase(None, false).with_context(|| {
+                format!("Can't start the conductor for {}", setup_path.display())
+            })?),
+        }
+    } else {
+        None
+    };
+    let config_path = write_config(setup_path.clone(), &conductor_config);
+    clear_app_ports(setup_path)?;
+    let logs = match log_dir {
+        Some(log_dir) => {
+            std::fs::create_dir_all(log_dir)
+                .with_context(|| format!("Failed to create log dir {}", log_dir.display()))?;
+            // So `hc logs` can find them.
+            crate::logs::record_log_dir(setup_path, &log_dir.canonicalize()?)?;
+            Some(ConductorLogs::in_dir(log_dir))
+        }
+        None => None,
+    };
+    crate::logs::record_conductor_log(setup_path, &conductor_log.effective(env))?;
     let (tx_config, rx_config) = oneshot::channel();
-    let mut child = start_holochain(holochain_path, config_path, tx_config).await;
-    check_started(&mut child).await;
-    let port = rx_config
+    let exited = Exited::default();
+    let mut child = start_holochain(
+        holochain_path,
+        config_path,
+        env,
+        conductor_log,
+        tx_config,
+        logs.as_ref(),
+        exited.clone(),
+        passphrase.as_ref(),
+        kill_on_drop,
+    )
+    .await?;
+    let admin = async {
+        // A conductor that can't bind a forced admin port exits straight away.
+        let bind_hint = match force_admin_port {
+            Some(port) => format!(". Is the forced admin port {} already in use?", port),
+            None => String::new(),
+        };
+        check_started(&mut child)
+            .await
+            .map_err(|e| anyhow!("{}{}", e, bind_hint))?;
+        let reported = tokio::time::timeout(*startup_timeout, rx_config)
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "Conductor didn't report its admin port within {:?}",
+                    startup_timeout
+                )
+            })?
+            .map_err(|_| {
+                anyhow!(
+                    "Conductor exited before reporting its admin port{}",
+                    bind_hint
+                )
+            })?;
+        let interfaces =
+            bound_admin_interfaces(admin_interfaces_in_config(&conductor_config), &reported);
+        record_admin_interfaces(setup_path, &interfaces)?;
+        let admin = interfaces
+            .into_iter()
+            .find(|i| i.role == AdminRole::Hc)
+            .map(|i| i.address)
+            .or_else(|| reported.first().cloned())
+            .ok_or_else(|| anyhow!("Conductor didn't report an admin interface"))?;
+        if let Some(keystore_timeout) = keystore_timeout {
+            let cmd = connect(&admin, connect_options, *connect_deadline, &exited).await?;
+            wait_for_keystore(&cmd, *keystore_timeout).await?;
+        }
+        anyhow::Result::<_>::Ok(admin)
+    }
+    .await;
+    match admin {
+        Ok(admin) => Ok((admin, child, logs, exited)),
+        Err(e) => {
+            child.kill().ok();
+            Err(e)
+        }
+    }
+}
+
+/// Same as [`spawn`] but also connects to the admin interface
+/// and waits for the keystore to be ready with [`wait_for_keystore`].
+/// Fails if the keystore isn't ready within the `keystore_timeout`.
+pub async fn run_async_ready(
+    holochain_path: &Path,
+    setup_path: PathBuf,
+    force_admin_port: Option<u16>,
+    keystore_timeout: Duration,
+) -> anyhow::Result<(CmdRunner, ConductorHandle)> {
+    let holochain = spawn(RunConfig {
+        holochain_path: holochain_path.to_path_buf(),
+        force_admin_port,
+        keystore_timeout: Some(keystore_timeout),
+        ..RunConfig::new(setup_path)
+    })
+    .await?;
+    let cmd = holochain.connect().await?;
+    Ok((cmd, holochain))
+}
+
+/// Wait until the conductor can use its keystore.
+/// The conductor can accept admin connections before the keystore
+/// is ready so requests like [`AdminRequest::GenerateAgentPubKey`] can fail
+/// right after starting.
+/// This checks by generating agent keys until one succeeds
+/// so it leaves an unused agent key in the keystore.
+///
+/// [`AdminRequest::GenerateAgentPubKey`]: holochain_conductor_api::AdminRequest::GenerateAgentPubKey
+pub async fn wait_for_keystore(cmd: &CmdRunner, timeout: Duration) -> anyhow::Result<()> {
+    let check = async {
+        while let Err(e) = cmd.generate_agent_pub_key().await {
+            tracing::debug!(?e, "Keystore is not ready");
+            tokio::time::delay_for(KEYSTORE_RETRY).await;
+        }
+    };
+    tokio::time::timeout(timeout, check)
         .await
-        .expect("Failed to get admin port from conductor");
-    Ok((port, child))
+        .map_err(|_| anyhow!("Keystore was not ready after {:?}", timeout))
+}
+
+/// Check if the conductor for this setup is running by
+/// connecting to its admin port or socket and making a request.
+/// A config with port `0` lets the conductor pick its port
+/// so the port it bound when hc last started it is checked.
+/// A setup with no admin interface, or one that hc hasn't run,
+/// is reported as not running.
+/// So is a setup whose admin socket doesn't exist.
+/// A conductor that rejects hc's default origin is running
+/// because only a running conductor can reject it.
+pub async fn is_running(setup_path: PathBuf) -> anyhow::Result<bool> {
+    let admin = match live_admin_address(&setup_path)? {
+        Some(AdminAddress::Port(0)) | None => return Ok(false),
+        Some(AdminAddress::Socket(path)) if !path.exists() => return Ok(false),
+        Some(admin) => admin,
+    };
+    let check = async {
+        match CmdRunner::try_new_at(&admin, websocket_config(), Duration::from_secs(0)).await {
+            Ok(cmd) => cmd.command(AdminRequest::ListDnas).await.is_ok(),
+            Err(e) => e.kind() == std::io::ErrorKind::PermissionDenied,
+        }
+    };
+    Ok(tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or(false))
+}
+
+/// The admin address of the setup's conductor.
+/// This is the address in the config unless the config has port `0`,
+/// in which case it's the port the conductor bound when hc last started it.
+pub fn live_admin_address(setup_path: &Path) -> anyhow::Result<Option<AdminAddress>> {
+    match get_admin_address(setup_path.to_path_buf())? {
+        Some(AdminAddress::Port(0)) => Ok(get_admin_interfaces(setup_path)?
+            .into_iter()
+            .find(|i| i.role == AdminRole::Hc)
+            .map(|i| i.address)),
+        address => Ok(address),
+    }
+}
+
+/// A program given as a relative path like `./holochain` is
+/// relative to where hc runs, not to the conductor's directory.
+/// A bare name is still looked up in the `PATH`.
+fn absolute_program(program: &Path) -> PathBuf {
+    if program.components().count() > 1 && program.is_relative() {
+        program
+            .canonicalize()
+            .unwrap_or_else(|_| program.to_path_buf())
+    } else {
+        program.to_path_buf()
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn start_holochain(
     holochain_path: &Path,
     config_path: PathBuf,
-    tx_config: oneshot::Sender<u16>,
-) -> Child {
+    env: &[(String, String)],
+    conductor_log: &ConductorLog,
+    tx_config: oneshot::Sender<Vec<AdminAddress>>,
+    logs: Option<&ConductorLogs>,
+    exited: Exited,
+    passphrase: Option<&Passphrase>,
+    kill_on_drop: bool,
+) -> anyhow::Result<Child> {
     tracing::info!("\n\n----\nstarting holochain\n----\n\n");
-    let mut cmd = Command::new(holochain_path);
-    cmd.arg("--structured")
-        // .env("RUST_LOG", "trace")
-        .arg("--config-path")
+    // Relative paths in the config, like an `--env-dir`, are
+    // relative to the setup so the conductor runs in the setup.
+    let config_path = config_path
+        .canonicalize()
+        .with_context(|| format!("Failed to find {}", config_path.display()))?;
+    let setup_dir = config_path
+        .parent()
+        .expect("The config is in the setup")
+        .to_path_buf();
+    let mut cmd = Command::new(absolute_program(holochain_path));
+    cmd.current_dir(setup_dir).arg("--structured");
+    if let Some(format) = conductor_log.format {
+        cmd.arg(format.structured());
+    }
+    cmd.arg("--config-path")
         .arg(config_path)
+        .envs(env.iter().map(|(k, v)| (k, v)))
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
-        .kill_on_drop(true);
+        .kill_on_drop(kill_on_drop);
+    if let Some(filter) = &conductor_log.filter {
+        cmd.env("RUST_LOG", filter);
+    }
+    if passphrase.is_some() {
+        cmd.stdin(Stdio::piped());
+    }
 
+    let logs = match logs {
+        Some(logs) => Some((log_file(&logs.stdout)?, log_file(&logs.stderr)?)),
+        None => None,
+    };
     let mut holochain = cmd.spawn().expect("Failed to spawn holochain");
-    // TODO: Allow redirecting output per conductor.
-    spawn_output(&mut holochain, tx_config);
-    holochain
+    if let Some(passphrase) = passphrase {
+        // The conductor reads the passphrase up to a newline.
+        // Closing stdin afterwards means it can't wait for more.
+        let mut stdin = holochain.stdin.take().expect("stdin is piped");
+        let sent = async {
+            stdin.write_all(passphrase.as_bytes()).await?;
+            stdin.write_all(b"\n").await
+        };
+        if let Err(e) = sent.await {
+            holochain.kill().ok();
+            return Err(anyhow!(e).context("Failed to send the passphrase to the conductor"));
+        }
+    }
+    spawn_output(&mut holochain, tx_config, logs, exited);
+    Ok(holochain)
+}
+
+fn log_file(path: &Path) -> anyhow::Result<tokio::fs::File> {
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create log file {}", path.display()))?;
+    Ok(tokio::fs::File::from_std(file))
 }
 
 // TODO: Find a better way to confirm the child is running.
-async fn check_started(holochain: &mut Child) {
+async fn check_started(holochain: &mut Child) -> anyhow::Result<()> {
     let started = tokio::time::timeout(std::time::Duration::from_millis(20), holochain).await;
-    if let Ok(status) = started {
-        panic!("Holochain failed to start. status: {:?}", status);
+    match started {
+        Ok(status) => Err(anyhow!("Holochain failed to start. status: {:?}", status)),
+        Err(_) => Ok(()),
     }
 }
 
-fn spawn_output(holochain: &mut Child, config: oneshot::Sender<u16>) {
+/// Forward the conductor's output to our stdout and stderr
+/// or to the log files if there are any.
+fn spawn_output(
+    holochain: &mut Child,
+    config: oneshot::Sender<Vec<AdminAddress>>,
+    logs: Option<(tokio::fs::File, tokio::fs::File)>,
+    exited: Exited,
+) {
     let stdout = holochain.stdout.take();
     let stderr = holochain.stderr.take();
+    let (mut stdout_log, mut stderr_log) = match logs {
+        Some((stdout, stderr)) => (Some(stdout), Some(stderr)),
+        None => (None, None),
+    };
     tokio::task::spawn(async move {
         let mut needs_setup = true;
         let mut config = Some(config);
+        let mut admins = Vec::new();
         if let Some(stdout) = stdout {
             let mut reader = BufReader::new(stdout).lines();
             while let Ok(Some(line)) = reader.next_line().await {
                 if needs_setup {
-                    match check_setup(&line, &mut needs_setup) {
-                        (true, Some(port)) => {
+                    if let (true, admin) = check_setup(&line, &mut needs_setup) {
+                        admins.extend(admin);
+                        // Every admin interface is listed before the end of the setup.
+                        if !needs_setup {
                             if let Some(config) = config.take() {
                                 config
-                                    .send(port)
-                                    .expect("Failed to send admin port from config");
+                                    .send(std::mem::take(&mut admins))
+                                    .expect("Failed to send admin ports from config");
                             }
-                            continue;
                         }
-                        (true, None) => continue,
-                        (false, _) => (),
+                        continue;
                     }
                 }
-                println!("{}", line);
+                match stdout_log.as_mut() {
+                    Some(log) => write_line(log, &line).await,
+                    None => println!("{}", line),
+                }
             }
         }
+        exited.set();
     });
     tokio::task::spawn(async move {
         if let Some(stderr) = stderr {
             let mut reader = BufReader::new(stderr).lines();
             while let Ok(Some(line)) = reader.next_line().await {
-                eprintln!("{}", line);
+                match stderr_log.as_mut() {
+                    Some(log) => write_line(log, &line).await,
+                    None => eprintln!("{}", line),
+                }
             }
         }
     });
 }
 
-fn check_setup(line: &str, needs_setup: &mut bool) -> (bool, Option<u16>) {
+async fn write_line(log: &mut tokio::fs::File, line: &str) {
+    let write = async {
+        log.write_all(format!("{}\n", line).as_bytes()).await?;
+        log.flush().await
+    };
+    if let Err(e) = write.await {
+        tracing::warn!(?e, "Failed to write conductor log");
+    }
+}
+
+fn check_setup(line: &str, needs_setup: &mut bool) -> (bool, Option<AdminAddress>) {
     if let Some(line) = line.strip_prefix("###") {
         if let Some(line) = line.strip_suffix("###") {
             match line {
@@ -156,9 +1020,12 @@ fn check_setup(line: &str, needs_setup: &mut bool) -> (bool, Option<u16>) {
                 _ => {
                     if let Some(v) = line.strip_prefix("ADMIN_PORT:") {
                         if let Ok(port) = v.parse::<u16>() {
-                            return (true, Some(port));
+                            return (true, Some(AdminAddress::Port(port)));
                         }
                     }
+                    if let Some(v) = line.strip_prefix("ADMIN_SOCKET:") {
+                        return (true, Some(AdminAddress::Socket(PathBuf::from(v))));
+                    }
                 }
             }
             return (true, None);
@@ -2,10 +2,28 @@
 //! This module gives basic helpers to save / load your setups
 //! in a `.hc` file.
 //! This is very much WIP and subject to change.
+use std::path::Path;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::ensure;
+use anyhow::Context;
 
 use crate::config;
 use crate::config::CONDUCTOR_CONFIG;
+use crate::failures::FailurePolicy;
+use crate::failures::Failures;
+use crate::ports::AdminAddress;
+
+/// Name of the file in a setup that records the directory its
+/// databases are in if they aren't in the setup.
+pub const DB_DIR_FILE: &str = "db-dir";
+
+/// Name of the file in a setup that marks it as generated with `hc gen --dev`.
+pub const DEV_MODE_FILE: &str = "dev-mode";
+
+/// How long [`status`] waits for an admin interface to accept a connection.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Save all setups to the `.hc` file in the `hc_dir` directory.
 pub fn save(mut hc_dir: PathBuf, paths: Vec<PathBuf>) -> anyhow::Result<()> {
@@ -27,7 +45,13 @@ pub fn save(mut hc_dir: PathBuf, paths: Vec<PathBuf>) -> anyhow::Result<()> {
 /// You can get the index by calling [`load`].
 /// If no setups are passed in then all are deleted.
 /// If all setups are deleted the `.hc` file will be removed.
-pub fn clean(mut hc_dir: PathBuf, setups: Vec<usize>) -> anyhow::Result<()> {
+/// A setup that can't be removed doesn't stop the others
+/// unless `on_failure` is [`FailurePolicy::FailFast`].
+pub fn clean(
+    mut hc_dir: PathBuf,
+    setups: Vec<usize>,
+    on_failure: Option<FailurePolicy>,
+) -> anyhow::Result<()> {
     let existing = load(hc_dir.clone())?;
     let setups_len = setups.len();
     let to_remove: Vec<_> = if setups.is_empty() {
@@ -36,11 +60,10 @@ pub fn clean(mut hc_dir: PathBuf, setups: Vec<usize>) -> anyhow::Result<()> {
         setups.into_iter().filter_map(|i| existing.get(i)).collect()
     };
     let to_remove_len = to_remove.len();
+    let mut failures = Failures::new(on_failure.unwrap_or(FailurePolicy::KeepGoing));
     for p in to_remove {
-        if p.exists() && p.is_dir() {
-            if let Err(e) = std::fs::remove_dir_all(p) {
-                tracing::error!("Failed to remove {} because {:?}", p.display(), e);
-            }
+        if let Err(e) = remove_setup(p) {
+            failures.fail(&p.display().to_string(), e)?;
         }
     }
     if setups_len == 0 || setups_len == to_remove_len {
@@ -49,9 +72,208 @@ pub fn clean(mut hc_dir: PathBuf, setups: Vec<usize>) -> anyhow::Result<()> {
             std::fs::remove_file(hc_dir)?;
         }
     }
+    failures.finish("Clean", to_remove_len)
+}
+
+/// Remove the setups in the `.hc` file in `hc_dir` that were generated
+/// with `hc gen --dev` and their entries, and keep the others.
+/// This is what `hc clean --dev` does.
+pub fn clean_dev(hc_dir: PathBuf, on_failure: Option<FailurePolicy>) -> anyhow::Result<()> {
+    let existing = load(hc_dir.clone())?;
+    let dev: Vec<_> = existing
+        .iter()
+        .enumerate()
+        .filter(|(_, path)| is_dev(path))
+        .map(|(i, _)| i)
+        .collect();
+    if dev.is_empty() {
+        msg!("There are no dev setups to clean");
+        return Ok(());
+    }
+    // Clean removes the .hc file if every setup is a dev setup.
+    let result = clean(hc_dir.clone(), dev.clone(), on_failure);
+    let file = hc_dir.join(".hc");
+    if file.exists() {
+        // Setups that failed to be removed keep their entries.
+        let kept: Vec<_> = existing
+            .into_iter()
+            .enumerate()
+            .filter(|(i, path)| !dev.contains(i) || path.exists())
+            .map(|(_, path)| path)
+            .collect();
+        write_entries(&file, &kept)?;
+    }
+    result
+}
+
+/// Remove a setup and its databases if they are somewhere else.
+/// Its shared keystore is removed if no other setup uses it.
+fn remove_setup(path: &Path) -> anyhow::Result<()> {
+    if let Some(keystore) = crate::keystore::release_keystore(path)? {
+        msg!("Removed shared keystore {}", keystore.display());
+    }
+    if let Some(db_dir) = db_dir(path)? {
+        if db_dir.is_dir() {
+            std::fs::remove_dir_all(&db_dir)
+                .with_context(|| format!("Failed to remove {}", db_dir.display()))?;
+        }
+    }
+    if path.exists() && path.is_dir() {
+        std::fs::remove_dir_all(path)
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
     Ok(())
 }
 
+/// Remove the setups from the `.hc` file in `hc_dir` whose directories
+/// are gone, like setups in a temp directory that was cleared on reboot.
+/// With `probe` the setups whose conductors don't respond are removed too.
+/// Only the entries are removed, never the directories,
+/// and the `.hc` file is removed if no setups are left.
+/// Returns the setups that were removed so running it again removes none.
+/// This is what `hc prune` does.
+pub async fn prune(hc_dir: PathBuf, probe: bool) -> anyhow::Result<Vec<PathBuf>> {
+    let file = hc_dir.join(".hc");
+    if !file.exists() {
+        data!("Pruned 0 setups, there is no .hc file");
+        return Ok(Vec::new());
+    }
+    let existing = std::fs::read_to_string(&file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let mut kept = Vec::new();
+    let mut pruned = Vec::new();
+    for path in existing
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(PathBuf::from)
+    {
+        if !path.is_dir() {
+            msg!("Pruned {}: the directory is gone", path.display());
+            pruned.push(path);
+        } else if probe && !crate::run::is_running(path.clone()).await.unwrap_or(false) {
+            msg!("Pruned {}: the conductor didn't respond", path.display());
+            pruned.push(path);
+        } else {
+            kept.push(path);
+        }
+    }
+    data!("Pruned {} setups, {} are left", pruned.len(), kept.len());
+    if !pruned.is_empty() {
+        write_entries(&file, &kept)?;
+    }
+    Ok(pruned)
+}
+
+/// Replace the setups in the `.hc` `file` with `kept`
+/// or remove the file if there are none.
+fn write_entries(file: &Path, kept: &[PathBuf]) -> anyhow::Result<()> {
+    if kept.is_empty() {
+        std::fs::remove_file(file).with_context(|| format!("Failed to remove {}", file.display()))
+    } else {
+        let contents: String = kept.iter().map(|p| format!("{}\n", p.display())).collect();
+        std::fs::write(file, contents)
+            .with_context(|| format!("Failed to write {}", file.display()))
+    }
+}
+
+/// Record that the databases of the setup at `setup_path`
+/// are in `db_dir` so [`clean`] removes them with the setup.
+pub fn record_db_dir(setup_path: &Path, db_dir: &Path) -> anyhow::Result<()> {
+    let file = setup_path.join(DB_DIR_FILE);
+    std::fs::write(&file, db_dir.display().to_string())
+        .with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// The directory the databases of the setup at `setup_path` are in
+/// if it was generated with `--db-dir`.
+pub fn db_dir(setup_path: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let file = setup_path.join(DB_DIR_FILE);
+    if !file.exists() {
+        return Ok(None);
+    }
+    let dir = std::fs::read_to_string(&file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    Ok(Some(PathBuf::from(dir.trim_end())))
+}
+
+/// Mark the setup at `setup_path` as generated with `hc gen --dev`.
+pub fn record_dev_mode(setup_path: &Path) -> anyhow::Result<()> {
+    let file = setup_path.join(DEV_MODE_FILE);
+    std::fs::write(
+        &file,
+        "Generated with `hc gen --dev`. Never connect it to a real network.\n",
+    )
+    .with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// Was the setup at `setup_path` generated with `hc gen --dev`.
+pub fn is_dev(setup_path: &Path) -> bool {
+    setup_path.join(DEV_MODE_FILE).is_file()
+}
+
+/// ` [dev]` for dev setups to tag them in lists.
+fn dev_tag(setup_path: &Path) -> &'static str {
+    if is_dev(setup_path) {
+        " [dev]"
+    } else {
+        ""
+    }
+}
+
+/// Move the setup at `index` in the `.hc` file to `new_path`,
+/// rewrite the paths in its config that are in the setup
+/// and save the new path in place of the old one.
+/// Returns where the setup is now.
+/// The setup can't be running and `new_path` can't exist.
+pub async fn relocate(hc_dir: PathBuf, index: usize, new_path: PathBuf) -> anyhow::Result<PathBuf> {
+    let old_path = load(hc_dir.clone())?
+        .get(index)
+        .cloned()
+        .with_context(|| format!("There is no setup at index {}", index))?;
+    ensure!(
+        !crate::run::is_running(old_path.clone()).await?,
+        "Setup {} is running. Stop it before relocating it",
+        index
+    );
+    ensure!(!new_path.exists(), "{} already exists", new_path.display());
+    if let Some(parent) = new_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::rename(&old_path, &new_path).with_context(|| {
+        format!(
+            "Failed to move {} to {}. A setup can't be moved to another file system",
+            old_path.display(),
+            new_path.display()
+        )
+    })?;
+    let new_path = new_path.canonicalize()?;
+    config::relocate_setup(&old_path, &new_path)?;
+    if let Some(mut dir) = db_dir(&new_path)? {
+        config::relocate_path(&mut dir, &old_path, &new_path);
+        record_db_dir(&new_path, &dir)?;
+    }
+    crate::keystore::move_keystore_user(&old_path, &new_path)?;
+
+    let file = hc_dir.join(".hc");
+    let existing = std::fs::read_to_string(&file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let rewritten: String = existing
+        .lines()
+        .map(|line| {
+            if Path::new(line) == old_path {
+                format!("{}\n", new_path.display())
+            } else {
+                format!("{}\n", line)
+            }
+        })
+        .collect();
+    std::fs::write(&file, rewritten)
+        .with_context(|| format!("Failed to write {}", file.display()))?;
+    msg!("Moved setup {} to {}", index, new_path.display());
+    Ok(new_path)
+}
+
 /// Load setup paths from the `.hc` file.
 pub fn load(mut hc_dir: PathBuf) -> anyhow::Result<Vec<PathBuf>> {
     let mut paths = Vec::new();
@@ -72,27 +294,169 @@ pub fn load(mut hc_dir: PathBuf) -> anyhow::Result<Vec<PathBuf>> {
     Ok(paths)
 }
 
+/// The setup at `index` in the `.hc` file in `hc_dir`, or the only
+/// setup if `index` is `None` and there is just one, with its index.
+/// `action` is what the command does with it for the error
+/// when there are no setups, e.g. `"show the logs of"`.
+pub fn choose_setup(
+    hc_dir: PathBuf,
+    index: Option<usize>,
+    action: &str,
+) -> anyhow::Result<(usize, PathBuf)> {
+    let setups = load(hc_dir)?;
+    match (index, setups.as_slice()) {
+        (Some(index), _) => setups
+            .get(index)
+            .cloned()
+            .map(|p| (index, p))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "There is no setup at index {}. Run `hc list` to see the setups",
+                    index
+                )
+            }),
+        (None, [path]) => Ok((0, path.clone())),
+        (None, []) => anyhow::bail!("There are no setups to {}", action),
+        (None, _) => anyhow::bail!(
+            "Choose a setup with --index. There are {} setups",
+            setups.len()
+        ),
+    }
+}
+
 /// Print out the setups contained in the `.hc` file.
 pub fn list(hc_dir: PathBuf, verbose: usize) -> anyhow::Result<()> {
-    let out = load(hc_dir)?.into_iter().enumerate().try_fold(
-        "\nSetups contained in `.hc`\n".to_string(),
-        |out, (i, path)| {
+    msg!("Setups contained in `.hc`");
+    let out = load(hc_dir)?
+        .into_iter()
+        .enumerate()
+        .try_fold(String::new(), |out, (i, path)| {
             let r = match verbose {
-                0 => format!("{}{}: {}\n", out, i, path.display()),
+                0 => format!("{}{}: {}{}\n", out, i, path.display(), dev_tag(&path)),
                 _ => {
                     let config = config::read_config(path.clone())?;
+                    let address = crate::ports::get_admin_address(path.clone())?;
+                    let admin = match &address {
+                        Some(admin) => admin.to_string(),
+                        None => "none".to_string(),
+                    };
+                    let mut port = match crate::ports::admin_port_from_config(&path)? {
+                        Some(port) => format!("Admin port: {}\n", port),
+                        None => match address {
+                            Some(AdminAddress::Socket(_)) => String::new(),
+                            _ => "Admin port: picked when run\n".to_string(),
+                        },
+                    };
+                    // In the form `--host` takes.
+                    if let Some(host) = crate::ports::admin_host_from_config(&path)? {
+                        port.push_str(&format!("Admin host: {}\n", host));
+                    }
+                    let origins = match crate::ports::allowed_origins_from_config(&path)? {
+                        Some(origins) => origins.join(", "),
+                        None => "any".to_string(),
+                    };
+                    port.push_str(&format!("Allowed origins: {}\n", origins));
+                    let app_ports = crate::ports::app_ports_from_config(&path)?;
+                    if !app_ports.is_empty() {
+                        let app_ports: Vec<_> = app_ports.iter().map(u16::to_string).collect();
+                        port.push_str(&format!("App ports: {}\n", app_ports.join(", ")));
+                    }
                     format!(
-                        "{}{}: {}\nConductor Config:\n{:?}\n",
+                        "{}{}: {}{}\nAdmin interface: {}\n{}Conductor Config:\n{:?}\n",
                         out,
                         i,
                         path.display(),
+                        dev_tag(&path),
+                        admin,
+                        port,
                         config
                     )
                 }
             };
             anyhow::Result::<_, anyhow::Error>::Ok(r)
-        },
-    )?;
-    msg!("{}", out);
+        })?;
+    data!("{}", out);
+    Ok(())
+}
+
+/// Print out the setups contained in the `.hc` file as JSON,
+/// with the index, path, admin interface, admin port, allowed origins,
+/// app ports in the config and whether it is a dev setup of each setup.
+/// The admin port is `null` if it is picked when the setup is run
+/// and the allowed origins are `null` if any origin is allowed.
+pub fn list_json(hc_dir: PathBuf) -> anyhow::Result<()> {
+    let setups = load(hc_dir)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let admin = crate::ports::get_admin_address(path.clone())?.map(|a| a.to_string());
+            let admin_port = crate::ports::admin_port_from_config(&path)?;
+            let admin_host = crate::ports::admin_host_from_config(&path)?;
+            let allowed_origins = crate::ports::allowed_origins_from_config(&path)?;
+            let app_ports = crate::ports::app_ports_from_config(&path)?;
+            Ok(serde_json::json!({
+                "index": index,
+                "path": path,
+                "admin": admin,
+                "admin_port": admin_port,
+                "admin_host": admin_host,
+                "allowed_origins": allowed_origins,
+                "app_ports": app_ports,
+                "dev": is_dev(&path),
+            }))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    data!("{}", serde_json::to_string_pretty(&setups)?);
+    Ok(())
+}
+
+/// Print the number of setups in the `.hc` file
+/// and how many of them are running.
+pub async fn count(hc_dir: PathBuf) -> anyhow::Result<()> {
+    let paths = load(hc_dir)?;
+    let total = paths.len();
+    let running = futures::future::try_join_all(paths.into_iter().map(crate::run::is_running))
+        .await?
+        .into_iter()
+        .filter(|r| *r)
+        .count();
+    data!(
+        "total: {}, running: {}, stopped: {}",
+        total,
+        running,
+        total - running
+    );
+    Ok(())
+}
+
+/// Print whether each admin interface of the setups in the `.hc` file
+/// is accepting connections.
+/// The ports are the ones the conductors bound when hc last started them
+/// and the log settings are the ones they were started with.
+pub async fn status(hc_dir: PathBuf) -> anyhow::Result<()> {
+    use std::fmt::Write;
+    let mut out = String::new();
+    for (i, path) in load(hc_dir)?.into_iter().enumerate() {
+        writeln!(out, "{}: {}{}", i, path.display(), dev_tag(&path))?;
+        let interfaces = crate::ports::get_admin_interfaces(&path)?;
+        let reachable = futures::future::join_all(
+            interfaces
+                .iter()
+                .map(|interface| crate::ports::is_reachable(interface, STATUS_TIMEOUT)),
+        )
+        .await;
+        for (interface, reachable) in interfaces.iter().zip(reachable) {
+            let state = match (&interface.address, reachable) {
+                (AdminAddress::Port(0), _) => "port picked when run",
+                (_, true) => "reachable",
+                (_, false) => "unreachable",
+            };
+            writeln!(out, "  {} admin {}: {}", interface.role, interface, state)?;
+        }
+        if let Some(log) = crate::logs::read_conductor_log(&path)? {
+            writeln!(out, "  conductor log: {}", log)?;
+        }
+    }
+    data!("{}", out);
     Ok(())
 }
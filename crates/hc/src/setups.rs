@@ -2,38 +2,264 @@
 use std::path::Path;
 use std::path::PathBuf;
 
+use anyhow::Context;
+use holochain_types::prelude::InstalledCell;
+use holochain_types::prelude::JsonProperties;
+
+use crate::calls::AppDna;
 use crate::calls::InstallApp;
 use crate::cmds::*;
-use crate::run::run_async;
+use crate::keystore::KeystoreKind;
+use crate::run::run_async_ready;
+use crate::run::spawn;
+use crate::run::ConductorHandle;
+use crate::run::RunConfig;
+use crate::run::DEFAULT_KEYSTORE_TIMEOUT;
+use crate::run::DEFAULT_STOP_TIMEOUT;
 use crate::CmdRunner;
 
+/// Running conductors for a list of setups.
+/// The conductors are stopped when this is dropped
+/// so they can't be leaked.
+/// They are asked to exit with `SIGTERM` and killed if they haven't
+/// within the [`DEFAULT_STOP_TIMEOUT`]. Dropping blocks until then,
+/// use [`Setups::shutdown`] in async code.
+///
+/// ```no_run
+/// # async fn f() -> anyhow::Result<()> {
+/// use holochain_hc::Setups;
+/// use std::path::Path;
+/// let paths = holochain_hc::save::load(std::env::current_dir()?)?;
+/// let mut setups = Setups::run(Path::new("holochain"), paths).await?;
+/// let cells = setups.get(0).unwrap().list_cell_ids().await?;
+/// println!("{:?}", cells);
+/// // All the conductors are stopped here.
+/// drop(setups);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Setups {
+    running: Vec<(CmdRunner, ConductorHandle)>,
+}
+
+impl Setups {
+    /// Run a conductor for each setup and connect to them.
+    /// Waits for each conductor's keystore to be ready.
+    /// If any conductor fails to start the others are stopped.
+    pub async fn run(holochain_path: &Path, paths: Vec<PathBuf>) -> anyhow::Result<Self> {
+        let running = paths
+            .into_iter()
+            .map(|path| run_async_ready(holochain_path, path, None, DEFAULT_KEYSTORE_TIMEOUT));
+        let running = futures::future::try_join_all(running).await?;
+        Ok(Self { running })
+    }
+
+    /// The connection to the conductor for the setup at this index.
+    pub fn get(&mut self, index: usize) -> Option<&mut CmdRunner> {
+        self.running.get_mut(index).map(|(cmd, _)| cmd)
+    }
+
+    /// The running conductor for the setup at this index.
+    pub fn handle(&self, index: usize) -> Option<&ConductorHandle> {
+        self.running.get(index).map(|(_, handle)| handle)
+    }
+
+    /// The number of running setups.
+    pub fn len(&self) -> usize {
+        self.running.len()
+    }
+
+    /// Are there no running setups.
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    /// Stop all the conductors and wait for them to exit.
+    /// Conductors that don't exit within the [`DEFAULT_STOP_TIMEOUT`] are killed.
+    pub async fn shutdown(mut self) -> anyhow::Result<()> {
+        let stopping = self.running.drain(..).map(|(cmd, holochain)| {
+            drop(cmd);
+            holochain.stop(DEFAULT_STOP_TIMEOUT)
+        });
+        for stopped in futures::future::join_all(stopping).await {
+            stopped?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Setups {
+    fn drop(&mut self) {
+        for (_, holochain) in &mut self.running {
+            holochain.terminate().ok();
+        }
+        let deadline = std::time::Instant::now() + DEFAULT_STOP_TIMEOUT;
+        for (_, holochain) in &mut self.running {
+            holochain.wait_or_kill_blocking(deadline);
+        }
+    }
+}
+
 /// Generates a new setup with a default [`ConductorConfig`]
 /// and optional network.
 /// Then installs the dnas with a new app per dna.
+/// The app is installed for the [`Create::reuse_agent`]
+/// from `$(pwd)/.hc_agents` if it is set.
+/// The admin interface is a unix socket if [`Create::uds`] is set.
+/// The app is left inactive if [`Create::no_enable`] is set.
+/// Zome calls are metered if [`Create::zome_call_metered`] is set.
+/// The interfaces listen on [`Create::bind`] if it is set.
+/// The interfaces accept the [`Create::allowed_origins`] and the origin of [`Create::connect`].
+/// The config has app interfaces on the [`Create::app_ports`] once the app is installed.
+/// The databases are in [`Create::db_dir`] or [`Create::env_dir`] if one is set.
+/// The keys are in the keystore [`Create::keystore_kind`] chooses.
+/// Another app sharing the app's cells is installed
+/// if [`Create::install_with_existing_cells`] is set.
+/// The setup is recorded as a dev setup if [`Create::dev`] is set
+/// but [`Create::apply_dev`] must be called first to set its options.
 pub async fn default_with_network(
     holochain_path: &Path,
     create: Create,
     directory: Option<PathBuf>,
     dnas: Vec<PathBuf>,
 ) -> anyhow::Result<PathBuf> {
+    let spec = create.config_spec()?;
+    let keystore = create.keystore_kind();
     let Create {
-        network,
         app_id,
         root,
+        reuse_agent,
+        uds,
+        no_enable,
+        allowed_origins,
+        app_ports,
+        properties,
+        db_dir,
+        env_dir,
+        install_with_existing_cells,
+        dev,
+        connect,
         ..
     } = create;
-    let path = crate::generate::generate(network.map(|n| n.into_inner().into()), root, directory)?;
-    let conductor = run_async(holochain_path, path.clone(), None).await?;
-    let mut cmd = CmdRunner::new(conductor.0).await;
+    if let Some(composed) = &install_with_existing_cells {
+        anyhow::ensure!(
+            *composed != app_id,
+            "--install-with-existing-cells needs another app id than {}",
+            app_id
+        );
+    }
+    let properties = properties
+        .map(|p| crate::dna::read_properties(&p))
+        .transpose()?;
+    let db_dir = db_dir.map(|d| prepare_db_dir(&d)).transpose()?;
+    let path = crate::generate::generate(spec.network.clone(), root, directory)?;
+    crate::config::apply_overrides(path.clone(), spec)?;
+    if dev {
+        crate::save::record_dev_mode(&path)?;
+    }
+    if let Some(db_dir) = &db_dir {
+        crate::config::use_environment_path(path.clone(), db_dir.clone())?;
+        crate::save::record_db_dir(&path, db_dir)?;
+    }
+    if let KeystoreKind::Shared(keystore) = &keystore {
+        crate::keystore::share_keystore(&path, keystore)?;
+    }
+    if let Some(env_dir) = env_dir {
+        let dir = path.join(&env_dir);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        // Relative so the setup can be moved.
+        crate::config::use_environment_path(path.clone(), env_dir)?;
+    }
+    if uds {
+        crate::ports::use_admin_socket(path.clone())?;
+    }
+    if !allowed_origins.is_empty() || connect.origin != crate::DEFAULT_ORIGIN {
+        let allowed = crate::ports::allowed_origins(&connect.origin, allowed_origins);
+        crate::ports::use_allowed_origins(path.clone(), allowed)?;
+    }
+    let mut agent = match reuse_agent {
+        Some(name) => {
+            let agent = crate::agents::get_or_create(&std::env::current_dir()?, &name)?;
+            crate::agents::use_keystore(path.clone(), &agent)?;
+            Some(agent)
+        }
+        None => None,
+    };
+    let holochain = spawn(RunConfig {
+        holochain_path: holochain_path.to_path_buf(),
+        keystore_timeout: Some(DEFAULT_KEYSTORE_TIMEOUT),
+        connect,
+        ..RunConfig::new(path.clone())
+    })
+    .await?;
+    let cmd = holochain.connect().await?;
+    let agent_key = match &mut agent {
+        Some(agent) => Some(crate::agents::agent_key(&cmd, agent).await?),
+        None => None,
+    };
     let install_app = InstallApp {
-        app_id,
-        agent_key: None,
+        app_id: app_id.clone(),
+        agent_key,
         dnas,
+        memproof_none: false,
+        memproof: Vec::new(),
+        no_enable,
+    };
+    let cells = match properties {
+        Some(properties) => install_with_properties(&cmd, install_app, properties).await?,
+        None => crate::calls::install_app(&cmd, install_app).await?,
     };
-    crate::calls::install_app(&mut cmd, install_app).await?;
+    if let Some(composed) = install_with_existing_cells {
+        let cells = cells
+            .into_iter()
+            .map(|cell| {
+                let hash = cell.as_id().dna_hash().clone();
+                (cell.into_id(), hash)
+            })
+            .collect();
+        crate::calls::install_app_with_existing_cells(&cmd, composed.clone(), cells).await?;
+        if !no_enable {
+            cmd.activate_app(composed.clone()).await?;
+        }
+        msg!("Installed {} with the cells of {}", composed, app_id);
+    }
+    if no_enable {
+        msg!(
+            "Installed {} without activating it. Activate it with `hc call activate-app {}`",
+            app_id,
+            app_id
+        );
+    }
+    // After installing so the app ports only need to be
+    // free when the setup is run.
+    if !app_ports.is_empty() {
+        crate::ports::use_config_app_ports(path.clone(), &app_ports)?;
+    }
     Ok(path)
 }
 
+/// Install the app with the properties of its dnas replaced.
+async fn install_with_properties(
+    cmd: &CmdRunner,
+    args: InstallApp,
+    properties: JsonProperties,
+) -> anyhow::Result<Vec<InstalledCell>> {
+    let proofs = crate::calls::membrane_proofs(&args)?;
+    let mut dnas = Vec::with_capacity(args.dnas.len());
+    for path in &args.dnas {
+        let dna = crate::dna::read_dna(path).await?;
+        let dna = crate::dna::with_modifiers(dna, None, Some(properties.clone())).await?;
+        dnas.push(AppDna::File(dna));
+    }
+    let dnas = dnas.into_iter().zip(proofs).collect();
+    if args.no_enable {
+        crate::calls::install_app_dnas_inactive(cmd, args.app_id, args.agent_key, dnas).await
+    } else {
+        crate::calls::install_app_dnas_with_proofs(cmd, args.app_id, args.agent_key, dnas).await
+    }
+}
+
 /// Same as [`default_with_network`] but creates n copies
 /// of this setup in thier own directory.
 pub async fn default_n(
@@ -42,7 +268,17 @@ pub async fn default_n(
     create: Create,
     dnas: Vec<PathBuf>,
 ) -> anyhow::Result<Vec<PathBuf>> {
-    msg!("Creating {} conductors with same settings", n);
+    anyhow::ensure!(
+        create.db_dir.is_none() || n <= 1,
+        "--db-dir can't be used with more than one conductor \
+        because conductors can't share databases"
+    );
+    anyhow::ensure!(
+        create.app_ports.is_empty() || n <= 1,
+        "--app-port can't be used with more than one conductor \
+        because they can't all listen on the same port"
+    );
+    tracing::info!("Creating {} conductors with same settings", n);
     let mut paths = Vec::with_capacity(n);
     for i in 0..n {
         let p = default_with_network(
@@ -57,3 +293,56 @@ pub async fn default_n(
     msg!("Created {:?}", paths);
     Ok(paths)
 }
+
+/// Create the directory to keep a new setup's databases in
+/// and check that no other conductor's databases are in it,
+/// because conductors can't share databases.
+/// Returns the absolute path of the directory.
+pub fn prepare_db_dir(db_dir: &Path) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(db_dir)
+        .with_context(|| format!("Failed to create the db dir {}", db_dir.display()))?;
+    let db_dir = db_dir.canonicalize()?;
+    for setup in crate::save::load(std::env::current_dir()?)? {
+        let environment: PathBuf = match crate::config::read_config(setup.clone())? {
+            Some(config) => crate::config::environment_path(&setup, &config),
+            None => continue,
+        };
+        anyhow::ensure!(
+            environment.canonicalize().ok().as_ref() != Some(&db_dir),
+            "The setup {} already keeps its databases in {} \
+            and conductors can't share databases",
+            setup.display(),
+            db_dir.display()
+        );
+    }
+    // Where the conductor's own environment would be.
+    anyhow::ensure!(
+        !db_dir.join("conductor").exists(),
+        "{} already has a conductor's databases in it \
+        and conductors can't share databases",
+        db_dir.display()
+    );
+    Ok(db_dir)
+}
+
+/// The keystore directory the setup's conductor uses.
+/// This is the setup's own keystore unless it was generated
+/// with `--reuse-agent`, in which case it is the agent's keystore
+/// in `$(pwd)/.hc_agents` that other setups share,
+/// or `--keystore shared:<path>`.
+/// Setups with an ephemeral keystore have no keystore directory.
+pub fn keystore_path(setup_path: &Path) -> anyhow::Result<PathBuf> {
+    let config = crate::config::read_config(setup_path.to_path_buf())?
+        .ok_or_else(|| anyhow::anyhow!("No conductor config in {}", setup_path.display()))?;
+    anyhow::ensure!(
+        !config.use_dangerous_test_keystore,
+        "The setup {} has an ephemeral keystore so its keys aren't stored on disk",
+        setup_path.display()
+    );
+    config.keystore_path.ok_or_else(|| {
+        anyhow::anyhow!(
+            "The setup {} doesn't set a keystore path so its keys aren't stored on disk",
+            setup_path.display()
+        )
+    })
+}
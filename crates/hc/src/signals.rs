@@ -0,0 +1,245 @@
+//! Buffering for the signals an app interface sends to an
+//! [`AppCmdRunner`](crate::AppCmdRunner).
+//!
+//! A chatty zome can emit signals faster than they are read
+//! so they are held in a [`SignalBuffer`] with a fixed capacity.
+//! When the buffer is full the [`OverflowPolicy`] decides which
+//! signals are dropped. Dropped signals are counted and a
+//! [`SignalEvent::Gap`] takes their place in the stream so a
+//! consumer knows where it missed signals and how many.
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use holochain_types::prelude::Signal;
+use holochain_websocket::WebsocketMessage;
+use holochain_websocket::WebsocketReceiver;
+use tokio::stream::StreamExt;
+use tokio::sync::Notify;
+
+/// How many signals an [`AppCmdRunner`](crate::AppCmdRunner)
+/// buffers by default.
+pub const DEFAULT_SIGNAL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Which signals to drop when a [`SignalBuffer`] is full.
+pub enum OverflowPolicy {
+    /// Drop the oldest buffered signal to make room for the new one.
+    /// The consumer always sees the latest signals.
+    DropOldest,
+    /// Drop the new signal.
+    /// The consumer sees every signal up to the first overflow.
+    DropNewest,
+}
+
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        OverflowPolicy::DropOldest
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// The capacity and overflow policy of a [`SignalBuffer`].
+pub struct SignalBufferConfig {
+    /// The most signals held at once.
+    /// A capacity of zero is treated as one.
+    pub capacity: usize,
+    /// Which signals to drop when the buffer is full.
+    pub policy: OverflowPolicy,
+}
+
+impl Default for SignalBufferConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_SIGNAL_CAPACITY,
+            policy: OverflowPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An item in the stream of signals read from a [`SignalBuffer`].
+pub enum SignalEvent {
+    /// A signal from the conductor.
+    Signal(Signal),
+    /// This many signals were dropped at this point in the stream
+    /// because the buffer was full.
+    Gap(u64),
+}
+
+/// A bounded queue of the signals received on an app interface.
+/// This is cheaply clone-able and all clones share the same queue.
+#[derive(Clone)]
+pub struct SignalBuffer(Arc<Shared>);
+
+struct Shared {
+    capacity: usize,
+    policy: OverflowPolicy,
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct State {
+    /// Signals and the gaps between them.
+    /// Gaps are merged so there are never two in a row.
+    events: VecDeque<SignalEvent>,
+    /// How many of the events are signals.
+    buffered: usize,
+    dropped: u64,
+    closed: bool,
+}
+
+impl SignalBuffer {
+    /// Create an empty buffer.
+    pub fn new(config: SignalBufferConfig) -> Self {
+        Self(Arc::new(Shared {
+            capacity: config.capacity.max(1),
+            policy: config.policy,
+            state: Mutex::new(State::default()),
+            notify: Notify::new(),
+        }))
+    }
+
+    /// Add a signal, dropping one if the buffer is full.
+    /// Signals pushed after the buffer is closed are ignored.
+    pub fn push(&self, signal: Signal) {
+        let mut state = self.state();
+        if state.closed {
+            return;
+        }
+        if state.buffered >= self.0.capacity {
+            state.dropped += 1;
+            match self.0.policy {
+                OverflowPolicy::DropOldest => state.drop_oldest(),
+                OverflowPolicy::DropNewest => {
+                    state.mark_gap_at_back();
+                    drop(state);
+                    self.0.notify.notify();
+                    return;
+                }
+            }
+        }
+        state.events.push_back(SignalEvent::Signal(signal));
+        state.buffered += 1;
+        drop(state);
+        self.0.notify.notify();
+    }
+
+    /// Mark the end of the stream.
+    /// Buffered signals can still be read.
+    pub fn close(&self) {
+        self.state().closed = true;
+        self.0.notify.notify();
+    }
+
+    /// Take the next event without waiting.
+    pub fn try_next(&self) -> Option<SignalEvent> {
+        self.state().pop()
+    }
+
+    /// Wait for the next event.
+    /// Returns `None` once the buffer is closed and empty.
+    pub async fn next(&self) -> Option<SignalEvent> {
+        loop {
+            {
+                let mut state = self.state();
+                let next = state.pop();
+                let done = next.is_none() && state.closed;
+                if next.is_some() || done {
+                    // Pass the wake up on to any other reader.
+                    if !state.events.is_empty() || state.closed {
+                        self.0.notify.notify();
+                    }
+                    return next;
+                }
+            }
+            self.0.notify.notified().await;
+        }
+    }
+
+    /// How many signals have been dropped since the buffer was created.
+    pub fn dropped_count(&self) -> u64 {
+        self.state().dropped
+    }
+
+    /// How many signals are buffered, not counting gaps.
+    pub fn len(&self) -> usize {
+        self.state().buffered
+    }
+
+    /// Are there no signals buffered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The most signals held at once.
+    pub fn capacity(&self) -> usize {
+        self.0.capacity
+    }
+
+    /// Has the stream ended.
+    pub fn is_closed(&self) -> bool {
+        self.state().closed
+    }
+
+    /// Fill this buffer from the signals on a websocket
+    /// until it closes.
+    pub(crate) fn spawn_feed(&self, mut recv: WebsocketReceiver) {
+        let buffer = self.clone();
+        tokio::task::spawn(async move {
+            while let Some(msg) = recv.next().await {
+                match msg {
+                    WebsocketMessage::Signal(bytes) => match Signal::try_from(bytes) {
+                        Ok(signal) => buffer.push(signal),
+                        Err(e) => tracing::warn!(?e, "Dropping a signal that failed to decode"),
+                    },
+                    WebsocketMessage::Request(..) => {
+                        tracing::warn!("Ignoring a request from the app interface")
+                    }
+                    WebsocketMessage::Close(close) => {
+                        tracing::debug!(?close, "App interface closed");
+                        break;
+                    }
+                }
+            }
+            buffer.close();
+        });
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.0.state.lock().expect("Signal buffer poisoned")
+    }
+}
+
+impl State {
+    fn pop(&mut self) -> Option<SignalEvent> {
+        let next = self.events.pop_front();
+        if let Some(SignalEvent::Signal(_)) = next {
+            self.buffered -= 1;
+        }
+        next
+    }
+
+    /// Replace the oldest signal with a gap,
+    /// merged with the gap in front of it if there is one.
+    fn drop_oldest(&mut self) {
+        let mut gap = 1;
+        if let Some(SignalEvent::Gap(n)) = self.events.front() {
+            gap += *n;
+            self.events.pop_front();
+        }
+        if let Some(SignalEvent::Signal(_)) = self.events.pop_front() {
+            self.buffered -= 1;
+        }
+        self.events.push_front(SignalEvent::Gap(gap));
+    }
+
+    fn mark_gap_at_back(&mut self) {
+        match self.events.back_mut() {
+            Some(SignalEvent::Gap(n)) => *n += 1,
+            _ => self.events.push_back(SignalEvent::Gap(1)),
+        }
+    }
+}
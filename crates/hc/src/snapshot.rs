@@ -0,0 +1,302 @@
+//! Checkpoint a setup's databases and go back to them later.
+//!
+//! [`snapshot`] copies the databases of a stopped setup into a named
+//! snapshot in `<setup>/.hc_snapshots/<name>` and [`restore`] replaces
+//! the setup's databases with the snapshot's. Databases created after
+//! the snapshot, like the ones for a newly installed cell, are removed
+//! on restore so the setup is back in the state it was snapshotted in.
+//!
+//! Only the databases are copied. The conductor config, keystore and
+//! tls certificate are left alone so agents generated after the snapshot
+//! keep their keys. Both refuse to run while the setup's conductor is
+//! running because its databases can change while they are copied.
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use structopt::StructOpt;
+use walkdir::WalkDir;
+
+use crate::config::read_config;
+use crate::run::is_running;
+
+/// The directory in a setup that holds its snapshots.
+pub const SNAPSHOTS_DIR: &str = ".hc_snapshots";
+/// The file of an LMDB database.
+const DATABASE_FILE: &str = "data.mdb";
+/// The lock file LMDB creates next to each database.
+/// It only matters to a running conductor so it isn't copied.
+const LOCK_FILE: &str = "lock.mdb";
+
+#[derive(Debug, StructOpt, Clone)]
+/// Copy a setup's databases to a named snapshot.
+///
+/// The setup's conductor must be stopped.
+/// For example `hc snapshot 0 clean`.
+pub struct Snapshot {
+    #[structopt(subcommand)]
+    /// List the snapshots instead of making one.
+    pub list: Option<SnapshotList>,
+    /// The index of the setup in `hc list`.
+    pub index: Option<usize>,
+    /// The name of the snapshot.
+    pub name: Option<String>,
+    #[structopt(long)]
+    /// Replace a snapshot with the same name.
+    pub force: bool,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+#[allow(missing_docs)]
+pub enum SnapshotList {
+    /// List the snapshots of the setups in `$(pwd)/.hc`.
+    List {
+        /// Only list the snapshots of the setup at this index.
+        index: Option<usize>,
+    },
+}
+
+#[derive(Debug, StructOpt, Clone)]
+/// Replace a setup's databases with a snapshot.
+///
+/// The setup's conductor must be stopped.
+/// For example `hc restore 0 clean`.
+pub struct Restore {
+    /// The index of the setup in `hc list`.
+    pub index: usize,
+    /// The name of the snapshot.
+    pub name: String,
+}
+
+/// Run `hc snapshot` for the setups in the `hc_dir`.
+pub async fn snapshot_cmd(hc_dir: PathBuf, cmd: Snapshot) -> anyhow::Result<()> {
+    let setups = crate::save::load(hc_dir)?;
+    match cmd {
+        Snapshot {
+            list: Some(SnapshotList::List { index }),
+            ..
+        } => {
+            let indices = match index {
+                Some(index) => vec![index],
+                None => (0..setups.len()).collect(),
+            };
+            for i in indices {
+                let names = list(setup_at(&setups, i)?)?;
+                if names.is_empty() {
+                    data!("Setup {}: no snapshots", i);
+                } else {
+                    data!("Setup {}: {}", i, names.join(", "));
+                }
+            }
+        }
+        Snapshot {
+            index: Some(index),
+            name: Some(name),
+            force,
+            ..
+        } => {
+            let files = snapshot(setup_at(&setups, index)?, &name, force).await?;
+            msg!("Saved {} databases of setup {} to {}", files, index, name);
+        }
+        _ => bail!(
+            "Pass the index of a setup and a name for the snapshot, e.g. `hc snapshot 0 clean`"
+        ),
+    }
+    Ok(())
+}
+
+/// Run `hc restore` for the setups in the `hc_dir`.
+pub async fn restore_cmd(hc_dir: PathBuf, cmd: Restore) -> anyhow::Result<()> {
+    let setups = crate::save::load(hc_dir)?;
+    let files = restore(setup_at(&setups, cmd.index)?, &cmd.name).await?;
+    msg!(
+        "Restored {} databases of setup {} from {}",
+        files,
+        cmd.index,
+        cmd.name
+    );
+    Ok(())
+}
+
+/// The directory the setup's snapshots are in.
+pub fn snapshots_path(setup_path: &Path) -> PathBuf {
+    setup_path.join(SNAPSHOTS_DIR)
+}
+
+/// The names of the setup's snapshots in order.
+pub fn list(setup_path: &Path) -> anyhow::Result<Vec<String>> {
+    let dir = snapshots_path(setup_path);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_str().map(String::from);
+        // Snapshots that are still being copied start with a `.`.
+        match name {
+            Some(name) if entry.file_type()?.is_dir() && !name.starts_with('.') => names.push(name),
+            _ => (),
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Copy the databases of the setup to the snapshot with this name
+/// and return how many were copied.
+/// An existing snapshot with the name is only replaced with `force`.
+/// It is an error if the setup's conductor is running.
+pub async fn snapshot(setup_path: &Path, name: &str, force: bool) -> anyhow::Result<usize> {
+    check_name(name)?;
+    ensure_stopped(setup_path, "snapshotting").await?;
+    let environment = environment_path(setup_path)?;
+    let target = snapshots_path(setup_path).join(name);
+    if target.exists() {
+        ensure!(
+            force,
+            "Setup {} already has a snapshot named {}. Use --force to replace it",
+            setup_path.display(),
+            name
+        );
+        std::fs::remove_dir_all(&target)
+            .with_context(|| format!("Failed to remove snapshot {}", target.display()))?;
+    }
+    let databases = databases(&environment)?;
+    ensure!(
+        !databases.is_empty(),
+        "Setup {} has no databases to snapshot. Run it once to create them",
+        setup_path.display()
+    );
+    // Copy to a temporary directory first so a failed copy
+    // doesn't leave a snapshot that is missing databases.
+    let partial = snapshots_path(setup_path).join(format!(".{}.partial", name));
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)?;
+    }
+    copy_databases(&environment, &partial, &databases)?;
+    std::fs::rename(&partial, &target)?;
+    Ok(databases.len())
+}
+
+/// Replace the databases of the setup with the snapshot with this name
+/// and return how many were restored.
+/// Databases that aren't in the snapshot are removed.
+/// It is an error if the setup's conductor is running.
+pub async fn restore(setup_path: &Path, name: &str) -> anyhow::Result<usize> {
+    check_name(name)?;
+    let source = snapshots_path(setup_path).join(name);
+    if !source.is_dir() {
+        let names = list(setup_path)?;
+        bail!(
+            "Setup {} has no snapshot named {}. Its snapshots are: {}",
+            setup_path.display(),
+            name,
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        );
+    }
+    ensure_stopped(setup_path, "restoring").await?;
+    let environment = environment_path(setup_path)?;
+    let snapshotted = databases(&source)?;
+    for database in databases(&environment)? {
+        let dir = environment.join(&database);
+        for file in &[DATABASE_FILE, LOCK_FILE] {
+            let file = dir.join(file);
+            if file.exists() {
+                std::fs::remove_file(&file)
+                    .with_context(|| format!("Failed to remove {}", file.display()))?;
+            }
+        }
+        // Leave directories the conductor put other files in.
+        if dir != environment && std::fs::read_dir(&dir)?.next().is_none() {
+            std::fs::remove_dir(&dir)?;
+        }
+    }
+    copy_databases(&source, &environment, &snapshotted)?;
+    Ok(snapshotted.len())
+}
+
+fn setup_at(setups: &[PathBuf], index: usize) -> anyhow::Result<&Path> {
+    setups.get(index).map(PathBuf::as_path).ok_or_else(|| {
+        anyhow!(
+            "There is no setup at index {}. Run `hc list` to see the setups",
+            index
+        )
+    })
+}
+
+/// Snapshot names are directory names so they can't be paths.
+fn check_name(name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    ensure!(
+        valid,
+        "Snapshot names can only have letters, numbers, `-`, `_` and `.` \
+        and can't start with `.`: {:?}",
+        name
+    );
+    Ok(())
+}
+
+async fn ensure_stopped(setup_path: &Path, doing: &str) -> anyhow::Result<()> {
+    ensure!(
+        !is_running(setup_path.to_path_buf()).await?,
+        "The conductor for setup {} is running. Stop it before {} its databases",
+        setup_path.display(),
+        doing
+    );
+    Ok(())
+}
+
+/// The directory the setup's conductor keeps its databases in.
+fn environment_path(setup_path: &Path) -> anyhow::Result<PathBuf> {
+    let config = read_config(setup_path.to_path_buf())?
+        .ok_or_else(|| anyhow!("No conductor config in {}", setup_path.display()))?;
+    Ok(crate::config::environment_path(setup_path, &config))
+}
+
+/// The directories of the databases in `root`, relative to it.
+/// Snapshots aren't databases of the setup.
+fn databases(root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut databases = Vec::new();
+    if !root.is_dir() {
+        return Ok(databases);
+    }
+    let walk = WalkDir::new(root)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|e| e.file_name() != SNAPSHOTS_DIR);
+    for entry in walk {
+        let entry = entry?;
+        if entry.file_type().is_file() && entry.file_name() == DATABASE_FILE {
+            let dir = entry.path().parent().expect("Files have a parent");
+            let relative = dir
+                .strip_prefix(root)
+                .expect("Walked paths are in the root");
+            databases.push(relative.to_path_buf());
+        }
+    }
+    Ok(databases)
+}
+
+fn copy_databases(from: &Path, to: &Path, databases: &[PathBuf]) -> anyhow::Result<()> {
+    for database in databases {
+        let dir = to.join(database);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let source = from.join(database).join(DATABASE_FILE);
+        std::fs::copy(&source, dir.join(DATABASE_FILE))
+            .with_context(|| format!("Failed to copy {}", source.display()))?;
+    }
+    Ok(())
+}
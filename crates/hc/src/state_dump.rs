@@ -0,0 +1,161 @@
+//! A typed model of the source chain returned by [`AdminRequest::DumpState`].
+//!
+//! The conductor answers with its source chain as a JSON string.
+//! [`SourceChainDump`] is what that string deserializes to so the
+//! headers and entries can be used without picking the JSON apart.
+//! [`CmdRunner::dump_state`] returns it and
+//! [`CmdRunner::dump_state_raw`] returns the JSON as it was sent.
+//!
+//! The conductor always dumps the whole chain so a [`TimeWindow`]
+//! from `--since` and `--until` is applied to it here.
+//!
+//! [`AdminRequest::DumpState`]: holochain_conductor_api::AdminRequest::DumpState
+//! [`CmdRunner::dump_state`]: crate::CmdRunner::dump_state
+//! [`CmdRunner::dump_state_raw`]: crate::CmdRunner::dump_state_raw
+use anyhow::anyhow;
+use anyhow::ensure;
+use anyhow::Context;
+use holochain_types::prelude::Element;
+use holochain_types::prelude::Entry;
+use holochain_types::prelude::Header;
+use holochain_types::prelude::HeaderHash;
+use holochain_types::prelude::HeaderHashed;
+use holochain_types::prelude::Signature;
+use holochain_types::prelude::SignedHeaderHashed;
+use holochain_types::prelude::Timestamp;
+use structopt::StructOpt;
+
+/// A cell's source chain, newest element first like the conductor dumps it.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct SourceChainDump {
+    /// Each header in the chain with its element.
+    pub items: Vec<DumpedItem>,
+}
+
+/// A header in a [`SourceChainDump`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DumpedItem {
+    /// The element of the header.
+    /// This is only `None` if the conductor has the header in its chain
+    /// but not the element, which means its database is corrupted.
+    pub element: Option<DumpedElement>,
+}
+
+/// An element in a [`SourceChainDump`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DumpedElement {
+    /// The author's signature of the header.
+    pub signature: Signature,
+    /// The hash of the header.
+    pub header_address: HeaderHash,
+    /// The header.
+    pub header: Header,
+    /// The entry if the header has one and the conductor has it.
+    pub entry: Option<Entry>,
+}
+
+impl SourceChainDump {
+    /// Deserialize the JSON the conductor responds with.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        serde_json::from_str(json).context("Failed to parse the dumped source chain")
+    }
+
+    /// The number of headers in the chain.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Is the chain empty.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The elements from the start of the chain.
+    /// Headers that are missing their element are skipped.
+    pub fn elements(&self) -> impl Iterator<Item = &DumpedElement> {
+        self.items.iter().rev().filter_map(|i| i.element.as_ref())
+    }
+
+    /// The headers from the start of the chain.
+    pub fn headers(&self) -> impl Iterator<Item = &Header> {
+        self.elements().map(|e| &e.header)
+    }
+
+    /// How many headers are missing their element.
+    pub fn missing(&self) -> usize {
+        self.items.iter().filter(|i| i.element.is_none()).count()
+    }
+
+    /// Keep only the elements whose header was written within the window.
+    /// Headers that are missing their element have no time so they
+    /// are kept and still show up in [`SourceChainDump::missing`].
+    pub fn retain_within(&mut self, window: &TimeWindow) {
+        self.items.retain(|i| match &i.element {
+            Some(e) => window.contains(e.header.timestamp()),
+            None => true,
+        });
+    }
+}
+
+#[derive(Debug, StructOpt, Clone, Default)]
+/// The `--since` and `--until` flags that limit a dump
+/// to the headers written within a window of time.
+pub struct TimeWindow {
+    #[structopt(long, parse(try_from_str = parse_time))]
+    /// Only include headers written at or after this RFC 3339 time.
+    /// For example `--since 2021-03-01T12:00:00Z`.
+    pub since: Option<Timestamp>,
+    #[structopt(long, parse(try_from_str = parse_time))]
+    /// Only include headers written before this RFC 3339 time.
+    /// For example `--until 2021-03-02T00:00:00+01:00`.
+    pub until: Option<Timestamp>,
+}
+
+impl TimeWindow {
+    /// Is neither end of the window set.
+    pub fn is_unbounded(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    /// Is this time in the window.
+    /// The start is inclusive and the end is exclusive.
+    pub fn contains(&self, time: Timestamp) -> bool {
+        self.since.map_or(true, |since| time >= since)
+            && self.until.map_or(true, |until| time < until)
+    }
+
+    /// It is an error if the window ends before it starts.
+    pub fn check(&self) -> anyhow::Result<()> {
+        if let (Some(since), Some(until)) = (self.since, self.until) {
+            ensure!(
+                since < until,
+                "--since {} must be before --until {}",
+                since,
+                until
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Parse an RFC 3339 time like `2021-03-01T12:00:00Z`.
+pub fn parse_time(arg: &str) -> anyhow::Result<Timestamp> {
+    arg.parse().map_err(|e| {
+        anyhow!(
+            "{} is not an RFC 3339 time like 2021-03-01T12:00:00Z: {}",
+            arg,
+            e
+        )
+    })
+}
+
+impl From<DumpedElement> for Element {
+    fn from(e: DumpedElement) -> Self {
+        let header = HeaderHashed::with_pre_hashed(e.header, e.header_address);
+        Element::new(
+            SignedHeaderHashed::with_presigned(header, e.signature),
+            e.entry,
+        )
+    }
+}
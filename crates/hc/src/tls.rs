@@ -0,0 +1,71 @@
+//! Helpers for self-signed TLS certificates for local testing.
+//!
+//! The conductor's interfaces only serve `ws` so these certificates
+//! need a TLS terminating proxy (e.g. `stunnel`) in front of the
+//! app interface to serve `wss`.
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+/// The directory in a setup that holds the certificate.
+pub const TLS_DIR: &str = "tls";
+const CERT_PEM: &str = "cert.pem";
+const CERT_DER: &str = "cert.der";
+const KEY_PEM: &str = "key.pem";
+
+/// The certificate files for a setup.
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    /// The PEM encoded certificate.
+    pub cert: PathBuf,
+    /// The PEM encoded PKCS#8 private key.
+    pub key: PathBuf,
+    /// The SHA-256 fingerprint of the certificate as
+    /// colon separated hex, e.g. `AB:CD:...`.
+    /// This is the form browsers display.
+    pub fingerprint: String,
+}
+
+/// Get the certificate for this setup, generating
+/// a new self-signed certificate for `localhost` if there
+/// isn't one yet or `regenerate` is set.
+pub fn setup_tls(setup_path: &Path, regenerate: bool) -> anyhow::Result<TlsFiles> {
+    let dir = setup_path.join(TLS_DIR);
+    let cert = dir.join(CERT_PEM);
+    let key = dir.join(KEY_PEM);
+    let der = dir.join(CERT_DER);
+    if regenerate || !(cert.is_file() && key.is_file() && der.is_file()) {
+        std::fs::create_dir_all(&dir)?;
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+        std::fs::write(&cert, generated.serialize_pem()?)?;
+        std::fs::write(&der, generated.serialize_der()?)?;
+        std::fs::write(&key, generated.serialize_private_key_pem())?;
+        tracing::info!("Generated a new TLS certificate at {}", cert.display());
+    }
+    let der = std::fs::read(&der)
+        .with_context(|| format!("Failed to read certificate {}", der.display()))?;
+    let files = TlsFiles {
+        cert,
+        key,
+        fingerprint: fingerprint(&der),
+    };
+    msg!(
+        "TLS certificate: {}\nTLS key: {}\nSHA-256 fingerprint: {}\n\
+        Holochain app interfaces serve ws so use a TLS proxy with this certificate to serve wss.",
+        files.cert.display(),
+        files.key.display(),
+        files.fingerprint
+    );
+    Ok(files)
+}
+
+/// SHA-256 fingerprint of a DER encoded certificate.
+fn fingerprint(der: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, der)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
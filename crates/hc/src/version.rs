@@ -0,0 +1,76 @@
+//! Checks that a conductor speaks the same version
+//! of the admin api as `hc`.
+//!
+//! Every [`CmdRunner`] makes an [`AdminRequest::RequestVersion`]
+//! when it connects so a mismatched `holochain` binary fails
+//! with a [`VersionMismatch`] instead of confusing
+//! serialization errors on later requests.
+//!
+//! [`CmdRunner`]: crate::CmdRunner
+//! [`AdminRequest::RequestVersion`]: holochain_conductor_api::AdminRequest::RequestVersion
+use holochain_conductor_api::ConductorVersion;
+use holochain_conductor_api::CONDUCTOR_API_VERSION;
+
+/// The range of conductor api versions that `hc` can talk to.
+/// This is semver compatible with the [`CONDUCTOR_API_VERSION`]
+/// `hc` was built with so while the api is `0.0.x` only
+/// the exact same version is compatible.
+pub fn compatible_conductor_api() -> semver::VersionReq {
+    semver::VersionReq::parse(&format!("^{}", CONDUCTOR_API_VERSION))
+        .expect("The crate version is valid semver")
+}
+
+/// Can `hc` talk to a conductor with this version.
+pub fn is_compatible(version: &ConductorVersion) -> bool {
+    match semver::Version::parse(&version.conductor_api) {
+        Ok(v) => compatible_conductor_api().matches(&v),
+        Err(_) => false,
+    }
+}
+
+/// The output of `hc --version`.
+pub fn long_version() -> String {
+    format!(
+        "{}\nCompatible with conductors using holochain_conductor_api {}",
+        env!("CARGO_PKG_VERSION"),
+        compatible_conductor_api()
+    )
+}
+
+/// The conductor doesn't implement a version of the
+/// admin api that `hc` is compatible with.
+#[derive(Debug, Clone)]
+pub struct VersionMismatch {
+    /// The versions the conductor reported.
+    /// `None` if the conductor didn't understand the request,
+    /// which means it is older than the version request.
+    pub conductor: Option<ConductorVersion>,
+}
+
+impl std::fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Version mismatch: hc {} needs a conductor using holochain_conductor_api {} ",
+            env!("CARGO_PKG_VERSION"),
+            compatible_conductor_api()
+        )?;
+        match &self.conductor {
+            Some(v) => write!(
+                f,
+                "but the conductor is holochain {} using holochain_conductor_api {}.",
+                v.holochain, v.conductor_api
+            )?,
+            None => write!(
+                f,
+                "but the conductor didn't report its version so it is likely an older holochain."
+            )?,
+        }
+        write!(
+            f,
+            " Install a holochain binary that matches this version of hc."
+        )
+    }
+}
+
+impl std::error::Error for VersionMismatch {}
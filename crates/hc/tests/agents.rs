@@ -0,0 +1,45 @@
+use holochain_hc as hc;
+use holochain_types::prelude::AgentPubKey;
+
+mod common;
+
+#[test]
+fn agents_keep_their_key_and_keystore() {
+    let hc_dir = common::test_dir();
+
+    let alice = hc::agents::get_or_create(&hc_dir, "alice").unwrap();
+    assert!(alice.agent_key.is_none());
+    assert!(alice.keystore.is_dir());
+    let key = AgentPubKey::from_raw_32(vec![1; 32]);
+    hc::agents::store_key(&alice, &key).unwrap();
+    hc::agents::get_or_create(&hc_dir, "bob").unwrap();
+
+    // The second use finds the same key and keystore.
+    let again = hc::agents::get_or_create(&hc_dir, "alice").unwrap();
+    assert_eq!(again.agent_key, Some(key.clone()));
+    assert_eq!(again.keystore, alice.keystore);
+
+    // Setups point at the shared keystore and removing them leaves it.
+    let setup = hc::generate::generate(None, None, None).unwrap();
+    hc::agents::use_keystore(setup.clone(), &alice).unwrap();
+    let config = hc::config::read_config(setup.clone()).unwrap().unwrap();
+    assert_eq!(config.keystore_path, Some(alice.keystore.clone()));
+    std::fs::remove_dir_all(setup).unwrap();
+    assert!(alice.keystore.is_dir());
+
+    let agents = hc::agents::list(&hc_dir).unwrap();
+    let names: Vec<_> = agents.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, vec!["alice", "bob"]);
+    assert_eq!(agents[0].agent_key, Some(key));
+    assert_eq!(agents[1].agent_key, None);
+
+    assert!(hc::agents::get_or_create(&hc_dir, "../alice").is_err());
+    assert_eq!(
+        hc::agents::get(&hc_dir, "alice").unwrap().keystore,
+        alice.keystore
+    );
+    let err = hc::agents::get(&hc_dir, "carol").unwrap_err();
+    assert!(err.to_string().contains("alice, bob"), "{}", err);
+
+    std::fs::remove_dir_all(hc_dir).ok();
+}
@@ -0,0 +1,420 @@
+use holochain_hc as hc;
+use holochain_types::prelude::*;
+use holochain_wasm_test_utils::TestWasm;
+
+use hc::calls::ActivateApp;
+use hc::calls::AddAppWs;
+use hc::calls::AgentActivitySummary;
+use hc::calls::AppInfo;
+use hc::calls::ChainStatusSummary;
+use hc::calls::EntrySummary;
+use hc::calls::GetAgentActivity;
+use hc::calls::GetRecord;
+use hc::calls::RecordSummary;
+use hc::AppCmdRunner;
+use hc::CmdRunner;
+
+mod common;
+
+/// Installs the foo test wasm with a [`CmdRunner`]
+/// and calls it through an [`AppCmdRunner`].
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn zome_call_round_trip() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+
+    let dna = common::test_dna(TestWasm::Foo).await;
+
+    let (cmd, _holochain) = CmdRunner::from_setup(path).await.unwrap();
+    let cells = hc::calls::install_app_dnas(&cmd, "foo-app".to_string(), None, vec![dna.into()])
+        .await
+        .unwrap();
+    let app_port = hc::calls::attach_app_interface(
+        &cmd,
+        AddAppWs {
+            port: None,
+            verify: true,
+            allowed_origins: Vec::new(),
+        },
+    )
+    .await
+    .unwrap();
+
+    let app = AppCmdRunner::try_new(app_port).await.unwrap();
+    let output: String = app
+        .call_zome(cells[0].as_id().clone(), TestWasm::Foo, "foo", None, ())
+        .await
+        .unwrap();
+    assert_eq!(output, "foo");
+}
+
+/// Creates an entry with the crd test wasm and gets it back
+/// by its header hash with [`hc::calls::get_record`].
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn get_record_by_header_hash() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+
+    let dna = common::test_dna(TestWasm::Crd).await;
+
+    let (cmd, _holochain) = CmdRunner::from_setup(path).await.unwrap();
+    let cells = hc::calls::install_app_dnas(&cmd, "crd-app".to_string(), None, vec![dna.into()])
+        .await
+        .unwrap();
+    let app_port = hc::calls::attach_app_interface(
+        &cmd,
+        AddAppWs {
+            port: None,
+            verify: true,
+            allowed_origins: Vec::new(),
+        },
+    )
+    .await
+    .unwrap();
+    let cell_id = cells[0].as_id().clone();
+
+    let app = AppCmdRunner::try_new(app_port).await.unwrap();
+    let header_hash: HeaderHash = app
+        .call_zome(cell_id.clone(), TestWasm::Crd, "create", None, ())
+        .await
+        .unwrap();
+
+    let get = |header_hash: HeaderHash| GetRecord {
+        dna: cell_id.dna_hash().clone(),
+        agent_key: cell_id.agent_pubkey().clone(),
+        header_hash,
+        zome: "crd".to_string(),
+        function: "read".to_string(),
+        json: false,
+        app_port: Some(app_port),
+    };
+    let record = hc::calls::get_record(&cmd, get(header_hash.clone()))
+        .await
+        .unwrap()
+        .expect("The record was just created");
+    let summary = RecordSummary::from(&record);
+    assert_eq!(summary.header_hash, header_hash.to_string());
+    assert_eq!(summary.header_type, "Create");
+    matches::assert_matches!(summary.entry, EntrySummary::App(_));
+
+    let missing = HeaderHash::from_raw_32(vec![0; 32]);
+    assert!(hc::calls::get_record(&cmd, get(missing))
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Creates entries with the create entry test wasm and gets
+/// the agent's activity with [`hc::calls::get_agent_activity`].
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn get_agent_activity_of_a_cell() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+
+    let dna = common::test_dna(TestWasm::Create).await;
+
+    let (cmd, _holochain) = CmdRunner::from_setup(path).await.unwrap();
+    let cells = hc::calls::install_app_dnas(&cmd, "create-app".to_string(), None, vec![dna.into()])
+        .await
+        .unwrap();
+    let app_port = hc::calls::attach_app_interface(
+        &cmd,
+        AddAppWs {
+            port: None,
+            verify: true,
+            allowed_origins: Vec::new(),
+        },
+    )
+    .await
+    .unwrap();
+    let cell_id = cells[0].as_id().clone();
+
+    let app = AppCmdRunner::try_new(app_port).await.unwrap();
+    let header_hash: HeaderHash = app
+        .call_zome(cell_id.clone(), TestWasm::Create, "create_entry", None, ())
+        .await
+        .unwrap();
+
+    let get = |agent: AgentPubKey| GetAgentActivity {
+        dna: cell_id.dna_hash().clone(),
+        agent_key: cell_id.agent_pubkey().clone(),
+        agent,
+        zome: "create_entry".to_string(),
+        function: "get_activity".to_string(),
+        status_only: false,
+        json: false,
+        app_port: Some(app_port),
+    };
+    let agent = cell_id.agent_pubkey().clone();
+    let activity = hc::calls::get_agent_activity(&cmd, get(agent.clone()))
+        .await
+        .unwrap();
+    let summary = AgentActivitySummary::new(&agent, &activity).unwrap();
+    assert!(summary.gaps.is_empty(), "{}", summary);
+    assert_eq!(
+        summary.valid.last().map(|(_, hash)| hash.clone()),
+        Some(header_hash.to_string())
+    );
+
+    let stranger = AgentPubKey::from_raw_32(vec![0; 32]);
+    let activity = hc::calls::get_agent_activity(&cmd, get(stranger.clone()))
+        .await
+        .unwrap();
+    let err = AgentActivitySummary::new(&stranger, &activity).unwrap_err();
+    assert!(
+        err.to_string().contains("is unknown to this cell"),
+        "{}",
+        err
+    );
+}
+
+/// Works out the gaps in an agent's activity and marks
+/// everything that is wrong with the chain.
+#[test]
+fn agent_activity_summaries_mark_gaps_and_rejected_headers() {
+    let agent = AgentPubKey::from_raw_32(vec![1; 32]);
+    let header = |n: u8| HeaderHash::from_raw_32(vec![n; 32]);
+    let activity = AgentActivity {
+        valid_activity: vec![(3, header(3)), (0, header(0)), (1, header(1))],
+        rejected_activity: vec![(4, header(4))],
+        status: ChainStatus::Invalid(ChainHead {
+            header_seq: 4,
+            hash: header(4),
+        }),
+        highest_observed: Some(HighestObserved {
+            header_seq: 7,
+            hash: vec![header(7)],
+        }),
+        warrants: Vec::new(),
+    };
+    let summary = AgentActivitySummary::new(&agent, &activity).unwrap();
+    assert_eq!(summary.gaps, vec![2..3, 5..8]);
+    assert_eq!(
+        summary.status,
+        ChainStatusSummary::Invalid {
+            seq: 4,
+            hash: header(4).to_string(),
+        }
+    );
+    let printed = summary.to_string();
+    let lines: Vec<_> = printed.lines().collect();
+    assert_eq!(
+        lines[1..],
+        [
+            format!("! status: invalid at #4 {}", header(4)),
+            format!("highest observed: #7 {}", header(7)),
+            format!("#0 {}", header(0)),
+            format!("#1 {}", header(1)),
+            "! #2 missing".to_string(),
+            format!("#3 {}", header(3)),
+            format!("! #4 {} rejected", header(4)),
+            "! #5 to #7 missing".to_string(),
+        ],
+        "{}",
+        printed
+    );
+
+    // Only asking for the status has no headers so there are no gaps.
+    let status_only = AgentActivity {
+        valid_activity: Vec::new(),
+        rejected_activity: Vec::new(),
+        status: ChainStatus::Valid(ChainHead {
+            header_seq: 2,
+            hash: header(2),
+        }),
+        highest_observed: None,
+        warrants: Vec::new(),
+    };
+    let summary = AgentActivitySummary::new(&agent, &status_only).unwrap();
+    assert!(summary.gaps.is_empty());
+    assert!(summary.to_string().contains("status: valid up to #2"));
+
+    let unknown = AgentActivity {
+        status: ChainStatus::Empty,
+        ..status_only
+    };
+    let err = AgentActivitySummary::new(&agent, &unknown).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains(&format!("Agent {} is unknown to this cell", agent)),
+        "{}",
+        err
+    );
+}
+
+/// Installs an app without activating it and checks its cells
+/// only run once it is activated.
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn install_without_activating() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+
+    let dna = common::test_dna(TestWasm::Foo).await;
+
+    let (cmd, _holochain) = CmdRunner::from_setup(path).await.unwrap();
+    let cells = hc::calls::install_app_dnas_inactive(
+        &cmd,
+        "foo-app".to_string(),
+        None,
+        vec![(dna.into(), None)],
+    )
+    .await
+    .unwrap();
+    let cell_id = cells[0].as_id().clone();
+
+    let inactive = hc::calls::list_inactive_apps(&cmd).await.unwrap();
+    assert_eq!(inactive.len(), 1);
+    assert_eq!(inactive[0].installed_app_id, "foo-app");
+    assert_eq!(inactive[0].cell_data, cells);
+    assert!(!hc::calls::list_cell_ids(&cmd)
+        .await
+        .unwrap()
+        .contains(&cell_id));
+
+    hc::calls::activate_app(
+        &cmd,
+        ActivateApp {
+            app_id: "foo-app".to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(hc::calls::list_inactive_apps(&cmd)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(hc::calls::list_cell_ids(&cmd)
+        .await
+        .unwrap()
+        .contains(&cell_id));
+}
+
+/// Installs a second app that uses the cells of the first
+/// and can't use a cell with another dna.
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn install_with_existing_cells() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+
+    let dna = common::test_dna(TestWasm::Foo).await;
+
+    let (cmd, _holochain) = CmdRunner::from_setup(path).await.unwrap();
+    let cells = hc::calls::install_app_dnas(&cmd, "foo-app".to_string(), None, vec![dna.into()])
+        .await
+        .unwrap();
+    let cell_id = cells[0].as_id().clone();
+
+    let existing = vec![(cell_id.clone(), cell_id.dna_hash().clone())];
+    let app = hc::calls::install_app_with_existing_cells(&cmd, "composed".to_string(), existing)
+        .await
+        .unwrap();
+    assert_eq!(app.cell_data[0].as_id(), &cell_id);
+    cmd.activate_app("composed".to_string()).await.unwrap();
+    assert_eq!(
+        hc::calls::list_cell_ids(&cmd).await.unwrap(),
+        vec![cell_id.clone()]
+    );
+
+    let other_dna = DnaHash::from_raw_32(vec![0; 32]);
+    let err = hc::calls::install_app_with_existing_cells(
+        &cmd,
+        "mismatched".to_string(),
+        vec![(cell_id, other_dna)],
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("but the app requires dna"),
+        "{:?}",
+        err
+    );
+}
+
+/// Gets the cell of each role of an app
+/// and lists the installed apps for an unknown app.
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn app_info_maps_roles_to_cells() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+
+    let dna = common::test_dna(TestWasm::Foo).await;
+
+    let (cmd, _holochain) = CmdRunner::from_setup(path).await.unwrap();
+    let cells = hc::calls::install_app_dnas(&cmd, "foo-app".to_string(), None, vec![dna.into()])
+        .await
+        .unwrap();
+
+    let info = hc::calls::app_info(
+        &cmd,
+        AppInfo {
+            app_id: "foo-app".to_string(),
+            app_port: None,
+            json: false,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(info.installed_app_id, "foo-app");
+    assert!(info.active);
+    assert_eq!(info.roles.len(), cells.len());
+    for (role, cell) in info.roles.iter().zip(&cells) {
+        assert_eq!(&role.role, cell.as_nick());
+        assert_eq!(role.cell.cell_id, cell.as_id().to_string());
+    }
+
+    let err = hc::calls::app_info(
+        &cmd,
+        AppInfo {
+            app_id: "bar-app".to_string(),
+            app_port: None,
+            json: false,
+        },
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("There is no app bar-app installed. The installed apps are: foo-app"),
+        "{}",
+        err
+    );
+}
+
+/// Checks the hash `hc dna hash` previews is the hash
+/// the conductor gives the dna when it is registered.
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn dna_hash_with_modifiers_matches_the_conductor() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+
+    let dir = common::test_dir();
+    let (dna_path, _) = common::write_test_dna(&dir, TestWasm::Foo).await;
+    let properties = || JsonProperties::new(serde_json::json!({"max_members": 5}));
+
+    let (cmd, _holochain) = CmdRunner::from_setup(path).await.unwrap();
+    let registered = cmd
+        .register_dna(
+            dna_path.clone(),
+            Some("my-network".to_string()),
+            Some(properties()),
+        )
+        .await
+        .unwrap();
+    let previewed = hc::dna::hash_with_modifiers(
+        &dna_path,
+        Some("my-network".to_string()),
+        Some(properties()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(previewed, registered);
+
+    std::fs::remove_dir_all(dir).ok();
+}
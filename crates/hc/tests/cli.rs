@@ -15,6 +15,8 @@ use portpicker::pick_unused_port;
 use tokio::process::Command;
 use url2::url2;
 
+mod common;
+
 const WEBSOCKET_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
 
 async fn websocket_client_by_port(
@@ -94,3 +96,103 @@ async fn run_multiple_on_same_port() {
     let _hc_admin2 = cmd.spawn().expect("Failed to spawn holochain");
     tokio::time::delay_for(std::time::Duration::from_secs(4)).await;
 }
+
+/// Calling a port with no conductor fails with a
+/// helpful message instead of a panic.
+#[test]
+fn call_dead_port_fails_with_message() {
+    let port: u16 = pick_unused_port().expect("No ports free");
+    let output = std::process::Command::cargo_bin("hc")
+        .unwrap()
+        .arg("call")
+        .arg(format!("-r={}", port))
+        .arg("list-dnas")
+        .output()
+        .expect("Failed to run hc");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "stderr: {}", stderr);
+    assert!(
+        stderr.contains("Is holochain running?"),
+        "stderr: {}",
+        stderr
+    );
+    assert!(!stderr.contains("panicked"), "stderr: {}", stderr);
+}
+
+#[test]
+fn version_prints_compatible_conductor_api() {
+    let output = std::process::Command::cargo_bin("hc")
+        .unwrap()
+        .arg("--version")
+        .output()
+        .expect("Failed to run hc");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {}", stdout);
+    assert!(
+        stdout.contains(&format!(
+            "holochain_conductor_api ^{}",
+            holochain_conductor_api::CONDUCTOR_API_VERSION
+        )),
+        "stdout: {}",
+        stdout
+    );
+}
+
+#[test]
+fn data_goes_to_stdout_when_quiet() {
+    let dir = common::test_dir();
+    let output = std::process::Command::cargo_bin("hc")
+        .unwrap()
+        .current_dir(&dir)
+        .env_remove("RUST_LOG")
+        .arg("-q")
+        .arg("list")
+        .arg("--count")
+        .output()
+        .expect("Failed to run hc");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert!(
+        stdout.contains("total: 0, running: 0, stopped: 0"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(stderr.is_empty(), "stderr: {}", stderr);
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn only_data_goes_to_stdout() {
+    let dir = common::test_dir();
+    let list = |args: &[&str]| {
+        std::process::Command::cargo_bin("hc")
+            .unwrap()
+            .current_dir(&dir)
+            .env_remove("RUST_LOG")
+            .arg("list")
+            .args(args)
+            .output()
+            .expect("Failed to run hc")
+    };
+
+    let output = list(&["--json"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {}", stdout);
+    let setups: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(setups, serde_json::json!([]));
+
+    let output = list(&[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "stderr: {}", stderr);
+    assert!(!stdout.contains("hc-admin"), "stdout: {}", stdout);
+    assert!(
+        stderr.contains("hc-admin: Setups contained in `.hc`"),
+        "stderr: {}",
+        stderr
+    );
+    // stderr is not a terminal so it isn't colored.
+    assert!(!stderr.contains('\u{1b}'), "stderr: {}", stderr);
+    std::fs::remove_dir_all(dir).ok();
+}
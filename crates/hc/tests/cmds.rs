@@ -0,0 +1,465 @@
+use std::convert::TryInto;
+
+use holochain_conductor_api::config::conductor::DbSyncLevel;
+use holochain_hc::calls::membrane_proofs;
+use holochain_hc::calls::AdminRequestCli;
+use holochain_hc::calls::InstallApp;
+use holochain_hc::cmds::Create;
+use holochain_hc::cmds::Network;
+use holochain_hc::cmds::NetworkType;
+use holochain_hc::keystore::KeystoreKind;
+use holochain_p2p::kitsune_p2p::KitsuneP2pConfig;
+use holochain_p2p::kitsune_p2p::KitsuneP2pTuningParams;
+use holochain_types::prelude::*;
+use structopt::StructOpt;
+
+mod common;
+
+#[test]
+fn network_tuning_flags_set_the_config() {
+    let network = Network::from_iter_safe(&[
+        "network",
+        "--gossip-delay-ms",
+        "1000",
+        "--notify-agents",
+        "1",
+        "mem",
+    ])
+    .unwrap();
+    let config: KitsuneP2pConfig = network.try_into().unwrap();
+    let defaults = KitsuneP2pTuningParams::default();
+    assert_eq!(config.tuning_params.gossip_loop_iteration_delay_ms, 1000);
+    assert_eq!(config.tuning_params.default_notify_remote_agent_count, 1);
+    assert_eq!(
+        config.tuning_params.default_rpc_single_timeout_ms,
+        defaults.default_rpc_single_timeout_ms
+    );
+
+    for (flag, value) in &[
+        ("--notify-agents", "0"),
+        ("--notify-agents", "256"),
+        ("--gossip-delay-ms", "0"),
+        ("--rpc-timeout-ms", "not-a-number"),
+    ] {
+        let result = Network::from_iter_safe(&["network", flag, value, "mem"]);
+        assert!(result.is_err(), "{} {} was accepted", flag, value);
+    }
+}
+
+#[test]
+fn conflicting_proxy_flags_are_a_usage_error() {
+    let gen = |args: &[&str]| {
+        let args = ["gen", "network"].iter().chain(args);
+        Create::from_iter_safe(args).unwrap()
+    };
+
+    let ok = gen(&["quic", "-p", "kitsune-proxy://a.host"]);
+    ok.check_network().unwrap();
+    let ok = gen(&["quic", "--proxy-mode", "server", "--proxy-accept-all"]);
+    ok.check_network().unwrap();
+
+    for (args, options) in &[
+        (
+            &["quic", "--proxy-mode", "client"][..],
+            "--proxy-mode client",
+        ),
+        (
+            &[
+                "quic",
+                "--proxy-mode",
+                "server",
+                "-p",
+                "kitsune-proxy://a.host",
+            ][..],
+            "-p",
+        ),
+        (&["quic", "--proxy-accept-all"][..], "--proxy-accept-all"),
+    ] {
+        let err = gen(args).check_network().unwrap_err();
+        assert_eq!(err.kind, structopt::clap::ErrorKind::ArgumentConflict);
+        assert!(err.message.contains(options), "{}", err.message);
+    }
+}
+
+#[test]
+fn memproof_flags_set_the_membrane_proofs() {
+    let install = |args: &[&str]| {
+        let args = ["install-app", "a.dna.gz", "b.dna.gz"].iter().chain(args);
+        InstallApp::from_iter_safe(args)
+    };
+
+    let proofs = membrane_proofs(&install(&[]).unwrap()).unwrap();
+    assert_eq!(proofs, vec![None, None]);
+
+    let proofs = membrane_proofs(&install(&["--memproof-none"]).unwrap()).unwrap();
+    assert!(proofs
+        .iter()
+        .all(|p| p.as_ref().map_or(false, |p| p.bytes().is_empty())));
+
+    let dir = common::test_dir();
+    let proof = dir.join("proof.bin");
+    std::fs::write(&proof, [1, 2, 3]).unwrap();
+    let proof = proof.to_str().unwrap();
+    let proofs =
+        membrane_proofs(&install(&["--memproof", proof, "--memproof", ""]).unwrap()).unwrap();
+    assert_eq!(proofs[0].as_ref().unwrap().bytes(), &vec![1, 2, 3]);
+    assert!(proofs[1].as_ref().unwrap().bytes().is_empty());
+
+    // One proof per dna.
+    assert!(membrane_proofs(&install(&["--memproof", proof]).unwrap()).is_err());
+    assert!(install(&["--memproof-none", "--memproof", proof]).is_err());
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn apps_can_be_installed_and_enabled_separately() {
+    let install = InstallApp::from_iter_safe(&["install-app", "--no-enable", "a.dna.gz"]).unwrap();
+    assert!(install.no_enable);
+
+    let call = AdminRequestCli::from_iter_safe(&["call", "enable-app", "my-app"]).unwrap();
+    matches::assert_matches!(call, AdminRequestCli::ActivateApp(a) if a.app_id == "my-app");
+    let call = AdminRequestCli::from_iter_safe(&["call", "disable-app", "my-app"]).unwrap();
+    matches::assert_matches!(call, AdminRequestCli::DeactivateApp(a) if a.app_id == "my-app");
+}
+
+#[test]
+fn app_info_takes_the_app_id_and_an_app_port() {
+    let call = AdminRequestCli::from_iter_safe(&[
+        "call",
+        "app-info",
+        "my-app",
+        "--json",
+        "--app-port",
+        "9000",
+    ])
+    .unwrap();
+    matches::assert_matches!(
+        call,
+        AdminRequestCli::AppInfo(a) if a.app_id == "my-app" && a.json && a.app_port == Some(9000)
+    );
+    assert!(AdminRequestCli::from_iter_safe(&["call", "app-info"]).is_err());
+}
+
+#[test]
+fn apps_can_be_installed_with_the_cells_of_the_generated_app() {
+    let create =
+        Create::from_iter_safe(&["gen", "--install-with-existing-cells", "composed"]).unwrap();
+    assert_eq!(
+        create.install_with_existing_cells.as_deref(),
+        Some("composed")
+    );
+    assert_eq!(Create::default().install_with_existing_cells, None);
+}
+
+#[test]
+fn op_counts_can_be_called_as_ops() {
+    let dna = DnaHash::from_raw_32(vec![1; 32]);
+    let agent = AgentPubKey::from_raw_32(vec![2; 32]);
+    let call = AdminRequestCli::from_iter_safe(&[
+        "call",
+        "ops",
+        &dna.to_string(),
+        &agent.to_string(),
+        "--json",
+    ])
+    .unwrap();
+    matches::assert_matches!(
+        call,
+        AdminRequestCli::DumpOpCounts(a) if a.dna == dna && a.agent_key == agent && a.json
+    );
+}
+
+#[test]
+fn zome_call_metering_can_be_called_as_metering() {
+    let dna = DnaHash::from_raw_32(vec![1; 32]);
+    let agent = AgentPubKey::from_raw_32(vec![2; 32]);
+    let call = AdminRequestCli::from_iter_safe(&[
+        "call",
+        "metering",
+        &dna.to_string(),
+        &agent.to_string(),
+    ])
+    .unwrap();
+    matches::assert_matches!(
+        call,
+        AdminRequestCli::DumpZomeCallMetering(a) if a.dna == dna && a.agent_key == agent && !a.json
+    );
+}
+
+#[test]
+fn coverage_takes_a_cell_id() {
+    let dna = DnaHash::from_raw_32(vec![1; 32]);
+    let agent = AgentPubKey::from_raw_32(vec![2; 32]);
+    let call = AdminRequestCli::from_iter_safe(&[
+        "call",
+        "coverage",
+        &dna.to_string(),
+        &agent.to_string(),
+    ])
+    .unwrap();
+    matches::assert_matches!(
+        call,
+        AdminRequestCli::Coverage(a) if a.dna == dna && a.agent_key == agent && a.width == 64 && !a.json
+    );
+    assert!(AdminRequestCli::from_iter_safe(&["call", "coverage", &dna.to_string()]).is_err());
+}
+
+#[test]
+fn dump_state_takes_a_time_window() {
+    let dna = DnaHash::from_raw_32(vec![1; 32]);
+    let agent = AgentPubKey::from_raw_32(vec![2; 32]);
+    let dump = |window: &[&str]| {
+        let (dna, agent) = (dna.to_string(), agent.to_string());
+        let mut args = vec!["call", "dump-state", &dna, &agent];
+        args.extend(window);
+        AdminRequestCli::from_iter_safe(&args)
+    };
+    let call = dump(&[
+        "--since",
+        "2021-03-01T12:00:00Z",
+        "--until",
+        "2021-03-01T14:00:00+01:00",
+    ])
+    .unwrap();
+    matches::assert_matches!(
+        call,
+        AdminRequestCli::DumpState(a)
+            if a.window.since == Some(Timestamp(1614600000, 0))
+            && a.window.until == Some(Timestamp(1614603600, 0))
+    );
+
+    let err = dump(&["--since", "yesterday"]).unwrap_err();
+    assert!(
+        err.message.contains("yesterday is not an RFC 3339 time"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn zome_call_metered_is_off_unless_asked_for() {
+    let create = Create::from_iter_safe(&["gen"]).unwrap();
+    assert!(!create.zome_call_metered);
+    let create = Create::from_iter_safe(&["gen", "--zome-call-metered"]).unwrap();
+    assert!(create.zome_call_metered);
+}
+
+#[test]
+fn dev_sets_the_options_that_were_not_passed() {
+    let dev = |args: &[&str]| {
+        let mut create = Create::from_iter_safe(std::iter::once(&"gen").chain(args)).unwrap();
+        create.apply_dev();
+        create
+    };
+    let create = dev(&["--dev"]);
+    assert_eq!(create.keystore_kind(), KeystoreKind::Ephemeral);
+    assert!(matches!(create.db_sync_level, Some(DbSyncLevel::Off)));
+    let network = create.network.clone().unwrap().into_inner();
+    assert!(network.bootstrap.is_none());
+    match &network.transport {
+        NetworkType::Quic(quic) => assert_eq!(
+            quic.bind_to,
+            Some(url2::Url2::parse(holochain_hc::cmds::DEV_BIND_TO))
+        ),
+        NetworkType::Mem => panic!("--dev uses quic"),
+    }
+    let config: KitsuneP2pConfig = network.try_into().unwrap();
+    assert_eq!(
+        config.tuning_params.gossip_loop_iteration_delay_ms,
+        holochain_hc::cmds::DEV_GOSSIP_DELAY_MS
+    );
+
+    // Flags that are passed win over --dev.
+    let create = dev(&[
+        "--dev",
+        "--keystore",
+        "persistent",
+        "--db-sync-level",
+        "fast",
+        "network",
+        "--gossip-delay-ms",
+        "100",
+        "mem",
+    ]);
+    assert_eq!(create.keystore_kind(), KeystoreKind::Persistent);
+    assert!(matches!(create.db_sync_level, Some(DbSyncLevel::Fast)));
+    let network = create.network.unwrap().into_inner();
+    assert!(matches!(network.transport, NetworkType::Mem));
+    assert_eq!(network.tuning.gossip_delay_ms, Some(100));
+    // A reused agent keeps its keystore.
+    assert_eq!(
+        dev(&["--dev", "--reuse-agent", "alice"]).keystore_kind(),
+        KeystoreKind::Persistent
+    );
+
+    let create = dev(&[]);
+    assert_eq!(create.keystore_kind(), KeystoreKind::Persistent);
+    assert!(create.db_sync_level.is_none());
+    assert!(create.network.is_none());
+}
+
+#[test]
+fn env_dirs_must_be_inside_the_setup() {
+    let create = Create::from_iter_safe(&["gen", "--env-dir", "./databases"]).unwrap();
+    assert_eq!(create.env_dir, Some("./databases".into()));
+    for outside in &["/var/databases", "../databases", "databases/../.."] {
+        let err = Create::from_iter_safe(&["gen", "--env-dir", outside]).unwrap_err();
+        assert!(err.message.contains("--db-dir"), "{}", err.message);
+    }
+    let err = Create::from_iter_safe(&["gen", "--env-dir", "databases", "--db-dir", "/tmp/dbs"])
+        .unwrap_err();
+    assert_eq!(err.kind, structopt::clap::ErrorKind::ArgumentConflict);
+}
+
+#[test]
+fn admin_interfaces_need_one_on_the_loopback() {
+    let create = Create::from_iter_safe(&[
+        "gen",
+        "--admin-interface",
+        "172.17.0.1:9200",
+        "--admin-interface",
+        "127.0.0.1:0",
+    ])
+    .unwrap();
+    let interfaces = create.config_spec().unwrap().admin_interfaces.unwrap();
+    let addresses: Vec<_> = interfaces.iter().map(|i| i.address).collect();
+    assert_eq!(
+        addresses,
+        vec![
+            Some("172.17.0.1".parse().unwrap()),
+            Some("127.0.0.1".parse().unwrap())
+        ]
+    );
+
+    let create = Create::from_iter_safe(&["gen", "--admin-interface", "172.17.0.1:9200"]).unwrap();
+    let err = create.config_spec().unwrap_err();
+    assert!(err.to_string().contains("loopback"), "{}", err);
+
+    let err =
+        Create::from_iter_safe(&["gen", "--admin-interface", "127.0.0.1:0", "--uds"]).unwrap_err();
+    assert_eq!(err.kind, structopt::clap::ErrorKind::ArgumentConflict);
+}
+
+#[test]
+fn app_ports_must_be_stable() {
+    let create =
+        Create::from_iter_safe(&["gen", "--app-port", "8888", "--app-port", "8889"]).unwrap();
+    assert_eq!(create.app_ports, vec![8888, 8889]);
+
+    let err = Create::from_iter_safe(&["gen", "--app-port", "0"]).unwrap_err();
+    assert!(err.message.contains("hc run -p 0"), "{}", err.message);
+}
+
+#[test]
+fn keystores_are_chosen_with_keystore_or_keystore_path() {
+    let keystore = |args: &[&str]| {
+        Create::from_iter_safe(std::iter::once(&"gen").chain(args))
+            .unwrap()
+            .keystore_kind()
+    };
+    assert_eq!(keystore(&[]), KeystoreKind::Persistent);
+    assert_eq!(
+        keystore(&["--keystore", "persistent"]),
+        KeystoreKind::Persistent
+    );
+    assert_eq!(
+        keystore(&["--keystore", "shared:../keys"]),
+        KeystoreKind::Shared("../keys".into())
+    );
+    assert_eq!(
+        keystore(&["--keystore-path", "../keys"]),
+        KeystoreKind::Shared("../keys".into())
+    );
+
+    let create = Create::from_iter_safe(&["gen", "--keystore", "ephemeral"]).unwrap();
+    assert_eq!(create.keystore_kind(), KeystoreKind::Ephemeral);
+    assert_eq!(
+        create.config_spec().unwrap().use_dangerous_test_keystore,
+        Some(true)
+    );
+    let create = Create::from_iter_safe(&["gen"]).unwrap();
+    assert_eq!(
+        create.config_spec().unwrap().use_dangerous_test_keystore,
+        None
+    );
+
+    for args in &[
+        &["gen", "--keystore", "shared:"][..],
+        &["gen", "--keystore", "memory"],
+    ] {
+        let err = Create::from_iter_safe(*args).unwrap_err();
+        assert_eq!(err.kind, structopt::clap::ErrorKind::ValueValidation);
+    }
+    for args in &[
+        &["gen", "--keystore", "ephemeral", "--keystore-path", "keys"][..],
+        &["gen", "--keystore-path", "keys", "--reuse-agent", "alice"],
+    ] {
+        let err = Create::from_iter_safe(*args).unwrap_err();
+        assert_eq!(err.kind, structopt::clap::ErrorKind::ArgumentConflict);
+    }
+}
+
+#[test]
+fn repl_lines_split_like_a_shell() {
+    use holochain_hc::repl::split_words;
+    assert_eq!(
+        split_words(r#"zome my-dna agent --payload '{"a": 1}' "two words" a\ b"#).unwrap(),
+        vec![
+            "zome",
+            "my-dna",
+            "agent",
+            "--payload",
+            r#"{"a": 1}"#,
+            "two words",
+            "a b"
+        ]
+    );
+    assert_eq!(split_words("  list-cells  ").unwrap(), vec!["list-cells"]);
+    assert_eq!(split_words("raw ''").unwrap(), vec!["raw", ""]);
+    assert!(split_words("raw '{").is_err());
+    assert!(split_words("list-cells \\").is_err());
+    assert_eq!(split_words(r"a\\b 'c\d'").unwrap(), vec![r"a\b", r"c\d"]);
+}
+
+#[test]
+fn replayed_responses_are_compared_without_ignored_fields() {
+    use holochain_hc::repl::diff;
+    use holochain_hc::repl::normalize;
+    use holochain_hc::repl::ReplayLine;
+    use serde_json::json;
+
+    let expected = json!({"posts": [{"content": "hi", "timestamp": 1}], "count": 1});
+    let mut actual =
+        json!({"posts": [{"content": "hey", "timestamp": 2}], "count": 1, "more": true});
+    let diff_lines = diff(&expected, &actual);
+    assert_eq!(
+        diff_lines,
+        vec![
+            r#".posts[0].content: expected "hi" but got "hey""#,
+            ".posts[0].timestamp: expected 1 but got 2",
+            ".more: not expected but got true",
+        ]
+    );
+
+    let ignore = vec!["timestamp".to_string(), "more".to_string()];
+    let mut expected = expected;
+    normalize(&mut expected, &ignore);
+    normalize(&mut actual, &ignore);
+    assert_eq!(
+        diff(&expected, &actual),
+        vec![r#".posts[0].content: expected "hi" but got "hey""#]
+    );
+    assert_eq!(
+        diff(&json!([1, 2]), &json!([1])),
+        vec![".: expected 2 items but got 1"]
+    );
+    assert!(diff(&json!("Dnas: []"), &json!("Dnas: []")).is_empty());
+
+    // A null expected response is still an expectation.
+    let line: ReplayLine =
+        serde_json::from_str(r#"{"call": "list-dnas", "expected": null}"#).unwrap();
+    assert_eq!(line.expected, Some(serde_json::Value::Null));
+    let line: ReplayLine = serde_json::from_str(r#"{"call": "list-dnas"}"#).unwrap();
+    assert_eq!(line.expected, None);
+    assert!(serde_json::from_str::<ReplayLine>(r#"{"call": "list-dnas", "expect": 1}"#).is_err());
+}
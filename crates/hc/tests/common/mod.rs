@@ -0,0 +1,56 @@
+//! Helpers shared by the integration tests.
+//! Not every test file uses all of them.
+#![allow(dead_code)]
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use holochain_types::prelude::*;
+use holochain_wasm_test_utils::TestWasm;
+
+/// A dna of one test wasm with a random uuid
+/// so every call has its own dna hash.
+pub async fn test_dna(wasm: TestWasm) -> DnaFile {
+    let dna = DnaDefBuilder::default()
+        .zomes(vec![wasm.into()])
+        .random_uuid()
+        .build()
+        .unwrap();
+    DnaFile::new(dna, vec![DnaWasm::from(wasm)]).await.unwrap()
+}
+
+/// Same as [`test_dna`] with the name of the dna set.
+pub async fn named_test_dna(name: &str, wasm: TestWasm) -> DnaFile {
+    let dna = DnaDefBuilder::default()
+        .name(name.to_string())
+        .zomes(vec![wasm.into()])
+        .random_uuid()
+        .build()
+        .unwrap();
+    DnaFile::new(dna, vec![DnaWasm::from(wasm)]).await.unwrap()
+}
+
+/// A new directory in the system temp directory.
+pub fn test_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(nanoid::nanoid!());
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write a [`test_dna`] to `<zome name>.dna.gz` in `dir`.
+pub async fn write_test_dna(dir: &Path, wasm: TestWasm) -> (PathBuf, DnaFile) {
+    let path = dir.join(format!("{}.dna.gz", ZomeName::from(wasm)));
+    write_dna(path, test_dna(wasm).await).await
+}
+
+/// Write a [`named_test_dna`] to `<name>.dna.gz` in `dir`.
+pub async fn write_named_test_dna(dir: &Path, name: &str, wasm: TestWasm) -> (PathBuf, DnaFile) {
+    let path = dir.join(format!("{}.dna.gz", name));
+    write_dna(path, named_test_dna(name, wasm).await).await
+}
+
+async fn write_dna(path: PathBuf, dna: DnaFile) -> (PathBuf, DnaFile) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, dna.to_file_content().await.unwrap()).unwrap();
+    (path, dna)
+}
@@ -0,0 +1,344 @@
+use std::net::IpAddr;
+
+use hc::cmds::Create;
+use hc::config::ConductorConfigSpec;
+use holochain_conductor_api::config::conductor::DbSyncLevel;
+use holochain_hc as hc;
+
+#[test]
+fn overrides_survive_regeneration() {
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let address: IpAddr = "::1".parse().unwrap();
+
+    let first = ConductorConfigSpec {
+        zome_call_metering: Some(true),
+        signing_service_uri: Some("http://localhost:8888".to_string()),
+        ..Default::default()
+    };
+    hc::config::apply_overrides(path.clone(), first).unwrap();
+
+    let second = ConductorConfigSpec {
+        interface_address: Some(address),
+        signing_service_uri: Some("http://localhost:9999".to_string()),
+        ..Default::default()
+    };
+    hc::config::apply_overrides(path.clone(), second).unwrap();
+
+    let config = hc::config::read_config(path.clone()).unwrap().unwrap();
+    assert!(config.zome_call_metering);
+    assert_eq!(config.interface_address, Some(address));
+    assert_eq!(
+        config.signing_service_uri.as_deref(),
+        Some("http://localhost:9999")
+    );
+    // Fields no spec set keep their generated values.
+    assert_eq!(config.keystore_path, Some(path.join("keystore")));
+    assert_eq!(
+        hc::config::read_overrides(path.clone()).unwrap(),
+        ConductorConfigSpec {
+            zome_call_metering: Some(true),
+            interface_address: Some(address),
+            signing_service_uri: Some("http://localhost:9999".to_string()),
+            ..Default::default()
+        }
+    );
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[test]
+fn flags_win_over_the_overrides_file() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    let file = dir.join("overrides.yaml");
+    std::fs::write(
+        &file,
+        "interface_address: \"127.0.0.1\"\nencryption_service_uri: \"http://localhost:7777\"\n",
+    )
+    .unwrap();
+
+    let create = Create {
+        config_overrides: Some(file.clone()),
+        bind: Some("::1".parse().unwrap()),
+        zome_call_metered: true,
+        db_sync_level: Some(DbSyncLevel::Off),
+        ..Default::default()
+    };
+    let spec = create.config_spec().unwrap();
+    assert_eq!(spec.interface_address, Some("::1".parse().unwrap()));
+    assert_eq!(
+        spec.encryption_service_uri.as_deref(),
+        Some("http://localhost:7777")
+    );
+    assert_eq!(spec.zome_call_metering, Some(true));
+    assert_eq!(spec.db_sync_level, Some(DbSyncLevel::Off));
+
+    // Typos aren't silently ignored.
+    std::fs::write(&file, "zome_call_metred: true\n").unwrap();
+    let err = ConductorConfigSpec::from_file(&file).unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("zome_call_metred"),
+        "{:?}",
+        err
+    );
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn network_builder_validates_before_building() {
+    use hc::config::NetworkConfigBuilder;
+    use hc::config::ProxyMode;
+    use holochain_p2p::kitsune_p2p::ProxyConfig;
+    use holochain_p2p::kitsune_p2p::TransportConfig;
+
+    let config = NetworkConfigBuilder::new()
+        .bootstrap(Some(url2::url2!("https://bootstrap.holo.host")))
+        .build()
+        .unwrap();
+    assert!(config.transport_pool.is_empty());
+    assert!(config.bootstrap_service.is_some());
+
+    let config = NetworkConfigBuilder::new()
+        .quic(None, None, Some(1234))
+        .proxy(ProxyMode::Server, None)
+        .build()
+        .unwrap();
+    match &config.transport_pool[..] {
+        [TransportConfig::Proxy {
+            sub_transport,
+            proxy_config: ProxyConfig::LocalProxyServer { .. },
+        }] => assert!(matches!(
+            **sub_transport,
+            TransportConfig::Quic {
+                override_port: Some(1234),
+                ..
+            }
+        )),
+        pool => panic!("Expected a proxy server, got {:?}", pool),
+    }
+
+    // A proxy needs quic.
+    let err = NetworkConfigBuilder::new()
+        .mem()
+        .proxy(
+            ProxyMode::Client,
+            Some(url2::url2!("kitsune-proxy://a.host")),
+        )
+        .build()
+        .unwrap_err();
+    assert!(err.to_string().contains("quic"), "{}", err);
+
+    let mut tuning = holochain_p2p::kitsune_p2p::KitsuneP2pTuningParams::default();
+    tuning.default_notify_remote_agent_count = 0;
+    let err = NetworkConfigBuilder::new()
+        .tuning(tuning)
+        .build()
+        .unwrap_err();
+    assert!(err.to_string().contains("--notify-agents"), "{}", err);
+}
+
+#[test]
+fn relocating_a_config_only_moves_paths_in_the_setup() {
+    use holochain_conductor_api::config::conductor::PassphraseServiceConfig;
+    use std::path::Path;
+
+    let from = Path::new("/setups/alice");
+    let to = Path::new("/moved/alice");
+    let mut config = hc::config::create_config(from.to_path_buf());
+    config.passphrase_service = Some(PassphraseServiceConfig::UnixSocket {
+        path: from.join("passphrase.sock"),
+    });
+    let mut shared = hc::config::create_config(Path::new("databases").to_path_buf());
+    shared.keystore_path = Some("/agents/bob/keystore".into());
+
+    hc::config::relocate_config(&mut config, from, to);
+    let environment_path: std::path::PathBuf = config.environment_path.clone().into();
+    assert_eq!(environment_path, to);
+    assert_eq!(config.keystore_path, Some(to.join("keystore")));
+    assert_eq!(
+        config.passphrase_service,
+        Some(PassphraseServiceConfig::UnixSocket {
+            path: to.join("passphrase.sock")
+        })
+    );
+
+    // Relative paths and paths outside the setup stay as they are.
+    let before = shared.clone();
+    hc::config::relocate_config(&mut shared, from, to);
+    assert_eq!(shared, before);
+}
+
+#[test]
+fn allowed_origins_are_kept_when_the_config_is_regenerated() {
+    use hc::config::ConfigCmd;
+    use hc::config::Reconfigure;
+    use holochain_conductor_api::AdminInterfaceConfig;
+    use holochain_conductor_api::InterfaceDriver;
+    use structopt::StructOpt;
+
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let spec = ConductorConfigSpec {
+        admin_interfaces: Some(vec![AdminInterfaceConfig {
+            driver: InterfaceDriver::Websocket { port: 0 },
+            allowed_origins: None,
+            address: None,
+        }]),
+        ..Default::default()
+    };
+    hc::config::apply_overrides(path.clone(), spec).unwrap();
+
+    let allowed = hc::allowed_origins(
+        hc::DEFAULT_ORIGIN,
+        vec!["http://localhost:3000".to_string()],
+    );
+    hc::use_allowed_origins(path.clone(), allowed).unwrap();
+    let expected = Some(vec![
+        hc::DEFAULT_ORIGIN.to_string(),
+        "http://localhost:3000".to_string(),
+    ]);
+    assert_eq!(hc::allowed_origins_from_config(&path).unwrap(), expected);
+    // The overrides that set the admin interface don't undo them.
+    hc::config::apply_overrides(path.clone(), ConductorConfigSpec::default()).unwrap();
+    assert_eq!(hc::allowed_origins_from_config(&path).unwrap(), expected);
+
+    hc::use_allowed_origins(
+        path.clone(),
+        hc::allowed_origins(hc::DEFAULT_ORIGIN, vec!["*".to_string()]),
+    )
+    .unwrap();
+    assert_eq!(hc::allowed_origins_from_config(&path).unwrap(), None);
+
+    // hc's own origin is allowed when it isn't the default.
+    assert_eq!(
+        hc::allowed_origins("my-tool", vec!["http://localhost:3000".to_string()]),
+        Some(vec![
+            "my-tool".to_string(),
+            "http://localhost:3000".to_string()
+        ])
+    );
+
+    let reconfigure = Reconfigure::from_iter_safe(&[
+        "config",
+        "set-origins",
+        "0",
+        "--allowed-origins",
+        "http://a,http://b",
+    ])
+    .unwrap();
+    match reconfigure.cmd {
+        Some(ConfigCmd::SetOrigins {
+            existing,
+            allowed_origins,
+        }) => {
+            assert_eq!(existing.indices, vec![0]);
+            assert_eq!(allowed_origins, vec!["http://a", "http://b"]);
+        }
+        _ => panic!("Expected set-origins"),
+    }
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[test]
+fn show_compares_the_config_with_a_new_one() {
+    use hc::config::ConfigCmd;
+    use hc::config::ConfigDiff;
+    use hc::config::Reconfigure;
+    use structopt::StructOpt;
+
+    let reconfigure =
+        Reconfigure::from_iter_safe(&["config", "show", "-i", "0", "--diff-default"]).unwrap();
+    match reconfigure.cmd {
+        Some(ConfigCmd::Show(show)) => {
+            assert_eq!(show.index, Some(0));
+            assert!(show.diff_default);
+        }
+        _ => panic!("Expected show"),
+    }
+    assert!(Reconfigure::from_iter_safe(&["config", "show", "--raw", "--json"]).is_err());
+
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let default = hc::config::create_config(path.clone());
+    let spec = ConductorConfigSpec {
+        zome_call_metering: Some(true),
+        interface_address: Some("::1".parse().unwrap()),
+        ..Default::default()
+    };
+    let config = hc::config::apply_overrides(path.clone(), spec).unwrap();
+    let diffs = hc::config::diff_configs(&default, &config).unwrap();
+    assert_eq!(
+        diffs,
+        vec![
+            ConfigDiff {
+                field: "interface_address".to_string(),
+                default: None,
+                value: Some(serde_json::json!("::1")),
+            },
+            ConfigDiff {
+                field: "zome_call_metering".to_string(),
+                default: Some(serde_json::json!(false)),
+                value: Some(serde_json::json!(true)),
+            },
+        ]
+    );
+    assert!(hc::config::diff_configs(&default, &default)
+        .unwrap()
+        .is_empty());
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[test]
+fn config_parse_errors_point_at_the_file_and_line() {
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let file = path.join(hc::config::CONDUCTOR_CONFIG);
+    std::fs::write(&file, "environment_path: /tmp/env\nadmin_interfaces: [\n").unwrap();
+    let err = hc::config::parse_config(&file).unwrap_err().to_string();
+    assert!(err.contains(&file.display().to_string()), "{}", err);
+    assert!(err.contains("line"), "{}", err);
+    let err = hc::config::read_config(path.clone())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(&file.display().to_string()), "{}", err);
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[test]
+fn builder_writes_configs_hc_can_read() {
+    use hc::config::ConductorConfigBuilder;
+    use hc::config::NetworkConfigBuilder;
+    use holochain_conductor_api::AdminInterfaceConfig;
+    use holochain_conductor_api::InterfaceDriver;
+
+    let dir = hc::generate::generate_directory(None, None).unwrap();
+    let builder = ConductorConfigBuilder::new(dir.clone())
+        .admin_interface(AdminInterfaceConfig {
+            driver: InterfaceDriver::Websocket { port: 0 },
+            allowed_origins: None,
+            address: None,
+        })
+        .network(NetworkConfigBuilder::new().build().unwrap())
+        .extra("db_sync_level", serde_yaml::Value::String("fast".into()))
+        .extra("a_newer_field", serde_yaml::Value::Bool(true));
+    let path = builder.write_to(&dir).unwrap();
+    assert_eq!(path, dir.join(hc::config::CONDUCTOR_CONFIG));
+    assert!(builder.build().contains("a_newer_field: true"));
+
+    // The extra fields replace the ones the config has.
+    let config = hc::config::parse_config(&path).unwrap();
+    assert_eq!(config.db_sync_level, DbSyncLevel::Fast);
+    assert_eq!(config.admin_interfaces.as_ref().unwrap().len(), 2);
+    assert!(config.network.is_some());
+    let mut expected = builder.config().clone();
+    expected.db_sync_level = DbSyncLevel::Fast;
+    assert_eq!(config, expected);
+
+    // The start is the same config as `hc gen` writes.
+    assert_eq!(
+        ConductorConfigBuilder::new(dir.clone()).config(),
+        &hc::config::create_config(dir.clone())
+    );
+
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,116 @@
+use holochain_hc as hc;
+
+use std::path::Path;
+
+use hc::config::CONDUCTOR_CONFIG;
+use hc::config_check::check_config;
+use holochain_conductor_api::InterfaceDriver;
+
+/// Set the admin port in the config without reserving it
+/// so two setups can be given the same port.
+fn set_admin_port(setup: &Path, port: u16) {
+    let mut config = hc::config::read_config(setup.to_path_buf())
+        .unwrap()
+        .unwrap();
+    config.admin_interfaces.as_mut().unwrap()[0].driver = InterfaceDriver::Websocket { port };
+    hc::config::write_config(setup.to_path_buf(), &config);
+}
+
+#[test]
+fn generated_configs_have_no_problems() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    assert_eq!(
+        check_config(&dir.join(CONDUCTOR_CONFIG), &[]),
+        Vec::<String>::new()
+    );
+    hc::config_check::check_setups(&[dir.clone()], &[dir.clone()]).unwrap();
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn hand_edited_configs_list_each_problem() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    let path = dir.join(CONDUCTOR_CONFIG);
+    std::fs::write(
+        &path,
+        "environment_path: ./missing\n\
+        zome_call_metred: true\n\
+        admin_interfaces: []\n",
+    )
+    .unwrap();
+    let problems = check_config(&path, &[]);
+    assert_eq!(problems.len(), 3, "{:?}", problems);
+    assert!(problems[0].contains("Unknown field zome_call_metred"));
+    assert!(problems[1].contains("environment path"));
+    assert!(problems[1].contains("doesn't exist"));
+    assert!(problems[2].contains("no admin_interfaces"));
+
+    // A config that doesn't parse only has that problem.
+    std::fs::write(&path, "environment_path: [\n").unwrap();
+    let problems = check_config(&path, &[]);
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert!(problems[0].contains("Failed to parse"), "{}", problems[0]);
+
+    let err = hc::config_check::check_setups(&[dir.clone()], &[]).unwrap_err();
+    assert!(err.to_string().contains("--skip-validation"), "{}", err);
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn admin_ports_cant_collide_across_setups() {
+    let alice = hc::generate::generate(None, None, None).unwrap();
+    let bob = hc::generate::generate(None, None, None).unwrap();
+    set_admin_port(&alice, 9911);
+    set_admin_port(&bob, 9911);
+    let setups = [alice.clone(), bob.clone()];
+
+    let problems = check_config(&alice.join(CONDUCTOR_CONFIG), &setups);
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert!(
+        problems[0].contains(&format!(
+            "port 9911 is also used by setup {}",
+            bob.display()
+        )),
+        "{}",
+        problems[0]
+    );
+
+    // Ports the conductor picks when it starts don't collide.
+    set_admin_port(&bob, 0);
+    assert!(check_config(&alice.join(CONDUCTOR_CONFIG), &setups).is_empty());
+
+    std::fs::remove_dir_all(alice).ok();
+    std::fs::remove_dir_all(bob).ok();
+}
+
+#[test]
+fn app_ports_in_configs_cant_collide() {
+    let alice = hc::generate::generate(None, None, None).unwrap();
+    let bob = hc::generate::generate(None, None, None).unwrap();
+    hc::use_config_app_ports(alice.clone(), &[8888, 9911]).unwrap();
+    hc::use_config_app_ports(bob.clone(), &[8888]).unwrap();
+    set_admin_port(&alice, 9911);
+    let setups = [alice.clone(), bob.clone()];
+
+    let problems = check_config(&alice.join(CONDUCTOR_CONFIG), &setups);
+    assert_eq!(problems.len(), 2, "{:?}", problems);
+    assert!(
+        problems[0].contains("App port 9911 is also an admin port"),
+        "{}",
+        problems[0]
+    );
+    assert!(
+        problems[1].contains(&format!(
+            "App port 8888 is also used by setup {}",
+            bob.display()
+        )),
+        "{}",
+        problems[1]
+    );
+    assert_eq!(hc::app_ports_from_config(&alice).unwrap(), vec![8888, 9911]);
+
+    std::fs::remove_dir_all(alice).ok();
+    std::fs::remove_dir_all(bob).ok();
+}
@@ -0,0 +1,82 @@
+use holochain_conductor_api::AgentArc;
+use holochain_hc::coverage::*;
+use holochain_p2p::dht_arc::DhtArc;
+use holochain_p2p::dht_arc::MAX_HALF_LENGTH;
+use holochain_types::prelude::AgentPubKey;
+
+const QUARTER: u32 = 1 << 30;
+
+fn approx(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+fn agent_arc(n: u8, center: u32, half_length: u32) -> AgentArc {
+    AgentArc {
+        agent: AgentPubKey::from_raw_32(vec![n; 32]),
+        arc: DhtArc::new(center, half_length),
+    }
+}
+
+#[test]
+fn arcs_have_bounds_unless_they_hold_nothing() {
+    assert_eq!(arc_bounds(&DhtArc::new(100, 0)), None);
+    assert_eq!(arc_bounds(&DhtArc::new(100, 1)), Some((100, 100)));
+    assert_eq!(arc_bounds(&DhtArc::new(100, 11)), Some((90, 110)));
+    // Arcs near location 0 wrap around the end of the dht.
+    assert_eq!(arc_bounds(&DhtArc::new(0, 11)), Some((u32::MAX - 9, 10)));
+}
+
+#[test]
+fn bars_show_the_held_part_of_the_dht() {
+    // Holds all but the first location of the second quarter of the dht.
+    let arc = DhtArc::new(QUARTER + QUARTER / 2, QUARTER / 2);
+    assert_eq!(coverage_bar(&arc, 8), "..+#....");
+    assert_eq!(coverage_bar(&arc, 2), "+.");
+    assert_eq!(coverage_bar(&DhtArc::new(0, MAX_HALF_LENGTH), 4), "####");
+    assert_eq!(coverage_bar(&DhtArc::new(0, 0), 4), "....");
+    // Wrapping arcs fill both ends of the bar.
+    assert_eq!(coverage_bar(&DhtArc::new(0, QUARTER / 2), 8), "#......+");
+}
+
+#[test]
+fn total_coverage_counts_overlaps_once() {
+    let first_half = agent_arc(1, QUARTER, QUARTER);
+    let inside = agent_arc(2, QUARTER + QUARTER / 2, QUARTER / 2);
+    assert!(approx(first_half.coverage(), 0.5));
+    assert!(approx(
+        total_coverage(&[first_half.clone(), inside.clone()]),
+        0.5
+    ));
+
+    let wrapping = agent_arc(3, 0, QUARTER / 2);
+    assert!(approx(
+        total_coverage(&[first_half, inside, wrapping]),
+        0.625
+    ));
+    assert_eq!(total_coverage(&[]), 0.0);
+    assert_eq!(total_coverage(&[agent_arc(4, 7, MAX_HALF_LENGTH)]), 1.0);
+}
+
+#[test]
+fn coverage_is_printed_for_each_agent() {
+    let arcs = vec![agent_arc(1, QUARTER, QUARTER), agent_arc(2, 0, 0)];
+    let mut out = String::new();
+    write_coverage(&mut out, &arcs, 4).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+    assert_eq!(lines[0], arcs[0].agent.to_string());
+    assert_eq!(lines[1], "  [+#..]  50.00% 0x00000001..=0x7fffffff");
+    assert_eq!(lines[2], arcs[1].agent.to_string());
+    assert_eq!(lines[3], "  [....]   0.00% holds nothing");
+    assert_eq!(lines[4], "Held by at least one agent: 50.00%");
+
+    let report = CoverageReport::new(&arcs);
+    assert!(approx(report.total, 0.5));
+    assert_eq!(report.arcs[0].center, QUARTER);
+    assert_eq!(report.arcs[0].first, Some(1));
+    assert_eq!(report.arcs[0].last, Some(0x7fff_ffff));
+    assert_eq!(report.arcs[1].first, None);
+
+    let mut out = String::new();
+    write_coverage(&mut out, &[], 4).unwrap();
+    assert_eq!(out, "No agents found for this dna\n");
+}
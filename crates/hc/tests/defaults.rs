@@ -0,0 +1,175 @@
+use holochain_hc as hc;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use hc::defaults::DefaultFlags;
+use hc::defaults::Defaults;
+use hc::defaults::Source;
+use hc::defaults::UserConfig;
+use hc::logs::ConductorLog;
+use hc::logs::LogFormat;
+use hc::project::ProjectConfig;
+
+const USER_CONFIG: &str = r#"
+holochain_path = "bin/holochain"
+root = "chains"
+
+[network]
+transport = "quic"
+bootstrap = "https://bootstrap.holo.host"
+
+[conductor_log]
+filter = "warn"
+format = "json"
+"#;
+
+#[test]
+fn flags_win_over_env_which_wins_over_files() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    let path = dir.join("config.toml");
+    std::fs::write(&path, USER_CONFIG).unwrap();
+    let user = UserConfig::load(&path).unwrap();
+    assert_eq!(user.holochain_path, Some(dir.join("bin/holochain")));
+    let file = Source::File(path.clone());
+
+    // Only the user config.
+    let no_env = |_: &str| None;
+    let defaults =
+        Defaults::resolve(Default::default(), &ProjectConfig::default(), &user, no_env).unwrap();
+    assert_eq!(defaults.holochain_path.value, dir.join("bin/holochain"));
+    assert_eq!(defaults.holochain_path.source, file);
+    assert_eq!(defaults.root.value, Some(dir.join("chains")));
+    assert_eq!(defaults.network.source, file);
+    assert!(defaults.network().unwrap().is_some());
+    assert_eq!(
+        defaults.conductor_log(),
+        ConductorLog {
+            filter: Some("warn".into()),
+            format: Some(LogFormat::Json),
+        }
+    );
+
+    // The hc.toml wins over the user config.
+    let project_path = dir.join(hc::project::PROJECT_CONFIG);
+    std::fs::write(&project_path, "root = \"project-chains\"\n").unwrap();
+    let project = ProjectConfig::load(&project_path).unwrap();
+    let defaults = Defaults::resolve(Default::default(), &project, &user, no_env).unwrap();
+    assert_eq!(defaults.root.value, Some(dir.join("project-chains")));
+    assert_eq!(defaults.root.source, Source::File(project_path));
+    assert_eq!(defaults.holochain_path.source, file);
+
+    // The environment wins over both.
+    let env: HashMap<_, _> = vec![
+        (hc::defaults::HOLOCHAIN_PATH_ENV, "/env/holochain"),
+        (hc::defaults::NETWORK_ENV, "mem"),
+        (hc::defaults::CONDUCTOR_LOG_FORMAT_ENV, "compact"),
+    ]
+    .into_iter()
+    .collect();
+    let env = |var: &str| env.get(var).map(|v| v.to_string());
+    let defaults = Defaults::resolve(Default::default(), &project, &user, env).unwrap();
+    assert_eq!(
+        defaults.holochain_path.value,
+        PathBuf::from("/env/holochain")
+    );
+    assert_eq!(
+        defaults.holochain_path.source,
+        Source::Env(hc::defaults::HOLOCHAIN_PATH_ENV)
+    );
+    assert_eq!(
+        defaults.network.source,
+        Source::Env(hc::defaults::NETWORK_ENV)
+    );
+    assert_eq!(
+        defaults.conductor_log_format.value,
+        Some(LogFormat::Compact)
+    );
+    assert_eq!(defaults.conductor_log_filter.source, file);
+
+    // And the flags win over everything.
+    let flags = DefaultFlags {
+        holochain_path: Some("/flag/holochain".into()),
+        root: None,
+        conductor_log: ConductorLog {
+            filter: Some("debug".into()),
+            format: None,
+        },
+    };
+    let defaults = Defaults::resolve(flags, &project, &user, env).unwrap();
+    assert_eq!(
+        defaults.holochain_path.value,
+        PathBuf::from("/flag/holochain")
+    );
+    assert_eq!(defaults.holochain_path.source, Source::Flag);
+    assert_eq!(
+        defaults.conductor_log_filter.value.as_deref(),
+        Some("debug")
+    );
+    assert_eq!(defaults.conductor_log_filter.source, Source::Flag);
+
+    let printed = defaults.to_string();
+    assert!(
+        printed.contains("holochain_path: /flag/holochain (flag)"),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains("network: mem (env HC_NETWORK)"),
+        "{}",
+        printed
+    );
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn nothing_set_uses_the_built_in_defaults() {
+    let defaults = Defaults::resolve(
+        Default::default(),
+        &ProjectConfig::default(),
+        &UserConfig::default(),
+        |_| None,
+    )
+    .unwrap();
+    assert_eq!(defaults.holochain_path.value, PathBuf::from("holochain"));
+    assert_eq!(defaults.holochain_path.source, Source::BuiltIn);
+    assert_eq!(defaults.root.value, None);
+    assert!(defaults.network.value.is_none());
+    assert_eq!(defaults.conductor_log(), ConductorLog::default());
+}
+
+#[test]
+fn malformed_user_configs_fall_back_to_the_built_in_defaults() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    let path = dir.join("config.toml");
+    std::fs::write(&path, "holochain_pth = \"holochain\"\n").unwrap();
+    let err = UserConfig::load(&path).unwrap_err();
+    assert!(err.to_string().contains("holochain_pth"), "{}", err);
+
+    let user = UserConfig::load_or_default(&path);
+    assert!(user.path.is_none());
+    assert!(user.holochain_path.is_none());
+    // A missing file is the same as an empty one.
+    assert!(UserConfig::load_or_default(&dir.join("missing.toml"))
+        .path
+        .is_none());
+
+    // Invalid environment variables are an error though.
+    let err = Defaults::resolve(
+        Default::default(),
+        &ProjectConfig::default(),
+        &user,
+        |var| {
+            if var == hc::defaults::NETWORK_ENV {
+                Some("tcp".to_string())
+            } else {
+                None
+            }
+        },
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("HC_NETWORK"), "{}", err);
+
+    std::fs::remove_dir_all(dir).ok();
+}
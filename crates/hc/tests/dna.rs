@@ -0,0 +1,396 @@
+use std::convert::TryFrom;
+use std::io::Write;
+
+use holochain_hc as hc;
+use holochain_types::prelude::*;
+use holochain_wasm_test_utils::TestWasm;
+
+mod common;
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(bytes).unwrap();
+    gz.finish().unwrap()
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn inspect_lists_zome_functions() {
+    let properties = JsonProperties::new(serde_json::json!({"max_members": 5}));
+    let dna = common::named_test_dna("inspect-test", TestWasm::Foo)
+        .await
+        .with_properties(SerializedBytes::try_from(properties).unwrap())
+        .await
+        .unwrap();
+    let dir = common::test_dir();
+    let path = dir.join("foo.dna.gz");
+    std::fs::write(&path, dna.to_file_content().await.unwrap()).unwrap();
+
+    let inspection = hc::dna::inspect(&path).await.unwrap();
+    assert_eq!(inspection.name, "inspect-test");
+    assert_eq!(inspection.hash, dna.dna_hash().to_string());
+    assert_eq!(
+        inspection.properties,
+        hc::dna::PropertiesInspection::Decoded(serde_json::json!({"max_members": 5}))
+    );
+    assert_eq!(inspection.zomes.len(), 1);
+    let zome = &inspection.zomes[0];
+    assert_eq!(zome.name, "foo");
+    let (wasm_hash, wasm) = dna.code().iter().next().unwrap();
+    assert_eq!(zome.wasm_hash, Some(wasm_hash.to_string()));
+    assert_eq!(zome.wasm_size, Some(wasm.code().len()));
+    assert_eq!(zome.functions, vec!["foo".to_string()]);
+    assert_eq!(zome.callbacks, vec!["init".to_string()]);
+    let listed = cfg!(feature = "entry_types");
+    assert_eq!(zome.entry_types, if listed { Some(vec![]) } else { None });
+
+    let printed = inspection.to_string();
+    assert!(printed.contains("max_members: 5"), "{}", printed);
+    assert_eq!(printed.contains("Entry types: none"), listed, "{}", printed);
+    assert!(
+        printed.contains(&format!("Wasm: {}", wasm_hash)),
+        "{}",
+        printed
+    );
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[cfg(feature = "entry_types")]
+#[tokio::test(threaded_scheduler)]
+async fn inspect_lists_entry_types() {
+    let dir = common::test_dir();
+    let (path, _) = common::write_test_dna(&dir, TestWasm::Create).await;
+
+    let inspection = hc::dna::inspect(&path).await.unwrap();
+    let zome = &inspection.zomes[0];
+    assert!(zome.callbacks.contains(&"entry_defs".to_string()));
+    use hc::dna::EntryVisibilityInspection::*;
+    assert_eq!(
+        zome.entry_types
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|e| (e.name.as_str(), e.visibility))
+            .collect::<Vec<_>>(),
+        vec![("post", Public), ("msg", Public), ("priv_msg", Private)]
+    );
+    let printed = inspection.to_string();
+    assert!(
+        printed.contains("Entry types: post, msg, priv_msg (private)"),
+        "{}",
+        printed
+    );
+    let json = serde_json::to_value(&inspection).unwrap();
+    assert_eq!(
+        json["zomes"][0]["entry_types"][2],
+        serde_json::json!({"name": "priv_msg", "visibility": "private"})
+    );
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn dna_from_bytes_checks_the_format() {
+    let dna = common::test_dna(TestWasm::Foo).await;
+    let bytes = dna.to_file_content().await.unwrap();
+
+    let decoded = hc::dna::dna_from_bytes(&bytes).await.unwrap();
+    assert_eq!(decoded.dna_hash(), dna.dna_hash());
+
+    let err = hc::dna::dna_from_bytes(b"not a dna").await.unwrap_err();
+    assert!(err.to_string().contains("not gzipped"), "{}", err);
+    // Gzipped but truncated.
+    let err = hc::dna::dna_from_bytes(&bytes[..bytes.len() / 2])
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Failed to decode"), "{}", err);
+    assert!(err.to_string().contains("not valid gzip"), "{}", err);
+    // Gzipped but not msgpack.
+    let err = hc::dna::dna_from_bytes(&gzip(&[0xc1])).await.unwrap_err();
+    assert!(err.to_string().contains("not valid msgpack"), "{}", err);
+    // Msgpack but not a dna.
+    let not_a_dna = holochain_types::prelude::encode(&"not a dna").unwrap();
+    let err = hc::dna::dna_from_bytes(&gzip(&not_a_dna))
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("doesn't have the fields of a dna"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn hash_with_modifiers_matches_a_modified_dna() {
+    let dir = common::test_dir();
+    let (path, dna) = common::write_test_dna(&dir, TestWasm::Foo).await;
+    let properties_path = dir.join("properties.json");
+    std::fs::write(&properties_path, r#"{"max_members": 5}"#).unwrap();
+
+    // No modifiers is the dna's own hash.
+    let hash = hc::dna::hash_with_modifiers(&path, None, None)
+        .await
+        .unwrap();
+    assert_eq!(&hash, dna.dna_hash());
+
+    let properties = hc::dna::read_properties(&properties_path).unwrap();
+    let hash = hc::dna::hash_with_modifiers(&path, Some("seed".into()), Some(properties))
+        .await
+        .unwrap();
+    let properties = JsonProperties::new(serde_json::json!({"max_members": 5}));
+    let expected = dna
+        .clone()
+        .with_properties(SerializedBytes::try_from(properties).unwrap())
+        .await
+        .unwrap()
+        .with_uuid("seed".into())
+        .await
+        .unwrap();
+    assert_eq!(&hash, expected.dna_hash());
+    assert_ne!(&hash, dna.dna_hash());
+
+    // Other seeds are other networks.
+    let other = hc::dna::hash_with_modifiers(&path, Some("other seed".into()), None)
+        .await
+        .unwrap();
+    assert_ne!(other, hash);
+
+    std::fs::write(&properties_path, "not json").unwrap();
+    let err = hc::dna::read_properties(&properties_path).unwrap_err();
+    assert!(err.to_string().contains("not valid JSON"), "{}", err);
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn app_bundles_are_rejected_with_a_clear_error() {
+    for (bundle, kind) in &[
+        ("app.webhapp", "a web hApp bundle"),
+        ("app.happ", "a hApp bundle"),
+    ] {
+        let err = hc::dna::parse_dnas(vec![bundle.into()]).unwrap_err();
+        assert!(err.to_string().contains(kind), "{}", err);
+    }
+    let err = hc::dna::parse_dnas(vec!["app.zip".into()]).unwrap_err();
+    assert!(
+        err.to_string().contains("not a valid dna file name"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn signed_dnas_verify_until_they_change() {
+    let keystore = holochain_keystore::test_keystore::spawn_test_keystore()
+        .await
+        .unwrap();
+    let alice = AgentPubKey::new_from_pure_entropy(&keystore).await.unwrap();
+    let bob = AgentPubKey::new_from_pure_entropy(&keystore).await.unwrap();
+    let dir = common::test_dir();
+    let (path, dna) = common::write_test_dna(&dir, TestWasm::Foo).await;
+    let content = std::fs::read(&path).unwrap();
+
+    let signature = hc::dna::sign(&keystore, &alice, &path).await.unwrap();
+    assert_eq!(signature.dna_hash, dna.dna_hash().to_string());
+    let signature_path = hc::dna::signature_path(&path);
+    assert_eq!(signature_path, dir.join("foo.dna.gz.sig"));
+    hc::dna::write_signature(&signature_path, &signature).unwrap();
+    let signature = hc::dna::read_signature(&signature_path).unwrap();
+    hc::dna::verify(&path, &signature, &alice).await.unwrap();
+
+    // Another agent didn't sign it.
+    let err = hc::dna::verify(&path, &signature, &bob).await.unwrap_err();
+    assert!(err.to_string().contains("is by agent"), "{}", err);
+
+    // Claiming to be another agent doesn't verify.
+    let forged = hc::dna::DnaSignature {
+        agent: bob.to_string(),
+        ..signature.clone()
+    };
+    let err = hc::dna::verify(&path, &forged, &bob).await.unwrap_err();
+    assert!(err.to_string().contains("doesn't match"), "{}", err);
+
+    // A different dna has a different hash.
+    let other = common::test_dna(TestWasm::Foo).await;
+    std::fs::write(&path, other.to_file_content().await.unwrap()).unwrap();
+    let err = hc::dna::verify(&path, &signature, &alice)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("is for dna"), "{}", err);
+
+    // A signature of the other dna doesn't sign this one.
+    let other_signature = hc::dna::sign(&keystore, &alice, &path).await.unwrap();
+    std::fs::write(&path, &content).unwrap();
+    let swapped = hc::dna::DnaSignature {
+        signature: other_signature.signature,
+        ..signature.clone()
+    };
+    let err = hc::dna::verify(&path, &swapped, &alice).await.unwrap_err();
+    assert!(err.to_string().contains("doesn't match"), "{}", err);
+    hc::dna::verify(&path, &signature, &alice).await.unwrap();
+
+    std::fs::write(&signature_path, "not a signature").unwrap();
+    let err = hc::dna::read_signature(&signature_path).unwrap_err();
+    assert!(err.to_string().contains("hc dna sign"), "{}", err);
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn globs_match_across_directories() {
+    use hc::dna::glob_matches;
+    assert!(glob_matches("*.dna.gz", "my.dna.gz"));
+    assert!(!glob_matches("*.dna.gz", "dnas/my.dna.gz"));
+    assert!(glob_matches(
+        "dnas/*/workdir/*.dna.gz",
+        "dnas/chat/workdir/chat.dna.gz"
+    ));
+    assert!(glob_matches(
+        "./dnas/*/workdir/*.dna.gz",
+        "dnas/chat/workdir/chat.dna.gz"
+    ));
+    assert!(!glob_matches(
+        "dnas/*/workdir/*.dna.gz",
+        "dnas/chat/chat.dna.gz"
+    ));
+    assert!(glob_matches("**/*.dna.gz", "my.dna.gz"));
+    assert!(glob_matches("**/*.dna.gz", "a/b/c/my.dna.gz"));
+    assert!(glob_matches("dnas/**/c?at.dna.gz", "dnas/x/y/chat.dna.gz"));
+    assert!(!glob_matches("dnas/**/c?at.dna.gz", "dnas/x/y/cat.dna.gz"));
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn dnas_are_discovered_once_each() {
+    use structopt::StructOpt;
+    let dir = common::test_dir();
+    let mut written = Vec::new();
+    for name in &["a", "b"] {
+        let workdir = dir.join("dnas").join(name).join("workdir");
+        let (path, _) = common::write_named_test_dna(&workdir, name, TestWasm::Foo).await;
+        written.push(path);
+    }
+    // A copy of the same dna and a stale build.
+    std::fs::copy(&written[1], dir.join("dnas/b/workdir/copy.dna.gz")).unwrap();
+    std::fs::create_dir_all(dir.join("target")).unwrap();
+    std::fs::copy(&written[0], dir.join("target/old.dna.gz")).unwrap();
+    std::fs::write(dir.join("target/broken.dna.gz"), "not a dna").unwrap();
+
+    let globs = hc::dna::DnaGlobs::from_iter_safe(&[
+        "gen",
+        "--dna-glob",
+        "dnas/*/workdir/*.dna.gz",
+        "--dna-glob",
+        "**/*.dna.gz",
+        "--exclude",
+        "target",
+    ])
+    .unwrap();
+    let found = hc::dna::discover_dnas(&dir, &globs).await.unwrap();
+    assert_eq!(found, written);
+
+    let globs = hc::dna::DnaGlobs {
+        dna_globs: vec!["*.dna.gz".into()],
+        exclude: Vec::new(),
+    };
+    let err = hc::dna::discover_dnas(&dir, &globs).await.unwrap_err();
+    assert!(err.to_string().contains("--dna-glob *.dna.gz"), "{}", err);
+
+    // Excluding needs something to exclude from.
+    assert!(hc::dna::DnaGlobs::from_iter_safe(&["gen", "--exclude", "target"]).is_err());
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn expected_dna_hashes_are_checked_by_position_or_path() {
+    use structopt::StructOpt;
+    let dir = common::test_dir();
+    let mut dnas = Vec::new();
+    let mut hashes = Vec::new();
+    for name in &["a", "b"] {
+        let (path, dna) = common::write_named_test_dna(&dir, name, TestWasm::Foo).await;
+        dnas.push(path);
+
+        hashes.push(dna.dna_hash().clone());
+    }
+    let hash = hashes[0].to_string();
+    let pair = format!("{}={}", dnas[1].display(), hashes[1]);
+    let expected = hc::dna::ExpectedDnaHashes::from_iter_safe(&[
+        "gen",
+        "--expect-dna-hash",
+        hash.as_str(),
+        "--expect-dna-hash",
+        pair.as_str(),
+    ])
+    .unwrap()
+    .expected;
+    assert_eq!(expected[0].path, None);
+    assert_eq!(expected[1].path.as_ref(), Some(&dnas[1]));
+    assert_eq!(expected[1].hash, hashes[1]);
+    hc::dna::check_dna_hashes(&dnas, &expected, None)
+        .await
+        .unwrap();
+
+    // In the wrong order both are reported.
+    let swapped: Vec<_> = hashes
+        .iter()
+        .rev()
+        .map(|hash| hc::dna::ExpectedDnaHash {
+            path: None,
+            hash: hash.clone(),
+        })
+        .collect();
+    let err = hc::dna::check_dna_hashes(&dnas, &swapped, None)
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with("2 of 2 dnas"), "{}", err);
+    assert!(
+        err.contains(&format!(
+            "{}: expected {} but it is {}",
+            dnas[0].display(),
+            hashes[1],
+            hashes[0]
+        )),
+        "{}",
+        err
+    );
+
+    // Properties change the hash the dna is installed with.
+    let properties = JsonProperties::new(serde_json::json!({"max_members": 5}));
+    let err = hc::dna::check_dna_hashes(&dnas[..1], &expected[..1], Some(properties))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().starts_with("1 of 1 dnas"), "{}", err);
+
+    let err = hc::dna::check_dna_hashes(&dnas[..1], &swapped, None)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("only 1 dnas"), "{}", err);
+    let err = hc::dna::check_dna_hashes(&dnas[..1], &expected[1..], None)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("isn't one of the dnas"), "{}", err);
+    assert!(hc::dna::parse_expected_dna_hash("a.dna.gz=not-a-hash").is_err());
+
+    // The same check on its own.
+    hc::dna::check_dna_hash(&dnas[0], &hashes[0]).await.unwrap();
+    let err = hc::dna::check_dna_hash(&dnas[0], &hashes[1])
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("not the expected"), "{}", err);
+    let path = dnas[0].display().to_string();
+    let verify =
+        hc::dna::Verify::from_iter_safe(&["verify", path.as_str(), hash.as_str()]).unwrap();
+    assert_eq!(
+        verify.against,
+        hc::dna::VerifyAgainst::Hash(hashes[0].clone())
+    );
+    let verify = hc::dna::Verify::from_iter_safe(&["verify", "a.dna.gz", "a.dna.gz.sig"]).unwrap();
+    assert_eq!(
+        verify.against,
+        hc::dna::VerifyAgainst::Signature("a.dna.gz.sig".into())
+    );
+
+    std::fs::remove_dir_all(dir).ok();
+}
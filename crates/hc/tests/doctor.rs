@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use holochain_hc as hc;
+use holochain_hc::doctor::Status;
+
+mod common;
+
+fn statuses_matching(report: &hc::doctor::Report, message: &str) -> Vec<Status> {
+    report
+        .checks
+        .iter()
+        .filter(|c| c.message.contains(message))
+        .map(|c| c.status)
+        .collect()
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn doctor_reports_problems() {
+    observability::test_run().ok();
+    let hc_dir = common::test_dir();
+    let setup = hc::generate::generate(None, None, None).unwrap();
+    let stale = hc_dir.join("deleted-setup");
+    hc::save::save(hc_dir.clone(), vec![setup.clone(), stale.clone()]).unwrap();
+    std::fs::write(hc_dir.join("broken.dna.gz"), b"not a dna").unwrap();
+
+    let report = hc::doctor::doctor(Path::new("not-a-holochain-binary"), hc_dir.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        statuses_matching(&report, "not-a-holochain-binary"),
+        vec![Status::Fail]
+    );
+    assert_eq!(
+        statuses_matching(&report, &format!("Setup 0 at {}", setup.display())),
+        vec![Status::Pass]
+    );
+    assert_eq!(
+        statuses_matching(&report, &format!("{} doesn't exist", stale.display())),
+        vec![Status::Warn]
+    );
+    assert_eq!(
+        statuses_matching(&report, "broken.dna.gz"),
+        vec![Status::Fail]
+    );
+    assert_eq!(report.failures(), 2);
+    // Every problem has a hint.
+    assert!(report
+        .checks
+        .iter()
+        .all(|c| (c.status == Status::Pass) == c.hint.is_none()));
+
+    std::fs::remove_dir_all(setup).ok();
+    std::fs::remove_dir_all(hc_dir).ok();
+}
@@ -0,0 +1,45 @@
+use holochain_hc as hc;
+use structopt::StructOpt;
+
+use hc::failures::FailureFlags;
+use hc::failures::FailurePolicy;
+use hc::failures::Failures;
+
+#[test]
+fn keep_going_lists_every_failure_at_the_end() {
+    let mut failures = Failures::new(FailurePolicy::KeepGoing);
+    failures.fail("Setup 0", anyhow::anyhow!("boom")).unwrap();
+    failures.fail("Setup 2", anyhow::anyhow!("bang")).unwrap();
+    assert_eq!(failures.failed(), ["Setup 0", "Setup 2"]);
+    let err = failures.finish("Call", 3).unwrap_err();
+    assert_eq!(err.to_string(), "Call failed on 2 of 3: Setup 0, Setup 2");
+
+    Failures::new(FailurePolicy::KeepGoing)
+        .finish("Call", 3)
+        .unwrap();
+}
+
+#[test]
+fn fail_fast_returns_the_first_failure() {
+    let mut failures = Failures::new(FailurePolicy::FailFast);
+    let err = failures
+        .fail("Setup 1", anyhow::anyhow!("boom"))
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Setup 1 failed");
+    assert_eq!(err.root_cause().to_string(), "boom");
+}
+
+#[test]
+fn the_flags_choose_a_policy_or_leave_the_default() {
+    let flags = |args: &[&str]| FailureFlags::from_iter_safe(std::iter::once(&"hc").chain(args));
+    assert_eq!(flags(&[]).unwrap().policy(), None);
+    assert_eq!(
+        flags(&["--fail-fast"]).unwrap().policy(),
+        Some(FailurePolicy::FailFast)
+    );
+    assert_eq!(
+        flags(&["--keep-going"]).unwrap().policy(),
+        Some(FailurePolicy::KeepGoing)
+    );
+    assert!(flags(&["--fail-fast", "--keep-going"]).is_err());
+}
@@ -0,0 +1,153 @@
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use holochain_hc as hc;
+use holochain_wasm_test_utils::TestWasm;
+
+mod common;
+
+/// Serve one response per connection in order and
+/// record the head of each request.
+fn serve(responses: Vec<Vec<u8>>) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+    std::thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0; 1];
+            while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                head.push(byte[0]);
+            }
+            recorded
+                .lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(&head).to_lowercase());
+            stream.write_all(&response).ok();
+        }
+    });
+    (url, requests)
+}
+
+fn response(status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n",
+        status,
+        body.len(),
+        headers
+    )
+    .into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+async fn dna_bytes() -> Vec<u8> {
+    common::test_dna(TestWasm::Foo)
+        .await
+        .to_file_content()
+        .await
+        .unwrap()
+}
+
+fn files_in(dir: &Path) -> Vec<PathBuf> {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect()
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn urls_are_downloaded_once_and_refreshed_with_their_etag() {
+    let bytes = dna_bytes().await;
+    let (base, requests) = serve(vec![
+        response("200 OK", "ETag: \"v1\"\r\n", &bytes),
+        response("304 Not Modified", "ETag: \"v1\"\r\n", &[]),
+    ]);
+    let url = format!("{}/releases/my-dna.dna.gz?download=1", base);
+    let cache = common::test_dir();
+
+    let dnas = hc::fetch::fetch_dnas(
+        vec![url.clone().into(), "local.dna.gz".into()],
+        &cache,
+        false,
+    )
+    .await
+    .unwrap();
+    let path = dnas[0].clone();
+    assert!(path.starts_with(&cache), "{}", path.display());
+    assert!(path.ends_with("my-dna.dna.gz"), "{}", path.display());
+    assert_eq!(std::fs::read(&path).unwrap(), bytes);
+    assert_eq!(dnas[1], PathBuf::from("local.dna.gz"));
+    assert_eq!(
+        hc::dna::parse_dnas(vec![path.clone()]).unwrap(),
+        vec![path.clone()]
+    );
+
+    // Running again uses the cache.
+    let again = hc::fetch::fetch(&url, &cache, false).await.unwrap();
+    assert_eq!(again, path);
+    assert_eq!(requests.lock().unwrap().len(), 1);
+
+    // Refreshing asks if the etag changed.
+    let refreshed = hc::fetch::fetch(&url, &cache, true).await.unwrap();
+    assert_eq!(refreshed, path);
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(
+        requests[1].contains("if-none-match: \"v1\""),
+        "{}",
+        requests[1]
+    );
+
+    std::fs::remove_dir_all(cache).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn failed_downloads_leave_nothing_in_the_cache() {
+    let bytes = dna_bytes().await;
+    let mut truncated = response("200 OK", "", &bytes);
+    truncated.truncate(truncated.len() - bytes.len() / 2);
+    let (base, _) = serve(vec![
+        response("500 Internal Server Error", "", &[]),
+        response("200 OK", "", b"not a dna"),
+        truncated,
+    ]);
+    let url = format!("{}/my-dna.dna.gz", base);
+    let cache = common::test_dir();
+
+    let err = hc::fetch::fetch(&url, &cache, false).await.unwrap_err();
+    assert!(err.to_string().contains("Failed to download"), "{}", err);
+    let err = hc::fetch::fetch(&url, &cache, false).await.unwrap_err();
+    assert!(err.to_string().contains("not a valid dna"), "{}", err);
+    let err = hc::fetch::fetch(&url, &cache, false).await.unwrap_err();
+    assert!(err.to_string().contains("Failed to download"), "{}", err);
+    assert_eq!(files_in(&cache), Vec::<PathBuf>::new());
+
+    // Nothing is listening any more.
+    let err = hc::fetch::fetch(&url, &cache, false).await.unwrap_err();
+    assert!(err.to_string().contains("Failed to download"), "{}", err);
+
+    std::fs::remove_dir_all(cache).ok();
+}
+
+#[test]
+fn only_http_urls_are_downloaded() {
+    assert!(hc::fetch::is_url(Path::new(
+        "https://example.com/my-dna.dna.gz"
+    )));
+    assert!(hc::fetch::is_url(Path::new(
+        "http://localhost:8000/my-dna.dna.gz"
+    )));
+    assert!(!hc::fetch::is_url(Path::new("./my-dna.dna.gz")));
+    assert!(!hc::fetch::is_url(Path::new(
+        "ftp://example.com/my-dna.dna.gz"
+    )));
+}
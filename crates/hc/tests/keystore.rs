@@ -0,0 +1,247 @@
+use std::path::Path;
+
+use holochain_hc as hc;
+
+use hc::passphrase::Passphrase;
+
+mod common;
+
+/// Write some files that look like a keystore.
+fn fake_keystore(keystore: &Path) {
+    std::fs::create_dir_all(keystore.join("store")).unwrap();
+    std::fs::write(keystore.join("store").join("keys.db"), b"secret keys").unwrap();
+    std::fs::write(keystore.join("config.yaml"), b"keystore: config").unwrap();
+    std::fs::write(keystore.join("pid"), b"1234").unwrap();
+}
+
+fn passphrase(passphrase: &str) -> Passphrase {
+    Passphrase::from(passphrase.to_string())
+}
+
+fn read(path: &Path) -> Vec<u8> {
+    std::fs::read(path).unwrap()
+}
+
+#[test]
+fn exported_keystores_import_with_the_passphrase() {
+    let dir = common::test_dir();
+    let keystore = dir.join("keystore");
+    fake_keystore(&keystore);
+
+    let exported = hc::keystore::export_keystore(&keystore, &passphrase("correct horse")).unwrap();
+    let secret = b"secret keys";
+    assert!(
+        !exported.windows(secret.len()).any(|w| w == secret),
+        "The export should be encrypted"
+    );
+
+    let err =
+        hc::keystore::import_keystore(&dir.join("other"), &exported, &passphrase("wrong"), false)
+            .err()
+            .unwrap();
+    assert!(err.to_string().contains("Wrong passphrase"), "{}", err);
+    let mut tampered = exported.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(hc::keystore::import_keystore(
+        &dir.join("other"),
+        &tampered,
+        &passphrase("correct horse"),
+        false
+    )
+    .is_err());
+
+    let imported = dir.join("imported");
+    let files =
+        hc::keystore::import_keystore(&imported, &exported, &passphrase("correct horse"), false)
+            .unwrap();
+    assert_eq!(files, 2);
+    assert_eq!(read(&imported.join("store").join("keys.db")), secret);
+    assert_eq!(read(&imported.join("config.yaml")), b"keystore: config");
+    // The pid of a keystore that was running isn't moved.
+    assert!(!imported.join("pid").exists());
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn existing_keys_are_only_replaced_with_force() {
+    let dir = common::test_dir();
+    let keystore = dir.join("keystore");
+    fake_keystore(&keystore);
+    let exported = hc::keystore::export_keystore(&keystore, &passphrase("passphrase")).unwrap();
+
+    let target = dir.join("target");
+    std::fs::create_dir_all(&target).unwrap();
+    std::fs::write(target.join("old-keys"), b"old").unwrap();
+    let err = hc::keystore::import_keystore(&target, &exported, &passphrase("passphrase"), false)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("--force"), "{}", err);
+    assert_eq!(read(&target.join("old-keys")), b"old");
+
+    hc::keystore::import_keystore(&target, &exported, &passphrase("passphrase"), true).unwrap();
+    assert!(!target.join("old-keys").exists());
+    assert_eq!(read(&target.join("config.yaml")), b"keystore: config");
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn keystores_need_a_passphrase() {
+    let dir = common::test_dir();
+    let file = dir.join("passphrase");
+    std::fs::write(&file, "from a file\n").unwrap();
+    let read_passphrase = |file: Option<&Path>| {
+        hc::passphrase::read_passphrase_with_env(file, hc::keystore::KEYSTORE_PASSPHRASE_ENV, false)
+    };
+    assert_eq!(
+        read_passphrase(Some(&file)).unwrap().as_bytes(),
+        b"from a file"
+    );
+
+    std::fs::write(&file, "\n").unwrap();
+    let err = read_passphrase(Some(&file)).err().unwrap();
+    assert!(err.to_string().contains("can't be empty"), "{}", err);
+    let err = read_passphrase(None).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains(hc::keystore::KEYSTORE_PASSPHRASE_ENV),
+        "{}",
+        err
+    );
+
+    let keystore = dir.join("keystore");
+    fake_keystore(&keystore);
+    assert!(hc::keystore::export_keystore(&keystore, &passphrase("")).is_err());
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn agents_move_between_setups() {
+    let from = hc::generate::generate(None, None, None).unwrap();
+    let to = hc::generate::generate(None, None, None).unwrap();
+    let from_keystore = hc::setups::keystore_path(&from).unwrap();
+    fake_keystore(&from_keystore);
+
+    let dir = common::test_dir();
+    let out = dir.join("agents.hckeys");
+    hc::calls::export_keystore(&from, &out, &passphrase("passphrase"))
+        .await
+        .unwrap();
+    let err = hc::calls::export_keystore(&from, &out, &passphrase("passphrase"))
+        .await
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("already exists"), "{}", err);
+
+    let files = hc::calls::import_keystore(&to, &out, &passphrase("passphrase"), false, false)
+        .await
+        .unwrap();
+    assert_eq!(files, 2);
+    let to_keystore = hc::setups::keystore_path(&to).unwrap();
+    assert_ne!(from_keystore, to_keystore);
+    assert_eq!(
+        read(&to_keystore.join("store").join("keys.db")),
+        b"secret keys"
+    );
+
+    std::fs::remove_dir_all(dir).ok();
+    std::fs::remove_dir_all(from).ok();
+    std::fs::remove_dir_all(to).ok();
+}
+
+#[test]
+fn shared_keystores_are_removed_with_their_last_setup() {
+    let hc_dir = common::test_dir();
+    let keystore = hc_dir.join("keys");
+    let setups: Vec<_> = (0..2)
+        .map(|_| hc::generate::generate(None, None, None).unwrap())
+        .collect();
+    for setup in &setups {
+        let shared = hc::keystore::share_keystore(setup, &keystore).unwrap();
+        assert_eq!(hc::setups::keystore_path(setup).unwrap(), shared);
+    }
+    let keystore = keystore.canonicalize().unwrap();
+    assert_eq!(hc::keystore::keystore_users(&keystore).unwrap().len(), 2);
+    fake_keystore(&keystore);
+    hc::save::save(hc_dir.clone(), setups.clone()).unwrap();
+
+    // The list of setups using the keystore isn't part of its keys.
+    let exported = hc::keystore::export_keystore(&keystore, &passphrase("passphrase")).unwrap();
+    let imported = hc_dir.join("imported");
+    assert_eq!(
+        hc::keystore::import_keystore(&imported, &exported, &passphrase("passphrase"), false)
+            .unwrap(),
+        2
+    );
+    assert!(!imported.join(hc::keystore::KEYSTORE_USERS_FILE).exists());
+
+    hc::save::clean(hc_dir.clone(), vec![0], None).unwrap();
+    assert!(!setups[0].exists());
+    assert!(keystore.join("config.yaml").exists());
+    assert_eq!(
+        hc::keystore::keystore_users(&keystore).unwrap(),
+        vec![setups[1].canonicalize().unwrap()]
+    );
+
+    hc::save::clean(hc_dir.clone(), Vec::new(), None).unwrap();
+    assert!(!setups[1].exists());
+    assert!(!keystore.exists());
+
+    std::fs::remove_dir_all(hc_dir).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn shared_keys_are_only_replaced_when_asked() {
+    let hc_dir = common::test_dir();
+    let keystore = hc_dir.join("keys");
+    let setups: Vec<_> = (0..2)
+        .map(|_| hc::generate::generate(None, None, None).unwrap())
+        .collect();
+    for setup in &setups {
+        hc::keystore::share_keystore(setup, &keystore).unwrap();
+    }
+    let keystore = keystore.canonicalize().unwrap();
+    fake_keystore(&keystore);
+    let other = hc_dir.join("other");
+    std::fs::create_dir_all(&other).unwrap();
+    std::fs::write(other.join("new-keys"), b"new").unwrap();
+    let out = hc_dir.join("agents.hckeys");
+    let exported = hc::keystore::export_keystore(&other, &passphrase("passphrase")).unwrap();
+    std::fs::write(&out, exported).unwrap();
+
+    let err = hc::calls::import_keystore(&setups[0], &out, &passphrase("passphrase"), true, false)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("is shared with setups"), "{}", err);
+    assert!(err.to_string().contains("--replace-shared"), "{}", err);
+    assert!(
+        err.to_string()
+            .contains(&setups[1].canonicalize().unwrap().display().to_string()),
+        "{}",
+        err
+    );
+    assert_eq!(read(&keystore.join("config.yaml")), b"keystore: config");
+
+    hc::calls::import_keystore(&setups[0], &out, &passphrase("passphrase"), true, true)
+        .await
+        .unwrap();
+    assert!(!keystore.join("config.yaml").exists());
+    assert_eq!(read(&keystore.join("new-keys")), b"new");
+    assert_eq!(hc::keystore::keystore_users(&keystore).unwrap().len(), 2);
+
+    // Replacing shared keys still needs --force.
+    use structopt::StructOpt;
+    assert!(hc::calls::ImportKeystore::from_iter_safe(&[
+        "import-keystore",
+        "agents.hckeys",
+        "--replace-shared",
+    ])
+    .is_err());
+
+    for setup in setups {
+        std::fs::remove_dir_all(setup).ok();
+    }
+    std::fs::remove_dir_all(hc_dir).ok();
+}
@@ -0,0 +1,43 @@
+use holochain_hc as hc;
+use holochain_wasm_test_utils::TestWasm;
+
+use hc::launch::LaunchOptions;
+use hc::AppCmdRunner;
+
+mod common;
+
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn launch_installs_for_each_agent() {
+    observability::test_run().ok();
+    let dna = common::test_dna(TestWasm::Foo).await;
+
+    let launched = hc::launch(LaunchOptions {
+        dnas: vec![dna.into()],
+        agents: 2,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let path = launched.path().to_path_buf();
+
+    assert_eq!(launched.cells.len(), 2);
+    assert_eq!(launched.cmd.list_cell_ids().await.unwrap().len(), 2);
+    assert_eq!(launched.app_ports.len(), 1);
+
+    let app = AppCmdRunner::try_new(launched.app_ports[0]).await.unwrap();
+    let output: String = app
+        .call_zome(
+            launched.cells[1][0].as_id().clone(),
+            TestWasm::Foo,
+            "foo",
+            None,
+            (),
+        )
+        .await
+        .unwrap();
+    assert_eq!(output, "foo");
+
+    launched.shutdown().await.unwrap();
+    assert!(!path.exists());
+}
@@ -0,0 +1,73 @@
+use holochain_hc as hc;
+
+use hc::logs::line_level;
+use hc::logs::LevelFilter;
+use hc::logs::LogLevel;
+use hc::logs::LogTail;
+
+#[test]
+fn levels_are_read_from_each_format() {
+    assert_eq!(
+        line_level("Jan 01 12:00:00.000  INFO holochain::conductor: started"),
+        Some(LogLevel::Info)
+    );
+    assert_eq!(
+        line_level("Jan 01 12:00:00.000 \u{1b}[33m WARN\u{1b}[0m kitsune_p2p: slow"),
+        Some(LogLevel::Warn)
+    );
+    assert_eq!(
+        line_level(r#"{"level":"ERROR","target":"holochain","fields":{}}"#),
+        Some(LogLevel::Error)
+    );
+    assert_eq!(line_level("    in holochain::conductor::handle"), None);
+    assert_eq!("Debug".parse::<LogLevel>().unwrap(), LogLevel::Debug);
+    assert!("loud".parse::<LogLevel>().is_err());
+}
+
+#[test]
+fn filtered_messages_are_dropped_with_their_other_lines() {
+    let mut filter = LevelFilter::new(Some(LogLevel::Warn));
+    let kept: Vec<_> = vec![
+        "12:00:00  INFO holochain: started",
+        "    in holochain::conductor",
+        "12:00:01  WARN holochain: slow",
+        "    in holochain::conductor",
+        "12:00:02 DEBUG holochain: detail",
+    ]
+    .into_iter()
+    .filter(|line| filter.keep(line))
+    .collect();
+    assert_eq!(
+        kept,
+        vec![
+            "12:00:01  WARN holochain: slow",
+            "    in holochain::conductor"
+        ]
+    );
+}
+
+#[test]
+fn tails_follow_new_lines_and_restarts() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    let path = dir.join("holochain-stdout.log");
+    std::fs::write(&path, "one\ntwo\nthree\nfou").unwrap();
+
+    let (mut tail, existing) = LogTail::open(path.clone(), 2).unwrap();
+    assert_eq!(existing, vec!["two", "three"]);
+
+    // The unfinished line is printed once it is finished.
+    std::fs::write(&path, "one\ntwo\nthree\nfour\nfive\n").unwrap();
+    assert_eq!(tail.read_new().unwrap(), vec!["four", "five"]);
+    assert!(tail.read_new().unwrap().is_empty());
+
+    // Running the conductor again recreates the file.
+    std::fs::write(&path, "again\n").unwrap();
+    assert_eq!(tail.read_new().unwrap(), vec!["again"]);
+
+    // The setup records where its logs are.
+    hc::logs::record_log_dir(&dir, &dir).unwrap();
+    let files = hc::logs::log_files(&dir).unwrap().unwrap();
+    assert_eq!(files.stdout, path);
+
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,61 @@
+use holochain_hc as hc;
+
+use hc::output::OutputFile;
+use hc::output::OutputFormat;
+
+#[test]
+fn the_format_is_chosen_by_the_extension() {
+    let format = |path: &str| OutputFormat::from_path(std::path::Path::new(path));
+    assert_eq!(format("cells.json"), OutputFormat::Json);
+    assert_eq!(format("cells.JSON"), OutputFormat::Json);
+    assert_eq!(format("cells.yaml"), OutputFormat::Yaml);
+    assert_eq!(format("cells.yml"), OutputFormat::Yaml);
+    assert_eq!(format("cells.txt"), OutputFormat::Text);
+    assert_eq!(format("cells"), OutputFormat::Text);
+}
+
+#[test]
+fn output_files_are_written_in_their_format() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    let write = |name: &str, data: &[&str]| {
+        let path = dir.join(name);
+        let file = OutputFile::create(&path).unwrap();
+        for data in data {
+            file.write(data);
+        }
+        file.finish().unwrap();
+        std::fs::read_to_string(path).unwrap()
+    };
+
+    // Colors are removed from text.
+    let text = write("out.txt", &["\u{1b}[32mCell\u{1b}[0m 1", "Cell 2"]);
+    assert_eq!(text, "Cell 1\nCell 2\n");
+
+    let json = write("one.json", &[r#"{"path": "setup-0"}"#]);
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json, serde_json::json!({"path": "setup-0"}));
+
+    // Several results are an array and text lines become strings.
+    let json = write("many.json", &["[1, 2]", "Cell 1\nCell 2"]);
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json, serde_json::json!([[1, 2], "Cell 1", "Cell 2"]));
+
+    let yaml = write("out.yaml", &[r#"{"running": 2}"#]);
+    let yaml: serde_json::Value = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(yaml, serde_json::json!({"running": 2}));
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn output_files_that_cant_be_created_are_an_error() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    let err = OutputFile::create(&dir.join("missing").join("out.json")).unwrap_err();
+    assert!(
+        err.to_string().contains("Failed to create output file"),
+        "{}",
+        err
+    );
+
+    std::fs::remove_dir_all(dir).ok();
+}
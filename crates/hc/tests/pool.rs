@@ -0,0 +1,231 @@
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use holochain_conductor_api::AdminRequest;
+use holochain_conductor_api::AdminResponse;
+use holochain_conductor_api::ConductorVersion;
+use holochain_conductor_api::CONDUCTOR_API_VERSION;
+use holochain_hc::RunnerPool;
+use holochain_websocket::websocket_bind;
+use holochain_websocket::WebsocketConfig;
+use holochain_websocket::WebsocketMessage;
+use holochain_websocket::WebsocketSender;
+use tokio::stream::StreamExt;
+use url2::url2;
+
+/// The server side of each connection to a mock admin interface
+/// so tests can see how many were opened and close them.
+type Connections = Arc<Mutex<Vec<WebsocketSender>>>;
+
+/// Bind a fake admin interface that answers the version
+/// handshake and `ListDnas`.
+/// Returns the port it is listening on.
+async fn mock_admin_interface() -> (u16, Connections) {
+    let mut server = websocket_bind(
+        url2!("ws://127.0.0.1:0"),
+        Arc::new(WebsocketConfig::default()),
+    )
+    .await
+    .unwrap();
+    let port = server.local_addr().port().expect("Listener has no port");
+    let connections = Connections::default();
+    let c = connections.clone();
+    tokio::task::spawn(async move {
+        while let Some(Ok((send, mut recv))) = server.next().await {
+            c.lock().unwrap().push(send);
+            tokio::task::spawn(async move {
+                while let Some(msg) = recv.next().await {
+                    if let WebsocketMessage::Request(data, respond) = msg {
+                        let request: AdminRequest = data.try_into().unwrap();
+                        let response = match request {
+                            AdminRequest::RequestVersion => {
+                                AdminResponse::VersionRequested(ConductorVersion {
+                                    holochain: "0.0.100".to_string(),
+                                    conductor_api: CONDUCTOR_API_VERSION.to_string(),
+                                })
+                            }
+                            AdminRequest::ListDnas => AdminResponse::DnasListed(Vec::new()),
+                            r => panic!("Unexpected request {:?}", r),
+                        };
+                        respond(response.try_into().unwrap()).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+    (port, connections)
+}
+
+fn opened(connections: &Connections) -> usize {
+    connections.lock().unwrap().len()
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn pooled_connections_are_reused() {
+    observability::test_run().ok();
+    let (port, connections) = mock_admin_interface().await;
+    let (other_port, other_connections) = mock_admin_interface().await;
+    let pool = RunnerPool::new();
+
+    let first = pool.get(port).await.unwrap();
+    assert!(!first.is_reused());
+    for _ in 0..10 {
+        let cmd = pool.get(port).await.unwrap();
+        assert!(cmd.is_reused());
+        assert_eq!(cmd.port(), port);
+        cmd.command(AdminRequest::ListDnas).await.unwrap();
+    }
+    // Every clone of the runner shares one connection.
+    assert_eq!(first.metrics().requests["ListDnas"].count, 10);
+    assert_eq!(opened(&connections), 1);
+
+    // Other ports get their own connection.
+    let other = pool.get(other_port).await.unwrap();
+    assert!(!other.is_reused());
+    assert_eq!(opened(&other_connections), 1);
+    let mut expected = vec![port, other_port];
+    expected.sort_unstable();
+    assert_eq!(pool.ports(), expected);
+
+    pool.evict(port);
+    assert_eq!(pool.ports(), vec![other_port]);
+    assert!(!pool.get(port).await.unwrap().is_reused());
+    assert_eq!(opened(&connections), 2);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn closed_connections_are_evicted() {
+    observability::test_run().ok();
+    let (port, connections) = mock_admin_interface().await;
+    let pool = RunnerPool::builder()
+        .health_check_after(Duration::from_secs(0))
+        .health_check_timeout(Duration::from_millis(500))
+        .build();
+
+    pool.get(port).await.unwrap();
+    assert!(pool.get(port).await.unwrap().is_reused());
+
+    // The conductor closes the connection.
+    let mut send = connections.lock().unwrap().pop().unwrap();
+    send.close(1000, "going away".to_string()).await.ok();
+    drop(send);
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+
+    let replacement = pool.get(port).await.unwrap();
+    assert!(!replacement.is_reused());
+    // The closed connection was taken out so this is the new one.
+    assert_eq!(opened(&connections), 1);
+    replacement
+        .command(AdminRequest::ListDnas)
+        .await
+        .expect("The replacement connection should work");
+    assert!(pool.get(port).await.unwrap().is_reused());
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn failed_connections_are_not_pooled() {
+    observability::test_run().ok();
+    let port = portpicker::pick_unused_port().expect("No ports free");
+    let pool = RunnerPool::new();
+    assert!(pool.get(port).await.is_err());
+    assert!(pool.ports().is_empty());
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn repl_calls_reuse_one_connection() {
+    observability::test_run().ok();
+    let (port, connections) = mock_admin_interface().await;
+    let pool = RunnerPool::new();
+    let mut input = String::from("# list the dnas ten times\n\n");
+    for _ in 0..10 {
+        input.push_str("list-dnas\n");
+    }
+    holochain_hc::repl::run_lines(&pool, "127.0.0.1", &[port], input.as_bytes(), false)
+        .await
+        .unwrap();
+    assert_eq!(opened(&connections), 1);
+    assert_eq!(
+        pool.get(port).await.unwrap().metrics().requests["ListDnas"].count,
+        10
+    );
+
+    // Bad lines are reported at the end without stopping the others.
+    let err = holochain_hc::repl::run_lines(
+        &pool,
+        "127.0.0.1",
+        &[port],
+        &b"not-a-call\nlist-dnas\nexit\nlist-dnas\n"[..],
+        false,
+    )
+    .await
+    .err()
+    .unwrap();
+    assert_eq!(err.to_string(), "1 of 2 calls failed");
+    assert_eq!(opened(&connections), 1);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn replayed_calls_are_compared_to_their_expected_responses() {
+    observability::test_run().ok();
+    let (port, connections) = mock_admin_interface().await;
+    let pool = RunnerPool::new();
+    let replay = r#"# recorded calls
+{"call": "list-dnas", "expected": "Dnas: []"}
+{"call": "list-dnas"}
+"#;
+    holochain_hc::repl::replay_lines(&pool, "127.0.0.1", &[port], replay.as_bytes(), &[])
+        .await
+        .unwrap();
+
+    // Every line is replayed and the ones that differ fail at the end.
+    let replay = r#"{"call": "list-dnas", "expected": "Dnas: [uhC0k]"}
+not json
+{"call": "list-dnas", "expected": "Dnas: []"}
+"#;
+    let err = holochain_hc::repl::replay_lines(&pool, "127.0.0.1", &[port], replay.as_bytes(), &[])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "2 of 3 replayed calls failed or didn't match"
+    );
+    assert_eq!(
+        pool.get(port).await.unwrap().metrics().requests["ListDnas"].count,
+        4
+    );
+    assert_eq!(opened(&connections), 1);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn connections_are_kept_per_host_and_port() {
+    observability::test_run().ok();
+    let (port, connections) = mock_admin_interface().await;
+    let pool = RunnerPool::new();
+    assert!(!pool.get(port).await.unwrap().is_reused());
+    // The default host is the same conductor.
+    let cmd = pool.get_on_host("127.0.0.1", port).await.unwrap();
+    assert!(cmd.is_reused());
+    assert_eq!(pool.addresses(), vec![("127.0.0.1".to_string(), port)]);
+    assert_eq!(opened(&connections), 1);
+
+    pool.evict_on_host("127.0.0.1", port);
+    assert!(pool.addresses().is_empty());
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn idle_connections_are_closed() {
+    observability::test_run().ok();
+    let (port, connections) = mock_admin_interface().await;
+    let pool = RunnerPool::builder()
+        .idle_timeout(Duration::from_millis(100))
+        .build();
+
+    drop(pool.get(port).await.unwrap());
+    tokio::time::delay_for(Duration::from_millis(400)).await;
+    assert!(pool.ports().is_empty());
+
+    assert!(!pool.get(port).await.unwrap().is_reused());
+    assert_eq!(opened(&connections), 2);
+}
@@ -0,0 +1,255 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use hc::AdminAddress;
+use hc::AdminRole;
+use hc::PortPool;
+use hc::PortTaken;
+use holochain_hc as hc;
+
+#[test]
+fn the_pool_never_hands_out_a_port_twice() {
+    let pool = Arc::new(PortPool::new(21000..=21999));
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                let owner = PathBuf::from(format!("setup-{}", i));
+                (0..20)
+                    .map(|_| pool.allocate(&owner).unwrap())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let ports: Vec<u16> = threads
+        .into_iter()
+        .flat_map(|t| t.join().unwrap())
+        .collect();
+    let distinct: HashSet<_> = ports.iter().collect();
+    assert_eq!(distinct.len(), 160);
+    assert!(ports.iter().all(|p| pool.range().contains(p)));
+
+    let lowest = *ports.iter().min().unwrap();
+    pool.release(lowest);
+    assert_eq!(pool.owner(lowest), None);
+    assert_eq!(pool.allocate(&PathBuf::from("again")).unwrap(), lowest);
+}
+
+#[test]
+fn an_exhausted_pool_is_an_error() {
+    let pool = PortPool::new(21500..=21500);
+    let owner = PathBuf::from("setup");
+    pool.reserve(21500, &PathBuf::from("other")).unwrap();
+    let err = pool.allocate(&owner).err().unwrap();
+    assert!(err.to_string().contains("No free ports left"), "{}", err);
+}
+
+#[test]
+fn forced_ports_are_reserved_for_one_setup() {
+    let a = hc::generate::generate(None, None, None).unwrap();
+    let b = hc::generate::generate(None, None, None).unwrap();
+
+    hc::force_admin_port(a.clone(), 39123).unwrap();
+    // Forcing it again on the same setup is fine.
+    hc::force_admin_port(a.clone(), 39123).unwrap();
+    let err = hc::force_admin_port(b.clone(), 39123).err().unwrap();
+    let taken = err.downcast_ref::<PortTaken>().unwrap();
+    assert_eq!(taken.owner, a);
+    assert_eq!(
+        hc::get_admin_address(b.clone()).unwrap(),
+        Some(AdminAddress::Port(0)),
+        "The config shouldn't change when the port is taken"
+    );
+
+    std::fs::remove_dir_all(a).ok();
+    std::fs::remove_dir_all(b).ok();
+}
+
+#[test]
+fn setups_generated_together_get_distinct_pooled_ports() {
+    let pool = Arc::new(PortPool::new(22000..=22999));
+    let threads: Vec<_> = (0..20)
+        .map(|_| {
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                let path = hc::generate::generate(None, None, None).unwrap();
+                let port = hc::pooled_admin_port(path.clone(), &pool).unwrap();
+                (path, port)
+            })
+        })
+        .collect();
+    let setups: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+    let ports: HashSet<_> = setups.iter().map(|(_, port)| *port).collect();
+    assert_eq!(ports.len(), 20);
+    for (path, port) in setups {
+        assert_eq!(
+            hc::get_admin_address(path.clone()).unwrap(),
+            Some(AdminAddress::Port(port))
+        );
+        assert_eq!(pool.owner(port), Some(path.clone()));
+        std::fs::remove_dir_all(path).ok();
+    }
+}
+
+/// Generating doesn't choose ports so setups generated
+/// at the same time leave their admin ports to the conductor.
+#[test]
+fn setups_generated_together_let_the_conductor_choose_the_port() {
+    let threads: Vec<_> = (0..20)
+        .map(|_| std::thread::spawn(|| hc::generate::generate(None, None, None).unwrap()))
+        .collect();
+    for path in threads.into_iter().map(|t| t.join().unwrap()) {
+        assert_eq!(
+            hc::get_admin_address(path.clone()).unwrap(),
+            Some(AdminAddress::Port(0))
+        );
+        std::fs::remove_dir_all(path).ok();
+    }
+}
+
+#[test]
+fn setups_without_a_config_are_an_error() {
+    let pool = PortPool::new(22500..=22500);
+    let path = hc::generate::generate_directory(None, None).unwrap();
+    let err = hc::pooled_admin_port(path.clone(), &pool).unwrap_err();
+    assert!(err.to_string().contains("Failed to find config"), "{}", err);
+    // A failed setup doesn't keep the port.
+    assert_eq!(pool.owner(22500), None);
+    let err = hc::use_admin_socket(path.clone()).unwrap_err();
+    assert!(err.to_string().contains("Failed to find config"), "{}", err);
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[test]
+fn port_ranges_parse() {
+    assert_eq!(hc::parse_port_range("9000-9100").unwrap(), 9000..=9100);
+    assert_eq!(hc::parse_port_range(" 9000 - 9000").unwrap(), 9000..=9000);
+    assert!(hc::parse_port_range("9000").is_err());
+    assert!(hc::parse_port_range("9100-9000").is_err());
+    assert!(hc::parse_port_range("0-10").is_err());
+}
+
+#[test]
+fn admin_ports_are_read_from_the_config() {
+    let path = hc::generate::generate(None, None, None).unwrap();
+    // Generated setups let the conductor pick the port.
+    assert_eq!(hc::admin_port_from_config(&path).unwrap(), None);
+
+    hc::force_admin_port(path.clone(), 39321).unwrap();
+    assert_eq!(hc::admin_port_from_config(&path).unwrap(), Some(39321));
+
+    #[cfg(unix)]
+    {
+        hc::use_admin_socket(path.clone()).unwrap();
+        assert_eq!(hc::admin_port_from_config(&path).unwrap(), None);
+    }
+
+    let config_path = path.join(hc::config::CONDUCTOR_CONFIG);
+    std::fs::write(&config_path, "admin_interfaces: not a list").unwrap();
+    let err = hc::admin_port_from_config(&path).err().unwrap();
+    assert!(
+        err.to_string().contains(&config_path.display().to_string()),
+        "{}",
+        err
+    );
+
+    std::fs::remove_file(&config_path).unwrap();
+    let err = hc::admin_port_from_config(&path).err().unwrap();
+    assert!(err.to_string().contains("Failed to read"), "{}", err);
+
+    PortPool::global().release(39321);
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[test]
+fn admin_hosts_follow_the_bind_address() {
+    let path = hc::generate::generate(None, None, None).unwrap();
+    assert_eq!(
+        hc::admin_host_from_config(&path).unwrap().as_deref(),
+        Some(hc::DEFAULT_HOST)
+    );
+
+    hc::config::use_interface_address(path.clone(), "::1".parse().unwrap()).unwrap();
+    assert_eq!(
+        hc::admin_host_from_config(&path).unwrap().as_deref(),
+        Some("::1")
+    );
+    // Every address includes the loopback of the same family.
+    hc::config::use_interface_address(path.clone(), "::".parse().unwrap()).unwrap();
+    assert_eq!(
+        hc::admin_host_from_config(&path).unwrap().as_deref(),
+        Some(hc::DEFAULT_HOST_V6)
+    );
+
+    #[cfg(unix)]
+    {
+        hc::use_admin_socket(path.clone()).unwrap();
+        assert_eq!(hc::admin_host_from_config(&path).unwrap(), None);
+    }
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+/// A setup with an admin interface for a dashboard
+/// before the one hc uses.
+fn setup_with_two_admin_interfaces() -> PathBuf {
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let mut config = hc::config::read_config(path.clone()).unwrap().unwrap();
+    let hc_interface = config.admin_interfaces.take().unwrap().remove(0);
+    let mut external = hc_interface.clone();
+    external.driver = holochain_conductor_api::InterfaceDriver::Websocket { port: 9200 };
+    external.address = Some("172.17.0.1".parse().unwrap());
+    config.admin_interfaces = Some(vec![external, hc_interface]);
+    hc::config::write_config(path.clone(), &config);
+    path
+}
+
+#[test]
+fn hc_uses_the_admin_interface_on_the_loopback() {
+    let path = setup_with_two_admin_interfaces();
+    hc::force_admin_port(path.clone(), 39322).unwrap();
+
+    let config = hc::config::read_config(path.clone()).unwrap().unwrap();
+    assert_eq!(hc::hc_admin_interface(&config).unwrap().address, None);
+    assert_eq!(hc::admin_port_from_config(&path).unwrap(), Some(39322));
+    assert_eq!(
+        hc::admin_host_from_config(&path).unwrap().as_deref(),
+        Some(hc::DEFAULT_HOST)
+    );
+
+    // Without a run they are listed with the ports in the config.
+    let interfaces = hc::get_admin_interfaces(&path).unwrap();
+    let listed: Vec<_> = interfaces.iter().map(|i| (i.role, i.to_string())).collect();
+    assert_eq!(
+        listed,
+        vec![
+            (AdminRole::External, "172.17.0.1:9200".to_string()),
+            (AdminRole::Hc, "port 39322".to_string()),
+        ]
+    );
+
+    PortPool::global().release(39322);
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn admin_interfaces_are_reachable_while_something_listens() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let interface = hc::AdminInterface {
+        role: AdminRole::Hc,
+        host: Some("127.0.0.1".parse().unwrap()),
+        address: AdminAddress::Port(port),
+    };
+    let timeout = std::time::Duration::from_secs(2);
+    assert!(hc::is_reachable(&interface, timeout).await);
+
+    drop(listener);
+    assert!(!hc::is_reachable(&interface, timeout).await);
+    let unbound = hc::AdminInterface {
+        address: AdminAddress::Port(0),
+        ..interface
+    };
+    assert!(!hc::is_reachable(&unbound, timeout).await);
+}
@@ -0,0 +1,115 @@
+use holochain_hc as hc;
+use structopt::StructOpt;
+
+use hc::cmds::Create;
+use hc::project::ProjectConfig;
+
+const PRESETS: &str = r#"
+root = "chains"
+
+[presets.quic-cluster]
+num_conductors = 3
+app_id = "my-app"
+dnas = ["my-app.dna.gz", "https://example.com/other.dna.gz"]
+properties = "properties.json"
+zome_call_metered = true
+bind = "::1"
+keystore = "shared:keys"
+network = { transport = "quic", bootstrap = "https://bootstrap.holo.host" }
+config_overrides = "overrides.yaml"
+
+[presets.quic-cluster.config]
+signing_service_uri = "http://localhost:1111"
+encryption_service_uri = "http://localhost:2222"
+
+[presets.offline-test]
+network = { transport = "mem" }
+"#;
+
+fn gen(args: &[&str]) -> Create {
+    Create::from_iter_safe(std::iter::once(&"gen").chain(args)).unwrap()
+}
+
+#[test]
+fn presets_fill_in_the_flags_that_were_not_passed() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    let path = dir.join(hc::project::PROJECT_CONFIG);
+    std::fs::write(&path, PRESETS).unwrap();
+    std::fs::write(
+        dir.join("overrides.yaml"),
+        "encryption_service_uri: \"http://localhost:3333\"\n\
+        decryption_service_uri: \"http://localhost:4444\"\n",
+    )
+    .unwrap();
+    let project = ProjectConfig::load(&path).unwrap();
+
+    let preset = project.preset("quic-cluster").unwrap();
+    assert_eq!(preset.num_conductors, Some(3));
+    // URLs aren't paths in the project.
+    assert_eq!(
+        preset.dnas,
+        vec![
+            dir.join("my-app.dna.gz"),
+            "https://example.com/other.dna.gz".into()
+        ]
+    );
+
+    let mut create = gen(&["--preset", "quic-cluster"]);
+    preset.apply(&mut create).unwrap();
+    assert_eq!(create.app_id, "my-app");
+    assert!(create.zome_call_metered);
+    assert_eq!(create.properties, Some(dir.join("properties.json")));
+    assert!(create.network.is_some());
+    assert_eq!(
+        create.keystore_kind(),
+        hc::keystore::KeystoreKind::Shared(dir.join("keys"))
+    );
+
+    // The preset's config, then its overrides file, then the flags.
+    let mut create = gen(&[
+        "--preset",
+        "quic-cluster",
+        "-a",
+        "other",
+        "--bind",
+        "127.0.0.1",
+    ]);
+    preset.apply(&mut create).unwrap();
+    assert_eq!(create.app_id, "other");
+    let spec = create.config_spec().unwrap();
+    assert_eq!(spec.interface_address, Some("127.0.0.1".parse().unwrap()));
+    assert_eq!(
+        spec.signing_service_uri.as_deref(),
+        Some("http://localhost:1111")
+    );
+    assert_eq!(
+        spec.encryption_service_uri.as_deref(),
+        Some("http://localhost:3333")
+    );
+    assert_eq!(
+        spec.decryption_service_uri.as_deref(),
+        Some("http://localhost:4444")
+    );
+    assert_eq!(spec.zome_call_metering, Some(true));
+
+    let err = project.preset("cluster").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("The presets are: offline-test, quic-cluster"),
+        "{}",
+        err
+    );
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn presets_with_unknown_options_are_an_error() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    let path = dir.join(hc::project::PROJECT_CONFIG);
+    std::fs::write(&path, "[presets.typo]\nnum_conductor = 3\n").unwrap();
+    let err = ProjectConfig::load(&path).unwrap_err();
+    assert!(err.to_string().contains("num_conductor"), "{}", err);
+
+    std::fs::remove_dir_all(dir).ok();
+}
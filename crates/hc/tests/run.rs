@@ -0,0 +1,729 @@
+#![cfg(unix)]
+use assert_cmd::prelude::*;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use holochain_conductor_api::config::conductor::PassphraseServiceConfig;
+use holochain_conductor_api::AdminInterfaceConfig;
+use holochain_conductor_api::InterfaceDriver;
+use holochain_hc as hc;
+use holochain_hc::logs::read_conductor_log;
+use holochain_hc::logs::ConductorLog;
+use holochain_hc::logs::LogFormat;
+use holochain_hc::passphrase::read_passphrase;
+use holochain_hc::passphrase::Passphrase;
+use holochain_hc::passphrase::PASSPHRASE_ENV;
+use holochain_hc::run::merge_env;
+use holochain_hc::run::parse_env_var;
+use holochain_hc::run::read_env_file;
+use holochain_hc::run::spawn;
+use holochain_hc::run::RunConfig;
+use holochain_hc::run::Stagger;
+use holochain_hc::run::StartupWave;
+use structopt::StructOpt;
+
+/// Write a script that prints the setup lines
+/// like holochain does and then keeps running.
+fn fake_holochain(dir: &Path, port: u16) -> PathBuf {
+    fake_script(
+        dir,
+        &format!(
+            "echo '###HOLOCHAIN_SETUP###'\n\
+            echo '###ADMIN_PORT:{}###'\n\
+            echo '###HOLOCHAIN_SETUP_END###'\n\
+            echo 'Conductor ready.'\n\
+            echo 'Something went wrong.' >&2\n\
+            echo \"HC_TEST_VAR=$HC_TEST_VAR\" >&2\n\
+            exec sleep 30\n",
+            port
+        ),
+    )
+}
+
+fn fake_script(dir: &Path, body: &str) -> PathBuf {
+    let path = dir.join("fake-holochain");
+    std::fs::write(&path, format!("#!/bin/sh\n{}", body)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn spawn_captures_logs_and_can_be_killed() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let holochain_path = fake_holochain(&path, 4321);
+
+    let mut holochain = spawn(RunConfig {
+        holochain_path,
+        env: vec![("HC_TEST_VAR".to_string(), "set".to_string())],
+        log_dir: Some(path.join("logs")),
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .unwrap();
+    assert_eq!(holochain.admin_port(), 4321);
+    assert_eq!(holochain.setup_path(), path.as_path());
+
+    let logs = holochain.logs().unwrap().clone();
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+    let stdout = std::fs::read_to_string(&logs.stdout).unwrap();
+    let stderr = std::fs::read_to_string(&logs.stderr).unwrap();
+    // The setup lines are consumed by hc.
+    assert_eq!(stdout, "Conductor ready.\n");
+    assert_eq!(stderr, "Something went wrong.\nHC_TEST_VAR=set\n");
+
+    holochain.kill().unwrap();
+    let status = tokio::time::timeout(Duration::from_secs(5), holochain.wait())
+        .await
+        .expect("Conductor didn't exit after kill")
+        .unwrap();
+    assert!(!status.success());
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn detached_conductors_keep_running() {
+    use nix::sys::signal::kill;
+    use nix::sys::signal::Signal;
+    use nix::unistd::Pid;
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let holochain_path = fake_holochain(&path, 4331);
+
+    let holochain = spawn(RunConfig {
+        holochain_path,
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .unwrap();
+    let child = holochain.detach();
+    let pid = Pid::from_raw(child.id() as i32);
+    drop(child);
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+    assert!(
+        kill(pid, None).is_ok(),
+        "The detached conductor was stopped"
+    );
+
+    kill(pid, Signal::SIGKILL).unwrap();
+    std::fs::remove_dir_all(path).ok();
+}
+
+/// `stop` sends `SIGTERM` so the conductor can exit cleanly.
+#[tokio::test(threaded_scheduler)]
+async fn stop_asks_the_conductor_to_exit() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let holochain_path = fake_script(
+        &path,
+        "echo '###HOLOCHAIN_SETUP###'\n\
+        echo '###ADMIN_PORT:4332###'\n\
+        echo '###HOLOCHAIN_SETUP_END###'\n\
+        trap 'exit 0' TERM\n\
+        while true; do sleep 0.1; done\n",
+    );
+
+    let holochain = spawn(RunConfig {
+        holochain_path,
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .unwrap();
+    let status = holochain.stop(Duration::from_secs(5)).await.unwrap();
+    assert!(status.success(), "{:?}", status);
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn stop_kills_conductors_that_ignore_it() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let holochain_path = fake_script(
+        &path,
+        "echo '###HOLOCHAIN_SETUP###'\n\
+        echo '###ADMIN_PORT:4333###'\n\
+        echo '###HOLOCHAIN_SETUP_END###'\n\
+        trap '' TERM\n\
+        exec sleep 30\n",
+    );
+
+    let holochain = spawn(RunConfig {
+        holochain_path,
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .unwrap();
+    let start = std::time::Instant::now();
+    let status = holochain.stop(Duration::from_millis(200)).await.unwrap();
+    assert!(!status.success());
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+/// The log filter replaces the `RUST_LOG` the conductor would get,
+/// the format is passed to `--structured` and both are recorded.
+#[tokio::test(threaded_scheduler)]
+async fn conductor_log_settings_are_passed_and_recorded() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let holochain_path = fake_script(
+        &path,
+        "echo '###HOLOCHAIN_SETUP###'\n\
+        echo '###ADMIN_PORT:4322###'\n\
+        echo '###HOLOCHAIN_SETUP_END###'\n\
+        echo \"$1 $2 RUST_LOG=$RUST_LOG\" >&2\n\
+        exec sleep 30\n",
+    );
+    let run = |conductor_log| {
+        spawn(RunConfig {
+            holochain_path: holochain_path.clone(),
+            env: vec![("RUST_LOG".to_string(), "debug".to_string())],
+            log_dir: Some(path.join("logs")),
+            conductor_log,
+            ..RunConfig::new(path.clone())
+        })
+    };
+    let stderr =
+        || std::fs::read_to_string(path.join("logs").join("holochain-stderr.log")).unwrap();
+
+    let log = ConductorLog {
+        filter: Some("warn".to_string()),
+        format: Some(LogFormat::Json),
+    };
+    let holochain = run(log.clone()).await.unwrap();
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+    assert_eq!(stderr(), "--structured Json RUST_LOG=warn\n");
+    assert_eq!(read_conductor_log(&path).unwrap(), Some(log.clone()));
+    assert_eq!(log.to_string(), "filter warn, json format");
+    drop(holochain);
+
+    // Without a filter the recorded one is the `RUST_LOG` the conductor got.
+    let holochain = run(ConductorLog::default()).await.unwrap();
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+    assert_eq!(stderr(), "--structured --config-path RUST_LOG=debug\n");
+    assert_eq!(
+        read_conductor_log(&path).unwrap(),
+        Some(ConductorLog {
+            filter: Some("debug".to_string()),
+            format: None,
+        })
+    );
+    drop(holochain);
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+/// Write a script that reports the admin port from
+/// the config it is started with.
+fn fake_holochain_reading_config(dir: &Path) -> PathBuf {
+    fake_script(
+        dir,
+        "port=$(grep -o 'port: [0-9]*' \"$3\" | head -n 1 | grep -o '[0-9]*')\n\
+        echo '###HOLOCHAIN_SETUP###'\n\
+        echo \"###ADMIN_PORT:$port###\"\n\
+        echo '###HOLOCHAIN_SETUP_END###'\n\
+        exec sleep 30\n",
+    )
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn forced_admin_ports_are_per_setup() {
+    observability::test_run().ok();
+    let first = hc::generate::generate(None, None, None).unwrap();
+    let second = hc::generate::generate(None, None, None).unwrap();
+    let spawn_forced = |path: PathBuf, port| {
+        let holochain_path = fake_holochain_reading_config(&path);
+        spawn(RunConfig {
+            holochain_path,
+            force_admin_port: Some(port),
+            ..RunConfig::new(path)
+        })
+    };
+
+    let (a, b) = futures::future::join(
+        spawn_forced(first.clone(), 41001),
+        spawn_forced(second.clone(), 41002),
+    )
+    .await;
+    let (a, b) = (a.unwrap(), b.unwrap());
+    assert_eq!(a.admin_port(), 41001);
+    assert_eq!(b.admin_port(), 41002);
+    // Each setup keeps its own port and the origins it allows.
+    for (path, port) in &[(&first, 41001), (&second, 41002)] {
+        let config = hc::config::read_config(path.to_path_buf())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            config.admin_interfaces.unwrap()[0],
+            AdminInterfaceConfig {
+                driver: InterfaceDriver::Websocket { port: *port },
+                allowed_origins: Some(vec![hc::DEFAULT_ORIGIN.to_string()]),
+                address: None,
+            }
+        );
+    }
+
+    drop((a, b));
+    std::fs::remove_dir_all(first).ok();
+    std::fs::remove_dir_all(second).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn spawn_records_every_admin_interface() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let mut config = hc::config::read_config(path.clone()).unwrap().unwrap();
+    let hc_interface = config.admin_interfaces.take().unwrap().remove(0);
+    let external = AdminInterfaceConfig {
+        driver: InterfaceDriver::Websocket { port: 0 },
+        address: Some("172.17.0.1".parse().unwrap()),
+        ..hc_interface.clone()
+    };
+    config.admin_interfaces = Some(vec![external, hc_interface]);
+    hc::config::write_config(path.clone(), &config);
+    // The ports are reported in the order of the config.
+    let holochain_path = fake_script(
+        &path,
+        "echo '###HOLOCHAIN_SETUP###'\n\
+        echo '###ADMIN_PORT:41010###'\n\
+        echo '###ADMIN_PORT:41011###'\n\
+        echo '###HOLOCHAIN_SETUP_END###'\n\
+        exec sleep 30\n",
+    );
+
+    let holochain = spawn(RunConfig {
+        holochain_path,
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .unwrap();
+    // hc connects to the one on the loopback.
+    assert_eq!(holochain.admin_port(), 41011);
+    let recorded = std::fs::read_to_string(path.join(hc::ADMIN_INTERFACES_FILE)).unwrap();
+    assert_eq!(recorded, "external 172.17.0.1:41010\nhc port 41011\n");
+    let interfaces = hc::get_admin_interfaces(&path).unwrap();
+    assert_eq!(interfaces[0].role, hc::AdminRole::External);
+    assert_eq!(interfaces[1].address, hc::AdminAddress::Port(41011));
+
+    drop(holochain);
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn spawn_keeps_the_admin_socket() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let socket = hc::use_admin_socket(path.clone()).unwrap();
+    let holochain_path = fake_script(
+        &path,
+        &format!(
+            "echo '###HOLOCHAIN_SETUP###'\n\
+            echo '###ADMIN_SOCKET:{}###'\n\
+            echo '###HOLOCHAIN_SETUP_END###'\n\
+            exec sleep 30\n",
+            socket.display()
+        ),
+    );
+
+    let holochain = spawn(RunConfig {
+        holochain_path,
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .unwrap();
+    assert_eq!(
+        holochain.admin_address(),
+        &hc::AdminAddress::Socket(socket.clone())
+    );
+    assert_eq!(holochain.admin_port(), 0);
+    // Running doesn't swap the socket for a random port
+    // and swapping the port for the socket keeps the origins.
+    let config = hc::config::read_config(path.clone()).unwrap().unwrap();
+    assert_eq!(
+        config.admin_interfaces.unwrap()[0],
+        AdminInterfaceConfig {
+            driver: InterfaceDriver::UnixSocket { path: socket },
+            allowed_origins: Some(vec![hc::DEFAULT_ORIGIN.to_string()]),
+            address: None,
+        }
+    );
+
+    drop(holochain);
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn a_conductor_that_cant_bind_a_forced_port_is_an_error() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let holochain_path = fake_script(&path, "echo 'Address in use' >&2\nexit 1\n");
+
+    let result = spawn(RunConfig {
+        holochain_path,
+        force_admin_port: Some(41003),
+        ..RunConfig::new(path.clone())
+    })
+    .await;
+    let err = result
+        .err()
+        .expect("Started without binding the admin port");
+    assert!(
+        err.to_string()
+            .contains("Is the forced admin port 41003 already in use?"),
+        "{}",
+        err
+    );
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn forced_admin_ports_that_are_bound_are_an_error() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let holochain_path = fake_holochain_reading_config(&path);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let err = spawn(RunConfig {
+        holochain_path: holochain_path.clone(),
+        force_admin_port: Some(port),
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .err()
+    .expect("Started on a port that is bound");
+    assert!(err.to_string().contains("already bound"), "{}", err);
+
+    drop(listener);
+    let holochain = spawn(RunConfig {
+        holochain_path,
+        force_admin_port: Some(port),
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .unwrap();
+    assert_eq!(holochain.admin_port(), port);
+
+    drop(holochain);
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn spawn_times_out_without_an_admin_port() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let holochain_path = fake_script(&path, "exec sleep 30\n");
+
+    let start = std::time::Instant::now();
+    let result = spawn(RunConfig {
+        holochain_path,
+        startup_timeout: Duration::from_millis(200),
+        ..RunConfig::new(path.clone())
+    })
+    .await;
+    let err = result.err().expect("Started without an admin port");
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(
+        err.to_string().contains("didn't report its admin port"),
+        "{}",
+        err
+    );
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn connect_errors_say_if_the_conductor_is_alive() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let port = portpicker::pick_unused_port().expect("No ports free");
+
+    // Reports a port that nothing listens on and keeps running.
+    let holochain = spawn(RunConfig {
+        holochain_path: fake_holochain(&path, port),
+        connect_deadline: Duration::from_millis(300),
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .unwrap();
+    let err = format!("{:#}", holochain.connect().await.err().unwrap());
+    assert!(err.contains("is still running"), "{}", err);
+    assert!(err.contains("attempts over"), "{}", err);
+    drop(holochain);
+
+    // Reports a port and then exits.
+    let holochain_path = fake_script(
+        &path,
+        &format!(
+            "echo '###HOLOCHAIN_SETUP###'\n\
+            echo '###ADMIN_PORT:{}###'\n\
+            echo '###HOLOCHAIN_SETUP_END###'\n\
+            sleep 0.1\n\
+            exit 1\n",
+            port
+        ),
+    );
+    let holochain = spawn(RunConfig {
+        holochain_path,
+        connect_deadline: Duration::from_secs(1),
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .unwrap();
+    let err = format!("{:#}", holochain.connect().await.err().unwrap());
+    assert!(err.contains("has exited"), "{}", err);
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[test]
+fn env_files_are_read_like_dotenv() {
+    let path = std::env::temp_dir().join(format!("hc-env-file-{}", std::process::id()));
+    std::fs::write(
+        &path,
+        "# Logging\n\
+        RUST_LOG=debug\n\
+        \n\
+        export HC_QUOTED=\"a b\"\n\
+        HC_SINGLE='c'\n\
+        HC_EMPTY=\n",
+    )
+    .unwrap();
+    let env = read_env_file(&path).unwrap();
+    assert_eq!(
+        env,
+        vec![
+            ("RUST_LOG".to_string(), "debug".to_string()),
+            ("HC_QUOTED".to_string(), "a b".to_string()),
+            ("HC_SINGLE".to_string(), "c".to_string()),
+            ("HC_EMPTY".to_string(), "".to_string()),
+        ]
+    );
+
+    std::fs::write(&path, "RUST_LOG=debug\nnot a variable\n").unwrap();
+    let err = read_env_file(&path).unwrap_err().to_string();
+    assert!(err.contains("Line 2"), "{}", err);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn passed_env_vars_win_over_the_file() {
+    assert_eq!(
+        parse_env_var("RUST_LOG=info,kitsune=debug").unwrap(),
+        ("RUST_LOG".to_string(), "info,kitsune=debug".to_string())
+    );
+    assert!(parse_env_var("=debug").is_err());
+    assert!(parse_env_var("RUST_LOG").is_err());
+
+    let file = vec![
+        ("RUST_LOG".to_string(), "info".to_string()),
+        ("HC_OTHER".to_string(), "1".to_string()),
+    ];
+    let passed = vec![("RUST_LOG".to_string(), "debug".to_string())];
+    assert_eq!(
+        merge_env(file, passed),
+        vec![
+            ("HC_OTHER".to_string(), "1".to_string()),
+            ("RUST_LOG".to_string(), "debug".to_string()),
+        ]
+    );
+}
+
+/// Make the setup's conductor read its keystore passphrase from stdin.
+fn ask_for_passphrase(path: &Path) {
+    let spec = hc::config::ConductorConfigSpec {
+        passphrase_service: Some(PassphraseServiceConfig::Cmd),
+        ..Default::default()
+    };
+    hc::config::apply_overrides(path.to_path_buf(), spec).unwrap();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn the_passphrase_is_written_to_the_conductor() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    ask_for_passphrase(&path);
+    let holochain_path = fake_script(
+        &path,
+        "read passphrase\n\
+        echo '###HOLOCHAIN_SETUP###'\n\
+        echo '###ADMIN_PORT:4322###'\n\
+        echo '###HOLOCHAIN_SETUP_END###'\n\
+        echo \"passphrase=$passphrase\" >&2\n\
+        exec sleep 30\n",
+    );
+
+    let holochain = spawn(RunConfig {
+        holochain_path,
+        log_dir: Some(path.join("logs")),
+        passphrase: Some(Passphrase::from("hunter2".to_string())),
+        ..RunConfig::new(path.clone())
+    })
+    .await
+    .unwrap();
+    assert_eq!(holochain.admin_port(), 4322);
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+    let stderr = std::fs::read_to_string(&holochain.logs().unwrap().stderr).unwrap();
+    assert_eq!(stderr, "passphrase=hunter2\n");
+
+    drop(holochain);
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn passphrases_come_from_the_file_then_the_env() {
+    observability::test_run().ok();
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let file = path.join("passphrase");
+    std::fs::write(&file, "from-file\n").unwrap();
+
+    std::env::set_var(PASSPHRASE_ENV, "from-env");
+    let passphrase = read_passphrase(Some(&file), false).unwrap();
+    assert_eq!(passphrase.as_bytes(), b"from-file");
+    assert_eq!(format!("{:?}", passphrase), "Passphrase(..)");
+    let passphrase = read_passphrase(None, false).unwrap();
+    assert_eq!(passphrase.as_bytes(), b"from-env");
+
+    // Without a terminal or a passphrase starting fails
+    // instead of leaving the conductor waiting on stdin.
+    std::env::remove_var(PASSPHRASE_ENV);
+    let err = read_passphrase(None, false).unwrap_err();
+    assert!(err.to_string().contains(PASSPHRASE_ENV), "{}", err);
+    ask_for_passphrase(&path);
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        spawn(RunConfig {
+            holochain_path: fake_script(&path, "read passphrase\nexec sleep 30\n"),
+            ..RunConfig::new(path.clone())
+        }),
+    )
+    .await
+    .expect("Waited for a passphrase that can't come");
+    let err = result.err().expect("Started without a passphrase");
+    assert!(
+        format!("{:?}", err).contains("--passphrase-file"),
+        "{:?}",
+        err
+    );
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[test]
+fn the_deadline_stops_hc_and_its_conductors() {
+    let path = hc::generate::generate(None, None, None).unwrap();
+    let holochain_path = fake_script(
+        &path,
+        "echo $$ > conductor-pid\n\
+        echo '###HOLOCHAIN_SETUP###'\n\
+        echo '###ADMIN_PORT:4323###'\n\
+        echo '###HOLOCHAIN_SETUP_END###'\n\
+        exec sleep 30\n",
+    );
+    let started = std::time::Instant::now();
+    let output = std::process::Command::cargo_bin("hc")
+        .unwrap()
+        .current_dir(&path)
+        .env(hc::defaults::USER_CONFIG_ENV, path.join("missing.toml"))
+        .env_remove(hc::defaults::HOLOCHAIN_PATH_ENV)
+        .arg("--deadline")
+        .arg("2")
+        .arg("-h")
+        .arg(&holochain_path)
+        .arg("run")
+        .arg("-e")
+        .arg(&path)
+        .output()
+        .expect("Failed to run hc");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(started.elapsed() < Duration::from_secs(20), "{}", stderr);
+    assert_eq!(
+        output.status.code(),
+        Some(hc::deadline::DEADLINE_EXIT_CODE),
+        "stderr: {}",
+        stderr
+    );
+    assert!(stderr.contains("--deadline of 2s"), "stderr: {}", stderr);
+
+    // The conductor was killed with hc.
+    let pid = std::fs::read_to_string(path.join("conductor-pid")).unwrap();
+    let alive = || {
+        std::process::Command::new("kill")
+            .arg("-0")
+            .arg(pid.trim())
+            .status()
+            .unwrap()
+            .success()
+    };
+    let stopped = std::time::Instant::now();
+    while alive() && stopped.elapsed() < Duration::from_secs(5) {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert!(!alive(), "The conductor is still running");
+
+    assert!(hc::deadline::parse_deadline("0").is_err());
+    assert!(hc::deadline::parse_deadline("soon").is_err());
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn conductors_in_a_wave_start_one_at_a_time() {
+    observability::test_run().ok();
+    let setups: Vec<_> = (0..3)
+        .map(|_| hc::generate::generate(None, None, None).unwrap())
+        .collect();
+    let starts = setups[0].join("starts");
+    // Each conductor takes 300ms to report its admin port.
+    let holochain_path = fake_script(
+        &setups[0],
+        &format!(
+            "date +%s%N >> {}\n\
+            sleep 0.3\n\
+            echo '###HOLOCHAIN_SETUP###'\n\
+            echo '###ADMIN_PORT:4324###'\n\
+            echo '###HOLOCHAIN_SETUP_END###'\n\
+            exec sleep 30\n",
+            starts.display()
+        ),
+    );
+    let stagger = Stagger::from_iter_safe(&["run", "--max-concurrent-starts", "1"]).unwrap();
+    let wave = StartupWave::new(setups.len(), &stagger);
+    let running = setups.iter().map(|path| {
+        let config = RunConfig {
+            holochain_path: holochain_path.clone(),
+            ..RunConfig::new(path.clone())
+        };
+        hc::run::run_in_wave(config, Vec::new(), &wave)
+    });
+    // The conductors keep running so stop them once they have all started.
+    let _ = tokio::time::timeout(Duration::from_secs(3), futures::future::join_all(running)).await;
+
+    let starts: Vec<u128> = std::fs::read_to_string(&starts)
+        .unwrap()
+        .lines()
+        .map(|line| line.parse().unwrap())
+        .collect();
+    assert_eq!(starts.len(), 3);
+    for pair in starts.windows(2) {
+        assert!(
+            pair[1] - pair[0] >= 250_000_000,
+            "A conductor started before the last one was ready: {:?}",
+            starts
+        );
+    }
+
+    assert!(Stagger::from_iter_safe(&["run", "--max-concurrent-starts", "0"]).is_err());
+    let stagger = Stagger::from_iter_safe(&["run", "--stagger", "100"]).unwrap();
+    assert_eq!(stagger.stagger, Some(Duration::from_millis(100)));
+
+    for path in setups {
+        std::fs::remove_dir_all(path).ok();
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use hc::calls::AddAppWs;
+use hc::Setups;
+use holochain_hc as hc;
+
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn setups_stop_their_conductors() {
+    observability::test_run().ok();
+    let paths = vec![
+        hc::generate::generate(None, None, None).unwrap(),
+        hc::generate::generate(None, None, None).unwrap(),
+    ];
+    let mut setups = Setups::run(Path::new("holochain"), paths.clone())
+        .await
+        .unwrap();
+    assert_eq!(setups.len(), 2);
+    setups.get(1).unwrap().list_cell_ids().await.unwrap();
+    assert!(setups.get(2).is_none());
+    let ports: Vec<_> = (0..2)
+        .map(|i| setups.handle(i).unwrap().admin_port())
+        .collect();
+
+    drop(setups);
+    tokio::time::delay_for(std::time::Duration::from_millis(500)).await;
+    for port in ports {
+        assert!(hc::CmdRunner::try_new(port).await.is_err());
+    }
+    for path in paths {
+        std::fs::remove_dir_all(path).ok();
+    }
+}
+
+/// Conductors bind port `0` and report the port they got
+/// so setups started together never share a port.
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn concurrent_setups_get_distinct_ports() {
+    observability::test_run().ok();
+    let paths: Vec<_> = (0..20)
+        .map(|_| hc::generate::generate(None, None, None).unwrap())
+        .collect();
+    let mut setups = Setups::run(Path::new("holochain"), paths.clone())
+        .await
+        .unwrap();
+    let admin_ports: HashSet<_> = (0..20)
+        .map(|i| setups.handle(i).unwrap().admin_port())
+        .collect();
+    assert_eq!(admin_ports.len(), 20);
+    assert!(!admin_ports.contains(&0));
+
+    let attach = (0..20).map(|i| {
+        let cmd = setups.get(i).unwrap().clone();
+        async move {
+            hc::calls::attach_app_interface(
+                &cmd,
+                AddAppWs {
+                    port: None,
+                    verify: true,
+                    allowed_origins: Vec::new(),
+                },
+            )
+            .await
+            .unwrap()
+        }
+    });
+    let app_ports: HashSet<_> = futures::future::join_all(attach)
+        .await
+        .into_iter()
+        .collect();
+    assert_eq!(app_ports.len(), 20);
+    assert!(app_ports.is_disjoint(&admin_ports));
+
+    drop(setups);
+    for path in paths {
+        std::fs::remove_dir_all(path).ok();
+    }
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn db_dirs_can_not_be_shared() {
+    let dir = hc::generate::generate(None, None, None).unwrap();
+    let db_dir = dir.join("dbs");
+    let create = hc::cmds::Create {
+        db_dir: Some(db_dir.clone()),
+        ..Default::default()
+    };
+    let err = hc::setups::default_n(Path::new("holochain"), 2, create, Vec::new())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("--db-dir"), "{}", err);
+
+    // The directory is created.
+    let prepared = hc::setups::prepare_db_dir(&db_dir).unwrap();
+    assert!(prepared.is_absolute() && prepared.is_dir());
+
+    // Once a conductor has its databases there no other setup can use it.
+    std::fs::create_dir(db_dir.join("conductor")).unwrap();
+    let err = hc::setups::prepare_db_dir(&db_dir).unwrap_err();
+    assert!(err.to_string().contains("can't share databases"), "{}", err);
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn clean_removes_the_db_dir() {
+    let hc_dir = hc::generate::generate(None, None, None).unwrap();
+    let setup = hc::generate::generate(None, None, None).unwrap();
+    let db_dir = hc::setups::prepare_db_dir(&hc_dir.join("dbs")).unwrap();
+    hc::config::use_environment_path(setup.clone(), db_dir.clone()).unwrap();
+    hc::save::record_db_dir(&setup, &db_dir).unwrap();
+    assert_eq!(hc::save::db_dir(&setup).unwrap(), Some(db_dir.clone()));
+    hc::save::save(hc_dir.clone(), vec![setup.clone()]).unwrap();
+
+    hc::save::clean(hc_dir.clone(), Vec::new(), None).unwrap();
+    assert!(!setup.exists());
+    assert!(!db_dir.exists());
+
+    std::fs::remove_dir_all(hc_dir).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn prune_drops_setups_whose_directories_are_gone() {
+    let hc_dir = hc::generate::generate(None, None, None).unwrap();
+    let alive = hc::generate::generate(None, None, None).unwrap();
+    let gone = hc::generate::generate(None, None, None).unwrap();
+    hc::save::save(hc_dir.clone(), vec![alive.clone(), gone.clone()]).unwrap();
+    std::fs::remove_dir_all(&gone).unwrap();
+
+    let pruned = hc::save::prune(hc_dir.clone(), false).await.unwrap();
+    assert_eq!(pruned, vec![gone]);
+    assert_eq!(hc::save::load(hc_dir.clone()).unwrap(), vec![alive.clone()]);
+    assert!(alive.exists());
+
+    // Running it again prunes nothing.
+    assert!(hc::save::prune(hc_dir.clone(), false)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // The .hc file goes when no setups are left.
+    std::fs::remove_dir_all(&alive).unwrap();
+    assert_eq!(
+        hc::save::prune(hc_dir.clone(), false).await.unwrap(),
+        vec![alive]
+    );
+    assert!(!hc_dir.join(".hc").exists());
+    assert!(hc::save::prune(hc_dir.clone(), false)
+        .await
+        .unwrap()
+        .is_empty());
+
+    std::fs::remove_dir_all(hc_dir).ok();
+}
+
+#[test]
+fn clean_dev_only_removes_dev_setups() {
+    let hc_dir = hc::generate::generate(None, None, None).unwrap();
+    let dev = hc::generate::generate(None, None, None).unwrap();
+    let other = hc::generate::generate(None, None, None).unwrap();
+    hc::save::record_dev_mode(&dev).unwrap();
+    assert!(hc::save::is_dev(&dev));
+    assert!(!hc::save::is_dev(&other));
+    hc::save::save(hc_dir.clone(), vec![dev.clone(), other.clone()]).unwrap();
+
+    hc::save::clean_dev(hc_dir.clone(), None).unwrap();
+    assert!(!dev.exists());
+    assert!(other.exists());
+    let left = std::fs::read_to_string(hc_dir.join(".hc")).unwrap();
+    assert_eq!(left, format!("{}\n", other.display()));
+
+    // Cleaning again is a no-op.
+    hc::save::clean_dev(hc_dir.clone(), None).unwrap();
+    assert_eq!(hc::save::load(hc_dir.clone()).unwrap(), vec![other.clone()]);
+
+    std::fs::remove_dir_all(other).ok();
+    std::fs::remove_dir_all(hc_dir).ok();
+}
+
+/// Conductors sharing a keystore connect to the same lair
+/// so they can run at the same time.
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn concurrent_conductors_share_a_keystore() {
+    observability::test_run().ok();
+    let root = hc::generate::generate(None, None, None).unwrap();
+    let keystore = root.join("keys");
+    let paths: Vec<_> = (0..3)
+        .map(|_| hc::generate::generate(None, Some(root.clone()), None).unwrap())
+        .collect();
+    for path in &paths {
+        hc::keystore::share_keystore(path, &keystore).unwrap();
+    }
+    let mut setups = Setups::run(Path::new("holochain"), paths.clone())
+        .await
+        .unwrap();
+    let keys = (0..3).map(|i| {
+        let cmd = setups.get(i).unwrap().clone();
+        async move { hc::calls::generate_agent_pub_key(&cmd).await.unwrap() }
+    });
+    let keys: HashSet<_> = futures::future::join_all(keys).await.into_iter().collect();
+    assert_eq!(keys.len(), 3);
+    for i in 0..3 {
+        setups.get(i).unwrap().list_cell_ids().await.unwrap();
+    }
+
+    drop(setups);
+    std::fs::remove_dir_all(root).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn relocate_moves_the_setup_and_rewrites_its_paths() {
+    let hc_dir = hc::generate::generate(None, None, None).unwrap();
+    let setup = hc::generate::generate(None, None, None).unwrap();
+    let overrides = hc::config::ConductorConfigSpec {
+        keystore_path: Some(setup.join("my-keystore")),
+        ..Default::default()
+    };
+    hc::config::apply_overrides(setup.clone(), overrides).unwrap();
+    hc::config::use_environment_path(setup.clone(), "databases".into()).unwrap();
+    hc::save::save(hc_dir.clone(), vec![setup.clone()]).unwrap();
+
+    let moved = hc::save::relocate(hc_dir.clone(), 0, hc_dir.join("moved").join("alice"))
+        .await
+        .unwrap();
+    assert!(!setup.exists());
+    assert_eq!(hc::save::load(hc_dir.clone()).unwrap(), vec![moved.clone()]);
+
+    let config = hc::config::read_config(moved.clone()).unwrap().unwrap();
+    assert_eq!(
+        hc::config::environment_path(&moved, &config),
+        moved.join("databases")
+    );
+    assert_eq!(config.keystore_path, Some(moved.join("my-keystore")));
+    // Regenerating from the overrides doesn't bring the old path back.
+    let spec = hc::config::read_overrides(moved.clone()).unwrap();
+    assert_eq!(spec.keystore_path, Some(moved.join("my-keystore")));
+
+    let err = hc::save::relocate(hc_dir.clone(), 0, hc_dir.clone())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("already exists"), "{}", err);
+    let err = hc::save::relocate(hc_dir.clone(), 1, hc_dir.join("bob"))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("no setup at index 1"), "{}", err);
+
+    std::fs::remove_dir_all(hc_dir).ok();
+}
+
+/// A config written by the builder is one the conductor starts with.
+#[tokio::test(threaded_scheduler)]
+#[ignore = "Figure out how to get holochain bin in CI"]
+async fn conductors_start_with_built_configs() {
+    use hc::config::ConductorConfigBuilder;
+    use hc::config::NetworkConfigBuilder;
+
+    observability::test_run().ok();
+    let path = hc::generate::generate_directory(None, None).unwrap();
+    ConductorConfigBuilder::new(path.clone())
+        .network(NetworkConfigBuilder::new().build().unwrap())
+        .extra("db_sync_level", serde_yaml::Value::String("off".into()))
+        .write_to(&path)
+        .unwrap();
+    let setups = Setups::run(Path::new("holochain"), vec![path.clone()])
+        .await
+        .unwrap();
+    setups.get(0).unwrap().list_cell_ids().await.unwrap();
+
+    drop(setups);
+    std::fs::remove_dir_all(path).ok();
+}
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use holochain_hc::AppCmdRunner;
+use holochain_hc::OverflowPolicy;
+use holochain_hc::SignalBuffer;
+use holochain_hc::SignalBufferConfig;
+use holochain_hc::SignalEvent;
+use holochain_types::prelude::test_signal;
+use holochain_types::prelude::Signal;
+use holochain_types::prelude::SystemSignal;
+use holochain_websocket::websocket_bind;
+use holochain_websocket::WebsocketConfig;
+use tokio::stream::StreamExt;
+use url2::url2;
+
+fn buffer(capacity: usize, policy: OverflowPolicy) -> SignalBuffer {
+    SignalBuffer::new(SignalBufferConfig { capacity, policy })
+}
+
+fn numbered(i: usize) -> SignalEvent {
+    SignalEvent::Signal(test_signal(&i.to_string()))
+}
+
+fn number(signal: &Signal) -> usize {
+    match signal {
+        Signal::System(SystemSignal::Test(s)) => s.parse().unwrap(),
+        s => panic!("Unexpected signal {:?}", s),
+    }
+}
+
+fn drain(buffer: &SignalBuffer) -> Vec<SignalEvent> {
+    std::iter::from_fn(|| buffer.try_next()).collect()
+}
+
+/// Bind a fake app interface that sends `count` numbered
+/// signals to each connection and then closes it.
+/// Returns the port it is listening on.
+async fn flooding_app_interface(count: usize) -> u16 {
+    let mut server = websocket_bind(
+        url2!("ws://127.0.0.1:0"),
+        Arc::new(WebsocketConfig::default()),
+    )
+    .await
+    .unwrap();
+    let port = server.local_addr().port().expect("Listener has no port");
+    tokio::task::spawn(async move {
+        while let Some(Ok((mut send, _recv))) = server.next().await {
+            for i in 0..count {
+                send.signal(test_signal(&i.to_string())).await.unwrap();
+            }
+            send.close(1000, "done".to_string()).await.ok();
+        }
+    });
+    port
+}
+
+#[test]
+fn drop_newest_keeps_the_first_signals_and_marks_the_gap() {
+    let signals = buffer(3, OverflowPolicy::DropNewest);
+    for i in 0..10 {
+        signals.push(test_signal(&i.to_string()));
+    }
+    assert_eq!(signals.len(), 3);
+    assert_eq!(signals.dropped_count(), 7);
+    assert_eq!(
+        drain(&signals),
+        vec![numbered(0), numbered(1), numbered(2), SignalEvent::Gap(7)]
+    );
+
+    // Signals after the gap follow it once there is room.
+    signals.push(test_signal("10"));
+    signals.push(test_signal("11"));
+    assert_eq!(drain(&signals), vec![numbered(10), numbered(11)]);
+    assert_eq!(signals.dropped_count(), 7);
+}
+
+#[test]
+fn drop_oldest_keeps_the_latest_signals_and_marks_the_gap() {
+    let signals = buffer(3, OverflowPolicy::DropOldest);
+    for i in 0..10 {
+        signals.push(test_signal(&i.to_string()));
+    }
+    assert_eq!(signals.len(), 3);
+    assert_eq!(signals.dropped_count(), 7);
+    assert_eq!(
+        drain(&signals),
+        vec![SignalEvent::Gap(7), numbered(7), numbered(8), numbered(9)]
+    );
+
+    // A gap is only reported for signals dropped since the last read.
+    for i in 10..15 {
+        signals.push(test_signal(&i.to_string()));
+    }
+    assert_eq!(
+        drain(&signals),
+        vec![
+            SignalEvent::Gap(2),
+            numbered(12),
+            numbered(13),
+            numbered(14)
+        ]
+    );
+    assert_eq!(signals.dropped_count(), 9);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn closed_buffers_end_after_the_buffered_signals() {
+    let signals = buffer(3, OverflowPolicy::DropOldest);
+    signals.push(test_signal("0"));
+    signals.close();
+    signals.push(test_signal("1"));
+    assert_eq!(signals.next().await, Some(numbered(0)));
+    assert_eq!(signals.next().await, None);
+    assert_eq!(signals.dropped_count(), 0);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn a_slow_consumer_of_a_signal_flood_stays_bounded() {
+    const SIGNALS: usize = 5000;
+    const CAPACITY: usize = 16;
+    for &policy in &[OverflowPolicy::DropOldest, OverflowPolicy::DropNewest] {
+        let port = flooding_app_interface(SIGNALS).await;
+        let app = AppCmdRunner::try_new_with_signal_buffer(
+            "127.0.0.1",
+            port,
+            WebsocketConfig::default(),
+            SignalBufferConfig {
+                capacity: CAPACITY,
+                policy,
+            },
+        )
+        .await
+        .unwrap();
+        let signals = app.signals();
+
+        let mut received = Vec::new();
+        let mut gaps = 0;
+        while let Some(event) = signals.next().await {
+            assert!(signals.len() <= CAPACITY);
+            match event {
+                SignalEvent::Signal(s) => received.push(number(&s)),
+                SignalEvent::Gap(n) => gaps += n,
+            }
+            tokio::time::delay_for(Duration::from_millis(1)).await;
+        }
+
+        assert!(
+            gaps > 0,
+            "The consumer should fall behind with {:?}",
+            policy
+        );
+        assert_eq!(signals.dropped_count(), gaps);
+        assert_eq!(received.len() as u64 + gaps, SIGNALS as u64);
+        // What is kept arrives in order.
+        assert!(received.windows(2).all(|w| w[0] < w[1]));
+        match policy {
+            OverflowPolicy::DropOldest => assert_eq!(received.last(), Some(&(SIGNALS - 1))),
+            OverflowPolicy::DropNewest => assert_eq!(received.first(), Some(&0)),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::convert::TryInto;
+use std::path::Path;
+use std::sync::Arc;
+
+use holochain_conductor_api::AdminRequest;
+use holochain_conductor_api::AdminResponse;
+use holochain_conductor_api::ConductorVersion;
+use holochain_conductor_api::CONDUCTOR_API_VERSION;
+use holochain_hc as hc;
+use holochain_websocket::websocket_bind;
+use holochain_websocket::WebsocketConfig;
+use holochain_websocket::WebsocketMessage;
+use tokio::stream::StreamExt;
+use url2::url2;
+
+/// Write a file where the conductor would have an LMDB database.
+fn fake_database(setup: &Path, name: &str, data: &str) {
+    let dir = setup.join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("data.mdb"), data).unwrap();
+    std::fs::write(dir.join("lock.mdb"), "lock").unwrap();
+}
+
+fn read_database(setup: &Path, name: &str) -> Option<String> {
+    std::fs::read_to_string(setup.join(name).join("data.mdb")).ok()
+}
+
+/// Bind a fake admin interface so the setup looks like it is running.
+async fn mock_admin_interface() -> u16 {
+    let mut server = websocket_bind(
+        url2!("ws://127.0.0.1:0"),
+        Arc::new(WebsocketConfig::default()),
+    )
+    .await
+    .unwrap();
+    let port = server.local_addr().port().expect("Listener has no port");
+    tokio::task::spawn(async move {
+        while let Some(Ok((_, mut recv))) = server.next().await {
+            tokio::task::spawn(async move {
+                while let Some(msg) = recv.next().await {
+                    if let WebsocketMessage::Request(data, respond) = msg {
+                        let request: AdminRequest = data.try_into().unwrap();
+                        let response = match request {
+                            AdminRequest::RequestVersion => {
+                                AdminResponse::VersionRequested(ConductorVersion {
+                                    holochain: "0.0.100".to_string(),
+                                    conductor_api: CONDUCTOR_API_VERSION.to_string(),
+                                })
+                            }
+                            _ => AdminResponse::DnasListed(Vec::new()),
+                        };
+                        respond(response.try_into().unwrap()).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+    port
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn restoring_a_snapshot_brings_back_the_databases() {
+    let setup = hc::generate::generate(None, None, None).unwrap();
+    fake_database(&setup, "conductor", "clean conductor");
+    fake_database(&setup, "cells/cell-a", "clean cell");
+
+    assert_eq!(
+        hc::snapshot::snapshot(&setup, "clean", false)
+            .await
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        hc::snapshot::list(&setup).unwrap(),
+        vec!["clean".to_string()]
+    );
+    // The keystore and config aren't databases.
+    let snapshot = hc::snapshot::snapshots_path(&setup).join("clean");
+    assert!(!snapshot.join("keystore").exists());
+    assert!(!snapshot.join("conductor").join("lock.mdb").exists());
+
+    fake_database(&setup, "conductor", "dirty conductor");
+    fake_database(&setup, "cells/cell-b", "new cell");
+
+    assert_eq!(hc::snapshot::restore(&setup, "clean").await.unwrap(), 2);
+    assert_eq!(
+        read_database(&setup, "conductor").as_deref(),
+        Some("clean conductor")
+    );
+    assert_eq!(
+        read_database(&setup, "cells/cell-a").as_deref(),
+        Some("clean cell")
+    );
+    assert!(!setup.join("cells").join("cell-b").exists());
+    assert!(setup.join(hc::config::CONDUCTOR_CONFIG).exists());
+
+    std::fs::remove_dir_all(setup).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn snapshots_are_only_replaced_with_force() {
+    let setup = hc::generate::generate(None, None, None).unwrap();
+    fake_database(&setup, "conductor", "first");
+    hc::snapshot::snapshot(&setup, "clean", false)
+        .await
+        .unwrap();
+
+    fake_database(&setup, "conductor", "second");
+    let err = hc::snapshot::snapshot(&setup, "clean", false)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("--force"), "{}", err);
+    hc::snapshot::snapshot(&setup, "clean", true).await.unwrap();
+
+    fake_database(&setup, "conductor", "third");
+    hc::snapshot::restore(&setup, "clean").await.unwrap();
+    assert_eq!(
+        read_database(&setup, "conductor").as_deref(),
+        Some("second")
+    );
+
+    let err = hc::snapshot::restore(&setup, "missing").await.unwrap_err();
+    assert!(
+        err.to_string().contains("Its snapshots are: clean"),
+        "{}",
+        err
+    );
+    for name in &["", "../escape", ".hidden"] {
+        assert!(hc::snapshot::snapshot(&setup, name, false).await.is_err());
+    }
+
+    std::fs::remove_dir_all(setup).ok();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn running_setups_are_not_restored() {
+    let setup = hc::generate::generate(None, None, None).unwrap();
+    fake_database(&setup, "conductor", "clean");
+    hc::snapshot::snapshot(&setup, "clean", false)
+        .await
+        .unwrap();
+    fake_database(&setup, "conductor", "dirty");
+
+    let port = mock_admin_interface().await;
+    hc::force_admin_port(setup.clone(), port).unwrap();
+    let err = hc::snapshot::restore(&setup, "clean").await.unwrap_err();
+    assert!(err.to_string().contains("is running"), "{}", err);
+    assert_eq!(read_database(&setup, "conductor").as_deref(), Some("dirty"));
+
+    std::fs::remove_dir_all(setup).ok();
+}
+
+#[test]
+fn snapshot_list_is_not_a_setup_index() {
+    use hc::snapshot::Snapshot;
+    use hc::snapshot::SnapshotList;
+    use structopt::StructOpt;
+
+    let cmd = Snapshot::from_iter_safe(&["snapshot", "list"]).unwrap();
+    assert!(matches!(cmd.list, Some(SnapshotList::List { index: None })));
+    let cmd = Snapshot::from_iter_safe(&["snapshot", "list", "1"]).unwrap();
+    assert!(matches!(
+        cmd.list,
+        Some(SnapshotList::List { index: Some(1) })
+    ));
+
+    let cmd = Snapshot::from_iter_safe(&["snapshot", "0", "clean"]).unwrap();
+    assert!(cmd.list.is_none());
+    assert_eq!(cmd.index, Some(0));
+    assert_eq!(cmd.name.as_deref(), Some("clean"));
+}
@@ -0,0 +1,112 @@
+use holochain_hc as hc;
+use holochain_types::prelude::Element;
+use holochain_types::prelude::Entry;
+use holochain_types::prelude::EntryType;
+use holochain_types::prelude::Header;
+use holochain_types::prelude::HeaderType;
+use holochain_types::prelude::Timestamp;
+
+use hc::state_dump::SourceChainDump;
+use hc::state_dump::TimeWindow;
+
+/// A `StateDumped` response captured from a conductor
+/// with a cell that has only done genesis.
+const GENESIS_DUMP: &str = include_str!("fixtures/state_dump.json");
+
+#[test]
+fn a_captured_state_dump_deserializes() {
+    let dump = SourceChainDump::from_json(GENESIS_DUMP).unwrap();
+    assert_eq!(dump.len(), 3);
+    assert_eq!(dump.missing(), 0);
+
+    let types: Vec<_> = dump.headers().map(Header::header_type).collect();
+    assert_eq!(
+        types,
+        vec![
+            HeaderType::Dna,
+            HeaderType::AgentValidationPkg,
+            HeaderType::Create
+        ]
+    );
+    let seqs: Vec<_> = dump.headers().map(Header::header_seq).collect();
+    assert_eq!(seqs, vec![0, 1, 2]);
+
+    let agent = dump.elements().last().unwrap();
+    assert_eq!(agent.header.entry_type(), Some(&EntryType::AgentPubKey));
+    match &agent.entry {
+        Some(Entry::Agent(key)) => assert_eq!(key, agent.header.author()),
+        entry => panic!("Expected the agent entry, got {:?}", entry),
+    }
+
+    // The elements are the same as the conductor's.
+    let element = Element::from(agent.clone());
+    assert_eq!(element.header_address(), &agent.header_address);
+    assert_eq!(element.signature(), &agent.signature);
+
+    // Serializing gives back the same JSON.
+    let json: serde_json::Value = serde_json::from_str(GENESIS_DUMP).unwrap();
+    assert_eq!(serde_json::to_value(&dump).unwrap(), json);
+}
+
+#[test]
+fn headers_missing_their_element_are_counted() {
+    let dump = SourceChainDump::from_json(r#"[{"element": null}]"#).unwrap();
+    assert_eq!(dump.len(), 1);
+    assert_eq!(dump.missing(), 1);
+    assert_eq!(dump.elements().count(), 0);
+
+    assert!(SourceChainDump::from_json("DUMP STATE").is_err());
+}
+
+#[test]
+fn dumps_are_limited_to_a_time_window() {
+    let window = |since: Option<&str>, until: Option<&str>| TimeWindow {
+        since: since.map(|t| hc::state_dump::parse_time(t).unwrap()),
+        until: until.map(|t| hc::state_dump::parse_time(t).unwrap()),
+    };
+    let seqs = |window: TimeWindow| {
+        let mut dump = SourceChainDump::from_json(GENESIS_DUMP).unwrap();
+        dump.retain_within(&window);
+        dump.headers().map(Header::header_seq).collect::<Vec<_>>()
+    };
+    // The genesis headers were written a second apart from 2021-01-07T06:13:20.123Z.
+    assert_eq!(seqs(window(None, None)), vec![0, 1, 2]);
+    assert_eq!(
+        seqs(window(Some("2021-01-07T06:13:21.123Z"), None)),
+        vec![1, 2]
+    );
+    assert_eq!(
+        seqs(window(None, Some("2021-01-07T06:13:21.123Z"))),
+        vec![0]
+    );
+    assert_eq!(
+        seqs(window(
+            Some("2021-01-07T07:13:21+01:00"),
+            Some("2021-01-07T06:13:22.5Z")
+        )),
+        vec![1, 2]
+    );
+
+    // Headers without their element stay so they are still reported.
+    let mut dump = SourceChainDump::from_json(r#"[{"element": null}]"#).unwrap();
+    dump.retain_within(&window(Some("2021-01-07T06:13:21Z"), None));
+    assert_eq!(dump.missing(), 1);
+
+    assert!(
+        window(Some("2021-01-07T06:13:21Z"), Some("2021-01-07T06:13:20Z"))
+            .check()
+            .is_err()
+    );
+    assert!(window(Some("2021-01-07T06:13:20Z"), None).check().is_ok());
+    assert_eq!(
+        hc::state_dump::parse_time("2021-01-07T06:13:20Z").unwrap(),
+        Timestamp(1610000000, 0)
+    );
+    let err = hc::state_dump::parse_time("2021-01-07").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("2021-01-07 is not an RFC 3339 time"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,20 @@
+use holochain_hc as hc;
+
+#[test]
+fn certificates_are_reused_until_regenerated() {
+    let path = hc::generate::generate(None, None, None).unwrap();
+
+    let first = hc::tls::setup_tls(&path, false).unwrap();
+    assert!(first.cert.is_file());
+    assert!(first.key.is_file());
+    // 32 bytes as colon separated hex.
+    assert_eq!(first.fingerprint.len(), 32 * 3 - 1);
+
+    let reused = hc::tls::setup_tls(&path, false).unwrap();
+    assert_eq!(first.fingerprint, reused.fingerprint);
+
+    let regenerated = hc::tls::setup_tls(&path, true).unwrap();
+    assert_ne!(first.fingerprint, regenerated.fingerprint);
+
+    std::fs::remove_dir_all(path).ok();
+}
@@ -1,4 +1,6 @@
 pub mod agent_info;
+pub mod base64_decode;
+pub mod base64_encode;
 pub mod call;
 pub mod call_remote;
 pub mod create;
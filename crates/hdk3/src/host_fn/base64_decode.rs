@@ -0,0 +1,16 @@
+use crate::prelude::*;
+
+/// Decode base64 with the standard alphabet and padding.
+///
+/// ```ignore
+/// let bytes = base64_decode(Base64Encoded::from("aG9sb2NoYWlu"))?;
+/// assert_eq!(bytes, Some(Bytes::from(b"holochain".to_vec())));
+/// ```
+///
+/// Returns `None` if the input isn't valid base64 so the zome can decide what bad input means,
+/// e.g. failing validation, rather than the whole call failing.
+///
+/// @see base64_encode
+pub fn base64_decode(encoded: Base64Encoded) -> ExternResult<Option<Bytes>> {
+    host_call::<Base64Encoded, Option<Bytes>>(__base64_decode, encoded)
+}
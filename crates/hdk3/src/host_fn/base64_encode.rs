@@ -0,0 +1,15 @@
+use crate::prelude::*;
+
+/// Encode bytes as base64 with the standard alphabet and padding.
+///
+/// ```ignore
+/// let encoded = base64_encode(Bytes::from(b"holochain".to_vec()))?;
+/// assert_eq!(encoded.as_ref(), "aG9sb2NoYWlu");
+/// ```
+///
+/// The encoding is done by the host so the zome doesn't need to bundle a base64 library
+/// in its wasm. The bytes are copied to the host and the text back to the guest so encoding
+/// in the guest may still be faster for very large or many small values.
+pub fn base64_encode(bytes: Bytes) -> ExternResult<Base64Encoded> {
+    host_call::<Bytes, Base64Encoded>(__base64_encode, bytes)
+}
@@ -21,6 +21,8 @@ pub use crate::hash_path::anchor::list_anchor_type_addresses;
 pub use crate::hash_path::anchor::Anchor;
 pub use crate::hash_path::path::Path;
 pub use crate::host_fn::agent_info::agent_info;
+pub use crate::host_fn::base64_decode::base64_decode;
+pub use crate::host_fn::base64_encode::base64_encode;
 pub use crate::host_fn::call::call;
 pub use crate::host_fn::call_remote::call_remote;
 pub use crate::host_fn::create::create;
@@ -77,6 +79,8 @@ macro_rules! holochain_externs {
         holochain_wasmer_guest::host_externs!(
             __trace,
             __hash_entry,
+            __base64_encode,
+            __base64_decode,
             __unreachable,
             __verify_signature,
             __sign,
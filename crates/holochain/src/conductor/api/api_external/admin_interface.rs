@@ -4,6 +4,8 @@ use crate::conductor::api::error::ConductorApiResult;
 
 use crate::conductor::api::error::SerializationError;
 
+use crate::conductor::entry_def_store::get_entry_defs;
+use crate::conductor::error::ConductorError;
 use crate::conductor::error::CreateAppError;
 use crate::conductor::interface::error::InterfaceError;
 use crate::conductor::interface::error::InterfaceResult;
@@ -55,6 +57,56 @@ impl RealAdminInterfaceApi {
     pub(crate) fn new(conductor_handle: ConductorHandle) -> Self {
         RealAdminInterfaceApi { conductor_handle }
     }
+
+    /// The dna a [`RegisterDnaPayload`] refers to with its
+    /// properties and uuid applied.
+    async fn dna_from_payload(&self, payload: RegisterDnaPayload) -> ConductorApiResult<DnaFile> {
+        let mut dna = match payload.source {
+            DnaSource::Hash(ref hash) => {
+                if payload.properties.is_none() && payload.uuid.is_none() {
+                    return Err(ConductorApiError::DnaReadError(
+                        "Hash Dna source requires properties or uuid to create a derived Dna"
+                            .to_string(),
+                    ));
+                }
+                self.conductor_handle.get_dna(hash).await.ok_or_else(|| {
+                    ConductorApiError::DnaReadError(format!(
+                        "Unable to create derived Dna: {} not registered",
+                        hash
+                    ))
+                })?
+            }
+            DnaSource::Path(path) => read_parse_dna(path, None).await?, // properties handled below
+            DnaSource::DnaFile(dna) => dna,
+        };
+        if let Some(props) = payload.properties {
+            let properties = SerializedBytes::try_from(props).map_err(SerializationError::from)?;
+            dna = dna.with_properties(properties).await?;
+        }
+        if let Some(uuid) = payload.uuid {
+            dna = dna.with_uuid(uuid).await?;
+        }
+        Ok(dna)
+    }
+}
+
+/// Check the dna the way installing it would, without storing anything:
+/// every wasm zome has its wasm and the wasm loads and gives its entry defs.
+fn validate_dna(dna: &DnaFile) -> ConductorApiResult<()> {
+    let mut is_full_wasm_dna = true;
+    for (zome_name, zome_def) in &dna.dna_def().zomes {
+        match zome_def {
+            ZomeDef::Wasm(_) => {
+                dna.get_wasm_for_zome(zome_name)?;
+            }
+            _ => is_full_wasm_dna = false,
+        }
+    }
+    // Only dnas with nothing but wasm zomes have their wasm installed.
+    if is_full_wasm_dna {
+        get_entry_defs(dna.clone()).map_err(ConductorError::from)?;
+    }
+    Ok(())
 }
 
 #[async_trait::async_trait]
@@ -74,32 +126,7 @@ impl AdminInterfaceApi for RealAdminInterfaceApi {
             }
             RegisterDna(payload) => {
                 trace!(register_dna_payload = ?payload);
-                let mut dna = match payload.source {
-                    DnaSource::Hash(ref hash) => {
-                        if payload.properties.is_none() && payload.uuid.is_none() {
-                            return Err(ConductorApiError::DnaReadError(
-                                "Hash Dna source requires properties or uuid to create a derived Dna"
-                                    .to_string(),
-                            ));
-                        }
-                        self.conductor_handle.get_dna(hash).await.ok_or_else(|| {
-                            ConductorApiError::DnaReadError(format!(
-                                "Unable to create derived Dna: {} not registered",
-                                hash
-                            ))
-                        })?
-                    }
-                    DnaSource::Path(path) => read_parse_dna(path, None).await?, // properties handled below
-                    DnaSource::DnaFile(dna) => dna,
-                };
-                if let Some(props) = payload.properties {
-                    let properties =
-                        SerializedBytes::try_from(props).map_err(SerializationError::from)?;
-                    dna = dna.with_properties(properties).await?;
-                }
-                if let Some(uuid) = payload.uuid {
-                    dna = dna.with_uuid(uuid).await?;
-                }
+                let dna = self.dna_from_payload(*payload).await?;
                 let hash = dna.dna_hash().clone();
                 let dna_list = self.conductor_handle.list_dnas().await?;
                 if !dna_list.contains(&hash) {
@@ -107,6 +134,12 @@ impl AdminInterfaceApi for RealAdminInterfaceApi {
                 }
                 Ok(AdminResponse::DnaRegistered(hash))
             }
+            ValidateDna(payload) => {
+                trace!(validate_dna_payload = ?payload);
+                let dna = self.dna_from_payload(*payload).await?;
+                validate_dna(&dna)?;
+                Ok(AdminResponse::DnaValidated(dna.dna_hash().clone()))
+            }
             InstallApp(payload) => {
                 trace!(?payload.dnas);
                 let InstallAppPayload {
@@ -123,7 +156,23 @@ impl AdminInterfaceApi for RealAdminInterfaceApi {
                         properties,
                         membrane_proof,
                         nick,
+                        existing_cell,
                     } = dna_payload;
+                    if let Some(cell_id) = existing_cell {
+                        if maybe_path.is_some() {
+                            return Err(ConductorApiError::DnaReadError("An existing cell can't be installed from a path, give the hash of its dna instead".to_string()));
+                        }
+                        if let Some(hash) = maybe_hash {
+                            if &hash != cell_id.dna_hash() {
+                                return Err(ConductorApiError::DnaReadError(format!("Existing cell {} has dna {} but the app requires dna {}", cell_id, cell_id.dna_hash(), hash)));
+                            }
+                        }
+                        if !self.conductor_handle.list_installed_cell_ids().await?.contains(&cell_id) {
+                            return Err(ConductorApiError::CellMissing(cell_id));
+                        }
+                        // The cell was already created so it has no genesis to prove membership for.
+                        return ConductorApiResult::Ok((InstalledCell::new(cell_id, nick), None));
+                    }
                     if maybe_path.is_none() && maybe_hash.is_none() {
                         return Err(ConductorApiError::DnaReadError("Neither path nor hash specified in payload".to_string()))
                     };
@@ -195,6 +244,10 @@ impl AdminInterfaceApi for RealAdminInterfaceApi {
                 let app_ids = self.conductor_handle.list_active_apps().await?;
                 Ok(AdminResponse::ActiveAppsListed(app_ids))
             }
+            ListInactiveApps => {
+                let apps = self.conductor_handle.list_inactive_apps().await?;
+                Ok(AdminResponse::InactiveAppsListed(apps))
+            }
             ActivateApp { installed_app_id } => {
                 // Activate app
                 self.conductor_handle
@@ -226,12 +279,15 @@ impl AdminInterfaceApi for RealAdminInterfaceApi {
                     .await?;
                 Ok(AdminResponse::AppDeactivated)
             }
-            AttachAppInterface { port } => {
+            AttachAppInterface {
+                port,
+                allowed_origins,
+            } => {
                 let port = port.unwrap_or(0);
                 let port = self
                     .conductor_handle
                     .clone()
-                    .add_app_interface(port)
+                    .add_app_interface(port, allowed_origins)
                     .await?;
                 Ok(AdminResponse::AppInterfaceAttached { port })
             }
@@ -239,6 +295,21 @@ impl AdminInterfaceApi for RealAdminInterfaceApi {
                 let state = self.conductor_handle.dump_cell_state(&cell_id).await?;
                 Ok(AdminResponse::StateDumped(state))
             }
+            DumpOpCounts { cell_id } => {
+                let counts = self.conductor_handle.dump_cell_op_counts(&cell_id).await?;
+                Ok(AdminResponse::OpCountsDumped(counts))
+            }
+            DumpZomeCallMetering { cell_id } => {
+                let totals = self
+                    .conductor_handle
+                    .dump_cell_zome_call_metering(&cell_id)
+                    .await?;
+                Ok(AdminResponse::ZomeCallMeteringDumped(totals))
+            }
+            DumpArcs { cell_id } => {
+                let arcs = self.conductor_handle.dump_cell_arcs(&cell_id).await?;
+                Ok(AdminResponse::ArcsDumped(arcs))
+            }
             AddAgentInfo { agent_infos } => {
                 self.conductor_handle.add_agent_infos(agent_infos).await?;
                 Ok(AdminResponse::AgentInfoAdded)
@@ -247,6 +318,10 @@ impl AdminInterfaceApi for RealAdminInterfaceApi {
                 let r = self.conductor_handle.get_agent_infos(cell_id).await?;
                 Ok(AdminResponse::AgentInfoRequested(r))
             }
+            RequestVersion => Ok(AdminResponse::VersionRequested(ConductorVersion {
+                holochain: env!("CARGO_PKG_VERSION").to_string(),
+                conductor_api: CONDUCTOR_API_VERSION.to_string(),
+            })),
         }
     }
 }
@@ -421,6 +496,51 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test(threaded_scheduler)]
+    async fn validate_dna_without_registering_it() -> Result<()> {
+        observability::test_run().ok();
+        let envs = test_environments();
+        let handle = Conductor::builder().test(&envs).await?;
+        let shutdown = handle.take_shutdown_handle().await.unwrap();
+        let admin_api = RealAdminInterfaceApi::new(handle.clone());
+        let uuid = Uuid::new_v4();
+        let dna = fake_dna_zomes(
+            &uuid.to_string(),
+            vec![(TestWasm::Foo.into(), TestWasm::Foo.into())],
+        );
+        let dna_hash = dna.dna_hash().clone();
+        let payload = |dna| RegisterDnaPayload {
+            uuid: None,
+            properties: None,
+            source: DnaSource::DnaFile(dna),
+        };
+
+        let response = admin_api
+            .handle_admin_request(AdminRequest::ValidateDna(Box::new(payload(dna))))
+            .await;
+        assert_matches!(response, AdminResponse::DnaValidated(h) if h == dna_hash);
+        let dna_list = admin_api.handle_admin_request(AdminRequest::ListDnas).await;
+        assert_matches!(dna_list, AdminResponse::DnasListed(a) if a.is_empty());
+
+        // Wasm that doesn't load is an error.
+        let broken = fake_dna_zomes(
+            &uuid.to_string(),
+            vec![("broken".into(), DnaWasm::from(vec![0, 1, 2, 3]))],
+        );
+        let response = admin_api
+            .handle_admin_request(AdminRequest::ValidateDna(Box::new(payload(broken))))
+            .await;
+        assert_matches!(response, AdminResponse::Error(_));
+        let dna_list = admin_api.handle_admin_request(AdminRequest::ListDnas).await;
+        assert_matches!(dna_list, AdminResponse::DnasListed(a) if a.is_empty());
+
+        handle.shutdown().await;
+        tokio::time::timeout(std::time::Duration::from_secs(1), shutdown)
+            .await
+            .ok();
+        Ok(())
+    }
+
     #[tokio::test(threaded_scheduler)]
     async fn install_list_dna_app() -> Result<()> {
         observability::test_run().ok();
@@ -475,6 +595,10 @@ mod test {
             install_response,
             AdminResponse::AppInstalled(cell_ids) if cell_ids == expected_cell_ids
         );
+        let res = admin_api
+            .handle_admin_request(AdminRequest::ListInactiveApps)
+            .await;
+        assert_matches!(res, AdminResponse::InactiveAppsListed(v) if v == vec![expected_cell_ids.clone()]);
         let dna_list = admin_api.handle_admin_request(AdminRequest::ListDnas).await;
         let expects = vec![dna_hash.clone()];
         assert_matches!(dna_list, AdminResponse::DnasListed(a) if a == expects);
@@ -486,6 +610,11 @@ mod test {
             .await;
         assert_matches!(res, AdminResponse::AppActivated);
 
+        let res = admin_api
+            .handle_admin_request(AdminRequest::ListInactiveApps)
+            .await;
+        assert_matches!(res, AdminResponse::InactiveAppsListed(v) if v.is_empty());
+
         let res = admin_api
             .handle_admin_request(AdminRequest::ListCellIds)
             .await;
@@ -516,6 +645,145 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test(threaded_scheduler)]
+    async fn install_app_with_existing_cells() -> Result<()> {
+        observability::test_run().ok();
+        let envs = test_environments();
+        let handle = Conductor::builder().test(&envs).await?;
+        let shutdown = handle.take_shutdown_handle().await.unwrap();
+        let admin_api = RealAdminInterfaceApi::new(handle.clone());
+        let dna = fake_dna_zomes(
+            &Uuid::new_v4().to_string(),
+            vec![(TestWasm::Foo.into(), TestWasm::Foo.into())],
+        );
+        let (dna_path, _tempdir) = write_fake_dna_file(dna.clone()).await.unwrap();
+        let agent_key = fake_agent_pubkey_1();
+        let cell_id = CellId::new(dna.dna_hash().clone(), agent_key.clone());
+        let install = |installed_app_id: &str, dnas| {
+            AdminRequest::InstallApp(Box::new(InstallAppPayload {
+                dnas,
+                installed_app_id: installed_app_id.to_string(),
+                agent_key: agent_key.clone(),
+            }))
+        };
+
+        let res = admin_api
+            .handle_admin_request(install(
+                "base",
+                vec![InstallAppDnaPayload::path_only(
+                    dna_path,
+                    "base".to_string(),
+                )],
+            ))
+            .await;
+        assert_matches!(res, AdminResponse::AppInstalled(_));
+
+        // The cell doesn't get a second genesis and is shared by both apps.
+        let res = admin_api
+            .handle_admin_request(install(
+                "composed",
+                vec![InstallAppDnaPayload::existing(
+                    cell_id.clone(),
+                    "shared".to_string(),
+                )],
+            ))
+            .await;
+        assert_matches!(
+            res,
+            AdminResponse::AppInstalled(app)
+                if app.cell_data == vec![InstalledCell::new(cell_id.clone(), "shared".to_string())]
+        );
+        for installed_app_id in &["base", "composed"] {
+            let res = admin_api
+                .handle_admin_request(AdminRequest::ActivateApp {
+                    installed_app_id: installed_app_id.to_string(),
+                })
+                .await;
+            assert_matches!(res, AdminResponse::AppActivated);
+        }
+        let res = admin_api
+            .handle_admin_request(AdminRequest::ListCellIds)
+            .await;
+        assert_matches!(res, AdminResponse::CellIdsListed(v) if v == vec![cell_id.clone()]);
+
+        // Deactivating one app keeps the cell the other app still uses.
+        let deactivate = |installed_app_id: &str| AdminRequest::DeactivateApp {
+            installed_app_id: installed_app_id.to_string(),
+        };
+        let res = admin_api.handle_admin_request(deactivate("base")).await;
+        assert_matches!(res, AdminResponse::AppDeactivated);
+        let res = admin_api
+            .handle_admin_request(AdminRequest::ListCellIds)
+            .await;
+        assert_matches!(res, AdminResponse::CellIdsListed(v) if v == vec![cell_id.clone()]);
+
+        // The cell is removed with the last app that uses it.
+        let res = admin_api.handle_admin_request(deactivate("composed")).await;
+        assert_matches!(res, AdminResponse::AppDeactivated);
+        let res = admin_api
+            .handle_admin_request(AdminRequest::ListCellIds)
+            .await;
+        assert_matches!(res, AdminResponse::CellIdsListed(v) if v.is_empty());
+
+        // The cell must have the dna the app requires.
+        let other_dna = fake_dna_zomes(
+            &Uuid::new_v4().to_string(),
+            vec![(TestWasm::Foo.into(), TestWasm::Foo.into())],
+        );
+        let mut payload = InstallAppDnaPayload::existing(cell_id.clone(), "shared".to_string());
+        payload.hash = Some(other_dna.dna_hash().clone());
+        let res = admin_api
+            .handle_admin_request(install("mismatched", vec![payload]))
+            .await;
+        assert_matches!(
+            res,
+            AdminResponse::Error(ExternalApiWireError::DnaReadError(e)) if e.contains("but the app requires dna")
+        );
+
+        // The cell must be installed.
+        let missing = CellId::new(dna.dna_hash().clone(), fake_agent_pubkey_2());
+        let res = admin_api
+            .handle_admin_request(install(
+                "missing",
+                vec![InstallAppDnaPayload::existing(
+                    missing,
+                    "shared".to_string(),
+                )],
+            ))
+            .await;
+        assert_matches!(res, AdminResponse::Error(_));
+
+        handle.shutdown().await;
+        tokio::time::timeout(std::time::Duration::from_secs(1), shutdown)
+            .await
+            .ok();
+        Ok(())
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn request_version() -> Result<()> {
+        observability::test_run().ok();
+        let envs = test_environments();
+        let handle = Conductor::builder().test(&envs).await?;
+        let shutdown = handle.take_shutdown_handle().await.unwrap();
+        let admin_api = RealAdminInterfaceApi::new(handle.clone());
+
+        let res = admin_api
+            .handle_admin_request(AdminRequest::RequestVersion)
+            .await;
+        assert_matches!(
+            res,
+            AdminResponse::VersionRequested(v)
+                if v.holochain == env!("CARGO_PKG_VERSION") && v.conductor_api == CONDUCTOR_API_VERSION
+        );
+
+        handle.shutdown().await;
+        tokio::time::timeout(std::time::Duration::from_secs(1), shutdown)
+            .await
+            .ok();
+        Ok(())
+    }
+
     #[tokio::test(threaded_scheduler)]
     async fn dna_read_parses() -> Result<()> {
         let uuid = Uuid::new_v4();
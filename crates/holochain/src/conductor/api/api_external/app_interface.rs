@@ -6,6 +6,8 @@ use crate::conductor::interface::error::InterfaceError;
 use crate::conductor::interface::error::InterfaceResult;
 use crate::conductor::state::AppInterfaceId;
 use crate::conductor::ConductorHandle;
+use crate::core::ribosome::HostFnTrace;
+use crate::core::workflow::ZomeCallResult;
 
 use holochain_serialized_bytes::prelude::*;
 
@@ -82,20 +84,23 @@ impl AppInterfaceApi for RealAppInterfaceApi {
                     })
             }
             AppRequest::ZomeCall(call) => {
-                match self.conductor_handle.call_zome(*call.clone()).await? {
-                    Ok(ZomeCallResponse::Ok(output)) => Ok(AppResponse::ZomeCall(Box::new(output))),
-                    Ok(ZomeCallResponse::Unauthorized(_, _, _, _)) => Ok(AppResponse::Error(
-                        ExternalApiWireError::ZomeCallUnauthorized(format!(
-                            "No capabilities grant has been committed that allows the CapSecret {:?} to call the function {} in zome {}",
-                            call.cap, call.fn_name, call.zome_name
-                        )),
-                    )),
-                    Ok(ZomeCallResponse::NetworkError(e)) => unreachable!(
-                        "Interface zome calls should never be routed to the network. This is a bug. Got {}",
-                        e
-                    ),
-                    Err(e) => Ok(AppResponse::Error(e.into())),
-                }
+                let result = self.conductor_handle.call_zome(*call.clone()).await?;
+                Ok(zome_call_response(&call, result, |output| {
+                    AppResponse::ZomeCall(Box::new(output))
+                }))
+            }
+            AppRequest::ZomeCallTraced(call) => {
+                let host_fn_trace = HostFnTrace::new();
+                let result = self
+                    .conductor_handle
+                    .call_zome_traced(*call.clone(), host_fn_trace.clone())
+                    .await?;
+                Ok(zome_call_response(&call, result, |output| {
+                    AppResponse::ZomeCallTraced(Box::new(TracedZomeCall {
+                        output,
+                        host_fn_calls: host_fn_trace.calls(),
+                    }))
+                }))
             }
             AppRequest::SignalSubscription(_) => Ok(AppResponse::Unimplemented(request)),
             AppRequest::Crypto(_) => Ok(AppResponse::Unimplemented(request)),
@@ -103,6 +108,29 @@ impl AppInterfaceApi for RealAppInterfaceApi {
     }
 }
 
+/// Turn the result of an interface zome call into the response,
+/// using `ok` to wrap the output of a successful call
+fn zome_call_response(
+    call: &ZomeCall,
+    result: ZomeCallResult,
+    ok: impl FnOnce(ExternIO) -> AppResponse,
+) -> AppResponse {
+    match result {
+        Ok(ZomeCallResponse::Ok(output)) => ok(output),
+        Ok(ZomeCallResponse::Unauthorized(_, _, _, _)) => AppResponse::Error(
+            ExternalApiWireError::ZomeCallUnauthorized(format!(
+                "No capabilities grant has been committed that allows the CapSecret {:?} to call the function {} in zome {}",
+                call.cap, call.fn_name, call.zome_name
+            )),
+        ),
+        Ok(ZomeCallResponse::NetworkError(e)) => unreachable!(
+            "Interface zome calls should never be routed to the network. This is a bug. Got {}",
+            e
+        ),
+        Err(e) => AppResponse::Error(e.into()),
+    }
+}
+
 #[async_trait::async_trait]
 impl InterfaceApi for RealAppInterfaceApi {
     type ApiRequest = AppRequest;
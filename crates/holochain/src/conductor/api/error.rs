@@ -84,6 +84,10 @@ pub enum ConductorApiError {
 
     #[error(transparent)]
     SourceChainError(#[from] SourceChainError),
+
+    /// Zome call metering was asked for but is not turned on.
+    #[error("Zome call metering is not turned on. Set `zome_call_metering: true` in the conductor config to record zome calls")]
+    ZomeCallMeteringDisabled,
 }
 
 /// All the serialization errors that can occur
@@ -18,7 +18,9 @@ use crate::core::queue_consumer::InitialQueueTriggers;
 use crate::core::queue_consumer::QueueTriggers;
 use crate::core::ribosome::guest_callback::init::InitResult;
 use crate::core::ribosome::real_ribosome::RealRibosome;
+use crate::core::ribosome::HostFnTrace;
 use crate::core::ribosome::ZomeCallInvocation;
+use crate::core::ribosome::ZomeCallMeter;
 use crate::core::workflow::call_zome_workflow;
 use crate::core::workflow::error::WorkflowError;
 use crate::core::workflow::genesis_workflow::genesis_workflow;
@@ -102,6 +104,7 @@ where
     env: EnvironmentWrite,
     holochain_p2p_cell: P2pCell,
     queue_triggers: QueueTriggers,
+    zome_call_meter: Option<ZomeCallMeter>,
 }
 
 impl Cell {
@@ -142,6 +145,7 @@ impl Cell {
                     env,
                     holochain_p2p_cell,
                     queue_triggers,
+                    zome_call_meter: None,
                 },
                 initial_queue_triggers,
             ))
@@ -182,6 +186,12 @@ impl Cell {
         Ok(())
     }
 
+    /// Record the zome calls this cell makes on the `meter`.
+    pub(crate) fn metered(mut self, meter: ZomeCallMeter) -> Self {
+        self.zome_call_meter = Some(meter);
+        self
+    }
+
     fn dna_hash(&self) -> &DnaHash {
         &self.id.dna_hash()
     }
@@ -653,6 +663,26 @@ impl Cell {
         &self,
         call: ZomeCall,
         workspace_lock: Option<CallZomeWorkspaceLock>,
+    ) -> CellResult<ZomeCallResult> {
+        self.call_zome_inner(call, workspace_lock, None).await
+    }
+
+    /// Function called by the Conductor when the host functions
+    /// the zome call makes should be recorded in the `host_fn_trace`
+    #[instrument(skip(self, call, host_fn_trace))]
+    pub async fn call_zome_traced(
+        &self,
+        call: ZomeCall,
+        host_fn_trace: HostFnTrace,
+    ) -> CellResult<ZomeCallResult> {
+        self.call_zome_inner(call, None, Some(host_fn_trace)).await
+    }
+
+    async fn call_zome_inner(
+        &self,
+        call: ZomeCall,
+        workspace_lock: Option<CallZomeWorkspaceLock>,
+        host_fn_trace: Option<HostFnTrace>,
     ) -> CellResult<ZomeCallResult> {
         // Check if init has run if not run it
         self.check_or_run_zome_init().await?;
@@ -667,6 +697,20 @@ impl Cell {
             None => CallZomeWorkspaceLock::new(CallZomeWorkspace::new(arc.clone().into())?),
         };
 
+        // a metered call needs a trace to count its host function calls
+        let host_fn_trace = match (&self.zome_call_meter, host_fn_trace) {
+            (Some(_), None) => Some(HostFnTrace::new()),
+            (_, host_fn_trace) => host_fn_trace,
+        };
+        let metered = self.zome_call_meter.clone().map(|meter| {
+            (
+                meter,
+                call.zome_name.clone(),
+                call.fn_name.clone(),
+                host_fn_trace.clone(),
+            )
+        });
+
         let conductor_api = self.conductor_api.clone();
         let signal_tx = self.signal_broadcaster().await;
         let ribosome = self.get_ribosome().await?;
@@ -678,8 +722,10 @@ impl Cell {
             conductor_api,
             signal_tx,
             is_root_zome_call,
+            host_fn_trace,
         };
-        Ok(call_zome_workflow(
+        let start = std::time::Instant::now();
+        let result = call_zome_workflow(
             workspace_lock,
             self.holochain_p2p_cell.clone(),
             keystore,
@@ -687,8 +733,18 @@ impl Cell {
             args,
             self.queue_triggers.produce_dht_ops.clone(),
         )
-        .await
-        .map_err(Box::new)?)
+        .await;
+        if let Some((meter, zome_name, fn_name, host_fn_trace)) = metered {
+            let host_fn_calls = host_fn_trace.map_or(0, |t| t.calls().len());
+            meter.record(
+                &self.id,
+                &zome_name,
+                &fn_name,
+                start.elapsed(),
+                host_fn_calls,
+            );
+        }
+        Ok(result.map_err(Box::new)?)
     }
 
     /// Check if each Zome's init callback has been run, and if not, run it.
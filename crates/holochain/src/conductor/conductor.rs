@@ -12,6 +12,7 @@ use super::api::RealAdminInterfaceApi;
 use super::api::RealAppInterfaceApi;
 use super::config::AdminInterfaceConfig;
 use super::config::InterfaceDriver;
+use super::config::StaticAppInterfaceConfig;
 use super::dna_store::DnaDefBuf;
 use super::dna_store::DnaStore;
 use super::dna_store::RealDnaStore;
@@ -24,6 +25,7 @@ use super::handle::ConductorHandleImpl;
 use super::interface::error::InterfaceResult;
 use super::interface::websocket::spawn_admin_interface_task;
 use super::interface::websocket::spawn_app_interface_task;
+use super::interface::websocket::spawn_unix_socket_listener;
 use super::interface::websocket::spawn_websocket_listener;
 use super::interface::websocket::SIGNAL_BUFFER_SIZE;
 use super::interface::SignalBroadcaster;
@@ -41,6 +43,7 @@ use super::state::ConductorState;
 use super::CellError;
 use super::{api::CellConductorApi, state::AppInterfaceConfig};
 use super::{api::CellConductorApiT, interface::AppInterfaceRuntime};
+use crate::conductor::api::error::ConductorApiError;
 use crate::conductor::api::error::ConductorApiResult;
 use crate::conductor::cell::Cell;
 use crate::conductor::config::ConductorConfig;
@@ -48,12 +51,16 @@ use crate::conductor::dna_store::MockDnaStore;
 use crate::conductor::error::ConductorResult;
 use crate::conductor::handle::ConductorHandle;
 use crate::core::queue_consumer::InitialQueueTriggers;
+use crate::core::ribosome::ZomeCallMeter;
 pub use builder::*;
 use fallible_iterator::FallibleIterator;
 use futures::future;
 use futures::future::TryFutureExt;
 use futures::stream::StreamExt;
 use holo_hash::DnaHash;
+use holochain_conductor_api::AgentArc;
+use holochain_conductor_api::OpCounts;
+use holochain_conductor_api::ZomeFnMetering;
 use holochain_keystore::lair_keystore::spawn_lair_keystore;
 use holochain_keystore::test_keystore::spawn_test_keystore;
 use holochain_keystore::KeystoreSender;
@@ -65,13 +72,21 @@ use holochain_lmdb::db;
 use holochain_lmdb::env::EnvironmentKind;
 use holochain_lmdb::env::EnvironmentWrite;
 use holochain_lmdb::env::ReadManager;
+use holochain_lmdb::env::SyncLevel;
 use holochain_lmdb::exports::SingleStore;
 use holochain_lmdb::fresh_reader;
 use holochain_lmdb::prelude::*;
+use holochain_state::dht_op_integration::IntegratedDhtOpsStore;
+use holochain_state::dht_op_integration::IntegrationLimboStore;
 use holochain_state::source_chain::SourceChainBuf;
+use holochain_state::validation_db::ValidationLimboStatus;
+use holochain_state::validation_db::ValidationLimboStore;
 use holochain_state::wasm::WasmBuf;
 use holochain_types::prelude::*;
 use kitsune_p2p::agent_store::AgentInfoSigned;
+use kitsune_p2p::dht_arc::DhtArc;
+use kitsune_p2p::dht_arc::MAX_HALF_LENGTH;
+use kitsune_p2p::KitsuneBinType;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::mpsc;
@@ -132,6 +147,9 @@ where
     /// the dynamically allocated port later.
     admin_websocket_ports: Vec<u16>,
 
+    /// The unix socket paths of the admin interfaces this conductor has open.
+    admin_unix_sockets: Vec<std::path::PathBuf>,
+
     /// Collection app interface data, keyed by id
     app_interfaces: HashMap<AppInterfaceId, AppInterfaceRuntime>,
 
@@ -154,8 +172,22 @@ where
     /// The root environment directory where all environments are created
     root_env_dir: EnvironmentRootPath,
 
+    /// How much the environments of this conductor flush to disk.
+    db_sync_level: SyncLevel,
+
     /// Handle to the network actor.
     holochain_p2p: holochain_p2p::HolochainP2pRef,
+
+    /// Totals of the zome calls made on each cell,
+    /// if zome call metering is turned on in the config.
+    zome_call_meter: Option<ZomeCallMeter>,
+
+    /// The address websocket interfaces listen on, if the config sets one.
+    interface_address: Option<std::net::IpAddr>,
+
+    /// The app interfaces in the config, which are started
+    /// on every startup and never kept in the state.
+    config_app_interfaces: Vec<StaticAppInterfaceConfig>,
 }
 
 impl Conductor {
@@ -246,22 +278,41 @@ where
     {
         let admin_api = RealAdminInterfaceApi::new(handle);
         let stop_tx = self.managed_task_stop_broadcaster.clone();
+        let address = self.interface_address;
 
         // Closure to process each admin config item
-        let spawn_from_config = |AdminInterfaceConfig { driver, .. }| {
+        let spawn_from_config = |AdminInterfaceConfig {
+                                     driver,
+                                     allowed_origins,
+                                     address: own_address,
+                                 }| {
             let admin_api = admin_api.clone();
             let stop_tx = stop_tx.clone();
             async move {
                 match driver {
                     InterfaceDriver::Websocket { port } => {
-                        let listener = spawn_websocket_listener(port).await?;
+                        let listener = spawn_websocket_listener(
+                            own_address.or(address),
+                            port,
+                            allowed_origins,
+                        )
+                        .await?;
                         let port = listener.local_addr().port().unwrap_or(port);
                         let handle: ManagedTaskHandle = spawn_admin_interface_task(
                             listener,
                             admin_api.clone(),
                             stop_tx.subscribe(),
                         )?;
-                        InterfaceResult::Ok((port, handle))
+                        InterfaceResult::Ok((InterfaceDriver::Websocket { port }, handle))
+                    }
+                    InterfaceDriver::UnixSocket { path } => {
+                        let listener = spawn_unix_socket_listener(&path, allowed_origins).await?;
+                        let handle: ManagedTaskHandle = spawn_admin_interface_task(
+                            listener,
+                            admin_api.clone(),
+                            stop_tx.subscribe(),
+                        )?;
+                        InterfaceResult::Ok((InterfaceDriver::UnixSocket { path }, handle))
                     }
                 }
             }
@@ -278,7 +329,7 @@ where
         let handles = handles.map_err(Box::new)?;
 
         {
-            let mut ports = Vec::new();
+            let mut drivers = Vec::new();
 
             // First, register the keepalive task, to ensure the conductor doesn't shut down
             // in the absence of other "real" tasks
@@ -288,8 +339,8 @@ where
             .await?;
 
             // Now that tasks are spawned, register them with the TaskManager
-            for (port, handle) in handles {
-                ports.push(port);
+            for (driver, handle) in handles {
+                drivers.push(driver);
                 self.manage_task(ManagedTaskAdd::new(
                     handle,
                     Box::new(|result| {
@@ -301,8 +352,11 @@ where
                 ))
                 .await?
             }
-            for p in ports {
-                self.add_admin_port(p);
+            for driver in drivers {
+                match driver {
+                    InterfaceDriver::Websocket { port } => self.add_admin_port(port),
+                    InterfaceDriver::UnixSocket { path } => self.add_admin_unix_socket(path),
+                }
             }
         }
         Ok(())
@@ -311,17 +365,48 @@ where
     pub(super) async fn add_app_interface_via_handle(
         &mut self,
         port: u16,
+        allowed_origins: Option<Vec<String>>,
         handle: ConductorHandle,
     ) -> ConductorResult<u16> {
         let interface_id: AppInterfaceId = format!("interface-{}", port).into();
+        let port = self
+            .spawn_app_interface(interface_id.clone(), port, allowed_origins.clone(), handle)
+            .await?;
+        let config = AppInterfaceConfig {
+            allowed_origins,
+            ..AppInterfaceConfig::websocket(port)
+        };
+        self.update_state(|mut state| {
+            state.app_interfaces.insert(interface_id, config);
+            Ok(state)
+        })
+        .await?;
+        Ok(port)
+    }
+
+    /// Start an app interface without adding it to the state.
+    async fn spawn_app_interface(
+        &mut self,
+        interface_id: AppInterfaceId,
+        port: u16,
+        allowed_origins: Option<Vec<String>>,
+        handle: ConductorHandle,
+    ) -> ConductorResult<u16> {
         let app_api = RealAppInterfaceApi::new(handle, interface_id.clone());
         // This receiver is thrown away because we can produce infinite new
         // receivers from the Sender
         let (signal_tx, _r) = tokio::sync::broadcast::channel(SIGNAL_BUFFER_SIZE);
         let stop_rx = self.managed_task_stop_broadcaster.subscribe();
-        let (port, task) = spawn_app_interface_task(port, app_api, signal_tx.clone(), stop_rx)
-            .await
-            .map_err(Box::new)?;
+        let (port, task) = spawn_app_interface_task(
+            self.interface_address,
+            port,
+            allowed_origins,
+            app_api,
+            signal_tx.clone(),
+            stop_rx,
+        )
+        .await
+        .map_err(Box::new)?;
         // TODO: RELIABILITY: Handle this task by restarting it if it fails and log the error
         self.manage_task(ManagedTaskAdd::dont_handle(task)).await?;
         let interface = AppInterfaceRuntime::Websocket { signal_tx };
@@ -330,32 +415,41 @@ where
             return Err(ConductorError::AppInterfaceIdCollision(interface_id));
         }
 
-        self.app_interfaces.insert(interface_id.clone(), interface);
-        let config = AppInterfaceConfig::websocket(port);
-        self.update_state(|mut state| {
-            state.app_interfaces.insert(interface_id, config);
-            Ok(state)
-        })
-        .await?;
+        self.app_interfaces.insert(interface_id, interface);
         Ok(port)
     }
 
-    /// Start all app interfaces currently in state.
+    /// Start the app interfaces in the config and then
+    /// all app interfaces currently in state.
+    /// An interface in state on the same port as one in the config is skipped.
     /// This should only be run at conductor initialization.
-    #[allow(irrefutable_let_patterns)]
     pub(super) async fn startup_app_interfaces_via_handle(
         &mut self,
         handle: ConductorHandle,
     ) -> ConductorResult<()> {
-        for i in self.get_state().await?.app_interfaces.values() {
+        for i in self.config_app_interfaces.clone() {
+            tracing::debug!("Starting up app interface from the config: {:?}", i);
+            let interface_id: AppInterfaceId = format!("interface-{}", i.port).into();
+            let _ = self
+                .spawn_app_interface(interface_id, i.port, i.allowed_origins, handle.clone())
+                .await?;
+        }
+        for (id, i) in self.get_state().await?.app_interfaces.iter() {
+            if self.app_interfaces.contains_key(id) {
+                tracing::warn!(
+                    "Not starting app interface {:?} because the config has one on the same port",
+                    id
+                );
+                continue;
+            }
             tracing::debug!("Starting up app interface: {:?}", i);
             let port = if let InterfaceDriver::Websocket { port } = i.driver {
                 port
             } else {
-                unreachable!()
+                unreachable!("App interfaces are only added with a websocket port")
             };
             let _ = self
-                .add_app_interface_via_handle(port, handle.clone())
+                .add_app_interface_via_handle(port, i.allowed_origins.clone(), handle.clone())
                 .await?;
         }
         Ok(())
@@ -382,6 +476,7 @@ where
     ) -> ConductorResult<()> {
         let root_env_dir = std::path::PathBuf::from(self.root_env_dir.clone());
         let keystore = self.keystore.clone();
+        let sync_level = self.db_sync_level;
 
         let cells_tasks = cell_ids_with_proofs.into_iter().map(|(cell_id, proof)| {
             let root_env_dir = root_env_dir.clone();
@@ -389,10 +484,11 @@ where
             let conductor_handle = conductor_handle.clone();
             let cell_id_inner = cell_id.clone();
             tokio::spawn(async move {
-                let env = EnvironmentWrite::new(
+                let env = EnvironmentWrite::new_with_sync_level(
                     &root_env_dir,
                     EnvironmentKind::Cell(cell_id_inner.clone()),
                     keystore.clone(),
+                    sync_level,
                 )?;
                 Cell::genesis(cell_id_inner, conductor_handle, env, proof).await
             })
@@ -410,10 +506,11 @@ where
         // If there were errors, cleanup and return the errors
         if !errors.is_empty() {
             for cell_id in success {
-                let env = EnvironmentWrite::new(
+                let env = EnvironmentWrite::new_with_sync_level(
                     &root_env_dir,
                     EnvironmentKind::Cell(cell_id),
                     keystore.clone(),
+                    sync_level,
                 )?;
                 env.remove().await?;
             }
@@ -445,6 +542,7 @@ where
         // Data required to create apps
         let root_env_dir = self.root_env_dir.clone();
         let keystore = self.keystore.clone();
+        let sync_level = self.db_sync_level;
 
         // Closure for creating all cells in an app
         let tasks = active_apps.into_iter().map(
@@ -480,10 +578,11 @@ where
                                 cell_id.agent_pubkey().clone(),
                             );
 
-                            let env = EnvironmentWrite::new_cell(
+                            let env = EnvironmentWrite::new_with_sync_level(
                                 &dir,
-                                cell_id.clone(),
+                                EnvironmentKind::Cell(cell_id.clone()),
                                 keystore.clone(),
+                                sync_level,
                             )?;
                             Cell::create(
                                 cell_id.clone(),
@@ -582,7 +681,8 @@ where
         Ok(())
     }
 
-    /// Deactivate an app in the database
+    /// Deactivate an app in the database.
+    /// Returns the app's cells that no other active app uses.
     pub(super) async fn deactivate_app_in_db(
         &mut self,
         installed_app_id: InstalledAppId,
@@ -607,12 +707,17 @@ where
             .clone()
             .into_iter()
             .map(|c| c.into_id())
+            .filter(|cell_id| !state.is_cell_active(cell_id))
             .collect())
     }
 
     /// Add fully constructed cells to the cell map in the Conductor
     pub(super) fn add_cells(&mut self, cells: Vec<(Cell, InitialQueueTriggers)>) {
         for (cell, trigger) in cells {
+            let cell = match &self.zome_call_meter {
+                Some(meter) => cell.metered(meter.clone()),
+                None => cell,
+            };
             let cell_id = cell.id().clone();
             tracing::info!(?cell_id, "ADD CELL");
             self.cells.insert(
@@ -751,6 +856,34 @@ where
         Ok(active_apps.keys().cloned().collect())
     }
 
+    pub(super) async fn list_inactive_apps(&self) -> ConductorResult<Vec<InstalledApp>> {
+        let inactive_apps = self.get_state().await?.inactive_apps;
+        Ok(inactive_apps
+            .into_iter()
+            .map(|(installed_app_id, cell_data)| InstalledApp {
+                installed_app_id,
+                cell_data,
+            })
+            .collect())
+    }
+
+    pub(super) async fn list_installed_cell_ids(&self) -> ConductorResult<Vec<CellId>> {
+        let state = self.get_state().await?;
+        let mut cell_ids: Vec<CellId> = Vec::new();
+        // A cell can be in more than one app.
+        for cell in state
+            .active_apps
+            .values()
+            .chain(state.inactive_apps.values())
+            .flatten()
+        {
+            if !cell_ids.contains(cell.as_id()) {
+                cell_ids.push(cell.as_id().clone());
+            }
+        }
+        Ok(cell_ids)
+    }
+
     pub(super) async fn dump_cell_state(&self, cell_id: &CellId) -> ConductorApiResult<String> {
         let cell = self.cell_by_id(cell_id)?;
         let arc = cell.env();
@@ -758,6 +891,47 @@ where
         Ok(source_chain.dump_as_json().await?)
     }
 
+    pub(super) fn dump_cell_op_counts(&self, cell_id: &CellId) -> ConductorApiResult<OpCounts> {
+        let cell = self.cell_by_id(cell_id)?;
+        Ok(count_dht_ops(cell.env().clone().into())?)
+    }
+
+    pub(super) fn dump_cell_arcs(&self, cell_id: &CellId) -> ConductorApiResult<Vec<AgentArc>> {
+        self.cell_by_id(cell_id)?;
+        let space = holochain_p2p::space_holo_to_kit(cell_id.dna_hash().clone());
+        let mut arcs = Vec::new();
+        for signed in all_agent_infos(self.p2p_env.clone().into())? {
+            let info = kitsune_p2p::agent_store::AgentInfo::try_from(&signed)
+                .map_err(ConductorError::from)?;
+            if *info.as_space_ref() != space {
+                continue;
+            }
+            let agent = info.as_agent_ref();
+            // Agents don't sign their arc into their agent info yet
+            // and gossip is a full sync, so every agent holds the
+            // whole dht around its own location.
+            let arc = DhtArc::new(agent.get_loc(), MAX_HALF_LENGTH);
+            arcs.push(AgentArc {
+                agent: AgentPubKey::from_raw_36(agent.0.clone()),
+                arc,
+            });
+        }
+        arcs.sort_by_key(|a| u32::from(a.arc.center_loc));
+        Ok(arcs)
+    }
+
+    pub(super) fn dump_cell_zome_call_metering(
+        &self,
+        cell_id: &CellId,
+    ) -> ConductorApiResult<Vec<ZomeFnMetering>> {
+        self.cell_by_id(cell_id)?;
+        let meter = self
+            .zome_call_meter
+            .as_ref()
+            .ok_or(ConductorApiError::ZomeCallMeteringDisabled)?;
+        Ok(meter.totals(cell_id))
+    }
+
     pub(super) fn p2p_env(&self) -> EnvironmentWrite {
         self.p2p_env.clone()
     }
@@ -768,6 +942,10 @@ where
         for port in &self.admin_websocket_ports {
             writeln!(&mut out, "###ADMIN_PORT:{}###", port).expect("Can't write setup to std out");
         }
+        for path in &self.admin_unix_sockets {
+            writeln!(&mut out, "###ADMIN_SOCKET:{}###", path.display())
+                .expect("Can't write setup to std out");
+        }
         println!("\n###HOLOCHAIN_SETUP###\n{}###HOLOCHAIN_SETUP_END###", out);
     }
 
@@ -793,6 +971,47 @@ where
     }
 }
 
+/// Count the ops in a cell's validation limbo, integration limbo
+/// and integrated stores by op type.
+fn count_dht_ops(env: EnvironmentRead) -> DatabaseResult<OpCounts> {
+    let validation_limbo = ValidationLimboStore::new(env.clone())?;
+    let integration_limbo: IntegrationLimboStore =
+        KvBufFresh::new(env.clone(), env.get_db(&*db::INTEGRATION_LIMBO)?);
+    let integrated: IntegratedDhtOpsStore =
+        KvBufFresh::new(env.clone(), env.get_db(&*db::INTEGRATED_DHT_OPS)?);
+    let mut counts = OpCounts::default();
+    fresh_reader!(env, |r| {
+        validation_limbo.iter(&r)?.for_each(|(_, v)| {
+            let c = counts.by_type.entry(v.op.to_string()).or_default();
+            match v.status {
+                ValidationLimboStatus::Pending => c.pending += 1,
+                ValidationLimboStatus::AwaitingSysDeps(_)
+                | ValidationLimboStatus::AwaitingAppDeps(_) => c.awaiting_deps += 1,
+                ValidationLimboStatus::SysValidated => c.sys_validated += 1,
+            }
+            Ok(())
+        })?;
+        integration_limbo.iter(&r)?.for_each(|(_, v)| {
+            counts
+                .by_type
+                .entry(v.op.to_string())
+                .or_default()
+                .awaiting_integration += 1;
+            Ok(())
+        })?;
+        integrated.iter(&r)?.for_each(|(_, v)| {
+            let c = counts.by_type.entry(v.op.to_string()).or_default();
+            match v.validation_status {
+                ValidationStatus::Valid => c.integrated += 1,
+                ValidationStatus::Rejected | ValidationStatus::Abandoned => c.rejected += 1,
+            }
+            Ok(())
+        })?;
+        DatabaseResult::Ok(())
+    })?;
+    Ok(counts)
+}
+
 //-----------------------------------------------------------------------------
 // Private methods
 //-----------------------------------------------------------------------------
@@ -826,10 +1045,15 @@ where
             managed_task_stop_broadcaster: stop_tx,
             task_manager_run_handle,
             admin_websocket_ports: Vec::new(),
+            admin_unix_sockets: Vec::new(),
             dna_store,
             keystore,
             root_env_dir,
+            db_sync_level: SyncLevel::Normal,
             holochain_p2p,
+            zome_call_meter: None,
+            interface_address: None,
+            config_app_interfaces: Vec::new(),
         })
     }
 
@@ -858,6 +1082,10 @@ where
         self.admin_websocket_ports.push(port);
     }
 
+    fn add_admin_unix_socket(&mut self, path: std::path::PathBuf) {
+        self.admin_unix_sockets.push(path);
+    }
+
     /// Sends a JoinHandle to the TaskManager task to be managed
     async fn manage_task(&mut self, handle: ManagedTaskAdd) -> ConductorResult<()> {
         self.managed_task_add_sender
@@ -872,6 +1100,7 @@ pub type ConductorStateDb = KvStore<UnitDbKey, ConductorState>;
 
 mod builder {
     use super::*;
+    use crate::conductor::config::DbSyncLevel;
     use crate::conductor::dna_store::RealDnaStore;
     use crate::conductor::ConductorHandle;
     use holochain_lmdb::env::EnvironmentKind;
@@ -912,6 +1141,15 @@ mod builder {
         }
     }
 
+    /// The LMDB sync level of the config's [DbSyncLevel].
+    fn lmdb_sync_level(level: DbSyncLevel) -> SyncLevel {
+        match level {
+            DbSyncLevel::Normal => SyncLevel::Normal,
+            DbSyncLevel::Fast => SyncLevel::Fast,
+            DbSyncLevel::Off => SyncLevel::Off,
+        }
+    }
+
     impl<DS> ConductorBuilder<DS>
     where
         DS: DnaStore + 'static,
@@ -954,18 +1192,28 @@ mod builder {
                 spawn_lair_keystore(self.config.keystore_path.as_deref()).await?
             };
             let env_path = self.config.environment_path.clone();
+            let sync_level = lmdb_sync_level(self.config.db_sync_level);
 
-            let environment = EnvironmentWrite::new(
+            let environment = EnvironmentWrite::new_with_sync_level(
                 env_path.as_ref(),
                 EnvironmentKind::Conductor,
                 keystore.clone(),
+                sync_level,
             )?;
 
-            let wasm_environment =
-                EnvironmentWrite::new(env_path.as_ref(), EnvironmentKind::Wasm, keystore.clone())?;
+            let wasm_environment = EnvironmentWrite::new_with_sync_level(
+                env_path.as_ref(),
+                EnvironmentKind::Wasm,
+                keystore.clone(),
+                sync_level,
+            )?;
 
-            let p2p_environment =
-                EnvironmentWrite::new(env_path.as_ref(), EnvironmentKind::P2p, keystore.clone())?;
+            let p2p_environment = EnvironmentWrite::new_with_sync_level(
+                env_path.as_ref(),
+                EnvironmentKind::P2p,
+                keystore.clone(),
+                sync_level,
+            )?;
 
             #[cfg(any(test, feature = "test_utils"))]
             let state = self.state;
@@ -1007,10 +1255,17 @@ mod builder {
         }
 
         async fn finish(
-            conductor: Conductor<DS>,
+            mut conductor: Conductor<DS>,
             conductor_config: ConductorConfig,
             p2p_evt: holochain_p2p::event::HolochainP2pEventReceiver,
         ) -> ConductorResult<ConductorHandle> {
+            if conductor_config.zome_call_metering {
+                conductor.zome_call_meter = Some(ZomeCallMeter::new());
+            }
+            conductor.interface_address = conductor_config.interface_address;
+            conductor.db_sync_level = lmdb_sync_level(conductor_config.db_sync_level);
+            conductor.config_app_interfaces = conductor_config.app_interfaces.unwrap_or_default();
+
             // Get data before handle
             let keystore = conductor.keystore.clone();
             let holochain_p2p = conductor.holochain_p2p.clone();
@@ -2,6 +2,7 @@ use super::Conductor;
 use super::ConductorState;
 use super::*;
 use crate::conductor::dna_store::MockDnaStore;
+use holochain_conductor_api::OpStatusCounts;
 use holochain_lmdb::test_utils::test_environments;
 use holochain_types::test_utils::fake_cell_id;
 use matches::assert_matches;
@@ -96,6 +97,106 @@ async fn app_ids_are_unique() {
     );
 }
 
+#[tokio::test(threaded_scheduler)]
+async fn op_counts_cover_each_store() {
+    use ::fixt::prelude::*;
+    use holo_hash::fixt::*;
+    use holochain_state::dht_op_integration::IntegratedDhtOpsValue;
+    use holochain_state::dht_op_integration::IntegrationLimboValue;
+    use holochain_state::validation_db::ValidationLimboValue;
+
+    let test_env = holochain_lmdb::test_utils::test_cell_env();
+    let env = test_env.env();
+    let activity = || DhtOpLight::RegisterAgentActivity(fixt!(HeaderHash), fixt!(AnyDhtHash));
+    let store_entry =
+        || DhtOpLight::StoreEntry(fixt!(HeaderHash), fixt!(EntryHash), fixt!(AnyDhtHash));
+    let limbo = |status, op| ValidationLimboValue {
+        status,
+        op,
+        basis: fixt!(AnyDhtHash),
+        time_added: timestamp::now(),
+        last_try: None,
+        num_tries: 0,
+        from_agent: None,
+    };
+    let integrated_value = |validation_status, op| IntegratedDhtOpsValue {
+        validation_status,
+        op,
+        when_integrated: timestamp::now(),
+    };
+
+    let mut validation_limbo = ValidationLimboStore::new(env.clone().into()).unwrap();
+    let mut integration_limbo: IntegrationLimboStore = KvBufFresh::new(
+        env.clone().into(),
+        env.get_db(&*db::INTEGRATION_LIMBO).unwrap(),
+    );
+    let mut integrated: IntegratedDhtOpsStore = KvBufFresh::new(
+        env.clone().into(),
+        env.get_db(&*db::INTEGRATED_DHT_OPS).unwrap(),
+    );
+    for status in vec![
+        ValidationLimboStatus::Pending,
+        ValidationLimboStatus::AwaitingSysDeps(fixt!(AnyDhtHash)),
+        ValidationLimboStatus::AwaitingAppDeps(vec![fixt!(AnyDhtHash)]),
+        ValidationLimboStatus::SysValidated,
+    ] {
+        validation_limbo
+            .put(fixt!(DhtOpHash), limbo(status, activity()))
+            .unwrap();
+    }
+    integration_limbo
+        .put(
+            fixt!(DhtOpHash),
+            IntegrationLimboValue {
+                validation_status: ValidationStatus::Valid,
+                op: activity(),
+            },
+        )
+        .unwrap();
+    integrated
+        .put(
+            fixt!(DhtOpHash),
+            integrated_value(ValidationStatus::Valid, activity()),
+        )
+        .unwrap();
+    integrated
+        .put(
+            fixt!(DhtOpHash),
+            integrated_value(ValidationStatus::Rejected, store_entry()),
+        )
+        .unwrap();
+    integrated
+        .put(
+            fixt!(DhtOpHash),
+            integrated_value(ValidationStatus::Abandoned, store_entry()),
+        )
+        .unwrap();
+    env.guard()
+        .with_commit::<DatabaseError, _, _>(|writer| {
+            validation_limbo.flush_to_txn_ref(writer)?;
+            integration_limbo.flush_to_txn_ref(writer)?;
+            integrated.flush_to_txn_ref(writer)?;
+            Ok(())
+        })
+        .unwrap();
+
+    let counts = count_dht_ops(env.clone().into()).unwrap();
+    assert_eq!(
+        counts.by_type["RegisterAgentActivity"],
+        OpStatusCounts {
+            pending: 1,
+            awaiting_deps: 2,
+            sys_validated: 1,
+            awaiting_integration: 1,
+            integrated: 1,
+            rejected: 0,
+        }
+    );
+    assert_eq!(counts.by_type["StoreEntry"].rejected, 2);
+    assert_eq!(counts.by_type.len(), 2);
+    assert_eq!(counts.total().total(), 8);
+}
+
 #[tokio::test(threaded_scheduler)]
 async fn can_set_fake_state() {
     let envs = test_environments();
@@ -188,3 +289,264 @@ async fn proxy_tls_inner(
 
     Ok(())
 }
+
+#[tokio::test(threaded_scheduler)]
+async fn zome_calls_are_metered_when_turned_on() {
+    use crate::test_utils::sweetest::SweetAgents;
+    use crate::test_utils::sweetest::SweetConductor;
+    use crate::test_utils::sweetest::SweetDnaFile;
+    use holochain_types::dna::zome::inline_zome::InlineZome;
+
+    let zome = InlineZome::new_unique(vec![])
+        .callback("echo", |_api, s: String| Ok(s))
+        .callback("unused", |_api, ()| Ok(()));
+    let (dna_file, _) = SweetDnaFile::unique_from_inline_zome("zome1", zome)
+        .await
+        .unwrap();
+
+    let mut conductor = SweetConductor::from_config(ConductorConfig {
+        zome_call_metering: true,
+        ..Default::default()
+    })
+    .await;
+    let alice = SweetAgents::one(conductor.keystore()).await;
+    let app = conductor
+        .setup_app_for_agent("app", alice, &[dna_file.clone()])
+        .await;
+    let cell = app.into_cells().pop().unwrap();
+    let cell_id = cell.cell_id().clone();
+
+    let handle = conductor.0.clone();
+    assert!(handle
+        .dump_cell_zome_call_metering(&cell_id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    for _ in 0..3 {
+        let _: String = conductor
+            .call(&cell.zome("zome1"), "echo", "hi".to_string())
+            .await;
+    }
+    let totals = handle.dump_cell_zome_call_metering(&cell_id).await.unwrap();
+    assert_eq!(totals.len(), 1);
+    assert_eq!(totals[0].zome_name, "zome1");
+    assert_eq!(totals[0].fn_name, "echo");
+    assert_eq!(totals[0].calls, 3);
+    assert!(totals[0].mean_time() <= totals[0].total_time);
+
+    // Metering is off by default.
+    let mut unmetered = SweetConductor::from_config(Default::default()).await;
+    let bob = SweetAgents::one(unmetered.keystore()).await;
+    let app = unmetered.setup_app_for_agent("app", bob, &[dna_file]).await;
+    let cell_id = app.into_cells().pop().unwrap().cell_id().clone();
+    assert_matches!(
+        unmetered.0.dump_cell_zome_call_metering(&cell_id).await,
+        Err(ConductorApiError::ZomeCallMeteringDisabled)
+    );
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn interfaces_listen_on_the_configured_address() {
+    use crate::test_utils::sweetest::SweetConductor;
+    use holochain_websocket::websocket_connect;
+    use holochain_websocket::WebsocketConfig;
+    use std::sync::Arc;
+    use url2::url2;
+
+    observability::test_run().ok();
+    let conductor = SweetConductor::from_config(ConductorConfig {
+        admin_interfaces: Some(vec![AdminInterfaceConfig {
+            driver: InterfaceDriver::Websocket { port: 0 },
+            allowed_origins: None,
+            address: None,
+        }]),
+        interface_address: Some(std::net::Ipv6Addr::LOCALHOST.into()),
+        ..Default::default()
+    })
+    .await;
+    let admin_port = conductor
+        .0
+        .get_arbitrary_admin_websocket_port()
+        .await
+        .unwrap();
+    let app_port = conductor
+        .0
+        .clone()
+        .add_app_interface(0, None)
+        .await
+        .unwrap();
+
+    for port in &[admin_port, app_port] {
+        let config = Arc::new(WebsocketConfig::default());
+        websocket_connect(url2!("ws://[::1]:{}", port), config.clone())
+            .await
+            .expect("The interface should listen on ::1");
+        assert!(websocket_connect(url2!("ws://127.0.0.1:{}", port), config)
+            .await
+            .is_err());
+    }
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn admin_interfaces_can_listen_on_their_own_address() {
+    use crate::test_utils::sweetest::SweetConductor;
+    use holochain_websocket::websocket_connect;
+    use holochain_websocket::WebsocketConfig;
+    use std::sync::Arc;
+    use url2::url2;
+
+    observability::test_run().ok();
+    let conductor = SweetConductor::from_config(ConductorConfig {
+        admin_interfaces: Some(vec![AdminInterfaceConfig {
+            driver: InterfaceDriver::Websocket { port: 0 },
+            allowed_origins: None,
+            address: Some(std::net::Ipv4Addr::LOCALHOST.into()),
+        }]),
+        interface_address: Some(std::net::Ipv6Addr::LOCALHOST.into()),
+        ..Default::default()
+    })
+    .await;
+    let admin_port = conductor
+        .0
+        .get_arbitrary_admin_websocket_port()
+        .await
+        .unwrap();
+    let app_port = conductor
+        .0
+        .clone()
+        .add_app_interface(0, None)
+        .await
+        .unwrap();
+
+    let config = Arc::new(WebsocketConfig::default());
+    websocket_connect(url2!("ws://127.0.0.1:{}", admin_port), config.clone())
+        .await
+        .expect("The admin interface should listen on its own address");
+    assert!(
+        websocket_connect(url2!("ws://[::1]:{}", admin_port), config.clone())
+            .await
+            .is_err()
+    );
+    // Other interfaces still use the conductor's address.
+    websocket_connect(url2!("ws://[::1]:{}", app_port), config)
+        .await
+        .expect("The app interface should listen on ::1");
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn app_interfaces_only_accept_their_allowed_origins() {
+    use crate::test_utils::sweetest::SweetConductor;
+    use holochain_websocket::websocket_connect;
+    use holochain_websocket::WebsocketConfig;
+    use std::sync::Arc;
+    use url2::url2;
+
+    observability::test_run().ok();
+    let conductor = SweetConductor::from_standard_config().await;
+    let origin = "http://localhost:8888".to_string();
+    let app_port = conductor
+        .0
+        .clone()
+        .add_app_interface(0, Some(vec![origin.clone()]))
+        .await
+        .unwrap();
+    let url = url2!("ws://127.0.0.1:{}", app_port);
+
+    for other in &[None, Some("http://example.com")] {
+        let mut config = WebsocketConfig::default();
+        config.origin = other.map(String::from);
+        assert!(websocket_connect(url.clone(), Arc::new(config))
+            .await
+            .is_err());
+    }
+    let config = WebsocketConfig::default().origin(origin.clone());
+    websocket_connect(url, Arc::new(config))
+        .await
+        .expect("The allowed origin should be accepted");
+
+    // The origins are kept for when the conductor starts again.
+    let state = conductor.0.get_state_from_handle().await.unwrap();
+    let interface = state.app_interfaces.values().next().unwrap();
+    assert_eq!(interface.allowed_origins, Some(vec![origin]));
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn app_interfaces_in_the_config_start_with_the_conductor() {
+    use crate::test_utils::sweetest::SweetConductor;
+    use holochain_websocket::websocket_connect;
+    use holochain_websocket::WebsocketConfig;
+    use std::sync::Arc;
+    use url2::url2;
+
+    observability::test_run().ok();
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let conductor = SweetConductor::from_config(ConductorConfig {
+        app_interfaces: Some(vec![StaticAppInterfaceConfig {
+            port,
+            allowed_origins: None,
+        }]),
+        ..Default::default()
+    })
+    .await;
+
+    websocket_connect(
+        url2!("ws://127.0.0.1:{}", port),
+        Arc::new(WebsocketConfig::default()),
+    )
+    .await
+    .expect("The app interface should be started from the config");
+    // It isn't kept in the state because it is in the config.
+    let state = conductor.0.get_state_from_handle().await.unwrap();
+    assert!(state.app_interfaces.is_empty());
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn arcs_are_dumped_for_the_agents_of_a_dna() {
+    use crate::test_utils::sweetest::SweetAgents;
+    use crate::test_utils::sweetest::SweetConductor;
+    use crate::test_utils::sweetest::SweetDnaFile;
+    use holochain_types::dna::zome::inline_zome::InlineZome;
+
+    let (dna_file, _) =
+        SweetDnaFile::unique_from_inline_zome("zome1", InlineZome::new_unique(vec![]))
+            .await
+            .unwrap();
+    let (other_dna_file, _) =
+        SweetDnaFile::unique_from_inline_zome("zome1", InlineZome::new_unique(vec![]))
+            .await
+            .unwrap();
+    let mut conductor = SweetConductor::from_standard_config().await;
+    let (alice, bob, carol) = SweetAgents::three(conductor.keystore()).await;
+    let apps = conductor
+        .setup_app_for_agents("app", &[alice.clone(), bob.clone()], &[dna_file])
+        .await;
+    conductor
+        .setup_app_for_agent("other", carol, &[other_dna_file])
+        .await;
+    let cell_id = apps.cells_flattened()[0].cell_id().clone();
+
+    let handle = conductor.0.clone();
+    // The agents are put in the peer store when they join the network.
+    let mut arcs = Vec::new();
+    for _ in 0..50 {
+        arcs = handle.dump_cell_arcs(&cell_id).await.unwrap();
+        if arcs.len() == 2 {
+            break;
+        }
+        tokio::time::delay_for(std::time::Duration::from_millis(100)).await;
+    }
+    let agents: std::collections::HashSet<_> = arcs.iter().map(|a| a.agent.clone()).collect();
+    assert_eq!(agents, vec![alice, bob].into_iter().collect());
+    assert!(arcs[0].arc.center_loc.0 <= arcs[1].arc.center_loc.0);
+    for arc in &arcs {
+        assert!(arc.arc.contains(arc.agent.get_loc()));
+        assert_eq!(arc.coverage(), 1.0);
+    }
+
+    assert!(handle.dump_cell_arcs(&fake_cell_id(1)).await.is_err());
+}
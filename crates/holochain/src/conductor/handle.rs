@@ -55,10 +55,14 @@ use super::p2p_store::put_agent_info_signed;
 use super::p2p_store::query_agent_info_signed;
 use super::Cell;
 use super::Conductor;
+use crate::core::ribosome::HostFnTrace;
 use crate::core::workflow::CallZomeWorkspaceLock;
 use crate::core::workflow::ZomeCallResult;
 use derive_more::From;
 use futures::future::FutureExt;
+use holochain_conductor_api::AgentArc;
+use holochain_conductor_api::OpCounts;
+use holochain_conductor_api::ZomeFnMetering;
 use holochain_p2p::event::HolochainP2pEvent::*;
 use holochain_types::prelude::*;
 use kitsune_p2p::agent_store::AgentInfoSigned;
@@ -100,8 +104,13 @@ pub trait ConductorHandleT: Send + Sync {
     /// Should only be run once at Conductor initialization.
     async fn startup_app_interfaces(self: Arc<Self>) -> ConductorResult<()>;
 
-    /// Add an app interface
-    async fn add_app_interface(self: Arc<Self>, port: u16) -> ConductorResult<u16>;
+    /// Add an app interface that only accepts connections
+    /// from the `allowed_origins` if they are set
+    async fn add_app_interface(
+        self: Arc<Self>,
+        port: u16,
+        allowed_origins: Option<Vec<String>>,
+    ) -> ConductorResult<u16>;
 
     /// Install a [Dna] in this Conductor
     async fn install_dna(&self, dna: DnaFile) -> ConductorResult<()>;
@@ -128,6 +137,14 @@ pub trait ConductorHandleT: Send + Sync {
     /// Invoke a zome function on a Cell
     async fn call_zome(&self, invocation: ZomeCall) -> ConductorApiResult<ZomeCallResult>;
 
+    /// Invoke a zome function on a Cell and record the host
+    /// functions it calls in the `host_fn_trace`
+    async fn call_zome_traced(
+        &self,
+        invocation: ZomeCall,
+        host_fn_trace: HostFnTrace,
+    ) -> ConductorApiResult<ZomeCallResult>;
+
     /// Invoke a zome function on a Cell with a workspace
     async fn call_zome_with_workspace(
         &self,
@@ -184,10 +201,31 @@ pub trait ConductorHandleT: Send + Sync {
     /// List Active AppIds
     async fn list_active_apps(&self) -> ConductorResult<Vec<InstalledAppId>>;
 
+    /// List the inactive Apps and their cells
+    async fn list_inactive_apps(&self) -> ConductorResult<Vec<InstalledApp>>;
+
+    /// List the Cell Ids of all installed Apps, active or inactive
+    async fn list_installed_cell_ids(&self) -> ConductorResult<Vec<CellId>>;
+
     /// Dump the cells state
     #[allow(clippy::ptr_arg)]
     async fn dump_cell_state(&self, cell_id: &CellId) -> ConductorApiResult<String>;
 
+    /// Count the cell's ops by type and validation / integration status
+    #[allow(clippy::ptr_arg)]
+    async fn dump_cell_op_counts(&self, cell_id: &CellId) -> ConductorApiResult<OpCounts>;
+
+    /// Get the arc each known agent holds of the cell's dna
+    #[allow(clippy::ptr_arg)]
+    async fn dump_cell_arcs(&self, cell_id: &CellId) -> ConductorApiResult<Vec<AgentArc>>;
+
+    /// Get the totals for each zome function the cell has run
+    #[allow(clippy::ptr_arg)]
+    async fn dump_cell_zome_call_metering(
+        &self,
+        cell_id: &CellId,
+    ) -> ConductorApiResult<Vec<ZomeFnMetering>>;
+
     /// Access the broadcast Sender which will send a Signal across every
     /// attached app interface
     async fn signal_broadcaster(&self) -> SignalBroadcaster;
@@ -271,9 +309,14 @@ impl<DS: DnaStore + 'static> ConductorHandleT for ConductorHandleImpl<DS> {
             .await
     }
 
-    async fn add_app_interface(self: Arc<Self>, port: u16) -> ConductorResult<u16> {
+    async fn add_app_interface(
+        self: Arc<Self>,
+        port: u16,
+        allowed_origins: Option<Vec<String>>,
+    ) -> ConductorResult<u16> {
         let mut lock = self.conductor.write().await;
-        lock.add_app_interface_via_handle(port, self.clone()).await
+        lock.add_app_interface_via_handle(port, allowed_origins, self.clone())
+            .await
     }
 
     async fn install_dna(&self, dna: DnaFile) -> ConductorResult<()> {
@@ -381,6 +424,15 @@ impl<DS: DnaStore + 'static> ConductorHandleT for ConductorHandleImpl<DS> {
         Ok(cell.call_zome(call, None).await?)
     }
 
+    async fn call_zome_traced(
+        &self,
+        call: ZomeCall,
+        host_fn_trace: HostFnTrace,
+    ) -> ConductorApiResult<ZomeCallResult> {
+        let cell = self.cell_by_id(&call.cell_id).await?;
+        Ok(cell.call_zome_traced(call, host_fn_trace).await?)
+    }
+
     async fn call_zome_with_workspace(
         &self,
         call: ZomeCall,
@@ -425,12 +477,15 @@ impl<DS: DnaStore + 'static> ConductorHandleT for ConductorHandleImpl<DS> {
         installed_app_id: InstalledAppId,
         cell_data: Vec<(InstalledCell, Option<MembraneProof>)>,
     ) -> ConductorResult<()> {
+        // Cells another app already installed have had their genesis.
+        let existing = self.list_installed_cell_ids().await?;
         self.conductor
             .read()
             .await
             .genesis_cells(
                 cell_data
                     .iter()
+                    .filter(|(c, _)| !existing.contains(c.as_id()))
                     .map(|(c, p)| (c.as_id().clone(), p.clone()))
                     .collect(),
                 self.clone(),
@@ -509,10 +564,36 @@ impl<DS: DnaStore + 'static> ConductorHandleT for ConductorHandleImpl<DS> {
         self.conductor.read().await.list_active_apps().await
     }
 
+    async fn list_inactive_apps(&self) -> ConductorResult<Vec<InstalledApp>> {
+        self.conductor.read().await.list_inactive_apps().await
+    }
+
+    async fn list_installed_cell_ids(&self) -> ConductorResult<Vec<CellId>> {
+        self.conductor.read().await.list_installed_cell_ids().await
+    }
+
     async fn dump_cell_state(&self, cell_id: &CellId) -> ConductorApiResult<String> {
         self.conductor.read().await.dump_cell_state(cell_id).await
     }
 
+    async fn dump_cell_op_counts(&self, cell_id: &CellId) -> ConductorApiResult<OpCounts> {
+        self.conductor.read().await.dump_cell_op_counts(cell_id)
+    }
+
+    async fn dump_cell_arcs(&self, cell_id: &CellId) -> ConductorApiResult<Vec<AgentArc>> {
+        self.conductor.read().await.dump_cell_arcs(cell_id)
+    }
+
+    async fn dump_cell_zome_call_metering(
+        &self,
+        cell_id: &CellId,
+    ) -> ConductorApiResult<Vec<ZomeFnMetering>> {
+        self.conductor
+            .read()
+            .await
+            .dump_cell_zome_call_metering(cell_id)
+    }
+
     async fn signal_broadcaster(&self) -> SignalBroadcaster {
         self.conductor.read().await.signal_broadcaster()
     }
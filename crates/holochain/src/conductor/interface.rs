@@ -2,8 +2,8 @@
 //! and dispatch them to the appropriate handlers within Holochain.
 //! They also allow emitting responses and one-way Signals.
 //!
-//! Currently the only InterfaceDriver is a Websocket-based one, whose
-//! implementation can be found in the `websocket` module here.
+//! Both InterfaceDrivers are Websocket-based, over a port or a unix socket,
+//! and their implementation can be found in the `websocket` module here.
 
 use crate::conductor::api::*;
 use error::InterfaceError;
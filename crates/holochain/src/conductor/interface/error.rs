@@ -29,6 +29,8 @@ pub enum InterfaceError {
     IoTodo(#[from] std::io::Error),
     #[error("Failed to find free port")]
     PortError,
+    #[error("Unix domain sockets are not supported on this platform, can't listen on {0}")]
+    UnixSocketUnsupported(std::path::PathBuf),
 }
 
 impl From<String> for InterfaceError {
@@ -1,5 +1,6 @@
 //! Module for establishing Websocket-based Interfaces,
 //! i.e. those configured with `InterfaceDriver::Websocket`
+//! or `InterfaceDriver::UnixSocket`
 
 use super::error::InterfaceError;
 use super::error::InterfaceResult;
@@ -16,6 +17,11 @@ use holochain_websocket::WebsocketMessage;
 use holochain_websocket::WebsocketReceiver;
 use holochain_websocket::WebsocketSender;
 use std::convert::TryFrom;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::path::Path;
 
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
@@ -32,18 +38,83 @@ use url2::url2;
 pub(crate) const SIGNAL_BUFFER_SIZE: usize = 50;
 const MAX_CONNECTIONS: usize = 400;
 
-/// Create a WebsocketListener to be used in interfaces
-pub async fn spawn_websocket_listener(port: u16) -> InterfaceResult<WebsocketListener> {
+/// Bind a websocket on `address`, or on the IPv4 loopback if there isn't
+/// one. Machines without an IPv4 loopback bind the IPv6 loopback instead.
+async fn bind(
+    address: Option<IpAddr>,
+    port: u16,
+    config: WebsocketConfig,
+) -> InterfaceResult<WebsocketListener> {
+    let url = |ip: IpAddr| url2!("ws://{}", SocketAddr::new(ip, port));
+    let config = Arc::new(config);
+    let ip = match address {
+        Some(ip) => ip,
+        None => match websocket_bind(url(Ipv4Addr::LOCALHOST.into()), config.clone()).await {
+            Err(e) if e.kind() == std::io::ErrorKind::AddrNotAvailable => {
+                debug!("No IPv4 loopback so binding the IPv6 loopback");
+                Ipv6Addr::LOCALHOST.into()
+            }
+            r => return Ok(r?),
+        },
+    };
+    Ok(websocket_bind(url(ip), config).await?)
+}
+
+/// Create a WebsocketListener to be used in interfaces.
+/// It listens on `address` or a loopback address if that is `None`.
+/// Connections from other origins are rejected if `allowed_origins` is set.
+pub async fn spawn_websocket_listener(
+    address: Option<IpAddr>,
+    port: u16,
+    allowed_origins: Option<Vec<String>>,
+) -> InterfaceResult<WebsocketListener> {
     trace!("Initializing Admin interface");
-    let listener = websocket_bind(
-        url2!("ws://127.0.0.1:{}", port),
-        Arc::new(WebsocketConfig::default()),
-    )
-    .await?;
+    let listener = bind(address, port, listener_config(allowed_origins)).await?;
+    trace!("LISTENING AT: {}", listener.local_addr());
+    Ok(listener)
+}
+
+/// Create a WebsocketListener on a unix domain socket to be used in admin interfaces.
+/// A socket file left behind by a conductor that has exited is replaced
+/// but a socket with a conductor listening on it is an error.
+#[cfg(unix)]
+pub async fn spawn_unix_socket_listener(
+    path: &Path,
+    allowed_origins: Option<Vec<String>>,
+) -> InterfaceResult<WebsocketListener> {
+    trace!("Initializing Admin interface on {}", path.display());
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                format!("Something is already listening on {}", path.display()),
+            )
+            .into());
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener =
+        holochain_websocket::websocket_bind_unix(path, Arc::new(listener_config(allowed_origins)))
+            .await?;
     trace!("LISTENING AT: {}", listener.local_addr());
     Ok(listener)
 }
 
+/// Unix domain sockets are only supported on unix.
+#[cfg(not(unix))]
+pub async fn spawn_unix_socket_listener(
+    path: &Path,
+    _allowed_origins: Option<Vec<String>>,
+) -> InterfaceResult<WebsocketListener> {
+    Err(InterfaceError::UnixSocketUnsupported(path.to_path_buf()))
+}
+
+fn listener_config(allowed_origins: Option<Vec<String>>) -> WebsocketConfig {
+    let mut config = WebsocketConfig::default();
+    config.allowed_origins = allowed_origins;
+    config
+}
+
 /// Create an Admin Interface, which only receives AdminRequest messages
 /// from the external client
 pub fn spawn_admin_interface_task<A: InterfaceApi>(
@@ -112,19 +183,18 @@ pub fn spawn_admin_interface_task<A: InterfaceApi>(
 }
 
 /// Create an App Interface, which includes the ability to receive signals
-/// from Cells via a broadcast channel
+/// from Cells via a broadcast channel.
+/// It listens on `address` like [spawn_websocket_listener].
 pub async fn spawn_app_interface_task<A: InterfaceApi>(
+    address: Option<IpAddr>,
     port: u16,
+    allowed_origins: Option<Vec<String>>,
     api: A,
     signal_broadcaster: broadcast::Sender<Signal>,
     mut stop_rx: StopReceiver,
 ) -> InterfaceResult<(u16, ManagedTaskHandle)> {
     trace!("Initializing App interface");
-    let mut listener = websocket_bind(
-        url2!("ws://127.0.0.1:{}", port),
-        Arc::new(WebsocketConfig::default()),
-    )
-    .await?;
+    let mut listener = bind(address, port, listener_config(allowed_origins)).await?;
     trace!("LISTENING AT: {}", listener.local_addr());
     let port = listener
         .local_addr()
@@ -674,7 +744,10 @@ pub mod test {
         let (_tmpdir, conductor_handle) = setup_admin().await;
         let shutdown = conductor_handle.take_shutdown_handle().await.unwrap();
         let admin_api = RealAdminInterfaceApi::new(conductor_handle.clone());
-        let msg = AdminRequest::AttachAppInterface { port: None };
+        let msg = AdminRequest::AttachAppInterface {
+            port: None,
+            allowed_origins: None,
+        };
         let msg = msg.try_into().unwrap();
         let respond = |bytes: SerializedBytes| {
             let response: AdminResponse = bytes.try_into().unwrap();
@@ -61,6 +61,16 @@ impl ConductorState {
             })
     }
 
+    /// Is this cell part of any active app.
+    /// Apps can share a cell so a cell is only removed
+    /// once no active app uses it.
+    pub fn is_cell_active(&self, cell_id: &CellId) -> bool {
+        self.active_apps
+            .values()
+            .flatten()
+            .any(|cell| cell.as_id() == cell_id)
+    }
+
     /// Returns the interface configuration with the given ID if present
     pub fn interface_by_id(&self, id: &AppInterfaceId) -> Option<AppInterfaceConfig> {
         self.app_interfaces.get(id).cloned()
@@ -84,6 +94,10 @@ pub struct AppInterfaceConfig {
 
     /// The driver for the interface, e.g. Websocket
     pub driver: InterfaceDriver,
+
+    /// Only accept connections from these origins if they are set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_origins: Option<Vec<String>>,
 }
 
 impl AppInterfaceConfig {
@@ -92,6 +106,7 @@ impl AppInterfaceConfig {
         Self {
             signal_subscriptions: HashMap::new(),
             driver: InterfaceDriver::Websocket { port },
+            allowed_origins: None,
         }
     }
 }
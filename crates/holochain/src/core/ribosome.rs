@@ -14,6 +14,7 @@ pub mod real_ribosome;
 
 use crate::conductor::api::CellConductorApi;
 use crate::conductor::api::CellConductorReadHandle;
+use crate::conductor::api::HostFnCall;
 use crate::conductor::api::ZomeCall;
 use crate::conductor::interface::SignalBroadcaster;
 use crate::core::ribosome::guest_callback::entry_defs::EntryDefsResult;
@@ -32,7 +33,6 @@ use crate::core::ribosome::guest_callback::validation_package::ValidationPackage
 use crate::core::ribosome::guest_callback::validation_package::ValidationPackageResult;
 use crate::core::ribosome::guest_callback::CallIterator;
 use crate::core::workflow::CallZomeWorkspaceLock;
-use derive_more::Constructor;
 use error::RibosomeResult;
 use guest_callback::entry_defs::EntryDefsHostAccess;
 use guest_callback::init::InitHostAccess;
@@ -41,6 +41,7 @@ use guest_callback::post_commit::PostCommitHostAccess;
 use guest_callback::validate::ValidateHostAccess;
 use guest_callback::validation_package::ValidationPackageHostAccess;
 use holo_hash::AgentPubKey;
+use holochain_conductor_api::ZomeFnMetering;
 use holochain_keystore::KeystoreSender;
 use holochain_p2p::HolochainP2pCell;
 use holochain_serialized_bytes::prelude::*;
@@ -366,7 +367,7 @@ impl From<ZomeCallInvocation> for ZomeCall {
     }
 }
 
-#[derive(Clone, Constructor)]
+#[derive(Clone)]
 pub struct ZomeCallHostAccess {
     pub workspace: CallZomeWorkspaceLock,
     pub keystore: KeystoreSender,
@@ -377,6 +378,136 @@ pub struct ZomeCallHostAccess {
     // "resource" to give access to, but rather it's a bit of data that makes sense in
     // the context of zome calls, but not every CallContext
     pub cell_id: CellId,
+    /// If set, every host function the zome call makes is recorded here
+    pub host_fn_trace: Option<HostFnTrace>,
+}
+
+impl ZomeCallHostAccess {
+    pub fn new(
+        workspace: CallZomeWorkspaceLock,
+        keystore: KeystoreSender,
+        network: HolochainP2pCell,
+        signal_tx: SignalBroadcaster,
+        call_zome_handle: CellConductorReadHandle,
+        cell_id: CellId,
+    ) -> Self {
+        Self {
+            workspace,
+            keystore,
+            network,
+            signal_tx,
+            call_zome_handle,
+            cell_id,
+            host_fn_trace: None,
+        }
+    }
+}
+
+/// Records the host functions called during a zome call, in call order.
+/// Clones share the same record so the caller can keep one
+/// and read the calls after the ribosome is done with the others.
+#[derive(Clone, Debug)]
+pub struct HostFnTrace {
+    start: std::time::Instant,
+    calls: std::sync::Arc<std::sync::Mutex<Vec<HostFnCall>>>,
+}
+
+impl Default for HostFnTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostFnTrace {
+    /// Start a new trace. Call times are relative to now.
+    pub fn new() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+            calls: Default::default(),
+        }
+    }
+
+    /// Record that the host function `name` was called at `called_at`
+    /// and has just returned.
+    pub fn record(&self, name: &str, called_at: std::time::Instant) {
+        let call = HostFnCall {
+            name: name.to_string(),
+            start: called_at.saturating_duration_since(self.start),
+            elapsed: called_at.elapsed(),
+        };
+        match self.calls.lock() {
+            Ok(mut calls) => calls.push(call),
+            Err(poisoned) => poisoned.into_inner().push(call),
+        }
+    }
+
+    /// The calls recorded so far.
+    pub fn calls(&self) -> Vec<HostFnCall> {
+        match self.calls.lock() {
+            Ok(calls) => calls.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
+
+/// Totals for each zome function, keyed by zome and function name.
+type ZomeFnTotals = std::collections::BTreeMap<(String, String), ZomeFnMetering>;
+
+/// Totals the zome calls made on each cell of a conductor
+/// that has `zome_call_metering` turned on.
+/// Clones share the same totals.
+#[derive(Clone, Debug, Default)]
+pub struct ZomeCallMeter {
+    totals: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<CellId, ZomeFnTotals>>>,
+}
+
+impl ZomeCallMeter {
+    /// Start with no calls recorded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a call to a zome function on a cell that took `elapsed`
+    /// and made `host_fn_calls` calls to host functions.
+    pub fn record(
+        &self,
+        cell_id: &CellId,
+        zome_name: &ZomeName,
+        fn_name: &FunctionName,
+        elapsed: std::time::Duration,
+        host_fn_calls: usize,
+    ) {
+        let mut totals = match self.totals.lock() {
+            Ok(totals) => totals,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let key = (zome_name.0.clone(), fn_name.0.clone());
+        let total = totals
+            .entry(cell_id.clone())
+            .or_default()
+            .entry(key)
+            .or_insert_with(|| ZomeFnMetering {
+                zome_name: zome_name.0.clone(),
+                fn_name: fn_name.0.clone(),
+                ..Default::default()
+            });
+        total.calls += 1;
+        total.total_time += elapsed;
+        total.host_fn_calls += host_fn_calls as u64;
+    }
+
+    /// The totals for each zome function the cell has run,
+    /// sorted by zome and function name.
+    pub fn totals(&self, cell_id: &CellId) -> Vec<ZomeFnMetering> {
+        let totals = match self.totals.lock() {
+            Ok(totals) => totals,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        totals
+            .get(cell_id)
+            .map(|t| t.values().cloned().collect())
+            .unwrap_or_default()
+    }
 }
 
 impl From<ZomeCallHostAccess> for HostAccess {
@@ -49,6 +49,12 @@ host_fn_api_impls! {
 
     fn agent_info (()) -> zt::agent_info::AgentInfo;
 
+    // Encode bytes as standard base64 on the host.
+    fn base64_encode (zt::bytes::Bytes) -> zt::base64_encoded::Base64Encoded;
+
+    // Decode standard base64 on the host. None if it isn't valid base64.
+    fn base64_decode (zt::base64_encoded::Base64Encoded) -> Option<zt::bytes::Bytes>;
+
     fn call (zt::call::Call) -> zt::ZomeCallResponse;
 
     // Header hash of the DeleteLink element.
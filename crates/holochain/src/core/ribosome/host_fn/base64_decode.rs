@@ -0,0 +1,17 @@
+use crate::core::ribosome::CallContext;
+use crate::core::ribosome::RibosomeT;
+use holochain_types::prelude::*;
+use holochain_wasmer_host::prelude::WasmError;
+use std::sync::Arc;
+
+/// decode base64 with the standard alphabet and padding
+/// the guest gets None if the input isn't valid base64 so it can handle bad input itself
+pub fn base64_decode(
+    _ribosome: Arc<impl RibosomeT>,
+    _call_context: Arc<CallContext>,
+    input: Base64Encoded,
+) -> Result<Option<Bytes>, WasmError> {
+    Ok(base64::decode(input.as_ref()).ok().map(Bytes::from))
+}
+
+// Tests for the decode round trip are in base64_encode.
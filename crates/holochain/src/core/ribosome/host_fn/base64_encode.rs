@@ -0,0 +1,95 @@
+use crate::core::ribosome::CallContext;
+use crate::core::ribosome::RibosomeT;
+use holochain_types::prelude::*;
+use holochain_wasmer_host::prelude::WasmError;
+use std::sync::Arc;
+
+/// encode the bytes as base64 with the standard alphabet and padding
+pub fn base64_encode(
+    _ribosome: Arc<impl RibosomeT>,
+    _call_context: Arc<CallContext>,
+    input: Bytes,
+) -> Result<Base64Encoded, WasmError> {
+    Ok(Base64Encoded::from(base64::encode(&input)))
+}
+
+#[cfg(test)]
+#[cfg(feature = "slow_tests")]
+pub mod wasm_test {
+    use crate::core::ribosome::host_fn::base64_decode::base64_decode;
+    use crate::core::ribosome::host_fn::base64_encode::base64_encode;
+    use crate::fixt::CallContextFixturator;
+    use crate::fixt::RealRibosomeFixturator;
+    use crate::fixt::ZomeCallHostAccessFixturator;
+    use ::fixt::prelude::*;
+    use holochain_wasm_test_utils::TestWasm;
+    use holochain_zome_types::prelude::Base64Encoded;
+    use holochain_zome_types::prelude::Bytes;
+    use std::sync::Arc;
+
+    #[tokio::test(threaded_scheduler)]
+    /// known values are encoded and decoded by the fns directly
+    async fn base64_test() {
+        let ribosome = Arc::new(
+            RealRibosomeFixturator::new(crate::fixt::curve::Zomes(vec![]))
+                .next()
+                .unwrap(),
+        );
+        let call_context = Arc::new(
+            CallContextFixturator::new(::fixt::Unpredictable)
+                .next()
+                .unwrap(),
+        );
+
+        let encoded = base64_encode(
+            ribosome.clone(),
+            call_context.clone(),
+            Bytes::from(b"holochain".to_vec()),
+        )
+        .unwrap();
+        assert_eq!(encoded, Base64Encoded::from("aG9sb2NoYWlu"));
+
+        let decoded = base64_decode(ribosome.clone(), call_context.clone(), encoded).unwrap();
+        assert_eq!(decoded, Some(Bytes::from(b"holochain".to_vec())));
+
+        let invalid = base64_decode(ribosome, call_context, "not base64!".into()).unwrap();
+        assert_eq!(invalid, None);
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    /// a known value round trips through the host fns via. a wasm call
+    async fn ribosome_base64_test() {
+        let test_env = holochain_lmdb::test_utils::test_cell_env();
+        let env = test_env.env();
+        let mut workspace =
+            crate::core::workflow::CallZomeWorkspace::new(env.clone().into()).unwrap();
+        crate::core::workflow::fake_genesis(&mut workspace.source_chain)
+            .await
+            .unwrap();
+        let workspace_lock = crate::core::workflow::CallZomeWorkspaceLock::new(workspace);
+
+        let mut host_access = fixt!(ZomeCallHostAccess);
+        host_access.workspace = workspace_lock;
+
+        let bytes = Bytes::from(vec![0, 1, 2, 253, 254, 255]);
+        let encoded: Base64Encoded = crate::call_test_ribosome!(
+            host_access,
+            TestWasm::Base64,
+            "base64_encode",
+            bytes.clone()
+        );
+        assert_eq!(encoded, Base64Encoded::from("AAEC/f7/"));
+
+        let decoded: Option<Bytes> =
+            crate::call_test_ribosome!(host_access, TestWasm::Base64, "base64_decode", encoded);
+        assert_eq!(decoded, Some(bytes));
+
+        let invalid: Option<Bytes> = crate::call_test_ribosome!(
+            host_access,
+            TestWasm::Base64,
+            "base64_decode",
+            Base64Encoded::from("AAEC/f7")
+        );
+        assert_eq!(invalid, None);
+    }
+}
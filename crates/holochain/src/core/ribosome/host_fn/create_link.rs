@@ -51,3 +51,46 @@ pub fn create_link<'a>(
 }
 
 // we rely on the tests for get_links and get_link_details
+// for the details of the links
+
+#[cfg(test)]
+#[cfg(feature = "slow_tests")]
+pub mod wasm_test {
+    use crate::fixt::ZomeCallHostAccessFixturator;
+    use ::fixt::prelude::*;
+    use hdk3::prelude::*;
+    use holochain_wasm_test_utils::TestWasm;
+
+    #[tokio::test(threaded_scheduler)]
+    async fn ribosome_create_link_then_get_links() {
+        let test_env = holochain_lmdb::test_utils::test_cell_env();
+        let env = test_env.env();
+        let mut workspace =
+            crate::core::workflow::CallZomeWorkspace::new(env.clone().into()).unwrap();
+
+        // commits fail validation if we don't do genesis
+        crate::core::workflow::fake_genesis(&mut workspace.source_chain)
+            .await
+            .unwrap();
+
+        let workspace_lock = crate::core::workflow::CallZomeWorkspaceLock::new(workspace);
+        let mut host_access = fixt!(ZomeCallHostAccess);
+        host_access.workspace = workspace_lock;
+
+        let links: Links =
+            crate::call_test_ribosome!(host_access, TestWasm::Link, "get_links", ());
+        assert!(links.into_inner().is_empty());
+
+        let header_hash: HeaderHash =
+            crate::call_test_ribosome!(host_access, TestWasm::Link, "create_link", ());
+
+        // The link is integrated to the authored metadata
+        // so it can be got without waiting for the network.
+        let links: Links =
+            crate::call_test_ribosome!(host_access, TestWasm::Link, "get_links", ());
+        let links = links.into_inner();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].create_link_hash, header_hash);
+        assert_eq!(links[0].tag, LinkTag::from(()));
+    }
+}
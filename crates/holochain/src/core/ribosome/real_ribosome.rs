@@ -27,6 +27,8 @@ use crate::core::ribosome::guest_callback::validation_package::ValidationPackage
 use crate::core::ribosome::guest_callback::validation_package::ValidationPackageResult;
 use crate::core::ribosome::guest_callback::CallIterator;
 use crate::core::ribosome::host_fn::agent_info::agent_info;
+use crate::core::ribosome::host_fn::base64_decode::base64_decode;
+use crate::core::ribosome::host_fn::base64_encode::base64_encode;
 use crate::core::ribosome::host_fn::call::call;
 use crate::core::ribosome::host_fn::call_remote::call_remote;
 use crate::core::ribosome::host_fn::capability_claims::capability_claims;
@@ -130,6 +132,12 @@ impl RealRibosome {
     fn imports(&self, call_context: CallContext) -> ImportObject {
         let host_fn_access = (&call_context.host_access()).into();
 
+        // only zome calls that asked for a trace record their host function calls
+        let host_fn_trace = match &call_context.host_access {
+            HostAccess::ZomeCall(ZomeCallHostAccess { host_fn_trace, .. }) => host_fn_trace.clone(),
+            _ => None,
+        };
+
         // it is important that RealRibosome and ZomeCallInvocation are cheap to clone here
         let self_arc = std::sync::Arc::new((*self).clone());
         let call_context_arc = std::sync::Arc::new(call_context);
@@ -138,7 +146,9 @@ impl RealRibosome {
             ( $host_function:ident ) => {{
                 let closure_self_arc = std::sync::Arc::clone(&self_arc);
                 let closure_call_context_arc = std::sync::Arc::clone(&call_context_arc);
+                let closure_host_fn_trace = host_fn_trace.clone();
                 move |ctx: &mut Ctx, guest_allocation_ptr: GuestPtr| -> Result<Len, WasmError> {
+                    let called_at = std::time::Instant::now();
                     let result = match $crate::holochain_wasmer_host::guest::from_guest_ptr(
                         ctx,
                         guest_allocation_ptr,
@@ -155,6 +165,9 @@ impl RealRibosome {
                         }
                         Err(wasm_error) => Err::<_, WasmError>(wasm_error),
                     };
+                    if let Some(trace) = &closure_host_fn_trace {
+                        trace.record(stringify!($host_function), called_at);
+                    }
                     $crate::holochain_wasmer_host::import::set_context_data(ctx, result)
                 }
             }};
@@ -171,6 +184,8 @@ impl RealRibosome {
         // imported host functions for core
         ns.insert("__trace", func!(invoke_host_function!(trace)));
         ns.insert("__hash_entry", func!(invoke_host_function!(hash_entry)));
+        ns.insert("__base64_encode", func!(invoke_host_function!(base64_encode)));
+        ns.insert("__base64_decode", func!(invoke_host_function!(base64_decode)));
         ns.insert("__unreachable", func!(invoke_host_function!(unreachable)));
 
         if let HostFnAccess {
@@ -572,4 +587,28 @@ pub mod wasm_test {
 
         assert_eq!("foobar", bar_result.as_str());
     }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn host_fn_calls_are_traced() {
+        let test_env = holochain_lmdb::test_utils::test_cell_env();
+        let env = test_env.env();
+        let mut workspace =
+            crate::core::workflow::CallZomeWorkspace::new(env.clone().into()).unwrap();
+        crate::core::workflow::fake_genesis(&mut workspace.source_chain)
+            .await
+            .unwrap();
+        let workspace_lock = crate::core::workflow::CallZomeWorkspaceLock::new(workspace);
+
+        let host_fn_trace = crate::core::ribosome::HostFnTrace::new();
+        let mut host_access = fixt!(ZomeCallHostAccess);
+        host_access.workspace = workspace_lock;
+        host_access.host_fn_trace = Some(host_fn_trace.clone());
+        let _: core::time::Duration =
+            crate::call_test_ribosome!(host_access, TestWasm::SysTime, "sys_time", ());
+
+        let calls = host_fn_trace.calls();
+        assert!(calls.iter().any(|c| c.name == "sys_time"), "{:?}", calls);
+        // Calls are recorded in order.
+        assert!(calls.windows(2).all(|w| w[0].start <= w[1].start));
+    }
 }
@@ -7,6 +7,7 @@ use crate::core::queue_consumer::OneshotWriter;
 use crate::core::queue_consumer::TriggerSender;
 use crate::core::ribosome::error::RibosomeError;
 use crate::core::ribosome::error::RibosomeResult;
+use crate::core::ribosome::HostFnTrace;
 use crate::core::ribosome::RibosomeT;
 use crate::core::ribosome::ZomeCallHostAccess;
 use crate::core::ribosome::ZomeCallInvocation;
@@ -46,6 +47,8 @@ pub struct CallZomeWorkflowArgs<Ribosome: RibosomeT + Send, C: CellConductorApiT
     pub signal_tx: SignalBroadcaster,
     pub conductor_api: C,
     pub is_root_zome_call: bool,
+    /// Record the host functions called by the zome function here
+    pub host_fn_trace: Option<HostFnTrace>,
 }
 
 #[instrument(skip(
@@ -100,6 +103,7 @@ async fn call_zome_workflow_inner<
         invocation,
         signal_tx,
         conductor_api,
+        host_fn_trace,
         ..
     } = args;
 
@@ -115,7 +119,7 @@ async fn call_zome_workflow_inner<
         let workspace_lock = workspace_lock.clone();
         let network = network.clone();
         move || {
-            let host_access = ZomeCallHostAccess::new(
+            let mut host_access = ZomeCallHostAccess::new(
                 workspace_lock,
                 keystore,
                 network,
@@ -123,6 +127,7 @@ async fn call_zome_workflow_inner<
                 call_zome_handle,
                 invocation.cell_id.clone(),
             );
+            host_access.host_fn_trace = host_fn_trace;
             let result = ribosome.call_zome_function(host_access, invocation);
             (ribosome, result)
         }
@@ -378,6 +383,7 @@ pub mod tests {
             signal_tx: SignalBroadcaster::noop(),
             conductor_api,
             is_root_zome_call: true,
+            host_fn_trace: None,
         };
         call_zome_workflow_inner(workspace.into(), network, keystore, args).await
     }
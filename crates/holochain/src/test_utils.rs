@@ -287,6 +287,8 @@ pub async fn setup_app_inner(
         .config(ConductorConfig {
             admin_interfaces: Some(vec![AdminInterfaceConfig {
                 driver: InterfaceDriver::Websocket { port: 0 },
+                allowed_origins: None,
+                address: None,
             }]),
             network,
             ..Default::default()
@@ -192,7 +192,10 @@ async fn speed_test(n: Option<usize>) -> TestEnvironments {
 
     // Setup websocket handle and app interface
     let (mut client, _) = websocket_client(&handle).await.unwrap();
-    let request = AdminRequest::AttachAppInterface { port: None };
+    let request = AdminRequest::AttachAppInterface {
+        port: None,
+        allowed_origins: None,
+    };
     let response = client.request(request);
     let response = response.await.unwrap();
     let app_port = match response {
@@ -320,6 +323,8 @@ pub async fn setup_app(
         .config(ConductorConfig {
             admin_interfaces: Some(vec![AdminInterfaceConfig {
                 driver: InterfaceDriver::Websocket { port: 0 },
+                allowed_origins: None,
+                address: None,
             }]),
             ..Default::default()
         })
@@ -74,7 +74,10 @@ fn create_config(port: u16, environment_path: PathBuf) -> ConductorConfig {
     ConductorConfig {
         admin_interfaces: Some(vec![AdminInterfaceConfig {
             driver: InterfaceDriver::Websocket { port },
+            allowed_origins: None,
+            address: None,
         }]),
+        app_interfaces: None,
         environment_path: environment_path.into(),
         network: None,
         signing_service_uri: None,
@@ -86,6 +89,9 @@ fn create_config(port: u16, environment_path: PathBuf) -> ConductorConfig {
         }),
         keystore_path: None,
         use_dangerous_test_keystore: true,
+        zome_call_metering: false,
+        interface_address: None,
+        db_sync_level: Default::default(),
     }
 }
 
@@ -165,6 +171,7 @@ async fn call_admin() {
         nick: "nick".into(),
         properties: Some(properties.clone()),
         membrane_proof: None,
+        existing_cell: None,
     };
     let agent_key = fake_agent_pubkey_1();
     let payload = InstallAppPayload {
@@ -261,7 +268,10 @@ pub async fn attach_app_interface(
     holochain: &mut Child,
     port: Option<u16>,
 ) -> u16 {
-    let request = AdminRequest::AttachAppInterface { port };
+    let request = AdminRequest::AttachAppInterface {
+        port,
+        allowed_origins: None,
+    };
     let response = client.request(request);
     let response = check_timeout(holochain, response, 1000).await;
     match response {
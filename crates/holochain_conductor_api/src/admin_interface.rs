@@ -2,6 +2,7 @@ use holo_hash::*;
 use holochain_types::prelude::*;
 use holochain_zome_types::cell::CellId;
 use kitsune_p2p::agent_store::AgentInfoSigned;
+use kitsune_p2p::dht_arc::DhtArc;
 
 /// Represents the available conductor functions to call over an Admin interface
 /// and will result in a corresponding [`AdminResponse`] message being sent back over the
@@ -39,6 +40,17 @@ pub enum AdminRequest {
     /// [`AdminResponse::DnaRegistered`]: enum.AdminResponse.html#variant.DnaRegistered
     RegisterDna(Box<RegisterDnaPayload>),
 
+    /// Check a DNA the way [`AdminRequest::RegisterDna`] would
+    /// without registering it, so nothing is stored in the conductor.
+    /// The wasm of every zome is loaded and asked for its entry defs.
+    ///
+    /// Will be responded to with an [`AdminResponse::DnaValidated`]
+    /// or an [`AdminResponse::Error`]
+    ///
+    /// [`AdminRequest::RegisterDna`]: enum.AdminRequest.html#variant.RegisterDna
+    /// [`AdminResponse::DnaValidated`]: enum.AdminResponse.html#variant.DnaValidated
+    ValidateDna(Box<RegisterDnaPayload>),
+
     /// Install an app from a list of `Dna` paths.
     /// Triggers genesis to be run on all `Cell`s and to be stored.
     /// An `App` is intended for use by
@@ -95,6 +107,18 @@ pub enum AdminRequest {
     /// [`AdminResponse::ActiveAppsListed`]: enum.AdminResponse.html#variant.ActiveAppsListed
     /// [`AdminResponse::Error`]: enum.AppResponse.html#variant.Error
     ListActiveApps,
+    /// List the installed Apps that are not active, with their cells.
+    /// Inactive Apps have no running cells so their cells are
+    /// not included in [`AdminRequest::ListCellIds`].
+    /// Takes no arguments.
+    ///
+    /// Will be responded to with an [`AdminResponse::InactiveAppsListed`]
+    /// or an [`AdminResponse::Error`]
+    ///
+    /// [`AdminRequest::ListCellIds`]: enum.AdminRequest.html#variant.ListCellIds
+    /// [`AdminResponse::InactiveAppsListed`]: enum.AdminResponse.html#variant.InactiveAppsListed
+    /// [`AdminResponse::Error`]: enum.AppResponse.html#variant.Error
+    ListInactiveApps,
     /// Changes the `App` specified by argument `installed_app_id` from an inactive state to an active state in the conductor,
     /// meaning that Zome calls can now be made and the `App` will be loaded on a reboot of the conductor.
     /// It is likely to want to call this after calling [`AdminRequest::InstallApp`], since a freshly
@@ -113,6 +137,7 @@ pub enum AdminRequest {
     /// Changes the `App` specified by argument `installed_app_id` from an active state to an inactive state in the conductor,
     /// meaning that Zome calls can no longer be made, and the `App` will not be loaded on a
     /// reboot of the conductor.
+    /// Cells the `App` shares with other active apps keep running.
     ///
     /// Will be responded to with an [`AdminResponse::AppDeactivated`]
     /// or an [`AdminResponse::Error`]
@@ -139,6 +164,13 @@ pub enum AdminRequest {
         /// Optional port, use None to let the
         /// OS choose a free port
         port: Option<u16>,
+        /// Only accept connections whose `Origin` header is one of these.
+        /// By default every connection is accepted.
+        /// See [`AdminInterfaceConfig::allowed_origins`].
+        ///
+        /// [`AdminInterfaceConfig::allowed_origins`]: crate::config::AdminInterfaceConfig::allowed_origins
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        allowed_origins: Option<Vec<String>>,
     },
     /// Dump the full state of the `Cell` specified by argument `cell_id`,
     /// including its chain, as a string containing JSON.
@@ -152,6 +184,49 @@ pub enum AdminRequest {
         /// The `CellId` for which to dump state
         cell_id: Box<CellId>,
     },
+    /// Count the [`DhtOp`]s held by the `Cell` specified by argument `cell_id`
+    /// by op type and by how far they are through validation and integration.
+    ///
+    /// This is useful for finding out why ops are not being integrated.
+    ///
+    /// Will be responded to with an [`AdminResponse::OpCountsDumped`]
+    /// or an [`AdminResponse::Error`]
+    ///
+    /// [`DhtOp`]: ../holochain_types/dht_op/enum.DhtOp.html
+    /// [`AdminResponse::Error`]: enum.AppResponse.html#variant.Error
+    /// [`AdminResponse::OpCountsDumped`]: enum.AdminResponse.html#variant.OpCountsDumped
+    DumpOpCounts {
+        /// The `CellId` for which to count ops
+        cell_id: Box<CellId>,
+    },
+    /// Get the totals for each zome function a cell has run
+    /// since the conductor started.
+    /// Only conductors with [`ConductorConfig::zome_call_metering`]
+    /// turned on keep these totals.
+    ///
+    /// Will be responded to with an [`AdminResponse::ZomeCallMeteringDumped`]
+    /// or an [`AdminResponse::Error`]
+    ///
+    /// [`ConductorConfig::zome_call_metering`]: crate::config::conductor::ConductorConfig::zome_call_metering
+    /// [`AdminResponse::Error`]: enum.AppResponse.html#variant.Error
+    /// [`AdminResponse::ZomeCallMeteringDumped`]: enum.AdminResponse.html#variant.ZomeCallMeteringDumped
+    DumpZomeCallMetering {
+        /// The `CellId` to get the totals for
+        cell_id: Box<CellId>,
+    },
+    /// Get the arc of the DHT each agent known to this conductor
+    /// claims to hold for a cell's dna.
+    /// This shows who is responsible for storing which data.
+    ///
+    /// Will be responded to with an [`AdminResponse::ArcsDumped`]
+    /// or an [`AdminResponse::Error`]
+    ///
+    /// [`AdminResponse::Error`]: enum.AppResponse.html#variant.Error
+    /// [`AdminResponse::ArcsDumped`]: enum.AdminResponse.html#variant.ArcsDumped
+    DumpArcs {
+        /// The `CellId` whose dna to get the arcs for
+        cell_id: Box<CellId>,
+    },
     /// Add a list [AgentInfoSigned] to this conductor's peer store.
     /// This is another way of finding peers on a dht.
     ///
@@ -177,6 +252,16 @@ pub enum AdminRequest {
         /// Optionally choose a specific agent info
         cell_id: Option<CellId>,
     },
+    /// Request the version of this conductor and of the
+    /// admin api it implements.
+    ///
+    /// Clients can use this to check they are talking to
+    /// a compatible conductor before making other requests.
+    ///
+    /// Will be responded to with an [`AdminResponse::VersionRequested`]
+    ///
+    /// [`AdminResponse::VersionRequested`]: enum.AdminResponse.html#variant.VersionRequested
+    RequestVersion,
 }
 
 /// Represents the possible responses to an [`AdminRequest`]
@@ -205,6 +290,13 @@ pub enum AdminResponse {
     /// [`AdminRequest::RegisterDna`]: enum.AdminRequest.html#variant.RegisterDna
     DnaRegistered(DnaHash),
 
+    /// The successful response to an [`AdminRequest::ValidateDna`].
+    ///
+    /// Contains the hash the DNA would be registered with.
+    ///
+    /// [`AdminRequest::ValidateDna`]: enum.AdminRequest.html#variant.ValidateDna
+    DnaValidated(DnaHash),
+
     /// The successful response to an [`AdminRequest::InstallApp`].
     ///
     /// The resulting [`InstalledApp`] contains the App id,
@@ -246,6 +338,12 @@ pub enum AdminResponse {
     ///
     /// [`AdminRequest::ListActiveApps`]: enum.AdminRequest.html#variant.ListActiveApps
     ActiveAppsListed(Vec<InstalledAppId>),
+    /// The succesful response to an [`AdminRequest::ListInactiveApps`].
+    ///
+    /// Contains every installed `App` that is not active and its cells
+    ///
+    /// [`AdminRequest::ListInactiveApps`]: enum.AdminRequest.html#variant.ListInactiveApps
+    InactiveAppsListed(Vec<InstalledApp>),
     /// The succesful response to an [`AdminRequest::AttachAppInterface`].
     ///
     /// `AppInterfaceApi` successfully attached.
@@ -276,6 +374,24 @@ pub enum AdminResponse {
     ///
     /// [`AdminRequest::DumpState`]: enum.AdminRequest.html#variant.DumpState
     StateDumped(String),
+    /// The succesful response to an [`AdminRequest::DumpOpCounts`].
+    ///
+    /// [`AdminRequest::DumpOpCounts`]: enum.AdminRequest.html#variant.DumpOpCounts
+    OpCountsDumped(OpCounts),
+    /// The succesful response to an [`AdminRequest::DumpZomeCallMetering`].
+    ///
+    /// There is an entry for each zome function the cell has run,
+    /// sorted by zome and function name.
+    ///
+    /// [`AdminRequest::DumpZomeCallMetering`]: enum.AdminRequest.html#variant.DumpZomeCallMetering
+    ZomeCallMeteringDumped(Vec<ZomeFnMetering>),
+    /// The succesful response to an [`AdminRequest::DumpArcs`].
+    ///
+    /// There is an entry for each agent in the cell's dna
+    /// found in the peer store, sorted by the center of their arc.
+    ///
+    /// [`AdminRequest::DumpArcs`]: enum.AdminRequest.html#variant.DumpArcs
+    ArcsDumped(Vec<AgentArc>),
     /// The succesful response to an [`AdminRequest::AddAgentInfo`].
     ///
     /// This means the agent info was successfully added to the peer store.
@@ -288,6 +404,153 @@ pub enum AdminResponse {
     ///
     /// [`AdminRequest::RequestAgentInfo`]: enum.AdminRequest.html#variant.RequestAgentInfo
     AgentInfoRequested(Vec<AgentInfoSigned>),
+    /// The succesful response to an [`AdminRequest::RequestVersion`].
+    ///
+    /// [`AdminRequest::RequestVersion`]: enum.AdminRequest.html#variant.RequestVersion
+    VersionRequested(ConductorVersion),
+}
+
+/// The version of this crate.
+/// Conductors and clients built with different versions
+/// of the admin api may not be able to understand each other.
+pub const CONDUCTOR_API_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The versions reported by a conductor in response
+/// to an [`AdminRequest::RequestVersion`].
+///
+/// [`AdminRequest::RequestVersion`]: enum.AdminRequest.html#variant.RequestVersion
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ConductorVersion {
+    /// The version of the holochain conductor.
+    pub holochain: String,
+    /// The [`CONDUCTOR_API_VERSION`] the conductor was built with.
+    ///
+    /// [`CONDUCTOR_API_VERSION`]: constant.CONDUCTOR_API_VERSION.html
+    pub conductor_api: String,
+}
+
+/// The arc of the DHT an agent holds, in response
+/// to an [`AdminRequest::DumpArcs`].
+///
+/// [`AdminRequest::DumpArcs`]: enum.AdminRequest.html#variant.DumpArcs
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AgentArc {
+    /// The agent that holds the arc.
+    pub agent: AgentPubKey,
+    /// The dht locations the agent is responsible for storing.
+    pub arc: DhtArc,
+}
+
+impl AgentArc {
+    /// The fraction of all dht locations the arc covers,
+    /// from `0.0` for nothing to `1.0` for the whole dht.
+    pub fn coverage(&self) -> f64 {
+        // Each step of the half length covers a location
+        // on both sides of the center, which is only counted once.
+        let len = match self.arc.half_length {
+            0 => 0,
+            half_length => 2 * half_length as u64 - 1,
+        };
+        (len as f64 / (1u64 << 32) as f64).min(1.0)
+    }
+}
+
+/// The number of [`DhtOp`]s a cell holds, in response
+/// to an [`AdminRequest::DumpOpCounts`].
+///
+/// [`DhtOp`]: ../holochain_types/dht_op/enum.DhtOp.html
+/// [`AdminRequest::DumpOpCounts`]: enum.AdminRequest.html#variant.DumpOpCounts
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct OpCounts {
+    /// The counts for each op type, keyed by its name, e.g. `StoreEntry`.
+    pub by_type: std::collections::BTreeMap<String, OpStatusCounts>,
+}
+
+impl OpCounts {
+    /// The counts of all op types added together.
+    pub fn total(&self) -> OpStatusCounts {
+        self.by_type
+            .values()
+            .fold(OpStatusCounts::default(), |total, c| total + *c)
+    }
+}
+
+/// How many ops are at each stage of validation and integration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct OpStatusCounts {
+    /// Waiting for sys validation.
+    pub pending: u64,
+    /// Waiting for dependencies before sys or app validation can go on.
+    pub awaiting_deps: u64,
+    /// Sys validated and waiting for app validation.
+    pub sys_validated: u64,
+    /// Validated and waiting to be integrated.
+    pub awaiting_integration: u64,
+    /// Integrated as valid.
+    pub integrated: u64,
+    /// Integrated as rejected or abandoned.
+    pub rejected: u64,
+}
+
+impl OpStatusCounts {
+    /// The number of ops at every stage.
+    pub fn total(&self) -> u64 {
+        self.pending
+            + self.awaiting_deps
+            + self.sys_validated
+            + self.awaiting_integration
+            + self.integrated
+            + self.rejected
+    }
+}
+
+impl std::ops::Add for OpStatusCounts {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            pending: self.pending + other.pending,
+            awaiting_deps: self.awaiting_deps + other.awaiting_deps,
+            sys_validated: self.sys_validated + other.sys_validated,
+            awaiting_integration: self.awaiting_integration + other.awaiting_integration,
+            integrated: self.integrated + other.integrated,
+            rejected: self.rejected + other.rejected,
+        }
+    }
+}
+
+/// The totals for one zome function, in response
+/// to an [`AdminRequest::DumpZomeCallMetering`].
+///
+/// The wasm engine can't meter fuel so the cost of a function
+/// is measured by how long it runs and how many host functions it calls.
+///
+/// [`AdminRequest::DumpZomeCallMetering`]: enum.AdminRequest.html#variant.DumpZomeCallMetering
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ZomeFnMetering {
+    /// The zome the function is in.
+    pub zome_name: String,
+    /// The name of the function.
+    pub fn_name: String,
+    /// How many times the function was called.
+    pub calls: u64,
+    /// How long all the calls took, including the host functions they called.
+    pub total_time: std::time::Duration,
+    /// How many host functions all the calls made.
+    pub host_fn_calls: u64,
+}
+
+impl ZomeFnMetering {
+    /// The average time a call took.
+    pub fn mean_time(&self) -> std::time::Duration {
+        if self.calls == 0 {
+            std::time::Duration::default()
+        } else {
+            std::time::Duration::from_nanos(
+                (self.total_time.as_nanos() / self.calls as u128) as u64,
+            )
+        }
+    }
 }
 
 /// Error type that goes over the websocket wire.
@@ -36,6 +36,17 @@ pub enum AppRequest {
     /// [`AppResponse::Error`]: enum.AppResponse.html#variant.Error
     ZomeCall(Box<ZomeCall>),
 
+    /// Call a zome function and record the host functions it calls.
+    /// Otherwise the same as [`AppRequest::ZomeCall`].
+    ///
+    /// Will be responded to with an [`AppResponse::ZomeCallTraced`]
+    /// or an [`AppResponse::Error`]
+    ///
+    /// [`AppRequest::ZomeCall`]: enum.AppRequest.html#variant.ZomeCall
+    /// [`AppResponse::ZomeCallTraced`]: enum.AppResponse.html#variant.ZomeCallTraced
+    /// [`AppResponse::Error`]: enum.AppResponse.html#variant.Error
+    ZomeCallTraced(Box<ZomeCall>),
+
     /// DEPRECATED. Use `ZomeCall`.
     ZomeCallInvocation(Box<ZomeCall>),
 
@@ -81,6 +92,14 @@ pub enum AppResponse {
     /// [`SerializedBytes`]: ../../../holochain_zome_types/query/struct.SerializedBytes.html
     ZomeCall(Box<ExternIO>),
 
+    /// The successful response to an [`AppRequest::ZomeCallTraced`].
+    ///
+    /// See [`TracedZomeCall`] for the output and the host function calls.
+    ///
+    /// [`AppRequest::ZomeCallTraced`]: enum.AppRequest.html#variant.ZomeCallTraced
+    /// [`TracedZomeCall`]: struct.TracedZomeCall.html
+    ZomeCallTraced(Box<TracedZomeCall>),
+
     /// DEPRECATED. See `ZomeCall`.
     ZomeCallInvocation(Box<ExternIO>),
 }
@@ -109,6 +128,30 @@ pub struct ZomeCall {
     pub provenance: AgentPubKey,
 }
 
+/// The result of a zome call made with an [`AppRequest::ZomeCallTraced`]
+///
+/// [`AppRequest::ZomeCallTraced`]: enum.AppRequest.html#variant.ZomeCallTraced
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct TracedZomeCall {
+    /// The serialized output of the zome function
+    pub output: ExternIO,
+    /// The host functions the zome function called, in the order they were called.
+    /// Calls to other zomes show up as a single `call` or `call_remote`
+    /// and the host functions they call are not included.
+    pub host_fn_calls: Vec<HostFnCall>,
+}
+
+/// A single call from a zome function into a host function
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HostFnCall {
+    /// The name of the host function, e.g. `create` or `sys_time`
+    pub name: String,
+    /// When the host function was called, relative to the start of the zome call
+    pub start: std::time::Duration,
+    /// How long the host function took
+    pub elapsed: std::time::Duration,
+}
+
 #[allow(missing_docs)]
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type", content = "data")]
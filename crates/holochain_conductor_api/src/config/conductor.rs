@@ -6,6 +6,7 @@ use serde::Deserialize;
 use serde::Serialize;
 
 mod admin_interface_config;
+mod db_sync_level;
 mod dpki_config;
 #[allow(missing_docs)]
 mod error;
@@ -16,6 +17,7 @@ pub mod paths;
 pub use paths::EnvironmentRootPath;
 
 pub use super::*;
+pub use db_sync_level::DbSyncLevel;
 pub use dpki_config::DpkiConfig;
 //pub use logger_config::LoggerConfig;
 pub use error::*;
@@ -73,8 +75,32 @@ pub struct ConductorConfig {
     /// Setup admin interfaces to control this conductor through a websocket connection
     pub admin_interfaces: Option<Vec<AdminInterfaceConfig>>,
 
+    /// App interfaces the conductor starts with,
+    /// as well as the ones attached through the admin interface.
+    #[serde(default)]
+    pub app_interfaces: Option<Vec<StaticAppInterfaceConfig>>,
+
+    /// The loopback or interface address the admin and app websockets
+    /// listen on, e.g. `::1` on a machine without IPv4.
+    /// If omitted, `127.0.0.1` is used or `::1` if there is no IPv4 loopback.
+    #[serde(default)]
+    pub interface_address: Option<std::net::IpAddr>,
+
     /// Config options for the network module. Optional.
     pub network: Option<holochain_p2p::kitsune_p2p::KitsuneP2pConfig>,
+
+    /// Record how often each zome function is called, how long it runs
+    /// and how many host functions it calls, to find expensive functions.
+    /// Read the totals with
+    /// [`AdminRequest::DumpZomeCallMetering`](crate::AdminRequest::DumpZomeCallMetering).
+    /// This makes every zome call a little slower so it is off by default.
+    #[serde(default)]
+    pub zome_call_metering: bool,
+
+    /// How long database writes wait to reach the disk.
+    /// If omitted, [`DbSyncLevel::Normal`] is used.
+    #[serde(default)]
+    pub db_sync_level: DbSyncLevel,
     //
     //
     // /// Which signals to emit
@@ -149,7 +175,11 @@ pub mod tests {
                 passphrase_service: Some(PassphraseServiceConfig::Cmd),
                 keystore_path: None,
                 admin_interfaces: None,
+                app_interfaces: None,
                 use_dangerous_test_keystore: false,
+                zome_call_metering: false,
+                interface_address: None,
+                db_sync_level: DbSyncLevel::Normal,
             }
         );
     }
@@ -236,13 +266,110 @@ pub mod tests {
                 passphrase_service: Some(PassphraseServiceConfig::Cmd),
                 keystore_path: None,
                 admin_interfaces: Some(vec![AdminInterfaceConfig {
-                    driver: InterfaceDriver::Websocket { port: 1234 }
+                    driver: InterfaceDriver::Websocket { port: 1234 },
+                    allowed_origins: None,
+                    address: None,
                 }]),
+                app_interfaces: None,
                 network: Some(network_config),
+                zome_call_metering: false,
+                interface_address: None,
+                db_sync_level: DbSyncLevel::Normal,
             }
         );
     }
 
+    #[test]
+    fn test_config_db_sync_level() {
+        let yaml = r#"---
+    environment_path: /path/to/env
+    db_sync_level: fast
+    "#;
+        let result: ConductorConfig = config_from_yaml(yaml).unwrap();
+        assert_eq!(result.db_sync_level, DbSyncLevel::Fast);
+
+        let yaml = r#"---
+    environment_path: /path/to/env
+    db_sync_level: off
+    "#;
+        let result: ConductorConfig = config_from_yaml(yaml).unwrap();
+        assert_eq!(result.db_sync_level, DbSyncLevel::Off);
+
+        let yaml = r#"---
+    environment_path: /path/to/env
+    db_sync_level: none
+    "#;
+        let result: ConductorConfigResult<ConductorConfig> = config_from_yaml(yaml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_config_unix_socket_admin_interface() {
+        let yaml = r#"---
+    environment_path: /path/to/env
+
+    admin_interfaces:
+      - driver:
+          type: unix_socket
+          path: /path/to/admin.sock
+    "#;
+        let result: ConductorConfig = config_from_yaml(yaml).unwrap();
+        assert_eq!(
+            result.admin_interfaces,
+            Some(vec![AdminInterfaceConfig {
+                driver: InterfaceDriver::UnixSocket {
+                    path: PathBuf::from("/path/to/admin.sock")
+                },
+                allowed_origins: None,
+                address: None,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_config_admin_interface_address() {
+        let yaml = r#"---
+    environment_path: /path/to/env
+
+    admin_interfaces:
+      - driver:
+          type: websocket
+          port: 0
+      - driver:
+          type: websocket
+          port: 9200
+        address: 172.17.0.1
+    "#;
+        let result: ConductorConfig = config_from_yaml(yaml).unwrap();
+        let addresses: Vec<_> = result
+            .admin_interfaces
+            .unwrap()
+            .into_iter()
+            .map(|i| i.address)
+            .collect();
+        assert_eq!(addresses, vec![None, Some("172.17.0.1".parse().unwrap())]);
+    }
+
+    #[test]
+    fn test_config_app_interfaces() {
+        let yaml = r#"---
+    environment_path: /path/to/env
+
+    app_interfaces:
+      - port: 9000
+        allowed_origins:
+          - hc-cli
+    "#;
+        let result: ConductorConfig = config_from_yaml(yaml).unwrap();
+        assert_eq!(
+            result.app_interfaces,
+            Some(vec![StaticAppInterfaceConfig {
+                port: 9000,
+                allowed_origins: Some(vec!["hc-cli".to_string()]),
+            }])
+        );
+    }
+
     #[test]
     fn test_config_keystore() {
         let yaml = r#"---
@@ -269,7 +396,11 @@ pub mod tests {
                 }),
                 keystore_path: Some(PathBuf::from("/path/to/keystore").into()),
                 admin_interfaces: None,
+                app_interfaces: None,
                 use_dangerous_test_keystore: true,
+                zome_call_metering: false,
+                interface_address: None,
+                db_sync_level: DbSyncLevel::Normal,
             }
         );
     }
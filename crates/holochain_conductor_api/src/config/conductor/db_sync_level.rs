@@ -0,0 +1,25 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+/// How long the conductor waits for its database writes to reach the disk.
+/// Writes are always asynchronous to some degree. Lower levels are faster
+/// but more of the latest writes can be lost if the machine crashes,
+/// so only use them for databases that can be thrown away, like in tests.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DbSyncLevel {
+    /// Data is flushed to disk as usual.
+    Normal,
+    /// The metadata of a commit isn't flushed to disk,
+    /// so a crash can lose the last commit.
+    Fast,
+    /// Nothing is explicitly flushed to disk and it is left to the OS.
+    /// A crash can lose any writes since the OS last flushed them.
+    Off,
+}
+
+impl Default for DbSyncLevel {
+    fn default() -> DbSyncLevel {
+        DbSyncLevel::Normal
+    }
+}
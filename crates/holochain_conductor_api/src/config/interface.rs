@@ -1,17 +1,45 @@
 use serde::Deserialize;
 use serde::Serialize;
+use std::net::IpAddr;
+use std::path::PathBuf;
 
 /// Information neeeded to spawn an Admin interface
 #[derive(Clone, Deserialize, Serialize, Debug, PartialEq)]
 pub struct AdminInterfaceConfig {
     /// By what means will the interface be exposed?
-    /// Current only option is a local websocket running on a configurable port.
+    /// Either a local websocket running on a configurable port
+    /// or, on unix, a websocket on a unix domain socket.
     pub driver: InterfaceDriver,
+    /// Only accept connections whose `Origin` header is one of these.
+    /// Connections without an `Origin` header are rejected too.
+    /// By default every connection is accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_origins: Option<Vec<String>>,
+    /// Listen on this address instead of the conductor's `interface_address`
+    /// so admin interfaces for different clients can listen on different networks.
+    /// Only used by websocket interfaces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<IpAddr>,
     // /// How long will this interface be accessible between authentications?
     // /// TODO: implement once we have authentication
     // _session_duration_seconds: Option<u32>,
 }
 
+/// Information needed to spawn an App interface when the conductor starts.
+/// Unlike the interfaces added with `AdminRequest::AttachAppInterface`
+/// these aren't kept in the conductor's state. They are started from
+/// the config every time the conductor starts instead.
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq)]
+pub struct StaticAppInterfaceConfig {
+    /// The port on which to establish the WebsocketListener.
+    /// If it is `0` the conductor picks a free port each time it starts.
+    pub port: u16,
+    /// Only accept connections whose `Origin` header is one of these.
+    /// By default every connection is accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_origins: Option<Vec<String>>,
+}
+
 /// Configuration for interfaces, specifying the means by which an interface
 /// should be opened.
 ///
@@ -25,4 +53,12 @@ pub enum InterfaceDriver {
         /// The port on which to establish the WebsocketListener
         port: u16,
     },
+    /// An interface implemented via Websockets over a unix domain socket.
+    /// Only supported on unix platforms.
+    /// Only admin interfaces can use a unix socket.
+    UnixSocket {
+        /// The path of the socket file.
+        /// A stale socket file at this path is replaced.
+        path: PathBuf,
+    },
 }
@@ -53,6 +53,31 @@ lazy_static! {
     };
 }
 
+/// How much LMDB flushes to disk when a write transaction commits.
+/// It is chosen when an environment is opened with
+/// [EnvironmentWrite::new_with_sync_level].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncLevel {
+    /// Flush the data and the metadata.
+    Normal,
+    /// Don't flush the metadata (`MDB_NOMETASYNC`).
+    /// A crash can lose the last commit.
+    Fast,
+    /// Don't flush anything and leave it to the OS (`MDB_NOSYNC`).
+    /// A crash can lose any writes the OS hasn't flushed yet.
+    Off,
+}
+
+impl SyncLevel {
+    fn flags(self) -> EnvironmentFlags {
+        match self {
+            SyncLevel::Normal => EnvironmentFlags::empty(),
+            SyncLevel::Fast => EnvironmentFlags::NO_META_SYNC,
+            SyncLevel::Off => EnvironmentFlags::NO_SYNC,
+        }
+    }
+}
+
 fn default_flags() -> EnvironmentFlags {
     // The flags WRITE_MAP and MAP_ASYNC make writes waaaaay faster by async writing to disk rather than blocking
     // There is some loss of data integrity guarantees that comes with this.
@@ -156,6 +181,17 @@ impl EnvironmentWrite {
         path_prefix: &Path,
         kind: EnvironmentKind,
         keystore: KeystoreSender,
+    ) -> DatabaseResult<EnvironmentWrite> {
+        Self::new_with_sync_level(path_prefix, kind, keystore, SyncLevel::Normal)
+    }
+
+    /// Create an environment that flushes to disk as much as `sync_level` says.
+    /// An environment that is already open keeps the level it was opened with.
+    pub fn new_with_sync_level(
+        path_prefix: &Path,
+        kind: EnvironmentKind,
+        keystore: KeystoreSender,
+        sync_level: SyncLevel,
     ) -> DatabaseResult<EnvironmentWrite> {
         let mut map = ENVIRONMENTS.write();
         let path = path_prefix.join(kind.path());
@@ -167,7 +203,8 @@ impl EnvironmentWrite {
             hash_map::Entry::Occupied(e) => e.get().clone(),
             hash_map::Entry::Vacant(e) => e
                 .insert({
-                    let rkv = rkv_builder(None, None)(&path)?;
+                    let flags = default_flags() | sync_level.flags();
+                    let rkv = rkv_builder(None, Some(flags))(&path)?;
                     tracing::debug!("Initializing databases for path {:?}", path);
                     initialize_databases(&rkv, &kind)?;
                     EnvironmentWrite(EnvironmentRead {
@@ -59,6 +59,10 @@ pub struct InstallAppDnaPayload {
     pub properties: Option<JsonProperties>,
     /// App-specific proof-of-membrane-membership, if required by this app
     pub membrane_proof: Option<MembraneProof>,
+    /// Use this Cell, which another app already installed, instead of creating a new one.
+    /// Its Dna must be the one the hash names, if there is one.
+    #[serde(default)]
+    pub existing_cell: Option<CellId>,
 }
 
 impl InstallAppDnaPayload {
@@ -70,6 +74,7 @@ impl InstallAppDnaPayload {
             nick,
             properties: None,
             membrane_proof: None,
+            existing_cell: None,
         }
     }
     /// Create a payload with no JsonProperties or MembraneProof. Good for tests.
@@ -80,6 +85,18 @@ impl InstallAppDnaPayload {
             nick,
             properties: None,
             membrane_proof: None,
+            existing_cell: None,
+        }
+    }
+    /// Create a payload that uses a Cell another app already installed.
+    pub fn existing(cell_id: CellId, nick: CellNick) -> Self {
+        Self {
+            path: None,
+            hash: Some(cell_id.dna_hash().clone()),
+            nick,
+            properties: None,
+            membrane_proof: None,
+            existing_cell: Some(cell_id),
         }
     }
 }
@@ -14,6 +14,33 @@
 //! [WebsocketReceiver](struct.WebsocketReceiver.html)
 //! ).
 //!
+//! On unix [websocket_bind_unix](fn.websocket_bind_unix.html) and
+//! [websocket_connect_unix](fn.websocket_connect_unix.html) do the same
+//! over a unix domain socket path.
+//!
+//! Connections to a peer that dies without closing the socket
+//! only notice once the tcp keepalive gives up. Set
+//! [ping_interval_ms](struct.WebsocketConfig.html#structfield.ping_interval_ms)
+//! to ping idle connections and fail pending requests sooner.
+//!
+//! Listeners with
+//! [allowed_origins](struct.WebsocketConfig.html#structfield.allowed_origins)
+//! reject connections that don't send one of those origins. Set
+//! [origin](struct.WebsocketConfig.html#structfield.origin) to send one.
+//!
+//! Set [proxy](struct.WebsocketConfig.html#structfield.proxy) to connect
+//! through a SOCKS5 or HTTP CONNECT proxy. Tls and the websocket handshake
+//! run over the proxied connection. Local hosts are connected to directly
+//! unless [ProxyConfig::force_local](struct.ProxyConfig.html#structfield.force_local)
+//! is set. Failures of the proxy itself come back as a
+//! [ProxyError](struct.ProxyError.html) so they can be told apart
+//! from the remote host refusing the connection.
+//!
+//! Requests still waiting for a response when the remote end closes
+//! the socket, or drops it without a close frame, fail with a
+//! [ConnectionClosedByPeer](struct.ConnectionClosedByPeer.html)
+//! that has the close code and reason.
+//!
 //! # Example
 //!
 //! ```
@@ -114,6 +141,9 @@ pub(crate) mod task_socket_sink;
 // handles receiving incoming messages
 pub(crate) mod task_socket_stream;
 
+// pings idle connections and closes dead ones
+pub(crate) mod task_keepalive;
+
 // the sender/write half of the split websocket
 mod websocket_sender;
 pub use websocket_sender::*;
@@ -126,6 +156,17 @@ pub use websocket_receiver::*;
 mod websocket_listener;
 pub use websocket_listener::*;
 
+// client side tls for wss connections
+mod tls;
+pub use tls::CertificateError;
+pub use tls::TlsConfig;
+
+// client side SOCKS5 and HTTP CONNECT proxies
+mod proxy;
+pub use proxy::ProxyConfig;
+pub use proxy::ProxyError;
+pub use proxy::ProxyErrorKind;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -172,4 +213,332 @@ mod tests {
 
         assert_eq!("echo: test", &rsp.0,);
     }
+
+    #[tokio::test]
+    async fn over_limit_response_is_an_error() {
+        observability::test_run().ok();
+        let mut server = websocket_bind(
+            url2!("ws://127.0.0.1:0"),
+            Arc::new(WebsocketConfig::default()),
+        )
+        .await
+        .unwrap();
+
+        let binding = server.local_addr().clone();
+
+        tokio::task::spawn(async move {
+            while let Some(maybe_con) = server.next().await {
+                let (_send, mut recv) = maybe_con.unwrap();
+
+                tokio::task::spawn(async move {
+                    if let Some(msg) = recv.next().await {
+                        if let WebsocketMessage::Request(_, respond) = msg {
+                            let msg = TestMessage("a".repeat(2 << 20));
+                            respond(msg.try_into().unwrap()).await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+
+        let config = WebsocketConfig::default()
+            .max_message_size(1 << 20)
+            .max_frame_size(1 << 20);
+        let (mut send, _recv) = websocket_connect(binding, Arc::new(config)).await.unwrap();
+
+        let msg = TestMessage("test".to_string());
+        let err = send.request::<_, TestMessage>(msg).await.unwrap_err();
+
+        assert_eq!(ErrorKind::ConnectionAborted, err.kind());
+        assert!(err
+            .to_string()
+            .contains("max_message_size of 1048576 bytes"));
+    }
+
+    #[tokio::test]
+    async fn large_responses_round_trip_with_and_without_compression() {
+        observability::test_run().ok();
+        let mut server = websocket_bind(
+            url2!("ws://127.0.0.1:0"),
+            Arc::new(WebsocketConfig::default()),
+        )
+        .await
+        .unwrap();
+
+        let binding = server.local_addr().clone();
+
+        tokio::task::spawn(async move {
+            while let Some(maybe_con) = server.next().await {
+                let (_send, mut recv) = maybe_con.unwrap();
+
+                tokio::task::spawn(async move {
+                    while let Some(msg) = recv.next().await {
+                        if let WebsocketMessage::Request(_, respond) = msg {
+                            let msg = TestMessage("a".repeat(1 << 20));
+                            respond(msg.try_into().unwrap()).await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+
+        for accept in &[true, false] {
+            let config = WebsocketConfig::default().accept_compressed_responses(*accept);
+            let (mut send, _recv) = websocket_connect(binding.clone(), Arc::new(config))
+                .await
+                .unwrap();
+
+            let msg = TestMessage("test".to_string());
+            let rsp: TestMessage = send.request(msg).await.unwrap();
+
+            assert_eq!("a".repeat(1 << 20), rsp.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn origins_not_allowed_are_rejected() {
+        observability::test_run().ok();
+        let config = WebsocketConfig::default().allowed_origins(vec!["hc-cli".to_string()]);
+        let mut server = websocket_bind(url2!("ws://127.0.0.1:0"), Arc::new(config))
+            .await
+            .unwrap();
+
+        let binding = server.local_addr().clone();
+
+        tokio::task::spawn(async move {
+            // rejected handshakes come out of the listener as errors
+            while let Some(maybe_con) = server.next().await {
+                if let Ok((_send, mut recv)) = maybe_con {
+                    tokio::task::spawn(async move { while recv.next().await.is_some() {} });
+                }
+            }
+        });
+
+        for origin in &[None, Some("http://example.com")] {
+            let mut config = WebsocketConfig::default();
+            config.origin = origin.map(String::from);
+            let err = websocket_connect(binding.clone(), Arc::new(config))
+                .await
+                .unwrap_err();
+            assert_eq!(ErrorKind::PermissionDenied, err.kind());
+            assert!(err.to_string().contains("not allowed"), "{}", err);
+        }
+
+        let config = WebsocketConfig::default().origin("hc-cli".to_string());
+        assert!(websocket_connect(binding, Arc::new(config)).await.is_ok());
+    }
+
+    /// internal accept one websocket and hand back the raw stream
+    /// so tests can play a misbehaving peer
+    async fn raw_peer() -> (
+        Url2,
+        tokio::sync::oneshot::Receiver<tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>>,
+    ) {
+        let mut listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = url2!("ws://{}", listener.local_addr().unwrap());
+        let (send, recv) = tokio::sync::oneshot::channel();
+        tokio::task::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            let _ = send.send(ws);
+        });
+        (url, recv)
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn pings_fail_requests_to_a_dead_peer() {
+        observability::test_run().ok();
+        let (url, peer) = raw_peer().await;
+
+        let config = WebsocketConfig::default()
+            .ping_interval_ms(50)
+            .max_missed_pings(2);
+        let (mut send, _recv) = websocket_connect(url, Arc::new(config)).await.unwrap();
+        // never read from the socket again so the pings go unanswered
+        let _peer = peer.await.unwrap();
+
+        let start = std::time::Instant::now();
+        let msg = TestMessage("test".to_string());
+        let err = send.request::<_, TestMessage>(msg).await.unwrap_err();
+
+        assert_eq!(ErrorKind::ConnectionAborted, err.kind());
+        assert!(err.to_string().contains("Connection lost"), "{}", err);
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn pings_wait_for_slow_large_responses() {
+        observability::test_run().ok();
+        let (url, peer) = raw_peer().await;
+
+        let config = WebsocketConfig::default()
+            .ping_interval_ms(50)
+            .max_missed_pings(2);
+        let (mut send, _recv) = websocket_connect(url, Arc::new(config)).await.unwrap();
+        let msg = TestMessage("test".to_string());
+        let request = tokio::task::spawn(send.request::<_, TestMessage>(msg));
+
+        let mut peer = peer.await.unwrap();
+        let id = match peer.next().await.unwrap().unwrap() {
+            tungstenite::Message::Binary(bytes) => {
+                let bytes: SerializedBytes = UnsafeBytes::from(bytes).into();
+                let msg: WireMessage = bytes.try_into().unwrap();
+                match msg {
+                    WireMessage::Request { id, .. } => id,
+                    msg => panic!("expected a request, got {:?}", msg),
+                }
+            }
+            msg => panic!("expected a request, got {:?}", msg),
+        };
+        let data: SerializedBytes = TestMessage("a".repeat(256 << 10)).try_into().unwrap();
+        let msg = WireMessage::Response {
+            id,
+            data: UnsafeBytes::from(data).into(),
+            compressed: false,
+        };
+        let msg: SerializedBytes = msg.try_into().unwrap();
+        let payload: Vec<u8> = UnsafeBytes::from(msg).into();
+
+        // write one binary frame by hand so it takes longer to arrive
+        // than the pings would wait for a pong
+        use tokio::io::AsyncWriteExt;
+        let socket = peer.get_mut();
+        let mut frame = vec![0x82, 127];
+        frame.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+        socket.write_all(&frame).await.unwrap();
+        for chunk in payload.chunks(payload.len() / 20 + 1) {
+            socket.write_all(chunk).await.unwrap();
+            tokio::time::delay_for(std::time::Duration::from_millis(20)).await;
+        }
+
+        let rsp = request.await.unwrap().unwrap();
+        assert_eq!("a".repeat(256 << 10), rsp.0);
+    }
+
+    /// internal read the request id from the raw peer
+    async fn next_request_id(
+        peer: &mut tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
+    ) -> String {
+        match peer.next().await.unwrap().unwrap() {
+            tungstenite::Message::Binary(bytes) => {
+                let bytes: SerializedBytes = UnsafeBytes::from(bytes).into();
+                let msg: WireMessage = bytes.try_into().unwrap();
+                match msg {
+                    WireMessage::Request { id, .. } => id,
+                    msg => panic!("expected a request, got {:?}", msg),
+                }
+            }
+            msg => panic!("expected a request, got {:?}", msg),
+        }
+    }
+
+    fn closed_by_peer(e: Error) -> ConnectionClosedByPeer {
+        assert_eq!(ErrorKind::ConnectionAborted, e.kind());
+        *e.into_inner()
+            .unwrap()
+            .downcast::<ConnectionClosedByPeer>()
+            .unwrap()
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn peer_closing_mid_request_is_a_typed_error() {
+        observability::test_run().ok();
+        let (url, peer) = raw_peer().await;
+
+        let (mut send, _recv) = websocket_connect(url, Arc::new(WebsocketConfig::default()))
+            .await
+            .unwrap();
+        let msg = TestMessage("test".to_string());
+        let request = tokio::task::spawn(send.request::<_, TestMessage>(msg));
+
+        let mut peer = peer.await.unwrap();
+        next_request_id(&mut peer).await;
+        peer.close(Some(tungstenite::protocol::CloseFrame {
+            code: 1001.into(),
+            reason: "shutting down".into(),
+        }))
+        .await
+        .unwrap();
+
+        let err = request.await.unwrap().unwrap_err();
+        let closed = closed_by_peer(ConnectionClosedByPeer::name_request(err, "Echo"));
+        assert_eq!(
+            ConnectionClosedByPeer {
+                during_request: Some("Echo".to_string()),
+                code: 1001,
+                reason: "shutting down".to_string(),
+            },
+            closed
+        );
+        assert_eq!(
+            "remote closed the websocket while handling Echo (code 1001: shutting down)",
+            closed.to_string()
+        );
+
+        // later requests don't wait for a socket that is gone
+        let msg = TestMessage("test".to_string());
+        let err = send.request::<_, TestMessage>(msg).await.unwrap_err();
+        assert!(err.to_string().contains("closed"), "{}", err);
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn peer_dropping_mid_request_is_a_typed_error() {
+        observability::test_run().ok();
+        let (url, peer) = raw_peer().await;
+
+        let (mut send, _recv) = websocket_connect(url, Arc::new(WebsocketConfig::default()))
+            .await
+            .unwrap();
+        let msg = TestMessage("test".to_string());
+        let request = tokio::task::spawn(send.request::<_, TestMessage>(msg));
+
+        let mut peer = peer.await.unwrap();
+        next_request_id(&mut peer).await;
+        drop(peer);
+
+        let closed = closed_by_peer(request.await.unwrap().unwrap_err());
+        assert_eq!(None, closed.during_request);
+        assert_eq!(1006, closed.code);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_socket_round_trip() {
+        observability::test_run().ok();
+        let dir = std::env::temp_dir().join(nanoid::nanoid!());
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.sock");
+        let mut server = websocket_bind_unix(&path, Arc::new(WebsocketConfig::default()))
+            .await
+            .unwrap();
+
+        assert_eq!("ws+unix", server.local_addr().scheme());
+        assert_eq!(path.to_str().unwrap(), server.local_addr().path());
+
+        tokio::task::spawn(async move {
+            while let Some(maybe_con) = server.next().await {
+                let (_send, mut recv) = maybe_con.unwrap();
+
+                tokio::task::spawn(async move {
+                    if let Some(msg) = recv.next().await {
+                        if let WebsocketMessage::Request(data, respond) = msg {
+                            let msg: TestMessage = data.try_into().unwrap();
+                            let msg = TestMessage(format!("echo: {}", msg.0));
+                            respond(msg.try_into().unwrap()).await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+
+        let (mut send, _recv) = websocket_connect_unix(&path, Arc::new(WebsocketConfig::default()))
+            .await
+            .unwrap();
+
+        let msg = TestMessage("test".to_string());
+        let rsp: TestMessage = send.request(msg).await.unwrap();
+
+        assert_eq!("echo: test", &rsp.0,);
+        std::fs::remove_dir_all(dir).ok();
+    }
 }
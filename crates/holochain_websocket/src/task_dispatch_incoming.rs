@@ -15,6 +15,8 @@ pub(crate) enum ToDispatchIncoming {
     },
     IncomingBytes(SerializedBytes),
     Close(WebsocketClosed),
+    /// the remote end closed the socket
+    PeerClosed(WebsocketClosed),
 }
 
 /// internal ToDispatchIncoming Sender
@@ -105,16 +107,44 @@ async fn process_incoming_message(
                         .await
                         .map_err(|e| Error::new(ErrorKind::Other, e))?;
                 }
-                WireMessage::Request { id, data } => {
+                WireMessage::Request {
+                    id,
+                    data,
+                    accept_compressed,
+                } => {
                     let data: SerializedBytes = UnsafeBytes::from(data).into();
                     tracing::trace!(message = "received request", ?data,);
                     let mut loc_send_sink = send_sink.clone();
+                    let compress_min_bytes = config.compress_min_bytes;
                     let respond: WebsocketRespond = Box::new(move |data| {
                         //let span = tracing::debug_span!("respond");
                         async move {
-                            let msg = WireMessage::Response {
-                                id,
-                                data: UnsafeBytes::from(data).into(),
+                            let data: Vec<u8> = UnsafeBytes::from(data).into();
+                            // only send compressed data if it's actually smaller
+                            let compressed =
+                                if accept_compressed && data.len() >= compress_min_bytes {
+                                    Some(compress(&data)?).filter(|c| c.len() < data.len())
+                                } else {
+                                    None
+                                };
+                            let msg = match compressed {
+                                Some(compressed) => {
+                                    tracing::trace!(
+                                        message = "compressed response",
+                                        from = data.len(),
+                                        to = compressed.len(),
+                                    );
+                                    WireMessage::Response {
+                                        id,
+                                        data: compressed,
+                                        compressed: true,
+                                    }
+                                }
+                                None => WireMessage::Response {
+                                    id,
+                                    data,
+                                    compressed: false,
+                                },
                             };
                             let bytes: SerializedBytes = msg.try_into()?;
                             let bytes: Vec<u8> = UnsafeBytes::from(bytes).into();
@@ -135,15 +165,57 @@ async fn process_incoming_message(
                         .await
                         .map_err(|e| Error::new(ErrorKind::Other, e))?;
                 }
-                WireMessage::Response { id, data } => {
+                WireMessage::Response {
+                    id,
+                    data,
+                    compressed,
+                } => {
+                    let data = if compressed {
+                        match decompress(&data, config.max_message_size) {
+                            Ok(Some(data)) => data,
+                            // treat this the same as an uncompressed message over the limit
+                            Ok(None) => {
+                                let closed = WebsocketClosed {
+                                    code: 1009,
+                                    reason: format!(
+                                        "Incoming message is larger than the max_message_size of {} bytes once decompressed",
+                                        config.max_message_size
+                                    ),
+                                };
+                                close_over_limit(send_pub, send_sink, tracker, closed).await?;
+                                // end this task
+                                return Ok(false);
+                            }
+                            Err(e) => {
+                                // only this request failed, the socket is fine
+                                tracker.handle_error(id, e);
+                                return Ok(true);
+                            }
+                        }
+                    } else {
+                        data
+                    };
                     let data: SerializedBytes = UnsafeBytes::from(data).into();
-                    tracing::trace!(message = "received response", ?data,);
+                    tracing::trace!(message = "received response", ?id, ?data,);
                     tracker.handle_response(id, data);
                 }
             }
         }
         // our raw socket is closed
         ToDispatchIncoming::Close(closed) => {
+            // no responses can arrive now so fail the pending requests
+            tracker.close_all(&closed);
+            send_pub
+                .send(WebsocketMessage::Close(closed))
+                .await
+                .map_err(|e| Error::new(ErrorKind::Other, e))?;
+
+            // end this task
+            return Ok(false);
+        }
+        // the remote closed the socket, e.g. it is shutting down
+        ToDispatchIncoming::PeerClosed(closed) => {
+            tracker.close_all_by_peer(&closed);
             send_pub
                 .send(WebsocketMessage::Close(closed))
                 .await
@@ -158,6 +230,34 @@ async fn process_incoming_message(
     Ok(true)
 }
 
+/// internal close the socket because a message was over the size limit
+async fn close_over_limit(
+    send_pub: &mut ToWebsocketReceiverSender,
+    send_sink: &mut ToSocketSinkSender,
+    tracker: &mut ResponseTracker,
+    closed: WebsocketClosed,
+) -> Result<()> {
+    tracing::error!(reason = %closed.reason);
+    tracker.close_all(&closed);
+    let (send, recv) = tokio::sync::oneshot::channel();
+    let frame = tungstenite::protocol::CloseFrame {
+        code: closed.code.into(),
+        reason: closed.reason.clone().into(),
+    };
+    // the remote may already be gone
+    if send_sink
+        .send((tungstenite::Message::Close(Some(frame)), send))
+        .await
+        .is_ok()
+    {
+        let _ = recv.await;
+    }
+    send_pub
+        .send(WebsocketMessage::Close(closed))
+        .await
+        .map_err(|e| Error::new(ErrorKind::Other, e))
+}
+
 /// internal track a response callback
 struct ResponseItem {
     expires_at: std::time::Instant,
@@ -165,9 +265,15 @@ struct ResponseItem {
     _span: tracing::Span,
 }
 
+/// how many timed out request ids are remembered so a late
+/// response can be told apart from one that matches no request
+const TIMED_OUT_MEMORY: usize = 64;
+
 /// internal struct for tracking response callbacks
 struct ResponseTracker {
     pending_responses: std::collections::HashMap<String, ResponseItem>,
+    /// the ids of the requests that timed out most recently
+    timed_out: std::collections::VecDeque<String>,
 }
 
 impl ResponseTracker {
@@ -175,6 +281,7 @@ impl ResponseTracker {
     fn priv_new() -> Self {
         Self {
             pending_responses: std::collections::HashMap::new(),
+            timed_out: std::collections::VecDeque::new(),
         }
     }
 
@@ -185,9 +292,77 @@ impl ResponseTracker {
 
     /// we received a response, try to match it up to a pending callback
     fn handle_response(&mut self, id: String, data: SerializedBytes) {
-        if let Some(mut item) = self.pending_responses.remove(&id) {
+        match self.pending_responses.remove(&id) {
+            Some(mut item) => {
+                if let Some(respond) = item.respond.take() {
+                    // The requester stopped waiting, e.g. the request was cancelled.
+                    if respond.send(Ok(data)).is_err() {
+                        tracing::debug!(
+                            ?id,
+                            "Discarding response for a request that is no longer waiting"
+                        );
+                    }
+                }
+            }
+            None => self.unmatched_response(&id),
+        }
+    }
+
+    /// we received a response we can't use, fail the pending callback
+    fn handle_error(&mut self, id: String, e: Error) {
+        match self.pending_responses.remove(&id) {
+            Some(mut item) => {
+                if let Some(respond) = item.respond.take() {
+                    if let Err(e) = respond.send(Err(e)) {
+                        tracing::warn!(error = ?e);
+                    }
+                }
+            }
+            None => self.unmatched_response(&id),
+        }
+    }
+
+    /// a response arrived with an id we aren't waiting for
+    fn unmatched_response(&self, id: &str) {
+        if self.timed_out.iter().any(|t| t == id) {
+            tracing::warn!(?id, "Received a response after its request timed out");
+        } else {
+            tracing::warn!(?id, "Received a response that matches no pending request");
+        }
+    }
+
+    /// the socket is closed - fail all pending callbacks with the reason
+    fn close_all(&mut self, closed: &WebsocketClosed) {
+        for (_, mut item) in self.pending_responses.drain() {
             if let Some(respond) = item.respond.take() {
-                if let Err(e) = respond.send(Ok(data)) {
+                let e = Error::new(
+                    ErrorKind::ConnectionAborted,
+                    format!(
+                        "Websocket closed before a response was received: {}",
+                        closed.reason
+                    ),
+                );
+                if let Err(e) = respond.send(Err(e)) {
+                    tracing::warn!(error = ?e);
+                }
+            }
+        }
+    }
+
+    /// the remote closed the socket - fail all pending callbacks
+    /// with a [ConnectionClosedByPeer] error
+    fn close_all_by_peer(&mut self, closed: &WebsocketClosed) {
+        for (_, mut item) in self.pending_responses.drain() {
+            if let Some(respond) = item.respond.take() {
+                let e = Error::new(
+                    ErrorKind::ConnectionAborted,
+                    ConnectionClosedByPeer {
+                        during_request: None,
+                        code: closed.code,
+                        reason: closed.reason.clone(),
+                    },
+                );
+                if let Err(e) = respond.send(Err(e)) {
                     tracing::warn!(error = ?e);
                 }
             }
@@ -197,13 +372,18 @@ impl ResponseTracker {
     /// check for any expired response callbacks - trigger timeout errors
     fn prune_expired(&mut self) {
         let now = std::time::Instant::now();
-        self.pending_responses.retain(|_k, v| {
+        let timed_out = &mut self.timed_out;
+        self.pending_responses.retain(|k, v| {
             if v.expires_at < now {
                 if let Some(respond) = v.respond.take() {
                     if let Err(e) = respond.send(Err(ErrorKind::TimedOut.into())) {
                         tracing::warn!(error = ?e);
                     }
                 }
+                timed_out.push_back(k.clone());
+                if timed_out.len() > TIMED_OUT_MEMORY {
+                    timed_out.pop_front();
+                }
                 false
             } else {
                 true
@@ -262,6 +442,20 @@ mod tests {
         let msg = WireMessage::Request {
             id: nanoid::nanoid!(),
             data,
+            accept_compressed: false,
+        };
+        let data: SerializedBytes = msg.try_into().unwrap();
+        ToDispatchIncoming::IncomingBytes(data)
+    }
+
+    fn test_request_accepting_compression(s: &str) -> ToDispatchIncoming {
+        let data: SerializedBytes = TestMessage(s.to_string()).try_into().unwrap();
+        let data: Vec<u8> = UnsafeBytes::from(data).into();
+
+        let msg = WireMessage::Request {
+            id: nanoid::nanoid!(),
+            data,
+            accept_compressed: true,
         };
         let data: SerializedBytes = msg.try_into().unwrap();
         ToDispatchIncoming::IncomingBytes(data)
@@ -285,7 +479,11 @@ mod tests {
         let data: SerializedBytes = TestMessage(s.to_string()).try_into().unwrap();
         let data: Vec<u8> = UnsafeBytes::from(data).into();
 
-        let msg = WireMessage::Response { id, data };
+        let msg = WireMessage::Response {
+            id,
+            data,
+            compressed: false,
+        };
         let data: SerializedBytes = msg.try_into().unwrap();
         ToDispatchIncoming::IncomingBytes(data)
     }
@@ -345,7 +543,7 @@ mod tests {
             .unwrap();
 
         match my_sink_recv.next().await.unwrap() {
-            WireMessage::Response { id: _, data } => {
+            WireMessage::Response { data, .. } => {
                 let msg: SerializedBytes = UnsafeBytes::from(data).into();
                 let msg: TestMessage = msg.try_into().unwrap();
                 assert_eq!("test3", &msg.0,);
@@ -375,4 +573,162 @@ mod tests {
 
         assert_eq!("None", &format!("{:?}", my_sink_recv.next().await));
     }
+
+    /// a writer that keeps everything the test subscriber logs
+    #[derive(Clone, Default)]
+    struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        fn logs(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unmatched_responses_are_warned_about() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_max_level(tracing::Level::WARN)
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let Prep {
+            recv_pub: _recv_pub,
+            recv_sink: _recv_sink,
+            mut send_dispatch,
+        } = prep_test();
+
+        // a stray response for a request that was never made
+        send_dispatch
+            .send(test_response("stray-id".to_string(), "stray"))
+            .await
+            .unwrap();
+
+        // a response that arrives after its request timed out
+        let (late_id, msg, late_recv) = test_register_response();
+        send_dispatch.send(msg).await.unwrap();
+        tokio::time::delay_for(std::time::Duration::from_millis(1100)).await;
+        // expired requests are pruned after the next message
+        let (id, msg, recv) = test_register_response();
+        send_dispatch.send(msg).await.unwrap();
+        assert_eq!(
+            "Err(Kind(TimedOut))",
+            &format!("{:?}", late_recv.await.unwrap())
+        );
+        send_dispatch
+            .send(test_response(late_id.clone(), "late"))
+            .await
+            .unwrap();
+
+        // responses are handled in order so the warnings
+        // are logged once this one is matched
+        send_dispatch.send(test_response(id, "test")).await.unwrap();
+        assert_eq!("Ok(\"test\")", &format!("{:?}", recv.await.unwrap()));
+
+        let logs = captured.logs();
+        let stray = logs
+            .lines()
+            .find(|l| l.contains("stray-id"))
+            .unwrap_or_else(|| panic!("no warning for the stray response in {}", logs));
+        assert!(stray.contains("WARN"), "{}", stray);
+        assert!(stray.contains("matches no pending request"), "{}", stray);
+        let late = logs
+            .lines()
+            .find(|l| l.contains(&late_id))
+            .unwrap_or_else(|| panic!("no warning for the late response in {}", logs));
+        assert!(late.contains("after its request timed out"), "{}", late);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_responses() {
+        init_tracing();
+
+        let Prep {
+            mut recv_pub,
+            mut recv_sink,
+            mut send_dispatch,
+        } = prep_test();
+
+        let big = "a".repeat(64 << 10);
+
+        // a large response to a request that accepts compression is compressed
+        send_dispatch
+            .send(test_request_accepting_compression("test1"))
+            .await
+            .unwrap();
+        let respond = match recv_pub.next().await.unwrap() {
+            WebsocketMessage::Request(_, respond) => respond,
+            _ => panic!("unexpected recv_pub type"),
+        };
+        let responding = tokio::task::spawn(respond(TestMessage(big.clone()).try_into().unwrap()));
+        let (msg, complete) = recv_sink.next().await.unwrap();
+        complete.send(()).unwrap();
+        responding.await.unwrap().unwrap();
+        let (data, compressed) = match msg {
+            tungstenite::Message::Binary(msg) => {
+                let msg: SerializedBytes = UnsafeBytes::from(msg).into();
+                let msg: WireMessage = msg.try_into().unwrap();
+                match msg {
+                    WireMessage::Response {
+                        data, compressed, ..
+                    } => (data, compressed),
+                    _ => panic!("unexpected response"),
+                }
+            }
+            _ => panic!("unexpected tungstenite type"),
+        };
+        assert!(compressed);
+        assert!(data.len() < big.len());
+
+        // it is decompressed when it arrives
+        let (id, msg, recv) = test_register_response();
+        send_dispatch.send(msg).await.unwrap();
+        let msg = WireMessage::Response {
+            id,
+            data,
+            compressed: true,
+        };
+        send_dispatch
+            .send(ToDispatchIncoming::IncomingBytes(msg.try_into().unwrap()))
+            .await
+            .unwrap();
+        let msg: TestMessage = recv.await.unwrap().unwrap().try_into().unwrap();
+        assert_eq!(big, msg.0);
+
+        // the same response to a request that doesn't accept compression is not
+        send_dispatch.send(test_request("test2")).await.unwrap();
+        let respond = match recv_pub.next().await.unwrap() {
+            WebsocketMessage::Request(_, respond) => respond,
+            _ => panic!("unexpected recv_pub type"),
+        };
+        let responding = tokio::task::spawn(respond(TestMessage(big).try_into().unwrap()));
+        let (msg, complete) = recv_sink.next().await.unwrap();
+        complete.send(()).unwrap();
+        responding.await.unwrap().unwrap();
+        match msg {
+            tungstenite::Message::Binary(msg) => {
+                let msg: SerializedBytes = UnsafeBytes::from(msg).into();
+                let msg: WireMessage = msg.try_into().unwrap();
+                match msg {
+                    WireMessage::Response { compressed, .. } => assert!(!compressed),
+                    _ => panic!("unexpected response"),
+                }
+            }
+            _ => panic!("unexpected tungstenite type"),
+        }
+    }
 }
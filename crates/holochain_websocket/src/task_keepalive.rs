@@ -0,0 +1,166 @@
+//! This task pings the remote end of an idle websocket and closes it
+//! if nothing comes back, so requests to a dead peer fail instead of hanging.
+//!
+//! Any bytes read from the socket count as a sign of life, not just pongs.
+//! A pong can be queued behind a large message that is still arriving
+//! so waiting on pongs alone would close healthy connections.
+
+use super::task_dispatch_incoming::ToDispatchIncoming;
+use super::task_dispatch_incoming::ToDispatchIncomingSender;
+use super::task_socket_sink::ToSocketSinkSender;
+use crate::*;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+
+/// internal the keepalive task stops when this is dropped
+pub(crate) type KeepaliveGuard = tokio::sync::oneshot::Sender<()>;
+
+/// internal records when a socket last read any bytes
+#[derive(Clone)]
+pub(crate) struct ReadClock(Arc<(Instant, AtomicU64)>);
+
+impl ReadClock {
+    /// internal constructor
+    fn new() -> Self {
+        Self(Arc::new((Instant::now(), AtomicU64::new(0))))
+    }
+
+    /// internal record a read
+    fn touch(&self) {
+        let (start, last) = &*self.0;
+        last.store(start.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// internal how long since the last read
+    fn idle(&self) -> Duration {
+        let (start, last) = &*self.0;
+        start
+            .elapsed()
+            .checked_sub(Duration::from_millis(last.load(Ordering::Relaxed)))
+            .unwrap_or_default()
+    }
+}
+
+/// internal a socket that records its reads on a [ReadClock]
+pub(crate) struct ReadTracked<S> {
+    inner: S,
+    clock: ReadClock,
+}
+
+impl<S> ReadTracked<S> {
+    /// internal constructor
+    pub(crate) fn new(inner: S) -> Self {
+        Self {
+            inner,
+            clock: ReadClock::new(),
+        }
+    }
+
+    /// internal the wrapped socket
+    pub(crate) fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// internal the clock this socket's reads are recorded on
+    pub(crate) fn clock(&self) -> ReadClock {
+        self.clock.clone()
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for ReadTracked<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        let read = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(n)) = &read {
+            if *n > 0 {
+                this.clock.touch();
+            }
+        }
+        read
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for ReadTracked<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// See module-level documentation for this internal task.
+/// Returns `None` if pings are disabled in the config.
+pub(crate) fn build(
+    config: Arc<WebsocketConfig>,
+    remote_addr: Url2,
+    clock: ReadClock,
+    mut send_sink: ToSocketSinkSender,
+    mut send_dispatch: ToDispatchIncomingSender,
+) -> Option<KeepaliveGuard> {
+    if config.ping_interval_ms == 0 {
+        return None;
+    }
+    let interval = Duration::from_millis(config.ping_interval_ms as u64);
+    let max_missed = config.max_missed_pings;
+    let (guard, mut stop) = tokio::sync::oneshot::channel();
+    tokio::task::spawn(async move {
+        tracing::trace!(
+            message = "starting keepalive task",
+            %remote_addr,
+        );
+        let mut missed = 0;
+        loop {
+            tokio::select! {
+                // the socket stream task ended
+                _ = &mut stop => break,
+                _ = tokio::time::delay_for(interval) => (),
+            }
+            if clock.idle() < interval {
+                missed = 0;
+                continue;
+            }
+            if missed >= max_missed {
+                let reason = format!(
+                    "Connection lost: nothing received after {} pings sent every {:?}",
+                    missed, interval
+                );
+                tracing::warn!(%remote_addr, %reason);
+                let msg = ToDispatchIncoming::Close(WebsocketClosed { code: 1006, reason });
+                let _ = send_dispatch.send(msg).await;
+                break;
+            }
+            missed += 1;
+            // don't wait behind outgoing messages, a full queue
+            // just means this ping counts as missed
+            let (send, _) = tokio::sync::oneshot::channel();
+            if send_sink
+                .try_send((tungstenite::Message::Ping(Vec::new()), send))
+                .is_err()
+            {
+                tracing::debug!(%remote_addr, "Send queue is full, skipping ping");
+            }
+        }
+        tracing::trace!(
+            message = "keepalive task ended",
+            %remote_addr,
+        );
+    });
+    Some(guard)
+}
@@ -1,5 +1,6 @@
 //! This task manages the incoming messages and events from the websocket stream.
 
+use super::task_keepalive::KeepaliveGuard;
 use super::task_socket_sink::ToSocketSinkSender;
 use crate::*;
 use task_dispatch_incoming::ToDispatchIncoming;
@@ -7,10 +8,12 @@ use task_dispatch_incoming::ToDispatchIncomingSender;
 
 /// See module-level documentation for this internal task
 pub(crate) fn build<S>(
+    config: Arc<WebsocketConfig>,
     remote_addr: Url2,
     mut send_sink: ToSocketSinkSender,
     mut send_dispatch: ToDispatchIncomingSender,
     mut stream: S,
+    keepalive: Option<KeepaliveGuard>,
 ) where
     S: 'static
         + std::marker::Unpin
@@ -18,6 +21,8 @@ pub(crate) fn build<S>(
         + Send,
 {
     tokio::task::spawn(async move {
+        // the keepalive task stops when this task ends
+        let _keepalive = keepalive;
         tracing::trace!(
             message = "starting socket stream task",
             %remote_addr,
@@ -52,17 +57,41 @@ pub(crate) fn build<S>(
                         }
                     }
                 }
+                Some(Err(e)) if is_reset_by_peer(&e) => {
+                    tracing::info!(
+                        message = "websocket reset by peer",
+                        %remote_addr,
+                        error = ?e,
+                    );
+                    let msg = ToDispatchIncoming::PeerClosed(WebsocketClosed {
+                        code: 1006,
+                        reason: format!("Connection reset: {}", e),
+                    });
+                    let _ = send_dispatch.send(msg).await;
+                    // end this task
+                    break;
+                }
                 Some(Err(e)) => {
                     tracing::error!(error = ?e);
-                    let msg = ToDispatchIncoming::Close(WebsocketClosed {
-                        code: 0,
-                        reason: format!("Internal Error: {:?}", e),
-                    });
+                    let reason = match e {
+                        tungstenite::Error::Capacity(e) => format!(
+                            "Incoming message is larger than the max_message_size of {} bytes or the max_frame_size of {} bytes: {}",
+                            config.max_message_size, config.max_frame_size, e
+                        ),
+                        e => format!("Internal Error: {:?}", e),
+                    };
+                    let msg = ToDispatchIncoming::Close(WebsocketClosed { code: 0, reason });
                     let _ = send_dispatch.send(msg).await;
                     // end this task
                     break;
                 }
                 None => {
+                    // the remote went away without a close frame
+                    let msg = ToDispatchIncoming::PeerClosed(WebsocketClosed {
+                        code: 1006,
+                        reason: "Connection ended without a close frame".to_string(),
+                    });
+                    let _ = send_dispatch.send(msg).await;
                     // end this task
                     break;
                 }
@@ -75,6 +104,22 @@ pub(crate) fn build<S>(
     });
 }
 
+/// internal did the remote end drop the connection without closing it,
+/// e.g. because its process exited
+fn is_reset_by_peer(e: &tungstenite::Error) -> bool {
+    match e {
+        tungstenite::Error::Io(e) => matches!(
+            e.kind(),
+            ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+                | ErrorKind::UnexpectedEof
+        ),
+        tungstenite::Error::Protocol(reason) => reason.contains("without closing handshake"),
+        _ => false,
+    }
+}
+
 /// internal process an individual incoming websocket message
 async fn process_incoming_message(
     remote_addr: &Url2,
@@ -97,7 +142,10 @@ async fn process_incoming_message(
             );
 
             send_dispatch
-                .send(ToDispatchIncoming::Close(WebsocketClosed { code, reason }))
+                .send(ToDispatchIncoming::PeerClosed(WebsocketClosed {
+                    code,
+                    reason,
+                }))
                 .await
                 .map_err(|e| Error::new(ErrorKind::Other, e))?;
 
@@ -147,7 +195,14 @@ mod tests {
         let (send_dispatch, recv_dispatch) = tokio::sync::mpsc::channel(1);
         let (send_stream, recv_stream) = tokio::sync::mpsc::channel(1);
 
-        build(url2!("test://"), send_sink, send_dispatch, recv_stream);
+        build(
+            Arc::new(WebsocketConfig::default()),
+            url2!("test://"),
+            send_sink,
+            send_dispatch,
+            recv_stream,
+            None,
+        );
 
         Prep {
             recv_sink,
@@ -203,7 +258,7 @@ mod tests {
             .unwrap();
 
         assert_eq!(
-            "Close(WebsocketClosed { code: 42, reason: \"test\" })",
+            "PeerClosed(WebsocketClosed { code: 42, reason: \"test\" })",
             &format!("{:?}", recv_dispatch.next().await.unwrap()),
         );
 
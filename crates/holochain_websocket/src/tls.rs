@@ -0,0 +1,584 @@
+//! client side tls for `wss://` connections
+
+use crate::*;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+
+/// A builder-style config for upgrading connections to tls.
+/// By default the server certificate must be signed by
+/// one of the system's certificate authorities.
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    /// PEM encoded certificate authorities to trust
+    /// instead of the system ones. [default = None]
+    pub ca_pem: Option<Vec<u8>>,
+
+    /// Accept any server certificate.
+    /// Only use this for self-signed certificates in a lab. [default = false]
+    pub insecure: bool,
+}
+
+impl TlsConfig {
+    /// Builder-style setter.
+    pub fn ca_pem(mut self, pem: Vec<u8>) -> Self {
+        self.ca_pem = Some(pem);
+        self
+    }
+
+    /// Builder-style setter.
+    pub fn insecure(mut self, insecure: bool) -> Self {
+        self.insecure = insecure;
+        self
+    }
+
+    /// internal build a rustls config for one connection
+    /// and the slot its verifier reports rejected certificates to
+    fn to_rustls(&self) -> Result<(Arc<rustls::ClientConfig>, Rejected)> {
+        let mut config = rustls::ClientConfig::new();
+        let rejected = Rejected::default();
+        if self.insecure {
+            config
+                .dangerous()
+                .set_certificate_verifier(Arc::new(InsecureVerifier));
+            return Ok((Arc::new(config), rejected));
+        }
+        config.root_store = match &self.ca_pem {
+            Some(pem) => {
+                let mut roots = rustls::RootCertStore::empty();
+                match roots.add_pem_file(&mut &pem[..]) {
+                    Ok((valid, _)) if valid > 0 => roots,
+                    _ => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "no valid PEM certificates in the tls ca",
+                        ))
+                    }
+                }
+            }
+            // use what could be loaded if some system certificates are broken
+            None => {
+                rustls_native_certs::load_native_certs().or_else(|(partial, e)| partial.ok_or(e))?
+            }
+        };
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(ReportingVerifier {
+                inner: rustls::WebPKIVerifier::new(),
+                rejected: rejected.clone(),
+            }));
+        Ok((Arc::new(config), rejected))
+    }
+}
+
+/// The server's tls certificate was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateError {
+    /// The subject of the certificate, e.g. `CN=example.com, O=Example`.
+    pub subject: String,
+    /// Why the certificate was rejected.
+    pub reason: String,
+}
+
+impl std::fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "tls certificate '{}' was rejected: {}",
+            self.subject, self.reason
+        )
+    }
+}
+
+impl std::error::Error for CertificateError {}
+
+/// internal where a verifier leaves the certificate it rejected
+/// so the handshake error can say which one and why
+type Rejected = Arc<Mutex<Option<CertificateError>>>;
+
+/// internal checks the certificate like rustls would
+/// and remembers why it was rejected
+struct ReportingVerifier {
+    inner: rustls::WebPKIVerifier,
+    rejected: Rejected,
+}
+
+impl rustls::ServerCertVerifier for ReportingVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &rustls::RootCertStore,
+        presented_certs: &[rustls::Certificate],
+        dns_name: webpki::DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> std::result::Result<rustls::ServerCertVerified, rustls::TLSError> {
+        self.inner
+            .verify_server_cert(roots, presented_certs, dns_name, ocsp_response)
+            .map_err(|e| {
+                let subject = presented_certs
+                    .first()
+                    .and_then(|cert| subject(&cert.0))
+                    .unwrap_or_else(|| "<no subject>".to_string());
+                let name: &str = dns_name.into();
+                *self.rejected.lock().unwrap() = Some(CertificateError {
+                    subject,
+                    reason: reason(&e, name),
+                });
+                e
+            })
+    }
+}
+
+/// internal accepts any certificate
+struct InsecureVerifier;
+
+impl rustls::ServerCertVerifier for InsecureVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &rustls::RootCertStore,
+        _presented_certs: &[rustls::Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> std::result::Result<rustls::ServerCertVerified, rustls::TLSError> {
+        Ok(rustls::ServerCertVerified::assertion())
+    }
+}
+
+/// internal describe a verification failure for a person
+fn reason(e: &rustls::TLSError, name: &str) -> String {
+    match e {
+        rustls::TLSError::WebPKIError(e) => match e {
+            webpki::Error::CertExpired => "it has expired".to_string(),
+            webpki::Error::CertNotValidYet => "it is not valid yet".to_string(),
+            webpki::Error::CertNotValidForName => format!("it is not valid for '{}'", name),
+            webpki::Error::UnknownIssuer => {
+                "it is not signed by a trusted certificate authority".to_string()
+            }
+            e => format!("{:?}", e),
+        },
+        rustls::TLSError::NoCertificatesPresented => "no certificate was sent".to_string(),
+        e => e.to_string(),
+    }
+}
+
+/// internal read one DER element
+/// returns the tag, its contents and the rest of the input
+fn der_element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&len, mut rest) = rest.split_first()?;
+    let len = if len & 0x80 == 0 {
+        len as usize
+    } else {
+        let n = (len & 0x7f) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let (bytes, r) = rest.split_at(n);
+        rest = r;
+        bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize)
+    };
+    if rest.len() < len {
+        return None;
+    }
+    let (contents, rest) = rest.split_at(len);
+    Some((tag, contents, rest))
+}
+
+/// internal all the DER elements in a sequence or set
+fn der_elements(mut input: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut out = Vec::new();
+    while !input.is_empty() {
+        let (tag, contents, rest) = der_element(input)?;
+        out.push((tag, contents));
+        input = rest;
+    }
+    Some(out)
+}
+
+/// internal the subject of a DER encoded x509 certificate
+/// rendered like `CN=example.com, O=Example`
+fn subject(cert: &[u8]) -> Option<String> {
+    const VERSION: u8 = 0xa0;
+    const OID: u8 = 0x06;
+    let (_, cert, _) = der_element(cert)?;
+    let (_, tbs, _) = der_element(cert)?;
+    let mut fields = der_elements(tbs)?;
+    if fields.first()?.0 == VERSION {
+        fields.remove(0);
+    }
+    // serial, signature, issuer, validity, subject
+    let (_, name) = fields.get(4)?;
+    let mut parts = Vec::new();
+    for (_, rdn) in der_elements(name)? {
+        for (_, attribute) in der_elements(rdn)? {
+            let attribute = der_elements(attribute)?;
+            let (oid, value) = match attribute.as_slice() {
+                [(OID, oid), (_, value)] => (oid, value),
+                _ => return None,
+            };
+            let key = match oid {
+                [0x55, 0x04, 0x03] => "CN",
+                [0x55, 0x04, 0x06] => "C",
+                [0x55, 0x04, 0x07] => "L",
+                [0x55, 0x04, 0x08] => "ST",
+                [0x55, 0x04, 0x0a] => "O",
+                [0x55, 0x04, 0x0b] => "OU",
+                _ => continue,
+            };
+            parts.push(format!("{}={}", key, String::from_utf8_lossy(value)));
+        }
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join(", "))
+}
+
+/// internal do the tls handshake for a new connection to `host`
+pub(crate) async fn connect(
+    config: &TlsConfig,
+    host: &str,
+    socket: tokio::net::TcpStream,
+) -> Result<TlsStream<rustls::ClientSession>> {
+    let (rustls_config, rejected) = config.to_rustls()?;
+    let name = match webpki::DNSNameRef::try_from_ascii_str(host) {
+        Ok(name) => name,
+        // nothing checks the name of an insecure connection
+        Err(_) if config.insecure => webpki::DNSNameRef::try_from_ascii_str("stub.stub").unwrap(),
+        Err(_) => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "tls needs a dns name to check the certificate against, got '{}'",
+                    host
+                ),
+            ))
+        }
+    };
+    let mut stream = TlsStream::new(socket, rustls::ClientSession::new(&rustls_config, name));
+    if let Err(e) = futures::future::poll_fn(|cx| stream.poll_handshake(cx)).await {
+        return Err(match rejected.lock().unwrap().take() {
+            Some(rejected) => Error::new(ErrorKind::InvalidData, rejected),
+            None => e,
+        });
+    }
+    Ok(stream)
+}
+
+/// internal a tcp socket that is either plain or tls
+pub(crate) enum Socket {
+    Tcp(tokio::net::TcpStream),
+    Tls(Box<TlsStream<rustls::ClientSession>>),
+    /// a unix domain socket and the path it is bound to
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream, std::path::PathBuf),
+}
+
+impl Socket {
+    /// the url of the other end of the socket
+    pub(crate) fn remote_url(&self, scheme: &str) -> Result<Url2> {
+        match self {
+            Socket::Tcp(s) => Ok(addr_to_url(s.peer_addr()?, scheme)),
+            Socket::Tls(s) => Ok(addr_to_url(s.io.peer_addr()?, scheme)),
+            #[cfg(unix)]
+            Socket::Unix(_, path) => path_to_url(path, scheme),
+        }
+    }
+}
+
+impl AsyncRead for Socket {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        match self.get_mut() {
+            Socket::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            Socket::Tls(s) => Pin::new(s.as_mut()).poll_read(cx, buf),
+            #[cfg(unix)]
+            Socket::Unix(s, _) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Socket {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        match self.get_mut() {
+            Socket::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            Socket::Tls(s) => Pin::new(s.as_mut()).poll_write(cx, buf),
+            #[cfg(unix)]
+            Socket::Unix(s, _) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        match self.get_mut() {
+            Socket::Tcp(s) => Pin::new(s).poll_flush(cx),
+            Socket::Tls(s) => Pin::new(s.as_mut()).poll_flush(cx),
+            #[cfg(unix)]
+            Socket::Unix(s, _) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        match self.get_mut() {
+            Socket::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            Socket::Tls(s) => Pin::new(s.as_mut()).poll_shutdown(cx),
+            #[cfg(unix)]
+            Socket::Unix(s, _) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+/// internal drives a rustls session over a tcp socket
+pub(crate) struct TlsStream<S> {
+    io: tokio::net::TcpStream,
+    session: S,
+    eof: bool,
+}
+
+/// internal blocking io adapter for rustls
+/// that turns a pending socket into `WouldBlock`
+struct SyncIo<'a, 'b> {
+    io: &'a mut tokio::net::TcpStream,
+    cx: &'a mut Context<'b>,
+}
+
+impl std::io::Read for SyncIo<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match Pin::new(&mut *self.io).poll_read(self.cx, buf) {
+            Poll::Ready(r) => r,
+            Poll::Pending => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+impl std::io::Write for SyncIo<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match Pin::new(&mut *self.io).poll_write(self.cx, buf) {
+            Poll::Ready(r) => r,
+            Poll::Pending => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match Pin::new(&mut *self.io).poll_flush(self.cx) {
+            Poll::Ready(r) => r,
+            Poll::Pending => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+/// internal turn a `WouldBlock` back into pending
+fn ready(r: Result<usize>) -> Poll<Result<usize>> {
+    match r {
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Poll::Pending,
+        r => Poll::Ready(r),
+    }
+}
+
+impl<S: rustls::Session> TlsStream<S> {
+    pub(crate) fn new(io: tokio::net::TcpStream, session: S) -> Self {
+        Self {
+            io,
+            session,
+            eof: false,
+        }
+    }
+
+    /// read tls data from the socket and process it
+    fn read_io(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let n = match ready(self.session.read_tls(&mut SyncIo {
+            io: &mut self.io,
+            cx,
+        })) {
+            Poll::Ready(Ok(n)) => n,
+            other => return other,
+        };
+        if n == 0 {
+            self.eof = true;
+        }
+        if let Err(e) = self.session.process_new_packets() {
+            // try to tell the other end why
+            let _ = self.write_io(cx);
+            return Poll::Ready(Err(Error::new(ErrorKind::InvalidData, e)));
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    /// write pending tls data to the socket
+    fn write_io(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        match ready(self.session.write_tls(&mut SyncIo {
+            io: &mut self.io,
+            cx,
+        })) {
+            Poll::Ready(Ok(0)) => Poll::Ready(Err(ErrorKind::WriteZero.into())),
+            other => other,
+        }
+    }
+
+    /// write all pending tls data to the socket
+    fn poll_write_all(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.session.wants_write() {
+            if let Err(e) = futures::ready!(self.write_io(cx)) {
+                return Poll::Ready(Err(e));
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// drive the handshake until it is complete
+    pub(crate) fn poll_handshake(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        loop {
+            futures::ready!(self.poll_write_all(cx))?;
+            if !self.session.is_handshaking() {
+                return Poll::Ready(Ok(()));
+            }
+            futures::ready!(self.read_io(cx))?;
+            if self.eof {
+                return Poll::Ready(Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "connection closed during the tls handshake",
+                )));
+            }
+        }
+    }
+}
+
+impl<S: rustls::Session + Unpin> AsyncRead for TlsStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            let n = std::io::Read::read(&mut this.session, buf)?;
+            if n > 0 || buf.is_empty() || this.eof {
+                return Poll::Ready(Ok(n));
+            }
+            futures::ready!(this.read_io(cx))?;
+            // answer anything the other end is waiting on, e.g. key updates
+            if let Poll::Ready(Err(e)) = this.poll_write_all(cx) {
+                return Poll::Ready(Err(e));
+            }
+        }
+    }
+}
+
+impl<S: rustls::Session + Unpin> AsyncWrite for TlsStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        // only buffer one write in the session at a time
+        futures::ready!(this.poll_write_all(cx))?;
+        let n = std::io::Write::write(&mut this.session, buf)?;
+        if let Poll::Ready(Err(e)) = this.poll_write_all(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        std::io::Write::flush(&mut this.session)?;
+        futures::ready!(this.poll_write_all(cx))?;
+        Pin::new(&mut this.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        this.session.send_close_notify();
+        futures::ready!(this.poll_write_all(cx))?;
+        Pin::new(&mut this.io).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a tls server that accepts websocket connections
+    /// and keeps them open
+    async fn tls_server(cert: &rcgen::Certificate) -> u16 {
+        let mut config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+        config
+            .set_single_cert(
+                vec![rustls::Certificate(cert.serialize_der().unwrap())],
+                rustls::PrivateKey(cert.serialize_private_key_der()),
+            )
+            .unwrap();
+        let config = Arc::new(config);
+        let mut listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::task::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut stream = TlsStream::new(socket, rustls::ServerSession::new(&config));
+                tokio::task::spawn(async move {
+                    if futures::future::poll_fn(|cx| stream.poll_handshake(cx))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                    if let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await {
+                        use futures::stream::StreamExt;
+                        while let Some(Ok(_)) = ws.next().await {}
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    fn cert(name: &str) -> rcgen::Certificate {
+        let mut params = rcgen::CertificateParams::new(vec![name.to_string()]);
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "hc test");
+        rcgen::Certificate::from_params(params).unwrap()
+    }
+
+    fn ws_config(tls: TlsConfig) -> Arc<WebsocketConfig> {
+        Arc::new(WebsocketConfig::default().tls(tls))
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn connects_with_a_custom_ca() {
+        observability::test_run().ok();
+        let cert = cert("localhost");
+        let port = tls_server(&cert).await;
+        let tls = TlsConfig::default().ca_pem(cert.serialize_pem().unwrap().into_bytes());
+        websocket_connect(url2!("wss://localhost:{}", port), ws_config(tls))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn rejected_certificates_name_the_subject_and_reason() {
+        observability::test_run().ok();
+        let cert = cert("example.com");
+        let port = tls_server(&cert).await;
+        let tls = TlsConfig::default().ca_pem(cert.serialize_pem().unwrap().into_bytes());
+        let err = websocket_connect(url2!("wss://localhost:{}", port), ws_config(tls))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let err = err
+            .into_inner()
+            .unwrap()
+            .downcast::<CertificateError>()
+            .unwrap();
+        assert_eq!(err.subject, "CN=hc test");
+        assert_eq!(err.reason, "it is not valid for 'localhost'");
+
+        // an insecure connection accepts it
+        let tls = TlsConfig::default().insecure(true);
+        websocket_connect(url2!("wss://localhost:{}", port), ws_config(tls))
+            .await
+            .unwrap();
+    }
+}
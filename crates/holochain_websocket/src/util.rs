@@ -46,38 +46,93 @@ pub(crate) enum WireMessage {
         id: String,
         #[serde(with = "serde_bytes")]
         data: Vec<u8>,
+        /// The requester can decompress the response.
+        /// Peers that predate compression never set this
+        /// so they always get uncompressed responses.
+        #[serde(default, skip_serializing_if = "is_false")]
+        accept_compressed: bool,
     },
     Response {
         id: String,
         #[serde(with = "serde_bytes")]
         data: Vec<u8>,
+        /// The data is deflate compressed.
+        #[serde(default, skip_serializing_if = "is_false")]
+        compressed: bool,
     },
 }
 try_from_serialized_bytes!(WireMessage);
 
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+/// internal deflate compress response data
+pub(crate) fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Write;
+    let mut encoder =
+        flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// internal inflate compressed response data
+/// returns None if the data inflates to more than `max_size` bytes
+pub(crate) fn decompress(data: &[u8], max_size: usize) -> Result<Option<Vec<u8>>> {
+    use std::io::Read;
+    let mut out = Vec::new();
+    flate2::read::DeflateDecoder::new(data)
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    if out.len() > max_size {
+        return Ok(None);
+    }
+    Ok(Some(out))
+}
+
 #[cfg(test)]
 pub(crate) fn init_tracing() {
     observability::test_run().unwrap();
 }
 
 /// internal socket type
-pub(crate) type RawSocket = tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>;
+pub(crate) type RawSocket =
+    tokio_tungstenite::WebSocketStream<task_keepalive::ReadTracked<tls::Socket>>;
 
 /// internal helper to convert addrs to urls
 pub(crate) fn addr_to_url(a: SocketAddr, scheme: &str) -> Url2 {
     url2!("{}://{}", scheme, a)
 }
 
-/// internal helper convert urls to socket addrs for binding / connection
-pub(crate) async fn url_to_addr(url: &Url2, scheme: &str) -> Result<SocketAddr> {
-    if url.scheme() != scheme || url.host_str().is_none() || url.port().is_none() {
-        return Err(Error::new(
+/// internal helper convert a unix socket path to a url
+/// like `ws+unix:///path/to/admin.sock`
+#[cfg(unix)]
+pub(crate) fn path_to_url(path: &std::path::Path, scheme: &str) -> Result<Url2> {
+    let path = std::env::current_dir()?.join(path);
+    Url2::try_parse(format!("{}+unix://{}", scheme, path.display())).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("bad socket path '{}': {:?}", path.display(), e),
+        )
+    })
+}
+
+/// internal helper check a url has this scheme, a host and a port
+pub(crate) fn url_host_port<'a>(url: &'a Url2, scheme: &str) -> Result<(&'a str, u16)> {
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) if url.scheme() == scheme => Ok((host, port)),
+        _ => Err(Error::new(
             ErrorKind::InvalidInput,
             format!("got: '{}', expected: '{}://host:port'", scheme, url),
-        ));
+        )),
     }
+}
 
-    let rendered = format!("{}:{}", url.host_str().unwrap(), url.port().unwrap());
+/// internal helper convert urls to socket addrs for binding / connection
+pub(crate) async fn url_to_addr(url: &Url2, scheme: &str) -> Result<SocketAddr> {
+    let (host, port) = url_host_port(url, scheme)?;
+    let rendered = format!("{}:{}", host, port);
 
     if let Ok(mut iter) = tokio::net::lookup_host(rendered.clone()).await {
         let mut tmp = iter.next();
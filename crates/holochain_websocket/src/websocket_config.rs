@@ -1,5 +1,8 @@
 //! defines a builder-style config struct for setting up websockets
 
+use crate::ProxyConfig;
+use crate::TlsConfig;
+
 /// A builder-style config struct for setting up websockets.
 #[derive(Debug)]
 pub struct WebsocketConfig {
@@ -24,8 +27,43 @@ pub struct WebsocketConfig {
     /// Maximum websocket frame size. [default = 16M]
     pub max_frame_size: usize,
 
+    /// Ping the remote end after this many milliseconds without
+    /// receiving anything. Zero disables pings. [default = 0]
+    pub ping_interval_ms: usize,
+
+    /// Close the connection when this many pings in a row get nothing back.
+    /// Pending requests then fail with a "Connection lost" error.
+    /// Anything received counts, so a large message that is still
+    /// arriving keeps the connection alive. [default = 3]
+    pub max_missed_pings: usize,
+
     /// Maximum number of pending new incoming connections. [default = 255]
     pub max_pending_connections: usize,
+
+    /// Ask the remote end to compress large responses to our requests.
+    /// Remotes that don't support compression ignore this.
+    /// Disable it to see the raw messages when debugging. [default = true]
+    pub accept_compressed_responses: bool,
+
+    /// Responses to requests that accept compression are compressed
+    /// if they are at least this many bytes. [default = 16K]
+    pub compress_min_bytes: usize,
+
+    /// Upgrade outgoing connections to tls.
+    /// Listeners do not serve tls. [default = None]
+    pub tls: Option<TlsConfig>,
+
+    /// Make outgoing tcp connections through this proxy.
+    /// Tls runs over the proxied connection. [default = None]
+    pub proxy: Option<ProxyConfig>,
+
+    /// Send this `Origin` header on outgoing connections. [default = None]
+    pub origin: Option<String>,
+
+    /// Listeners reject incoming connections with HTTP 403 unless
+    /// they send one of these as their `Origin` header.
+    /// None accepts every connection. [default = None]
+    pub allowed_origins: Option<Vec<String>>,
 }
 
 impl Default for WebsocketConfig {
@@ -37,7 +75,15 @@ impl Default for WebsocketConfig {
             max_send_queue: 10,
             max_message_size: 64 << 20,
             max_frame_size: 16 << 20,
+            ping_interval_ms: 0,
+            max_missed_pings: 3,
             max_pending_connections: 255,
+            accept_compressed_responses: true,
+            compress_min_bytes: 16 << 10,
+            tls: None,
+            proxy: None,
+            origin: None,
+            allowed_origins: None,
         }
     }
 }
@@ -78,6 +124,56 @@ impl WebsocketConfig {
         self.max_frame_size = max;
         self
     }
+
+    /// Builder-style setter.
+    pub fn ping_interval_ms(mut self, ms: usize) -> Self {
+        self.ping_interval_ms = ms;
+        self
+    }
+
+    /// Builder-style setter.
+    pub fn max_missed_pings(mut self, max: usize) -> Self {
+        self.max_missed_pings = max;
+        self
+    }
+
+    /// Builder-style setter.
+    pub fn accept_compressed_responses(mut self, accept: bool) -> Self {
+        self.accept_compressed_responses = accept;
+        self
+    }
+
+    /// Builder-style setter.
+    pub fn compress_min_bytes(mut self, min: usize) -> Self {
+        self.compress_min_bytes = min;
+        self
+    }
+
+    /// Builder-style setter.
+    /// Also sets the scheme to "wss".
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.scheme = "wss";
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Builder-style setter.
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Builder-style setter.
+    pub fn origin(mut self, origin: String) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Builder-style setter.
+    pub fn allowed_origins(mut self, origins: Vec<String>) -> Self {
+        self.allowed_origins = Some(origins);
+        self
+    }
 }
 
 /// internal helper to convert our configs into tungstenite configs
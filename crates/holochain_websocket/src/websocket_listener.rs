@@ -3,6 +3,10 @@
 use crate::*;
 use futures::stream::BoxStream;
 use futures::stream::StreamExt;
+use tungstenite::handshake::server::Callback;
+use tungstenite::handshake::server::ErrorResponse;
+use tungstenite::handshake::server::Request as HandshakeRequest;
+use tungstenite::handshake::server::Response as HandshakeResponse;
 
 /// Websocket listening / server socket. This struct is an async Stream -
 /// calling `.next().await` will give you a Future that will in turn resolve
@@ -75,6 +79,37 @@ pub async fn websocket_bind(addr: Url2, config: Arc<WebsocketConfig>) -> Result<
     })
 }
 
+/// Bind a new websocket listening socket on a unix domain socket at this path.
+/// The path must not already exist.
+/// Returns a [WebsocketListener](struct.WebsocketListener.html) instance
+/// with a `ws+unix://` local address.
+#[cfg(unix)]
+pub async fn websocket_bind_unix(
+    path: &std::path::Path,
+    config: Arc<WebsocketConfig>,
+) -> Result<WebsocketListener> {
+    let socket = tokio::net::UnixListener::bind(path)?;
+    let local_addr = path_to_url(path, config.scheme)?;
+    let path = path.to_path_buf();
+    let socket = socket
+        .map({
+            let config = config.clone();
+            move |socket_result| connect_unix(config.clone(), path.clone(), socket_result)
+        })
+        .buffer_unordered(config.max_pending_connections)
+        .boxed();
+
+    tracing::info!(
+        message = "bind",
+        local_addr = %local_addr,
+    );
+    Ok(WebsocketListener {
+        config,
+        local_addr,
+        socket,
+    })
+}
+
 /// Connects the new listener
 async fn connect(
     config: Arc<WebsocketConfig>,
@@ -89,8 +124,9 @@ async fn connect(
                 message = "accepted incoming raw socket",
                 remote_addr = %socket.peer_addr()?,
             );
-            let socket = tokio_tungstenite::accept_async_with_config(
-                socket,
+            let socket = tokio_tungstenite::accept_hdr_async_with_config(
+                task_keepalive::ReadTracked::new(tls::Socket::Tcp(socket)),
+                CheckOrigin(config.clone()),
                 Some(tungstenite::protocol::WebSocketConfig {
                     max_send_queue: Some(config.max_send_queue),
                     max_message_size: Some(config.max_message_size),
@@ -104,3 +140,58 @@ async fn connect(
         Err(e) => Err(Error::new(ErrorKind::Other, e)),
     }
 }
+
+/// Connects the new unix socket listener
+#[cfg(unix)]
+async fn connect_unix(
+    config: Arc<WebsocketConfig>,
+    path: std::path::PathBuf,
+    socket_result: std::io::Result<tokio::net::UnixStream>,
+) -> Result<(WebsocketSender, WebsocketReceiver)> {
+    let socket = socket_result?;
+    tracing::debug!(
+        message = "accepted incoming unix socket",
+        path = %path.display(),
+    );
+    let socket = tokio_tungstenite::accept_hdr_async_with_config(
+        task_keepalive::ReadTracked::new(tls::Socket::Unix(socket, path)),
+        CheckOrigin(config.clone()),
+        Some(config.to_tungstenite()),
+    )
+    .await
+    .map_err(|e| Error::new(ErrorKind::Other, e))?;
+    build_websocket_pair(config, socket)
+}
+
+/// internal handshake callback that rejects origins
+/// not in the config's allowed_origins
+struct CheckOrigin(Arc<WebsocketConfig>);
+
+impl Callback for CheckOrigin {
+    fn on_request(
+        self,
+        request: &HandshakeRequest,
+        response: HandshakeResponse,
+    ) -> std::result::Result<HandshakeResponse, ErrorResponse> {
+        let allowed = match &self.0.allowed_origins {
+            Some(allowed) => allowed,
+            None => return Ok(response),
+        };
+        let origin = request
+            .headers()
+            .get(tungstenite::http::header::ORIGIN)
+            .and_then(|o| o.to_str().ok());
+        match origin {
+            Some(origin) if allowed.iter().any(|a| a == origin) => Ok(response),
+            _ => {
+                tracing::warn!(
+                    ?origin,
+                    "rejecting a connection from an origin that is not allowed"
+                );
+                let mut error = ErrorResponse::new(Some("Origin not allowed".to_string()));
+                *error.status_mut() = tungstenite::http::StatusCode::FORBIDDEN;
+                Err(error)
+            }
+        }
+    }
+}
@@ -13,6 +13,68 @@ pub struct WebsocketClosed {
     pub reason: String,
 }
 
+/// The remote end closed the websocket while a request was waiting
+/// for its response, e.g. because the remote process shut down.
+/// Requests that fail this way are a
+/// [ConnectionAborted](std::io::ErrorKind::ConnectionAborted) error
+/// wrapping this.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionClosedByPeer {
+    /// The request that was in flight, if the caller named it.
+    /// The websocket doesn't know what its requests are so this is
+    /// always `None` until set with [ConnectionClosedByPeer::during].
+    pub during_request: Option<String>,
+
+    /// Websocket canonical close code.
+    /// This is 1006 if the connection ended without a close frame.
+    pub code: u16,
+
+    /// Subjective close reason.
+    pub reason: String,
+}
+
+impl ConnectionClosedByPeer {
+    /// Name the request that was in flight.
+    pub fn during(mut self, request: impl Into<String>) -> Self {
+        self.during_request = Some(request.into());
+        self
+    }
+
+    /// Name the request in flight if this error wraps
+    /// a [ConnectionClosedByPeer]. Other errors are unchanged.
+    pub fn name_request(e: Error, request: &str) -> Error {
+        let is_closed = e.get_ref().map_or(false, |e| e.is::<Self>());
+        if !is_closed {
+            return e;
+        }
+        let kind = e.kind();
+        let closed = e
+            .into_inner()
+            .and_then(|e| e.downcast::<Self>().ok())
+            .expect("checked the error is a ConnectionClosedByPeer");
+        Error::new(kind, closed.during(request))
+    }
+}
+
+impl std::fmt::Display for ConnectionClosedByPeer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.during_request {
+            Some(request) => write!(f, "remote closed the websocket while handling {}", request)?,
+            None => write!(
+                f,
+                "remote closed the websocket before a response was received"
+            )?,
+        }
+        if self.reason.is_empty() {
+            write!(f, " (code {})", self.code)
+        } else {
+            write!(f, " (code {}: {})", self.code, self.reason)
+        }
+    }
+}
+
+impl std::error::Error for ConnectionClosedByPeer {}
+
 /// Callback for responding to incoming RPC requests
 pub type WebsocketRespond =
     Box<dyn FnOnce(SerializedBytes) -> BoxFuture<'static, Result<()>> + 'static + Send + Sync>;
@@ -105,21 +167,93 @@ pub async fn websocket_connect(
     url: Url2,
     config: Arc<WebsocketConfig>,
 ) -> Result<(WebsocketSender, WebsocketReceiver)> {
-    let addr = url_to_addr(&url, config.scheme).await?;
-    let socket = tokio::net::TcpStream::connect(addr).await?;
+    let socket = match &config.proxy {
+        Some(proxy) if proxy.applies_to(url.host_str().unwrap_or_default()) => {
+            // the proxy resolves the host so it isn't looked up here
+            let (host, port) = url_host_port(&url, config.scheme)?;
+            proxy::connect(proxy, host, port).await?
+        }
+        _ => tokio::net::TcpStream::connect(url_to_addr(&url, config.scheme).await?).await?,
+    };
     socket.set_keepalive(Some(std::time::Duration::from_secs(
         config.tcp_keepalive_s as u64,
     )))?;
+    let socket = match &config.tls {
+        Some(tls) => {
+            let host = url.host_str().unwrap_or_default();
+            tls::Socket::Tls(Box::new(tls::connect(tls, host, socket).await?))
+        }
+        None => tls::Socket::Tcp(socket),
+    };
+    let (socket, _) = tokio_tungstenite::client_async_with_config(
+        client_request(url.as_str(), &config)?,
+        task_keepalive::ReadTracked::new(socket),
+        Some(config.to_tungstenite()),
+    )
+    .await
+    .map_err(|e| handshake_error(e, &config))?;
+    build_websocket_pair(config, socket)
+}
+
+/// Establish a new outgoing websocket connection over the unix domain
+/// socket at this path. Tls is not used on unix sockets
+/// so [WebsocketConfig::tls](struct.WebsocketConfig.html) is ignored.
+#[cfg(unix)]
+pub async fn websocket_connect_unix(
+    path: &std::path::Path,
+    config: Arc<WebsocketConfig>,
+) -> Result<(WebsocketSender, WebsocketReceiver)> {
+    let socket = tokio::net::UnixStream::connect(path).await?;
+    let socket = task_keepalive::ReadTracked::new(tls::Socket::Unix(socket, path.to_path_buf()));
+    // the handshake needs a ws url but the host is never looked up
     let (socket, _) = tokio_tungstenite::client_async_with_config(
-        url.as_str(),
+        client_request("ws://localhost/", &config)?,
         socket,
         Some(config.to_tungstenite()),
     )
     .await
-    .map_err(|e| Error::new(ErrorKind::Other, e))?;
+    .map_err(|e| handshake_error(e, &config))?;
     build_websocket_pair(config, socket)
 }
 
+/// internal build the handshake request with the configured origin
+fn client_request(
+    url: &str,
+    config: &WebsocketConfig,
+) -> Result<tungstenite::handshake::client::Request> {
+    use tungstenite::client::IntoClientRequest;
+    let mut request = url
+        .into_client_request()
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    if let Some(origin) = &config.origin {
+        let origin = tungstenite::http::HeaderValue::from_str(origin).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid origin {:?}: {}", origin, e),
+            )
+        })?;
+        request
+            .headers_mut()
+            .insert(tungstenite::http::header::ORIGIN, origin);
+    }
+    Ok(request)
+}
+
+/// internal a listener that doesn't allow our origin answers the
+/// handshake with 403, which is reported as PermissionDenied
+fn handshake_error(e: tungstenite::Error, config: &WebsocketConfig) -> Error {
+    match e {
+        tungstenite::Error::Http(status) if status == 403 => {
+            let msg = match &config.origin {
+                Some(origin) => format!("Origin {:?} not allowed by the remote (HTTP 403)", origin),
+                None => "Connections without an origin not allowed by the remote (HTTP 403)".into(),
+            };
+            Error::new(ErrorKind::PermissionDenied, msg)
+        }
+        e => Error::new(ErrorKind::Other, e),
+    }
+}
+
 /// internal set up the tokio tasks that keep a websocket running
 /// and produce the public (WebsocketSender, WebsocketReceiver) pair.
 pub(crate) fn build_websocket_pair(
@@ -128,10 +262,12 @@ pub(crate) fn build_websocket_pair(
 ) -> Result<(WebsocketSender, WebsocketReceiver)> {
     let remote_addr = url2!(
         "{}#{}",
-        addr_to_url(socket.get_ref().peer_addr()?, config.scheme),
+        socket.get_ref().get_ref().remote_url(config.scheme)?,
         nanoid::nanoid!(),
     );
 
+    let read_clock = socket.get_ref().clock();
+
     // split the sink and stream so we can handle them simultaneously
     use futures::stream::StreamExt;
     let (raw_sink, raw_stream) = socket.split();
@@ -141,25 +277,42 @@ pub(crate) fn build_websocket_pair(
 
     // the socket sink task handles sending outgoing data
     let send_sink = task_socket_sink::build(config.clone(), remote_addr.clone(), raw_sink);
+    let accept_compressed = config.accept_compressed_responses;
 
     // the dispatch task gathers:
     //  - register responses from the WebsocketSender
     //  - incoming data from the socket stream task
-    let send_dispatch =
-        task_dispatch_incoming::build(config, remote_addr.clone(), send_pub, send_sink.clone());
+    let send_dispatch = task_dispatch_incoming::build(
+        config.clone(),
+        remote_addr.clone(),
+        send_pub,
+        send_sink.clone(),
+    );
+
+    // the keepalive task pings the remote when the socket is idle
+    // and closes the socket through the dispatcher if it stays silent
+    let keepalive = task_keepalive::build(
+        config.clone(),
+        remote_addr.clone(),
+        read_clock,
+        send_sink.clone(),
+        send_dispatch.clone(),
+    );
 
     // the socket stream task forwards incoming data to the dispatcher
     // it also responds to pings by directly sending to the sink
     task_socket_stream::build(
+        config,
         remote_addr.clone(),
         send_sink.clone(),
         send_dispatch.clone(),
         raw_stream,
+        keepalive,
     );
 
     // return our send / recv pair
     Ok((
-        WebsocketSender::priv_new(send_sink, send_dispatch),
+        WebsocketSender::priv_new(send_sink, send_dispatch, accept_compressed),
         WebsocketReceiver::priv_new(remote_addr, recv_pub),
     ))
 }
@@ -13,6 +13,7 @@ use tracing_futures::Instrument;
 pub struct WebsocketSender {
     send_sink: ToSocketSinkSender,
     send_dispatch: ToDispatchIncomingSender,
+    accept_compressed: bool,
 }
 
 impl WebsocketSender {
@@ -20,10 +21,12 @@ impl WebsocketSender {
     pub(crate) fn priv_new(
         send_sink: ToSocketSinkSender,
         send_dispatch: ToDispatchIncomingSender,
+        accept_compressed: bool,
     ) -> Self {
         Self {
             send_sink,
             send_dispatch,
+            accept_compressed,
         }
     }
 
@@ -92,6 +95,26 @@ impl WebsocketSender {
     /// Make a rpc request of the remote end of this websocket
     #[must_use]
     pub fn request<SB1, SB2>(&mut self, msg: SB1) -> BoxFuture<'static, Result<SB2>>
+    where
+        SB1: 'static + std::convert::TryInto<SerializedBytes> + Send + std::fmt::Debug,
+        <SB1 as std::convert::TryInto<SerializedBytes>>::Error:
+            'static + std::error::Error + Send + Sync,
+        SB2: 'static + std::convert::TryFrom<SerializedBytes> + Send,
+        <SB2 as std::convert::TryFrom<SerializedBytes>>::Error:
+            'static + std::error::Error + Send + Sync,
+    {
+        self.request_with_id(nanoid::nanoid!(), msg)
+    }
+
+    /// Make a rpc request with the id its response is matched by,
+    /// so the caller can log it next to its own request.
+    /// The id must be unique among the pending requests on this websocket.
+    #[must_use]
+    pub fn request_with_id<SB1, SB2>(
+        &mut self,
+        id: String,
+        msg: SB1,
+    ) -> BoxFuture<'static, Result<SB2>>
     where
         SB1: 'static + std::convert::TryInto<SerializedBytes> + Send + std::fmt::Debug,
         <SB1 as std::convert::TryInto<SerializedBytes>>::Error:
@@ -102,6 +125,7 @@ impl WebsocketSender {
     {
         let mut send_sink = self.send_sink.clone();
         let mut send_dispatch = self.send_dispatch.clone();
+        let accept_compressed = self.accept_compressed;
         async move {
             tracing::trace!(request_msg = ?msg);
             let bytes: SerializedBytes = msg
@@ -109,8 +133,6 @@ impl WebsocketSender {
                 .map_err(|e| Error::new(ErrorKind::Other, e))?;
             let bytes: Vec<u8> = UnsafeBytes::from(bytes).into();
 
-            let id = nanoid::nanoid!();
-
             let (send_response, recv_response) = tokio::sync::oneshot::channel();
 
             send_dispatch
@@ -119,9 +141,13 @@ impl WebsocketSender {
                     respond: send_response,
                 })
                 .await
-                .map_err(|e| Error::new(ErrorKind::Other, e))?;
+                .map_err(|_| Error::new(ErrorKind::NotConnected, "Websocket is closed"))?;
 
-            let msg = WireMessage::Request { id, data: bytes };
+            let msg = WireMessage::Request {
+                id,
+                data: bytes,
+                accept_compressed,
+            };
             let bytes: SerializedBytes = msg.try_into()?;
             let bytes: Vec<u8> = UnsafeBytes::from(bytes).into();
 
@@ -132,15 +158,18 @@ impl WebsocketSender {
             send_sink
                 .send((msg, send_complete))
                 .await
-                .map_err(|e| Error::new(ErrorKind::Other, e))?;
+                .map_err(|_| Error::new(ErrorKind::NotConnected, "Websocket is closed"))?;
 
             recv_complete
                 .await
-                .map_err(|e| Error::new(ErrorKind::Other, e))?;
-
-            let bytes = recv_response
-                .await
-                .map_err(|e| Error::new(ErrorKind::Other, e))??;
+                .map_err(|_| Error::new(ErrorKind::NotConnected, "Websocket is closed"))?;
+
+            let bytes = recv_response.await.map_err(|_| {
+                Error::new(
+                    ErrorKind::ConnectionAborted,
+                    "Websocket closed before a response was received",
+                )
+            })??;
             let bytes: SerializedBytes = UnsafeBytes::from(bytes).into();
             Ok(SB2::try_from(bytes).map_err(|e| Error::new(ErrorKind::Other, e))?)
         }
@@ -0,0 +1,31 @@
+//! Base64 text for the base64_encode and base64_decode host functions
+//! so zomes don't need to bundle a base64 library in their wasm.
+
+/// Bytes encoded as base64 with the standard alphabet and padding.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Base64Encoded(String);
+
+impl From<String> for Base64Encoded {
+    fn from(s: String) -> Self {
+        Self(s)
+    }
+}
+
+impl From<&str> for Base64Encoded {
+    fn from(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
+impl From<Base64Encoded> for String {
+    fn from(encoded: Base64Encoded) -> Self {
+        encoded.0
+    }
+}
+
+impl AsRef<str> for Base64Encoded {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
@@ -11,6 +11,7 @@
 
 #[allow(missing_docs)]
 pub mod agent_info;
+pub mod base64_encoded;
 pub mod bytes;
 #[allow(missing_docs)]
 pub mod call;
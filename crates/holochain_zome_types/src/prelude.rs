@@ -1,6 +1,7 @@
 //! Common types
 
 pub use crate::agent_info::*;
+pub use crate::base64_encoded::*;
 pub use crate::bytes::*;
 pub use crate::call::*;
 pub use crate::call_remote::*;
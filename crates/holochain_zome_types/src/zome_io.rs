@@ -83,6 +83,12 @@ wasm_io_types! {
 
     fn agent_info (()) -> zt::agent_info::AgentInfo;
 
+    // Encode bytes as standard base64 on the host.
+    fn base64_encode (zt::bytes::Bytes) -> zt::base64_encoded::Base64Encoded;
+
+    // Decode standard base64 on the host. None if it isn't valid base64.
+    fn base64_decode (zt::base64_encoded::Base64Encoded) -> Option<zt::bytes::Bytes>;
+
     // Header hash of the DeleteLink element.
     fn call_remote (zt::call_remote::CallRemote) -> zt::ZomeCallResponse;
 
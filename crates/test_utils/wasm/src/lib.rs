@@ -7,6 +7,7 @@ const WASM_WORKSPACE_TARGET: &str = "wasm_workspace/target";
 pub enum TestWasm {
     AgentInfo,
     Anchor,
+    Base64,
     Bench,
     Capability,
     Create,
@@ -51,6 +52,7 @@ impl From<TestWasm> for ZomeName {
         ZomeName::from(match test_wasm {
             TestWasm::AgentInfo => "agent_info",
             TestWasm::Anchor => "anchor",
+            TestWasm::Base64 => "base64",
             TestWasm::Bench => "bench",
             TestWasm::Capability => "capability",
             TestWasm::Create => "create_entry",
@@ -99,6 +101,7 @@ impl From<TestWasm> for DnaWasm {
                 get_code("wasm32-unknown-unknown/release/test_wasm_agent_info.wasm")
             }
             TestWasm::Anchor => get_code("wasm32-unknown-unknown/release/test_wasm_anchor.wasm"),
+            TestWasm::Base64 => get_code("wasm32-unknown-unknown/release/test_wasm_base64.wasm"),
             TestWasm::Bench => get_code("wasm32-unknown-unknown/release/test_wasm_bench.wasm"),
             TestWasm::Capability => {
                 get_code("wasm32-unknown-unknown/release/test_wasm_capability.wasm")
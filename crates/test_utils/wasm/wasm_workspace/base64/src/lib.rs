@@ -0,0 +1,11 @@
+use hdk3::prelude::*;
+
+#[hdk_extern]
+fn base64_encode(bytes: Bytes) -> ExternResult<Base64Encoded> {
+    hdk3::prelude::base64_encode(bytes)
+}
+
+#[hdk_extern]
+fn base64_decode(encoded: Base64Encoded) -> ExternResult<Option<Bytes>> {
+    hdk3::prelude::base64_decode(encoded)
+}